@@ -0,0 +1,178 @@
+/*!
+ * Conversion Benchmarks
+ *
+ * Each request/response conversion direction, response cache keys, and the
+ * chat handler path (parse, convert, mock provider, convert back, filter,
+ * serialize) so regressions in the converters show up before release.
+ */
+
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::{PostProcessConfig, ReasoningFilterRule, ReasoningMode};
+use aiclient2api_rust::convert::{convert_data, ChatRequest, ChatResponse, ChatStream, ConversionType};
+use aiclient2api_rust::postprocess::PostProcessor;
+use aiclient2api_rust::{http_cache, json, reasoning};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+const PROTOCOLS: [ModelProtocol; 3] = [ModelProtocol::OpenAI, ModelProtocol::Claude, ModelProtocol::Gemini];
+
+/// A multi-turn OpenAI conversation with a tool round trip and an image
+fn openai_request() -> Value {
+    let mut messages = vec![json!({"role": "system", "content": "You are a helpful assistant."})];
+    for turn in 0..8 {
+        messages.push(json!({"role": "user", "content": format!("Question {}: what is the weather like?", turn)}));
+        messages.push(json!({"role": "assistant", "content": format!("Answer {}: it is sunny. ", turn).repeat(20)}));
+    }
+    messages.push(json!({"role": "user", "content": [
+        {"type": "text", "text": "And in this picture?"},
+        {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", "iVBORw0KGgo".repeat(4096))}}
+    ]}));
+    messages.push(json!({"role": "assistant", "content": null, "tool_calls": [
+        {"id": "call_1", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}}
+    ]}));
+    messages.push(json!({"role": "tool", "tool_call_id": "call_1", "content": "{\"temp\":21}"}));
+    json!({
+        "model": "gpt-4o",
+        "messages": messages,
+        "max_tokens": 1024,
+        "temperature": 0.7,
+        "tools": [{"type": "function", "function": {
+            "name": "weather",
+            "description": "Current weather",
+            "parameters": {"type": "object", "properties": {"city": {"type": "string"}}}
+        }}]
+    })
+}
+
+fn requests() -> Vec<(ModelProtocol, Value)> {
+    let openai = openai_request();
+    PROTOCOLS
+        .iter()
+        .map(|&protocol| {
+            let body = convert_data(openai.clone(), ConversionType::Request, ModelProtocol::OpenAI, protocol, None).unwrap();
+            (protocol, body)
+        })
+        .collect()
+}
+
+fn claude_response() -> Value {
+    json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-3-opus",
+        "content": [
+            {"type": "text", "text": "<think>The user wants the weather.</think>It is 21 degrees in Paris.  \n".repeat(40)},
+            {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {"city": "Paris"}}
+        ],
+        "stop_reason": "tool_use",
+        "usage": {"input_tokens": 1200, "output_tokens": 800}
+    })
+}
+
+fn responses() -> Vec<(ModelProtocol, Value)> {
+    let claude = claude_response();
+    PROTOCOLS
+        .iter()
+        .map(|&protocol| {
+            let body = match protocol {
+                ModelProtocol::Claude => claude.clone(),
+                ModelProtocol::OpenAI => convert_data(claude.clone(), ConversionType::Response, ModelProtocol::Claude, protocol, Some("gpt-4o")).unwrap(),
+                ModelProtocol::Gemini => json!({
+                    "candidates": [{"content": {"role": "model", "parts": [{"text": claude["content"][0]["text"]}]}, "finishReason": "STOP"}],
+                    "usageMetadata": {"promptTokenCount": 1200, "candidatesTokenCount": 800, "totalTokenCount": 2000}
+                }),
+            };
+            (protocol, body)
+        })
+        .collect()
+}
+
+fn bench_conversions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert");
+    for (kind, samples) in [(ConversionType::Request, requests()), (ConversionType::Response, responses())] {
+        for (from, body) in &samples {
+            for &to in PROTOCOLS.iter().filter(|to| *to != from) {
+                // Not every response direction exists (e.g. into Gemini)
+                if convert_data(body.clone(), kind, *from, to, Some("model")).is_err() {
+                    continue;
+                }
+                let name = format!("{:?}/{}_to_{}", kind, from.as_str(), to.as_str());
+                group.bench_function(name, |b| {
+                    b.iter_batched(
+                        || body.clone(),
+                        |body| convert_data(body, kind, *from, to, Some("model")).unwrap(),
+                        BatchSize::SmallInput,
+                    )
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+fn bench_cache_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cache_key");
+    for (name, body) in [("small", json!({"object": "list", "data": []})), ("large", claude_response())] {
+        let bytes = serde_json::to_vec(&body).unwrap();
+        group.bench_function(name, |b| b.iter(|| http_cache::etag_for(black_box(&bytes))));
+    }
+    group.finish();
+}
+
+/// The OpenAI chat handler against a mock Claude backend, minus the network
+fn handler_path(body: Vec<u8>, rule: &ReasoningFilterRule, processor: &PostProcessor, upstream: &Value) -> Vec<u8> {
+    let request = ChatRequest::new(ModelProtocol::OpenAI, json::from_vec(body).unwrap());
+    let upstream_body = request.into_protocol(ModelProtocol::Claude, Some("claude-3-opus")).unwrap();
+    black_box(serde_json::to_vec(&upstream_body).unwrap());
+
+    let response = ChatResponse::new(ModelProtocol::Claude, upstream.clone());
+    let mut response = response.into_protocol(ModelProtocol::OpenAI, Some("claude-3-opus")).unwrap();
+    reasoning::filter_response(rule, &mut response);
+    processor.process_response(&mut response);
+    serde_json::to_vec(&response).unwrap()
+}
+
+fn bench_handler(c: &mut Criterion) {
+    let body = serde_json::to_vec(&openai_request()).unwrap();
+    let upstream = claude_response();
+    let rule = ReasoningFilterRule {
+        model: "*".to_string(),
+        mode: ReasoningMode::Field,
+        tag: "think".to_string(),
+    };
+    let processor = PostProcessor::new(&PostProcessConfig {
+        trim_trailing_whitespace: true,
+        normalize_code_fences: true,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let mut group = c.benchmark_group("handler");
+    group.bench_function("chat_completion", |b| {
+        b.iter_batched(|| body.clone(), |body| handler_path(body, &rule, &processor, &upstream), BatchSize::SmallInput)
+    });
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let events: Vec<Value> = std::iter::once(json!({"type": "message_start", "message": {"id": "msg_1"}}))
+        .chain(std::iter::once(json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}})))
+        .chain((0..200).map(|i| json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": format!("token{} ", i)}})))
+        .chain([json!({"type": "content_block_stop", "index": 0}), json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}}), json!({"type": "message_stop"})])
+        .collect();
+    group.bench_function("chat_completion_stream", |b| {
+        b.iter_batched(
+            || events.clone(),
+            |events| {
+                let stream = ChatStream::new(ModelProtocol::Claude, Box::pin(futures::stream::iter(events.into_iter().map(Ok))));
+                let chunks = stream.into_protocol(ModelProtocol::OpenAI, Some("claude-3-opus")).unwrap();
+                runtime.block_on(chunks.map(|chunk| serde_json::to_vec(&chunk.unwrap()).unwrap()).collect::<Vec<_>>())
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_conversions, bench_cache_keys, bench_handler);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+/*!
+ * JSON Parsing Benchmarks
+ *
+ * Compares plain serde_json with the hot-path parser on a multimodal chat
+ * request. Run with and without `--features simd-json` to see the difference.
+ */
+
+use aiclient2api_rust::json;
+use base64::Engine;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use serde_json::{json as value, Value};
+
+/// Chat request carrying `images` base64 images of `image_bytes` each
+fn multimodal_request(images: usize, image_bytes: usize) -> Vec<u8> {
+    let image = base64::engine::general_purpose::STANDARD.encode(vec![0x5a; image_bytes]);
+    let content: Vec<Value> = (0..images)
+        .map(|_| value!({"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", image)}}))
+        .chain(std::iter::once(value!({"type": "text", "text": "Describe these images."})))
+        .collect();
+    serde_json::to_vec(&value!({
+        "model": "gpt-4o",
+        "messages": [{"role": "user", "content": content}],
+        "max_tokens": 1024
+    }))
+    .unwrap()
+}
+
+fn bench_parsing(c: &mut Criterion) {
+    let body = multimodal_request(4, 512 * 1024);
+    let mut group = c.benchmark_group("multimodal_request");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+
+    group.bench_function("serde_json", |b| {
+        b.iter(|| serde_json::from_slice::<Value>(black_box(&body)).unwrap())
+    });
+    group.bench_function("hot_path", |b| {
+        b.iter_batched(|| body.clone(), |body| json::from_vec(body).unwrap(), BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parsing);
+criterion_main!(benches);
@@ -5,33 +5,90 @@
  */
 
 use crate::common::*;
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::passthrough::ForwardRequest;
+use crate::request_context::RequestContext;
 use anyhow::Result;
 use async_trait::async_trait;
+use bytes::Bytes;
 use futures::Stream;
 use std::pin::Pin;
 
+/// Upstream response body, chunk by chunk
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
 /// Trait defining the interface for all AI service adapters
 #[async_trait]
 pub trait ApiServiceAdapter: Send + Sync {
-    /// Generate content (non-streaming)
+    /// Wire format this provider speaks upstream
+    fn protocol(&self) -> ModelProtocol;
+
+    /// Generate content (non-streaming); the response is in the provider's protocol
     async fn generate_content(
         &self,
         model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<serde_json::Value>;
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatResponse>;
 
-    /// Generate content (streaming)
+    /// Generate content (streaming); chunks are in the provider's protocol
     async fn generate_content_stream(
         &self,
         model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>>;
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatStream>;
+
+    /// Whether `generate_content_stream_raw` is available
+    fn supports_stream_passthrough(&self) -> bool {
+        false
+    }
+
+    /// Generate content (streaming), returning the upstream SSE bytes unparsed so
+    /// they can be relayed as is to a client speaking the same protocol
+    async fn generate_content_stream_raw(
+        &self,
+        _model: &str,
+        _request: ChatRequest,
+        _ctx: &RequestContext,
+    ) -> Result<ByteStream> {
+        anyhow::bail!("Stream passthrough is not supported by this provider")
+    }
+
+    /// Relay a raw API call (e.g. the Files API) with this provider's credentials
+    async fn forward(&self, _request: ForwardRequest, _ctx: &RequestContext) -> Result<reqwest::Response> {
+        anyhow::bail!("API passthrough is not supported by this provider")
+    }
 
     /// List available models
     async fn list_models(&self) -> Result<ModelListResponse>;
 
+    /// Details of one model, `None` when the provider does not offer it; by default its
+    /// entry in the model list
+    async fn get_model(&self, model: &str) -> Result<Option<ModelInfo>> {
+        Ok(self.list_models().await?.entries().find(|m| m.model_id() == Some(model)).cloned())
+    }
+
     /// Refresh authentication token (if applicable)
     async fn refresh_token(&self) -> Result<()>;
+
+    /// Upstream endpoint, used to pre-warm a connection at startup
+    fn upstream_url(&self) -> Option<String> {
+        None
+    }
+
+    /// Drop any internally cached data, returning the number of entries removed
+    async fn clear_cache(&self) -> usize {
+        0
+    }
+}
+
+/// Regional endpoints of a provider, health-checked in the background
+#[cfg(any(feature = "openai", feature = "claude"))]
+fn regions(base_urls: Vec<String>, config: &crate::config::Config, client: &reqwest::Client) -> std::sync::Arc<crate::regions::Regions> {
+    let regions = std::sync::Arc::new(crate::regions::Regions::new(base_urls, &config.region_failover));
+    regions.spawn_health_checks(client.clone());
+    regions
 }
 
 /// Factory function to create appropriate adapter based on provider type
@@ -39,7 +96,47 @@ pub async fn create_adapter(
     provider: ModelProvider,
     config: &crate::config::Config,
 ) -> Result<Box<dyn ApiServiceAdapter>> {
+    let aggregate = config.stream_aggregation.providers.iter().any(|name| name == provider.as_str());
+    let continuation = config
+        .auto_continuation
+        .clone()
+        .filter(|c| c.providers.is_empty() || c.providers.iter().any(|name| name == provider.as_str()));
+    let empty_retry = config
+        .empty_response_retry
+        .clone()
+        .filter(|r| r.providers.is_empty() || r.providers.iter().any(|name| name == provider.as_str()));
+    let prefill = crate::stream_recovery::supports_prefill(&provider);
+
+    let mut adapter = create_provider_adapter(provider, config).await?;
+    if aggregate {
+        adapter = Box::new(crate::stream_aggregation::StreamAggregatingAdapter::new(adapter));
+    }
+    if let Some(retry) = empty_retry {
+        let fallback = match retry.fallback_provider.as_deref().map(|name| (name, ModelProvider::from_str(name))) {
+            Some((name, Some(fallback))) => match create_provider_adapter(fallback, config).await {
+                Ok(fallback) => Some((name.to_string(), fallback)),
+                Err(e) => {
+                    tracing::warn!("Empty response fallback provider {} is unavailable: {}", name, e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        adapter = Box::new(crate::empty_response::EmptyResponseRetryAdapter::new(adapter, retry.retries, fallback));
+    }
+    if let Some(continuation) = continuation {
+        adapter = Box::new(crate::continuation::ContinuingAdapter::new(adapter, continuation, prefill));
+    }
+    Ok(adapter)
+}
+
+async fn create_provider_adapter(
+    provider: ModelProvider,
+    config: &crate::config::Config,
+) -> Result<Box<dyn ApiServiceAdapter>> {
+    let client = crate::http_client::shared(&config.http_client)?;
     match provider {
+        #[cfg(feature = "gemini")]
         ModelProvider::GeminiCliOAuth => {
             let service = crate::providers::gemini::GeminiApiService::new(
                 config.gemini_oauth_creds_base64.clone(),
@@ -47,48 +144,67 @@ pub async fn create_adapter(
                 config.project_id.clone(),
                 config.request_max_retries,
                 config.request_base_delay,
+                client,
             ).await?;
             Ok(Box::new(service))
         }
+        #[cfg(feature = "openai")]
         ModelProvider::OpenAICustom => {
             let api_key = config.openai_api_key.clone()
                 .ok_or_else(|| anyhow::anyhow!("OpenAI API key is required"))?;
+            let base_urls = crate::regions::base_urls(config.openai_base_url.as_ref(), &config.openai_base_urls, "https://api.openai.com/v1");
             let service = crate::providers::openai::OpenAIApiService::new(
                 api_key,
-                config.openai_base_url.clone(),
+                regions(base_urls, config, &client),
+                config.openai_organization.clone(),
+                config.openai_project.clone(),
                 config.request_max_retries,
                 config.request_base_delay,
+                client,
             )?;
             Ok(Box::new(service))
         }
+        #[cfg(feature = "claude")]
         ModelProvider::ClaudeCustom => {
             let api_key = config.claude_api_key.clone()
                 .ok_or_else(|| anyhow::anyhow!("Claude API key is required"))?;
+            let base_urls = crate::regions::base_urls(config.claude_base_url.as_ref(), &config.claude_base_urls, "https://api.anthropic.com");
             let service = crate::providers::claude::ClaudeApiService::new(
                 api_key,
-                config.claude_base_url.clone(),
+                regions(base_urls, config, &client),
+                config.claude_beta_flags.clone(),
                 config.request_max_retries,
                 config.request_base_delay,
+                client,
             )?;
             Ok(Box::new(service))
         }
+        #[cfg(feature = "kiro")]
         ModelProvider::ClaudeKiroOAuth => {
             let service = crate::providers::kiro::KiroApiService::new(
                 config.kiro_oauth_creds_base64.clone(),
                 config.kiro_oauth_creds_file_path.clone(),
                 config.request_max_retries,
                 config.request_base_delay,
+                client,
             ).await?;
             Ok(Box::new(service))
         }
+        #[cfg(feature = "qwen")]
         ModelProvider::OpenAIQwenOAuth => {
             let service = crate::providers::qwen::QwenApiService::new(
                 config.qwen_oauth_creds_file_path.clone(),
                 config.request_max_retries,
                 config.request_base_delay,
+                client,
             ).await?;
             Ok(Box::new(service))
         }
+        #[allow(unreachable_patterns)]
+        provider => {
+            let _ = client;
+            anyhow::bail!("Provider {} is not available in this build (enable its Cargo feature)", provider.as_str())
+        }
     }
 }
 
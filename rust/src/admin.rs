@@ -0,0 +1,730 @@
+/*!
+ * Admin API
+ *
+ * Operational endpoints under `/admin`. Every mutation is written to the audit log.
+ * Reads need the viewer role and mutations the operator role; the static admin
+ * key acts as an operator. Without `admin_api_key` or OIDC the endpoints do not
+ * exist: the client key is never an admin credential.
+ */
+
+use crate::common::is_authorized;
+use crate::config::{ModelAlias, ModelRoute, RequestLimits};
+use crate::provider_registry::Routing;
+use crate::oidc::{session_id_from_cookie, AdminRole, AdminSession, OidcClient, SESSION_COOKIE};
+use crate::server::{AppError, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Build the admin router
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/admin/login", get(login_handler))
+        .route("/admin/callback", get(callback_handler))
+        .route("/admin/session", get(session_handler))
+        .route("/admin/logout", post(logout_handler))
+        .route("/admin/providers", get(list_providers_handler))
+        .route("/admin/providers/health", get(key_health_handler))
+        .route(
+            "/admin/providers/:provider_type/:uuid/disable",
+            post(disable_provider_handler),
+        )
+        .route(
+            "/admin/providers/:provider_type/:uuid/enable",
+            post(enable_provider_handler),
+        )
+        .route("/admin/routing", get(get_routing_handler).put(update_routing_handler))
+        .route("/admin/reload", post(reload_handler))
+        .route("/admin/cache/clear", post(clear_cache_handler))
+        .route("/admin/models/refresh", post(refresh_models_handler))
+        .route("/admin/keys", get(list_keys_handler).post(create_key_handler))
+        .route("/admin/keys/:id/rotate", post(rotate_key_handler))
+        .route("/admin/keys/:id", delete(revoke_key_handler))
+        .route("/admin/keys/:id/privacy", put(key_privacy_handler))
+        .route("/admin/keys/:id/models", put(key_models_handler))
+        .route("/admin/keys/:id/limits", put(key_limits_handler))
+        .route("/admin/privacy/purge", post(purge_handler))
+        .route("/admin/audit", get(audit_query_handler))
+        .route("/admin/audit/verify", get(audit_verify_handler))
+        .route("/admin/requests/:id", get(transcript_handler))
+}
+
+/// Audit actor of requests authorized by `admin_api_key`
+const ADMIN_KEY_ACTOR: &str = "admin-key";
+
+/// Check the admin key or OIDC session and return the acting identity
+pub(crate) async fn authorize_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    required: AdminRole,
+) -> Result<String, AppError> {
+    if let Some(session) = admin_session(state, headers).await {
+        if session.role < required {
+            return Err(AppError::Forbidden(format!(
+                "The {:?} role cannot perform this action.",
+                session.role
+            )));
+        }
+        return Ok(session.subject);
+    }
+
+    // The client key never grants admin access: without an admin key or OIDC there is no admin API
+    let Some(ref required_key) = state.config.admin_api_key else {
+        return Err(match state.oidc {
+            Some(_) => AppError::Unauthorized,
+            None => AppError::NotFound("The admin API is not configured".to_string()),
+        });
+    };
+
+    let auth_header = headers.get("authorization").and_then(|v| v.to_str().ok());
+    let api_key_header = headers.get("x-api-key").and_then(|v| v.to_str().ok());
+
+    if !is_authorized(auth_header, api_key_header, None, None, required_key) {
+        return Err(AppError::Unauthorized);
+    }
+
+    // The static key cannot identify a person; audit entries name the credential instead
+    Ok(ADMIN_KEY_ACTOR.to_string())
+}
+
+async fn admin_session(state: &AppState, headers: &HeaderMap) -> Option<AdminSession> {
+    let oidc = state.oidc.as_ref()?;
+    let cookie = headers.get(header::COOKIE)?.to_str().ok()?;
+    oidc.session(session_id_from_cookie(cookie)?).await
+}
+
+fn oidc_client(state: &AppState) -> Result<&OidcClient, AppError> {
+    state
+        .oidc
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("OIDC login is not configured".to_string()))
+}
+
+async fn login_handler(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let url = oidc_client(&state)?.begin_login().await?;
+    Ok(Redirect::to(&url).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+async fn callback_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Response, AppError> {
+    let oidc = oidc_client(&state)?;
+    if let Some(error) = query.error {
+        warn!("OIDC login failed: {}", error);
+        return Err(AppError::Unauthorized);
+    }
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return Err(AppError::BadRequest("Missing code or state".to_string()));
+    };
+
+    let (session_id, session) = oidc.complete_login(&code, &login_state).await.map_err(|e| {
+        warn!("OIDC login rejected: {:#}", e);
+        AppError::Unauthorized
+    })?;
+
+    state
+        .audit
+        .record(&session.subject, "admin.login", &session.subject, json!(null), json!({ "role": session.role }))
+        .await?;
+
+    let cookie = format!(
+        "{}={}; Path=/admin; HttpOnly; Secure; SameSite=Lax; Max-Age={}",
+        SESSION_COOKIE,
+        session_id,
+        oidc.session_ttl_secs()
+    );
+    Ok(([(header::SET_COOKIE, cookie)], Json(json!({ "session": session }))).into_response())
+}
+
+async fn session_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let session = admin_session(&state, &headers).await.ok_or(AppError::Unauthorized)?;
+    Ok(Json(json!({ "session": session })).into_response())
+}
+
+async fn logout_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let oidc = oidc_client(&state)?;
+    let session_id = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(session_id_from_cookie)
+        .ok_or(AppError::Unauthorized)?;
+
+    if let Some(session) = oidc.logout(session_id).await {
+        state
+            .audit
+            .record(&session.subject, "admin.logout", &session.subject, json!(null), json!(null))
+            .await?;
+    }
+
+    let cookie = format!("{}=; Path=/admin; HttpOnly; Secure; SameSite=Lax; Max-Age=0", SESSION_COOKIE);
+    Ok(([(header::SET_COOKIE, cookie)], StatusCode::NO_CONTENT).into_response())
+}
+
+async fn list_providers_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_admin(&state, &headers, AdminRole::Viewer).await?;
+    Ok(Json(state.pool_manager.snapshot().await).into_response())
+}
+
+/// Health score and quarantine state of every pool key
+async fn key_health_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_admin(&state, &headers, AdminRole::Viewer).await?;
+    Ok(Json(state.pool_manager.health_snapshot().await).into_response())
+}
+
+async fn disable_provider_handler(
+    State(state): State<Arc<AppState>>,
+    Path((provider_type, uuid)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    set_provider_health(&state, &headers, &provider_type, &uuid, false).await
+}
+
+async fn enable_provider_handler(
+    State(state): State<Arc<AppState>>,
+    Path((provider_type, uuid)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    set_provider_health(&state, &headers, &provider_type, &uuid, true).await
+}
+
+async fn set_provider_health(
+    state: &AppState,
+    headers: &HeaderMap,
+    provider_type: &str,
+    uuid: &str,
+    healthy: bool,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(state, headers, AdminRole::Operator).await?;
+
+    let before = state
+        .pool_manager
+        .get_provider(provider_type, uuid)
+        .await
+        .ok_or_else(|| AppError::NotFound(format!("Provider {}/{} not found", provider_type, uuid)))?;
+
+    if healthy {
+        state.pool_manager.mark_provider_healthy(provider_type, uuid).await;
+    } else {
+        state.pool_manager.mark_provider_unhealthy(provider_type, uuid).await;
+    }
+
+    crate::cluster::publish_provider_health(state.shared_state.as_ref(), provider_type, uuid, healthy).await?;
+
+    let after = state.pool_manager.get_provider(provider_type, uuid).await;
+    let action = if healthy { "provider.enable" } else { "provider.disable" };
+
+    state
+        .audit
+        .record(
+            &actor,
+            action,
+            &format!("{}/{}", provider_type, uuid),
+            json!({ "is_healthy": before.is_healthy }),
+            json!({ "is_healthy": after.map(|p| p.is_healthy) }),
+        )
+        .await?;
+
+    Ok(Json(json!({ "provider_type": provider_type, "uuid": uuid, "is_healthy": healthy })).into_response())
+}
+
+async fn clear_cache_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let mut cleared = 0;
+    for adapter in state.providers.snapshot().await.adapters.values() {
+        cleared += adapter.clear_cache().await;
+    }
+
+    state
+        .audit
+        .record(
+            &actor,
+            "cache.clear",
+            &state.config.model_provider,
+            json!({ "entries": cleared }),
+            json!({ "entries": 0 }),
+        )
+        .await?;
+
+    Ok(Json(json!({ "cleared": cleared })).into_response())
+}
+
+/// Drop the cached model lists and model details, so the next requests fetch them anew
+async fn refresh_models_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+    let cache = state
+        .model_list_cache
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("The model list cache is not enabled".to_string()))?;
+
+    let generation = cache.refresh().await?;
+
+    state
+        .audit
+        .record(&actor, "models.refresh", "model_list_cache", json!(null), json!({ "generation": generation }))
+        .await?;
+
+    Ok(Json(json!({ "refreshed": true, "generation": generation })).into_response())
+}
+
+/// Parts of the routing table to replace; omitted parts are kept
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub(crate) struct UpdateRoutingRequest {
+    default_provider: Option<String>,
+    routes: Option<Vec<ModelRoute>>,
+    aliases: Option<HashMap<String, ModelAlias>>,
+}
+
+async fn get_routing_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_admin(&state, &headers, AdminRole::Viewer).await?;
+    Ok(Json(state.providers.snapshot().await.summary()).into_response())
+}
+
+async fn update_routing_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateRoutingRequest>,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let current = state.providers.snapshot().await;
+    // Adapters for newly referenced providers are created from the startup configuration
+    let routing = Routing::with_adapters(
+        &state.config,
+        request.default_provider.unwrap_or_else(|| current.default_provider.clone()),
+        request.routes.unwrap_or_else(|| current.routes.clone()),
+        request.aliases.unwrap_or_else(|| current.aliases.clone()),
+        &current.adapters,
+    )
+    .await
+    .map_err(|e| AppError::BadRequest(format!("Invalid routing: {:#}", e)))?;
+    let after = routing.summary();
+    let before = state.providers.replace(routing).await.summary();
+
+    state
+        .audit
+        .record(&actor, "routing.update", "routing", before, after.clone())
+        .await?;
+
+    Ok(Json(after).into_response())
+}
+
+async fn reload_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let routing = Routing::reload()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Reload failed, keeping current routing: {:#}", e)))?;
+    let after = routing.summary();
+    let before = state.providers.replace(routing).await.summary();
+    info!("Configuration reloaded: providers, routes and aliases replaced");
+
+    state
+        .audit
+        .record(&actor, "config.reload", "routing", before, after.clone())
+        .await?;
+
+    Ok(Json(after).into_response())
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub(crate) struct CreateKeyRequest {
+    name: Option<String>,
+    #[serde(default)]
+    scopes: Vec<String>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Alternative to `expires_at`: lifetime in seconds from now
+    expires_in_seconds: Option<i64>,
+    #[serde(default)]
+    no_content_logging: bool,
+    /// Tenant the key belongs to, for `tenant_models`
+    tenant: Option<String>,
+    /// Models and aliases the key may call; unset for the tenant's allowlist
+    models: Option<Vec<String>>,
+    /// Request size limits; unset for the global `request_limits`
+    limits: Option<RequestLimits>,
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub(crate) struct RotateKeyRequest {
+    /// How long the old key stays valid after rotation
+    grace_seconds: Option<i64>,
+}
+
+const DEFAULT_ROTATION_GRACE_SECONDS: i64 = 24 * 3600;
+
+async fn list_keys_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_admin(&state, &headers, AdminRole::Viewer).await?;
+    Ok(Json(json!({ "keys": state.key_store.list().await })).into_response())
+}
+
+async fn create_key_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateKeyRequest>,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let expires_at = request.expires_at.or_else(|| {
+        request
+            .expires_in_seconds
+            .map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs))
+    });
+
+    let (mut key, plaintext) = state
+        .key_store
+        .create(request.name, request.scopes, expires_at)
+        .await?;
+    if request.no_content_logging {
+        key = state
+            .key_store
+            .set_no_content_logging(&key.id, true)
+            .await?
+            .unwrap_or(key);
+    }
+    if request.tenant.is_some() || request.models.is_some() {
+        key = state
+            .key_store
+            .set_model_access(&key.id, request.tenant, request.models)
+            .await?
+            .unwrap_or(key);
+    }
+    if request.limits.is_some() {
+        key = state
+            .key_store
+            .set_limits(&key.id, request.limits)
+            .await?
+            .unwrap_or(key);
+    }
+
+    state
+        .audit
+        .record(&actor, "key.create", &key.id, json!(null), json!(key))
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(json!({ "key": plaintext, "record": key }))).into_response())
+}
+
+async fn rotate_key_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    request: Option<Json<RotateKeyRequest>>,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let grace_seconds = request
+        .and_then(|Json(r)| r.grace_seconds)
+        .unwrap_or(DEFAULT_ROTATION_GRACE_SECONDS);
+    let before = state.key_store.get(&id).await;
+
+    let (old, new_key, plaintext) = state
+        .key_store
+        .rotate(&id, chrono::Duration::seconds(grace_seconds))
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Active key {} not found", id)))?;
+
+    state
+        .audit
+        .record(&actor, "key.rotate", &id, json!(before), json!({ "old": old, "new": new_key }))
+        .await?;
+
+    Ok(Json(json!({ "key": plaintext, "record": new_key, "previous": old })).into_response())
+}
+
+async fn revoke_key_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let before = state.key_store.get(&id).await;
+    let key = state
+        .key_store
+        .revoke(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Key {} not found", id)))?;
+
+    state
+        .audit
+        .record(&actor, "key.revoke", &id, json!(before), json!(key))
+        .await?;
+
+    Ok(Json(json!({ "record": key })).into_response())
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub(crate) struct KeyPrivacyRequest {
+    no_content_logging: bool,
+}
+
+async fn key_privacy_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<KeyPrivacyRequest>,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let before = state.key_store.get(&id).await;
+    let key = state
+        .key_store
+        .set_no_content_logging(&id, request.no_content_logging)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Key {} not found", id)))?;
+
+    state
+        .audit
+        .record(
+            &actor,
+            "key.privacy",
+            &id,
+            json!({ "no_content_logging": before.map(|k| k.no_content_logging) }),
+            json!({ "no_content_logging": key.no_content_logging }),
+        )
+        .await?;
+
+    Ok(Json(json!({ "record": key })).into_response())
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub(crate) struct KeyModelsRequest {
+    /// Tenant the key belongs to, for `tenant_models`
+    tenant: Option<String>,
+    /// Models and aliases the key may call (`gpt-4o*` matches by prefix); unset for the
+    /// tenant's allowlist
+    models: Option<Vec<String>>,
+}
+
+async fn key_models_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<KeyModelsRequest>,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let before = state.key_store.get(&id).await;
+    let key = state
+        .key_store
+        .set_model_access(&id, request.tenant, request.models)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Key {} not found", id)))?;
+
+    state
+        .audit
+        .record(
+            &actor,
+            "key.models",
+            &id,
+            json!(before.map(|k| json!({ "tenant": k.tenant, "models": k.models }))),
+            json!({ "tenant": key.tenant, "models": key.models }),
+        )
+        .await?;
+
+    Ok(Json(json!({ "record": key })).into_response())
+}
+
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub(crate) struct KeyLimitsRequest {
+    /// Request size limits; unset (or `null`) for the global `request_limits`
+    limits: Option<RequestLimits>,
+}
+
+async fn key_limits_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<KeyLimitsRequest>,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let before = state.key_store.get(&id).await;
+    let key = state
+        .key_store
+        .set_limits(&id, request.limits)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Key {} not found", id)))?;
+
+    state
+        .audit
+        .record(
+            &actor,
+            "key.limits",
+            &id,
+            json!({ "limits": before.map(|k| k.limits) }),
+            json!({ "limits": key.limits }),
+        )
+        .await?;
+
+    Ok(Json(json!({ "record": key })).into_response())
+}
+
+/// Whose data to delete; at least one of the fields is required
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub(crate) struct PurgeRequest {
+    /// A client key ID, short for `client_id` `key:<id>`
+    key_id: Option<String>,
+    /// Any client ID: `static`, `key:<id>`, `tenant:<name>` or `jwt:<subject>`
+    client_id: Option<String>,
+    /// An end-user identifier as sent in `user` / `x-user-id`
+    end_user: Option<String>,
+}
+
+/// Delete what is stored about a client or end user: conversations, dataset records,
+/// usage rollups, request transcripts, end-user usage and the day's quota counter
+async fn purge_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<PurgeRequest>,
+) -> Result<Response, AppError> {
+    let actor = authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let client_id = request.client_id.or_else(|| request.key_id.map(|id| format!("key:{}", id)));
+    // Stored under the same (possibly hashed) form the request path records
+    let end_user = request.end_user.map(|id| match crate::server::end_user_hash_salt(&state.config) {
+        Some(salt) => crate::request_context::hash_end_user(&id, salt),
+        None => id,
+    });
+    if client_id.is_none() && end_user.is_none() {
+        return Err(AppError::BadRequest("client_id, key_id or end_user is required".to_string()));
+    }
+
+    let conversations = match (&state.conversations, &client_id) {
+        (Some(store), Some(client_id)) => store.purge_owner(client_id).await?,
+        _ => 0,
+    };
+    let dataset_records = match state.dataset {
+        Some(ref dataset) => dataset.purge(client_id.as_deref(), end_user.as_deref()).await?,
+        None => 0,
+    };
+    let usage_rollups = match (&state.usage_rollup, &client_id) {
+        (Some(rollup), Some(client_id)) => rollup.purge_client(client_id).await?,
+        _ => 0,
+    };
+    let transcripts = match (&state.transcripts, &client_id) {
+        (Some(store), Some(client_id)) => store.purge_client(client_id),
+        _ => 0,
+    };
+    let end_user_usage = end_user.as_deref().is_some_and(|id| state.metrics.forget_end_user(id));
+    if let Some(ref client_id) = client_id {
+        let key = crate::server::daily_quota_key(client_id, chrono::Utc::now());
+        state.shared_state.delete(&key).await?;
+    }
+
+    let purged = json!({
+        "conversations": conversations,
+        "dataset_records": dataset_records,
+        "usage_rollups": usage_rollups,
+        "transcripts": transcripts,
+        "end_user_usage": end_user_usage,
+    });
+    // The audit log names the end user only by hash
+    let target = client_id
+        .clone()
+        .unwrap_or_else(|| format!("end_user:{}", crate::dataset::subject_hash(end_user.as_deref().unwrap_or_default())));
+    state
+        .audit
+        .record(&actor, "privacy.purge", &target, json!(null), purged.clone())
+        .await?;
+
+    Ok(Json(json!({ "client_id": client_id, "purged": purged })).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    action: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn audit_query_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, AppError> {
+    authorize_admin(&state, &headers, AdminRole::Viewer).await?;
+
+    let entries = state
+        .audit
+        .query(query.action.as_deref(), query.limit.unwrap_or(100))
+        .await;
+
+    Ok(Json(json!({ "entries": entries })).into_response())
+}
+
+async fn audit_verify_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_admin(&state, &headers, AdminRole::Viewer).await?;
+
+    let body = match state.audit.verify().await {
+        Ok(count) => json!({ "valid": true, "entries": count }),
+        Err(seq) => json!({ "valid": false, "broken_at_seq": seq }),
+    };
+
+    Ok(Json(body).into_response())
+}
+
+/// The transcript of a recent request; it holds prompts and answers, so operators only
+async fn transcript_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    authorize_admin(&state, &headers, AdminRole::Operator).await?;
+
+    let store = state
+        .transcripts
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("Request transcripts are not enabled".to_string()))?;
+    let transcript = store
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound(format!("No transcript of request {}; only the latest are kept", id)))?;
+    Ok(Json(transcript).into_response())
+}
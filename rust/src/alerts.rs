@@ -0,0 +1,255 @@
+/*!
+ * Alerts
+ *
+ * Posts outage notifications to Slack, Discord or generic JSON webhooks: when
+ * a provider or regional endpoint is taken out of rotation (its circuit
+ * opens), when the share of failed upstream calls spikes, when a client
+ * crosses a share of its daily quota, and when a client keeps sending the same
+ * request (a retry loop gone wrong). Repeats of an alert are held back for
+ * `dedup_secs` and counted, so a flapping upstream does not flood the channel.
+ */
+
+use crate::config::{AlertWebhook, AlertsConfig, WebhookFormat};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+static GLOBAL: OnceLock<Alerter> = OnceLock::new();
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    CircuitOpen,
+    ErrorRateSpike,
+    BudgetThreshold,
+    DuplicateRequests,
+}
+
+impl AlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CircuitOpen => "circuit_open",
+            Self::ErrorRateSpike => "error_rate_spike",
+            Self::BudgetThreshold => "budget_threshold",
+            Self::DuplicateRequests => "duplicate_requests",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub kind: AlertKind,
+    /// What the alert is about: a provider, an endpoint or a client
+    pub subject: String,
+    pub message: String,
+}
+
+impl Alert {
+    /// Alerts with the same key are repeats of each other
+    fn key(&self) -> String {
+        format!("{}:{}", self.kind.as_str(), self.subject)
+    }
+}
+
+/// Webhook body for an alert; `suppressed` repeats were held back since the last one sent
+pub fn payload(format: WebhookFormat, alert: &Alert, suppressed: u64) -> Value {
+    let mut text = format!("[{}] {}: {}", alert.kind.as_str(), alert.subject, alert.message);
+    if suppressed > 0 {
+        text.push_str(&format!(" ({} repeats suppressed)", suppressed));
+    }
+    match format {
+        WebhookFormat::Slack => json!({"text": text}),
+        WebhookFormat::Discord => json!({"content": text}),
+        WebhookFormat::Generic => json!({
+            "kind": alert.kind.as_str(),
+            "subject": alert.subject,
+            "message": alert.message,
+            "suppressed": suppressed,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        }),
+    }
+}
+
+/// Thresholds (shares of `quota`) crossed by the request that brought usage to `used`
+pub fn crossed_thresholds(thresholds: &[f64], used: u64, quota: u64) -> Vec<f64> {
+    if quota == 0 || used == 0 {
+        return Vec::new();
+    }
+    thresholds
+        .iter()
+        .copied()
+        .filter(|threshold| {
+            let limit = (threshold * quota as f64).ceil().max(1.0) as u64;
+            used >= limit && used - 1 < limit
+        })
+        .collect()
+}
+
+/// Upstream calls finished within one second
+#[derive(Debug, Default)]
+struct Bucket {
+    second: u64,
+    calls: u64,
+    failures: u64,
+}
+
+pub struct Alerter {
+    config: AlertsConfig,
+    client: Client,
+    started: Instant,
+    /// Per alert key: when it was last sent and the repeats held back since
+    sent: Mutex<HashMap<String, (Instant, u64)>>,
+    /// Per provider: recent upstream calls
+    calls: Mutex<HashMap<String, VecDeque<Bucket>>>,
+}
+
+impl Alerter {
+    pub fn new(config: &AlertsConfig, client: Client) -> Self {
+        Self {
+            config: config.clone(),
+            client,
+            started: Instant::now(),
+            sent: Mutex::new(HashMap::new()),
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether an alert goes out at `now`; if so, the number of repeats held back before it
+    pub fn admit(&self, alert: &Alert, now: Instant) -> Option<u64> {
+        let mut sent = self.sent.lock().unwrap();
+        let dedup = Duration::from_secs(self.config.dedup_secs);
+        match sent.get_mut(&alert.key()) {
+            Some((last, suppressed)) if now.saturating_duration_since(*last) < dedup => {
+                *suppressed += 1;
+                None
+            }
+            Some((last, suppressed)) => {
+                *last = now;
+                Some(std::mem::take(suppressed))
+            }
+            None => {
+                sent.insert(alert.key(), (now, 0));
+                Some(0)
+            }
+        }
+    }
+
+    /// Post an alert to every webhook in the background, unless it is a held-back repeat
+    pub fn notify(&self, alert: Alert) {
+        let Some(suppressed) = self.admit(&alert, Instant::now()) else {
+            return;
+        };
+        warn!("Alert [{}] {}: {}", alert.kind.as_str(), alert.subject, alert.message);
+        if self.config.webhooks.is_empty() || tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        let (client, webhooks) = (self.client.clone(), self.config.webhooks.clone());
+        tokio::spawn(async move {
+            for webhook in webhooks {
+                send(&client, &webhook, &alert, suppressed).await;
+            }
+        });
+    }
+
+    /// Count an upstream call; returns an alert when the provider's error rate is over the threshold
+    pub fn record_call_at(&self, provider: &str, ok: bool, second: u64) -> Option<Alert> {
+        let mut calls = self.calls.lock().unwrap();
+        let buckets = calls.entry(provider.to_string()).or_default();
+        let oldest = second.saturating_sub(self.config.error_rate_window_secs.saturating_sub(1));
+        while buckets.front().is_some_and(|b| b.second < oldest) {
+            buckets.pop_front();
+        }
+        if buckets.back().is_none_or(|b| b.second < second) {
+            buckets.push_back(Bucket { second, ..Default::default() });
+        }
+        let bucket = buckets.back_mut().unwrap();
+        bucket.calls += 1;
+        bucket.failures += u64::from(!ok);
+
+        let (total, failures) = buckets.iter().fold((0, 0), |(total, failures), b| (total + b.calls, failures + b.failures));
+        let spiking = total >= self.config.error_rate_min_requests.max(1)
+            && failures as f64 / total as f64 >= self.config.error_rate_threshold;
+        spiking.then(|| Alert {
+            kind: AlertKind::ErrorRateSpike,
+            subject: provider.to_string(),
+            message: format!(
+                "{} of {} upstream calls failed in the last {}s",
+                failures, total, self.config.error_rate_window_secs
+            ),
+        })
+    }
+
+    pub fn record_call(&self, provider: &str, ok: bool) {
+        if let Some(alert) = self.record_call_at(provider, ok, self.started.elapsed().as_secs()) {
+            self.notify(alert);
+        }
+    }
+
+    /// Alert for each budget threshold the client's latest request crossed
+    pub fn quota_used(&self, client_id: &str, used: u64, quota: u64) {
+        for threshold in crossed_thresholds(&self.config.budget_thresholds, used, quota) {
+            self.notify(Alert {
+                kind: AlertKind::BudgetThreshold,
+                // One alert per threshold, so crossing 100% is not held back as a repeat of 80%
+                subject: format!("{} ({:.0}% of daily quota)", client_id, threshold * 100.0),
+                message: format!("used {} of {} daily requests", used, quota),
+            });
+        }
+    }
+}
+
+async fn send(client: &Client, webhook: &AlertWebhook, alert: &Alert, suppressed: u64) {
+    let body = payload(webhook.format, alert, suppressed);
+    match client.post(&webhook.url).json(&body).timeout(WEBHOOK_TIMEOUT).send().await {
+        Ok(response) if !response.status().is_success() => {
+            warn!("Alert webhook returned {}", response.status());
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Alert webhook failed: {}", e),
+    }
+}
+
+/// Install the process-wide alerter; later calls are ignored
+pub fn configure(config: &AlertsConfig, client: Client) {
+    let _ = GLOBAL.set(Alerter::new(config, client));
+}
+
+/// A provider or endpoint was taken out of rotation; a no-op unless alerts are configured
+pub fn circuit_opened(subject: &str, message: String) {
+    if let Some(alerter) = GLOBAL.get() {
+        alerter.notify(Alert {
+            kind: AlertKind::CircuitOpen,
+            subject: subject.to_string(),
+            message,
+        });
+    }
+}
+
+/// Count an upstream call towards the provider's error rate
+pub fn record_call(provider: &str, ok: bool) {
+    if let Some(alerter) = GLOBAL.get() {
+        alerter.record_call(provider, ok);
+    }
+}
+
+/// A client's daily quota usage after its latest request
+pub fn quota_used(client_id: &str, used: u64, quota: u64) {
+    if let Some(alerter) = GLOBAL.get() {
+        alerter.quota_used(client_id, used, quota);
+    }
+}
+
+/// A client sent the same request more often than `duplicate_requests` allows
+pub fn duplicate_requests(client_id: &str, message: String) {
+    if let Some(alerter) = GLOBAL.get() {
+        alerter.notify(Alert {
+            kind: AlertKind::DuplicateRequests,
+            subject: client_id.to_string(),
+            message,
+        });
+    }
+}
@@ -0,0 +1,58 @@
+/*!
+ * Model Aliases
+ *
+ * An alias gives clients a stable model name, decoupled from vendor churn. It
+ * stands for one model, or for a list of targets tried in order: each target
+ * is a model, optionally pinned to a provider and with request fields set for
+ * it (`temperature`, `max_tokens`, ...). An alias with a single target is
+ * served like a request for that model, streaming included. With several,
+ * the targets are asked in turn until one answers without an error; a
+ * streaming client gets that answer replayed, and `x-alias-model` names the
+ * target that gave it.
+ */
+
+use crate::config::AliasTarget;
+use anyhow::{anyhow, Result};
+use futures::Future;
+use serde_json::{json, Value};
+use tracing::warn;
+
+/// Response header naming the target whose answer was returned
+pub const MODEL_HEADER: &str = "x-alias-model";
+
+/// Point a request body at a target: its model, with its parameters set
+pub fn apply(target: &AliasTarget, body: &mut Value) {
+    body["model"] = json!(target.model);
+    if let Some(obj) = body.as_object_mut() {
+        for (key, value) in &target.params {
+            obj.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// The first answer from the targets in order, and the model that gave it.
+/// `call(target, body)` sends one non-streaming request and returns the response
+pub async fn run<F, Fut>(alias: &str, targets: &[AliasTarget], mut body: Value, call: F) -> Result<(Value, String)>
+where
+    F: Fn(AliasTarget, Value) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    if let Some(obj) = body.as_object_mut() {
+        obj.remove("stream");
+        obj.remove("stream_options");
+    }
+
+    let mut last_error = None;
+    for target in targets {
+        let mut request = body.clone();
+        apply(target, &mut request);
+        match call(target.clone(), request).await {
+            Ok(response) => return Ok((response, target.model.clone())),
+            Err(e) => {
+                warn!("Alias {}: {} failed, falling back: {}", alias, target.model, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow!("Alias {} has no targets", alias)))
+}
@@ -0,0 +1,160 @@
+/*!
+ * Audit Log
+ *
+ * Append-only, hash-chained record of admin API mutations.
+ */
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// A single audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: String,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub before: Value,
+    pub after: Value,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Hash of every field except `hash` itself, chained to the previous entry
+    fn compute_hash(&self) -> String {
+        let payload = serde_json::json!({
+            "seq": self.seq,
+            "timestamp": self.timestamp,
+            "actor": self.actor,
+            "action": self.action,
+            "target": self.target,
+            "before": self.before,
+            "after": self.after,
+            "prev_hash": self.prev_hash,
+        });
+        format!("{:x}", Sha256::digest(payload.to_string().as_bytes()))
+    }
+}
+
+pub struct AuditLog {
+    file_path: Option<PathBuf>,
+    entries: RwLock<Vec<AuditEntry>>,
+}
+
+impl AuditLog {
+    /// Open the audit log, loading and verifying any existing entries from disk
+    pub async fn open(file_path: Option<PathBuf>) -> Result<Self> {
+        let mut entries = Vec::new();
+
+        if let Some(ref path) = file_path {
+            if let Ok(content) = tokio::fs::read_to_string(path).await {
+                for (line_no, line) in content.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let entry: AuditEntry = serde_json::from_str(line)
+                        .with_context(|| format!("Failed to parse audit log line {}", line_no + 1))?;
+                    entries.push(entry);
+                }
+                info!("Loaded {} audit log entries from {:?}", entries.len(), path);
+            }
+        }
+
+        if let Err(seq) = verify_chain(&entries) {
+            warn!("Audit log chain is broken at entry {}; the log may have been tampered with", seq);
+        }
+
+        Ok(Self {
+            file_path,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// Append a new entry and persist it
+    pub async fn record(
+        &self,
+        actor: &str,
+        action: &str,
+        target: &str,
+        before: Value,
+        after: Value,
+    ) -> Result<AuditEntry> {
+        let mut entries = self.entries.write().await;
+
+        let (seq, prev_hash) = match entries.last() {
+            Some(last) => (last.seq + 1, last.hash.clone()),
+            None => (1, GENESIS_HASH.to_string()),
+        };
+
+        let mut entry = AuditEntry {
+            seq,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            target: target.to_string(),
+            before,
+            after,
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        if let Some(ref path) = self.file_path {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .context("Failed to open audit log file")?;
+            let line = format!("{}\n", serde_json::to_string(&entry)?);
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await?;
+        }
+
+        info!("Audit: {} {} {}", entry.actor, entry.action, entry.target);
+        entries.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Most recent entries first, optionally filtered by action
+    pub async fn query(&self, action: Option<&str>, limit: usize) -> Vec<AuditEntry> {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .rev()
+            .filter(|e| action.map(|a| e.action == a).unwrap_or(true))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Verify the whole chain, returning the sequence number of the first bad entry
+    pub async fn verify(&self) -> std::result::Result<usize, u64> {
+        let entries = self.entries.read().await;
+        verify_chain(&entries).map(|_| entries.len())
+    }
+}
+
+/// Check that every entry's hash is intact and links to its predecessor
+pub fn verify_chain(entries: &[AuditEntry]) -> std::result::Result<(), u64> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for entry in entries {
+        if entry.prev_hash != prev_hash || entry.hash != entry.compute_hash() {
+            return Err(entry.seq);
+        }
+        prev_hash = entry.hash.clone();
+    }
+    Ok(())
+}
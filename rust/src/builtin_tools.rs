@@ -0,0 +1,105 @@
+/*!
+ * Anthropic Built-in Tools
+ *
+ * Computer use, bash and text editor tools on the Claude messages endpoint.
+ * These are defined by their versioned `type` and executed by the client, but
+ * only Anthropic's API knows how to prompt for them, so requests using them
+ * are checked here, sent to Claude backends untouched (with the beta flag the
+ * tool version needs) and refused with a clear error everywhere else.
+ */
+
+use crate::common::ModelProvider;
+use anyhow::Result;
+use serde_json::Value;
+
+/// A built-in tool family: its `type` prefix, the `name`s Anthropic accepts, and per-version beta flags
+struct BuiltinTool {
+    prefix: &'static str,
+    names: &'static [&'static str],
+    betas: &'static [(&'static str, &'static str)],
+}
+
+const BUILTIN_TOOLS: &[BuiltinTool] = &[
+    BuiltinTool {
+        prefix: "computer_",
+        names: &["computer"],
+        betas: &[("computer_20241022", "computer-use-2024-10-22"), ("computer_20250124", "computer-use-2025-01-24")],
+    },
+    BuiltinTool {
+        prefix: "bash_",
+        names: &["bash"],
+        betas: &[("bash_20241022", "computer-use-2024-10-22"), ("bash_20250124", "computer-use-2025-01-24")],
+    },
+    BuiltinTool {
+        prefix: "text_editor_",
+        names: &["str_replace_editor", "str_replace_based_edit_tool"],
+        betas: &[("text_editor_20241022", "computer-use-2024-10-22"), ("text_editor_20250124", "computer-use-2025-01-24")],
+    },
+];
+
+fn builtin(tool: &Value) -> Option<(&str, &'static BuiltinTool)> {
+    let tool_type = tool.get("type")?.as_str()?;
+    let family = BUILTIN_TOOLS.iter().find(|family| tool_type.starts_with(family.prefix))?;
+    Some((tool_type, family))
+}
+
+fn tools(claude_req: &Value) -> impl Iterator<Item = &Value> {
+    claude_req.get("tools").and_then(|t| t.as_array()).into_iter().flatten()
+}
+
+/// Built-in tool types a Claude request uses, e.g. `computer_20250124`
+pub fn requested(claude_req: &Value) -> Vec<&str> {
+    tools(claude_req).filter_map(|tool| Some(builtin(tool)?.0)).collect()
+}
+
+/// Check the built-in tools are well formed, so mistakes surface as a 400 rather than an upstream error
+pub fn validate(claude_req: &Value) -> Result<()> {
+    for tool in tools(claude_req) {
+        let Some((tool_type, family)) = builtin(tool) else {
+            continue;
+        };
+        let name = tool.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+        if !family.names.contains(&name) {
+            anyhow::bail!("{} tool must be named {}", tool_type, family.names.join(" or "));
+        }
+        if family.prefix == "computer_" {
+            for field in ["display_width_px", "display_height_px"] {
+                if tool.get(field).and_then(|v| v.as_u64()).unwrap_or(0) == 0 {
+                    anyhow::bail!("{} tool requires a positive integer {}", tool_type, field);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `anthropic-beta` flags the request's built-in tool versions need
+pub fn beta_flags(claude_req: &Value) -> Vec<String> {
+    let mut flags: Vec<String> = Vec::new();
+    for tool_type in requested(claude_req) {
+        let beta = BUILTIN_TOOLS
+            .iter()
+            .flat_map(|family| family.betas)
+            .find(|(version, _)| *version == tool_type);
+        if let Some((_, flag)) = beta {
+            if !flags.iter().any(|f| f == flag) {
+                flags.push(flag.to_string());
+            }
+        }
+    }
+    flags
+}
+
+/// Backends that honor Anthropic's built-in tools
+pub fn supported(provider: Option<&ModelProvider>) -> bool {
+    matches!(provider, Some(ModelProvider::ClaudeCustom))
+}
+
+/// Error for a request whose built-in tools the configured backend cannot run
+pub fn unsupported_message(provider: &str, tool_types: &[&str]) -> String {
+    format!(
+        "Built-in tools ({}) are only supported with a Claude API backend; the current provider {} cannot run them",
+        tool_types.join(", "),
+        provider
+    )
+}
@@ -0,0 +1,132 @@
+/*!
+ * Cascade
+ *
+ * Virtual models that try a cheap model first and escalate to costlier ones
+ * only when its answer fails the quality checks: it must finish normally, be
+ * at least `min_chars` long, not read as a refusal and, with a judge
+ * configured, score at least `min_score` out of 10. A step that errors
+ * escalates too. The last step's answer is returned whatever it looks like.
+ */
+
+use crate::common::{ModelListResponse, ModelProtocol};
+use crate::config::CascadeConfig;
+use crate::ensemble::{answer_text, finished, first_number, judge_request};
+use anyhow::{anyhow, Result};
+use futures::Future;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+/// Response header naming the step whose answer was returned
+pub const MODEL_HEADER: &str = "x-cascade-model";
+
+/// Refusal phrases, looked for case-insensitively in the answer's first 200 characters
+const REFUSAL_PHRASES: &[&str] = &[
+    "i can't help",
+    "i cannot help",
+    "i can't assist",
+    "i cannot assist",
+    "i'm unable to",
+    "i am unable to",
+    "i'm not able to",
+    "i am not able to",
+    "i'm sorry, but i can",
+    "sorry, i can't",
+    "i won't be able to",
+];
+
+const JUDGE_PROMPT: &str = "You grade an answer to a conversation. \
+Reply with a single score from 1 (useless) to 10 (excellent) for how correct, complete and helpful it is.";
+
+/// The cascade a requested model names, if any
+pub fn find<'a>(cascades: &'a [CascadeConfig], model: &str) -> Option<&'a CascadeConfig> {
+    cascades.iter().find(|cascade| cascade.model == model)
+}
+
+/// Add the virtual models to a model list
+pub fn list_models(cascades: &[CascadeConfig], models: &mut ModelListResponse) {
+    for cascade in cascades {
+        models.add_virtual(&cascade.model, "cascade");
+    }
+}
+
+/// The first acceptable answer (in `protocol`, with the cascade's model name) and the step that gave it.
+/// `call(model, body)` sends one non-streaming request in `protocol` and returns the response in it
+pub async fn run<F, Fut>(cascade: &CascadeConfig, protocol: ModelProtocol, mut body: Value, call: F) -> Result<(Value, String)>
+where
+    F: Fn(String, Value) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    if let Some(obj) = body.as_object_mut() {
+        obj.remove("stream");
+        obj.remove("stream_options");
+    }
+
+    for (i, step) in cascade.steps.iter().enumerate() {
+        let last = i + 1 == cascade.steps.len();
+        let mut request = body.clone();
+        request["model"] = json!(step);
+        let mut response = match call(step.clone(), request).await {
+            Ok(response) => response,
+            Err(e) if last => return Err(e),
+            Err(e) => {
+                warn!("Cascade {}: {} failed, escalating: {}", cascade.model, step, e);
+                continue;
+            }
+        };
+
+        if !last {
+            if let Some(reason) = rejection(cascade, protocol, &body, &response, &call).await {
+                info!("Cascade {}: escalating past {}: {}", cascade.model, step, reason);
+                continue;
+            }
+        }
+        info!("Cascade {}: answer from {}", cascade.model, step);
+        if response.get("model").is_some() {
+            response["model"] = json!(cascade.model);
+        }
+        return Ok((response, step.clone()));
+    }
+    Err(anyhow!("Cascade {} has no steps", cascade.model))
+}
+
+/// Why an answer fails the cascade's checks, `None` when it passes
+async fn rejection<F, Fut>(cascade: &CascadeConfig, protocol: ModelProtocol, body: &Value, response: &Value, call: &F) -> Option<String>
+where
+    F: Fn(String, Value) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    if !finished(response, protocol) {
+        return Some("did not finish normally".to_string());
+    }
+    let text = answer_text(response, protocol);
+    let chars = text.trim().chars().count();
+    if chars < cascade.min_chars {
+        return Some(format!("{} chars, below {}", chars, cascade.min_chars));
+    }
+    if cascade.detect_refusals && is_refusal(&text, &cascade.refusal_phrases) {
+        return Some("refusal".to_string());
+    }
+
+    let judge = cascade.judge.as_ref()?;
+    let conversation = crate::logger::extract_prompt_from_request(body, protocol.as_str());
+    let prompt = format!("Conversation:\n{}\n\nAnswer:\n{}\n\nScore (1-10):", conversation, text);
+    match call(judge.clone(), judge_request(judge, protocol, JUDGE_PROMPT, &prompt)).await {
+        Ok(verdict) => match first_number(&answer_text(&verdict, protocol)) {
+            Some(score) if score >= cascade.min_score => None,
+            Some(score) => Some(format!("judge score {} below {}", score, cascade.min_score)),
+            None => Some("judge gave no score".to_string()),
+        },
+        Err(e) => Some(format!("judge failed: {}", e)),
+    }
+}
+
+/// Whether the answer's opening contains one of the built-in refusal phrases or `extra`
+pub fn is_refusal(text: &str, extra: &[String]) -> bool {
+    let opening: String = text.trim_start().chars().take(200).collect::<String>().to_lowercase();
+    let opening = opening.replace('\u{2019}', "'");
+    REFUSAL_PHRASES
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+        .any(|phrase| opening.contains(&phrase.to_lowercase()))
+}
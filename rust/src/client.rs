@@ -0,0 +1,207 @@
+/*!
+ * Embedded client
+ *
+ * Sends chat requests to the configured providers from within a Rust program,
+ * without the HTTP server. Requests go through the same pipeline as the
+ * server's: model aliases and routes pick the provider, the body is converted
+ * to the provider's protocol and the answer back to the caller's, adapters
+ * retry within the retry budget, and the response cache (when configured)
+ * answers repeated requests and stands in for a failed upstream. Ensemble
+ * and cascade models are answered by the models behind them, without the cache.
+ *
+ * ```no_run
+ * # async fn example() -> anyhow::Result<()> {
+ * use aiclient2api_rust::{client::UnifiedClient, config::Config, convert::ChatRequest};
+ * use serde_json::json;
+ *
+ * let client = UnifiedClient::new(Config::load()?).await?;
+ * let request = ChatRequest::OpenAI(json!({
+ *     "model": "gpt-4o-mini",
+ *     "messages": [{"role": "user", "content": "Hello!"}]
+ * }));
+ * let response = client.chat(request).await?;
+ * println!("{}", response["choices"][0]["message"]["content"]);
+ * # Ok(())
+ * # }
+ * ```
+ */
+
+use crate::config::Config;
+use crate::convert::ChatRequest;
+use crate::provider_registry::{ProviderRegistry, Routing, Upstream};
+use crate::request_context::RequestContext;
+use crate::response_cache::ResponseCache;
+use crate::stream_recovery::ValueStream;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Client ID the embedded client's requests are attributed to (e.g. for `stale_if_error_clients`)
+pub const CLIENT_ID: &str = "embedded";
+
+pub struct UnifiedClient {
+    config: Config,
+    providers: ProviderRegistry,
+    cache: Option<ResponseCache>,
+}
+
+impl UnifiedClient {
+    /// Create adapters for the configured providers; `vault://` and `aws-sm://`
+    /// credential references are resolved first
+    pub async fn new(mut config: Config) -> Result<Self> {
+        crate::secret_refs::resolve_config(&mut config, &reqwest::Client::new()).await?;
+        crate::retry_budget::configure(&config.retry_budget);
+
+        let routing = Routing::build(&config).await?;
+        let cache = config.response_cache.as_ref().map(|response_cache| {
+            ResponseCache::new(crate::cluster::open_store(&config, None), response_cache)
+        });
+
+        Ok(Self {
+            config,
+            providers: ProviderRegistry::new(routing),
+            cache,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Providers, model routes and aliases in effect; replace them with `set_routing`
+    pub async fn routing(&self) -> Arc<Routing> {
+        self.providers.snapshot().await
+    }
+
+    pub async fn set_routing(&self, routing: Routing) {
+        self.providers.replace(routing).await;
+    }
+
+    /// Send a chat request to the provider its model routes to; the response is
+    /// in the request's protocol
+    pub async fn chat(&self, request: ChatRequest) -> Result<Value> {
+        self.send(None, request).await
+    }
+
+    /// Send a chat request to the named provider, ignoring model routes
+    pub async fn chat_with_provider(&self, provider: &str, request: ChatRequest) -> Result<Value> {
+        self.send(Some(provider), request).await
+    }
+
+    /// Streaming chat; chunks are in the request's protocol. Cache hits, ensemble or cascade answers
+    /// and answers of providers listed in `simulated_streaming` are replayed as a stream
+    pub async fn chat_stream(&self, request: ChatRequest) -> Result<ValueStream> {
+        if let Some(answer) = self.answer_virtual(&request).await {
+            return Ok(crate::simulated_stream::replay(&answer?, request.protocol(), &self.config.simulated_streaming.pacing));
+        }
+        let (upstream, model, mut request) = self.prepare(None, request).await?;
+        let protocol = request.protocol();
+
+        if let Some(cache) = &self.cache {
+            if let Some(cached) = cache.get(&cache.key(protocol, request.body())).await {
+                return Ok(crate::simulated_stream::replay(&cached, protocol, cache.pacing()));
+            }
+        }
+
+        let ctx = RequestContext::default();
+        let simulated = &self.config.simulated_streaming;
+        if simulated.providers.contains(&upstream.provider) {
+            if let Some(fields) = request.body_mut().as_object_mut() {
+                fields.remove("stream");
+                fields.remove("stream_options");
+            }
+            let response = upstream.adapter.generate_content(&model, request, &ctx).await?;
+            let response = response.into_protocol(protocol, Some(&model))?;
+            return Ok(crate::simulated_stream::replay(&response, protocol, &simulated.pacing));
+        }
+        let stream = upstream.adapter.generate_content_stream(&model, request, &ctx).await?;
+        stream.into_protocol(protocol, Some(&model))
+    }
+
+    async fn send(&self, provider: Option<&str>, request: ChatRequest) -> Result<Value> {
+        if provider.is_none() {
+            if let Some(answer) = self.answer_virtual(&request).await {
+                return answer;
+            }
+        }
+        let (upstream, model, request) = self.prepare(provider, request).await?;
+        let protocol = request.protocol();
+
+        let key = self.cache.as_ref().map(|cache| cache.key(protocol, request.body()));
+        if let Some(cached) = self.cached(key.as_deref()).await {
+            return Ok(cached);
+        }
+
+        let ctx = RequestContext::default();
+        let result = upstream
+            .adapter
+            .generate_content(&model, request, &ctx)
+            .await
+            .and_then(|response| response.into_protocol(protocol, Some(&model)));
+        match (result, &self.cache, key) {
+            (Ok(response), Some(cache), Some(key)) => {
+                if let Err(e) = cache.put(&key, &response).await {
+                    warn!("Failed to cache response: {}", e);
+                }
+                Ok(response)
+            }
+            (Err(e), Some(cache), Some(key)) => match cache.get_stale(&key, CLIENT_ID).await {
+                Some(stale) => {
+                    warn!("Upstream failed ({}); serving a stale cached response", e);
+                    Ok(stale)
+                }
+                None => Err(e),
+            },
+            (result, _, _) => result,
+        }
+    }
+
+    /// Answer a request for an ensemble or cascade model by asking the models behind it;
+    /// `None` for other models
+    async fn answer_virtual(&self, request: &ChatRequest) -> Option<Result<Value>> {
+        let routing = self.providers.snapshot().await;
+        let model = routing.resolve_alias(request.body().get("model")?.as_str()?);
+        let ensemble = crate::ensemble::find(&self.config.ensembles, &model);
+        let cascade = crate::cascade::find(&self.config.cascades, &model);
+        if ensemble.is_none() && cascade.is_none() {
+            return None;
+        }
+
+        let protocol = request.protocol();
+        let routing = &routing;
+        let call = |model: String, body: Value| async move {
+            let upstream = routing.upstream_for(&model, None)?;
+            let response = upstream
+                .adapter
+                .generate_content(&model, ChatRequest::new(protocol, body), &RequestContext::default())
+                .await?;
+            response.into_protocol(protocol, Some(&model))
+        };
+        let body = request.body().clone();
+        let result = match (ensemble, cascade) {
+            (Some(ensemble), _) => crate::ensemble::run(ensemble, protocol, body, call).await,
+            (None, Some(cascade)) => crate::cascade::run(cascade, protocol, body, call).await,
+            (None, None) => return None,
+        };
+        Some(result.map(|(response, _)| response))
+    }
+
+    /// Resolve the alias and pick the provider; the body carries the resolved model
+    async fn prepare(&self, provider: Option<&str>, mut request: ChatRequest) -> Result<(Upstream, String, ChatRequest)> {
+        let routing = self.providers.snapshot().await;
+        let model = request
+            .body()
+            .get("model")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("model is required"))?;
+        let model = routing.resolve_alias(model);
+        request.body_mut()["model"] = json!(model);
+        let upstream = routing.upstream_for(&model, provider)?;
+        Ok((upstream, model, request))
+    }
+
+    async fn cached(&self, key: Option<&str>) -> Option<Value> {
+        self.cache.as_ref()?.get(key?).await
+    }
+}
@@ -0,0 +1,241 @@
+/*!
+ * Cluster Shared State
+ *
+ * Key/value state that must be consistent across proxy instances (rate-limit
+ * counters, quota usage, circuit-breaker state, used request signatures,
+ * idempotency records). A single
+ * instance keeps it in memory; with `redis_url` configured every instance
+ * shares it through Redis.
+ */
+
+use crate::config::Config;
+use crate::pool_manager::ProviderPoolManager;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::info;
+
+#[async_trait]
+pub trait SharedStore: Send + Sync {
+    /// Add `delta` to a counter, starting a fresh `ttl` window when the counter does not exist
+    async fn incr(&self, key: &str, delta: i64, ttl: Duration) -> Result<i64>;
+
+    async fn get(&self, key: &str) -> Result<Option<String>>;
+
+    async fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<()>;
+
+    /// Set only if the key is absent; returns whether the value was stored
+    async fn set_if_absent(&self, key: &str, value: &str, ttl: Duration) -> Result<bool>;
+
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Backend name for logs and `/health`
+    fn backend(&self) -> &'static str;
+}
+
+/// Single-instance store
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, (String, Option<Instant>)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn live<'a>(
+        entries: &'a mut HashMap<String, (String, Option<Instant>)>,
+        key: &str,
+    ) -> Option<&'a mut (String, Option<Instant>)> {
+        let expired = matches!(entries.get(key), Some((_, Some(at))) if *at <= Instant::now());
+        if expired {
+            entries.remove(key);
+        }
+        entries.get_mut(key)
+    }
+}
+
+#[async_trait]
+impl SharedStore for MemoryStore {
+    async fn incr(&self, key: &str, delta: i64, ttl: Duration) -> Result<i64> {
+        let mut entries = self.entries.lock().await;
+        if let Some((value, _)) = Self::live(&mut entries, key) {
+            let next = value.parse::<i64>().unwrap_or(0) + delta;
+            *value = next.to_string();
+            return Ok(next);
+        }
+        entries.insert(key.to_string(), (delta.to_string(), Some(Instant::now() + ttl)));
+        Ok(delta)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>> {
+        let mut entries = self.entries.lock().await;
+        Ok(Self::live(&mut entries, key).map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<()> {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.entries
+            .lock()
+            .await
+            .insert(key.to_string(), (value.to_string(), expires_at));
+        Ok(())
+    }
+
+    async fn set_if_absent(&self, key: &str, value: &str, ttl: Duration) -> Result<bool> {
+        let mut entries = self.entries.lock().await;
+        if Self::live(&mut entries, key).is_some() {
+            return Ok(false);
+        }
+        entries.insert(key.to_string(), (value.to_string(), Some(Instant::now() + ttl)));
+        Ok(true)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.entries.lock().await.remove(key);
+        Ok(())
+    }
+
+    fn backend(&self) -> &'static str {
+        "memory"
+    }
+}
+
+/// Redis-backed store shared by all instances; keys are namespaced with `prefix`
+pub struct RedisStore {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisStore {
+    pub fn new(conn: ConnectionManager, prefix: impl Into<String>) -> Self {
+        Self {
+            conn,
+            prefix: prefix.into(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+#[async_trait]
+impl SharedStore for RedisStore {
+    async fn incr(&self, key: &str, delta: i64, ttl: Duration) -> Result<i64> {
+        // INCRBY and the first-write EXPIRE must be atomic, or a crash leaves a counter that never resets
+        let script = redis::Script::new(
+            r"
+            local value = redis.call('INCRBY', KEYS[1], ARGV[1])
+            if redis.call('PTTL', KEYS[1]) < 0 then
+                redis.call('PEXPIRE', KEYS[1], ARGV[2])
+            end
+            return value
+            ",
+        );
+        let mut conn = self.conn.clone();
+        let value: i64 = script
+            .key(self.key(key))
+            .arg(delta)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(value)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<String>> {
+        let mut conn = self.conn.clone();
+        Ok(conn.get(self.key(key)).await?)
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<()> {
+        let mut conn = self.conn.clone();
+        match ttl {
+            Some(ttl) => conn.pset_ex::<_, _, ()>(self.key(key), value, ttl.as_millis() as u64).await?,
+            None => conn.set::<_, _, ()>(self.key(key), value).await?,
+        }
+        Ok(())
+    }
+
+    async fn set_if_absent(&self, key: &str, value: &str, ttl: Duration) -> Result<bool> {
+        let mut conn = self.conn.clone();
+        let stored: Option<String> = redis::cmd("SET")
+            .arg(self.key(key))
+            .arg(value)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(stored.is_some())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let mut conn = self.conn.clone();
+        conn.del::<_, ()>(self.key(key)).await?;
+        Ok(())
+    }
+
+    fn backend(&self) -> &'static str {
+        "redis"
+    }
+}
+
+/// Open a managed (auto-reconnecting) Redis connection
+pub async fn redis_connection(url: &str) -> Result<ConnectionManager> {
+    let client = redis::Client::open(url).context("Invalid redis_url")?;
+    ConnectionManager::new(client)
+        .await
+        .context("Failed to connect to Redis")
+}
+
+/// Build the shared store: Redis in cluster mode, memory otherwise
+pub fn open_store(config: &Config, redis: Option<ConnectionManager>) -> Arc<dyn SharedStore> {
+    match redis {
+        Some(conn) => {
+            info!("Cluster mode enabled: sharing state through Redis");
+            Arc::new(RedisStore::new(conn, config.cluster_key_prefix.clone()))
+        }
+        None => Arc::new(MemoryStore::new()),
+    }
+}
+
+fn provider_health_key(provider_type: &str, uuid: &str) -> String {
+    format!("provider_health:{}:{}", provider_type, uuid)
+}
+
+/// Publish a provider's circuit state so other instances pick it up
+pub async fn publish_provider_health(
+    store: &dyn SharedStore,
+    provider_type: &str,
+    uuid: &str,
+    healthy: bool,
+) -> Result<()> {
+    let value = if healthy { "healthy" } else { "unhealthy" };
+    store.set(&provider_health_key(provider_type, uuid), value, None).await
+}
+
+/// Apply provider circuit state published by other instances to the local pool
+pub async fn sync_provider_health(store: &dyn SharedStore, pool_manager: &ProviderPoolManager) -> Result<()> {
+    for (provider_type, providers) in pool_manager.snapshot().await {
+        for provider in providers {
+            let shared = store.get(&provider_health_key(&provider_type, &provider.uuid)).await?;
+            match shared.as_deref() {
+                Some("healthy") if !provider.is_healthy => {
+                    pool_manager.mark_provider_healthy(&provider_type, &provider.uuid).await;
+                }
+                Some("unhealthy") if provider.is_healthy => {
+                    pool_manager.mark_provider_unhealthy(&provider_type, &provider.uuid).await;
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
@@ -2,7 +2,6 @@
  * Common types and utilities
  */
 
-use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -22,6 +21,16 @@ impl ModelProtocol {
             Self::Claude => "claude",
         }
     }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "gemini" => Some(Self::Gemini),
+            "openai" => Some(Self::OpenAI),
+            "claude" => Some(Self::Claude),
+            _ => None,
+        }
+    }
 }
 
 /// Model provider identifiers
@@ -45,6 +54,7 @@ impl ModelProvider {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "gemini-cli-oauth" => Some(Self::GeminiCliOAuth),
@@ -104,11 +114,33 @@ pub struct ApiRequest {
     pub top_p: Option<f32>,
     pub stream: Option<bool>,
     pub tools: Option<Vec<serde_json::Value>>,
+    pub top_k: Option<u32>,
+    pub stop_sequences: Option<Vec<String>>,
+    pub metadata: Option<serde_json::Value>,
+    pub tool_choice: Option<serde_json::Value>,
     
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+impl ModelInfo {
+    /// Bare model id (Gemini-style `models/` prefix removed)
+    pub fn model_id(&self) -> Option<&str> {
+        let id = self.id.as_deref().or(self.name.as_deref())?;
+        Some(id.strip_prefix("models/").unwrap_or(id))
+    }
+
+    /// OpenAI model object, as in `/v1/models`
+    pub fn to_openai(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "id": self.model_id()?,
+            "object": "model",
+            "created": self.created.unwrap_or(0),
+            "owned_by": self.owned_by.as_deref().unwrap_or("system"),
+        }))
+    }
+}
+
 /// Message structure (OpenAI/Claude format)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -248,6 +280,73 @@ pub struct ModelListResponse {
     pub models: Option<Vec<ModelInfo>>,
 }
 
+impl ModelListResponse {
+    /// All listed models, whichever field the provider filled
+    pub fn entries(&self) -> impl Iterator<Item = &ModelInfo> {
+        self.data.iter().chain(self.models.iter()).flatten()
+    }
+
+    /// List a model the proxy answers itself, in whichever field the provider filled
+    pub fn add_virtual(&mut self, id: &str, owned_by: &str) {
+        if self.entries().any(|m| m.model_id() == Some(id)) {
+            return;
+        }
+        let list = match (&mut self.data, &mut self.models) {
+            (None, Some(list)) => list,
+            (data, _) => data.get_or_insert_with(Vec::new),
+        };
+        list.push(ModelInfo {
+            id: Some(id.to_string()),
+            name: None,
+            object: Some("model".to_string()),
+            created: None,
+            owned_by: Some(owned_by.to_string()),
+            extra: HashMap::new(),
+        });
+    }
+
+    /// OpenAI `/v1/models` body
+    pub fn to_openai(&self) -> serde_json::Value {
+        let data: Vec<serde_json::Value> = self.entries().filter_map(ModelInfo::to_openai).collect();
+        serde_json::json!({ "object": "list", "data": data })
+    }
+
+    /// Anthropic `/v1/models` body
+    pub fn to_claude(&self) -> serde_json::Value {
+        let data: Vec<serde_json::Value> = self
+            .entries()
+            .filter_map(|m| {
+                let id = m.model_id()?;
+                let created_at = chrono::DateTime::from_timestamp(m.created.unwrap_or(0), 0).unwrap_or_default();
+                Some(serde_json::json!({
+                    "type": "model",
+                    "id": id,
+                    "display_name": id,
+                    "created_at": created_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                }))
+            })
+            .collect();
+        let first_id = data.first().map(|m| m["id"].clone());
+        let last_id = data.last().map(|m| m["id"].clone());
+        serde_json::json!({ "data": data, "has_more": false, "first_id": first_id, "last_id": last_id })
+    }
+
+    /// Gemini `/v1beta/models` body
+    pub fn to_gemini(&self) -> serde_json::Value {
+        let models: Vec<serde_json::Value> = self
+            .entries()
+            .filter_map(|m| {
+                let id = m.model_id()?;
+                Some(serde_json::json!({
+                    "name": format!("models/{}", id),
+                    "displayName": id,
+                }))
+            })
+            .collect();
+        serde_json::json!({ "models": models })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
     pub id: Option<String>,
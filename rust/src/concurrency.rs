@@ -0,0 +1,242 @@
+/*!
+ * Adaptive Concurrency
+ *
+ * AIMD limit on in-flight upstream calls, one limiter per provider. Each
+ * healthy completion probes the limit upward (about +1 per limit's worth of
+ * successes); a 429, timeout or latency spike well above the running baseline
+ * cuts it by `backoff_ratio`. Requests over the limit wait for a slot up to
+ * `queue_timeout_ms` and are then rejected, so each upstream settles at the
+ * throughput it can actually sustain.
+ */
+
+use crate::config::AdaptiveConcurrencyConfig;
+use crate::stream_errors::ErrorKind;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Weight of the newest sample in the latency baseline
+const BASELINE_ALPHA: f64 = 0.1;
+
+/// How an upstream call went, as far as the limiter is concerned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Success(Duration),
+    /// Rate limited, overloaded or timed out: back off
+    Overloaded,
+    /// Failed for a reason unrelated to load (bad request, auth, ...)
+    Ignored,
+}
+
+impl Outcome {
+    pub fn of<T>(result: &Result<T>, latency: Duration) -> Self {
+        match result {
+            Ok(_) => Self::Success(latency),
+            Err(e) if is_overload_error(e) => Self::Overloaded,
+            Err(_) => Self::Ignored,
+        }
+    }
+}
+
+/// Whether a failed call signals that the upstream is saturated: rate limited, overloaded or timed out
+pub fn is_overload_error(error: &anyhow::Error) -> bool {
+    !matches!(crate::stream_errors::classify(error), ErrorKind::Api)
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: f64,
+    in_flight: usize,
+    /// Moving average of successful call latency, in seconds
+    baseline: Option<f64>,
+    /// Bumped on every decrease; permits issued before it don't decrease again
+    generation: u64,
+}
+
+pub struct AdaptiveLimiter {
+    config: AdaptiveConcurrencyConfig,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(config: &AdaptiveConcurrencyConfig) -> Self {
+        let limit = config.initial_limit.clamp(config.min_limit.max(1), config.max_limit.max(1)) as f64;
+        Self {
+            config: config.clone(),
+            state: Mutex::new(LimiterState {
+                limit,
+                in_flight: 0,
+                baseline: None,
+                generation: 0,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Current limit, rounded down to whole calls
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    /// Take a slot if one is free right now
+    pub fn try_acquire(self: &Arc<Self>) -> Option<Permit> {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight >= state.limit as usize {
+            return None;
+        }
+        state.in_flight += 1;
+        Some(Permit {
+            limiter: self.clone(),
+            started: Instant::now(),
+            generation: state.generation,
+            recorded: false,
+        })
+    }
+
+    /// Wait for a slot, giving up after `queue_timeout_ms`
+    pub async fn acquire(self: &Arc<Self>) -> Result<Permit> {
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(self.config.queue_timeout_ms);
+        loop {
+            // Register before checking so a release in between is not missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            if let Some(permit) = self.try_acquire() {
+                return Ok(permit);
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                anyhow::bail!("Upstream concurrency limit ({}) reached", self.limit());
+            }
+        }
+    }
+
+    fn record(&self, outcome: Outcome, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        let (min, max) = (self.config.min_limit.max(1) as f64, self.config.max_limit.max(1) as f64);
+
+        let overloaded = match outcome {
+            Outcome::Success(latency) => {
+                let latency = latency.as_secs_f64();
+                let spike = state
+                    .baseline
+                    .is_some_and(|baseline| latency > baseline * self.config.latency_tolerance);
+                state.baseline = Some(match state.baseline {
+                    Some(baseline) => baseline + BASELINE_ALPHA * (latency - baseline),
+                    None => latency,
+                });
+                spike
+            }
+            Outcome::Overloaded => true,
+            Outcome::Ignored => return,
+        };
+
+        if overloaded {
+            // One cut per congestion event, not one per request caught in it
+            if generation >= state.generation {
+                state.limit = (state.limit * self.config.backoff_ratio).max(min);
+                state.generation += 1;
+            }
+        } else if state.in_flight as f64 >= state.limit / 2.0 {
+            // Only probe upward while the current limit is actually being used
+            let grown = (state.limit + 1.0 / state.limit).min(max);
+            if grown as usize > state.limit as usize {
+                self.released.notify_waiters();
+            }
+            state.limit = grown;
+        }
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().in_flight -= 1;
+        self.released.notify_one();
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        json!({
+            "limit": state.limit as usize,
+            "in_flight": state.in_flight,
+            "baseline_latency_ms": state.baseline.map(|b| (b * 1000.0).round() as u64),
+        })
+    }
+}
+
+/// A slot held for one upstream call; released on drop
+pub struct Permit {
+    limiter: Arc<AdaptiveLimiter>,
+    started: Instant,
+    generation: u64,
+    recorded: bool,
+}
+
+impl Permit {
+    /// Time since the slot was granted
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Feed the call's outcome back into the limit; only the first call counts
+    pub fn record(&mut self, outcome: Outcome) {
+        if !self.recorded {
+            self.recorded = true;
+            self.limiter.record(outcome, self.generation);
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/// Keep `guard` (a permit) alive until the stream is finished or dropped
+pub fn hold<S, G>(stream: S, guard: G) -> impl Stream<Item = S::Item> + Send
+where
+    S: Stream + Send,
+    G: Send,
+{
+    stream.map(move |item| {
+        let _held = &guard;
+        item
+    })
+}
+
+/// Per-provider limiters, created on first use
+pub struct ConcurrencyLimits {
+    config: AdaptiveConcurrencyConfig,
+    limiters: Mutex<HashMap<String, Arc<AdaptiveLimiter>>>,
+}
+
+impl ConcurrencyLimits {
+    pub fn new(config: &AdaptiveConcurrencyConfig) -> Self {
+        Self {
+            config: config.clone(),
+            limiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limiter(&self, provider: &str) -> Arc<AdaptiveLimiter> {
+        self.limiters
+            .lock()
+            .unwrap()
+            .entry(provider.to_string())
+            .or_insert_with(|| Arc::new(AdaptiveLimiter::new(&self.config)))
+            .clone()
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let limiters = self.limiters.lock().unwrap();
+        Value::Object(limiters.iter().map(|(provider, limiter)| (provider.clone(), limiter.snapshot())).collect())
+    }
+}
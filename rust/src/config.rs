@@ -4,9 +4,10 @@
  * Handles loading and managing server configuration from files and command-line arguments.
  */
 
+use crate::config_validation::{self, ConfigErrors, Diagnostic, Severity};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 
@@ -41,6 +42,11 @@ pub struct Config {
     #[serde(default = "default_port")]
     pub port: u16,
 
+    /// Further ports that each speak one native API, e.g. Claude on 3001 for the Anthropic SDK;
+    /// `port` keeps serving every API plus the admin endpoints
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+
     /// Required API key for authentication
     #[serde(default = "default_api_key")]
     pub required_api_key: String,
@@ -53,17 +59,49 @@ pub struct Config {
     #[serde(default)]
     pub default_model_providers: Vec<String>,
 
+    /// Requests for matching models go to the named provider instead of `model_provider`
+    /// (see `provider_registry` module); changeable at runtime through `/admin/routing`
+    #[serde(default)]
+    pub model_routes: Vec<ModelRoute>,
+    /// Provider-selection policies consulted in order before `model_routes`; the first
+    /// to pick a provider wins (see `router` module)
+    #[serde(default)]
+    pub routers: Vec<RouterConfig>,
+    /// Names clients may use in place of an upstream model, e.g. `{"fast": "gpt-4o-mini"}`,
+    /// or a list of models tried in order, each with its own provider and parameters
+    #[serde(default)]
+    pub model_aliases: HashMap<String, ModelAlias>,
+    /// Models and aliases each tenant may call (`gpt-4o*` matches by prefix), for client keys
+    /// and JWTs of the tenant that carry no allowlist of their own; other tenants may call any
+    #[serde(default)]
+    pub tenant_models: HashMap<String, Vec<String>>,
+
     /// OpenAI configuration
     #[serde(default)]
     pub openai_api_key: Option<String>,
     #[serde(default)]
     pub openai_base_url: Option<String>,
+    /// Regional endpoints in preference order, failed over between (see `regions` module);
+    /// `openai_base_url`, if also set, is tried first
+    #[serde(default)]
+    pub openai_base_urls: Vec<String>,
+    /// Sent as `OpenAI-Organization` / `OpenAI-Project` unless the client supplies its own
+    #[serde(default)]
+    pub openai_organization: Option<String>,
+    #[serde(default)]
+    pub openai_project: Option<String>,
 
     /// Claude configuration
     #[serde(default)]
     pub claude_api_key: Option<String>,
     #[serde(default)]
     pub claude_base_url: Option<String>,
+    /// Regional endpoints in preference order, like `openai_base_urls`
+    #[serde(default)]
+    pub claude_base_urls: Vec<String>,
+    /// `anthropic-beta` flags always sent to Claude, merged with client-requested ones
+    #[serde(default)]
+    pub claude_beta_flags: Vec<String>,
 
     /// Gemini OAuth configuration
     #[serde(default)]
@@ -97,23 +135,1206 @@ pub struct Config {
     #[serde(default = "default_prompt_log_base_name")]
     pub prompt_log_base_name: String,
 
-    /// Retry configuration
-    #[serde(default = "default_max_retries")]
-    pub request_max_retries: u32,
-    #[serde(default = "default_base_delay")]
-    pub request_base_delay: u64,
+    /// Retry configuration
+    #[serde(default = "default_max_retries")]
+    pub request_max_retries: u32,
+    #[serde(default = "default_base_delay")]
+    pub request_base_delay: u64,
+
+    /// Cron configuration
+    #[serde(default = "default_cron_near_minutes")]
+    pub cron_near_minutes: u64,
+    #[serde(default = "default_cron_refresh_token")]
+    pub cron_refresh_token: bool,
+
+    /// Provider pools configuration
+    #[serde(default)]
+    pub provider_pools_file_path: Option<PathBuf>,
+    #[serde(default)]
+    pub provider_pools: HashMap<String, Vec<ProviderConfig>>,
+    /// Scoring and quarantine of the pool keys
+    #[serde(default)]
+    pub key_health: KeyHealthConfig,
+
+    /// Admin API key; without it (or `oidc`) the admin API is disabled
+    #[serde(default)]
+    pub admin_api_key: Option<String>,
+    #[serde(default = "default_audit_log_file")]
+    pub audit_log_file_path: Option<PathBuf>,
+
+    /// Client key store managed through `/admin/keys`
+    #[serde(default = "default_client_keys_file")]
+    pub client_keys_file_path: Option<PathBuf>,
+
+    /// How often `vault://` / `aws-sm://` provider credential references are re-fetched (0 disables);
+    /// the server's own secrets are resolved only at startup
+    #[serde(default = "default_secret_refresh_interval")]
+    pub secret_refresh_interval_secs: u64,
+
+    /// Cluster mode: instances share rate limits, quotas and provider health through Redis
+    #[serde(default)]
+    pub redis_url: Option<String>,
+    #[serde(default = "default_cluster_key_prefix")]
+    pub cluster_key_prefix: String,
+
+    /// Requests allowed per client within `rate_limit_window_secs` (0 disables rate limiting)
+    #[serde(default)]
+    pub rate_limit_requests: u32,
+    #[serde(default = "default_rate_limit_window")]
+    pub rate_limit_window_secs: u64,
+    /// `memory` (per instance) or `redis` (shared across the cluster)
+    #[serde(default = "default_rate_limit_backend")]
+    pub rate_limit_backend: String,
+
+    /// Compress responses (gzip/brotli, per `Accept-Encoding`) at or above `compression_min_size` bytes
+    #[serde(default = "default_response_compression")]
+    pub response_compression: bool,
+    #[serde(default = "default_compression_min_size")]
+    pub compression_min_size: u16,
+    /// Accept request bodies sent with `Content-Encoding: gzip` or `br`, decompressed before
+    /// parsing (see `request_decompression` module)
+    #[serde(default = "default_request_decompression")]
+    pub request_decompression: bool,
+    /// Largest request body, as sent or, when compressed, once decompressed; larger ones are
+    /// refused with 413
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+
+    /// Accept client JWTs in addition to static keys
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+
+    /// OIDC login for the admin API (in addition to `admin_api_key`)
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+
+    /// Require HMAC-signed client requests
+    #[serde(default)]
+    pub request_signing: Option<RequestSigningConfig>,
+
+    /// Inbound headers forwarded to the upstream provider (case-insensitive, `prefix-*` allowed)
+    #[serde(default)]
+    pub forward_headers: Vec<String>,
+
+    /// Replace end-user ids with a salted hash before they reach upstreams or usage records
+    #[serde(default)]
+    pub hash_end_user_ids: bool,
+    #[serde(default)]
+    pub end_user_hash_salt: Option<String>,
+
+    /// Per-model handling of `<think>` style reasoning blocks in responses
+    #[serde(default)]
+    pub reasoning_filters: Vec<ReasoningFilterRule>,
+
+    /// Shrinking of the prompt before dispatch (see `prompt_compression` module)
+    #[serde(default)]
+    pub prompt_compression: Option<PromptCompressionConfig>,
+
+    /// Shrinking of tool descriptions and schemas before dispatch (see `tool_minification` module)
+    #[serde(default)]
+    pub tool_minification: Option<ToolMinificationConfig>,
+
+    /// Rewrites applied to assistant text before it is returned
+    #[serde(default)]
+    pub post_processing: Option<PostProcessConfig>,
+
+    /// Times a stream that dies partway is resumed with the text so far as prefill (0 disables)
+    #[serde(default = "default_stream_resume_attempts")]
+    pub stream_resume_attempts: u32,
+
+    /// Streaming requests answered from a buffered upstream call, replayed as a stream
+    #[serde(default)]
+    pub simulated_streaming: SimulatedStreamingConfig,
+
+    /// Non-streaming requests answered by consuming an upstream stream
+    #[serde(default)]
+    pub stream_aggregation: StreamAggregationConfig,
+
+    /// Retry empty answers, optionally on another provider (see `empty_response` module)
+    #[serde(default)]
+    pub empty_response_retry: Option<EmptyResponseRetryConfig>,
+
+    /// Continue responses cut off by the output token limit (see `continuation` module)
+    #[serde(default)]
+    pub auto_continuation: Option<AutoContinuationConfig>,
+
+    /// Connection pool and protocol settings of the HTTP client shared by all providers
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+
+    /// Per-request upstream timeouts overriding `http_client.request_timeout_secs`
+    /// (see `request_timeout` module)
+    #[serde(default)]
+    pub request_timeouts: RequestTimeoutConfig,
+
+    /// Global cap on upstream retries, on top of `request_max_retries`
+    #[serde(default)]
+    pub retry_budget: RetryBudgetConfig,
+
+    /// What `/readyz` checks besides the registered providers
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
+
+    /// AIMD limit on in-flight upstream calls per provider
+    #[serde(default)]
+    pub adaptive_concurrency: AdaptiveConcurrencyConfig,
+
+    /// Pace upstream calls to stay under the rate limits upstreams publish
+    #[serde(default)]
+    pub rate_limit_pacing: RateLimitPacingConfig,
+
+    /// Dedicated reranking backend for `/v1/rerank`; without one, reranking is emulated with the chat provider
+    #[serde(default)]
+    pub rerank: Option<RerankConfig>,
+
+    /// Agent mode: calls to these tools are executed by the proxy (see `tool_loop` module)
+    #[serde(default)]
+    pub tool_loop: Option<ToolLoopConfig>,
+
+    /// Search backend behind OpenAI `web_search` / Gemini `googleSearch` tools (see `web_search` module)
+    #[serde(default)]
+    pub web_search: Option<WebSearchConfig>,
+
+    /// Virtual models answered by several models in parallel (see `ensemble` module)
+    #[serde(default)]
+    pub ensembles: Vec<EnsembleConfig>,
+
+    /// Virtual models trying cheap models first and escalating on poor answers (see `cascade` module)
+    #[serde(default)]
+    pub cascades: Vec<CascadeConfig>,
+
+    /// Per-model limits and capabilities, on top of the built-in table (see `model_registry` module)
+    #[serde(default)]
+    pub model_registry: ModelRegistryConfig,
+
+    /// Limits on what one client request may carry, whatever the model; client keys may
+    /// set their own (see `request_limits` module)
+    #[serde(default)]
+    pub request_limits: RequestLimits,
+
+    /// Redaction and sampling of logs (see `log_redaction` module)
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    /// Server-side history for requests carrying `x-conversation-id` (see `conversations` module)
+    #[serde(default)]
+    pub conversations: Option<ConversationStoreConfig>,
+
+    /// Reuse responses to identical chat requests, replayed as a stream to streaming ones (see `response_cache` module)
+    #[serde(default)]
+    pub response_cache: Option<ResponseCacheConfig>,
+
+    /// Keep upstream model lists and model details for a while instead of fetching them
+    /// for every request (see `model_list_cache` module)
+    #[serde(default)]
+    pub model_list_cache: Option<ModelListCacheConfig>,
+
+    /// Stop a client repeating the same request in a loop (see `duplicate_requests` module)
+    #[serde(default)]
+    pub duplicate_requests: Option<DuplicateRequestConfig>,
+
+    /// Answer a retried `Idempotency-Key` with the recorded response (see `idempotency` module)
+    #[serde(default)]
+    pub idempotency: Option<IdempotencyConfig>,
+
+    /// Running totals of tokens, cost and latency per conversation and tag (see `usage_rollup` module)
+    #[serde(default)]
+    pub usage_rollup: Option<UsageRollupConfig>,
+
+    /// When a regional endpoint is taken out of rotation and how it is checked
+    #[serde(default)]
+    pub region_failover: RegionFailoverConfig,
+
+    /// Webhook notifications for outages, error spikes and quota thresholds (see `alerts` module)
+    #[serde(default)]
+    pub alerts: Option<AlertsConfig>,
+
+    /// Anonymized records of opted-in clients' exchanges for dataset building (see `dataset` module)
+    #[serde(default)]
+    pub dataset: Option<DatasetConfig>,
+
+    /// Recent requests kept in memory for `/admin/requests/:id` (see `transcripts` module)
+    #[serde(default)]
+    pub transcripts: Option<TranscriptConfig>,
+}
+
+/// A listener serving a single protocol's routes (and `/health`) on its own port
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListenerConfig {
+    pub port: u16,
+    /// Defaults to `host`
+    #[serde(default)]
+    pub host: Option<String>,
+    /// `openai`, `claude` or `gemini`
+    pub protocol: String,
+}
+
+/// Routing rule: models matching `model` (exact, or a prefix ending in `*`) are sent to `provider`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct ModelRoute {
+    pub model: String,
+    pub provider: String,
+}
+
+/// What a model alias stands for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum ModelAlias {
+    /// The model name sent upstream
+    Model(String),
+    /// Models tried in order until one answers (see `aliases` module)
+    Targets(Vec<AliasTarget>),
+}
+
+impl ModelAlias {
+    /// The targets in order; a plain alias has one
+    pub fn targets(&self) -> Vec<AliasTarget> {
+        match self {
+            Self::Model(model) => vec![AliasTarget::new(model)],
+            Self::Targets(targets) => targets.clone(),
+        }
+    }
+}
+
+impl From<&str> for ModelAlias {
+    fn from(model: &str) -> Self {
+        Self::Model(model.to_string())
+    }
+}
+
+/// One model an alias may be served by
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct AliasTarget {
+    pub model: String,
+    /// Provider to send it to, instead of the one the routes pick
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Request fields set for this target, e.g. `{"temperature": 0.3}`
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    #[cfg_attr(feature = "server", schema(value_type = Object))]
+    pub params: serde_json::Map<String, serde_json::Value>,
+}
+
+impl AliasTarget {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            provider: None,
+            params: serde_json::Map::new(),
+        }
+    }
+}
+
+/// One provider-selection policy; `model` patterns match like `ModelRoute::model`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RouterConfig {
+    /// Fixed model → provider rules, like `model_routes`
+    Static { routes: Vec<ModelRoute> },
+    /// Spread matching requests over providers in proportion to their weights
+    Weighted { model: String, weights: BTreeMap<String, u32> },
+    /// Send requests carrying images to `vision` and requests declaring tools to `tools`
+    Capability {
+        #[serde(default = "default_router_model")]
+        model: String,
+        #[serde(default)]
+        vision: Option<String>,
+        #[serde(default)]
+        tools: Option<String>,
+    },
+    /// Send matching requests to the provider with the lowest price (per million tokens)
+    Cost { model: String, prices: BTreeMap<String, f64> },
+}
+
+fn default_router_model() -> String {
+    "*".to_string()
+}
+
+/// Retries may not exceed `ratio` of the requests in the last `window_secs`,
+/// plus `min_retries_per_sec` so a quiet instance can still retry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryBudgetConfig {
+    #[serde(default = "default_retry_budget_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_retry_budget_ratio")]
+    pub ratio: f64,
+    #[serde(default = "default_retry_budget_min_per_sec")]
+    pub min_retries_per_sec: f64,
+    #[serde(default = "default_retry_budget_window")]
+    pub window_secs: u64,
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_retry_budget_enabled(),
+            ratio: default_retry_budget_ratio(),
+            min_retries_per_sec: default_retry_budget_min_per_sec(),
+            window_secs: default_retry_budget_window(),
+        }
+    }
+}
+
+/// Readiness probing; with `probe_upstreams` the instance is ready only while at least
+/// one provider's upstream is reachable, checked at most every `probe_interval_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessConfig {
+    /// Load tokenizers and fetch model lists at startup; not ready until done
+    #[serde(default = "default_warm_up")]
+    pub warm_up: bool,
+    #[serde(default)]
+    pub probe_upstreams: bool,
+    #[serde(default = "default_probe_interval")]
+    pub probe_interval_secs: u64,
+    #[serde(default = "default_probe_timeout")]
+    pub probe_timeout_secs: u64,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            warm_up: default_warm_up(),
+            probe_upstreams: false,
+            probe_interval_secs: default_probe_interval(),
+            probe_timeout_secs: default_probe_timeout(),
+        }
+    }
+}
+
+/// Adaptive upstream concurrency (see `concurrency` module); the limit starts
+/// at `initial_limit` and moves between `min_limit` and `max_limit`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdaptiveConcurrencyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_concurrency_initial_limit")]
+    pub initial_limit: usize,
+    #[serde(default = "default_concurrency_min_limit")]
+    pub min_limit: usize,
+    #[serde(default = "default_concurrency_max_limit")]
+    pub max_limit: usize,
+    /// Multiplier applied to the limit on a 429, timeout or latency spike
+    #[serde(default = "default_concurrency_backoff_ratio")]
+    pub backoff_ratio: f64,
+    /// A call slower than this multiple of the latency baseline counts as a spike
+    #[serde(default = "default_concurrency_latency_tolerance")]
+    pub latency_tolerance: f64,
+    /// How long a request waits for a free slot before being rejected
+    #[serde(default = "default_concurrency_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+}
+
+impl Default for AdaptiveConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            initial_limit: default_concurrency_initial_limit(),
+            min_limit: default_concurrency_min_limit(),
+            max_limit: default_concurrency_max_limit(),
+            backoff_ratio: default_concurrency_backoff_ratio(),
+            latency_tolerance: default_concurrency_latency_tolerance(),
+            queue_timeout_ms: default_concurrency_queue_timeout_ms(),
+        }
+    }
+}
+
+/// Pacing against upstream rate-limit headers (see `pacing` module)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitPacingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Share of each published limit left unused, as a margin for calls already in flight
+    #[serde(default = "default_pacing_reserve_ratio")]
+    pub reserve_ratio: f64,
+    /// Longest a call waits for a limit to reset; beyond it the client gets a 429 at once
+    #[serde(default = "default_pacing_max_wait_ms")]
+    pub max_wait_ms: u64,
+}
+
+impl Default for RateLimitPacingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reserve_ratio: default_pacing_reserve_ratio(),
+            max_wait_ms: default_pacing_max_wait_ms(),
+        }
+    }
+}
+
+/// Cohere/Jina-compatible reranking backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankConfig {
+    /// Full URL of the backend's rerank endpoint, e.g. `https://api.jina.ai/v1/rerank`
+    pub url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Model sent to the backend, replacing the one in the request
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Tools the proxy runs itself, looping until the model gives a final answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolLoopConfig {
+    pub tools: Vec<HttpToolConfig>,
+    /// Model round trips spent on tool calls before an answer is forced
+    #[serde(default = "default_tool_loop_max_iterations")]
+    pub max_iterations: u32,
+    #[serde(default = "default_tool_timeout")]
+    pub timeout_secs: u64,
+}
+
+/// A tool backed by an HTTP callback, which receives `{"name", "arguments"}`
+/// and whose response body becomes the tool result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpToolConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// JSON schema of the arguments
+    #[serde(default = "default_tool_parameters")]
+    pub parameters: serde_json::Value,
+    pub url: String,
+    /// Extra headers sent to the callback, e.g. its credentials
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Tavily-compatible search API: POST `{"query", "max_results"}` returning `{"results": [{"title", "url", "content"}]}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchConfig {
+    pub url: String,
+    /// Sent as a bearer token
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_web_search_max_results")]
+    pub max_results: usize,
+    /// Searches the model may run before it has to answer
+    #[serde(default = "default_web_search_max_iterations")]
+    pub max_iterations: u32,
+}
+
+/// A virtual model: the request goes to every member and the best answer is returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleConfig {
+    /// Name clients request; listed by the models endpoints
+    pub model: String,
+    /// Models asked in parallel, each routed like a request for it
+    pub members: Vec<String>,
+    /// Model picking the best answer; without one, the longest complete answer wins
+    #[serde(default)]
+    pub judge: Option<String>,
+}
+
+/// A virtual model: steps are tried in order until an answer passes the checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CascadeConfig {
+    /// Name clients request; listed by the models endpoints
+    pub model: String,
+    /// Models tried cheapest first; the last step's answer is always accepted
+    pub steps: Vec<String>,
+    /// Shorter answers escalate
+    #[serde(default)]
+    pub min_chars: usize,
+    /// Answers that read as refusals ("I can't help with that") escalate
+    #[serde(default = "default_cascade_detect_refusals")]
+    pub detect_refusals: bool,
+    /// Phrases marking a refusal, on top of the built-in ones
+    #[serde(default)]
+    pub refusal_phrases: Vec<String>,
+    /// Model scoring each answer from 1 to 10; an unreadable score escalates
+    #[serde(default)]
+    pub judge: Option<String>,
+    #[serde(default = "default_cascade_min_score")]
+    pub min_score: usize,
+}
+
+/// Model registry entries and how requests are checked against them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelRegistryConfig {
+    /// Added or corrected entries; they take precedence over built-in entries, field by field
+    #[serde(default)]
+    pub models: Vec<ModelSpec>,
+    /// Reject requests exceeding the target model's limits before dispatch
+    #[serde(default)]
+    pub enforce_limits: bool,
+    /// Clamp `max_tokens` to the model's limit instead of rejecting
+    #[serde(default)]
+    pub lenient: bool,
+    /// Send requests needing a capability the model lacks (images, tools) to a capable fallback model
+    #[serde(default)]
+    pub capability_routing: bool,
+    /// Models tried in order when the requested one lacks a capability
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+}
+
+/// Limits and capabilities of the models whose name starts with `model`; unset fields are unknown
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelSpec {
+    pub model: String,
+    #[serde(default)]
+    pub max_output_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+    #[serde(default)]
+    pub max_images: Option<usize>,
+    /// Largest inline (base64) image, decoded
+    #[serde(default)]
+    pub max_image_bytes: Option<usize>,
+    #[serde(default)]
+    pub max_tools: Option<usize>,
+    #[serde(default)]
+    pub vision: Option<bool>,
+    #[serde(default)]
+    pub tools: Option<bool>,
+    /// USD per million input tokens, for usage rollups
+    #[serde(default)]
+    pub input_price: Option<f64>,
+    /// USD per million output tokens
+    #[serde(default)]
+    pub output_price: Option<f64>,
+}
+
+/// Largest request a client may send; unset fields are not limited
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct RequestLimits {
+    /// Messages (Gemini `contents`) in the conversation
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+    /// Images, inline or by URL
+    #[serde(default)]
+    pub max_images: Option<usize>,
+    /// Base64 payloads (images, audio, files) together, as sent
+    #[serde(default)]
+    pub max_base64_bytes: Option<usize>,
+}
+
+impl RequestLimits {
+    /// These limits, with `fallback`'s for the fields left unset
+    pub fn or(&self, fallback: &RequestLimits) -> RequestLimits {
+        RequestLimits {
+            max_messages: self.max_messages.or(fallback.max_messages),
+            max_images: self.max_images.or(fallback.max_images),
+            max_base64_bytes: self.max_base64_bytes.or(fallback.max_base64_bytes),
+        }
+    }
+}
+
+/// How message content appears in the prompt log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRedaction {
+    /// Logged as is
+    #[default]
+    Off,
+    /// Replaced by a SHA-256 prefix and its length, so identical prompts can still be matched up
+    Hash,
+    /// Cut to `truncate_chars` characters
+    Truncate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub content: ContentRedaction,
+    #[serde(default = "default_log_truncate_chars")]
+    pub truncate_chars: usize,
+    /// Mask API keys, bearer tokens and similar credentials in every log line
+    #[serde(default = "default_scrub_secrets")]
+    pub scrub_secrets: bool,
+    /// Share of requests whose info/debug logs are kept, by path prefix (longest
+    /// match wins, e.g. `{"/v1/chat": 0.1}`); unlisted routes are always logged
+    #[serde(default)]
+    pub sample_rates: HashMap<String, f64>,
+}
+
+/// Stored conversations live in the shared state store: in memory, or in Redis in cluster mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationStoreConfig {
+    /// A conversation is forgotten this long after its last turn
+    #[serde(default = "default_conversation_ttl")]
+    pub ttl_secs: u64,
+    /// Older turns beyond this many messages are dropped (system messages are kept)
+    #[serde(default = "default_conversation_max_messages")]
+    pub max_messages: usize,
+}
+
+/// Cached responses live in the shared state store, like conversations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    #[serde(default = "default_response_cache_ttl")]
+    pub ttl_secs: u64,
+    /// Sampling parameters that distinguish cache entries; those left out are
+    /// ignored, so e.g. requests differing only in `temperature` share an entry
+    #[serde(default = "default_response_cache_vary")]
+    pub vary: Vec<String>,
+    /// Further request fields left out of the cache key
+    #[serde(default)]
+    pub ignore_fields: Vec<String>,
+    /// How a cached response is streamed back to streaming requests
+    #[serde(default)]
+    pub replay_pacing: StreamPacingConfig,
+    /// How long past its TTL an entry may still stand in for a failed upstream call
+    /// (sent with `X-Cache: STALE`); 0 disables stale serving
+    #[serde(default)]
+    pub stale_if_error_secs: u64,
+    /// Per-client overrides of `stale_if_error_secs`, by client ID
+    /// (`static`, `key:<id>`, `tenant:<name>` or `jwt:<subject>`)
+    #[serde(default)]
+    pub stale_if_error_clients: HashMap<String, u64>,
+}
+
+/// How long fetched model lists and model details are reused
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelListCacheConfig {
+    /// For a provider's model list (`/v1/models`)
+    #[serde(default = "default_models_ttl")]
+    pub models_ttl_secs: u64,
+    /// For one model's details (`/v1/models/{model}`), which change even more rarely
+    #[serde(default = "default_model_metadata_ttl")]
+    pub metadata_ttl_secs: u64,
+}
+
+impl Default for ModelListCacheConfig {
+    fn default() -> Self {
+        Self {
+            models_ttl_secs: default_models_ttl(),
+            metadata_ttl_secs: default_model_metadata_ttl(),
+        }
+    }
+}
+
+/// A client may send the same request `max_repeats` times per `window_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateRequestConfig {
+    #[serde(default = "default_duplicate_max_repeats")]
+    pub max_repeats: u64,
+    #[serde(default = "default_duplicate_window")]
+    pub window_secs: u64,
+}
+
+impl Default for DuplicateRequestConfig {
+    fn default() -> Self {
+        Self {
+            max_repeats: default_duplicate_max_repeats(),
+            window_secs: default_duplicate_window(),
+        }
+    }
+}
+
+/// Answers to requests with an `Idempotency-Key` are kept for `ttl_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotencyConfig {
+    #[serde(default = "default_idempotency_ttl")]
+    pub ttl_secs: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_idempotency_ttl(),
+        }
+    }
+}
+
+/// Totals are kept for `ttl_secs` from a conversation's or tag's first request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRollupConfig {
+    #[serde(default = "default_usage_rollup_ttl")]
+    pub ttl_secs: u64,
+}
+
+impl Default for UsageRollupConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_usage_rollup_ttl(),
+        }
+    }
+}
+
+/// An endpoint failing `failure_threshold` times in a row is skipped for
+/// `cooldown_secs`; endpoints are probed every `health_check_interval_secs` (0 disables probing)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionFailoverConfig {
+    #[serde(default = "default_region_failure_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "default_region_cooldown")]
+    pub cooldown_secs: u64,
+    #[serde(default = "default_region_health_check_interval")]
+    pub health_check_interval_secs: u64,
+}
+
+impl Default for RegionFailoverConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_region_failure_threshold(),
+            cooldown_secs: default_region_cooldown(),
+            health_check_interval_secs: default_region_health_check_interval(),
+        }
+    }
+}
+
+/// Where an alert webhook posts to and in which payload shape
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertWebhook {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// `{"text": ...}` for a Slack incoming webhook
+    Slack,
+    /// `{"content": ...}` for a Discord webhook
+    Discord,
+    /// The alert as a JSON object
+    #[default]
+    Generic,
+}
+
+/// Repeats of an alert (same kind and subject) are held back for `dedup_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub webhooks: Vec<AlertWebhook>,
+    #[serde(default = "default_alert_dedup")]
+    pub dedup_secs: u64,
+    /// Share of failed upstream calls in `error_rate_window_secs` that counts as a spike
+    #[serde(default = "default_alert_error_rate")]
+    pub error_rate_threshold: f64,
+    #[serde(default = "default_alert_error_window")]
+    pub error_rate_window_secs: u64,
+    /// Calls needed in the window before the error rate is judged
+    #[serde(default = "default_alert_error_min_requests")]
+    pub error_rate_min_requests: u64,
+    /// Shares of a client's daily quota that raise an alert when crossed
+    #[serde(default = "default_alert_budget_thresholds")]
+    pub budget_thresholds: Vec<f64>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            webhooks: Vec::new(),
+            dedup_secs: default_alert_dedup(),
+            error_rate_threshold: default_alert_error_rate(),
+            error_rate_window_secs: default_alert_error_window(),
+            error_rate_min_requests: default_alert_error_min_requests(),
+            budget_thresholds: default_alert_budget_thresholds(),
+        }
+    }
+}
+
+/// Exchanges of the listed clients are written as JSONL records to files in
+/// `directory`, a new file every `max_file_bytes`, or with `s3` set, uploaded as objects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetConfig {
+    /// Client IDs that opted in (`static`, `key:<id>`, `tenant:<name>`, `jwt:<subject>`); `*` for every client
+    #[serde(default)]
+    pub clients: Vec<String>,
+    #[serde(default = "default_dataset_directory")]
+    pub directory: PathBuf,
+    #[serde(default = "default_dataset_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Records buffered for S3 are uploaded at least this often
+    #[serde(default = "default_dataset_flush_interval")]
+    pub flush_interval_secs: u64,
+    #[serde(default)]
+    pub s3: Option<DatasetS3Config>,
+}
+
+/// An S3-compatible bucket (AWS, MinIO, R2, ...), addressed path-style
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetS3Config {
+    /// e.g. `https://s3.us-east-1.amazonaws.com`
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_dataset_s3_region")]
+    pub region: String,
+    /// Prepended to object keys, e.g. `datasets/`
+    #[serde(default)]
+    pub prefix: String,
+    /// Default to `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY`
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+}
+
+/// How many of the latest requests to keep transcripts of
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptConfig {
+    #[serde(default = "default_transcript_capacity")]
+    pub capacity: usize,
+}
+
+/// Backends that only answer in one piece. Streaming requests for them (and those using
+/// built-in web search) are sent as buffered calls and the answer is streamed at `pacing`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimulatedStreamingConfig {
+    /// Providers whose upstream cannot stream, e.g. an OpenAI-compatible server without SSE
+    #[serde(default)]
+    pub providers: Vec<String>,
+    #[serde(default)]
+    pub pacing: StreamPacingConfig,
+}
+
+/// Backends that only answer with a stream. Non-streaming calls to them open a stream
+/// and return the assembled response
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamAggregationConfig {
+    /// Providers whose upstream cannot answer in one piece, e.g. chat-app OAuth endpoints
+    #[serde(default)]
+    pub providers: Vec<String>,
+}
+
+/// Retrying answers without any content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmptyResponseRetryConfig {
+    /// Providers whose answers are checked; empty means all
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Retries on the same provider
+    #[serde(default = "default_empty_response_retries")]
+    pub retries: u32,
+    /// Provider tried once the retries are used up, with the same model name
+    #[serde(default)]
+    pub fallback_provider: Option<String>,
+}
+
+/// Re-prompting a model whose answer hit the output token limit, stitching the parts together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoContinuationConfig {
+    /// Providers whose responses are continued; empty means all
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Follow-up requests per response at most
+    #[serde(default = "default_continuation_max_continuations")]
+    pub max_continuations: u32,
+    /// No further continuation once the stitched answer has this many tokens
+    #[serde(default)]
+    pub max_output_tokens: Option<usize>,
+    /// User message asking to go on, for backends that cannot continue an assistant prefill
+    #[serde(default = "default_continuation_prompt")]
+    pub prompt: String,
+}
+
+/// Pacing of a stream synthesized from a complete response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamPacingConfig {
+    /// Characters of text per chunk
+    #[serde(default = "default_pacing_chunk_chars")]
+    pub chunk_chars: usize,
+    /// Delay between chunks
+    #[serde(default = "default_pacing_interval_ms")]
+    pub interval_ms: u64,
+}
+
+impl Default for StreamPacingConfig {
+    fn default() -> Self {
+        Self {
+            chunk_chars: default_pacing_chunk_chars(),
+            interval_ms: default_pacing_interval_ms(),
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            content: ContentRedaction::default(),
+            truncate_chars: default_log_truncate_chars(),
+            scrub_secrets: default_scrub_secrets(),
+            sample_rates: HashMap::new(),
+        }
+    }
+}
+
+/// Upstream HTTP client tuning (see `http_client` module)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpClientConfig {
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    #[serde(default = "default_pool_idle_timeout")]
+    pub pool_idle_timeout_secs: u64,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout_secs: u64,
+    /// TCP keep-alive probe interval (0 disables)
+    #[serde(default = "default_tcp_keepalive")]
+    pub tcp_keepalive_secs: u64,
+    /// Speak HTTP/2 without ALPN negotiation (only for upstreams known to support it)
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+    /// HTTP/2 PING interval keeping idle connections alive
+    #[serde(default)]
+    pub http2_keep_alive_interval_secs: Option<u64>,
+    /// Open a connection to each upstream at startup so the first request skips the TLS handshake
+    #[serde(default)]
+    pub prewarm: bool,
+    /// `User-Agent` sent to every upstream
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    /// Extra headers sent to every upstream, e.g. an `x-client-name` a gateway routes on
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Upstream timeouts chosen per request: the client's `x-aiproxy-timeout-ms`
+/// (capped at `max_ms`), else the most specific matching route, else the provider's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestTimeoutConfig {
+    /// Longest timeout a client may ask for
+    #[serde(default = "default_max_request_timeout_ms")]
+    pub max_ms: u64,
+    /// Timeout by request path; patterns may end in `*`
+    #[serde(default)]
+    pub routes: HashMap<String, u64>,
+    /// Timeout by provider
+    #[serde(default)]
+    pub providers: HashMap<String, u64>,
+}
+
+impl Default for RequestTimeoutConfig {
+    fn default() -> Self {
+        Self {
+            max_ms: default_max_request_timeout_ms(),
+            routes: HashMap::new(),
+            providers: HashMap::new(),
+        }
+    }
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            pool_idle_timeout_secs: default_pool_idle_timeout(),
+            connect_timeout_secs: default_connect_timeout(),
+            request_timeout_secs: default_request_timeout(),
+            tcp_keepalive_secs: default_tcp_keepalive(),
+            http2_prior_knowledge: false,
+            http2_keep_alive_interval_secs: None,
+            prewarm: false,
+            user_agent: default_user_agent(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// Prompt compression; the steps run in the order of the fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptCompressionConfig {
+    /// Strip trailing whitespace and collapse runs of blank lines
+    #[serde(default = "default_compression_collapse_whitespace")]
+    pub collapse_whitespace: bool,
+    /// Regexes whose matches are removed from prompt text (disclaimers, signatures, ...)
+    #[serde(default)]
+    pub boilerplate_patterns: Vec<String>,
+    /// Replace a paragraph repeated from earlier in the prompt with a short marker
+    #[serde(default = "default_compression_deduplicate")]
+    pub deduplicate: bool,
+    /// Paragraphs shorter than this are never treated as repeated context
+    #[serde(default = "default_compression_min_block_chars")]
+    pub min_block_chars: usize,
+    /// Have a model summarize long older messages
+    #[serde(default)]
+    pub llm: Option<LlmCompressionConfig>,
+}
+
+/// Model-based compression of long messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmCompressionConfig {
+    /// Model rewriting the messages, routed like a request for it
+    pub model: String,
+    /// Only messages with at least this many tokens are compressed
+    #[serde(default = "default_llm_compression_min_tokens")]
+    pub min_tokens: usize,
+    /// The latest messages are always sent verbatim
+    #[serde(default = "default_llm_compression_keep_recent")]
+    pub keep_recent: usize,
+}
+
+/// Tool descriptions always have their whitespace collapsed; markdown and examples go too
+/// unless turned off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolMinificationConfig {
+    /// Reduce markdown (emphasis, headings, list markers, links, code fences) to plain text
+    #[serde(default = "default_minify_strip_markdown")]
+    pub strip_markdown: bool,
+    /// Remove `examples` / `example` from parameter schemas
+    #[serde(default = "default_minify_drop_examples")]
+    pub drop_examples: bool,
+}
+
+impl Default for ToolMinificationConfig {
+    fn default() -> Self {
+        Self {
+            strip_markdown: default_minify_strip_markdown(),
+            drop_examples: default_minify_drop_examples(),
+        }
+    }
+}
+
+/// Assistant text post-processing (streams are processed line by line)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostProcessConfig {
+    #[serde(default)]
+    pub replacements: Vec<ReplacementRule>,
+    #[serde(default)]
+    pub trim_trailing_whitespace: bool,
+    /// Tidy code fence lines and close a fence left open at the end
+    #[serde(default)]
+    pub normalize_code_fences: bool,
+}
+
+/// Regex replacement; `replacement` may use `$1` / `${name}` capture references
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacementRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+}
+
+/// What to do with reasoning blocks embedded in assistant text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningMode {
+    /// Drop the reasoning entirely
+    #[default]
+    Strip,
+    /// Move it into a `reasoning_content` field next to the text
+    Field,
+}
+
+/// Reasoning filter for models matching `model` (exact, or a prefix ending in `*`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReasoningFilterRule {
+    pub model: String,
+    #[serde(default)]
+    pub mode: ReasoningMode,
+    /// Tag name without brackets
+    #[serde(default = "default_reasoning_tag")]
+    pub tag: String,
+}
+
+/// HMAC request signing (see `request_signing` module for the signed payload)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestSigningConfig {
+    pub secret: String,
+    /// Maximum clock skew, and how long a signature is remembered to block replays
+    #[serde(default = "default_replay_window")]
+    pub replay_window_secs: u64,
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+    #[serde(default = "default_signature_timestamp_header")]
+    pub timestamp_header: String,
+}
 
-    /// Cron configuration
-    #[serde(default = "default_cron_near_minutes")]
-    pub cron_near_minutes: u64,
-    #[serde(default = "default_cron_refresh_token")]
-    pub cron_refresh_token: bool,
+/// Admin OIDC login (authorization code + PKCE)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// Must point at this server's `/admin/callback`
+    pub redirect_url: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: String,
+    #[serde(default = "default_oidc_roles_claim")]
+    pub roles_claim: String,
+    #[serde(default = "default_oidc_operator_roles")]
+    pub operator_roles: Vec<String>,
+    #[serde(default = "default_oidc_viewer_roles")]
+    pub viewer_roles: Vec<String>,
+    #[serde(default = "default_oidc_session_ttl")]
+    pub session_ttl_secs: u64,
+}
 
-    /// Provider pools configuration
+/// Client JWT validation and claim mapping
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtConfig {
+    /// Shared secret for HS256 tokens
     #[serde(default)]
-    pub provider_pools_file_path: Option<PathBuf>,
+    pub hs256_secret: Option<String>,
+    /// JWKS endpoint for RS256 tokens
     #[serde(default)]
-    pub provider_pools: HashMap<String, Vec<ProviderConfig>>,
+    pub jwks_url: Option<String>,
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default = "default_jwt_tenant_claim")]
+    pub tenant_claim: String,
+    #[serde(default = "default_jwt_models_claim")]
+    pub models_claim: String,
+    #[serde(default = "default_jwt_quota_claim")]
+    pub quota_claim: String,
+    /// Claim listing the scopes a token grants (array or space-separated)
+    #[serde(default = "default_jwt_scopes_claim")]
+    pub scopes_claim: String,
+    /// Scopes of tokens without the scopes claim; none by default
+    #[serde(default)]
+    pub default_scopes: Vec<String>,
+    #[serde(default = "default_jwks_cache_secs")]
+    pub jwks_cache_secs: u64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            hs256_secret: None,
+            jwks_url: None,
+            issuer: None,
+            audience: None,
+            tenant_claim: default_jwt_tenant_claim(),
+            models_claim: default_jwt_models_claim(),
+            quota_claim: default_jwt_quota_claim(),
+            scopes_claim: default_jwt_scopes_claim(),
+            default_scopes: Vec::new(),
+            jwks_cache_secs: default_jwks_cache_secs(),
+        }
+    }
+}
+
+/// Health scoring of pool keys (see `key_health` module)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyHealthConfig {
+    /// Penalty at which a key is quarantined: an auth failure counts 3, a 429 1 and a 5xx 0.5
+    #[serde(default = "default_key_quarantine_threshold")]
+    pub quarantine_threshold: f64,
+    /// Time for a key's penalty to halve
+    #[serde(default = "default_key_half_life_secs")]
+    pub half_life_secs: u64,
+    /// First quarantine period, doubled for each quarantine in a row
+    #[serde(default = "default_key_backoff_secs")]
+    pub backoff_secs: u64,
+    #[serde(default = "default_key_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    /// How often keys past their quarantine are probed
+    #[serde(default = "default_key_probe_interval_secs")]
+    pub probe_interval_secs: u64,
+}
+
+impl Default for KeyHealthConfig {
+    fn default() -> Self {
+        Self {
+            quarantine_threshold: default_key_quarantine_threshold(),
+            half_life_secs: default_key_half_life_secs(),
+            backoff_secs: default_key_backoff_secs(),
+            max_backoff_secs: default_key_max_backoff_secs(),
+            probe_interval_secs: default_key_probe_interval_secs(),
+        }
+    }
 }
 
 /// Provider configuration for pool management
@@ -192,29 +1413,438 @@ fn default_cron_refresh_token() -> bool {
     true
 }
 
+fn default_audit_log_file() -> Option<PathBuf> {
+    Some(PathBuf::from("audit_log.jsonl"))
+}
+
+fn default_client_keys_file() -> Option<PathBuf> {
+    Some(PathBuf::from("client_keys.json"))
+}
+
+fn default_secret_refresh_interval() -> u64 {
+    300
+}
+
+fn default_cluster_key_prefix() -> String {
+    "aiclient2api:".to_string()
+}
+
+fn default_rate_limit_window() -> u64 {
+    60
+}
+
+fn default_rate_limit_backend() -> String {
+    "memory".to_string()
+}
+
+fn default_response_compression() -> bool {
+    true
+}
+
+fn default_compression_min_size() -> u16 {
+    1024
+}
+
+fn default_request_decompression() -> bool {
+    true
+}
+
+fn default_max_body_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_jwt_tenant_claim() -> String {
+    "tenant".to_string()
+}
+
+fn default_jwt_models_claim() -> String {
+    "allowed_models".to_string()
+}
+
+fn default_jwt_quota_claim() -> String {
+    "quota".to_string()
+}
+
+fn default_jwt_scopes_claim() -> String {
+    "scope".to_string()
+}
+
+fn default_jwks_cache_secs() -> u64 {
+    600
+}
+
+fn default_oidc_scopes() -> String {
+    "openid profile email".to_string()
+}
+
+fn default_oidc_roles_claim() -> String {
+    "roles".to_string()
+}
+
+fn default_oidc_operator_roles() -> Vec<String> {
+    vec!["operator".to_string()]
+}
+
+fn default_oidc_viewer_roles() -> Vec<String> {
+    vec!["viewer".to_string()]
+}
+
+fn default_oidc_session_ttl() -> u64 {
+    8 * 3600
+}
+
+fn default_replay_window() -> u64 {
+    300
+}
+
+fn default_signature_header() -> String {
+    "x-signature".to_string()
+}
+
+fn default_signature_timestamp_header() -> String {
+    "x-signature-timestamp".to_string()
+}
+
+fn default_reasoning_tag() -> String {
+    "think".to_string()
+}
+
+fn default_stream_resume_attempts() -> u32 {
+    1
+}
+
+fn default_empty_response_retries() -> u32 {
+    1
+}
+
+fn default_continuation_max_continuations() -> u32 {
+    3
+}
+
+fn default_continuation_prompt() -> String {
+    "Continue exactly where you stopped. Do not repeat anything or add commentary.".to_string()
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    10
+}
+
+fn default_pool_idle_timeout() -> u64 {
+    90
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_request_timeout() -> u64 {
+    60
+}
+
+fn default_max_request_timeout_ms() -> u64 {
+    600_000
+}
+
+fn default_tcp_keepalive() -> u64 {
+    60
+}
+
+fn default_user_agent() -> String {
+    format!("aiclient2api-rust/{}", env!("CARGO_PKG_VERSION"))
+}
+
+fn default_warm_up() -> bool {
+    true
+}
+
+fn default_probe_interval() -> u64 {
+    30
+}
+
+fn default_probe_timeout() -> u64 {
+    5
+}
+
+fn default_retry_budget_enabled() -> bool {
+    true
+}
+
+fn default_retry_budget_ratio() -> f64 {
+    0.1
+}
+
+fn default_retry_budget_min_per_sec() -> f64 {
+    1.0
+}
+
+fn default_retry_budget_window() -> u64 {
+    10
+}
+
+fn default_concurrency_initial_limit() -> usize {
+    8
+}
+
+fn default_concurrency_min_limit() -> usize {
+    1
+}
+
+fn default_concurrency_max_limit() -> usize {
+    128
+}
+
+fn default_concurrency_backoff_ratio() -> f64 {
+    0.75
+}
+
+fn default_concurrency_latency_tolerance() -> f64 {
+    2.0
+}
+
+fn default_concurrency_queue_timeout_ms() -> u64 {
+    10000
+}
+
+fn default_key_quarantine_threshold() -> f64 {
+    3.0
+}
+
+fn default_key_half_life_secs() -> u64 {
+    300
+}
+
+fn default_key_backoff_secs() -> u64 {
+    60
+}
+
+fn default_key_max_backoff_secs() -> u64 {
+    3600
+}
+
+fn default_key_probe_interval_secs() -> u64 {
+    30
+}
+
+fn default_pacing_reserve_ratio() -> f64 {
+    0.05
+}
+
+fn default_pacing_max_wait_ms() -> u64 {
+    5000
+}
+
+fn default_tool_loop_max_iterations() -> u32 {
+    5
+}
+
+fn default_tool_timeout() -> u64 {
+    30
+}
+
+fn default_tool_parameters() -> serde_json::Value {
+    serde_json::json!({"type": "object", "properties": {}})
+}
+
+fn default_cascade_detect_refusals() -> bool {
+    true
+}
+
+fn default_cascade_min_score() -> usize {
+    7
+}
+
+fn default_compression_collapse_whitespace() -> bool {
+    true
+}
+
+fn default_compression_deduplicate() -> bool {
+    true
+}
+
+fn default_minify_strip_markdown() -> bool {
+    true
+}
+
+fn default_minify_drop_examples() -> bool {
+    true
+}
+
+fn default_compression_min_block_chars() -> usize {
+    200
+}
+
+fn default_llm_compression_min_tokens() -> usize {
+    1000
+}
+
+fn default_llm_compression_keep_recent() -> usize {
+    2
+}
+
+fn default_web_search_max_results() -> usize {
+    5
+}
+
+fn default_web_search_max_iterations() -> u32 {
+    3
+}
+
+fn default_log_truncate_chars() -> usize {
+    64
+}
+
+fn default_scrub_secrets() -> bool {
+    true
+}
+
+fn default_conversation_ttl() -> u64 {
+    24 * 3600
+}
+
+fn default_conversation_max_messages() -> usize {
+    100
+}
+
+fn default_response_cache_ttl() -> u64 {
+    300
+}
+
+fn default_models_ttl() -> u64 {
+    300
+}
+
+fn default_model_metadata_ttl() -> u64 {
+    3600
+}
+
+fn default_duplicate_max_repeats() -> u64 {
+    10
+}
+
+fn default_duplicate_window() -> u64 {
+    60
+}
+
+fn default_idempotency_ttl() -> u64 {
+    86400
+}
+
+fn default_usage_rollup_ttl() -> u64 {
+    7 * 24 * 3600
+}
+
+fn default_response_cache_vary() -> Vec<String> {
+    ["temperature", "top_p", "top_k", "seed"].iter().map(|s| s.to_string()).collect()
+}
+
+fn default_pacing_chunk_chars() -> usize {
+    20
+}
+
+fn default_pacing_interval_ms() -> u64 {
+    15
+}
+
+fn default_region_failure_threshold() -> u32 {
+    2
+}
+
+fn default_region_cooldown() -> u64 {
+    30
+}
+
+fn default_region_health_check_interval() -> u64 {
+    30
+}
+
+fn default_alert_dedup() -> u64 {
+    600
+}
+
+fn default_alert_error_rate() -> f64 {
+    0.5
+}
+
+fn default_alert_error_window() -> u64 {
+    60
+}
+
+fn default_alert_error_min_requests() -> u64 {
+    20
+}
+
+fn default_alert_budget_thresholds() -> Vec<f64> {
+    vec![0.8, 1.0]
+}
+
+fn default_transcript_capacity() -> usize {
+    200
+}
+
+fn default_dataset_directory() -> PathBuf {
+    PathBuf::from("datasets")
+}
+
+fn default_dataset_max_file_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_dataset_flush_interval() -> u64 {
+    300
+}
+
+fn default_dataset_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
 fn default_healthy() -> bool {
     true
 }
 
+/// Environment variable holding an entire config document, used instead of the config file
+pub const CONFIG_JSON_ENV: &str = "AIPROXY_CONFIG_JSON";
+
 impl Config {
     /// Load configuration from config file, environment, and command-line arguments
     pub fn load() -> Result<Self> {
         Self::load_with_args(&std::env::args().collect::<Vec<_>>())
     }
 
-    /// Load configuration with custom arguments (for testing)
+    /// Load configuration with custom arguments (for testing); warnings are logged
     pub fn load_with_args(args: &[String]) -> Result<Self> {
+        let (config, warnings) = Self::load_with_diagnostics(args)?;
+        for warning in &warnings {
+            tracing::warn!("{}", warning);
+        }
+        Ok(config)
+    }
+
+    /// Load configuration, returning the warnings found in it; any error-level
+    /// finding fails the load with a `ConfigErrors` listing every diagnostic
+    pub fn load_with_diagnostics(args: &[String]) -> Result<(Self, Vec<Diagnostic>)> {
         // Parse command-line arguments
         let cli_config = Self::parse_cli_args(args)?;
         
-        let config_path = cli_config.config_file.as_deref().unwrap_or("config.json");
+        let config_path = cli_config.config_file.clone().unwrap_or_else(|| "config.json".to_string());
         
-        let mut config: Config = if let Ok(content) = fs::read_to_string(config_path) {
-            serde_json::from_str(&content)
-                .context("Failed to parse config.json")?
-        } else {
+        // A full config document in the environment takes the place of the config file
+        let env_config = std::env::var(CONFIG_JSON_ENV).ok().filter(|v| !v.trim().is_empty());
+
+        let document = match env_config {
+            Some(content) => Some((CONFIG_JSON_ENV.to_string(), content)),
+            None => fs::read_to_string(&config_path).ok().map(|content| (config_path.clone(), content)),
+        };
+
+        let mut diagnostics = Vec::new();
+        let mut config: Config = match &document {
+            Some((source, content)) => {
+                let (config, warnings) = config_validation::parse(content, source)
+                    .map_err(|e| anyhow::Error::new(ConfigErrors(vec![e])))?;
+                diagnostics.extend(warnings);
+                config
+            }
             // Use default configuration if file doesn't exist
-            Self::default()
+            None => Self::default(),
         };
 
         // Load system prompt content if file exists
@@ -223,22 +1853,40 @@ impl Config {
         }
 
         // Load provider pools if configured
+        let mut pools_document = None;
         if let Some(ref pools_path) = config.provider_pools_file_path {
             if pools_path.exists() {
                 let pools_content = fs::read_to_string(pools_path)
                     .context("Failed to read provider pools file")?;
-                config.provider_pools = serde_json::from_str(&pools_content)
-                    .context("Failed to parse provider pools file")?;
+                let pools_source = pools_path.display().to_string();
+                let (pools, warnings) = config_validation::parse(&pools_content, &pools_source)
+                    .map_err(|e| anyhow::Error::new(ConfigErrors(vec![e])))?;
+                config.provider_pools = pools;
+                diagnostics.extend(warnings);
+                pools_document = Some((pools_source, pools_content));
             }
         }
 
         // Merge CLI arguments into config
         config.merge_cli_args(cli_config);
 
+        // Decrypt credentials stored as enc:v1:... values
+        crate::secrets::decrypt_config(&mut config)?;
+
         // Normalize provider configuration
         config.normalize_providers();
 
-        Ok(config)
+        diagnostics.extend(config_validation::validate(
+            &config,
+            document.as_ref().map(|(source, _)| source.as_str()).unwrap_or(&config_path),
+            document.as_ref().map(|(_, content)| content.as_str()),
+            pools_document.as_ref().map(|(source, content)| (source.as_str(), content.as_str())),
+        ));
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            return Err(anyhow::Error::new(ConfigErrors(diagnostics)));
+        }
+
+        Ok((config, diagnostics))
     }
 
     /// Parse command-line arguments
@@ -380,13 +2028,23 @@ impl Default for Config {
         Self {
             host: default_host(),
             port: default_port(),
+            listeners: Vec::new(),
             required_api_key: default_api_key(),
             model_provider: default_model_provider(),
             default_model_providers: vec![],
+            model_routes: Vec::new(),
+            routers: Vec::new(),
+            model_aliases: HashMap::new(),
+            tenant_models: HashMap::new(),
             openai_api_key: None,
             openai_base_url: None,
+            openai_base_urls: Vec::new(),
+            openai_organization: None,
+            openai_project: None,
             claude_api_key: None,
             claude_base_url: None,
+            claude_base_urls: Vec::new(),
+            claude_beta_flags: Vec::new(),
             gemini_oauth_creds_base64: None,
             gemini_oauth_creds_file_path: None,
             project_id: None,
@@ -404,6 +2062,59 @@ impl Default for Config {
             cron_refresh_token: default_cron_refresh_token(),
             provider_pools_file_path: None,
             provider_pools: HashMap::new(),
+            key_health: KeyHealthConfig::default(),
+            admin_api_key: None,
+            audit_log_file_path: default_audit_log_file(),
+            client_keys_file_path: default_client_keys_file(),
+            secret_refresh_interval_secs: default_secret_refresh_interval(),
+            redis_url: None,
+            cluster_key_prefix: default_cluster_key_prefix(),
+            rate_limit_requests: 0,
+            rate_limit_window_secs: default_rate_limit_window(),
+            rate_limit_backend: default_rate_limit_backend(),
+            response_compression: default_response_compression(),
+            compression_min_size: default_compression_min_size(),
+            request_decompression: default_request_decompression(),
+            max_body_bytes: default_max_body_bytes(),
+            jwt: None,
+            oidc: None,
+            request_signing: None,
+            forward_headers: Vec::new(),
+            hash_end_user_ids: false,
+            end_user_hash_salt: None,
+            reasoning_filters: Vec::new(),
+            prompt_compression: None,
+            tool_minification: None,
+            post_processing: None,
+            stream_resume_attempts: default_stream_resume_attempts(),
+            simulated_streaming: SimulatedStreamingConfig::default(),
+            stream_aggregation: StreamAggregationConfig::default(),
+            empty_response_retry: None,
+            auto_continuation: None,
+            http_client: HttpClientConfig::default(),
+            request_timeouts: RequestTimeoutConfig::default(),
+            retry_budget: RetryBudgetConfig::default(),
+            readiness: ReadinessConfig::default(),
+            adaptive_concurrency: AdaptiveConcurrencyConfig::default(),
+            rate_limit_pacing: RateLimitPacingConfig::default(),
+            rerank: None,
+            tool_loop: None,
+            web_search: None,
+            ensembles: Vec::new(),
+            cascades: Vec::new(),
+            model_registry: ModelRegistryConfig::default(),
+            request_limits: RequestLimits::default(),
+            logging: LoggingConfig::default(),
+            conversations: None,
+            response_cache: None,
+            model_list_cache: None,
+            duplicate_requests: None,
+            idempotency: None,
+            usage_rollup: None,
+            region_failover: RegionFailoverConfig::default(),
+            alerts: None,
+            dataset: None,
+            transcripts: None,
         }
     }
 }
@@ -0,0 +1,612 @@
+/*!
+ * Config validation
+ *
+ * Parses config documents with diagnostics pointing at the offending line:
+ * type mismatches name the field path (`http_client.connect_timeout_secs`)
+ * instead of failing with a bare deserialization error, unknown fields are
+ * reported as warnings (they are otherwise silently ignored, so a typo loses
+ * a setting), and a semantic pass catches settings that parse but cannot work
+ * together, such as a provider pool for an undefined provider.
+ */
+
+use crate::common::{ModelProtocol, ModelProvider};
+use crate::config::{Config, ModelAlias, ProviderConfig, RouterConfig};
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a config document; `line`/`column` are 1-based and
+/// absent when the setting did not come from the document (e.g. CLI arguments)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Where the document came from, e.g. `config.json`
+    pub source: String,
+    /// Dotted path of the field, e.g. `provider_pools.openai-custom[0].uuid`
+    pub path: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}", self.source)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}: ", severity)?;
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// All problems of a document that could not be used
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<Diagnostic>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self.0.iter().filter(|d| d.severity == Severity::Error).count();
+        write!(f, "invalid configuration ({} error{})", errors, if errors == 1 { "" } else { "s" })?;
+        for diagnostic in &self.0 {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// Deserialize `content`, collecting unknown fields as warnings; a syntax or
+/// type error is returned as a single error diagnostic with its field path
+pub fn parse<T: DeserializeOwned>(content: &str, source: &str) -> Result<(T, Vec<Diagnostic>), Diagnostic> {
+    let mut ignored: Vec<String> = Vec::new();
+    let mut deserializer = serde_json::Deserializer::from_str(content);
+    let mut on_ignored = |path: serde_ignored::Path| ignored.push(path.to_string());
+    let result: Result<T, _> =
+        serde_path_to_error::deserialize(serde_ignored::Deserializer::new(&mut deserializer, &mut on_ignored));
+    let value = result
+        .map_err(|err| {
+            let path = normalize_path(&err.path().to_string());
+            syntax_error(source, path, err.into_inner())
+        })
+        .and_then(|value| {
+            deserializer.end().map_err(|e| syntax_error(source, String::new(), e))?;
+            Ok(value)
+        })?;
+
+    let warnings = ignored
+        .into_iter()
+        .map(|path| {
+            let path = normalize_path(&path);
+            let (line, column) = locate(content, &path).unzip();
+            Diagnostic {
+                severity: Severity::Warning,
+                source: source.to_string(),
+                path,
+                line,
+                column,
+                message: "unknown field, ignored".to_string(),
+            }
+        })
+        .collect();
+    Ok((value, warnings))
+}
+
+fn syntax_error(source: &str, path: String, err: serde_json::Error) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        source: source.to_string(),
+        path,
+        line: Some(err.line()).filter(|l| *l > 0),
+        column: Some(err.column()).filter(|c| *c > 0),
+        message: strip_position(&err.to_string()),
+    }
+}
+
+/// Cross-field checks of a loaded config. `source`/`content` locate the
+/// findings in the main document; pool findings are located in `pools`
+/// (the provider pools file) when the pools came from one
+pub fn validate(config: &Config, source: &str, content: Option<&str>, pools: Option<(&str, &str)>) -> Vec<Diagnostic> {
+    let mut checker = Checker { source, content, diagnostics: Vec::new() };
+
+    if ModelProvider::from_str(&config.model_provider).is_none() {
+        checker.error("model_provider", format!("unknown provider `{}` (expected one of {})", config.model_provider, provider_names()));
+    }
+    for (i, provider) in config.default_model_providers.iter().enumerate() {
+        // `model_provider` is copied in when the list is empty; it is reported above
+        if provider != &config.model_provider && ModelProvider::from_str(provider).is_none() {
+            checker.error(&format!("default_model_providers[{}]", i), format!("unknown provider `{}`", provider));
+        }
+    }
+
+    for (i, route) in config.model_routes.iter().enumerate() {
+        if ModelProvider::from_str(&route.provider).is_none() {
+            checker.error(&format!("model_routes[{}].provider", i), format!("route for `{}` references undefined provider `{}`", route.model, route.provider));
+        }
+    }
+    for (i, router) in config.routers.iter().enumerate() {
+        let path = format!("routers[{}]", i);
+        let providers: Vec<&String> = match router {
+            RouterConfig::Static { routes } => routes.iter().map(|route| &route.provider).collect(),
+            RouterConfig::Weighted { weights, .. } => {
+                if weights.values().all(|weight| *weight == 0) {
+                    checker.error(&format!("{}.weights", path), "at least one provider needs a weight above 0".to_string());
+                }
+                weights.keys().collect()
+            }
+            RouterConfig::Capability { vision, tools, .. } => {
+                if vision.is_none() && tools.is_none() {
+                    checker.warning(&path, "neither `vision` nor `tools` is set; the router never picks a provider".to_string());
+                }
+                vision.iter().chain(tools).collect()
+            }
+            RouterConfig::Cost { prices, .. } => {
+                for (provider, price) in prices {
+                    if !price.is_finite() || *price < 0.0 {
+                        checker.error(&format!("{}.prices.{}", path, provider), format!("price must be a non-negative number, got {}", price));
+                    }
+                }
+                prices.keys().collect()
+            }
+        };
+        for provider in providers {
+            if ModelProvider::from_str(provider).is_none() {
+                checker.error(&path, format!("router references undefined provider `{}`", provider));
+            }
+        }
+    }
+    for (alias, target) in &config.model_aliases {
+        let path = format!("model_aliases.{}", alias);
+        let targets = target.targets();
+        if targets.is_empty() {
+            checker.error(&path, "needs at least one target".to_string());
+        }
+        for (i, target) in targets.iter().enumerate() {
+            let path = match config.model_aliases[alias] {
+                ModelAlias::Model(_) => path.clone(),
+                ModelAlias::Targets(_) => format!("{}[{}]", path, i),
+            };
+            if config.model_aliases.contains_key(&target.model) && &target.model != alias {
+                checker.warning(&path, format!("target `{}` is itself an alias; aliases are resolved once", target.model));
+            }
+            if let Some(provider) = &target.provider {
+                if ModelProvider::from_str(provider).is_none() {
+                    checker.error(&format!("{}.provider", path), format!("undefined provider `{}`", provider));
+                }
+            }
+        }
+    }
+    for (tenant, models) in &config.tenant_models {
+        if models.is_empty() {
+            checker.warning(&format!("tenant_models.{}", tenant), "is empty; the tenant may call no model".to_string());
+        }
+    }
+
+    for (i, provider) in config.simulated_streaming.providers.iter().enumerate() {
+        if ModelProvider::from_str(provider).is_none() {
+            checker.error(&format!("simulated_streaming.providers[{}]", i), format!("unknown provider `{}`", provider));
+        }
+    }
+    for (i, provider) in config.stream_aggregation.providers.iter().enumerate() {
+        let path = format!("stream_aggregation.providers[{}]", i);
+        if ModelProvider::from_str(provider).is_none() {
+            checker.error(&path, format!("unknown provider `{}`", provider));
+        } else if config.simulated_streaming.providers.contains(provider) {
+            checker.error(&path, format!("`{}` is also listed in simulated_streaming.providers", provider));
+        }
+    }
+
+    if let Some(retry) = &config.empty_response_retry {
+        for (i, provider) in retry.providers.iter().enumerate() {
+            if ModelProvider::from_str(provider).is_none() {
+                checker.error(&format!("empty_response_retry.providers[{}]", i), format!("unknown provider `{}`", provider));
+            }
+        }
+        if let Some(fallback) = &retry.fallback_provider {
+            if ModelProvider::from_str(fallback).is_none() {
+                checker.error("empty_response_retry.fallback_provider", format!("unknown provider `{}`", fallback));
+            }
+        }
+    }
+    if let Some(continuation) = &config.auto_continuation {
+        for (i, provider) in continuation.providers.iter().enumerate() {
+            if ModelProvider::from_str(provider).is_none() {
+                checker.error(&format!("auto_continuation.providers[{}]", i), format!("unknown provider `{}`", provider));
+            }
+        }
+        if continuation.max_continuations == 0 {
+            checker.warning("auto_continuation.max_continuations", "is 0, so no response is ever continued".to_string());
+        }
+    }
+    if config.transcripts.as_ref().is_some_and(|transcripts| transcripts.capacity == 0) {
+        checker.error("transcripts.capacity", "must be at least 1; remove `transcripts` to keep none".to_string());
+    }
+
+    let mut ports = vec![config.port];
+    for (i, listener) in config.listeners.iter().enumerate() {
+        if ModelProtocol::from_str(&listener.protocol).is_none() {
+            checker.error(&format!("listeners[{}].protocol", i), format!("unknown protocol `{}` (expected `openai`, `claude` or `gemini`)", listener.protocol));
+        }
+        if ports.contains(&listener.port) {
+            checker.error(&format!("listeners[{}].port", i), format!("port {} is already in use by another listener", listener.port));
+        }
+        ports.push(listener.port);
+    }
+
+    match pools {
+        Some((pools_source, pools_content)) => {
+            let mut pool_checker = Checker { source: pools_source, content: Some(pools_content), diagnostics: Vec::new() };
+            check_pools(&mut pool_checker, &config.provider_pools, "");
+            checker.diagnostics.append(&mut pool_checker.diagnostics);
+        }
+        None => check_pools(&mut checker, &config.provider_pools, "provider_pools."),
+    }
+
+    match config.rate_limit_backend.as_str() {
+        "memory" => {}
+        "redis" if config.redis_url.is_none() => {
+            checker.error("rate_limit_backend", "`redis` backend requires `redis_url`".to_string())
+        }
+        "redis" => {}
+        other => checker.error("rate_limit_backend", format!("unknown backend `{}` (expected `memory` or `redis`)", other)),
+    }
+
+    if !["none", "console", "file"].contains(&config.prompt_log_mode.as_str()) {
+        checker.error("prompt_log_mode", format!("unknown mode `{}` (expected `none`, `console` or `file`)", config.prompt_log_mode));
+    }
+    if !["overwrite", "append"].contains(&config.system_prompt_mode.as_str()) {
+        checker.error("system_prompt_mode", format!("unknown mode `{}` (expected `overwrite` or `append`)", config.system_prompt_mode));
+    }
+
+    if let Some(jwt) = &config.jwt {
+        if jwt.hs256_secret.is_none() && jwt.jwks_url.is_none() {
+            checker.error("jwt", "either `hs256_secret` or `jwks_url` is required".to_string());
+        }
+    }
+
+    let concurrency = &config.adaptive_concurrency;
+    if concurrency.min_limit > concurrency.max_limit {
+        checker.error("adaptive_concurrency.min_limit", format!("{} exceeds `max_limit` ({})", concurrency.min_limit, concurrency.max_limit));
+    } else if !(concurrency.min_limit..=concurrency.max_limit).contains(&concurrency.initial_limit) {
+        checker.error("adaptive_concurrency.initial_limit", format!("{} is outside `min_limit`..`max_limit`", concurrency.initial_limit));
+    }
+    if config.key_health.quarantine_threshold <= 0.0 {
+        checker.error("key_health.quarantine_threshold", "must be greater than 0".to_string());
+    }
+    if config.key_health.probe_interval_secs == 0 {
+        checker.error("key_health.probe_interval_secs", "must be at least 1".to_string());
+    }
+    if !(0.0..1.0).contains(&config.rate_limit_pacing.reserve_ratio) {
+        checker.error("rate_limit_pacing.reserve_ratio", format!("{} is outside 0..1", config.rate_limit_pacing.reserve_ratio));
+    }
+
+    let timeouts = &config.request_timeouts;
+    if timeouts.max_ms == 0 {
+        checker.error("request_timeouts.max_ms", "must be at least 1".to_string());
+    }
+    for (section, map) in [("routes", &timeouts.routes), ("providers", &timeouts.providers)] {
+        for (name, ms) in map {
+            if *ms == 0 {
+                checker.error(&format!("request_timeouts.{}.{}", section, name), "must be at least 1".to_string());
+            }
+        }
+    }
+
+    if config.max_body_bytes == 0 {
+        checker.error("max_body_bytes", "must be at least 1".to_string());
+    }
+
+    if config.idempotency.as_ref().is_some_and(|idempotency| idempotency.ttl_secs == 0) {
+        checker.error("idempotency.ttl_secs", "must be at least 1".to_string());
+    }
+
+    if let Some(ref duplicates) = config.duplicate_requests {
+        if duplicates.max_repeats == 0 {
+            checker.error("duplicate_requests.max_repeats", "must be at least 1".to_string());
+        }
+        if duplicates.window_secs == 0 {
+            checker.error("duplicate_requests.window_secs", "must be at least 1".to_string());
+        }
+    }
+
+    if let Some(ref rollup) = config.usage_rollup {
+        if rollup.ttl_secs == 0 {
+            checker.error("usage_rollup.ttl_secs", "must be at least 1".to_string());
+        }
+    }
+
+    let limits = &config.request_limits;
+    for (field, value) in [
+        ("max_messages", limits.max_messages),
+        ("max_images", limits.max_images),
+        ("max_base64_bytes", limits.max_base64_bytes),
+    ] {
+        if value == Some(0) {
+            checker.warning(&format!("request_limits.{}", field), "is 0; every request carrying any is rejected".to_string());
+        }
+    }
+
+    if http::HeaderValue::from_str(&config.http_client.user_agent).is_err() {
+        checker.error("http_client.user_agent", "not a valid header value".to_string());
+    }
+    for (name, value) in &config.http_client.headers {
+        let path = format!("http_client.headers.{}", name);
+        if http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            checker.error(&path, format!("`{}` is not a valid header name", name));
+        } else if http::HeaderValue::from_str(value).is_err() {
+            checker.error(&path, "not a valid header value".to_string());
+        }
+    }
+    for (prefix, rate) in &config.logging.sample_rates {
+        if !(0.0..=1.0).contains(rate) {
+            checker.error(&format!("logging.sample_rates.{}", prefix), format!("rate {} is outside 0..1", rate));
+        }
+    }
+
+    if let Some(post) = &config.post_processing {
+        for (i, rule) in post.replacements.iter().enumerate() {
+            if let Err(e) = regex::Regex::new(&rule.pattern) {
+                checker.error(&format!("post_processing.replacements[{}].pattern", i), format!("invalid regex: {}", e));
+            }
+        }
+    }
+
+    if let Some(compression) = &config.prompt_compression {
+        for (i, pattern) in compression.boilerplate_patterns.iter().enumerate() {
+            if let Err(e) = regex::Regex::new(pattern) {
+                checker.error(&format!("prompt_compression.boilerplate_patterns[{}]", i), format!("invalid regex: {}", e));
+            }
+        }
+    }
+
+    if let Some(alerts) = &config.alerts {
+        if alerts.webhooks.is_empty() {
+            checker.warning("alerts.webhooks", "no webhooks configured, alerts are dropped".to_string());
+        }
+        for (i, threshold) in alerts.budget_thresholds.iter().enumerate() {
+            if *threshold <= 0.0 {
+                checker.error(&format!("alerts.budget_thresholds[{}]", i), format!("threshold {} must be positive", threshold));
+            }
+        }
+    }
+
+    if let Some(tool_loop) = &config.tool_loop {
+        let mut names = HashSet::new();
+        for (i, tool) in tool_loop.tools.iter().enumerate() {
+            if !names.insert(tool.name.as_str()) {
+                checker.error(&format!("tool_loop.tools[{}].name", i), format!("duplicate tool `{}`", tool.name));
+            }
+        }
+    }
+
+    // Ensembles and cascades share the model namespace
+    let mut virtual_models = HashSet::new();
+    for (i, ensemble) in config.ensembles.iter().enumerate() {
+        if !virtual_models.insert(ensemble.model.as_str()) {
+            checker.error(&format!("ensembles[{}].model", i), format!("duplicate ensemble `{}`", ensemble.model));
+        }
+        if ensemble.members.is_empty() {
+            checker.error(&format!("ensembles[{}].members", i), "no members configured".to_string());
+        }
+        if ensemble.members.contains(&ensemble.model) {
+            checker.error(&format!("ensembles[{}].members", i), format!("ensemble `{}` cannot be its own member", ensemble.model));
+        }
+        if ensemble.judge.as_ref() == Some(&ensemble.model) {
+            checker.error(&format!("ensembles[{}].judge", i), format!("ensemble `{}` cannot judge itself", ensemble.model));
+        }
+    }
+
+    for (i, cascade) in config.cascades.iter().enumerate() {
+        if !virtual_models.insert(cascade.model.as_str()) {
+            checker.error(&format!("cascades[{}].model", i), format!("virtual model `{}` is defined twice", cascade.model));
+        }
+        if cascade.steps.len() < 2 {
+            checker.error(&format!("cascades[{}].steps", i), "a cascade needs at least two steps".to_string());
+        }
+        if cascade.steps.contains(&cascade.model) {
+            checker.error(&format!("cascades[{}].steps", i), format!("cascade `{}` cannot be its own step", cascade.model));
+        }
+        if !(1..=10).contains(&cascade.min_score) {
+            checker.error(&format!("cascades[{}].min_score", i), format!("score {} is not between 1 and 10", cascade.min_score));
+        }
+    }
+
+    let registry = &config.model_registry;
+    if registry.capability_routing && registry.fallback_models.is_empty() {
+        checker.warning("model_registry.capability_routing", "enabled without `fallback_models`, requests are rejected instead".to_string());
+    }
+    for (i, spec) in registry.models.iter().enumerate() {
+        for (field, price) in [("input_price", spec.input_price), ("output_price", spec.output_price)] {
+            if price.is_some_and(|price| !(price >= 0.0 && price.is_finite())) {
+                checker.error(&format!("model_registry.models[{}].{}", i, field), "must be a non-negative number".to_string());
+            }
+        }
+    }
+
+    if config.secret_refresh_interval_secs > 0 {
+        for field in crate::secret_refs::startup_only_references(config) {
+            checker.warning(&field, "secret reference is resolved only at startup; refreshes reach provider credentials, so restart after rotating it".to_string());
+        }
+    }
+
+    for (path, url) in [("rerank.url", config.rerank.as_ref().map(|r| &r.url)), ("web_search.url", config.web_search.as_ref().map(|w| &w.url))] {
+        if let Some(url) = url {
+            if url::Url::parse(url).is_err() {
+                checker.error(path, format!("`{}` is not a valid URL", url));
+            }
+        }
+    }
+    for (field, urls) in [("openai_base_urls", &config.openai_base_urls), ("claude_base_urls", &config.claude_base_urls)] {
+        for (i, url) in urls.iter().enumerate() {
+            if url::Url::parse(url).is_err() {
+                checker.error(&format!("{}[{}]", field, i), format!("`{}` is not a valid URL", url));
+            }
+        }
+    }
+
+    checker.diagnostics
+}
+
+fn check_pools(checker: &mut Checker, pools: &HashMap<String, Vec<ProviderConfig>>, prefix: &str) {
+    let mut names: Vec<&String> = pools.keys().collect();
+    names.sort();
+    for name in names {
+        if ModelProvider::from_str(name).is_none() {
+            checker.error(&format!("{}{}", prefix, name), format!("pool for undefined provider `{}` (expected one of {})", name, provider_names()));
+        }
+        let mut uuids = HashSet::new();
+        for (i, entry) in pools[name].iter().enumerate() {
+            if !uuids.insert(entry.uuid.as_str()) {
+                checker.error(&format!("{}{}[{}].uuid", prefix, name, i), format!("duplicate uuid `{}`", entry.uuid));
+            }
+        }
+    }
+}
+
+fn provider_names() -> String {
+    ["gemini-cli-oauth", "openai-custom", "claude-custom", "claude-kiro-oauth", "openai-qwen-oauth"]
+        .iter()
+        .map(|p| format!("`{}`", p))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+struct Checker<'a> {
+    source: &'a str,
+    content: Option<&'a str>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn push(&mut self, severity: Severity, path: &str, message: String) {
+        let (line, column) = self.content.and_then(|c| locate(c, path)).unzip();
+        self.diagnostics.push(Diagnostic {
+            severity,
+            source: self.source.to_string(),
+            path: path.to_string(),
+            line,
+            column,
+            message,
+        });
+    }
+
+    fn error(&mut self, path: &str, message: String) {
+        self.push(Severity::Error, path, message);
+    }
+
+    fn warning(&mut self, path: &str, message: String) {
+        self.push(Severity::Warning, path, message);
+    }
+}
+
+/// `a.b.0.c` (serde_ignored's form) and `a.b[0].c` both become `a.b[0].c`
+fn normalize_path(path: &str) -> String {
+    if path == "." {
+        return String::new();
+    }
+    let mut out = String::new();
+    for segment in path.split('.') {
+        if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+            out.push_str(&format!("[{}]", segment));
+        } else {
+            if !out.is_empty() {
+                out.push('.');
+            }
+            out.push_str(segment);
+        }
+    }
+    out
+}
+
+/// serde_json appends " at line X column Y"; the diagnostic carries those separately
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(pos) => message[..pos].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// Line and column of the key ending `path`, found by looking up each object
+/// key of the path in turn after the previous one, stepping over array elements
+/// for indices
+fn locate(content: &str, path: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    let mut found = None;
+    for segment in path.split('.') {
+        let mut parts = segment.split('[');
+        let key = parts.next().unwrap_or(segment);
+        if !key.is_empty() {
+            let needle = format!("\"{}\"", key);
+            let mut from = offset;
+            loop {
+                let pos = from + content[from..].find(&needle)?;
+                let after = pos + needle.len();
+                if content[after..].trim_start().starts_with(':') {
+                    found = Some(pos);
+                    offset = after;
+                    break;
+                }
+                from = after;
+            }
+        }
+        for index in parts {
+            let index: usize = index.trim_end_matches(']').parse().ok()?;
+            offset = skip_elements(content, offset, index)?;
+        }
+    }
+    let pos = found?;
+    let line = content[..pos].matches('\n').count() + 1;
+    let column = pos - content[..pos].rfind('\n').map(|p| p + 1).unwrap_or(0) + 1;
+    Some((line, column))
+}
+
+/// Offset of element `index` of the array opening at or after `from`
+fn skip_elements(content: &str, from: usize, index: usize) -> Option<usize> {
+    let start = from + content[from..].find('[')? + 1;
+    let (mut depth, mut seen, mut in_string, mut escaped) = (0usize, 0usize, false, false);
+    if index == 0 {
+        return Some(start);
+    }
+    for (i, c) in content[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '{' => depth += 1,
+            ']' | '}' if depth == 0 => return None,
+            ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                seen += 1;
+                if seen == index {
+                    return Some(start + i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
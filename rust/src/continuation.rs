@@ -0,0 +1,593 @@
+/*!
+ * Auto Continuation
+ *
+ * Opt-in handling of answers cut off by the output token limit
+ * (`finish_reason: "length"`, `stop_reason: "max_tokens"`, `finishReason:
+ * "MAX_TOKENS"`). The request is sent again with the answer so far, as an
+ * assistant prefill where the backend continues one and otherwise followed by
+ * a short "continue" message, and the parts are stitched into one response or
+ * one uninterrupted stream with summed usage. This repeats until the model
+ * stops on its own, `max_continuations` follow-ups have been made or the
+ * answer reaches `max_output_tokens`. Answers with tool calls are left as
+ * they are.
+ */
+
+use crate::adapter::ApiServiceAdapter;
+use crate::common::{ModelInfo, ModelListResponse, ModelProtocol};
+use crate::config::AutoContinuationConfig;
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::passthrough::ForwardRequest;
+use crate::request_context::RequestContext;
+use crate::stream_recovery::ValueStream;
+use crate::tokenizer::ModelTokenizer;
+use anyhow::Result;
+use async_stream::stream;
+use async_trait::async_trait;
+use futures::{Future, StreamExt};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// When to ask for more, and how
+#[derive(Debug, Clone)]
+pub struct ContinuationPolicy {
+    pub config: AutoContinuationConfig,
+    /// The backend continues a trailing assistant message (else a user message asks it to go on)
+    pub prefill: bool,
+    pub model: String,
+}
+
+impl ContinuationPolicy {
+    /// Whether an answer that stopped for `reason` gets another segment
+    fn should_continue(&self, reason: Option<&str>, tool_calls: bool, text: &str, continuations: u32) -> bool {
+        if !reason.is_some_and(truncated) || tool_calls || text.trim().is_empty() {
+            return false;
+        }
+        if continuations >= self.config.max_continuations {
+            info!("Answer from {} is still truncated after {} continuation(s)", self.model, continuations);
+            return false;
+        }
+        match self.config.max_output_tokens {
+            Some(max) if ModelTokenizer::for_model(&self.model).count_text(text) >= max => {
+                info!("Answer from {} reached the continuation budget of {} tokens", self.model, max);
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Whether a finish reason means the output token limit was hit
+pub fn truncated(reason: &str) -> bool {
+    matches!(reason, "length" | "max_tokens" | "MAX_TOKENS")
+}
+
+fn finish_reason(response: &Value, protocol: ModelProtocol) -> Option<&str> {
+    let reason = match protocol {
+        ModelProtocol::OpenAI => response.pointer("/choices/0/finish_reason"),
+        ModelProtocol::Claude => response.get("stop_reason"),
+        ModelProtocol::Gemini => response.pointer("/candidates/0/finishReason"),
+    };
+    reason.and_then(|r| r.as_str())
+}
+
+fn has_tool_calls(response: &Value, protocol: ModelProtocol) -> bool {
+    match protocol {
+        ModelProtocol::OpenAI => response
+            .pointer("/choices/0/message/tool_calls")
+            .and_then(|t| t.as_array())
+            .is_some_and(|calls| !calls.is_empty()),
+        ModelProtocol::Claude => response
+            .get("content")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+            .any(|block| matches!(block.get("type").and_then(|t| t.as_str()), Some("tool_use" | "server_tool_use"))),
+        ModelProtocol::Gemini => candidate_parts(response).iter().any(|part| part.get("functionCall").is_some()),
+    }
+}
+
+fn candidate_parts(response: &Value) -> Vec<Value> {
+    response.pointer("/candidates/0/content/parts").and_then(|p| p.as_array()).cloned().unwrap_or_default()
+}
+
+/// The request for the next segment: the original one with the answer so far appended
+pub fn continuation_request(body: &Value, protocol: ModelProtocol, text: &str, prefill: bool, prompt: &str) -> Value {
+    let mut body = body.clone();
+    let (list, assistant, content) = match protocol {
+        ModelProtocol::Gemini => ("contents", "model", "parts"),
+        _ => ("messages", "assistant", "content"),
+    };
+    // A prefill ending in whitespace is rejected; the model regenerates it
+    let text = if prefill { text.trim_end() } else { text };
+    let Some(messages) = body.get_mut(list).and_then(|m| m.as_array_mut()) else {
+        return body;
+    };
+
+    match messages.last_mut() {
+        // Extend an assistant turn the client already started
+        Some(last) if last.get("role").and_then(|r| r.as_str()) == Some(assistant) => match last.get_mut(content) {
+            Some(Value::String(existing)) => existing.push_str(text),
+            Some(Value::Array(parts)) if protocol == ModelProtocol::Gemini => parts.push(json!({"text": text})),
+            Some(Value::Array(blocks)) => blocks.push(json!({"type": "text", "text": text})),
+            _ => last[content] = json!(text),
+        },
+        _ if protocol == ModelProtocol::Gemini => messages.push(json!({"role": "model", "parts": [{"text": text}]})),
+        _ => messages.push(json!({"role": "assistant", "content": text})),
+    }
+    if !prefill {
+        messages.push(match protocol {
+            ModelProtocol::Gemini => json!({"role": "user", "parts": [{"text": prompt}]}),
+            _ => json!({"role": "user", "content": prompt}),
+        });
+    }
+    body
+}
+
+/// Top-level counts of `extra` added to those of `usage`; other fields of `usage` are kept
+fn sum_usage(usage: &Value, extra: &Map<String, Value>) -> Value {
+    let mut sum = usage.as_object().cloned().unwrap_or_default();
+    for (field, value) in extra {
+        if let Some(count) = value.as_u64() {
+            let current = sum.get(field).and_then(|v| v.as_u64()).unwrap_or(0);
+            sum.insert(field.clone(), json!(current + count));
+        }
+    }
+    Value::Object(sum)
+}
+
+/// Append the next segment's response to the one built so far
+pub fn stitch(response: &mut Value, next: Value, protocol: ModelProtocol) {
+    let usage_field = if protocol == ModelProtocol::Gemini { "usageMetadata" } else { "usage" };
+    if let Some(extra) = next.get(usage_field).and_then(|u| u.as_object()) {
+        response[usage_field] = sum_usage(response.get(usage_field).unwrap_or(&Value::Null), extra);
+    }
+
+    match protocol {
+        ModelProtocol::OpenAI => {
+            let more = next.pointer("/choices/0/message/content").and_then(|c| c.as_str()).unwrap_or_default();
+            if let Some(message) = response.pointer_mut("/choices/0/message") {
+                let joined = format!("{}{}", message["content"].as_str().unwrap_or_default(), more);
+                message["content"] = json!(joined);
+            }
+            if let (Some(choice), Some(reason)) = (response.pointer_mut("/choices/0"), next.pointer("/choices/0/finish_reason")) {
+                choice["finish_reason"] = reason.clone();
+            }
+        }
+        ModelProtocol::Claude => {
+            let blocks = next.get("content").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+            if let Some(content) = response.get_mut("content").and_then(|c| c.as_array_mut()) {
+                append_parts(content, blocks, |block| (block.get("type").and_then(|t| t.as_str()) == Some("text")).then_some("text"));
+            }
+            for field in ["stop_reason", "stop_sequence"] {
+                if let Some(value) = next.get(field) {
+                    response[field] = value.clone();
+                }
+            }
+        }
+        ModelProtocol::Gemini => {
+            let parts = candidate_parts(&next);
+            if let Some(content) = response.pointer_mut("/candidates/0/content/parts").and_then(|p| p.as_array_mut()) {
+                append_parts(content, parts, |part| (part.get("text").is_some() && part.get("thought").is_none()).then_some("text"));
+            }
+            if let (Some(candidate), Some(reason)) = (response.pointer_mut("/candidates/0"), next.pointer("/candidates/0/finishReason")) {
+                candidate["finishReason"] = reason.clone();
+            }
+        }
+    }
+}
+
+/// Append `more` to `parts`, joining the first of them onto the last existing one when both are text
+fn append_parts(parts: &mut Vec<Value>, more: Vec<Value>, text_field: impl Fn(&Value) -> Option<&'static str>) {
+    let mut more = more.into_iter().peekable();
+    if let (Some(last), Some(first)) = (parts.last_mut(), more.peek()) {
+        if let (Some(field), Some(_)) = (text_field(last), text_field(first)) {
+            let joined = format!("{}{}", last[field].as_str().unwrap_or_default(), first[field].as_str().unwrap_or_default());
+            last[field] = json!(joined);
+            more.next();
+        }
+    }
+    parts.extend(more);
+}
+
+/// Follows a stream across segments, rewriting each segment's events so the client sees one response
+#[derive(Debug)]
+struct Splicer {
+    protocol: ModelProtocol,
+    segment: u32,
+    /// Answer text of every segment so far
+    text: String,
+    reason: Option<String>,
+    tool_calls: bool,
+    /// Usage of the finished segments, summed
+    prior_usage: Map<String, Value>,
+    /// Usage reported in the current segment
+    segment_usage: Map<String, Value>,
+    /// OpenAI: the first segment's completion id, reused by the others
+    openai_id: Option<Value>,
+    /// Claude: the next free block index, the last block if it is text, and the
+    /// text block left open for the next segment to continue
+    next_index: u64,
+    last_text_block: Option<u64>,
+    pending_stop: Option<u64>,
+    base: Option<u64>,
+}
+
+impl Splicer {
+    fn new(protocol: ModelProtocol) -> Self {
+        Self {
+            protocol,
+            segment: 0,
+            text: String::new(),
+            reason: None,
+            tool_calls: false,
+            prior_usage: Map::new(),
+            segment_usage: Map::new(),
+            openai_id: None,
+            next_index: 0,
+            last_text_block: None,
+            pending_stop: None,
+            base: None,
+        }
+    }
+
+    /// Record the usage an event reports and, after the first segment, add the earlier segments' to it
+    fn track_usage(&mut self, usage: &mut Value) {
+        let Some(fields) = usage.as_object() else {
+            return;
+        };
+        self.segment_usage.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+        if self.segment > 0 {
+            *usage = sum_usage(&Value::Object(self.segment_usage.clone()), &self.prior_usage);
+        }
+    }
+
+    /// Rewrite an upstream event; the result may be empty (already seen) or carry an extra closing event
+    fn process(&mut self, event: Value) -> Vec<Value> {
+        match self.protocol {
+            ModelProtocol::OpenAI => self.process_openai(event),
+            ModelProtocol::Claude => self.process_claude(event),
+            ModelProtocol::Gemini => self.process_gemini(event),
+        }
+    }
+
+    fn process_openai(&mut self, mut event: Value) -> Vec<Value> {
+        match (&self.openai_id, event.get("id")) {
+            (None, Some(id)) => self.openai_id = Some(id.clone()),
+            (Some(id), Some(_)) => event["id"] = id.clone(),
+            _ => {}
+        }
+        if let Some(usage) = event.get_mut("usage").filter(|u| !u.is_null()) {
+            let mut usage = usage.take();
+            self.track_usage(&mut usage);
+            event["usage"] = usage;
+        }
+        if let Some(choice) = event.pointer("/choices/0") {
+            if let Some(text) = choice.pointer("/delta/content").and_then(|c| c.as_str()) {
+                self.text.push_str(text);
+            }
+            if choice.pointer("/delta/tool_calls").is_some_and(|t| !t.is_null()) {
+                self.tool_calls = true;
+            }
+            if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+                self.reason = Some(reason.to_string());
+            }
+        }
+        vec![event]
+    }
+
+    fn process_claude(&mut self, mut event: Value) -> Vec<Value> {
+        let kind = event.get("type").and_then(|t| t.as_str()).unwrap_or_default().to_string();
+        let mut out = Vec::new();
+
+        if self.segment > 0 {
+            if kind == "message_start" {
+                if let Some(mut usage) = event.pointer("/message/usage").cloned() {
+                    self.track_usage(&mut usage);
+                }
+                return out;
+            }
+            match event.get("index").and_then(|i| i.as_u64()) {
+                Some(index) => {
+                    if kind == "content_block_start" && self.base.is_none() {
+                        let is_text = event.pointer("/content_block/type").and_then(|t| t.as_str()) == Some("text");
+                        match self.pending_stop.take() {
+                            // The segment's first text block continues the one left open
+                            Some(open) if is_text && index == 0 => {
+                                self.base = Some(open);
+                                return out;
+                            }
+                            Some(open) => out.push(json!({"type": "content_block_stop", "index": open})),
+                            None => {}
+                        }
+                        self.base = Some(self.next_index);
+                    }
+                    event["index"] = json!(self.base.unwrap_or(self.next_index) + index);
+                }
+                None => {
+                    if let Some(open) = self.pending_stop.take() {
+                        out.push(json!({"type": "content_block_stop", "index": open}));
+                    }
+                }
+            }
+        }
+
+        match kind.as_str() {
+            "message_start" => {
+                if let Some(usage) = event.pointer_mut("/message/usage") {
+                    let mut tracked = usage.take();
+                    self.track_usage(&mut tracked);
+                    *usage = tracked;
+                }
+            }
+            "content_block_start" => {
+                let index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let block_type = event.pointer("/content_block/type").and_then(|t| t.as_str());
+                if matches!(block_type, Some("tool_use" | "server_tool_use")) {
+                    self.tool_calls = true;
+                }
+                self.last_text_block = (block_type == Some("text")).then_some(index);
+                self.next_index = self.next_index.max(index + 1);
+            }
+            "content_block_delta" => {
+                if let Some(text) = event.pointer("/delta/text").and_then(|t| t.as_str()) {
+                    self.text.push_str(text);
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = event.pointer("/delta/stop_reason").and_then(|r| r.as_str()) {
+                    self.reason = Some(reason.to_string());
+                }
+                if let Some(usage) = event.get_mut("usage") {
+                    let mut tracked = usage.take();
+                    self.track_usage(&mut tracked);
+                    *usage = tracked;
+                }
+            }
+            _ => {}
+        }
+        out.push(event);
+        out
+    }
+
+    fn process_gemini(&mut self, mut event: Value) -> Vec<Value> {
+        if let Some(usage) = event.get_mut("usageMetadata") {
+            let mut tracked = usage.take();
+            self.track_usage(&mut tracked);
+            *usage = tracked;
+        }
+        for part in candidate_parts(&event) {
+            if part.get("functionCall").is_some() {
+                self.tool_calls = true;
+            }
+            if let (Some(text), None) = (part.get("text").and_then(|t| t.as_str()), part.get("thought")) {
+                self.text.push_str(text);
+            }
+        }
+        if let Some(reason) = event.pointer("/candidates/0/finishReason").and_then(|r| r.as_str()) {
+            self.reason = Some(reason.to_string());
+        }
+        vec![event]
+    }
+
+    /// Events that end a segment: held back until it is known whether another segment follows
+    fn is_tail(&self, event: &Value) -> bool {
+        match self.protocol {
+            ModelProtocol::OpenAI => {
+                let choices = event.get("choices").and_then(|c| c.as_array());
+                choices.is_none_or(|c| c.is_empty())
+                    || choices.into_iter().flatten().any(|choice| choice.get("finish_reason").is_some_and(|r| !r.is_null()))
+            }
+            ModelProtocol::Claude => matches!(
+                event.get("type").and_then(|t| t.as_str()),
+                Some("content_block_stop" | "message_delta" | "message_stop")
+            ),
+            ModelProtocol::Gemini => event.pointer("/candidates/0/finishReason").is_some(),
+        }
+    }
+
+    /// What is left of a segment's tail when another segment follows: its content, without the ending
+    fn bridge(&mut self, tail: Vec<Value>) -> Vec<Value> {
+        let mut out = Vec::new();
+        for mut event in tail {
+            match self.protocol {
+                ModelProtocol::OpenAI => {
+                    let Some(choices) = event.get_mut("choices").and_then(|c| c.as_array_mut()).filter(|c| !c.is_empty()) else {
+                        continue;
+                    };
+                    let mut has_content = false;
+                    for choice in choices.iter_mut() {
+                        choice["finish_reason"] = Value::Null;
+                        has_content |= choice.get("delta").and_then(|d| d.as_object()).is_some_and(|d| !d.is_empty());
+                    }
+                    if let Some(fields) = event.as_object_mut() {
+                        fields.remove("usage");
+                    }
+                    if has_content {
+                        out.push(event);
+                    }
+                }
+                ModelProtocol::Claude => {
+                    if event.get("type").and_then(|t| t.as_str()) != Some("content_block_stop") {
+                        continue;
+                    }
+                    let index = event.get("index").and_then(|i| i.as_u64());
+                    if index.is_some() && index == self.last_text_block {
+                        self.pending_stop = index;
+                    } else {
+                        out.push(event);
+                    }
+                }
+                ModelProtocol::Gemini => {
+                    if let Some(candidate) = event.pointer_mut("/candidates/0").and_then(|c| c.as_object_mut()) {
+                        candidate.remove("finishReason");
+                    }
+                    if !candidate_parts(&event).is_empty() {
+                        out.push(event);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn next_segment(&mut self) {
+        let prior = sum_usage(&Value::Object(std::mem::take(&mut self.segment_usage)), &self.prior_usage);
+        self.prior_usage = prior.as_object().cloned().unwrap_or_default();
+        self.segment += 1;
+        self.reason = None;
+        self.base = None;
+    }
+}
+
+/// Stitch continuation segments onto a stream; `reopen(body)` starts the stream of a follow-up request
+pub fn continued<F, Fut>(first: ValueStream, protocol: ModelProtocol, body: Value, policy: ContinuationPolicy, reopen: F) -> ValueStream
+where
+    F: Fn(Value) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<ValueStream>> + Send,
+{
+    Box::pin(stream! {
+        let mut splicer = Splicer::new(protocol);
+        let mut current = first;
+        let mut continuations = 0;
+
+        loop {
+            let mut tail = Vec::new();
+            while let Some(item) = current.next().await {
+                let event = match item {
+                    Ok(event) => event,
+                    Err(e) => {
+                        for event in tail.drain(..) {
+                            yield Ok(event);
+                        }
+                        yield Err(e);
+                        return;
+                    }
+                };
+                for event in splicer.process(event) {
+                    if splicer.is_tail(&event) {
+                        tail.push(event);
+                        continue;
+                    }
+                    for held in tail.drain(..) {
+                        yield Ok(held);
+                    }
+                    yield Ok(event);
+                }
+            }
+
+            if policy.should_continue(splicer.reason.as_deref(), splicer.tool_calls, &splicer.text, continuations) {
+                continuations += 1;
+                info!("Answer from {} hit the token limit, continuing ({}/{})", policy.model, continuations, policy.config.max_continuations);
+                let request = continuation_request(&body, protocol, &splicer.text, policy.prefill, &policy.config.prompt);
+                match reopen(request).await {
+                    Ok(stream) => {
+                        for event in splicer.bridge(tail) {
+                            yield Ok(event);
+                        }
+                        splicer.next_segment();
+                        current = stream;
+                        continue;
+                    }
+                    Err(e) => warn!("Continuation request to {} failed, returning the truncated answer: {}", policy.model, e),
+                }
+            }
+            for event in tail {
+                yield Ok(event);
+            }
+            return;
+        }
+    })
+}
+
+/// Continues the wrapped adapter's truncated answers, buffered and streamed. Streams are
+/// rewritten, so raw stream passthrough is not offered
+pub struct ContinuingAdapter {
+    inner: Arc<dyn ApiServiceAdapter>,
+    config: AutoContinuationConfig,
+    prefill: bool,
+}
+
+impl ContinuingAdapter {
+    pub fn new(inner: Box<dyn ApiServiceAdapter>, config: AutoContinuationConfig, prefill: bool) -> Self {
+        Self { inner: Arc::from(inner), config, prefill }
+    }
+
+    fn policy(&self, model: &str) -> ContinuationPolicy {
+        ContinuationPolicy {
+            config: self.config.clone(),
+            prefill: self.prefill,
+            model: model.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ApiServiceAdapter for ContinuingAdapter {
+    fn protocol(&self) -> ModelProtocol {
+        self.inner.protocol()
+    }
+
+    async fn generate_content(&self, model: &str, request: ChatRequest, ctx: &RequestContext) -> Result<ChatResponse> {
+        let protocol = self.inner.protocol();
+        let body = request.into_protocol(protocol, Some(model))?;
+        let mut response = self.inner.generate_content(model, ChatRequest::new(protocol, body.clone()), ctx).await?;
+
+        let policy = self.policy(model);
+        let mut text = crate::logger::extract_text_from_response(response.body(), protocol.as_str());
+        let mut continuations = 0;
+        while policy.should_continue(finish_reason(response.body(), protocol), has_tool_calls(response.body(), protocol), &text, continuations) {
+            continuations += 1;
+            info!("Answer from {} hit the token limit, continuing ({}/{})", model, continuations, self.config.max_continuations);
+            let request = continuation_request(&body, protocol, &text, self.prefill, &self.config.prompt);
+            let next = match self.inner.generate_content(model, ChatRequest::new(protocol, request), ctx).await {
+                Ok(next) => next.into_body(),
+                Err(e) => {
+                    warn!("Continuation request to {} failed, returning the truncated answer: {}", model, e);
+                    break;
+                }
+            };
+            text.push_str(&crate::logger::extract_text_from_response(&next, protocol.as_str()));
+            stitch(response.body_mut(), next, protocol);
+        }
+        Ok(response)
+    }
+
+    async fn generate_content_stream(&self, model: &str, request: ChatRequest, ctx: &RequestContext) -> Result<ChatStream> {
+        let protocol = self.inner.protocol();
+        let body = request.into_protocol(protocol, Some(model))?;
+        let first = self.inner.generate_content_stream(model, ChatRequest::new(protocol, body.clone()), ctx).await?;
+
+        let (inner, model_name, ctx) = (self.inner.clone(), model.to_string(), ctx.clone());
+        let reopen = move |request: Value| {
+            let (inner, model, ctx) = (inner.clone(), model_name.clone(), ctx.clone());
+            async move { Ok(inner.generate_content_stream(&model, ChatRequest::new(protocol, request), &ctx).await?.into_chunks()) }
+        };
+        let policy = self.policy(model);
+        Ok(first.map(|protocol, chunks| continued(chunks, protocol, body, policy, reopen)))
+    }
+
+    async fn forward(&self, request: ForwardRequest, ctx: &RequestContext) -> Result<reqwest::Response> {
+        self.inner.forward(request, ctx).await
+    }
+
+    async fn list_models(&self) -> Result<ModelListResponse> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model(&self, model: &str) -> Result<Option<ModelInfo>> {
+        self.inner.get_model(model).await
+    }
+
+    async fn refresh_token(&self) -> Result<()> {
+        self.inner.refresh_token().await
+    }
+
+    fn upstream_url(&self) -> Option<String> {
+        self.inner.upstream_url()
+    }
+
+    async fn clear_cache(&self) -> usize {
+        self.inner.clear_cache().await
+    }
+}
@@ -0,0 +1,328 @@
+/*!
+ * Conversation Store
+ *
+ * Server-side chat history for thin clients. A request carrying an
+ * `x-conversation-id` header sends only its new messages; the proxy prepends
+ * the stored turns, forwards the whole conversation and appends the new
+ * messages and the assistant's reply to the store. History is trimmed here,
+ * in one place, instead of in every client. Turns are kept in the shared
+ * state store, so conversations follow the cluster backend (memory or Redis).
+ */
+
+use crate::cluster::SharedStore;
+use crate::common::ModelProtocol;
+use crate::config::ConversationStoreConfig;
+use anyhow::{Context, Result};
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+pub const CONVERSATION_HEADER: &str = "x-conversation-id";
+
+const MAX_ID_LEN: usize = 128;
+
+/// Conversation IDs are chosen by clients; keep them to characters safe in store keys
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+fn is_system(message: &Value) -> bool {
+    message.get("role").and_then(|r| r.as_str()) == Some("system")
+}
+
+/// Keep system messages and the newest `max_messages` others, starting at a user turn
+/// so no reply or tool result is left without what it answers
+pub fn trim(messages: Vec<Value>, max_messages: usize) -> Vec<Value> {
+    let (system, turns): (Vec<Value>, Vec<Value>) = messages.into_iter().partition(is_system);
+    let skip = turns.len().saturating_sub(max_messages);
+    let mut turns: Vec<Value> = turns.into_iter().skip(skip).collect();
+    let first_user = turns
+        .iter()
+        .position(|m| m.get("role").and_then(|r| r.as_str()) == Some("user") && !is_tool_result(m))
+        .unwrap_or(turns.len());
+    turns.drain(..first_user);
+    system.into_iter().chain(turns).collect()
+}
+
+/// Claude tool results travel in user messages
+fn is_tool_result(message: &Value) -> bool {
+    message
+        .get("content")
+        .and_then(|c| c.as_array())
+        .is_some_and(|blocks| blocks.iter().any(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result")))
+}
+
+/// Put the stored history in front of the request's messages and return the full conversation.
+/// A system message in the request replaces the stored ones
+pub fn with_history(body: &mut Value, history: Vec<Value>) -> Vec<Value> {
+    let new_messages = body.get("messages").and_then(|m| m.as_array()).cloned().unwrap_or_default();
+    let replaces_system = new_messages.iter().any(is_system);
+    let messages: Vec<Value> = history
+        .into_iter()
+        .filter(|m| !(replaces_system && is_system(m)))
+        .chain(new_messages)
+        .collect();
+    body["messages"] = json!(messages);
+    messages
+}
+
+/// The assistant message of a buffered response, in the request's protocol
+pub fn assistant_message(response: &Value, protocol: ModelProtocol) -> Option<Value> {
+    match protocol {
+        ModelProtocol::Claude => {
+            let content = response.get("content")?;
+            Some(json!({"role": "assistant", "content": content}))
+        }
+        _ => {
+            let message = response.pointer("/choices/0/message")?;
+            let mut stored = json!({"role": "assistant", "content": message.get("content").cloned().unwrap_or(Value::Null)});
+            if let Some(tool_calls) = message.get("tool_calls") {
+                stored["tool_calls"] = tool_calls.clone();
+            }
+            Some(stored)
+        }
+    }
+}
+
+/// Rebuilds the assistant message from a stream of chunks
+#[derive(Debug)]
+pub struct StreamedReply {
+    protocol: ModelProtocol,
+    text: String,
+    /// OpenAI: `tool_calls` by index; Claude: content blocks in order
+    parts: Vec<Value>,
+    /// Claude `input_json_delta` fragments of the open tool_use block
+    partial_json: String,
+}
+
+impl StreamedReply {
+    pub fn new(protocol: ModelProtocol) -> Self {
+        Self {
+            protocol,
+            text: String::new(),
+            parts: Vec::new(),
+            partial_json: String::new(),
+        }
+    }
+
+    pub fn observe(&mut self, chunk: &Value) {
+        match self.protocol {
+            ModelProtocol::Claude => self.observe_claude(chunk),
+            _ => self.observe_openai(chunk),
+        }
+    }
+
+    fn observe_openai(&mut self, chunk: &Value) {
+        let Some(delta) = chunk.pointer("/choices/0/delta") else {
+            return;
+        };
+        if let Some(text) = delta.get("content").and_then(|c| c.as_str()) {
+            self.text.push_str(text);
+        }
+        for call in delta.get("tool_calls").and_then(|t| t.as_array()).into_iter().flatten() {
+            let index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize;
+            while self.parts.len() <= index {
+                self.parts.push(json!({"type": "function", "function": {"name": "", "arguments": ""}}));
+            }
+            let stored = &mut self.parts[index];
+            if let Some(id) = call.get("id") {
+                stored["id"] = id.clone();
+            }
+            for field in ["name", "arguments"] {
+                if let Some(fragment) = call.pointer(&format!("/function/{}", field)).and_then(|v| v.as_str()) {
+                    let joined = format!("{}{}", stored["function"][field].as_str().unwrap_or_default(), fragment);
+                    stored["function"][field] = json!(joined);
+                }
+            }
+        }
+    }
+
+    fn observe_claude(&mut self, chunk: &Value) {
+        match chunk.get("type").and_then(|t| t.as_str()) {
+            Some("content_block_start") => {
+                if let Some(block) = chunk.get("content_block") {
+                    self.parts.push(block.clone());
+                    self.partial_json.clear();
+                }
+            }
+            Some("content_block_delta") => {
+                let Some(block) = self.parts.last_mut() else {
+                    return;
+                };
+                let delta = chunk.get("delta").unwrap_or(&Value::Null);
+                if let Some(json) = delta.get("partial_json").and_then(|j| j.as_str()) {
+                    self.partial_json.push_str(json);
+                    return;
+                }
+                // text_delta, thinking_delta and signature_delta extend the field they are named after
+                for field in ["text", "thinking", "signature"] {
+                    if let Some(fragment) = delta.get(field).and_then(|t| t.as_str()) {
+                        let joined = format!("{}{}", block[field].as_str().unwrap_or_default(), fragment);
+                        block[field] = json!(joined);
+                    }
+                }
+            }
+            Some("content_block_stop") => {
+                if let Some(block) = self.parts.last_mut() {
+                    if !self.partial_json.is_empty() {
+                        block["input"] = serde_json::from_str(&self.partial_json).unwrap_or_else(|_| json!({}));
+                        self.partial_json.clear();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The assistant message, `None` if nothing was streamed
+    pub fn into_message(self) -> Option<Value> {
+        match self.protocol {
+            ModelProtocol::Claude => {
+                (!self.parts.is_empty()).then(|| json!({"role": "assistant", "content": self.parts}))
+            }
+            _ => {
+                if self.text.is_empty() && self.parts.is_empty() {
+                    return None;
+                }
+                let mut message = json!({"role": "assistant", "content": self.text});
+                if !self.parts.is_empty() {
+                    message["tool_calls"] = json!(self.parts);
+                }
+                Some(message)
+            }
+        }
+    }
+}
+
+/// A request's conversation: its ID and every message sent upstream
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    pub id: String,
+    pub messages: Vec<Value>,
+    /// Client ID the conversation is indexed under, so it can be purged with the client's data
+    pub owner: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct ConversationStore {
+    store: Arc<dyn SharedStore>,
+    config: ConversationStoreConfig,
+}
+
+impl ConversationStore {
+    pub fn new(store: Arc<dyn SharedStore>, config: &ConversationStoreConfig) -> Self {
+        Self {
+            store,
+            config: config.clone(),
+        }
+    }
+
+    fn key(id: &str) -> String {
+        format!("conversation:{}", id)
+    }
+
+    fn owner_key(owner: &str) -> String {
+        format!("conversation_owner:{}", owner)
+    }
+
+    /// IDs of the conversations stored for an owner (some may have expired since)
+    pub async fn owned_by(&self, owner: &str) -> Result<Vec<String>> {
+        match self.store.get(&Self::owner_key(owner)).await? {
+            Some(stored) => serde_json::from_str(&stored).context("Stored conversation index is not an ID list"),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn index(&self, owner: &str, id: &str) -> Result<()> {
+        let mut ids = self.owned_by(owner).await?;
+        if !ids.iter().any(|known| known == id) {
+            ids.push(id.to_string());
+        }
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        self.store.set(&Self::owner_key(owner), &serde_json::to_string(&ids)?, Some(ttl)).await
+    }
+
+    /// Delete every conversation of an owner; returns how many were indexed
+    pub async fn purge_owner(&self, owner: &str) -> Result<usize> {
+        let ids = self.owned_by(owner).await?;
+        for id in &ids {
+            self.store.delete(&Self::key(id)).await?;
+        }
+        self.store.delete(&Self::owner_key(owner)).await?;
+        Ok(ids.len())
+    }
+
+    /// Stored messages of a conversation; empty for a new one
+    pub async fn history(&self, id: &str) -> Result<Vec<Value>> {
+        match self.store.get(&Self::key(id)).await? {
+            Some(stored) => serde_json::from_str(&stored).context("Stored conversation is not a message list"),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Start a conversation turn: the stored history goes in front of the request's messages
+    pub async fn begin(&self, id: &str, body: &mut Value) -> Result<Conversation> {
+        let history = self.history(id).await?;
+        Ok(Conversation {
+            id: id.to_string(),
+            messages: with_history(body, history),
+            owner: None,
+        })
+    }
+
+    /// Store the conversation plus the assistant's reply
+    pub async fn finish(&self, conversation: Conversation, reply: Option<Value>) {
+        let Some(reply) = reply else {
+            return;
+        };
+        let Conversation { id, mut messages, owner } = conversation;
+        messages.push(reply);
+        if let Err(e) = self.save(&id, messages).await {
+            warn!("Failed to store conversation {}: {}", id, e);
+            return;
+        }
+        if let Some(owner) = owner {
+            if let Err(e) = self.index(&owner, &id).await {
+                warn!("Failed to index conversation {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Store the conversation, trimmed, with a fresh TTL
+    pub async fn save(&self, id: &str, messages: Vec<Value>) -> Result<()> {
+        let messages = trim(messages, self.config.max_messages);
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        self.store.set(&Self::key(id), &serde_json::to_string(&messages)?, Some(ttl)).await
+    }
+
+    /// Store the conversation plus the reply once a stream completes; an interrupted
+    /// or empty stream leaves the stored history as it was
+    pub fn record_stream(
+        &self,
+        conversation: Conversation,
+        protocol: ModelProtocol,
+        inner: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+        let store = self.clone();
+        Box::pin(async_stream::stream! {
+            let mut inner = inner;
+            let mut reply = StreamedReply::new(protocol);
+            let mut failed = false;
+            while let Some(item) = inner.next().await {
+                match item {
+                    Ok(ref chunk) => reply.observe(chunk),
+                    Err(_) => failed = true,
+                }
+                yield item;
+            }
+            if !failed {
+                store.finish(conversation, reply.into_message()).await;
+            }
+        })
+    }
+}
@@ -5,8 +5,11 @@
  */
 
 use crate::common::*;
+use crate::stream_errors::UpstreamStreamError;
+use crate::stream_recovery::ValueStream;
 use anyhow::Result;
-use serde_json::Value;
+use futures::StreamExt;
+use serde_json::{json, Value};
 use uuid::Uuid;
 
 /// Conversion type
@@ -71,12 +74,8 @@ pub fn convert_data(
         (ConversionType::Response, ModelProtocol::Claude, ModelProtocol::Gemini) => {
             to_claude_response_from_gemini(data, model)
         }
-        (ConversionType::StreamChunk, ModelProtocol::Claude, ModelProtocol::OpenAI) => {
-            to_claude_stream_chunk_from_openai(data, model)
-        }
-        (ConversionType::StreamChunk, ModelProtocol::Claude, ModelProtocol::Gemini) => {
-            to_claude_stream_chunk_from_gemini(data, model)
-        }
+        // Claude events for a chunk depend on the blocks already open, so streams
+        // are converted whole by `ChatStream::into_protocol`
         (ConversionType::ModelList, ModelProtocol::Claude, ModelProtocol::OpenAI) => {
             to_claude_model_list_from_openai(data)
         }
@@ -91,6 +90,12 @@ pub fn convert_data(
         (ConversionType::Request, ModelProtocol::Gemini, ModelProtocol::Claude) => {
             to_gemini_request_from_claude(data)
         }
+        (ConversionType::Response, ModelProtocol::Gemini, ModelProtocol::OpenAI) => {
+            to_gemini_response_from_openai(data, model)
+        }
+        (ConversionType::Response, ModelProtocol::Gemini, ModelProtocol::Claude) => {
+            to_gemini_response_from_claude(data, model)
+        }
 
         _ => anyhow::bail!(
             "Unsupported conversion: {:?} from {:?} to {:?}",
@@ -101,17 +106,475 @@ pub fn convert_data(
     }
 }
 
+/// Chat request body, in the protocol it is written in
+///
+/// Adapters receive this instead of a bare `Value` and convert it to their
+/// own protocol exactly once, so a body can no longer reach an upstream in
+/// the wrong format. The protocol is the variant: it cannot be changed apart
+/// from the body, and code handling one protocol's bodies matches on it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatRequest {
+    OpenAI(Value),
+    Claude(Value),
+    Gemini(Value),
+}
+
+impl ChatRequest {
+    /// A body in `protocol`, for callers that only know the protocol at run time
+    pub fn new(protocol: ModelProtocol, body: Value) -> Self {
+        match protocol {
+            ModelProtocol::OpenAI => Self::OpenAI(body),
+            ModelProtocol::Claude => Self::Claude(body),
+            ModelProtocol::Gemini => Self::Gemini(body),
+        }
+    }
+
+    pub fn protocol(&self) -> ModelProtocol {
+        match self {
+            Self::OpenAI(_) => ModelProtocol::OpenAI,
+            Self::Claude(_) => ModelProtocol::Claude,
+            Self::Gemini(_) => ModelProtocol::Gemini,
+        }
+    }
+
+    pub fn body(&self) -> &Value {
+        match self {
+            Self::OpenAI(body) | Self::Claude(body) | Self::Gemini(body) => body,
+        }
+    }
+
+    pub fn body_mut(&mut self) -> &mut Value {
+        match self {
+            Self::OpenAI(body) | Self::Claude(body) | Self::Gemini(body) => body,
+        }
+    }
+
+    pub fn into_body(self) -> Value {
+        match self {
+            Self::OpenAI(body) | Self::Claude(body) | Self::Gemini(body) => body,
+        }
+    }
+
+    /// The body in `protocol`, converted only if it is written in another one, with
+    /// sampling parameters clamped to the ranges `protocol`'s backends accept
+    pub fn into_protocol(mut self, protocol: ModelProtocol, model: Option<&str>) -> Result<Value> {
+        let from = self.protocol();
+        for (pointer, value, _) in parameter_clamps(self.body(), from, protocol) {
+            if let Some(field) = self.body_mut().pointer_mut(pointer) {
+                *field = value;
+            }
+        }
+        let mut body = convert_data(self.into_body(), ConversionType::Request, from, protocol, model)?;
+        // Gemini takes the model and streaming from the URL; the server keeps them in the body
+        if let (ModelProtocol::Gemini, Some(fields)) = (protocol, body.as_object_mut()) {
+            fields.remove("model");
+            fields.remove("stream");
+        }
+        crate::transcripts::record_upstream_request(&body);
+        Ok(body)
+    }
+}
+
+/// Sampling parameters of a request, by name and where `protocol` keeps them
+fn sampling_parameters(protocol: ModelProtocol) -> &'static [(&'static str, &'static str)] {
+    match protocol {
+        ModelProtocol::OpenAI => &[
+            ("temperature", "/temperature"),
+            ("top_p", "/top_p"),
+            ("max_tokens", "/max_tokens"),
+            ("max_tokens", "/max_completion_tokens"),
+        ],
+        ModelProtocol::Claude => &[
+            ("temperature", "/temperature"),
+            ("top_p", "/top_p"),
+            ("top_k", "/top_k"),
+            ("max_tokens", "/max_tokens"),
+        ],
+        ModelProtocol::Gemini => &[
+            ("temperature", "/generationConfig/temperature"),
+            ("top_p", "/generationConfig/topP"),
+            ("top_k", "/generationConfig/topK"),
+            ("max_tokens", "/generationConfig/maxOutputTokens"),
+        ],
+    }
+}
+
+/// The range `backend` accepts for a sampling parameter (`None` for no upper bound)
+fn parameter_range(parameter: &str, backend: ModelProtocol) -> Option<(f64, Option<f64>)> {
+    match (parameter, backend) {
+        ("temperature", ModelProtocol::Claude) => Some((0.0, Some(1.0))),
+        ("temperature", _) => Some((0.0, Some(2.0))),
+        ("top_p", _) => Some((0.0, Some(1.0))),
+        ("top_k", ModelProtocol::OpenAI) => None,
+        ("top_k", _) | ("max_tokens", _) => Some((1.0, None)),
+        _ => None,
+    }
+}
+
+/// Sampling parameters outside the range `to`'s backends accept: where each is, its
+/// clamped value and a note for the client
+pub fn parameter_clamps(body: &Value, from: ModelProtocol, to: ModelProtocol) -> Vec<(&'static str, Value, String)> {
+    let mut clamps = Vec::new();
+    for &(parameter, pointer) in sampling_parameters(from) {
+        let (Some(value), Some((min, max))) = (body.pointer(pointer).and_then(|v| v.as_f64()), parameter_range(parameter, to)) else {
+            continue;
+        };
+        let clamped = max.map_or(value, |max| value.min(max)).max(min);
+        if clamped == value {
+            continue;
+        }
+        let bound = if clamped > value { "minimum" } else { "maximum" };
+        let field = &pointer[1..];
+        let note = format!("{} {} is outside the range {} accepts; clamped to the {} of {}", field, value, to_name(to), bound, clamped);
+        // Integers stay integers
+        let clamped = if body.pointer(pointer).is_some_and(|v| v.is_i64() || v.is_u64()) {
+            json!(clamped as i64)
+        } else {
+            json!(clamped)
+        };
+        clamps.push((pointer, clamped, note));
+    }
+    clamps
+}
+
+fn to_name(protocol: ModelProtocol) -> &'static str {
+    match protocol {
+        ModelProtocol::OpenAI => "OpenAI",
+        ModelProtocol::Claude => "Claude",
+        ModelProtocol::Gemini => "Gemini",
+    }
+}
+
+/// Response header listing lossy-conversion warnings, one value per warning
+pub const WARNINGS_HEADER: &str = "x-aiproxy-warnings";
+/// Field added to JSON responses with the same warnings
+pub const WARNINGS_FIELD: &str = "aiproxy_warnings";
+
+/// OpenAI request fields Claude requests do not carry (see `OPENAI_GEMINI_SHARED` for Gemini)
+const OPENAI_UNMAPPED: &[&str] = &[
+    "/logit_bias",
+    "/frequency_penalty",
+    "/presence_penalty",
+    "/seed",
+    "/logprobs",
+    "/top_logprobs",
+    "/response_format",
+    "/reasoning_effort",
+    "/max_completion_tokens",
+];
+
+/// Claude request fields neither OpenAI nor Gemini requests carry
+const CLAUDE_UNMAPPED: &[&str] = &["/thinking", "/service_tier"];
+
+/// Gemini request fields Claude requests do not carry (see `OPENAI_GEMINI_SHARED` for OpenAI)
+const GEMINI_UNMAPPED: &[&str] = &[
+    "/safetySettings",
+    "/cachedContent",
+    "/generationConfig/candidateCount",
+    "/generationConfig/presencePenalty",
+    "/generationConfig/frequencyPenalty",
+    "/generationConfig/seed",
+    "/generationConfig/responseMimeType",
+    "/generationConfig/responseSchema",
+    "/generationConfig/responseLogprobs",
+    "/generationConfig/thinkingConfig",
+];
+
+/// Generation parameters OpenAI and Gemini requests both carry, as (OpenAI, Gemini) pointers
+const OPENAI_GEMINI_SHARED: &[(&str, &str)] = &[
+    ("/n", "/generationConfig/candidateCount"),
+    ("/frequency_penalty", "/generationConfig/frequencyPenalty"),
+    ("/presence_penalty", "/generationConfig/presencePenalty"),
+    ("/seed", "/generationConfig/seed"),
+    ("/response_format", "/generationConfig/responseMimeType"),
+    ("/response_format", "/generationConfig/responseSchema"),
+];
+
+/// Whether the field at `pointer` survives converting from `from` to `to`
+fn carried(pointer: &str, from: ModelProtocol, to: ModelProtocol) -> bool {
+    OPENAI_GEMINI_SHARED.iter().any(|(openai, gemini)| match (from, to) {
+        (ModelProtocol::OpenAI, ModelProtocol::Gemini) => *openai == pointer,
+        (ModelProtocol::Gemini, ModelProtocol::OpenAI) => *gemini == pointer,
+        _ => false,
+    })
+}
+
+/// Tools without an equivalent in the other protocols, by their `type` or Gemini key
+fn unmapped_tools(body: &Value, from: ModelProtocol) -> Vec<String> {
+    let tools = body.get("tools").and_then(|t| t.as_array()).into_iter().flatten();
+    tools
+        .enumerate()
+        .filter_map(|(index, tool)| {
+            let kind = match from {
+                ModelProtocol::OpenAI => tool
+                    .get("type")
+                    .and_then(|t| t.as_str())
+                    .filter(|t| !matches!(*t, "function" | "code_interpreter" | "code_execution"))?
+                    .to_string(),
+                ModelProtocol::Claude => tool
+                    .get("type")
+                    .and_then(|t| t.as_str())
+                    .filter(|t| tool.get("input_schema").is_none() && *t != crate::convert_detailed::CLAUDE_CODE_EXECUTION_TOOL)?
+                    .to_string(),
+                ModelProtocol::Gemini => tool
+                    .as_object()?
+                    .keys()
+                    .find(|key| !matches!(key.as_str(), "functionDeclarations" | "codeExecution"))?
+                    .clone(),
+            };
+            Some(format!("tools[{}] ({})", index, kind))
+        })
+        .collect()
+}
+
+/// Tool parameter schemas losing keywords Gemini's function declarations do not accept
+fn gemini_tool_schema_warnings(body: &Value, from: ModelProtocol) -> Vec<String> {
+    let pointer = match from {
+        ModelProtocol::OpenAI => "/function/parameters",
+        ModelProtocol::Claude => "/input_schema",
+        ModelProtocol::Gemini => return Vec::new(),
+    };
+    let tools = body.get("tools").and_then(|t| t.as_array()).into_iter().flatten();
+    tools
+        .enumerate()
+        .filter_map(|(index, tool)| {
+            let dropped = crate::gemini_schema::parameters(tool.pointer(pointer)?)?.dropped;
+            if dropped.is_empty() {
+                return None;
+            }
+            let name = tool.pointer("/function/name").or_else(|| tool.get("name")).and_then(|n| n.as_str()).unwrap_or_default();
+            let dropped: Vec<String> = dropped.into_iter().collect();
+            Some(format!(
+                "tools[{}] ({}) parameters use {} which Gemini does not support; dropped from the schema",
+                index,
+                name,
+                dropped.join(", ")
+            ))
+        })
+        .collect()
+}
+
+/// Request fields converting from `from` to `to` drops or approximates
+pub fn conversion_warnings(body: &Value, from: ModelProtocol, to: ModelProtocol) -> Vec<String> {
+    let clamped = parameter_clamps(body, from, to).into_iter().map(|(_, _, note)| note);
+    if from == to {
+        return clamped.collect();
+    }
+    let target = to_name(to);
+    let unmapped = match from {
+        ModelProtocol::OpenAI => OPENAI_UNMAPPED,
+        ModelProtocol::Claude => CLAUDE_UNMAPPED,
+        ModelProtocol::Gemini => GEMINI_UNMAPPED,
+    };
+
+    let mut warnings: Vec<String> = clamped.collect();
+    warnings.extend(
+        unmapped
+            .iter()
+            .filter(|pointer| !carried(pointer, from, to) && body.pointer(pointer).is_some_and(|v| !v.is_null()))
+            .map(|pointer| format!("{} has no {} equivalent and was dropped", &pointer[1..], target)),
+    );
+    warnings.extend(
+        unmapped_tools(body, from)
+            .into_iter()
+            .map(|tool| format!("{} has no {} equivalent and was dropped", tool, target)),
+    );
+
+    // Approximations
+    if from == ModelProtocol::OpenAI && to != ModelProtocol::Gemini && body.get("n").and_then(|n| n.as_u64()).is_some_and(|n| n > 1) {
+        warnings.push(format!("n > 1 is not supported by {}; a single choice is returned", target));
+    }
+    let max_tokens = body.get("max_tokens").or_else(|| body.pointer("/generationConfig/maxOutputTokens"));
+    if to == ModelProtocol::Claude && max_tokens.is_none() {
+        warnings.push("max_tokens is required by Claude; the default was used".to_string());
+    }
+    if let Some(schema) = body.pointer("/response_format/json_schema/schema").filter(|_| to == ModelProtocol::Gemini) {
+        let dropped = crate::gemini_schema::to_gemini(schema).dropped;
+        if !dropped.is_empty() {
+            let dropped: Vec<String> = dropped.into_iter().collect();
+            warnings.push(format!("response_format json_schema uses {} which Gemini does not support; dropped from the schema", dropped.join(", ")));
+        }
+    }
+    if to == ModelProtocol::Gemini {
+        warnings.extend(gemini_tool_schema_warnings(body, from));
+    }
+    let top_k = body.get("top_k").or_else(|| body.pointer("/generationConfig/topK"));
+    if from != ModelProtocol::OpenAI && to == ModelProtocol::OpenAI && top_k.is_some() {
+        warnings.push("top_k is not supported by OpenAI; dropped".to_string());
+    }
+    warnings
+}
+
+/// Chat response body, in the protocol it is written in
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatResponse {
+    OpenAI(Value),
+    Claude(Value),
+    Gemini(Value),
+}
+
+impl ChatResponse {
+    /// A body in `protocol`, for callers that only know the protocol at run time
+    pub fn new(protocol: ModelProtocol, body: Value) -> Self {
+        match protocol {
+            ModelProtocol::OpenAI => Self::OpenAI(body),
+            ModelProtocol::Claude => Self::Claude(body),
+            ModelProtocol::Gemini => Self::Gemini(body),
+        }
+    }
+
+    pub fn protocol(&self) -> ModelProtocol {
+        match self {
+            Self::OpenAI(_) => ModelProtocol::OpenAI,
+            Self::Claude(_) => ModelProtocol::Claude,
+            Self::Gemini(_) => ModelProtocol::Gemini,
+        }
+    }
+
+    pub fn body(&self) -> &Value {
+        match self {
+            Self::OpenAI(body) | Self::Claude(body) | Self::Gemini(body) => body,
+        }
+    }
+
+    pub fn body_mut(&mut self) -> &mut Value {
+        match self {
+            Self::OpenAI(body) | Self::Claude(body) | Self::Gemini(body) => body,
+        }
+    }
+
+    pub fn into_body(self) -> Value {
+        match self {
+            Self::OpenAI(body) | Self::Claude(body) | Self::Gemini(body) => body,
+        }
+    }
+
+    /// The body in `protocol`, converted only if it is written in another one
+    pub fn into_protocol(self, protocol: ModelProtocol, model: Option<&str>) -> Result<Value> {
+        let from = self.protocol();
+        let body = self.into_body();
+        crate::transcripts::record_upstream_response(&body);
+        convert_data(body, ConversionType::Response, from, protocol, model)
+    }
+}
+
+/// Streamed chunks, in the protocol they are written in
+pub enum ChatStream {
+    OpenAI(ValueStream),
+    Claude(ValueStream),
+    Gemini(ValueStream),
+}
+
+impl ChatStream {
+    /// Chunks in `protocol`, for callers that only know the protocol at run time
+    pub fn new(protocol: ModelProtocol, chunks: ValueStream) -> Self {
+        match protocol {
+            ModelProtocol::OpenAI => Self::OpenAI(chunks),
+            ModelProtocol::Claude => Self::Claude(chunks),
+            ModelProtocol::Gemini => Self::Gemini(chunks),
+        }
+    }
+
+    pub fn protocol(&self) -> ModelProtocol {
+        match self {
+            Self::OpenAI(_) => ModelProtocol::OpenAI,
+            Self::Claude(_) => ModelProtocol::Claude,
+            Self::Gemini(_) => ModelProtocol::Gemini,
+        }
+    }
+
+    pub fn into_chunks(self) -> ValueStream {
+        match self {
+            Self::OpenAI(chunks) | Self::Claude(chunks) | Self::Gemini(chunks) => chunks,
+        }
+    }
+
+    /// The chunks rewritten by `f`, which keeps them in the same protocol
+    pub fn map(self, f: impl FnOnce(ModelProtocol, ValueStream) -> ValueStream) -> Self {
+        let protocol = self.protocol();
+        Self::new(protocol, f(protocol, self.into_chunks()))
+    }
+
+    /// The chunks in `protocol`, converted as they arrive if written in another one
+    pub fn into_protocol(self, protocol: ModelProtocol, model: Option<&str>) -> Result<ValueStream> {
+        let model = model.unwrap_or("unknown").to_string();
+        match (self, protocol) {
+            (Self::Claude(chunks), ModelProtocol::OpenAI) => {
+                let mut converter = crate::convert_detailed::ClaudeStreamToOpenAI::new(&model);
+                Ok(Box::pin(chunks.filter_map(move |item| {
+                    let item = match item {
+                        Ok(event) => match UpstreamStreamError::from_claude_event(&event) {
+                            Some(error) => Some(Err(error.into())),
+                            None => converter.convert(&event).map(Ok),
+                        },
+                        Err(e) => Some(Err(e)),
+                    };
+                    futures::future::ready(item)
+                })))
+            }
+            (Self::Gemini(chunks), ModelProtocol::OpenAI) => {
+                let id = format!("chatcmpl-{}", Uuid::new_v4());
+                Ok(Box::pin(chunks.map(move |item| {
+                    let chunk = item?;
+                    match UpstreamStreamError::from_gemini_chunk(&chunk) {
+                        Some(error) => Err(error.into()),
+                        None => Ok(crate::convert_detailed::gemini_chunk_to_openai(&chunk, &id, &model)),
+                    }
+                })))
+            }
+            (Self::OpenAI(chunks), ModelProtocol::Gemini) => {
+                let mut converter = crate::convert_detailed::OpenAIStreamToGemini::new();
+                Ok(Box::pin(chunks.filter_map(move |item| {
+                    futures::future::ready(match item {
+                        Ok(chunk) => converter.convert(&chunk).map(Ok),
+                        Err(e) => Some(Err(e)),
+                    })
+                })))
+            }
+            (Self::OpenAI(mut chunks), ModelProtocol::Claude) => {
+                let mut converter = crate::convert_detailed::OpenAIStreamToClaude::new(&model);
+                Ok(Box::pin(async_stream::stream! {
+                    while let Some(item) = chunks.next().await {
+                        match item {
+                            Ok(chunk) => {
+                                for event in converter.convert(&chunk) {
+                                    yield Ok(event);
+                                }
+                            }
+                            // A failed stream is not closed as if it had finished
+                            Err(e) => {
+                                yield Err(e);
+                                return;
+                            }
+                        }
+                    }
+                    for event in converter.finish() {
+                        yield Ok(event);
+                    }
+                }))
+            }
+            // By way of OpenAI chunks
+            (Self::Claude(chunks), ModelProtocol::Gemini) => {
+                let chunks = Self::Claude(chunks).into_protocol(ModelProtocol::OpenAI, Some(&model))?;
+                Self::OpenAI(chunks).into_protocol(ModelProtocol::Gemini, Some(&model))
+            }
+            (Self::Gemini(chunks), ModelProtocol::Claude) => {
+                let chunks = Self::Gemini(chunks).into_protocol(ModelProtocol::OpenAI, Some(&model))?;
+                Self::OpenAI(chunks).into_protocol(ModelProtocol::Claude, Some(&model))
+            }
+            // Already in `protocol`
+            (stream, _) => Ok(stream.into_chunks()),
+        }
+    }
+}
+
 // Conversion functions using detailed implementations
 fn to_openai_request_from_gemini(data: Value) -> Result<Value> {
-    // Gemini to OpenAI request is reverse of OpenAI to Gemini
-    // For now, pass through
-    Ok(data)
+    crate::convert_detailed::gemini_request_to_openai(data)
 }
 
 fn to_openai_request_from_claude(data: Value) -> Result<Value> {
-    // Claude to OpenAI request is reverse of OpenAI to Claude
-    // For now, pass through
-    Ok(data)
+    crate::convert_detailed::claude_request_to_openai(data)
 }
 
 fn to_openai_response_from_gemini(data: Value, model: Option<&str>) -> Result<Value> {
@@ -122,25 +585,23 @@ fn to_openai_response_from_claude(data: Value, model: Option<&str>) -> Result<Va
     crate::convert_detailed::claude_response_to_openai(data, model.unwrap_or("claude-3-opus"))
 }
 
-fn to_openai_stream_chunk_from_gemini(_data: Value, model: Option<&str>) -> Result<Value> {
-    // TODO: Implement conversion
-    Ok(serde_json::json!({
-        "id": format!("chatcmpl-{}", Uuid::new_v4()),
-        "object": "chat.completion.chunk",
-        "created": chrono::Utc::now().timestamp(),
-        "model": model.unwrap_or("unknown"),
-        "choices": []
-    }))
+fn to_openai_stream_chunk_from_gemini(data: Value, model: Option<&str>) -> Result<Value> {
+    let id = format!("chatcmpl-{}", Uuid::new_v4());
+    Ok(crate::convert_detailed::gemini_chunk_to_openai(&data, &id, model.unwrap_or("unknown")))
 }
 
-fn to_openai_stream_chunk_from_claude(_data: Value, model: Option<&str>) -> Result<Value> {
-    // TODO: Implement conversion
-    Ok(serde_json::json!({
-        "id": format!("chatcmpl-{}", Uuid::new_v4()),
-        "object": "chat.completion.chunk",
-        "created": chrono::Utc::now().timestamp(),
-        "model": model.unwrap_or("unknown"),
-        "choices": []
+fn to_openai_stream_chunk_from_claude(data: Value, model: Option<&str>) -> Result<Value> {
+    // A lone event has no stream state; use ChatStream to convert a whole stream
+    let model = model.unwrap_or("unknown");
+    let mut converter = crate::convert_detailed::ClaudeStreamToOpenAI::new(model);
+    Ok(converter.convert(&data).unwrap_or_else(|| {
+        serde_json::json!({
+            "id": format!("chatcmpl-{}", Uuid::new_v4()),
+            "object": "chat.completion.chunk",
+            "created": chrono::Utc::now().timestamp(),
+            "model": model,
+            "choices": []
+        })
     }))
 }
 
@@ -165,47 +626,17 @@ fn to_claude_request_from_openai(data: Value) -> Result<Value> {
 }
 
 fn to_claude_request_from_gemini(data: Value) -> Result<Value> {
-    // Gemini to Claude: convert via intermediate format if needed
-    // For now, pass through
-    Ok(data)
+    crate::convert_detailed::gemini_request_to_claude(data)
 }
 
-fn to_claude_response_from_openai(_data: Value, model: Option<&str>) -> Result<Value> {
-    // OpenAI to Claude response is reverse
-    // For now, return basic structure
-    Ok(serde_json::json!({
-        "id": format!("msg_{}", Uuid::new_v4()),
-        "type": "message",
-        "role": "assistant",
-        "content": [],
-        "model": model.unwrap_or("claude-3-opus"),
-        "stop_reason": "end_turn",
-        "usage": {}
-    }))
+fn to_claude_response_from_openai(data: Value, model: Option<&str>) -> Result<Value> {
+    crate::convert_detailed::openai_response_to_claude(data, model.unwrap_or("claude-3-opus"))
 }
 
 fn to_claude_response_from_gemini(data: Value, model: Option<&str>) -> Result<Value> {
     crate::convert_detailed::gemini_response_to_claude(data, model.unwrap_or("claude-3-opus"))
 }
 
-fn to_claude_stream_chunk_from_openai(_data: Value, _model: Option<&str>) -> Result<Value> {
-    // TODO: Implement conversion
-    Ok(serde_json::json!({
-        "type": "content_block_delta",
-        "index": 0,
-        "delta": {}
-    }))
-}
-
-fn to_claude_stream_chunk_from_gemini(_data: Value, _model: Option<&str>) -> Result<Value> {
-    // TODO: Implement conversion
-    Ok(serde_json::json!({
-        "type": "content_block_delta",
-        "index": 0,
-        "delta": {}
-    }))
-}
-
 fn to_claude_model_list_from_openai(_data: Value) -> Result<Value> {
     // TODO: Implement conversion
     Ok(serde_json::json!({
@@ -228,3 +659,18 @@ fn to_gemini_request_from_claude(data: Value) -> Result<Value> {
     crate::convert_detailed::claude_request_to_gemini(data)
 }
 
+fn to_gemini_response_from_openai(data: Value, model: Option<&str>) -> Result<Value> {
+    let mut response = crate::unified::UnifiedResponse::from_openai(data)?;
+    if let Some(model) = model {
+        response.model = model.to_string();
+    }
+    Ok(response.into_gemini())
+}
+
+fn to_gemini_response_from_claude(data: Value, model: Option<&str>) -> Result<Value> {
+    let mut response = crate::unified::UnifiedResponse::from_claude(data)?;
+    if let Some(model) = model {
+        response.model = model.to_string();
+    }
+    Ok(response.into_gemini())
+}
@@ -1,544 +1,622 @@
 /*!
  * Detailed Format Conversion Implementations
  *
- * Complete conversion logic between OpenAI, Claude, and Gemini formats.
+ * Conversions between OpenAI, Claude, and Gemini formats: requests and
+ * responses go through the unified form, stream chunks are rewritten here.
  */
 
-use crate::common::*;
+use crate::unified::{FinishReason, UnifiedRequest, UnifiedResponse};
 use anyhow::Result;
 use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 
-const DEFAULT_MAX_TOKENS: u32 = 8192;
-const DEFAULT_GEMINI_MAX_TOKENS: u32 = 65536;
-const DEFAULT_TEMPERATURE: f32 = 1.0;
-const DEFAULT_TOP_P: f32 = 0.9;
+/// `max_tokens` sent to Claude when the client gave none; Claude requires one
+pub const DEFAULT_MAX_TOKENS: u32 = 8192;
 
 // ============================================================================
-// OpenAI <-> Gemini Conversions
+// Gemini Finish Reasons
 // ============================================================================
 
-pub fn openai_request_to_gemini(openai_req: Value) -> Result<Value> {
-    let mut gemini_req = json!({});
-    
-    // Extract system messages
-    let (system_instruction, non_system_messages) = extract_system_messages(&openai_req)?;
-    
-    if let Some(system) = system_instruction {
-        gemini_req["systemInstruction"] = system;
-    }
-    
-    // Convert messages to Gemini contents
-    let mut contents = Vec::new();
-    let mut last_role = String::new();
-    let mut accumulated_parts = Vec::new();
-    
-    for msg in non_system_messages {
-        let role = msg.get("role")
-            .and_then(|r| r.as_str())
-            .unwrap_or("user");
-        
-        let gemini_role = if role == "assistant" { "model" } else { role };
-        
-        // Handle tool responses
-        if role == "tool" {
-            if !accumulated_parts.is_empty() {
-                contents.push(json!({
-                    "role": last_role,
-                    "parts": accumulated_parts
-                }));
-                accumulated_parts = Vec::new();
-            }
-            
-            contents.push(json!({
-                "role": "function",
-                "parts": [{
-                    "functionResponse": {
-                        "name": msg.get("name").and_then(|n| n.as_str()).unwrap_or("unknown"),
-                        "response": {"content": msg.get("content").unwrap_or(&json!(""))}
-                    }
-                }]
-            }));
-            
-            last_role = String::new();
-            continue;
-        }
-        
-        // Convert content to parts
-        let parts = convert_openai_content_to_gemini_parts(msg.get("content").unwrap_or(&json!("")))?;
-        
-        // Merge consecutive messages from same role
-        if gemini_role == last_role {
-            accumulated_parts.extend(parts);
-        } else {
-            if !accumulated_parts.is_empty() {
-                contents.push(json!({
-                    "role": last_role,
-                    "parts": accumulated_parts
-                }));
-            }
-            accumulated_parts = parts;
-            last_role = gemini_role.to_string();
-        }
-    }
-    
-    if !accumulated_parts.is_empty() {
-        contents.push(json!({
-            "role": last_role,
-            "parts": accumulated_parts
-        }));
-    }
-    
-    gemini_req["contents"] = json!(contents);
-    
-    // Generation config
-    let mut gen_config = json!({});
-    if let Some(temp) = openai_req.get("temperature") {
-        gen_config["temperature"] = temp.clone();
-    }
-    if let Some(max_tokens) = openai_req.get("max_tokens") {
-        gen_config["maxOutputTokens"] = max_tokens.clone();
-    }
-    if let Some(top_p) = openai_req.get("top_p") {
-        gen_config["topP"] = top_p.clone();
+/// Gemini finish reasons meaning the answer was withheld or cut short by a filter
+const GEMINI_FILTER_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "OTHER",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
+
+/// OpenAI `finish_reason` for a Gemini candidate's `finishReason`
+pub fn gemini_finish_reason(reason: Option<&str>, has_tool_calls: bool) -> &'static str {
+    match reason {
+        _ if has_tool_calls => "tool_calls",
+        Some("MAX_TOKENS") => "length",
+        Some(reason) if GEMINI_FILTER_REASONS.contains(&reason) => "content_filter",
+        _ => "stop",
     }
-    
-    if !gen_config.as_object().unwrap().is_empty() {
-        gemini_req["generationConfig"] = gen_config;
+}
+
+/// A prompt Gemini refused to answer at all (`promptFeedback.blockReason`, no candidates)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptBlocked {
+    pub reason: String,
+}
+
+impl std::fmt::Display for PromptBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The prompt was blocked by the upstream content filter ({})", self.reason)
     }
-    
-    Ok(gemini_req)
 }
 
-pub fn gemini_response_to_openai(gemini_resp: Value, model: &str) -> Result<Value> {
-    let content = extract_gemini_response_content(&gemini_resp);
-    
-    let usage = if let Some(usage_meta) = gemini_resp.get("usageMetadata") {
-        json!({
-            "prompt_tokens": usage_meta.get("promptTokenCount").unwrap_or(&json!(0)),
-            "completion_tokens": usage_meta.get("candidatesTokenCount").unwrap_or(&json!(0)),
-            "total_tokens": usage_meta.get("totalTokenCount").unwrap_or(&json!(0))
-        })
-    } else {
-        json!({"prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0})
-    };
-    
-    Ok(json!({
-        "id": format!("chatcmpl-{}", Uuid::new_v4()),
-        "object": "chat.completion",
-        "created": chrono::Utc::now().timestamp(),
-        "model": model,
-        "choices": [{
-            "index": 0,
-            "message": {
-                "role": "assistant",
-                "content": content
-            },
-            "finish_reason": "stop"
-        }],
-        "usage": usage
-    }))
+impl std::error::Error for PromptBlocked {}
+
+/// The block on a Gemini response that carries no candidates because the prompt was refused
+pub fn gemini_prompt_blocked(gemini_resp: &Value) -> Option<PromptBlocked> {
+    let has_candidates = gemini_resp
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .is_some_and(|c| !c.is_empty());
+    let reason = gemini_resp.pointer("/promptFeedback/blockReason")?.as_str()?;
+    (!has_candidates).then(|| PromptBlocked { reason: reason.to_string() })
 }
 
 // ============================================================================
-// OpenAI <-> Claude Conversions
+// Requests and Responses
 // ============================================================================
 
+// Each pair of protocols converts through the unified form (see `unified`)
+
+pub fn openai_request_to_gemini(openai_req: Value) -> Result<Value> {
+    Ok(UnifiedRequest::from_openai(openai_req)?.into_gemini())
+}
+
+pub fn gemini_request_to_openai(gemini_req: Value) -> Result<Value> {
+    Ok(UnifiedRequest::from_gemini(gemini_req)?.into_openai())
+}
+
 pub fn openai_request_to_claude(openai_req: Value) -> Result<Value> {
-    let mut claude_req = json!({});
-    
-    // Extract system message
-    let (system_instruction, non_system_messages) = extract_system_messages(&openai_req)?;
-    
-    if let Some(system) = system_instruction {
-        if let Some(parts) = system.get("parts").and_then(|p| p.as_array()) {
-            if let Some(first_part) = parts.first() {
-                if let Some(text) = first_part.get("text") {
-                    claude_req["system"] = text.clone();
-                }
-            }
-        }
-    }
-    
-    // Convert messages
-    let mut claude_messages = Vec::new();
-    
-    for msg in non_system_messages {
-        let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
-        
-        if role == "tool" {
-            // Tool result
-            claude_messages.push(json!({
-                "role": "user",
-                "content": [{
-                    "type": "tool_result",
-                    "tool_use_id": msg.get("tool_call_id").and_then(|id| id.as_str()).unwrap_or(""),
-                    "content": msg.get("content").and_then(|c| c.as_str()).unwrap_or("")
-                }]
-            }));
-        } else {
-            let claude_role = if role == "assistant" { "assistant" } else { "user" };
-            let content = convert_openai_content_to_claude_content(msg.get("content").unwrap_or(&json!("")))?;
-            
-            if !content.as_array().map(|a| a.is_empty()).unwrap_or(false) {
-                claude_messages.push(json!({
-                    "role": claude_role,
-                    "content": content
-                }));
-            }
-        }
-    }
-    
-    claude_req["messages"] = json!(claude_messages);
-    claude_req["model"] = openai_req.get("model").cloned().unwrap_or(json!("claude-3-opus"));
-    claude_req["max_tokens"] = openai_req.get("max_tokens").cloned().unwrap_or(json!(DEFAULT_MAX_TOKENS));
-    
-    if let Some(temp) = openai_req.get("temperature") {
-        claude_req["temperature"] = temp.clone();
-    }
-    if let Some(top_p) = openai_req.get("top_p") {
-        claude_req["top_p"] = top_p.clone();
-    }
-    
-    Ok(claude_req)
+    Ok(UnifiedRequest::from_openai(openai_req)?.into_claude())
+}
+
+pub fn claude_request_to_openai(claude_req: Value) -> Result<Value> {
+    Ok(UnifiedRequest::from_claude(claude_req)?.into_openai())
+}
+
+pub fn claude_request_to_gemini(claude_req: Value) -> Result<Value> {
+    Ok(UnifiedRequest::from_claude(claude_req)?.into_gemini())
+}
+
+pub fn gemini_request_to_claude(gemini_req: Value) -> Result<Value> {
+    Ok(UnifiedRequest::from_gemini(gemini_req)?.into_claude())
+}
+
+pub fn gemini_response_to_openai(gemini_resp: Value, model: &str) -> Result<Value> {
+    let mut response = UnifiedResponse::from_gemini(gemini_resp)?;
+    response.model = model.to_string();
+    Ok(response.into_openai())
 }
 
 pub fn claude_response_to_openai(claude_resp: Value, model: &str) -> Result<Value> {
-    let content = if let Some(content_arr) = claude_resp.get("content").and_then(|c| c.as_array()) {
-        content_arr.iter()
-            .filter_map(|block| {
-                if block.get("type")?.as_str()? == "text" {
-                    block.get("text")?.as_str()
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    } else {
-        String::new()
-    };
-    
-    let finish_reason = match claude_resp.get("stop_reason").and_then(|r| r.as_str()) {
-        Some("end_turn") => "stop",
-        Some(other) => other,
-        None => "stop"
-    };
-    
-    let usage = if let Some(usage) = claude_resp.get("usage") {
-        json!({
-            "prompt_tokens": usage.get("input_tokens").unwrap_or(&json!(0)),
-            "completion_tokens": usage.get("output_tokens").unwrap_or(&json!(0)),
-            "total_tokens": usage.get("input_tokens").unwrap_or(&json!(0)).as_i64().unwrap_or(0) 
-                + usage.get("output_tokens").unwrap_or(&json!(0)).as_i64().unwrap_or(0)
-        })
-    } else {
-        json!({"prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0})
-    };
-    
-    Ok(json!({
-        "id": format!("chatcmpl-{}", Uuid::new_v4()),
-        "object": "chat.completion",
-        "created": chrono::Utc::now().timestamp(),
-        "model": model,
-        "choices": [{
-            "index": 0,
-            "message": {
-                "role": "assistant",
-                "content": content
-            },
-            "finish_reason": finish_reason
-        }],
-        "usage": usage
-    }))
+    let mut response = UnifiedResponse::from_claude(claude_resp)?;
+    response.model = model.to_string();
+    Ok(response.into_openai())
+}
+
+pub fn openai_response_to_claude(openai_resp: Value, model: &str) -> Result<Value> {
+    let mut response = UnifiedResponse::from_openai(openai_resp)?;
+    response.model = model.to_string();
+    Ok(response.into_claude())
+}
+
+pub fn gemini_response_to_claude(gemini_resp: Value, model: &str) -> Result<Value> {
+    let mut response = UnifiedResponse::from_gemini(gemini_resp)?;
+    response.model = model.to_string();
+    Ok(response.into_claude())
 }
 
 // ============================================================================
-// Claude <-> Gemini Conversions
+// Citations and Structured Output
 // ============================================================================
 
-pub fn claude_request_to_gemini(claude_req: Value) -> Result<Value> {
-    let mut gemini_req = json!({});
-    
-    // System instruction
-    if let Some(system) = claude_req.get("system") {
-        gemini_req["systemInstruction"] = json!({
-            "parts": [{"text": system}]
-        });
+/// OpenAI `response_format` asking for output following `schema`
+pub fn openai_json_schema(schema: Value) -> Value {
+    json!({"type": "json_schema", "json_schema": {"name": "response", "schema": schema}})
+}
+
+/// OpenAI `url_citation` annotation, the common shape web citations from every backend are returned in
+pub fn url_citation(url: &str, title: Option<&str>) -> Value {
+    json!({"type": "url_citation", "url_citation": {"url": url, "title": title.unwrap_or(url)}})
+}
+
+/// Attach web citations to an OpenAI message as `annotations`, one per distinct URL
+pub fn set_annotations<'a>(message: &mut Value, citations: impl IntoIterator<Item = (&'a str, Option<&'a str>)>) {
+    let mut seen = Vec::new();
+    let annotations: Vec<Value> = citations
+        .into_iter()
+        .filter(|(url, _)| {
+            let new = !seen.contains(url);
+            seen.push(*url);
+            new
+        })
+        .map(|(url, title)| url_citation(url, title))
+        .collect();
+    if !annotations.is_empty() {
+        message["annotations"] = json!(annotations);
     }
-    
-    // Convert messages
-    let mut contents = Vec::new();
-    
-    if let Some(messages) = claude_req.get("messages").and_then(|m| m.as_array()) {
-        for msg in messages {
-            let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
-            let gemini_role = if role == "assistant" { "model" } else { "user" };
-            
-            let parts = convert_claude_content_to_gemini_parts(msg.get("content").unwrap_or(&json!([])))?;
-            
-            if !parts.as_array().map(|a| a.is_empty()).unwrap_or(true) {
-                contents.push(json!({
-                    "role": gemini_role,
-                    "parts": parts
-                }));
-            }
+}
+
+// ============================================================================
+// Stream Chunks
+// ============================================================================
+
+// Claude streams typed events and Gemini streams partial responses; both are
+// rewritten as OpenAI `chat.completion.chunk`s sharing one completion id.
+// OpenAI chunks are in turn rewritten as Gemini partial responses for Gemini
+// clients and as Claude events for Claude clients; the remaining pairs go by
+// way of OpenAI chunks.
+
+fn openai_chunk(id: &str, model: &str, delta: Value, finish_reason: Option<&str>) -> Value {
+    json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": chrono::Utc::now().timestamp(),
+        "model": model,
+        "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+    })
+}
+
+/// Stateful Claude event stream to OpenAI chunk conversion
+#[derive(Debug)]
+pub struct ClaudeStreamToOpenAI {
+    id: String,
+    model: String,
+    /// Claude content block index -> OpenAI tool call index
+    tool_indices: HashMap<u64, usize>,
+}
+
+impl ClaudeStreamToOpenAI {
+    pub fn new(model: &str) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", Uuid::new_v4()),
+            model: model.to_string(),
+            tool_indices: HashMap::new(),
         }
     }
-    
-    gemini_req["contents"] = json!(contents);
-    
-    // Generation config
-    let mut gen_config = json!({});
-    if let Some(max_tokens) = claude_req.get("max_tokens") {
-        gen_config["maxOutputTokens"] = max_tokens.clone();
-    }
-    if let Some(temp) = claude_req.get("temperature") {
-        gen_config["temperature"] = temp.clone();
-    }
-    if let Some(top_p) = claude_req.get("top_p") {
-        gen_config["topP"] = top_p.clone();
-    }
-    
-    if !gen_config.as_object().unwrap().is_empty() {
-        gemini_req["generationConfig"] = gen_config;
-    }
-    
-    Ok(gemini_req)
-}
 
-pub fn gemini_response_to_claude(gemini_resp: Value, model: &str) -> Result<Value> {
-    let mut content_blocks = Vec::new();
-    
-    if let Some(candidates) = gemini_resp.get("candidates").and_then(|c| c.as_array()) {
-        for candidate in candidates {
-            if let Some(parts) = candidate.get("content")
-                .and_then(|c| c.get("parts"))
-                .and_then(|p| p.as_array()) 
-            {
-                for part in parts {
-                    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                        content_blocks.push(json!({
-                            "type": "text",
-                            "text": text
-                        }));
-                    }
+    /// Convert one event; `None` for events with no OpenAI equivalent
+    pub fn convert(&mut self, event: &Value) -> Option<Value> {
+        let index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+        let delta = match event.get("type").and_then(|t| t.as_str())? {
+            "message_start" => json!({"role": "assistant", "content": ""}),
+            "content_block_start" => {
+                let block = event.get("content_block")?;
+                if block.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+                    return None;
                 }
+                let tool_index = self.tool_indices.len();
+                self.tool_indices.insert(index, tool_index);
+                json!({"tool_calls": [{
+                    "index": tool_index,
+                    "id": block.get("id").unwrap_or(&json!("")),
+                    "type": "function",
+                    "function": {"name": block.get("name").unwrap_or(&json!("")), "arguments": ""}
+                }]})
             }
-        }
+            "content_block_delta" => {
+                let delta = event.get("delta")?;
+                match delta.get("type").and_then(|t| t.as_str())? {
+                    "text_delta" => json!({"content": delta.get("text")?}),
+                    "input_json_delta" => json!({"tool_calls": [{
+                        "index": self.tool_indices.get(&index)?,
+                        "function": {"arguments": delta.get("partial_json")?}
+                    }]}),
+                    _ => return None,
+                }
+            }
+            "message_delta" => {
+                let finish_reason = match event.pointer("/delta/stop_reason").and_then(|r| r.as_str())? {
+                    "end_turn" | "stop_sequence" => "stop",
+                    "tool_use" => "tool_calls",
+                    "max_tokens" => "length",
+                    "refusal" => "content_filter",
+                    other => other,
+                };
+                return Some(openai_chunk(&self.id, &self.model, json!({}), Some(finish_reason)));
+            }
+            _ => return None,
+        };
+        Some(openai_chunk(&self.id, &self.model, delta, None))
     }
-    
-    let usage = if let Some(usage_meta) = gemini_resp.get("usageMetadata") {
-        json!({
-            "input_tokens": usage_meta.get("promptTokenCount").unwrap_or(&json!(0)),
-            "output_tokens": usage_meta.get("candidatesTokenCount").unwrap_or(&json!(0))
+}
+
+/// Convert one streamed Gemini response to an OpenAI chunk
+pub fn gemini_chunk_to_openai(chunk: &Value, id: &str, model: &str) -> Value {
+    let parts = chunk
+        .pointer("/candidates/0/content/parts")
+        .and_then(|p| p.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let text: String = parts
+        .iter()
+        .filter(|p| !p.get("thought").and_then(|t| t.as_bool()).unwrap_or(false))
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect();
+    let tool_calls: Vec<Value> = gemini_function_calls(parts)
+        .iter()
+        .enumerate()
+        .map(|(index, call)| {
+            let mut call = tool_call_to_openai(call);
+            call["index"] = json!(index);
+            call
         })
-    } else {
-        json!({"input_tokens": 0, "output_tokens": 0})
+        .collect();
+
+    let mut delta = json!({});
+    if !text.is_empty() {
+        delta["content"] = json!(text);
+    }
+    // A blocked prompt streams a single chunk with no candidates
+    let finish_reason = match chunk.pointer("/candidates/0/finishReason").and_then(|r| r.as_str()) {
+        Some(reason) => Some(gemini_finish_reason(Some(reason), !tool_calls.is_empty())),
+        None => gemini_prompt_blocked(chunk).map(|_| "content_filter"),
     };
-    
-    Ok(json!({
-        "id": format!("msg_{}", Uuid::new_v4()),
-        "type": "message",
-        "role": "assistant",
-        "content": content_blocks,
-        "model": model,
-        "stop_reason": "end_turn",
-        "usage": usage
-    }))
+    if !tool_calls.is_empty() {
+        delta["tool_calls"] = json!(tool_calls);
+    }
+    openai_chunk(id, model, delta, finish_reason)
 }
 
-// ============================================================================
-// Helper Functions
-// ============================================================================
+/// Stateful OpenAI chunk stream to Gemini partial response conversion
+///
+/// Text is passed on as it arrives. Tool call arguments stream in pieces, so a
+/// choice's calls are held until it finishes and sent as whole `functionCall` parts.
+#[derive(Debug, Default)]
+pub struct OpenAIStreamToGemini {
+    /// (choice index, tool call index) -> (name, arguments so far)
+    tool_calls: BTreeMap<(u64, u64), (String, String)>,
+}
+
+impl OpenAIStreamToGemini {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-fn extract_system_messages(openai_req: &Value) -> Result<(Option<Value>, Vec<Value>)> {
-    let mut system_parts = Vec::new();
-    let mut non_system = Vec::new();
-    
-    if let Some(messages) = openai_req.get("messages").and_then(|m| m.as_array()) {
-        for msg in messages {
-            if msg.get("role").and_then(|r| r.as_str()) == Some("system") {
-                if let Some(content) = msg.get("content").and_then(|c| c.as_str()) {
-                    system_parts.push(json!({"text": content}));
+    /// Convert one chunk; `None` for chunks with nothing to pass on (role openers, tool call pieces)
+    pub fn convert(&mut self, chunk: &Value) -> Option<Value> {
+        let mut candidates = Vec::new();
+        for choice in chunk.get("choices").and_then(|c| c.as_array()).into_iter().flatten() {
+            let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            let delta = choice.get("delta").unwrap_or(&Value::Null);
+            let mut parts = Vec::new();
+            for (field, thought) in [("reasoning_content", true), ("content", false)] {
+                if let Some(text) = delta.get(field).and_then(|t| t.as_str()).filter(|t| !t.is_empty()) {
+                    parts.push(if thought { json!({"text": text, "thought": true}) } else { json!({"text": text}) });
                 }
-            } else {
-                non_system.push(msg.clone());
             }
-        }
-    }
-    
-    let system_instruction = if !system_parts.is_empty() {
-        Some(json!({"parts": system_parts}))
-    } else {
-        None
-    };
-    
-    Ok((system_instruction, non_system))
-}
-
-fn convert_openai_content_to_gemini_parts(content: &Value) -> Result<Vec<Value>> {
-    let mut parts = Vec::new();
-    
-    if let Some(text) = content.as_str() {
-        parts.push(json!({"text": text}));
-    } else if let Some(arr) = content.as_array() {
-        for item in arr {
-            if let Some(item_type) = item.get("type").and_then(|t| t.as_str()) {
-                match item_type {
-                    "text" => {
-                        if let Some(text) = item.get("text") {
-                            parts.push(json!({"text": text}));
-                        }
-                    }
-                    "image_url" => {
-                        if let Some(image_url) = item.get("image_url") {
-                            let url = if let Some(url_str) = image_url.as_str() {
-                                url_str
-                            } else {
-                                image_url.get("url").and_then(|u| u.as_str()).unwrap_or("")
-                            };
-                            
-                            if url.starts_with("data:") {
-                                // Base64 image
-                                if let Some((header, data)) = url.split_once(',') {
-                                    let mime_type = header.strip_prefix("data:")
-                                        .and_then(|s| s.split(';').next())
-                                        .unwrap_or("image/jpeg");
-                                    
-                                    parts.push(json!({
-                                        "inlineData": {
-                                            "mimeType": mime_type,
-                                            "data": data
-                                        }
-                                    }));
-                                }
-                            } else {
-                                // URL
-                                parts.push(json!({
-                                    "fileData": {
-                                        "mimeType": "image/jpeg",
-                                        "fileUri": url
-                                    }
-                                }));
-                            }
-                        }
-                    }
-                    _ => {}
+            for call in delta.get("tool_calls").and_then(|c| c.as_array()).into_iter().flatten() {
+                let position = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let (name, arguments) = self.tool_calls.entry((index, position)).or_default();
+                name.push_str(call.pointer("/function/name").and_then(|n| n.as_str()).unwrap_or_default());
+                arguments.push_str(call.pointer("/function/arguments").and_then(|a| a.as_str()).unwrap_or_default());
+            }
+
+            let finish_reason = choice.get("finish_reason").and_then(|r| r.as_str());
+            if finish_reason.is_some() {
+                let (finished, pending) = std::mem::take(&mut self.tool_calls)
+                    .into_iter()
+                    .partition(|((choice, _), _)| *choice == index);
+                self.tool_calls = pending;
+                for (name, arguments) in finished.into_values() {
+                    let args = serde_json::from_str::<Value>(&arguments).unwrap_or_else(|_| json!({}));
+                    parts.push(json!({"functionCall": {"name": name, "args": args}}));
                 }
             }
+            if parts.is_empty() && finish_reason.is_none() {
+                continue;
+            }
+            let mut candidate = json!({"index": index, "content": {"role": "model", "parts": parts}});
+            if let Some(reason) = finish_reason {
+                candidate["finishReason"] = json!(FinishReason::from_openai(Some(reason)).gemini());
+            }
+            candidates.push(candidate);
         }
+
+        // Usage comes last, on a chunk of its own when the client asked for it
+        let usage = chunk.get("usage").filter(|u| u.is_object());
+        if candidates.is_empty() && usage.is_none() {
+            return None;
+        }
+        let mut response = json!({"candidates": candidates});
+        if let Some(usage) = usage {
+            let tokens = |field: &str| usage.get(field).and_then(|t| t.as_u64()).unwrap_or(0);
+            response["usageMetadata"] = json!({
+                "promptTokenCount": tokens("prompt_tokens"),
+                "candidatesTokenCount": tokens("completion_tokens"),
+                "totalTokenCount": tokens("prompt_tokens") + tokens("completion_tokens"),
+            });
+        }
+        Some(response)
     }
-    
-    Ok(parts)
 }
 
-fn convert_openai_content_to_claude_content(content: &Value) -> Result<Value> {
-    let mut content_blocks = Vec::new();
-    
-    if let Some(text) = content.as_str() {
-        if !text.is_empty() {
-            content_blocks.push(json!({"type": "text", "text": text}));
+/// A Claude content block being streamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpenBlock {
+    Text,
+    Thinking,
+    /// The tool call at this OpenAI index
+    ToolUse(u64),
+}
+
+/// Stateful OpenAI chunk stream to Claude event conversion
+///
+/// Claude streams one message of indexed content blocks: reasoning, text and
+/// each tool call get a block of their own, opened on their first delta and
+/// closed when another opens. OpenAI reports the finish reason and usage on
+/// the last chunks, so the message is closed by `finish` once the stream ends.
+#[derive(Debug)]
+pub struct OpenAIStreamToClaude {
+    model: String,
+    started: bool,
+    /// The open block and its Claude index
+    open: Option<(OpenBlock, usize)>,
+    blocks: usize,
+    /// OpenAI tool call index -> call id, telling a new call from the next piece of one
+    tool_ids: HashMap<u64, String>,
+    stop_reason: Option<&'static str>,
+    /// (input, output) tokens
+    usage: (u64, u64),
+}
+
+impl OpenAIStreamToClaude {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            started: false,
+            open: None,
+            blocks: 0,
+            tool_ids: HashMap::new(),
+            stop_reason: None,
+            usage: (0, 0),
+        }
+    }
+
+    /// Convert one chunk; the first also yields `message_start`
+    pub fn convert(&mut self, chunk: &Value) -> Vec<Value> {
+        let mut events = self.start();
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            let tokens = |field: &str| usage.get(field).and_then(|t| t.as_u64()).unwrap_or(0);
+            self.usage = (tokens("prompt_tokens"), tokens("completion_tokens"));
+        }
+        // A Claude message holds a single answer: the first choice
+        let Some(choice) = chunk.pointer("/choices/0") else {
+            return events;
+        };
+        let delta = choice.get("delta").unwrap_or(&Value::Null);
+
+        for (field, block) in [("reasoning_content", OpenBlock::Thinking), ("content", OpenBlock::Text)] {
+            if let Some(text) = delta.get(field).and_then(|t| t.as_str()).filter(|t| !t.is_empty()) {
+                let index = self.open_block(block, &mut events);
+                let delta = match block {
+                    OpenBlock::Thinking => json!({"type": "thinking_delta", "thinking": text}),
+                    _ => json!({"type": "text_delta", "text": text}),
+                };
+                events.push(json!({"type": "content_block_delta", "index": index, "delta": delta}));
+            }
         }
-    } else if let Some(arr) = content.as_array() {
-        for item in arr {
-            if let Some(item_type) = item.get("type").and_then(|t| t.as_str()) {
-                match item_type {
-                    "text" => {
-                        if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                            if !text.is_empty() {
-                                content_blocks.push(json!({"type": "text", "text": text}));
-                            }
-                        }
-                    }
-                    "image_url" => {
-                        if let Some(image_url) = item.get("image_url") {
-                            let url = if let Some(url_str) = image_url.as_str() {
-                                url_str
-                            } else {
-                                image_url.get("url").and_then(|u| u.as_str()).unwrap_or("")
-                            };
-                            
-                            if url.starts_with("data:") {
-                                if let Some((header, data)) = url.split_once(',') {
-                                    let media_type = header.strip_prefix("data:")
-                                        .and_then(|s| s.split(';').next())
-                                        .unwrap_or("image/jpeg");
-                                    
-                                    content_blocks.push(json!({
-                                        "type": "image",
-                                        "source": {
-                                            "type": "base64",
-                                            "media_type": media_type,
-                                            "data": data
-                                        }
-                                    }));
-                                }
-                            }
-                        }
-                    }
-                    _ => {}
+
+        for call in delta.get("tool_calls").and_then(|c| c.as_array()).into_iter().flatten() {
+            let position = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            let id = call.get("id").and_then(|i| i.as_str()).filter(|id| !id.is_empty());
+            let known = self.tool_ids.get(&position);
+            if known.is_none() || id.is_some_and(|id| Some(id) != known.map(String::as_str)) {
+                let id = id.map(str::to_string).unwrap_or_else(|| format!("toolu_{}", Uuid::new_v4().simple()));
+                let name = call.pointer("/function/name").and_then(|n| n.as_str()).unwrap_or_default();
+                self.tool_ids.insert(position, id.clone());
+                let block = json!({"type": "tool_use", "id": id, "name": name, "input": {}});
+                self.start_block(OpenBlock::ToolUse(position), block, &mut events);
+            }
+            let arguments = call.pointer("/function/arguments").and_then(|a| a.as_str()).unwrap_or_default();
+            match self.open {
+                Some((OpenBlock::ToolUse(open), index)) if open == position && !arguments.is_empty() => {
+                    events.push(json!({
+                        "type": "content_block_delta",
+                        "index": index,
+                        "delta": {"type": "input_json_delta", "partial_json": arguments}
+                    }));
                 }
+                _ => {}
             }
         }
+
+        if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+            self.stop_reason = Some(FinishReason::from_openai(Some(reason)).claude());
+        }
+        events
     }
-    
-    Ok(json!(content_blocks))
-}
-
-fn convert_claude_content_to_gemini_parts(content: &Value) -> Result<Value> {
-    let mut parts = Vec::new();
-    
-    if let Some(text) = content.as_str() {
-        parts.push(json!({"text": text}));
-    } else if let Some(arr) = content.as_array() {
-        for block in arr {
-            if let Some(block_type) = block.get("type").and_then(|t| t.as_str()) {
-                match block_type {
-                    "text" => {
-                        if let Some(text) = block.get("text") {
-                            parts.push(json!({"text": text}));
-                        }
-                    }
-                    "image" => {
-                        if let Some(source) = block.get("source") {
-                            if source.get("type").and_then(|t| t.as_str()) == Some("base64") {
-                                parts.push(json!({
-                                    "inlineData": {
-                                        "mimeType": source.get("media_type").unwrap_or(&json!("image/jpeg")),
-                                        "data": source.get("data").unwrap_or(&json!(""))
-                                    }
-                                }));
-                            }
-                        }
-                    }
-                    _ => {}
-                }
+
+    /// The events closing the message, once the stream has ended
+    pub fn finish(&mut self) -> Vec<Value> {
+        let mut events = self.start();
+        self.close_block(&mut events);
+        let (input_tokens, output_tokens) = self.usage;
+        events.push(json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": self.stop_reason.unwrap_or("end_turn"), "stop_sequence": null},
+            "usage": {"input_tokens": input_tokens, "output_tokens": output_tokens}
+        }));
+        events.push(json!({"type": "message_stop"}));
+        events
+    }
+
+    fn start(&mut self) -> Vec<Value> {
+        if std::mem::replace(&mut self.started, true) {
+            return Vec::new();
+        }
+        vec![json!({
+            "type": "message_start",
+            "message": {
+                "id": format!("msg_{}", Uuid::new_v4().simple()),
+                "type": "message",
+                "role": "assistant",
+                "model": self.model,
+                "content": [],
+                "stop_reason": null,
+                "stop_sequence": null,
+                "usage": {"input_tokens": 0, "output_tokens": 0}
             }
+        })]
+    }
+
+    /// The index of the text or thinking `block`, starting it unless it is the open one
+    fn open_block(&mut self, block: OpenBlock, events: &mut Vec<Value>) -> usize {
+        if let Some((_, index)) = self.open.filter(|(open, _)| *open == block) {
+            return index;
         }
+        let content_block = match block {
+            OpenBlock::Thinking => json!({"type": "thinking", "thinking": ""}),
+            _ => json!({"type": "text", "text": ""}),
+        };
+        self.start_block(block, content_block, events)
     }
-    
-    Ok(json!(parts))
-}
-
-fn extract_gemini_response_content(gemini_resp: &Value) -> String {
-    if let Some(candidates) = gemini_resp.get("candidates").and_then(|c| c.as_array()) {
-        candidates.iter()
-            .filter_map(|candidate| {
-                let parts = candidate.get("content")?
-                    .get("parts")?
-                    .as_array()?;
-                
-                let texts: Vec<&str> = parts.iter()
-                    .filter_map(|p| p.get("text")?.as_str())
-                    .collect();
-                
-                Some(texts.join(" "))
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    } else {
-        String::new()
+
+    /// Close the open block and start a new one
+    fn start_block(&mut self, block: OpenBlock, content_block: Value, events: &mut Vec<Value>) -> usize {
+        self.close_block(events);
+        let index = self.blocks;
+        self.blocks += 1;
+        self.open = Some((block, index));
+        events.push(json!({"type": "content_block_start", "index": index, "content_block": content_block}));
+        index
     }
+
+    fn close_block(&mut self, events: &mut Vec<Value>) {
+        if let Some((_, index)) = self.open.take() {
+            events.push(json!({"type": "content_block_stop", "index": index}));
+        }
+    }
+}
+
+// ============================================================================
+// Message Names
+// ============================================================================
+
+// Claude and Gemini have no per-message `name`, so an OpenAI message's name
+// travels as a leading marker text block / part and is split back out on the
+// way to OpenAI. Consecutive messages merged into one Gemini turn each keep
+// their own marker.
+
+const NAME_MARKER_OPEN: &str = "<name>";
+const NAME_MARKER_CLOSE: &str = "</name>";
+
+pub fn name_marker(name: &str) -> String {
+    format!("{}{}{}", NAME_MARKER_OPEN, name, NAME_MARKER_CLOSE)
+}
+
+/// The name carried by a marker text, if `text` is exactly one
+pub fn parse_name_marker(text: &str) -> Option<&str> {
+    text.strip_prefix(NAME_MARKER_OPEN)?
+        .strip_suffix(NAME_MARKER_CLOSE)
+        .filter(|name| !name.is_empty() && !name.contains(['<', '>', '\n']))
+}
+
+// ============================================================================
+// Tool Call IDs
+// ============================================================================
+
+// OpenAI and Claude tool ids are carried over verbatim. Gemini function calls
+// usually have no id, so one is derived from the call itself: converting the
+// same history again yields the same ids, and clients can match their tool
+// results to the calls they were shown. Tool results sent to Gemini are named
+// after the call their id refers to.
+
+struct ToolCall {
+    id: String,
+    name: String,
+    arguments: Value,
+}
+
+/// Stable id for the function call at `position` among a turn's calls
+pub fn synthetic_tool_call_id(name: &str, args: &Value, position: usize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(name.as_bytes());
+    hasher.update([0]);
+    hasher.update(args.to_string().as_bytes());
+    hasher.update((position as u64).to_le_bytes());
+    let digest = hasher.finalize();
+    let hex: String = digest[..12].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("call_{}", hex)
+}
+
+/// Function calls in a list of Gemini parts, with their ids
+fn gemini_function_calls(parts: &[Value]) -> Vec<ToolCall> {
+    parts
+        .iter()
+        .filter_map(|p| p.get("functionCall"))
+        .enumerate()
+        .filter_map(|(position, call)| {
+            let name = call.get("name")?.as_str()?.to_string();
+            let arguments = call.get("args").cloned().unwrap_or_else(|| json!({}));
+            let id = match call.get("id").and_then(|i| i.as_str()) {
+                Some(id) if !id.is_empty() => id.to_string(),
+                _ => synthetic_tool_call_id(&name, &arguments, position),
+            };
+            Some(ToolCall { id, name, arguments })
+        })
+        .collect()
+}
+
+fn tool_call_to_openai(call: &ToolCall) -> Value {
+    json!({
+        "id": call.id,
+        "type": "function",
+        "function": {"name": call.name, "arguments": call.arguments.to_string()}
+    })
+}
+
+// ============================================================================
+// Code Execution
+// ============================================================================
+
+// A code-interpreter tool is `{"type": "code_interpreter"}` in the OpenAI form
+// (`code_execution` is accepted too) and maps to Gemini's `codeExecution` and
+// Anthropic's code execution tool. Code the provider ran comes back as OpenAI
+// `code_interpreter_calls` on the message, shaped like Assistants run steps.
+
+/// Anthropic code execution tool type, and the beta flag it needs
+pub const CLAUDE_CODE_EXECUTION_TOOL: &str = "code_execution_20250522";
+pub const CLAUDE_CODE_EXECUTION_BETA: &str = "code-execution-2025-05-22";
+
+fn is_code_interpreter(tool: &Value) -> bool {
+    matches!(tool.get("type").and_then(|t| t.as_str()), Some("code_interpreter" | "code_execution"))
 }
 
+/// Whether a Claude request enables the code execution tool
+pub fn claude_uses_code_execution(claude_req: &Value) -> bool {
+    claude_req
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .is_some_and(|tools| tools.iter().any(|tool| tool["type"] == CLAUDE_CODE_EXECUTION_TOOL))
+}
+
+/// Remove code-interpreter tools from an OpenAI request, for backends that
+/// cannot run code; returns whether any were removed
+pub fn drop_code_interpreter(openai_req: &mut Value) -> bool {
+    let Some(tools) = openai_req.get_mut("tools").and_then(|t| t.as_array_mut()) else {
+        return false;
+    };
+    let before = tools.len();
+    tools.retain(|tool| !is_code_interpreter(tool));
+    let dropped = tools.len() != before;
+    if tools.is_empty() {
+        if let Some(obj) = openai_req.as_object_mut() {
+            obj.remove("tools");
+            obj.remove("tool_choice");
+        }
+    }
+    dropped
+}
@@ -0,0 +1,407 @@
+/*!
+ * Dataset Recording
+ *
+ * For clients that opt in, each chat exchange is written as one JSONL record
+ * (prompt, completion, model and client-supplied scores) for later
+ * fine-tuning or evaluation datasets. Every string in a record is anonymized
+ * first: credentials, e-mail addresses, phone numbers and IP addresses are
+ * masked, and client identifiers (`user`, `metadata`) are not kept; the
+ * client and end user are stored only as hashes, so their records can be
+ * purged on request. Records go to size-rotated files, or are batched into
+ * objects in an S3-compatible bucket.
+ */
+
+use crate::common::ModelProtocol;
+use crate::config::{DatasetConfig, DatasetS3Config};
+use crate::conversations::StreamedReply;
+use crate::secret_refs::{hex_sha256, hmac_sha256, to_hex};
+use crate::stream_recovery::ValueStream;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures::StreamExt;
+use regex::Regex;
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Request header with scores to store alongside the exchange, a JSON object of numbers
+pub const SCORES_HEADER: &str = "x-dataset-scores";
+
+/// Request fields that make up the prompt
+const PROMPT_FIELDS: &[&str] = &["system", "messages", "tools", "tool_choice"];
+
+/// Personal data patterns and what they are replaced with
+fn pii_patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            (r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}", "[EMAIL]"),
+            (r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "[IP]"),
+            (r"\+\d[\d\s\-]{7,14}\d|\(?\b\d{3}\)?[\s\-.]\d{3}[\s\-.]\d{4}\b", "[PHONE]"),
+        ]
+        .iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).expect("valid PII pattern"), *replacement))
+        .collect()
+    })
+}
+
+/// Text with credentials and personal data masked
+pub fn anonymize_text(text: &str) -> String {
+    let mut text = crate::log_redaction::scrub_secrets(text).into_owned();
+    for (pattern, replacement) in pii_patterns() {
+        if pattern.is_match(&text) {
+            text = pattern.replace_all(&text, *replacement).into_owned();
+        }
+    }
+    text
+}
+
+/// Anonymize every string in a JSON value
+pub fn anonymize(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = anonymize_text(text),
+        Value::Array(items) => items.iter_mut().for_each(anonymize),
+        Value::Object(fields) => fields.values_mut().for_each(anonymize),
+        _ => {}
+    }
+}
+
+/// The anonymized prompt of a request in its client protocol
+pub fn prompt(request: &Value) -> Value {
+    let mut prompt: Map<String, Value> = PROMPT_FIELDS
+        .iter()
+        .filter_map(|field| request.get(*field).map(|value| (field.to_string(), value.clone())))
+        .collect();
+    prompt.values_mut().for_each(anonymize);
+    Value::Object(prompt)
+}
+
+/// Pseudonymous form of a client or end-user ID stored in records
+pub fn subject_hash(id: &str) -> String {
+    hex_sha256(id.as_bytes())[..16].to_string()
+}
+
+/// Scores from the `x-dataset-scores` header; anything but numbers is dropped
+pub fn parse_scores(header: Option<&str>) -> Value {
+    let scores: Map<String, Value> = header
+        .and_then(|header| serde_json::from_str::<Map<String, Value>>(header).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, score)| score.is_number())
+        .collect();
+    Value::Object(scores)
+}
+
+/// An exchange being recorded: everything but the completion
+pub struct Recording {
+    recorder: Arc<DatasetRecorder>,
+    client: String,
+    end_user: Option<String>,
+    protocol: ModelProtocol,
+    model: String,
+    prompt: Value,
+    scores: Value,
+}
+
+impl Recording {
+    pub fn with_scores(mut self, scores: Value) -> Self {
+        self.scores = scores;
+        self
+    }
+
+    fn record(&self, mut completion: Value, usage: Option<&Value>) -> Value {
+        anonymize(&mut completion);
+        json!({
+            "id": uuid::Uuid::new_v4().to_string(),
+            "timestamp": Utc::now().to_rfc3339(),
+            "client": self.client,
+            "end_user": self.end_user,
+            "protocol": self.protocol.as_str(),
+            "model": self.model,
+            "prompt": self.prompt,
+            "completion": completion,
+            "usage": usage.cloned().unwrap_or(Value::Null),
+            "scores": self.scores,
+        })
+    }
+
+    /// Record the exchange with a buffered response
+    pub async fn finish(self, response: &Value) {
+        let Some(completion) = crate::conversations::assistant_message(response, self.protocol) else {
+            return;
+        };
+        let record = self.record(completion, response.get("usage"));
+        if let Err(e) = self.recorder.write(&record).await {
+            warn!("Failed to write dataset record: {}", e);
+        }
+    }
+
+    /// Record the exchange once the stream completes; an interrupted or empty stream is not recorded
+    pub fn record_stream(self, inner: ValueStream) -> ValueStream {
+        Box::pin(async_stream::stream! {
+            let mut inner = inner;
+            let mut reply = StreamedReply::new(self.protocol);
+            let mut usage = None;
+            let mut failed = false;
+            while let Some(item) = inner.next().await {
+                match item {
+                    Ok(ref chunk) => {
+                        reply.observe(chunk);
+                        if let Some(chunk_usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+                            usage = Some(chunk_usage.clone());
+                        }
+                    }
+                    Err(_) => failed = true,
+                }
+                yield item;
+            }
+            if let (false, Some(completion)) = (failed, reply.into_message()) {
+                let record = self.record(completion, usage.as_ref());
+                if let Err(e) = self.recorder.write(&record).await {
+                    warn!("Failed to write dataset record: {}", e);
+                }
+            }
+        })
+    }
+}
+
+/// The file being appended to, or the records waiting for upload
+#[derive(Default)]
+struct Sink {
+    file: Option<(PathBuf, u64)>,
+    buffer: String,
+}
+
+pub struct DatasetRecorder {
+    config: DatasetConfig,
+    client: Client,
+    sink: Mutex<Sink>,
+}
+
+impl DatasetRecorder {
+    pub fn new(config: &DatasetConfig, client: Client) -> Self {
+        Self {
+            config: config.clone(),
+            client,
+            sink: Mutex::new(Sink::default()),
+        }
+    }
+
+    /// Whether a client opted in to recording
+    pub fn records(&self, client_id: &str) -> bool {
+        self.config.clients.iter().any(|c| c == client_id || c == "*")
+    }
+
+    /// Start recording an exchange of an opted-in client
+    pub fn begin(
+        self: &Arc<Self>,
+        client_id: &str,
+        end_user: Option<&str>,
+        protocol: ModelProtocol,
+        model: &str,
+        request: &Value,
+    ) -> Recording {
+        Recording {
+            recorder: self.clone(),
+            client: subject_hash(client_id),
+            end_user: end_user.map(subject_hash),
+            protocol,
+            model: model.to_string(),
+            prompt: prompt(request),
+            scores: json!({}),
+        }
+    }
+
+    /// Append a record; with S3, uploaded once the batch reaches `max_file_bytes`
+    pub async fn write(&self, record: &Value) -> Result<()> {
+        let line = format!("{}\n", serde_json::to_string(record)?);
+        let mut sink = self.sink.lock().await;
+        if self.config.s3.is_some() {
+            sink.buffer.push_str(&line);
+            if sink.buffer.len() as u64 >= self.config.max_file_bytes {
+                self.upload(&mut sink).await?;
+            }
+            return Ok(());
+        }
+
+        let line_len = line.len() as u64;
+        let rotate = sink
+            .file
+            .as_ref()
+            .is_none_or(|(_, size)| *size > 0 && size + line_len > self.config.max_file_bytes);
+        if rotate {
+            tokio::fs::create_dir_all(&self.config.directory)
+                .await
+                .context("Failed to create the dataset directory")?;
+            let path = self.config.directory.join(file_name());
+            info!("Writing dataset records to {:?}", path);
+            sink.file = Some((path, 0));
+        }
+        let (path, size) = sink.file.as_mut().expect("file opened above");
+        let mut file = OpenOptions::new().create(true).append(true).open(&*path).await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        *size += line_len;
+        Ok(())
+    }
+
+    /// Remove the records of a client and/or end user from the local files and the
+    /// upload buffer, returning how many were removed; objects already in S3 are not touched
+    pub async fn purge(&self, client_id: Option<&str>, end_user: Option<&str>) -> Result<usize> {
+        let (client, end_user) = (client_id.map(subject_hash), end_user.map(subject_hash));
+        let matches = |line: &str| {
+            let Ok(record) = serde_json::from_str::<Value>(line) else {
+                return false;
+            };
+            let field_is = |field: &str, hash: &Option<String>| hash.as_deref().is_some_and(|hash| record[field] == hash);
+            field_is("client", &client) || field_is("end_user", &end_user)
+        };
+
+        let mut sink = self.sink.lock().await;
+        let before = sink.buffer.lines().count();
+        sink.buffer = sink.buffer.lines().filter(|line| !matches(line)).map(|line| format!("{}\n", line)).collect();
+        let mut removed = before - sink.buffer.lines().count();
+
+        let mut entries = match tokio::fs::read_dir(&self.config.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(removed),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "jsonl") {
+                continue;
+            }
+            let content = tokio::fs::read_to_string(&path).await?;
+            let kept: Vec<&str> = content.lines().filter(|line| !matches(line)).collect();
+            let dropped = content.lines().count() - kept.len();
+            if dropped == 0 {
+                continue;
+            }
+            let rewritten: String = kept.iter().map(|line| format!("{}\n", line)).collect();
+            tokio::fs::write(&path, &rewritten).await?;
+            if let Some((_, size)) = sink.file.as_mut().filter(|(current, _)| *current == path) {
+                *size = rewritten.len() as u64;
+            }
+            removed += dropped;
+        }
+        Ok(removed)
+    }
+
+    /// Upload buffered records now
+    pub async fn flush(&self) -> Result<()> {
+        let mut sink = self.sink.lock().await;
+        self.upload(&mut sink).await
+    }
+
+    async fn upload(&self, sink: &mut Sink) -> Result<()> {
+        let Some(ref s3) = self.config.s3 else {
+            return Ok(());
+        };
+        if sink.buffer.is_empty() {
+            return Ok(());
+        }
+        let key = format!("{}{}", s3.prefix, file_name());
+        put_object(&self.client, s3, &key, sink.buffer.as_bytes()).await?;
+        info!("Uploaded dataset records to s3://{}/{}", s3.bucket, key);
+        sink.buffer.clear();
+        Ok(())
+    }
+
+    /// Upload buffered records every `flush_interval_secs`, until the recorder is dropped
+    pub fn spawn_flusher(self: &Arc<Self>) {
+        let interval = self.config.flush_interval_secs;
+        if self.config.s3.is_none() || interval == 0 {
+            return;
+        }
+        let recorder = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(recorder) = recorder.upgrade() else {
+                    break;
+                };
+                if let Err(e) = recorder.flush().await {
+                    warn!("Failed to upload dataset records: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// A unique, time-ordered name for a file or object of records
+pub fn file_name() -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    format!("dataset-{}-{}.jsonl", Utc::now().format("%Y%m%d-%H%M%S"), &id[..8])
+}
+
+/// Upload an object with a SigV4-signed, path-style PUT
+async fn put_object(client: &Client, s3: &DatasetS3Config, key: &str, body: &[u8]) -> Result<()> {
+    let access_key = match s3.access_key_id {
+        Some(ref key) => key.clone(),
+        None => std::env::var("AWS_ACCESS_KEY_ID").context("No S3 access key configured")?,
+    };
+    let secret_key = match s3.secret_access_key {
+        Some(ref key) => key.clone(),
+        None => std::env::var("AWS_SECRET_ACCESS_KEY").context("No S3 secret key configured")?,
+    };
+    let endpoint = url::Url::parse(&s3.endpoint).context("Invalid S3 endpoint")?;
+    let host = match (endpoint.host_str(), endpoint.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => anyhow::bail!("S3 endpoint has no host"),
+    };
+    let path = format!("/{}/{}", s3.bucket, key);
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date_stamp = now.format("%Y%m%d").to_string();
+    let payload_hash = hex_sha256(body);
+
+    let signed_headers = "content-type;host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "PUT\n{}\n\ncontent-type:application/x-ndjson\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        path, host, payload_hash, amz_date, signed_headers, payload_hash
+    );
+    let credential_scope = format!("{}/{}/s3/aws4_request", date_stamp, s3.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        credential_scope,
+        hex_sha256(canonical_request.as_bytes())
+    );
+    let k_date = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date_stamp.as_bytes());
+    let k_region = hmac_sha256(&k_date, s3.region.as_bytes());
+    let k_service = hmac_sha256(&k_region, b"s3");
+    let k_signing = hmac_sha256(&k_service, b"aws4_request");
+    let signature = to_hex(&hmac_sha256(&k_signing, string_to_sign.as_bytes()));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key, credential_scope, signed_headers, signature
+    );
+
+    let response = client
+        .put(format!("{}{}", s3.endpoint.trim_end_matches('/'), path))
+        .header("Content-Type", "application/x-ndjson")
+        .header("X-Amz-Content-Sha256", &payload_hash)
+        .header("X-Amz-Date", &amz_date)
+        .header("Authorization", authorization)
+        .body(body.to_vec())
+        .send()
+        .await
+        .context("S3 upload failed")?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        anyhow::bail!("S3 returned {}: {}", status, error_text);
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+/*!
+ * Duplicate Requests
+ *
+ * A client stuck in a retry loop sends the same request over and over, each
+ * one a paid upstream call. With `duplicate_requests` configured, every chat
+ * request is counted per client and per request body (compared in canonical
+ * form, so key order does not matter) over `window_secs`. Past `max_repeats`
+ * the request is answered from the response cache when it holds the answer,
+ * otherwise refused with a 429, and an alert names the client. Counts are kept
+ * in the shared store, so a loop spread over the cluster is caught as well.
+ */
+
+use crate::cluster::SharedStore;
+use crate::common::ModelProtocol;
+use crate::config::DuplicateRequestConfig;
+use anyhow::Result;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A request sent more often than allowed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeat {
+    /// Times the client sent it within the window, this one included
+    pub count: u64,
+    /// The first request over the limit, the one to raise the alert for
+    pub first: bool,
+}
+
+/// Shared store key counting a client's copies of one request
+pub fn counter_key(client_id: &str, protocol: ModelProtocol, body: &Value) -> String {
+    let digest = Sha256::digest(crate::response_cache::canonical_json(body).as_bytes());
+    format!("dup:{}:{:?}:{:x}", client_id, protocol, digest)
+}
+
+pub struct DuplicateDetector {
+    store: Arc<dyn SharedStore>,
+    config: DuplicateRequestConfig,
+}
+
+impl DuplicateDetector {
+    pub fn new(store: Arc<dyn SharedStore>, config: &DuplicateRequestConfig) -> Self {
+        Self {
+            store,
+            config: config.clone(),
+        }
+    }
+
+    pub fn config(&self) -> &DuplicateRequestConfig {
+        &self.config
+    }
+
+    /// Count a request; `Some` once the client has sent it more than `max_repeats` times
+    /// within the window
+    pub async fn observe(&self, client_id: &str, protocol: ModelProtocol, body: &Value) -> Result<Option<Repeat>> {
+        let key = counter_key(client_id, protocol, body);
+        let count = self.store.incr(&key, 1, Duration::from_secs(self.config.window_secs)).await? as u64;
+        Ok((count > self.config.max_repeats).then_some(Repeat {
+            count,
+            first: count == self.config.max_repeats + 1,
+        }))
+    }
+}
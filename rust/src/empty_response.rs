@@ -0,0 +1,190 @@
+/*!
+ * Empty Response Retry
+ *
+ * Some backends intermittently answer with nothing: no candidates, an empty
+ * message or whitespace only. For the providers `empty_response_retry` covers,
+ * such an answer is retried on the same provider and then, if configured, on
+ * a fallback provider before the request fails. Streams are checked up to
+ * their first real content, which is only then passed on. Answers stopped by
+ * a content filter and blocked prompts are not retried.
+ */
+
+use crate::adapter::ApiServiceAdapter;
+use crate::common::{ModelInfo, ModelListResponse, ModelProtocol};
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::passthrough::ForwardRequest;
+use crate::request_context::RequestContext;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+use tracing::warn;
+
+fn has_text(text: Option<&Value>) -> bool {
+    text.and_then(|t| t.as_str()).is_some_and(|t| !t.trim().is_empty())
+}
+
+/// Whether a complete response carries no answer at all
+pub fn is_degenerate(response: &Value, protocol: ModelProtocol) -> bool {
+    match protocol {
+        ModelProtocol::OpenAI => {
+            let Some(choice) = response.pointer("/choices/0") else {
+                return true;
+            };
+            let message = choice.get("message").unwrap_or(&Value::Null);
+            let filtered = choice.get("finish_reason").and_then(|r| r.as_str()) == Some("content_filter");
+            !filtered
+                && !has_text(message.get("content"))
+                && !has_text(message.get("refusal"))
+                && message.get("tool_calls").and_then(|t| t.as_array()).is_none_or(|calls| calls.is_empty())
+        }
+        ModelProtocol::Claude => {
+            let filtered = matches!(response.get("stop_reason").and_then(|r| r.as_str()), Some("refusal"));
+            let blocks = response.get("content").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+            !filtered
+                && !blocks.iter().any(|block| match block.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => has_text(block.get("text")),
+                    // Reasoning alone is no answer
+                    Some("thinking" | "redacted_thinking") => false,
+                    _ => true,
+                })
+        }
+        ModelProtocol::Gemini => {
+            // A blocked prompt is reported as such, not retried
+            if response.pointer("/promptFeedback/blockReason").is_some() {
+                return false;
+            }
+            let Some(candidate) = response.pointer("/candidates/0") else {
+                return true;
+            };
+            let filtered = matches!(
+                candidate.get("finishReason").and_then(|r| r.as_str()),
+                Some("SAFETY" | "RECITATION" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII")
+            );
+            let parts = candidate.pointer("/content/parts").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+            !filtered
+                && !parts
+                    .iter()
+                    .any(|part| (part.get("thought").is_none() && has_text(part.get("text"))) || part.get("text").is_none())
+        }
+    }
+}
+
+/// Whether a stream event carries part of an answer (or an error, which is passed on as is)
+pub fn has_content(event: &Value, protocol: ModelProtocol) -> bool {
+    match protocol {
+        ModelProtocol::OpenAI => {
+            event.get("error").is_some()
+                || event.get("choices").and_then(|c| c.as_array()).into_iter().flatten().any(|choice| {
+                    let delta = choice.get("delta").unwrap_or(&Value::Null);
+                    has_text(delta.get("content"))
+                        || has_text(delta.get("refusal"))
+                        || delta.get("tool_calls").is_some_and(|t| !t.is_null())
+                        || choice.get("finish_reason").and_then(|r| r.as_str()) == Some("content_filter")
+                })
+        }
+        ModelProtocol::Claude => match event.get("type").and_then(|t| t.as_str()) {
+            Some("content_block_delta") => has_text(event.pointer("/delta/text")) || event.pointer("/delta/partial_json").is_some(),
+            Some("content_block_start") => !matches!(
+                event.pointer("/content_block/type").and_then(|t| t.as_str()),
+                Some("text" | "thinking" | "redacted_thinking")
+            ),
+            Some("message_delta") => event.pointer("/delta/stop_reason").and_then(|r| r.as_str()) == Some("refusal"),
+            Some("error") => true,
+            _ => false,
+        },
+        // Gemini chunks are small complete responses; a blocked prompt counts as content
+        ModelProtocol::Gemini => !is_degenerate(event, protocol),
+    }
+}
+
+/// Retries the wrapped adapter's empty answers, then tries a fallback provider. Streams
+/// have to be inspected, so raw stream passthrough is not offered
+pub struct EmptyResponseRetryAdapter {
+    inner: Box<dyn ApiServiceAdapter>,
+    retries: u32,
+    fallback: Option<(String, Box<dyn ApiServiceAdapter>)>,
+}
+
+impl EmptyResponseRetryAdapter {
+    pub fn new(inner: Box<dyn ApiServiceAdapter>, retries: u32, fallback: Option<(String, Box<dyn ApiServiceAdapter>)>) -> Self {
+        Self { inner, retries, fallback }
+    }
+
+    /// The adapters to try, in order, with a name for the logs
+    fn attempts(&self) -> Vec<(&str, &dyn ApiServiceAdapter)> {
+        let mut attempts = vec![("the same provider", self.inner.as_ref()); self.retries as usize + 1];
+        if let Some((name, adapter)) = &self.fallback {
+            attempts.push((name.as_str(), adapter.as_ref()));
+        }
+        attempts
+    }
+}
+
+#[async_trait]
+impl ApiServiceAdapter for EmptyResponseRetryAdapter {
+    fn protocol(&self) -> ModelProtocol {
+        self.inner.protocol()
+    }
+
+    async fn generate_content(&self, model: &str, request: ChatRequest, ctx: &RequestContext) -> Result<ChatResponse> {
+        let attempts = self.attempts();
+        for (attempt, (_, adapter)) in attempts.iter().enumerate() {
+            let response = adapter.generate_content(model, request.clone(), ctx).await?;
+            if !is_degenerate(response.body(), response.protocol()) {
+                return Ok(response);
+            }
+            if let Some((next, _)) = attempts.get(attempt + 1) {
+                warn!("Empty response from {} (attempt {}), retrying on {}", model, attempt + 1, next);
+            }
+        }
+        Err(anyhow!("Upstream returned an empty response for {} after {} attempt(s)", model, attempts.len()))
+    }
+
+    async fn generate_content_stream(&self, model: &str, request: ChatRequest, ctx: &RequestContext) -> Result<ChatStream> {
+        let attempts = self.attempts();
+        for (attempt, (_, adapter)) in attempts.iter().enumerate() {
+            let stream = adapter.generate_content_stream(model, request.clone(), ctx).await?;
+            let (protocol, mut chunks) = (stream.protocol(), stream.into_chunks());
+
+            // Hold back the opening events until something worth sending arrives
+            let mut head = Vec::new();
+            while let Some(item) = chunks.next().await {
+                let decisive = item.as_ref().map_or(true, |event| has_content(event, protocol));
+                head.push(item);
+                if decisive {
+                    let chunks = futures::stream::iter(head).chain(chunks);
+                    return Ok(ChatStream::new(protocol, Box::pin(chunks)));
+                }
+            }
+            if let Some((next, _)) = attempts.get(attempt + 1) {
+                warn!("Empty stream from {} (attempt {}), retrying on {}", model, attempt + 1, next);
+            }
+        }
+        Err(anyhow!("Upstream returned an empty stream for {} after {} attempt(s)", model, attempts.len()))
+    }
+
+    async fn forward(&self, request: ForwardRequest, ctx: &RequestContext) -> Result<reqwest::Response> {
+        self.inner.forward(request, ctx).await
+    }
+
+    async fn list_models(&self) -> Result<ModelListResponse> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model(&self, model: &str) -> Result<Option<ModelInfo>> {
+        self.inner.get_model(model).await
+    }
+
+    async fn refresh_token(&self) -> Result<()> {
+        self.inner.refresh_token().await
+    }
+
+    fn upstream_url(&self) -> Option<String> {
+        self.inner.upstream_url()
+    }
+
+    async fn clear_cache(&self) -> usize {
+        self.inner.clear_cache().await
+    }
+}
@@ -0,0 +1,176 @@
+/*!
+ * Ensemble
+ *
+ * Virtual models answered by several models at once. The request is sent to
+ * every member in parallel; a judge model then picks the best answer, or,
+ * without a judge (or when its verdict can't be read), the longest answer
+ * that finished normally wins. Members that fail are left out; the request
+ * fails only when all of them do.
+ */
+
+use crate::common::{ModelListResponse, ModelProtocol};
+use crate::config::EnsembleConfig;
+use anyhow::{anyhow, Result};
+use futures::Future;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+/// Response header naming the member whose answer was returned
+pub const MEMBER_HEADER: &str = "x-ensemble-member";
+
+const JUDGE_PROMPT: &str = "You compare candidate answers to a conversation. \
+Reply with the number of the best candidate only: the most correct, complete and helpful one.";
+
+/// The ensemble a requested model names, if any
+pub fn find<'a>(ensembles: &'a [EnsembleConfig], model: &str) -> Option<&'a EnsembleConfig> {
+    ensembles.iter().find(|ensemble| ensemble.model == model)
+}
+
+/// Add the virtual models to a model list
+pub fn list_models(ensembles: &[EnsembleConfig], models: &mut ModelListResponse) {
+    for ensemble in ensembles {
+        models.add_virtual(&ensemble.model, "ensemble");
+    }
+}
+
+/// The winning answer (in `protocol`, with the ensemble's model name) and the member that gave it.
+/// `call(model, body)` sends one non-streaming request in `protocol` and returns the response in it
+pub async fn run<F, Fut>(ensemble: &EnsembleConfig, protocol: ModelProtocol, mut body: Value, call: F) -> Result<(Value, String)>
+where
+    F: Fn(String, Value) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    if let Some(obj) = body.as_object_mut() {
+        obj.remove("stream");
+        obj.remove("stream_options");
+    }
+
+    let calls = ensemble.members.iter().map(|member| {
+        let mut body = body.clone();
+        body["model"] = json!(member);
+        call(member.clone(), body)
+    });
+    let results = futures::future::join_all(calls).await;
+
+    let mut candidates = Vec::new();
+    let mut last_error = None;
+    for (member, result) in ensemble.members.iter().zip(results) {
+        match result {
+            Ok(response) => candidates.push((member.clone(), response)),
+            Err(e) => {
+                warn!("Ensemble {}: member {} failed: {}", ensemble.model, member, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return Err(last_error.unwrap_or_else(|| anyhow!("Ensemble {} has no members", ensemble.model)));
+    }
+
+    let texts: Vec<String> = candidates.iter().map(|(_, response)| answer_text(response, protocol)).collect();
+    let mut winner = None;
+    if let (Some(judge), true) = (&ensemble.judge, candidates.len() > 1) {
+        let request = judge_request(judge, protocol, JUDGE_PROMPT, &judge_prompt(protocol, &body, &texts));
+        match call(judge.clone(), request).await {
+            Ok(verdict) => {
+                winner = parse_verdict(&answer_text(&verdict, protocol), candidates.len());
+                if winner.is_none() {
+                    warn!("Ensemble {}: judge {} gave no usable verdict", ensemble.model, judge);
+                }
+            }
+            Err(e) => warn!("Ensemble {}: judge {} failed: {}", ensemble.model, judge, e),
+        }
+    }
+    let winner = winner.unwrap_or_else(|| best_by_heuristic(&candidates, &texts, protocol));
+
+    let (member, mut response) = candidates.swap_remove(winner);
+    info!("Ensemble {}: answer from {} ({} candidate(s))", ensemble.model, member, texts.len());
+    if response.get("model").is_some() {
+        response["model"] = json!(ensemble.model);
+    }
+    Ok((response, member))
+}
+
+/// Index of the longest answer among those that finished normally (else among all); ties go to the earlier member
+pub fn best_by_heuristic(candidates: &[(String, Value)], texts: &[String], protocol: ModelProtocol) -> usize {
+    let mut best = 0;
+    let mut best_score = (false, 0);
+    for (i, ((_, response), text)) in candidates.iter().zip(texts).enumerate() {
+        let score = (finished(response, protocol), text.chars().count());
+        if i == 0 || score > best_score {
+            best = i;
+            best_score = score;
+        }
+    }
+    best
+}
+
+/// Whether the answer ended on its own, rather than on a length limit or a filter
+pub(crate) fn finished(response: &Value, protocol: ModelProtocol) -> bool {
+    let reason = match protocol {
+        ModelProtocol::OpenAI => response.pointer("/choices/0/finish_reason"),
+        ModelProtocol::Claude => response.get("stop_reason"),
+        ModelProtocol::Gemini => response.pointer("/candidates/0/finishReason"),
+    };
+    matches!(
+        reason.and_then(|r| r.as_str()),
+        Some("stop" | "tool_calls" | "end_turn" | "tool_use" | "stop_sequence" | "STOP")
+    )
+}
+
+pub(crate) fn answer_text(response: &Value, protocol: ModelProtocol) -> String {
+    crate::logger::extract_text_from_response(response, protocol.as_str())
+}
+
+/// Prompt asking which candidate answer to the conversation is best
+fn judge_prompt(protocol: ModelProtocol, body: &Value, texts: &[String]) -> String {
+    let conversation = crate::logger::extract_prompt_from_request(body, protocol.as_str());
+    let mut prompt = format!("Conversation:\n{}\n", conversation);
+    for (i, text) in texts.iter().enumerate() {
+        prompt.push_str(&format!("\nCandidate {}:\n{}\n", i + 1, text));
+    }
+    prompt.push_str(&format!("\nWhich candidate (1-{}) is best?", texts.len()));
+    prompt
+}
+
+/// A short, deterministic request to a judge model, in `protocol`
+pub(crate) fn judge_request(judge: &str, protocol: ModelProtocol, system: &str, prompt: &str) -> Value {
+    match protocol {
+        ModelProtocol::OpenAI => json!({
+            "model": judge,
+            "messages": [
+                {"role": "system", "content": system},
+                {"role": "user", "content": prompt}
+            ],
+            "temperature": 0
+        }),
+        ModelProtocol::Claude => json!({
+            "model": judge,
+            "max_tokens": 16,
+            "system": system,
+            "messages": [{"role": "user", "content": prompt}],
+            "temperature": 0
+        }),
+        ModelProtocol::Gemini => json!({
+            "systemInstruction": {"parts": [{"text": system}]},
+            "contents": [{"role": "user", "parts": [{"text": prompt}]}],
+            "generationConfig": {"temperature": 0}
+        }),
+    }
+}
+
+/// The first whole number in a judge's reply
+pub(crate) fn first_number(reply: &str) -> Option<usize> {
+    let digits: String = reply
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// The candidate index (0-based) from the first number in the judge's reply, if in range
+pub fn parse_verdict(reply: &str, candidates: usize) -> Option<usize> {
+    let number = first_number(reply)?;
+    (1..=candidates).contains(&number).then(|| number - 1)
+}
@@ -0,0 +1,273 @@
+/*!
+ * Gemini Schema
+ *
+ * Gemini takes schemas (`responseSchema`) in its own subset of OpenAPI 3.0,
+ * not JSON Schema: no `$ref`, no `additionalProperties`, no type arrays. A
+ * client's JSON Schema is translated before it is sent: `$ref`s to `$defs` /
+ * `definitions` are inlined (a recursive reference becomes a plain object),
+ * `["string", "null"]` becomes `nullable`, `const` a one-value `enum`, `oneOf`
+ * `anyOf` and `allOf` is merged. Keywords Gemini does not know, and string
+ * formats other than `enum` / `date-time`, are dropped and reported, so the
+ * caller can warn that the schema was loosened. Function declarations take
+ * their `parameters` through the same translation.
+ */
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+
+/// Keywords Gemini accepts in a schema
+const SUPPORTED: &[&str] = &[
+    "type",
+    "format",
+    "title",
+    "description",
+    "nullable",
+    "enum",
+    "items",
+    "minItems",
+    "maxItems",
+    "properties",
+    "required",
+    "propertyOrdering",
+    "anyOf",
+    "minimum",
+    "maximum",
+    "minLength",
+    "maxLength",
+    "minProperties",
+    "maxProperties",
+    "pattern",
+    "default",
+    "example",
+];
+
+/// Bookkeeping keywords dropped without a mention
+const SILENT: &[&str] = &["$schema", "$id", "$comment", "$defs", "definitions", "$ref", "strict"];
+
+/// A schema in Gemini's dialect, with the JSON Schema keywords that had to be dropped
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeminiSchema {
+    pub schema: Value,
+    pub dropped: BTreeSet<String>,
+}
+
+/// Translate a JSON Schema into Gemini's schema dialect
+pub fn to_gemini(schema: &Value) -> GeminiSchema {
+    let defs = schema
+        .get("$defs")
+        .or_else(|| schema.get("definitions"))
+        .and_then(|d| d.as_object())
+        .cloned()
+        .unwrap_or_default();
+    let mut translator = Translator {
+        defs,
+        resolving: Vec::new(),
+        dropped: BTreeSet::new(),
+    };
+    let schema = translator.translate(schema);
+    GeminiSchema {
+        schema,
+        dropped: translator.dropped,
+    }
+}
+
+/// A function declaration's `parameters`; `None` for a function without arguments, which
+/// Gemini wants declared with no schema rather than an object without properties
+pub fn parameters(schema: &Value) -> Option<GeminiSchema> {
+    let translated = to_gemini(schema);
+    let properties = translated.schema.get("properties").and_then(|p| p.as_object());
+    if translated.schema.get("type").and_then(|t| t.as_str()) == Some("object") && properties.is_none_or(|p| p.is_empty()) {
+        return None;
+    }
+    Some(translated)
+}
+
+/// Translate a Gemini schema back into JSON Schema (lowercase types, `nullable` as a type array)
+pub fn to_json_schema(schema: &Value) -> Value {
+    let Some(object) = schema.as_object() else {
+        return schema.clone();
+    };
+    let mut out = Map::new();
+    for (key, value) in object {
+        let value = match key.as_str() {
+            "type" => json!(value.as_str().map(str::to_lowercase).unwrap_or_default()),
+            "nullable" => continue,
+            "properties" => Value::Object(
+                value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, property)| (name.clone(), to_json_schema(property)))
+                    .collect(),
+            ),
+            "items" => to_json_schema(value),
+            "anyOf" => Value::Array(value.as_array().into_iter().flatten().map(to_json_schema).collect()),
+            _ => value.clone(),
+        };
+        out.insert(key.clone(), value);
+    }
+    if object.get("nullable").and_then(|n| n.as_bool()) == Some(true) {
+        if let Some(Value::String(kind)) = out.get("type").cloned() {
+            out.insert("type".to_string(), json!([kind, "null"]));
+        }
+    }
+    Value::Object(out)
+}
+
+struct Translator {
+    defs: Map<String, Value>,
+    /// Definitions being inlined, to stop at a recursive reference
+    resolving: Vec<String>,
+    dropped: BTreeSet<String>,
+}
+
+impl Translator {
+    fn translate(&mut self, schema: &Value) -> Value {
+        let Some(object) = schema.as_object() else {
+            return schema.clone();
+        };
+
+        if let Some(reference) = object.get("$ref").and_then(|r| r.as_str()) {
+            let mut resolved = self.resolve(reference);
+            // Siblings of `$ref` (usually a description) refine the referenced schema
+            if let (Value::Object(resolved), Value::Object(rest)) = (&mut resolved, self.translate_keywords(object)) {
+                resolved.extend(rest);
+            }
+            return resolved;
+        }
+        self.translate_keywords(object)
+    }
+
+    fn resolve(&mut self, reference: &str) -> Value {
+        let name = reference
+            .strip_prefix("#/$defs/")
+            .or_else(|| reference.strip_prefix("#/definitions/"));
+        let Some(name) = name.filter(|name| self.defs.contains_key(*name)) else {
+            self.dropped.insert(format!("$ref {}", reference));
+            return json!({});
+        };
+        if self.resolving.iter().any(|r| r == name) {
+            self.dropped.insert(format!("recursive $ref {}", reference));
+            return json!({"type": "object"});
+        }
+        let definition = self.defs[name].clone();
+        self.resolving.push(name.to_string());
+        let resolved = self.translate(&definition);
+        self.resolving.pop();
+        resolved
+    }
+
+    fn translate_keywords(&mut self, object: &Map<String, Value>) -> Value {
+        let mut out = Map::new();
+        for (key, value) in object {
+            match key.as_str() {
+                "type" => match value {
+                    Value::Array(types) => {
+                        let kinds: Vec<&Value> = types.iter().filter(|t| t.as_str() != Some("null")).collect();
+                        if kinds.len() < types.len() {
+                            out.insert("nullable".to_string(), json!(true));
+                        }
+                        match kinds.as_slice() {
+                            [kind] => {
+                                out.insert("type".to_string(), (*kind).clone());
+                            }
+                            [] => {}
+                            kinds => {
+                                let any_of = kinds.iter().map(|kind| json!({"type": kind})).collect();
+                                out.insert("anyOf".to_string(), Value::Array(any_of));
+                            }
+                        }
+                    }
+                    kind => {
+                        out.insert("type".to_string(), kind.clone());
+                    }
+                },
+                "const" => {
+                    out.insert("enum".to_string(), json!([value]));
+                }
+                "properties" => {
+                    let properties = value
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .map(|(name, property)| (name.clone(), self.translate(property)))
+                        .collect();
+                    out.insert("properties".to_string(), Value::Object(properties));
+                }
+                "items" => {
+                    out.insert("items".to_string(), self.translate(value));
+                }
+                "anyOf" | "oneOf" => {
+                    let variants: Vec<Value> = value.as_array().into_iter().flatten().map(|v| self.translate(v)).collect();
+                    let (nulls, variants): (Vec<Value>, Vec<Value>) =
+                        variants.into_iter().partition(|v| v.get("type").and_then(|t| t.as_str()) == Some("null"));
+                    if !nulls.is_empty() {
+                        out.insert("nullable".to_string(), json!(true));
+                    }
+                    match <[Value; 1]>::try_from(variants) {
+                        // `anyOf: [X, null]`, the usual optional field, is just a nullable X
+                        Ok([Value::Object(only)]) => out.extend(only),
+                        Ok([only]) => {
+                            out.insert("anyOf".to_string(), json!([only]));
+                        }
+                        Err(variants) if !variants.is_empty() => {
+                            out.insert("anyOf".to_string(), Value::Array(variants));
+                        }
+                        Err(_) => {}
+                    }
+                }
+                "allOf" => {
+                    for part in value.as_array().into_iter().flatten() {
+                        if let Value::Object(part) = self.translate(part) {
+                            merge(&mut out, part);
+                        }
+                    }
+                }
+                "format" => match value.as_str() {
+                    Some(format) if supported_format(object.get("type"), format) => {
+                        out.insert("format".to_string(), value.clone());
+                    }
+                    _ => {
+                        self.dropped.insert(format!("format {}", value.as_str().unwrap_or_default()));
+                    }
+                },
+                key if SUPPORTED.contains(&key) => {
+                    out.insert(key.to_string(), value.clone());
+                }
+                key if SILENT.contains(&key) => {}
+                key => {
+                    self.dropped.insert(key.to_string());
+                }
+            }
+        }
+        Value::Object(out)
+    }
+}
+
+/// Gemini's formats by type; strings only take `enum` and `date-time`
+fn supported_format(kind: Option<&Value>, format: &str) -> bool {
+    match kind.and_then(|k| k.as_str()) {
+        Some("number") => matches!(format, "float" | "double"),
+        Some("integer") => matches!(format, "int32" | "int64"),
+        _ => matches!(format, "enum" | "date-time"),
+    }
+}
+
+/// Merge an `allOf` part: properties and required fields add up, other keywords are overwritten
+fn merge(into: &mut Map<String, Value>, part: Map<String, Value>) {
+    for (key, value) in part {
+        match (key.as_str(), into.get_mut(&key), value) {
+            ("properties", Some(Value::Object(existing)), Value::Object(more)) => existing.extend(more),
+            ("required", Some(Value::Array(existing)), Value::Array(more)) => {
+                for name in more {
+                    if !existing.contains(&name) {
+                        existing.push(name);
+                    }
+                }
+            }
+            (_, _, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
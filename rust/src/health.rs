@@ -0,0 +1,129 @@
+/*!
+ * Health Probes
+ *
+ * `/livez` answers as long as the process serves requests. `/readyz` tells an
+ * orchestrator whether the instance should receive traffic: the configuration
+ * is loaded, providers are registered, the startup warm-up has finished and,
+ * with `readiness.probe_upstreams`, at least one provider's upstream is
+ * reachable. Probe results are reused for `probe_interval_secs`, so frequent
+ * checks do not become upstream traffic.
+ *
+ * The warm-up loads the tokenizer vocabularies and asks every provider for its
+ * models (which also fetches OAuth tokens and opens connections), so the first
+ * requests do not pay for it.
+ */
+
+use crate::config::ReadinessConfig;
+use crate::provider_registry::Routing;
+use crate::tokenizer::ModelTokenizer;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// How long the warm-up waits for one provider's model list
+const WARM_UP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Load the tokenizers and each provider's model list; failures are only logged
+pub async fn warm_up(routing: &Routing) {
+    let started = Instant::now();
+    let tokenizers = tokio::task::spawn_blocking(ModelTokenizer::preload);
+    let model_lists = routing.providers().into_iter().map(|provider| {
+        let adapter = routing.adapters[&provider].clone();
+        async move {
+            match tokio::time::timeout(WARM_UP_TIMEOUT, adapter.list_models()).await {
+                Ok(Ok(models)) => info!("Warm-up: {} lists {} models", provider, models.entries().count()),
+                Ok(Err(e)) => warn!("Warm-up: listing models of {} failed: {}", provider, e),
+                Err(_) => warn!("Warm-up: listing models of {} timed out", provider),
+            }
+        }
+    });
+    futures::future::join_all(model_lists).await;
+    if let Err(e) = tokenizers.await {
+        warn!("Warm-up: loading tokenizers failed: {}", e);
+    }
+    info!("Warm-up finished in {:?}", started.elapsed());
+}
+
+/// Whether `url` answers at all; like the region health checks, anything below 500 counts
+pub async fn reachable(client: &Client, url: &str, timeout: Duration) -> bool {
+    match client.head(url).timeout(timeout).send().await {
+        Ok(response) => !response.status().is_server_error(),
+        Err(_) => false,
+    }
+}
+
+pub struct ReadinessProbe {
+    config: ReadinessConfig,
+    client: Client,
+    /// When the upstreams were last probed, and the results
+    last: Mutex<Option<(Instant, BTreeMap<String, bool>)>>,
+    warmed_up: AtomicBool,
+}
+
+impl ReadinessProbe {
+    pub fn new(config: &ReadinessConfig, client: Client) -> Self {
+        Self {
+            config: config.clone(),
+            client,
+            last: Mutex::new(None),
+            warmed_up: AtomicBool::new(!config.warm_up),
+        }
+    }
+
+    /// Open the readiness gate held while the startup warm-up runs
+    pub fn finish_warm_up(&self) {
+        self.warmed_up.store(true, Ordering::Relaxed);
+    }
+
+    /// Reachability of each provider's upstream; providers without a known upstream URL count
+    /// as reachable. Probes again once the results are stale or the providers have changed
+    pub async fn upstreams(&self, routing: &Routing) -> BTreeMap<String, bool> {
+        let interval = Duration::from_secs(self.config.probe_interval_secs);
+        let mut last = self.last.lock().await;
+        if let Some((at, results)) = last.as_ref() {
+            if at.elapsed() < interval && results.keys().eq(routing.providers().iter()) {
+                return results.clone();
+            }
+        }
+
+        let timeout = Duration::from_secs(self.config.probe_timeout_secs);
+        let probes = routing.providers().into_iter().map(|provider| {
+            let url = routing.adapters[&provider].upstream_url();
+            async move {
+                let ok = match url {
+                    Some(url) => reachable(&self.client, &url, timeout).await,
+                    None => true,
+                };
+                if !ok {
+                    warn!("Readiness probe: upstream of {} is unreachable", provider);
+                }
+                (provider, ok)
+            }
+        });
+        let results: BTreeMap<String, bool> = futures::future::join_all(probes).await.into_iter().collect();
+        *last = Some((Instant::now(), results.clone()));
+        results
+    }
+
+    /// Whether the instance is ready, and the individual checks for the response body
+    pub async fn check(&self, routing: &Routing) -> (bool, Value) {
+        let providers = !routing.adapters.is_empty();
+        let mut checks = json!({"config": true, "providers": providers});
+        let mut ready = providers;
+        if self.config.warm_up {
+            let warmed_up = self.warmed_up.load(Ordering::Relaxed);
+            ready &= warmed_up;
+            checks["warmed_up"] = json!(warmed_up);
+        }
+        if self.config.probe_upstreams {
+            let upstreams = self.upstreams(routing).await;
+            ready &= upstreams.values().any(|ok| *ok);
+            checks["upstreams"] = json!(upstreams);
+        }
+        (ready, checks)
+    }
+}
@@ -0,0 +1,52 @@
+/*!
+ * Conditional Responses
+ *
+ * ETag / If-None-Match support for slowly changing resources such as model
+ * lists, so polling clients get a `304 Not Modified` instead of the full body.
+ */
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Strong ETag derived from the serialized body
+pub fn etag_for(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("\"{}\"", hex)
+}
+
+/// Whether an `If-None-Match` header value matches the current ETag (weak comparison)
+pub fn if_none_match(header_value: &str, etag: &str) -> bool {
+    let bare = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = bare(etag);
+
+    header_value
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || bare(candidate) == etag)
+}
+
+/// JSON response with an ETag, or an empty 304 when the client already has this version
+pub fn conditional_json(request_headers: &HeaderMap, body: &Value) -> Response {
+    let etag = etag_for(body.to_string().as_bytes());
+    let etag_header = HeaderValue::from_str(&etag).expect("ETag is ASCII hex");
+
+    let not_modified = request_headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| if_none_match(v, &etag));
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(body).into_response()
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(header::ETAG, etag_header);
+    // Clients may keep the copy but must revalidate before reuse
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("private, no-cache"));
+    response
+}
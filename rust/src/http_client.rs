@@ -0,0 +1,92 @@
+/*!
+ * Upstream HTTP Client
+ *
+ * One connection-pooled client shared by every provider (and by adapters
+ * rebuilt on credential refresh), so connections and TLS sessions are reused
+ * across accounts. It identifies itself with the configured `User-Agent` and
+ * extra headers. Optionally pre-warms a connection to each upstream at
+ * startup to take the TLS handshake off the first request.
+ */
+
+use crate::config::HttpClientConfig;
+use anyhow::{Context, Result};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{info, warn};
+
+static SHARED: OnceLock<Client> = OnceLock::new();
+
+/// The configured extra headers; a provider's own header of the same name takes precedence
+pub fn default_headers(config: &HttpClientConfig) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(value).with_context(|| format!("Invalid value for header {}", name))?;
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+/// Build a client from `config`
+pub fn build(config: &HttpClientConfig) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(&config.user_agent)
+        .default_headers(default_headers(config)?)
+        .timeout(Duration::from_secs(config.request_timeout_secs))
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs))
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .tcp_keepalive((config.tcp_keepalive_secs > 0).then(|| Duration::from_secs(config.tcp_keepalive_secs)))
+        .tcp_nodelay(true);
+    if config.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+    if let Some(interval) = config.http2_keep_alive_interval_secs {
+        builder = builder
+            .http2_keep_alive_interval(Duration::from_secs(interval))
+            .http2_keep_alive_while_idle(true);
+    }
+    Ok(builder.build()?)
+}
+
+/// The process-wide client, built from `config` on first use
+pub fn shared(config: &HttpClientConfig) -> Result<Client> {
+    if let Some(client) = SHARED.get() {
+        return Ok(client.clone());
+    }
+    let client = build(config)?;
+    Ok(SHARED.get_or_init(|| client).clone())
+}
+
+/// `scheme://host[:port]` of a URL
+pub fn origin(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    match url.origin() {
+        origin @ url::Origin::Tuple(..) => Some(origin.ascii_serialization()),
+        url::Origin::Opaque(_) => None,
+    }
+}
+
+/// Open a pooled connection to each upstream origin; any HTTP response counts,
+/// failures are only logged
+pub async fn prewarm(client: &Client, urls: &[String]) -> usize {
+    let mut origins: Vec<String> = urls.iter().filter_map(|url| origin(url)).collect();
+    origins.sort();
+    origins.dedup();
+
+    let attempts = origins.iter().map(|origin| async move {
+        match client.head(origin.as_str()).send().await {
+            Ok(_) => {
+                info!("Pre-warmed connection to {}", origin);
+                true
+            }
+            Err(e) => {
+                warn!("Failed to pre-warm connection to {}: {}", origin, e);
+                false
+            }
+        }
+    });
+    futures::future::join_all(attempts).await.into_iter().filter(|ok| *ok).count()
+}
@@ -0,0 +1,127 @@
+/*!
+ * Idempotency Keys
+ *
+ * A client that retries a chat request after a timeout cannot tell whether the
+ * first attempt was answered, and a blind retry is a second paid upstream
+ * call. With `idempotency` configured, a request carrying an `Idempotency-Key`
+ * header is recorded per client and key: while the first attempt runs, a retry
+ * is refused with a 409; once it has been answered, a retry gets the recorded
+ * answer back (marked `Idempotent-Replayed: true`) for `ttl_secs`. Reusing a
+ * key for a different request body is a 422. Failed attempts and streamed
+ * answers are not recorded, so their retries run again. Records are kept in
+ * the shared store, so a retry that lands on another instance is answered too.
+ */
+
+use crate::cluster::SharedStore;
+use crate::common::ModelProtocol;
+use crate::config::IdempotencyConfig;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long a first attempt may hold its key before a retry may run again
+const IN_FLIGHT_SECS: u64 = 600;
+
+/// Keys are opaque client strings; bounded so they cannot bloat the store
+pub fn valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 255 && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// One client's use of one key, with the request it was first used for
+#[derive(Debug, Clone)]
+pub struct IdempotentRequest {
+    record_key: String,
+    fingerprint: String,
+}
+
+impl IdempotentRequest {
+    pub fn new(client_id: &str, key: &str, protocol: ModelProtocol, body: &Value) -> Self {
+        let digest = Sha256::digest(crate::response_cache::canonical_json(body).as_bytes());
+        Self {
+            record_key: format!("idem:{}:{}", client_id, key),
+            fingerprint: format!("{:?}:{:x}", protocol, digest),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<Value>,
+}
+
+/// What to do with a request carrying an idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Claim {
+    /// The key is new: handle the request
+    New,
+    /// Already answered: send this back
+    Replay(Value),
+    /// The first attempt is still running
+    InProgress,
+    /// The key was first used for a different request
+    Mismatch,
+}
+
+pub struct IdempotencyStore {
+    store: Arc<dyn SharedStore>,
+    config: IdempotencyConfig,
+}
+
+impl IdempotencyStore {
+    pub fn new(store: Arc<dyn SharedStore>, config: &IdempotencyConfig) -> Self {
+        Self {
+            store,
+            config: config.clone(),
+        }
+    }
+
+    /// Take the key for this request, or find out what became of its first use
+    pub async fn claim(&self, request: &IdempotentRequest) -> Result<Claim> {
+        let pending = serde_json::to_string(&Record {
+            fingerprint: request.fingerprint.clone(),
+            response: None,
+        })?;
+        if self
+            .store
+            .set_if_absent(&request.record_key, &pending, Duration::from_secs(IN_FLIGHT_SECS))
+            .await?
+        {
+            return Ok(Claim::New);
+        }
+
+        // Gone since the claim failed (expired or released): treat it as still running
+        let Some(stored) = self.store.get(&request.record_key).await? else {
+            return Ok(Claim::InProgress);
+        };
+        let record: Record = serde_json::from_str(&stored)?;
+        Ok(match record.response {
+            _ if record.fingerprint != request.fingerprint => Claim::Mismatch,
+            Some(response) => Claim::Replay(response),
+            None => Claim::InProgress,
+        })
+    }
+
+    /// Record the answer to a claimed request for its retries
+    pub async fn complete(&self, request: &IdempotentRequest, response: &Value) -> Result<()> {
+        let record = serde_json::to_string(&Record {
+            fingerprint: request.fingerprint.clone(),
+            response: Some(response.clone()),
+        })?;
+        self.store
+            .set(&request.record_key, &record, Some(Duration::from_secs(self.config.ttl_secs)))
+            .await
+    }
+
+    /// Give the key up after a failed attempt, so a retry runs again
+    pub async fn release(&self, request: &IdempotentRequest) -> Result<()> {
+        self.store.delete(&request.record_key).await
+    }
+}
@@ -0,0 +1,34 @@
+/*!
+ * JSON Parsing
+ *
+ * Hot-path parsing of request and response bodies. With the `simd-json`
+ * feature enabled bodies are parsed with SIMD instructions, which mostly pays
+ * off on large multimodal payloads carrying base64 images; otherwise
+ * serde_json is used.
+ */
+
+use anyhow::Result;
+use serde_json::Value;
+
+/// Parse a body that is no longer needed; with `simd-json` it is parsed in place
+pub fn from_vec(bytes: Vec<u8>) -> Result<Value> {
+    parse(bytes)
+}
+
+/// Parse a borrowed body; with `simd-json` it is copied first
+pub fn from_slice(bytes: &[u8]) -> Result<Value> {
+    #[cfg(feature = "simd-json")]
+    return parse(bytes.to_vec());
+    #[cfg(not(feature = "simd-json"))]
+    return Ok(serde_json::from_slice(bytes)?);
+}
+
+#[cfg(feature = "simd-json")]
+fn parse(mut bytes: Vec<u8>) -> Result<Value> {
+    Ok(simd_json::serde::from_slice(&mut bytes)?)
+}
+
+#[cfg(not(feature = "simd-json"))]
+fn parse(bytes: Vec<u8>) -> Result<Value> {
+    Ok(serde_json::from_slice(&bytes)?)
+}
@@ -0,0 +1,189 @@
+/*!
+ * JWT Client Authentication
+ *
+ * Validates client bearer JWTs (HS256 with a shared secret, or RS256 against a
+ * JWKS endpoint) and maps their claims to a tenant, allowed models, scopes and a
+ * daily request quota. A token unlocks only the scopes it carries.
+ */
+
+use crate::config::JwtConfig;
+use anyhow::{Context, Result};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tracing::debug;
+
+/// Claims the proxy acts on
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtIdentity {
+    pub subject: String,
+    pub tenant: Option<String>,
+    /// `None` means every model is allowed
+    pub allowed_models: Option<Vec<String>>,
+    /// Requests per UTC day
+    pub quota: Option<u64>,
+    /// Scopes the token grants (`chat`, `models`, `files`, `fine_tuning`, or `*`)
+    pub scopes: Vec<String>,
+}
+
+impl JwtIdentity {
+    pub fn allows(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope || s == "*")
+    }
+}
+
+/// An unknown `kid` refetches the JWKS at most this often, so made-up kids cannot turn
+/// every request into an outbound fetch
+const JWKS_MIN_REFETCH: Duration = Duration::from_secs(30);
+
+pub struct JwtValidator {
+    config: JwtConfig,
+    client: reqwest::Client,
+    jwks: RwLock<Option<(JwkSet, Instant)>>,
+    /// Held while fetching, so concurrent misses share one fetch
+    fetching: Mutex<()>,
+}
+
+/// A JWT has three base64url segments; static keys never contain dots
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+impl JwtValidator {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            jwks: RwLock::new(None),
+            fetching: Mutex::new(()),
+        }
+    }
+
+    /// Verify the signature and standard claims, then extract the identity
+    pub async fn validate(&self, token: &str) -> Result<JwtIdentity> {
+        let claims = self.validate_claims(token).await?;
+        Ok(self.identity_from_claims(&claims))
+    }
+
+    /// Verify the signature and standard claims, returning every claim
+    pub async fn validate_claims(&self, token: &str) -> Result<HashMap<String, Value>> {
+        let header = decode_header(token).context("Malformed JWT header")?;
+
+        let key = match header.alg {
+            Algorithm::HS256 => {
+                let secret = self
+                    .config
+                    .hs256_secret
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("HS256 tokens are not accepted"))?;
+                DecodingKey::from_secret(secret.as_bytes())
+            }
+            Algorithm::RS256 => {
+                let kid = header.kid.as_deref().ok_or_else(|| anyhow::anyhow!("JWT has no kid"))?;
+                self.jwks_key(kid).await?
+            }
+            other => anyhow::bail!("Unsupported JWT algorithm: {:?}", other),
+        };
+
+        let mut validation = Validation::new(header.alg);
+        match self.config.audience {
+            Some(ref audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(ref issuer) = self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        Ok(decode::<HashMap<String, Value>>(token, &key, &validation)
+            .context("JWT validation failed")?
+            .claims)
+    }
+
+    fn identity_from_claims(&self, claims: &HashMap<String, Value>) -> JwtIdentity {
+        let allowed_models = claims.get(&self.config.models_claim).and_then(string_list);
+        let scopes = claims
+            .get(&self.config.scopes_claim)
+            .and_then(string_list)
+            .unwrap_or_else(|| self.config.default_scopes.clone());
+
+        JwtIdentity {
+            subject: claims
+                .get("sub")
+                .and_then(|v| v.as_str())
+                .unwrap_or("anonymous")
+                .to_string(),
+            tenant: claims
+                .get(&self.config.tenant_claim)
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            allowed_models,
+            quota: claims.get(&self.config.quota_claim).and_then(|v| v.as_u64()),
+            scopes,
+        }
+    }
+
+    /// Look up a signing key, refetching the JWKS when stale or when the kid is unknown (key rotation)
+    async fn jwks_key(&self, kid: &str) -> Result<DecodingKey> {
+        if let Some(key) = self.cached_key(kid).await {
+            return key;
+        }
+        let _fetching = self.fetching.lock().await;
+        // Another request may have refetched while this one waited
+        if let Some(key) = self.cached_key(kid).await {
+            return key;
+        }
+
+        let url = self
+            .config
+            .jwks_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("RS256 tokens require jwks_url"))?;
+        debug!("Fetching JWKS from {}", url);
+        let jwks: JwkSet = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("Failed to fetch JWKS")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid JWKS document")?;
+
+        let key = jwks
+            .find(kid)
+            .map(DecodingKey::from_jwk)
+            .transpose()?
+            .ok_or_else(|| anyhow::anyhow!("No JWKS key with kid {}", kid));
+        *self.jwks.write().await = Some((jwks, Instant::now()));
+        key
+    }
+
+    /// The answer the cached JWKS gives for `kid`; `None` when it is stale, or misses the kid
+    /// and is old enough to refetch
+    async fn cached_key(&self, kid: &str) -> Option<Result<DecodingKey>> {
+        let cached = self.jwks.read().await;
+        let (ref jwks, fetched_at) = *cached.as_ref()?;
+        let age = fetched_at.elapsed();
+        if age >= Duration::from_secs(self.config.jwks_cache_secs) {
+            return None;
+        }
+        match jwks.find(kid) {
+            Some(jwk) => Some(DecodingKey::from_jwk(jwk).map_err(Into::into)),
+            None if age < JWKS_MIN_REFETCH => Some(Err(anyhow::anyhow!("No JWKS key with kid {}", kid))),
+            None => None,
+        }
+    }
+}
+
+/// A claim holding a list: an array, or a space-separated string like OAuth scopes
+fn string_list(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Array(items) => Some(items.iter().filter_map(|m| m.as_str().map(String::from)).collect()),
+        Value::String(s) => Some(s.split_whitespace().map(String::from).collect()),
+        _ => None,
+    }
+}
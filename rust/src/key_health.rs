@@ -0,0 +1,187 @@
+/*!
+ * Key Health
+ *
+ * Scores each key in `provider_pools` by its recent failures: an auth failure
+ * weighs 3, a 429 1 and a 5xx 0.5, and the total halves every
+ * `half_life_secs`, so old trouble is forgotten. A key whose penalty reaches
+ * `quarantine_threshold` is taken out of rotation for `backoff_secs`, doubled
+ * for every quarantine in a row up to `max_backoff_secs`, and an alert is
+ * sent. Once its backoff is over, a background probe asks the upstream for
+ * its model list with the key; a key that answers is back in rotation, one
+ * that still fails goes back into quarantine for longer. Keys of providers
+ * that cannot be probed this way return after their backoff and are judged
+ * by the next requests.
+ */
+
+use crate::config::{KeyHealthConfig, ProviderConfig};
+use crate::upstream_error::Failure;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How an upstream call went for the key it used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    Success,
+    /// 401 or 403: the key is revoked, expired or lacks access
+    AuthFailure,
+    /// 429
+    RateLimited,
+    /// 5xx
+    ServerError,
+    /// Failed for a reason that says nothing about the key (bad request, network, ...)
+    Other,
+}
+
+impl KeyOutcome {
+    pub fn of<T>(result: &anyhow::Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(e) => Self::of_error(e),
+        }
+    }
+
+    /// Classify a failed call by the upstream status it carries; without one it says nothing about the key
+    pub fn of_error(error: &anyhow::Error) -> Self {
+        match crate::upstream_error::classify(error) {
+            Failure::Status(401 | 403) => Self::AuthFailure,
+            Failure::Status(429) => Self::RateLimited,
+            Failure::Status(500..=599) => Self::ServerError,
+            Failure::Status(_) | Failure::Timeout | Failure::Other => Self::Other,
+        }
+    }
+
+    fn penalty(self) -> f64 {
+        match self {
+            Self::AuthFailure => 3.0,
+            Self::RateLimited => 1.0,
+            Self::ServerError => 0.5,
+            Self::Success | Self::Other => 0.0,
+        }
+    }
+}
+
+/// The health of one key
+#[derive(Debug, Clone, Default)]
+pub struct KeyHealth {
+    penalty: f64,
+    updated: Option<Instant>,
+    /// Quarantines in a row, for the backoff
+    quarantines: u32,
+    /// Set while quarantined; the key is probed once it has passed
+    quarantined_until: Option<Instant>,
+    auth_failures: u64,
+    rate_limited: u64,
+    server_errors: u64,
+}
+
+impl KeyHealth {
+    /// Record a call made with the key; returns the quarantine period when this call puts
+    /// the key into quarantine
+    pub fn record(&mut self, outcome: KeyOutcome, config: &KeyHealthConfig, now: Instant) -> Option<Duration> {
+        match outcome {
+            KeyOutcome::AuthFailure => self.auth_failures += 1,
+            KeyOutcome::RateLimited => self.rate_limited += 1,
+            KeyOutcome::ServerError => self.server_errors += 1,
+            // A key that works again starts its backoff over
+            KeyOutcome::Success => self.quarantines = 0,
+            KeyOutcome::Other => {}
+        }
+        self.penalty = self.decayed(config, now) + outcome.penalty();
+        self.updated = Some(now);
+        if self.quarantined_until.is_none() && self.penalty >= config.quarantine_threshold {
+            return Some(self.quarantine(config, now));
+        }
+        None
+    }
+
+    /// Apply a probe's result; returns the new quarantine period when the key still fails
+    pub fn probed(&mut self, ok: bool, config: &KeyHealthConfig, now: Instant) -> Option<Duration> {
+        if ok {
+            self.release();
+            None
+        } else {
+            Some(self.quarantine(config, now))
+        }
+    }
+
+    /// Put the key back into rotation with a clean score
+    pub fn release(&mut self) {
+        self.penalty = 0.0;
+        self.quarantined_until = None;
+    }
+
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantined_until.is_some()
+    }
+
+    /// Quarantined and past its backoff
+    pub fn due_for_probe(&self, now: Instant) -> bool {
+        self.quarantined_until.is_some_and(|until| until <= now)
+    }
+
+    /// 100 for a key without recent failures, 0 at the quarantine threshold
+    pub fn score(&self, config: &KeyHealthConfig, now: Instant) -> u8 {
+        let share = self.decayed(config, now) / config.quarantine_threshold;
+        ((1.0 - share).clamp(0.0, 1.0) * 100.0).round() as u8
+    }
+
+    pub fn snapshot(&self, config: &KeyHealthConfig, now: Instant) -> Value {
+        json!({
+            "score": self.score(config, now),
+            "quarantined": self.is_quarantined(),
+            "quarantined_for_secs": self.quarantined_until.map(|until| until.saturating_duration_since(now).as_secs()),
+            "auth_failures": self.auth_failures,
+            "rate_limited": self.rate_limited,
+            "server_errors": self.server_errors,
+        })
+    }
+
+    fn decayed(&self, config: &KeyHealthConfig, now: Instant) -> f64 {
+        let Some(updated) = self.updated else {
+            return 0.0;
+        };
+        let half_lives = now.saturating_duration_since(updated).as_secs_f64() / config.half_life_secs.max(1) as f64;
+        self.penalty * 0.5_f64.powf(half_lives)
+    }
+
+    fn quarantine(&mut self, config: &KeyHealthConfig, now: Instant) -> Duration {
+        let backoff = config.backoff_secs.saturating_mul(1 << self.quarantines.min(20)).min(config.max_backoff_secs);
+        self.quarantines += 1;
+        let backoff = Duration::from_secs(backoff);
+        self.quarantined_until = Some(now + backoff);
+        backoff
+    }
+}
+
+/// The API key of a pool entry: its `*_API_KEY` credential
+pub fn api_key(entry: &ProviderConfig) -> Option<&str> {
+    entry
+        .credentials
+        .iter()
+        .filter(|(name, _)| name.ends_with("_API_KEY"))
+        .find_map(|(_, value)| value.as_str())
+        .filter(|key| !key.is_empty())
+}
+
+/// Whether the key of a pool entry works, asking the upstream for its model list;
+/// `None` for providers that cannot be probed with a plain API key
+pub async fn probe(client: &Client, provider_type: &str, entry: &ProviderConfig) -> Option<bool> {
+    let key = api_key(entry)?;
+    let credential = |name: &str| entry.credentials.get(name).and_then(|v| v.as_str()).map(|url| url.trim_end_matches('/'));
+    let request = if provider_type.starts_with("openai") {
+        let base_url = credential("OPENAI_BASE_URL").unwrap_or("https://api.openai.com/v1");
+        client.get(format!("{}/models", base_url)).bearer_auth(key)
+    } else if provider_type.starts_with("claude") {
+        let base_url = credential("CLAUDE_BASE_URL").unwrap_or("https://api.anthropic.com");
+        client
+            .get(format!("{}/v1/models", base_url))
+            .header("x-api-key", key)
+            .header("anthropic-version", "2023-06-01")
+    } else {
+        return None;
+    };
+    Some(matches!(request.timeout(PROBE_TIMEOUT).send().await, Ok(response) if response.status().is_success()))
+}
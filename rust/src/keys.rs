@@ -0,0 +1,335 @@
+/*!
+ * Client Key Store
+ *
+ * Manages proxy client keys: creation with expiry and scopes, rotation with a
+ * grace period, revocation, and last-used tracking. Only SHA-256 hashes of the
+ * keys are persisted. A key may belong to a tenant and be limited to a list of
+ * models and aliases; without a list of its own it gets the tenant's
+ * `tenant_models` entry.
+ */
+
+use crate::config::RequestLimits;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::sync::{RwLock, RwLockWriteGuard};
+use tracing::info;
+use uuid::Uuid;
+
+const KEY_PREFIX: &str = "sk-aic-";
+
+/// A stored client key (never contains the plaintext key)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientKey {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub key_hash: String,
+    /// First characters of the key, for display only
+    pub key_hint: String,
+    /// Allowed scopes; empty means all scopes
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Set when the key was rotated; the key stays valid until `expires_at` (the grace end)
+    #[serde(default)]
+    pub replaced_by: Option<String>,
+    /// Opted out of content logging: no prompt log entries or dataset records
+    #[serde(default)]
+    pub no_content_logging: bool,
+    /// Tenant the key belongs to, for `tenant_models`
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// Models and aliases the key may call (`gpt-4o*` matches by prefix); `None` means
+    /// the tenant's allowlist, or every model for keys without a tenant entry
+    #[serde(default)]
+    pub models: Option<Vec<String>>,
+    /// Request size limits of the key, over the global `request_limits`
+    #[serde(default)]
+    pub limits: Option<RequestLimits>,
+}
+
+impl ClientKey {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.map(|exp| exp > now).unwrap_or(true)
+    }
+
+    pub fn allows(&self, scope: &str) -> bool {
+        self.scopes.is_empty() || self.scopes.iter().any(|s| s == scope || s == "*")
+    }
+}
+
+/// Whether an allowlist of models admits `model`: exactly, by a `prefix*` pattern, or `*`
+pub fn allows_model(allowed: &[String], model: &str) -> bool {
+    allowed.iter().any(|pattern| crate::router::matches(pattern, model))
+}
+
+pub struct KeyStore {
+    file_path: Option<PathBuf>,
+    keys: RwLock<Vec<ClientKey>>,
+    dirty: AtomicBool,
+    /// Modification time of the file as last read or written, to notice outside edits
+    file_modified: Mutex<Option<SystemTime>>,
+}
+
+/// Hash a plaintext key for storage and lookup
+pub fn hash_key(plaintext: &str) -> String {
+    format!("{:x}", Sha256::digest(plaintext.as_bytes()))
+}
+
+fn generate_plaintext() -> String {
+    format!("{}{}{}", KEY_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+impl KeyStore {
+    /// Open the key store, loading existing keys from disk if present
+    pub async fn open(file_path: Option<PathBuf>) -> Result<Self> {
+        let mut keys = Vec::new();
+
+        if let Some(ref path) = file_path {
+            if let Ok(content) = fs::read_to_string(path).await {
+                keys = serde_json::from_str(&content).context("Failed to parse client keys file")?;
+                info!("Loaded {} client keys from {:?}", keys.len(), path);
+            }
+        }
+
+        let file_modified = Mutex::new(Self::modified_time(file_path.as_ref()).await);
+        Ok(Self {
+            file_path,
+            keys: RwLock::new(keys),
+            dirty: AtomicBool::new(false),
+            file_modified,
+        })
+    }
+
+    async fn modified_time(path: Option<&PathBuf>) -> Option<SystemTime> {
+        fs::metadata(path?).await.ok()?.modified().ok()
+    }
+
+    /// Write through a temporary file renamed into place, so a reader never sees half a file
+    async fn save(&self, keys: &[ClientKey]) -> Result<()> {
+        if let Some(ref path) = self.file_path {
+            let json = serde_json::to_string_pretty(keys)?;
+            let mut temp = path.clone().into_os_string();
+            temp.push(".tmp");
+            fs::write(&temp, json).await.context("Failed to write client keys file")?;
+            fs::rename(&temp, path).await.context("Failed to replace client keys file")?;
+            *self.file_modified.lock().unwrap() = Self::modified_time(Some(path)).await;
+        }
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Pick up keys added or revoked in the file by another process (the `keys`
+    /// CLI), keeping the newer last-used time of keys known to both
+    pub async fn reload_if_changed(&self) -> Result<bool> {
+        let mut keys = self.keys.write().await;
+        self.reload_into(&mut keys).await
+    }
+
+    async fn reload_into(&self, keys: &mut Vec<ClientKey>) -> Result<bool> {
+        let modified = Self::modified_time(self.file_path.as_ref()).await;
+        if modified.is_none() || modified == *self.file_modified.lock().unwrap() {
+            return Ok(false);
+        }
+        let Some(ref path) = self.file_path else {
+            return Ok(false);
+        };
+
+        let content = fs::read_to_string(path).await.context("Failed to read client keys file")?;
+        let mut loaded: Vec<ClientKey> = serde_json::from_str(&content).context("Failed to parse client keys file")?;
+
+        for key in &mut loaded {
+            if let Some(current) = keys.iter().find(|k| k.id == key.id) {
+                key.last_used_at = key.last_used_at.max(current.last_used_at);
+            }
+        }
+        info!("Reloaded {} client keys from {:?}", loaded.len(), path);
+        *keys = loaded;
+        *self.file_modified.lock().unwrap() = modified;
+        Ok(true)
+    }
+
+    /// Lock the keys for a change, first picking up outside edits so saving does not undo them
+    async fn lock_for_update(&self) -> Result<RwLockWriteGuard<'_, Vec<ClientKey>>> {
+        let mut keys = self.keys.write().await;
+        self.reload_into(&mut keys).await?;
+        Ok(keys)
+    }
+
+    /// Persist pending last-used updates
+    pub async fn flush_if_dirty(&self) -> Result<()> {
+        if self.dirty.load(Ordering::Relaxed) {
+            let keys = self.lock_for_update().await?;
+            self.save(&keys).await?;
+        }
+        Ok(())
+    }
+
+    /// Create a new key, returning the stored record and the plaintext (shown once)
+    pub async fn create(
+        &self,
+        name: Option<String>,
+        scopes: Vec<String>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(ClientKey, String)> {
+        let plaintext = generate_plaintext();
+        let key = ClientKey {
+            id: Uuid::new_v4().to_string(),
+            name,
+            key_hash: hash_key(&plaintext),
+            key_hint: plaintext[..KEY_PREFIX.len() + 4].to_string(),
+            scopes,
+            created_at: Utc::now(),
+            expires_at,
+            revoked_at: None,
+            last_used_at: None,
+            replaced_by: None,
+            no_content_logging: false,
+            tenant: None,
+            models: None,
+            limits: None,
+        };
+
+        let mut keys = self.lock_for_update().await?;
+        keys.push(key.clone());
+        self.save(&keys).await?;
+
+        Ok((key, plaintext))
+    }
+
+    /// Issue a replacement key; the old key remains valid for `grace` and then expires
+    pub async fn rotate(&self, id: &str, grace: Duration) -> Result<Option<(ClientKey, ClientKey, String)>> {
+        let now = Utc::now();
+        let plaintext = generate_plaintext();
+
+        let mut keys = self.lock_for_update().await?;
+        let Some(old) = keys.iter_mut().find(|k| k.id == id && k.is_active(now)) else {
+            return Ok(None);
+        };
+
+        let new_key = ClientKey {
+            id: Uuid::new_v4().to_string(),
+            name: old.name.clone(),
+            key_hash: hash_key(&plaintext),
+            key_hint: plaintext[..KEY_PREFIX.len() + 4].to_string(),
+            scopes: old.scopes.clone(),
+            created_at: now,
+            expires_at: old.expires_at,
+            revoked_at: None,
+            last_used_at: None,
+            replaced_by: None,
+            no_content_logging: old.no_content_logging,
+            tenant: old.tenant.clone(),
+            models: old.models.clone(),
+            limits: old.limits.clone(),
+        };
+
+        let grace_end = now + grace;
+        old.expires_at = Some(old.expires_at.map(|exp| exp.min(grace_end)).unwrap_or(grace_end));
+        old.replaced_by = Some(new_key.id.clone());
+        let old = old.clone();
+
+        keys.push(new_key.clone());
+        self.save(&keys).await?;
+
+        Ok(Some((old, new_key, plaintext)))
+    }
+
+    /// Revoke a key immediately
+    pub async fn revoke(&self, id: &str) -> Result<Option<ClientKey>> {
+        let mut keys = self.lock_for_update().await?;
+        let Some(key) = keys.iter_mut().find(|k| k.id == id) else {
+            return Ok(None);
+        };
+
+        if key.revoked_at.is_none() {
+            key.revoked_at = Some(Utc::now());
+        }
+        let key = key.clone();
+        self.save(&keys).await?;
+
+        Ok(Some(key))
+    }
+
+    /// Opt a key in or out of content logging
+    pub async fn set_no_content_logging(&self, id: &str, no_content_logging: bool) -> Result<Option<ClientKey>> {
+        let mut keys = self.lock_for_update().await?;
+        let Some(key) = keys.iter_mut().find(|k| k.id == id) else {
+            return Ok(None);
+        };
+
+        key.no_content_logging = no_content_logging;
+        let key = key.clone();
+        self.save(&keys).await?;
+
+        Ok(Some(key))
+    }
+
+    /// Set the tenant of a key and the models it may call
+    pub async fn set_model_access(
+        &self,
+        id: &str,
+        tenant: Option<String>,
+        models: Option<Vec<String>>,
+    ) -> Result<Option<ClientKey>> {
+        let mut keys = self.lock_for_update().await?;
+        let Some(key) = keys.iter_mut().find(|k| k.id == id) else {
+            return Ok(None);
+        };
+
+        key.tenant = tenant;
+        key.models = models;
+        let key = key.clone();
+        self.save(&keys).await?;
+
+        Ok(Some(key))
+    }
+
+    /// Set the request size limits of a key; `None` for the global limits
+    pub async fn set_limits(&self, id: &str, limits: Option<RequestLimits>) -> Result<Option<ClientKey>> {
+        let mut keys = self.lock_for_update().await?;
+        let Some(key) = keys.iter_mut().find(|k| k.id == id) else {
+            return Ok(None);
+        };
+
+        key.limits = limits;
+        let key = key.clone();
+        self.save(&keys).await?;
+
+        Ok(Some(key))
+    }
+
+    pub async fn get(&self, id: &str) -> Option<ClientKey> {
+        self.keys.read().await.iter().find(|k| k.id == id).cloned()
+    }
+
+    pub async fn list(&self) -> Vec<ClientKey> {
+        self.keys.read().await.clone()
+    }
+
+    /// Look up an active key by its plaintext and record its use
+    pub async fn authenticate(&self, plaintext: &str) -> Option<ClientKey> {
+        let hash = hash_key(plaintext);
+        let now = Utc::now();
+
+        let mut keys = self.keys.write().await;
+        let key = keys.iter_mut().find(|k| k.key_hash == hash && k.is_active(now))?;
+        key.last_used_at = Some(now);
+        self.dirty.store(true, Ordering::Relaxed);
+
+        Some(key.clone())
+    }
+}
@@ -0,0 +1,154 @@
+/*!
+ * Client key CLI
+ *
+ * `keys add|list|revoke` subcommands operating on the client key store file,
+ * so operators can manage keys without the admin API or hand-editing JSON. A
+ * running server picks the changes up within a minute (see
+ * `KeyStore::reload_if_changed`).
+ */
+
+use crate::keys::{ClientKey, KeyStore};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Duration, Utc};
+
+pub const USAGE: &str = "\
+Usage:
+  aiclient2api-rust keys add [--name NAME] [--scope SCOPE]... [--expires-in-days DAYS] [--no-content-logging]
+                          [--tenant TENANT] [--model MODEL]...
+  aiclient2api-rust keys list
+  aiclient2api-rust keys revoke <ID>
+
+Common options: --config FILE (to locate `client_keys_file_path`)";
+
+/// Run a `keys` subcommand; `args` are the arguments after `keys`.
+/// Returns what to print on stdout
+pub async fn run(args: &[String], store: &KeyStore) -> Result<String> {
+    match args.first().map(String::as_str) {
+        Some("add") => add(&args[1..], store).await,
+        Some("list") => Ok(format_list(&store.list().await, Utc::now())),
+        Some("revoke") => {
+            let id = args.get(1).ok_or_else(|| anyhow!("keys revoke requires a key ID\n\n{}", USAGE))?;
+            match store.revoke(id).await? {
+                Some(key) => Ok(format!("Revoked key {} ({})", key.id, key.key_hint)),
+                None => bail!("No key with ID {}", id),
+            }
+        }
+        Some(other) => bail!("Unknown keys subcommand `{}`\n\n{}", other, USAGE),
+        None => bail!("{}", USAGE),
+    }
+}
+
+async fn add(args: &[String], store: &KeyStore) -> Result<String> {
+    let mut name = None;
+    let mut scopes = Vec::new();
+    let mut expires_at: Option<DateTime<Utc>> = None;
+    let mut no_content_logging = false;
+    let mut tenant = None;
+    let mut models = Vec::new();
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = || args.get(i + 1).ok_or_else(|| anyhow!("{} requires a value", args[i]));
+        match args[i].as_str() {
+            "--name" => {
+                name = Some(value()?.clone());
+                i += 2;
+            }
+            "--scope" => {
+                scopes.push(value()?.clone());
+                i += 2;
+            }
+            "--expires-in-days" => {
+                let days: i64 = value()?.parse().map_err(|_| anyhow!("--expires-in-days must be a whole number"))?;
+                expires_at = Some(Utc::now() + Duration::days(days));
+                i += 2;
+            }
+            "--tenant" => {
+                tenant = Some(value()?.clone());
+                i += 2;
+            }
+            "--model" => {
+                models.push(value()?.clone());
+                i += 2;
+            }
+            "--no-content-logging" => {
+                no_content_logging = true;
+                i += 1;
+            }
+            // Global options such as `--config FILE` are handled by the config loader
+            "--config" => i += 2,
+            other => bail!("Unknown option `{}` for keys add\n\n{}", other, USAGE),
+        }
+    }
+
+    let (mut key, plaintext) = store.create(name, scopes, expires_at).await?;
+    if no_content_logging {
+        key = store.set_no_content_logging(&key.id, true).await?.unwrap_or(key);
+    }
+    if tenant.is_some() || !models.is_empty() {
+        let models = (!models.is_empty()).then_some(models);
+        key = store.set_model_access(&key.id, tenant, models).await?.unwrap_or(key);
+    }
+
+    Ok(format!(
+        "Created key {}{}\n\n    {}\n\nStore this key now; it cannot be shown again.",
+        key.id,
+        key.name.as_deref().map(|n| format!(" ({})", n)).unwrap_or_default(),
+        plaintext
+    ))
+}
+
+fn status(key: &ClientKey, now: DateTime<Utc>) -> &'static str {
+    if key.revoked_at.is_some() {
+        "revoked"
+    } else if !key.is_active(now) {
+        "expired"
+    } else if key.replaced_by.is_some() {
+        "rotating"
+    } else {
+        "active"
+    }
+}
+
+/// One line per key: ID, hint, status, name, scopes, expiry and last use
+pub fn format_list(keys: &[ClientKey], now: DateTime<Utc>) -> String {
+    if keys.is_empty() {
+        return "No client keys".to_string();
+    }
+    let date = |t: Option<DateTime<Utc>>| t.map(|t| t.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_else(|| "-".to_string());
+
+    let mut rows = vec![[
+        "ID".to_string(),
+        "KEY".to_string(),
+        "STATUS".to_string(),
+        "NAME".to_string(),
+        "SCOPES".to_string(),
+        "EXPIRES".to_string(),
+        "LAST USED".to_string(),
+    ]];
+    for key in keys {
+        rows.push([
+            key.id.clone(),
+            format!("{}...", key.key_hint),
+            status(key, now).to_string(),
+            key.name.clone().unwrap_or_else(|| "-".to_string()),
+            if key.scopes.is_empty() { "*".to_string() } else { key.scopes.join(",") },
+            date(key.expires_at),
+            date(key.last_used_at),
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..7).map(|c| rows.iter().map(|r| r[c].chars().count()).max().unwrap_or(0)).collect();
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
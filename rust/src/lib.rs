@@ -4,12 +4,80 @@
  * Core library modules for the AI API proxy server.
  */
 
+pub mod adapter;
+pub mod alerts;
+pub mod aliases;
+pub mod audit;
+pub mod builtin_tools;
+pub mod cascade;
+pub mod client;
+pub mod cluster;
 pub mod common;
+pub mod concurrency;
+pub mod config;
+pub mod config_validation;
+pub mod continuation;
+pub mod conversations;
 pub mod convert;
 pub mod convert_detailed;
+pub mod dataset;
+pub mod duplicate_requests;
+pub mod empty_response;
+pub mod ensemble;
+pub mod gemini_schema;
+pub mod health;
+#[cfg(feature = "server")]
+pub mod http_cache;
+pub mod http_client;
+pub mod idempotency;
+pub mod json;
+pub mod jwt_auth;
+pub mod key_health;
+pub mod keys;
+pub mod keys_cli;
+pub mod log_redaction;
 pub mod logger;
+pub mod metrics;
+pub mod model_list_cache;
+pub mod model_registry;
+pub mod oidc;
+pub mod pacing;
+pub mod pool_manager;
+pub mod passthrough;
+pub mod postprocess;
+pub mod prompt_compression;
+pub mod provider_registry;
+pub mod providers;
+pub mod rate_limit;
+pub mod reasoning;
+pub mod regions;
+pub mod request_decompression;
+pub mod request_context;
+pub mod request_id;
+pub mod request_limits;
+pub mod rerank;
+pub mod response_cache;
+pub mod request_signing;
+pub mod request_timeout;
+pub mod retry_budget;
+pub mod router;
+pub mod secret_refs;
+pub mod secrets;
+pub mod simulated_stream;
+pub mod stream_aggregation;
+pub mod stream_errors;
+pub mod stream_recovery;
 pub mod system_prompt;
+pub mod tokenizer;
+pub mod tool_loop;
+pub mod tool_minification;
+pub mod transcripts;
+pub mod unified;
+pub mod upstream_error;
+pub mod usage_rollup;
+pub mod web_search;
 
 // Re-export commonly used types
+pub use client::UnifiedClient;
 pub use common::{ModelProtocol, ModelProvider};
 
@@ -0,0 +1,167 @@
+/*!
+ * Log Redaction
+ *
+ * Keeps prompts and credentials out of the logs so verbose logging can run in
+ * production: prompt log content can be hashed or truncated, API keys and
+ * bearer tokens are masked in every log line, and per-route sampling rates
+ * keep only a share of the info/debug output of busy routes (warnings and
+ * errors are always kept).
+ */
+
+use crate::config::{ContentRedaction, LoggingConfig};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::future::Future;
+use std::io::Write;
+use std::sync::OnceLock;
+use tracing::{Level, Metadata};
+
+static GLOBAL: OnceLock<LoggingConfig> = OnceLock::new();
+
+tokio::task_local! {
+    /// Whether the request being handled was picked for logging
+    static IN_SAMPLE: bool;
+}
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Credential patterns; the first capture group (if any) is kept as context
+fn secret_patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            r"()sk-[A-Za-z0-9_\-]{16,}",
+            r"()AIza[0-9A-Za-z_\-]{30,}",
+            r"(?i)(bearer\s+)[A-Za-z0-9._\-~+/=]{8,}",
+            r#"(?i)((?:api[_-]?key|access[_-]?token|refresh[_-]?token|client[_-]?secret|password)"?\s*[:=]\s*"?)[^"'\s,&}]{4,}"#,
+            r"(?i)([?&]key=)[^&\s]+",
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("valid secret pattern"))
+        .collect()
+    })
+}
+
+/// Set the process-wide settings from the config; call once at startup
+pub fn configure(config: &LoggingConfig) {
+    let _ = GLOBAL.set(config.clone());
+}
+
+/// Defaults until `configure` runs, so logging before the config is loaded does not fix the settings
+fn settings() -> &'static LoggingConfig {
+    static DEFAULT: OnceLock<LoggingConfig> = OnceLock::new();
+    GLOBAL.get().unwrap_or_else(|| DEFAULT.get_or_init(LoggingConfig::default))
+}
+
+/// Mask credentials in a log line
+pub fn scrub_secrets(text: &str) -> Cow<'_, str> {
+    let mut text = Cow::Borrowed(text);
+    for pattern in secret_patterns() {
+        if pattern.is_match(&text) {
+            let replaced = pattern.replace_all(&text, format!("${{1}}{}", REDACTED));
+            text = Cow::Owned(replaced.into_owned());
+        }
+    }
+    text
+}
+
+/// Message content as the prompt log should show it
+pub fn redact_content(text: &str, mode: ContentRedaction, truncate_chars: usize) -> String {
+    match mode {
+        ContentRedaction::Off => text.to_string(),
+        ContentRedaction::Hash => {
+            let digest = Sha256::digest(text.as_bytes());
+            let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+            format!("[sha256:{} len={}]", hex, text.chars().count())
+        }
+        ContentRedaction::Truncate => {
+            let total = text.chars().count();
+            if total <= truncate_chars {
+                return text.to_string();
+            }
+            let kept: String = text.chars().take(truncate_chars).collect();
+            format!("{}…[+{} chars]", kept, total - truncate_chars)
+        }
+    }
+}
+
+/// Prompt log content with the configured redaction and secret scrubbing applied
+pub fn prompt_log_content(text: &str) -> String {
+    let settings = settings();
+    let text = redact_content(text, settings.content, settings.truncate_chars);
+    if settings.scrub_secrets {
+        scrub_secrets(&text).into_owned()
+    } else {
+        text
+    }
+}
+
+/// Sampling rate for a request path: the longest matching prefix, 1.0 if none matches
+pub fn sample_rate(path: &str, rates: &std::collections::HashMap<String, f64>) -> f64 {
+    rates
+        .iter()
+        .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(1.0, |(_, rate)| rate.clamp(0.0, 1.0))
+}
+
+/// Draw whether a request with this sampling rate is logged
+pub fn draw(rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    let sample = uuid::Uuid::new_v4().as_u128() as f64 / u128::MAX as f64;
+    sample < rate
+}
+
+/// Run request handling with its sampling decision in scope
+pub async fn scope<F: Future>(in_sample: bool, f: F) -> F::Output {
+    IN_SAMPLE.scope(in_sample, f).await
+}
+
+/// Whether the current request is being logged; work outside a request always is
+pub fn in_sample() -> bool {
+    IN_SAMPLE.try_with(|sampled| *sampled).unwrap_or(true)
+}
+
+/// Log filter: warnings and errors always pass, other events only for sampled requests
+pub fn sampling_filter(metadata: &Metadata<'_>) -> bool {
+    *metadata.level() <= Level::WARN || in_sample()
+}
+
+/// Log writer that masks credentials in each formatted line before it reaches stdout
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScrubbingStdout;
+
+/// One formatted event, written out (scrubbed) when dropped
+pub struct ScrubbedLine {
+    buffer: Vec<u8>,
+}
+
+impl Write for ScrubbedLine {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ScrubbedLine {
+    fn drop(&mut self) {
+        let line = String::from_utf8_lossy(&self.buffer);
+        let line = if settings().scrub_secrets { scrub_secrets(&line) } else { line };
+        let _ = std::io::stdout().lock().write_all(line.as_bytes());
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for ScrubbingStdout {
+    type Writer = ScrubbedLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ScrubbedLine { buffer: Vec::new() }
+    }
+}
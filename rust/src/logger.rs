@@ -55,6 +55,13 @@ impl ConversationLogger {
             return Ok(());
         }
 
+        // Console entries go through the sampling log filter; file entries are skipped here
+        if matches!(self.mode, LogMode::File) && !crate::log_redaction::in_sample() {
+            return Ok(());
+        }
+
+        let content = crate::log_redaction::prompt_log_content(content);
+
         let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S");
         let log_entry = format!(
             "{} [{}]:\n{}\n--------------------------------------\n",
@@ -8,21 +8,84 @@
  * License: GPL-3.0
  */
 
+pub mod admin;
+pub mod alerts;
+pub mod aliases;
+pub mod audit;
+pub mod builtin_tools;
+pub mod cascade;
+pub mod cluster;
 pub mod config;
+pub mod config_validation;
+pub mod continuation;
+pub mod conversations;
+pub mod gemini_schema;
+pub mod health;
+pub mod http_cache;
+pub mod http_client;
+pub mod idempotency;
+pub mod json;
+pub mod jwt_auth;
+pub mod key_health;
+pub mod keys;
+pub mod keys_cli;
 pub mod server;
 pub mod common;
+pub mod concurrency;
 pub mod adapter;
 pub mod convert;
 pub mod convert_detailed;
+pub mod dataset;
+pub mod duplicate_requests;
+pub mod empty_response;
+pub mod ensemble;
 pub mod providers;
+pub mod request_signing;
+pub mod retry_budget;
+pub mod secret_refs;
+pub mod secrets;
+pub mod simulated_stream;
+pub mod stream_aggregation;
+pub mod stream_errors;
+pub mod stream_recovery;
+pub mod oidc;
+pub mod openapi;
+pub mod pacing;
 pub mod pool_manager;
+pub mod provider_registry;
+pub mod passthrough;
+pub mod postprocess;
+pub mod prompt_compression;
+pub mod rate_limit;
+pub mod reasoning;
+pub mod regions;
+pub mod request_decompression;
+pub mod request_context;
+pub mod request_id;
+pub mod request_limits;
+pub mod request_timeout;
+pub mod router;
+pub mod rerank;
+pub mod response_cache;
 pub mod strategies;
 pub mod system_prompt;
+pub mod tokenizer;
+pub mod tool_loop;
+pub mod tool_minification;
+pub mod transcripts;
+pub mod unified;
+pub mod upstream_error;
+pub mod usage_rollup;
+pub mod web_search;
+pub mod log_redaction;
 pub mod logger;
+pub mod metrics;
+pub mod model_list_cache;
+pub mod model_registry;
 
 use anyhow::Result;
 use tracing::{info, error};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -32,9 +95,68 @@ async fn main() -> Result<()> {
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "aiclient2api_rust=info,tower_http=debug".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(log_redaction::ScrubbingStdout)
+                .with_filter(tracing_subscriber::filter::filter_fn(log_redaction::sampling_filter)),
+        )
         .init();
 
+    // Encrypt a credential for storage in config files, then exit
+    let args: Vec<String> = std::env::args().collect();
+    // The plaintext comes from stdin so it never lands in shell history or `ps` output
+    if args.iter().any(|a| a == "--encrypt-secret") {
+        use std::io::{BufRead, IsTerminal};
+        let master_key = std::env::var(secrets::MASTER_KEY_ENV)
+            .map_err(|_| anyhow::anyhow!("{} must be set to encrypt secrets", secrets::MASTER_KEY_ENV))?;
+        let stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            eprint!("Secret to encrypt: ");
+        }
+        let mut plaintext = String::new();
+        stdin.lock().read_line(&mut plaintext)?;
+        let plaintext = plaintext.trim_end_matches(['\r', '\n']);
+        if plaintext.is_empty() {
+            anyhow::bail!("--encrypt-secret reads the secret from stdin, but none was given");
+        }
+        println!("{}", secrets::encrypt(plaintext, &secrets::derive_key(&master_key))?);
+        return Ok(());
+    }
+
+    // Manage the client key store, then exit
+    if args.get(1).map(String::as_str) == Some("keys") {
+        let config = config::Config::load_with_args(&args)?;
+        let path = config
+            .client_keys_file_path
+            .ok_or_else(|| anyhow::anyhow!("client_keys_file_path is not configured"))?;
+        let store = keys::KeyStore::open(Some(path)).await?;
+        match keys_cli::run(&args[2..], &store).await {
+            Ok(output) => println!("{}", output),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    // Validate the configuration, print every finding, then exit
+    if args.iter().any(|a| a == "--check-config") {
+        match config::Config::load_with_diagnostics(&args) {
+            Ok((_, warnings)) => {
+                for warning in &warnings {
+                    println!("{}", warning);
+                }
+                println!("configuration OK ({} warnings)", warnings.len());
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     info!("Starting AIClient-2-API Rust Server...");
 
     // Load configuration
@@ -0,0 +1,389 @@
+/*!
+ * Runtime Metrics
+ *
+ * Lightweight in-process counters backing the `/stats` endpoint.
+ */
+
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Number of latency samples retained per provider for percentile calculation
+const LATENCY_WINDOW: usize = 1000;
+/// Distinct end users tracked; ids beyond this are not recorded
+const MAX_END_USERS: usize = 10_000;
+/// Distinct `key=value` tags tracked; tags beyond this are not recorded
+const MAX_TAGS: usize = 10_000;
+
+#[derive(Default)]
+struct ProviderStats {
+    requests: u64,
+    errors: u64,
+    latencies_ms: VecDeque<u64>,
+}
+
+#[derive(Default)]
+struct StreamStats {
+    streams: u64,
+    ttft_ms: VecDeque<u64>,
+    tokens_per_second: VecDeque<f64>,
+}
+
+#[derive(Default)]
+struct UsageStats {
+    requests: u64,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+pub struct Metrics {
+    started_at: Instant,
+    total_requests: AtomicU64,
+    client_errors: AtomicU64,
+    server_errors: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    active_streams: Arc<AtomicU64>,
+    providers: Mutex<HashMap<String, ProviderStats>>,
+    end_users: Mutex<HashMap<String, UsageStats>>,
+    /// Keyed by `key=value`
+    tags: Mutex<HashMap<String, UsageStats>>,
+    /// Keyed by `provider/model`
+    streams: Mutex<HashMap<String, StreamStats>>,
+}
+
+/// Decrements the active stream counter when the stream is dropped
+pub struct StreamGuard {
+    active_streams: Arc<AtomicU64>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.active_streams.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            total_requests: AtomicU64::new(0),
+            client_errors: AtomicU64::new(0),
+            server_errors: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            active_streams: Arc::new(AtomicU64::new(0)),
+            providers: Mutex::new(HashMap::new()),
+            end_users: Mutex::new(HashMap::new()),
+            tags: Mutex::new(HashMap::new()),
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a completed HTTP request by its response status code
+    pub fn record_request(&self, status: u16) {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+        if status >= 500 {
+            self.server_errors.fetch_add(1, Ordering::Relaxed);
+        } else if status >= 400 {
+            self.client_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark a stream as active until the returned guard is dropped
+    pub fn stream_started(&self) -> StreamGuard {
+        self.active_streams.fetch_add(1, Ordering::Relaxed);
+        StreamGuard {
+            active_streams: self.active_streams.clone(),
+        }
+    }
+
+    /// Record the outcome and latency of an upstream provider call
+    pub fn record_provider_call(&self, provider: &str, latency: Duration, success: bool) {
+        let mut providers = self.providers.lock().unwrap();
+        let stats = providers.entry(provider.to_string()).or_default();
+        stats.requests += 1;
+        if !success {
+            stats.errors += 1;
+        }
+        if stats.latencies_ms.len() >= LATENCY_WINDOW {
+            stats.latencies_ms.pop_front();
+        }
+        stats.latencies_ms.push_back(latency.as_millis() as u64);
+    }
+
+    /// Record a request made on behalf of an end user, with its token usage when known
+    pub fn record_end_user(&self, end_user: &str, input_tokens: u64, output_tokens: u64) {
+        let mut end_users = self.end_users.lock().unwrap();
+        record_usage(&mut end_users, end_user, MAX_END_USERS, input_tokens, output_tokens);
+    }
+
+    /// Drop the usage recorded for an end user; returns whether there was any
+    pub fn forget_end_user(&self, end_user: &str) -> bool {
+        self.end_users.lock().unwrap().remove(end_user).is_some()
+    }
+
+    /// Record a tagged request against each of its tags, with its token usage when known
+    pub fn record_tags(&self, tags: &BTreeMap<String, String>, input_tokens: u64, output_tokens: u64) {
+        if tags.is_empty() {
+            return;
+        }
+        let mut recorded = self.tags.lock().unwrap();
+        for (key, value) in tags {
+            let label = format!("{}={}", key, value);
+            record_usage(&mut recorded, &label, MAX_TAGS, input_tokens, output_tokens);
+        }
+    }
+
+    /// Record time to first token and throughput of a finished stream
+    pub fn record_stream(&self, provider: &str, model: &str, timing: &StreamTiming) {
+        let mut streams = self.streams.lock().unwrap();
+        let stats = streams.entry(format!("{}/{}", provider, model)).or_default();
+        stats.streams += 1;
+        if let Some(ttft) = timing.ttft {
+            if stats.ttft_ms.len() >= LATENCY_WINDOW {
+                stats.ttft_ms.pop_front();
+            }
+            stats.ttft_ms.push_back(ttft.as_millis() as u64);
+        }
+        if let Some(rate) = timing.tokens_per_second {
+            if stats.tokens_per_second.len() >= LATENCY_WINDOW {
+                stats.tokens_per_second.pop_front();
+            }
+            stats.tokens_per_second.push_back(rate);
+        }
+    }
+
+    /// Build a JSON snapshot of all counters
+    pub fn snapshot(&self) -> Value {
+        let total = self.total_requests.load(Ordering::Relaxed);
+        let client_errors = self.client_errors.load(Ordering::Relaxed);
+        let server_errors = self.server_errors.load(Ordering::Relaxed);
+        let hits = self.cache_hits.load(Ordering::Relaxed);
+        let misses = self.cache_misses.load(Ordering::Relaxed);
+
+        let providers: serde_json::Map<String, Value> = self
+            .providers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, stats)| {
+                let mut sorted: Vec<u64> = stats.latencies_ms.iter().copied().collect();
+                sorted.sort_unstable();
+                (
+                    name.clone(),
+                    json!({
+                        "requests": stats.requests,
+                        "errors": stats.errors,
+                        "error_rate": ratio(stats.errors, stats.requests),
+                        "latency_ms": {
+                            "p50": percentile(&sorted, 50.0),
+                            "p90": percentile(&sorted, 90.0),
+                            "p99": percentile(&sorted, 99.0),
+                        }
+                    }),
+                )
+            })
+            .collect();
+
+        let end_users = usage_snapshot(&self.end_users.lock().unwrap());
+        let tags = usage_snapshot(&self.tags.lock().unwrap());
+
+        let streams: serde_json::Map<String, Value> = self
+            .streams
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, stats)| {
+                let mut ttft: Vec<u64> = stats.ttft_ms.iter().copied().collect();
+                ttft.sort_unstable();
+                let rates = &stats.tokens_per_second;
+                let mean_rate = (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64);
+                (
+                    key.clone(),
+                    json!({
+                        "streams": stats.streams,
+                        "ttft_ms": {
+                            "p50": percentile(&ttft, 50.0),
+                            "p90": percentile(&ttft, 90.0),
+                            "p99": percentile(&ttft, 99.0),
+                        },
+                        "tokens_per_second": mean_rate,
+                    }),
+                )
+            })
+            .collect();
+
+        json!({
+            "uptime_seconds": self.started_at.elapsed().as_secs(),
+            "requests": {
+                "total": total,
+                "client_errors": client_errors,
+                "server_errors": server_errors,
+                "error_rate": ratio(server_errors, total),
+            },
+            "cache": {
+                "hits": hits,
+                "misses": misses,
+                "hit_ratio": ratio(hits, hits + misses),
+            },
+            "active_streams": self.active_streams.load(Ordering::Relaxed),
+            "providers": providers,
+            "end_users": end_users,
+            "tags": tags,
+            "streams": streams,
+        })
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn record_usage(usage: &mut HashMap<String, UsageStats>, key: &str, max_keys: usize, input_tokens: u64, output_tokens: u64) {
+    if !usage.contains_key(key) && usage.len() >= max_keys {
+        return;
+    }
+    let stats = usage.entry(key.to_string()).or_default();
+    stats.requests += 1;
+    stats.input_tokens += input_tokens;
+    stats.output_tokens += output_tokens;
+}
+
+fn usage_snapshot(usage: &HashMap<String, UsageStats>) -> serde_json::Map<String, Value> {
+    usage
+        .iter()
+        .map(|(key, stats)| {
+            (
+                key.clone(),
+                json!({
+                    "requests": stats.requests,
+                    "input_tokens": stats.input_tokens,
+                    "output_tokens": stats.output_tokens,
+                }),
+            )
+        })
+        .collect()
+}
+
+fn ratio(part: u64, total: u64) -> Option<f64> {
+    if total == 0 {
+        None
+    } else {
+        Some(part as f64 / total as f64)
+    }
+}
+
+/// Timing of one streamed response
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamTiming {
+    /// Time from the request reaching the provider to the first generated text
+    pub ttft: Option<Duration>,
+    pub output_tokens: u64,
+    /// Output tokens per second after the first token
+    pub tokens_per_second: Option<f64>,
+}
+
+/// Watches stream chunks for the first token and the output token count
+pub struct StreamTimer {
+    started: Instant,
+    first_token: Option<Instant>,
+    text_chunks: u64,
+    reported_tokens: Option<u64>,
+}
+
+/// Whether a Claude, OpenAI or Gemini stream chunk carries generated text
+fn has_generated_text(chunk: &Value) -> bool {
+    let non_empty = |v: Option<&Value>| v.and_then(|v| v.as_str()).is_some_and(|s| !s.is_empty());
+
+    if let Some(delta) = chunk.get("delta").filter(|_| chunk.get("type").and_then(|t| t.as_str()) == Some("content_block_delta")) {
+        return ["text", "thinking", "partial_json"].iter().any(|key| non_empty(delta.get(*key)));
+    }
+    if let Some(choices) = chunk.get("choices").and_then(|c| c.as_array()) {
+        return choices.iter().any(|choice| {
+            let delta = choice.get("delta");
+            non_empty(delta.and_then(|d| d.get("content"))) || non_empty(delta.and_then(|d| d.get("reasoning_content")))
+        });
+    }
+    chunk
+        .pointer("/candidates/0/content/parts")
+        .and_then(|p| p.as_array())
+        .is_some_and(|parts| parts.iter().any(|part| non_empty(part.get("text"))))
+}
+
+impl StreamTimer {
+    pub fn new(started: Instant) -> Self {
+        Self {
+            started,
+            first_token: None,
+            text_chunks: 0,
+            reported_tokens: None,
+        }
+    }
+
+    pub fn observe(&mut self, chunk: &Value, now: Instant) {
+        if has_generated_text(chunk) {
+            self.first_token.get_or_insert(now);
+            self.text_chunks += 1;
+        }
+        // Providers report cumulative usage, usually on the last chunk
+        let usage = chunk.get("usage").or_else(|| chunk.get("usageMetadata"));
+        let reported = usage.and_then(|u| {
+            ["output_tokens", "completion_tokens", "candidatesTokenCount"]
+                .iter()
+                .find_map(|key| u.get(*key).and_then(|v| v.as_u64()))
+        });
+        if let Some(tokens) = reported {
+            self.reported_tokens = Some(self.reported_tokens.map_or(tokens, |t| t.max(tokens)));
+        }
+    }
+
+    /// Reported usage when available, otherwise one token per text chunk
+    pub fn finish(&self, now: Instant) -> StreamTiming {
+        let output_tokens = self.reported_tokens.unwrap_or(self.text_chunks);
+        let tokens_per_second = self.first_token.and_then(|first| {
+            let generating = now.duration_since(first).as_secs_f64();
+            (generating > 0.0 && output_tokens > 1).then(|| (output_tokens - 1) as f64 / generating)
+        });
+        StreamTiming {
+            ttft: self.first_token.map(|first| first.duration_since(self.started)),
+            output_tokens,
+            tokens_per_second,
+        }
+    }
+}
+
+/// Input and output token counts from a Claude, OpenAI or Gemini response
+pub fn usage_tokens(response: &Value) -> (u64, u64) {
+    let count = |value: &Value, keys: &[&str]| {
+        keys.iter().find_map(|key| value.get(*key).and_then(|v| v.as_u64())).unwrap_or(0)
+    };
+    match response.get("usage").or_else(|| response.get("usageMetadata")) {
+        Some(usage) => (
+            count(usage, &["input_tokens", "prompt_tokens", "promptTokenCount"]),
+            count(usage, &["output_tokens", "completion_tokens", "candidatesTokenCount"]),
+        ),
+        None => (0, 0),
+    }
+}
+
+/// Nearest-rank percentile over an already sorted slice
+pub fn percentile(sorted: &[u64], pct: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
@@ -0,0 +1,89 @@
+/*!
+ * Model List Cache
+ *
+ * Dashboards poll `/v1/models` far more often than the upstream's model list
+ * changes. With `model_list_cache` configured, each provider's model list is
+ * kept in the shared store for `models_ttl_secs`, and the details of a single
+ * model (`/v1/models/{model}`) for `metadata_ttl_secs`. Failed fetches are not
+ * cached. `POST /admin/models/refresh` drops every entry at once, across the
+ * cluster: entries are keyed by a generation number that the refresh bumps.
+ */
+
+use crate::cluster::SharedStore;
+use crate::common::{ModelInfo, ModelListResponse};
+use crate::config::ModelListCacheConfig;
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+const GENERATION_KEY: &str = "models:generation";
+
+/// Outlives any entry, so the generation is never reset under a live entry
+const GENERATION_TTL: Duration = Duration::from_secs(90 * 24 * 3600);
+
+pub struct ModelListCache {
+    store: Arc<dyn SharedStore>,
+    config: ModelListCacheConfig,
+}
+
+impl ModelListCache {
+    pub fn new(store: Arc<dyn SharedStore>, config: &ModelListCacheConfig) -> Self {
+        Self {
+            store,
+            config: config.clone(),
+        }
+    }
+
+    /// A provider's model list, fetched when not cached
+    pub async fn models<F>(&self, provider: &str, fetch: F) -> Result<ModelListResponse>
+    where
+        F: Future<Output = Result<ModelListResponse>>,
+    {
+        let ttl = Duration::from_secs(self.config.models_ttl_secs);
+        self.cached(&format!("list:{}", provider), ttl, fetch).await
+    }
+
+    /// One model's details (`None` when the provider does not know it), fetched when not cached
+    pub async fn model<F>(&self, provider: &str, model: &str, fetch: F) -> Result<Option<ModelInfo>>
+    where
+        F: Future<Output = Result<Option<ModelInfo>>>,
+    {
+        let ttl = Duration::from_secs(self.config.metadata_ttl_secs);
+        self.cached(&format!("model:{}:{}", provider, model), ttl, fetch).await
+    }
+
+    /// Drop every cached list and model; returns the new generation
+    pub async fn refresh(&self) -> Result<i64> {
+        self.store.incr(GENERATION_KEY, 1, GENERATION_TTL).await
+    }
+
+    async fn cached<T, F>(&self, key: &str, ttl: Duration, fetch: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T>>,
+    {
+        // A store failure means fetching every time, not failing the request
+        let key = match self.store.incr(GENERATION_KEY, 0, GENERATION_TTL).await {
+            Ok(generation) => format!("models:{}:{}", generation, key),
+            Err(e) => {
+                warn!("Model list cache unavailable: {}", e);
+                return fetch.await;
+            }
+        };
+        if let Ok(Some(cached)) = self.store.get(&key).await {
+            if let Ok(value) = serde_json::from_str(&cached) {
+                return Ok(value);
+            }
+        }
+
+        let value = fetch.await?;
+        if let Err(e) = self.store.set(&key, &serde_json::to_string(&value)?, Some(ttl)).await {
+            warn!("Could not cache {}: {}", key, e);
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,324 @@
+/*!
+ * Model Registry
+ *
+ * Known limits and capabilities per model family, matched by model-name
+ * prefix (the longest match wins, so `gpt-4o` beats `gpt-4`). Entries from
+ * the config override the built-in table field by field. Requests can be
+ * checked against the limits before dispatch, so an oversized request gets a
+ * precise 400 from the proxy instead of a cryptic upstream error, and routed
+ * to a model with the capabilities (vision, tools) the request needs.
+ */
+
+use crate::config::{ModelRegistryConfig, ModelSpec};
+use anyhow::Result;
+use serde_json::Value;
+
+const MB: usize = 1024 * 1024;
+
+/// `(prefix, max_output_tokens, vision, tools)` for the built-in table
+const BUILTIN: &[(&str, u64, bool, bool)] = &[
+    ("gpt-5", 128_000, true, true),
+    ("gpt-4.1", 32_768, true, true),
+    ("gpt-4o", 16_384, true, true),
+    ("gpt-4-turbo", 4_096, true, true),
+    ("gpt-4", 8_192, false, true),
+    ("gpt-3.5-turbo", 4_096, false, true),
+    ("o1-mini", 65_536, false, false),
+    ("o1", 100_000, true, true),
+    ("o3", 100_000, true, true),
+    ("o4-mini", 100_000, true, true),
+    ("claude-opus-4", 32_000, true, true),
+    ("claude-sonnet-4", 64_000, true, true),
+    ("claude-4-sonnet", 64_000, true, true),
+    ("claude-3-7-sonnet", 64_000, true, true),
+    ("claude-3-5-sonnet", 8_192, true, true),
+    ("claude-3-5-haiku", 8_192, true, true),
+    ("claude-3-opus", 4_096, true, true),
+    ("claude-3-haiku", 4_096, true, true),
+    ("gemini-2.5-pro", 65_536, true, true),
+    ("gemini-2.5-flash", 65_536, true, true),
+    ("gemini-2.0-flash", 8_192, true, true),
+    ("gemini-1.5", 8_192, true, true),
+    ("qwen3-coder", 65_536, false, true),
+];
+
+fn builtin_spec(prefix: &str, max_output_tokens: u64, vision: bool, tools: bool) -> ModelSpec {
+    let mut spec = ModelSpec {
+        model: prefix.to_string(),
+        max_output_tokens: Some(max_output_tokens),
+        vision: Some(vision),
+        tools: Some(tools),
+        ..Default::default()
+    };
+    // Provider-wide caps
+    if prefix.starts_with("claude") {
+        spec.max_images = Some(100);
+        spec.max_image_bytes = Some(5 * MB);
+    } else if prefix.starts_with("gemini") {
+        spec.max_images = Some(3000);
+        spec.max_image_bytes = Some(20 * MB);
+    } else {
+        spec.max_image_bytes = Some(20 * MB);
+        spec.max_tools = Some(128);
+    }
+    spec
+}
+
+/// Fill the fields `base` leaves unknown from `fallback`
+fn merge(base: ModelSpec, fallback: ModelSpec) -> ModelSpec {
+    ModelSpec {
+        model: base.model,
+        max_output_tokens: base.max_output_tokens.or(fallback.max_output_tokens),
+        max_messages: base.max_messages.or(fallback.max_messages),
+        max_images: base.max_images.or(fallback.max_images),
+        max_image_bytes: base.max_image_bytes.or(fallback.max_image_bytes),
+        max_tools: base.max_tools.or(fallback.max_tools),
+        vision: base.vision.or(fallback.vision),
+        tools: base.tools.or(fallback.tools),
+        input_price: base.input_price.or(fallback.input_price),
+        output_price: base.output_price.or(fallback.output_price),
+    }
+}
+
+/// Model name without an API path prefix (`models/gemini-...`), lowercased
+fn normalize(model: &str) -> String {
+    model.rsplit('/').next().unwrap_or(model).to_ascii_lowercase()
+}
+
+fn longest_match<'a>(specs: impl Iterator<Item = &'a ModelSpec>, model: &str) -> Option<&'a ModelSpec> {
+    specs
+        .filter(|spec| model.starts_with(&spec.model.to_ascii_lowercase()))
+        .max_by_key(|spec| spec.model.len())
+}
+
+pub struct ModelRegistry {
+    builtin: Vec<ModelSpec>,
+    configured: Vec<ModelSpec>,
+}
+
+impl ModelRegistry {
+    pub fn new(config: &ModelRegistryConfig) -> Self {
+        Self {
+            builtin: BUILTIN
+                .iter()
+                .map(|&(prefix, max_output_tokens, vision, tools)| builtin_spec(prefix, max_output_tokens, vision, tools))
+                .collect(),
+            configured: config.models.clone(),
+        }
+    }
+
+    /// What is known about a model, `None` if nothing matches
+    pub fn lookup(&self, model: &str) -> Option<ModelSpec> {
+        let model = normalize(model);
+        let builtin = longest_match(self.builtin.iter(), &model).cloned();
+        let configured = longest_match(self.configured.iter(), &model).cloned();
+        match (configured, builtin) {
+            (Some(configured), Some(builtin)) => Some(merge(configured, builtin)),
+            (configured, builtin) => configured.or(builtin),
+        }
+    }
+
+    /// Whether `model` has a capability; models not known to lack it are assumed to have it
+    pub fn supports(&self, model: &str, capability: Capability) -> bool {
+        let Some(spec) = self.lookup(model) else {
+            return true;
+        };
+        let flag = match capability {
+            Capability::Vision => spec.vision,
+            Capability::Tools => spec.tools,
+        };
+        flag.unwrap_or(true)
+    }
+
+    /// The first of `model` and then `fallbacks` that has every capability the
+    /// request needs; the error explains what each candidate lacks
+    pub fn route(&self, model: &str, fallbacks: &[String], features: &RequestFeatures) -> Result<String> {
+        let required = features.required_capabilities();
+        if required.is_empty() {
+            return Ok(model.to_string());
+        }
+        let mut reasons = Vec::new();
+        for candidate in std::iter::once(model).chain(fallbacks.iter().map(String::as_str)) {
+            let missing: Vec<&str> = required
+                .iter()
+                .filter(|capability| !self.supports(candidate, **capability))
+                .map(|capability| capability.as_str())
+                .collect();
+            if missing.is_empty() {
+                return Ok(candidate.to_string());
+            }
+            reasons.push(format!("{} lacks {}", candidate, missing.join(" and ")));
+        }
+        let required: Vec<&str> = required.iter().map(|c| c.as_str()).collect();
+        anyhow::bail!("No model can serve a request needing {}: {}", required.join(" and "), reasons.join("; "))
+    }
+
+    /// Check a request against `model`'s limits. In lenient mode an oversized
+    /// `max_tokens` is clamped (and reported in the returned notes) instead of rejected
+    pub fn enforce(&self, model: &str, body: &mut Value, lenient: bool) -> Result<Vec<String>> {
+        let Some(spec) = self.lookup(model) else {
+            return Ok(Vec::new());
+        };
+        let features = RequestFeatures::of(body);
+        let mut notes = Vec::new();
+
+        if let (Some((field, requested)), Some(limit)) = (features.max_tokens, spec.max_output_tokens) {
+            if requested > limit {
+                if !lenient {
+                    anyhow::bail!("{} of {} exceeds the {} output token limit of {}", field, requested, limit, model);
+                }
+                set_max_tokens(body, field, limit);
+                notes.push(format!("{} clamped from {} to {}", field, requested, limit));
+            }
+        }
+        if let Some(limit) = spec.max_messages {
+            if features.messages > limit {
+                anyhow::bail!("Request has {} messages; {} accepts at most {}", features.messages, model, limit);
+            }
+        }
+        if let Some(limit) = spec.max_images {
+            if features.images.len() > limit {
+                anyhow::bail!("Request has {} images; {} accepts at most {}", features.images.len(), model, limit);
+            }
+        }
+        if let Some(limit) = spec.max_image_bytes {
+            if let Some((index, size)) = features.images.iter().enumerate().find(|(_, size)| **size > limit) {
+                anyhow::bail!(
+                    "Image {} is {:.1} MB; {} accepts images up to {:.1} MB",
+                    index + 1,
+                    *size as f64 / MB as f64,
+                    model,
+                    limit as f64 / MB as f64
+                );
+            }
+        }
+        if let Some(limit) = spec.max_tools {
+            if features.tools > limit {
+                anyhow::bail!("Request defines {} tools; {} accepts at most {}", features.tools, model, limit);
+            }
+        }
+        Ok(notes)
+    }
+}
+
+/// Something a request can need from a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Vision,
+    Tools,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vision => "vision",
+            Self::Tools => "tools",
+        }
+    }
+}
+
+/// What a request (in any protocol) asks of a model
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestFeatures {
+    /// Output token field and its value
+    pub max_tokens: Option<(&'static str, u64)>,
+    pub messages: usize,
+    /// Decoded size of each image; 0 for images passed by URL
+    pub images: Vec<usize>,
+    pub tools: usize,
+}
+
+impl RequestFeatures {
+    pub fn of(body: &Value) -> Self {
+        let max_tokens = ["max_completion_tokens", "max_tokens"]
+            .into_iter()
+            .find_map(|field| Some((field, body.get(field)?.as_u64()?)))
+            .or_else(|| Some(("maxOutputTokens", body.pointer("/generationConfig/maxOutputTokens")?.as_u64()?)));
+
+        let messages = body
+            .get("messages")
+            .or_else(|| body.get("contents"))
+            .and_then(|m| m.as_array());
+        let mut images = Vec::new();
+        for message in messages.into_iter().flatten() {
+            collect_images(message, &mut images);
+        }
+
+        let tools = body
+            .get("tools")
+            .and_then(|t| t.as_array())
+            .map(|tools| {
+                tools
+                    .iter()
+                    .map(|tool| match tool.get("functionDeclarations").and_then(|d| d.as_array()) {
+                        Some(declarations) => declarations.len(),
+                        None => 1,
+                    })
+                    .sum()
+            })
+            .unwrap_or(0);
+
+        Self {
+            max_tokens,
+            messages: messages.map_or(0, |m| m.len()),
+            images,
+            tools,
+        }
+    }
+
+    pub fn required_capabilities(&self) -> Vec<Capability> {
+        let mut required = Vec::new();
+        if !self.images.is_empty() {
+            required.push(Capability::Vision);
+        }
+        if self.tools > 0 {
+            required.push(Capability::Tools);
+        }
+        required
+    }
+}
+
+/// Decoded size of base64 text
+fn base64_size(data: &str) -> usize {
+    let data = data.trim_end_matches('=');
+    data.len() * 3 / 4
+}
+
+fn is_image_mime(value: &Value) -> bool {
+    value.get("mimeType").and_then(|m| m.as_str()).is_some_and(|m| m.starts_with("image/"))
+}
+
+/// Images anywhere in a message: OpenAI `image_url` parts, Claude `image`
+/// blocks (also inside tool results) and Gemini inline/file image parts
+fn collect_images(value: &Value, images: &mut Vec<usize>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| collect_images(item, images)),
+        Value::Object(fields) => {
+            if let Some(image_url) = fields.get("image_url") {
+                let url = image_url.get("url").unwrap_or(image_url).as_str().unwrap_or_default();
+                let size = match url.strip_prefix("data:") {
+                    Some(data_url) => data_url.split_once(',').map_or(0, |(_, data)| base64_size(data)),
+                    None => 0,
+                };
+                images.push(size);
+            } else if fields.get("type").and_then(|t| t.as_str()) == Some("image") {
+                let data = fields.get("source").and_then(|s| s.get("data")).and_then(|d| d.as_str());
+                images.push(data.map_or(0, base64_size));
+            } else if let Some(inline) = fields.get("inlineData").filter(|i| is_image_mime(i)) {
+                images.push(inline.get("data").and_then(|d| d.as_str()).map_or(0, base64_size));
+            } else if fields.get("fileData").is_some_and(is_image_mime) {
+                images.push(0);
+            } else {
+                fields.values().for_each(|field| collect_images(field, images));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn set_max_tokens(body: &mut Value, field: &str, limit: u64) {
+    match field {
+        "maxOutputTokens" => body["generationConfig"]["maxOutputTokens"] = limit.into(),
+        _ => body[field] = limit.into(),
+    }
+}
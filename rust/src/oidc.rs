@@ -0,0 +1,266 @@
+/*!
+ * OIDC Admin Login
+ *
+ * Authorization code flow with PKCE for the admin API. Operators sign in through
+ * the configured identity provider; the ID token's role claim decides whether the
+ * session may only read (viewer) or also change state (operator).
+ */
+
+use crate::config::{JwtConfig, OidcConfig};
+use crate::jwt_auth::JwtValidator;
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::sync::{OnceCell, RwLock};
+use uuid::Uuid;
+
+pub const SESSION_COOKIE: &str = "aic_admin_session";
+/// How long a login may take between redirect and callback
+const LOGIN_TIMEOUT_MINUTES: i64 = 10;
+/// Logins started but not finished; the oldest are dropped beyond this
+pub const MAX_PENDING_LOGINS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    Viewer,
+    Operator,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminSession {
+    pub subject: String,
+    pub role: AdminRole,
+    pub expires_at: DateTime<Utc>,
+}
+
+struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+pub struct OidcClient {
+    config: OidcConfig,
+    client: reqwest::Client,
+    provider: OnceCell<(Discovery, JwtValidator)>,
+    pending: RwLock<HashMap<String, PendingLogin>>,
+    sessions: RwLock<HashMap<String, AdminSession>>,
+}
+
+fn random_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// S256 code challenge for a PKCE verifier
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Map the ID token's role claim to an admin role; `None` means no admin access
+pub fn role_from_claims(config: &OidcConfig, claims: &HashMap<String, Value>) -> Option<AdminRole> {
+    let roles: Vec<&str> = match claims.get(&config.roles_claim) {
+        Some(Value::Array(items)) => items.iter().filter_map(|r| r.as_str()).collect(),
+        Some(Value::String(s)) => s.split_whitespace().collect(),
+        _ => Vec::new(),
+    };
+
+    let has_any = |wanted: &[String]| roles.iter().any(|r| wanted.iter().any(|w| w == r));
+    if has_any(&config.operator_roles) {
+        Some(AdminRole::Operator)
+    } else if has_any(&config.viewer_roles) {
+        Some(AdminRole::Viewer)
+    } else {
+        None
+    }
+}
+
+/// Extract the admin session id from a `Cookie` header
+pub fn session_id_from_cookie(cookie_header: &str) -> Option<&str> {
+    cookie_header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            provider: OnceCell::new(),
+            pending: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Fetch the discovery document once and build an ID token validator from it
+    async fn provider(&self) -> Result<&(Discovery, JwtValidator)> {
+        self.provider
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                let discovery: Discovery = self
+                    .client
+                    .get(&url)
+                    .send()
+                    .await
+                    .context("Failed to fetch OIDC discovery document")?
+                    .error_for_status()?
+                    .json()
+                    .await
+                    .context("Invalid OIDC discovery document")?;
+
+                let validator = JwtValidator::new(JwtConfig {
+                    jwks_url: Some(discovery.jwks_uri.clone()),
+                    issuer: Some(self.config.issuer.clone()),
+                    audience: Some(self.config.client_id.clone()),
+                    ..Default::default()
+                });
+                Ok((discovery, validator))
+            })
+            .await
+    }
+
+    /// Start a login: remember the PKCE verifier and return the identity provider URL
+    pub async fn begin_login(&self) -> Result<String> {
+        let (discovery, _) = self.provider().await?;
+
+        let state = random_token();
+        let code_verifier = random_token();
+        let nonce = random_token();
+        let challenge = pkce_challenge(&code_verifier);
+
+        let mut url = url::Url::parse(&discovery.authorization_endpoint)
+            .context("Invalid authorization endpoint")?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        let now = Utc::now();
+        let mut pending = self.pending.write().await;
+        pending.retain(|_, login| login.expires_at > now);
+        // `/admin/login` is unauthenticated, so a flood of hits must not grow the map without bound
+        while pending.len() >= MAX_PENDING_LOGINS {
+            let Some(oldest) = pending
+                .iter()
+                .min_by_key(|(_, login)| login.expires_at)
+                .map(|(state, _)| state.clone())
+            else {
+                break;
+            };
+            pending.remove(&oldest);
+        }
+        pending.insert(
+            state,
+            PendingLogin {
+                code_verifier,
+                nonce,
+                expires_at: now + Duration::minutes(LOGIN_TIMEOUT_MINUTES),
+            },
+        );
+
+        Ok(url.to_string())
+    }
+
+    /// Finish a login: exchange the code, verify the ID token and open a session
+    pub async fn complete_login(&self, code: &str, state: &str) -> Result<(String, AdminSession)> {
+        let login = self
+            .pending
+            .write()
+            .await
+            .remove(state)
+            .filter(|login| login.expires_at > Utc::now())
+            .ok_or_else(|| anyhow::anyhow!("Unknown or expired login state"))?;
+
+        let (discovery, validator) = self.provider().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", login.code_verifier.as_str()),
+        ];
+        if let Some(ref secret) = self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+
+        let tokens: Value = self
+            .client
+            .post(&discovery.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .context("Token request failed")?
+            .error_for_status()?
+            .json()
+            .await?;
+        let id_token = tokens
+            .get("id_token")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Token response has no id_token"))?;
+
+        let claims = validator.validate_claims(id_token).await?;
+        if claims.get("nonce").and_then(|n| n.as_str()) != Some(login.nonce.as_str()) {
+            anyhow::bail!("ID token nonce mismatch");
+        }
+
+        let role = role_from_claims(&self.config, &claims)
+            .ok_or_else(|| anyhow::anyhow!("User has no admin role"))?;
+        let subject = claims
+            .get("email")
+            .or_else(|| claims.get("sub"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .to_string();
+
+        let session = AdminSession {
+            subject,
+            role,
+            expires_at: Utc::now() + Duration::seconds(self.config.session_ttl_secs as i64),
+        };
+        let session_id = random_token();
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, s| s.expires_at > Utc::now());
+        sessions.insert(session_id.clone(), session.clone());
+
+        Ok((session_id, session))
+    }
+
+    pub async fn session(&self, session_id: &str) -> Option<AdminSession> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .get(session_id)
+            .filter(|s| s.expires_at > Utc::now())
+            .cloned()
+    }
+
+    pub async fn logout(&self, session_id: &str) -> Option<AdminSession> {
+        self.sessions.write().await.remove(session_id)
+    }
+
+    pub fn session_ttl_secs(&self) -> u64 {
+        self.config.session_ttl_secs
+    }
+}
@@ -0,0 +1,918 @@
+/*!
+ * OpenAPI Document
+ *
+ * Describes every route the proxy serves, served at `/openapi.json` with a
+ * Swagger UI at `/docs`. Request and response schemas cover the fields the
+ * proxy reads or adds; other fields of each protocol are accepted and passed
+ * on unchanged. Proxy-specific headers are documented on every client route.
+ */
+
+// The functions and types below exist only to be described; nothing calls or builds them
+#![allow(dead_code)]
+
+use crate::admin::{CreateKeyRequest, KeyLimitsRequest, KeyModelsRequest, KeyPrivacyRequest, PurgeRequest, RotateKeyRequest, UpdateRoutingRequest};
+use crate::config::ModelRoute;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, IntoResponses, Modify, OpenApi, ToSchema};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "AIClient-2-API",
+        description = "Unified proxy for OpenAI, Claude and Gemini protocols"
+    ),
+    paths(
+        health,
+        livez,
+        readyz,
+        stats,
+        chat_completions,
+        provider_chat_completions,
+        models,
+        retrieve_model,
+        provider_models,
+        messages,
+        provider_messages,
+        token_count,
+        rerank,
+        usage_rollup,
+        files,
+        fine_tuning_jobs,
+        gemini_models,
+        gemini_generate,
+        gemini_stream_generate,
+        admin_login,
+        admin_callback,
+        admin_session,
+        admin_logout,
+        admin_providers,
+        admin_key_health,
+        admin_disable_provider,
+        admin_enable_provider,
+        admin_get_routing,
+        admin_update_routing,
+        admin_reload,
+        admin_clear_cache,
+        admin_refresh_models,
+        admin_list_keys,
+        admin_create_key,
+        admin_rotate_key,
+        admin_revoke_key,
+        admin_key_privacy,
+        admin_key_models,
+        admin_key_limits,
+        admin_purge,
+        admin_audit,
+        admin_audit_verify,
+        admin_request_transcript,
+    ),
+    components(schemas(ModelRoute)),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "OpenAI", description = "OpenAI-compatible endpoints"),
+        (name = "Claude", description = "Anthropic Messages API-compatible endpoints"),
+        (name = "Gemini", description = "Gemini API-compatible endpoints"),
+        (name = "Proxy", description = "Proxy-specific endpoints"),
+        (name = "Admin", description = "Operational endpoints; mutations are written to the audit log"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Client credentials are accepted in any of these forms; the admin API takes a bearer key or an OIDC session
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("bearer", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+        components.add_security_scheme("x_api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("x-api-key"))));
+        components.add_security_scheme("x_goog_api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("x-goog-api-key"))));
+        components.add_security_scheme("key_query", SecurityScheme::ApiKey(ApiKey::Query(ApiKeyValue::new("key"))));
+    }
+}
+
+/// The document as served at `/openapi.json`
+pub fn document() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+// ============================================================================
+// Headers
+// ============================================================================
+
+/// Proxy-specific request headers accepted on client routes
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+struct ProxyHeaders {
+    /// End-user identifier for usage attribution; the `user` body field takes precedence
+    #[param(rename = "x-user-id")]
+    user_id: Option<String>,
+    /// Id for correlating the request across systems; one is generated when absent
+    #[param(rename = "x-request-id")]
+    request_id: Option<String>,
+    /// Attribution tags as `key=value` pairs separated by commas
+    #[param(rename = "x-aiproxy-tags")]
+    tags: Option<String>,
+    /// Server-side conversation to continue (when conversation storage is enabled)
+    #[param(rename = "x-conversation-id")]
+    conversation_id: Option<String>,
+    /// `no-cache` bypasses the response cache
+    #[param(rename = "cache-control")]
+    cache_control: Option<String>,
+    /// Quality scores recorded with the request in the dataset, as `name=value` pairs
+    #[param(rename = "x-dataset-scores")]
+    dataset_scores: Option<String>,
+}
+
+/// Headers selecting the upstream account on the OpenAI path
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+struct OpenAIHeaders {
+    #[param(rename = "openai-organization")]
+    organization: Option<String>,
+    #[param(rename = "openai-project")]
+    project: Option<String>,
+}
+
+/// Headers forwarded to Claude upstreams
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+struct ClaudeHeaders {
+    /// Beta flags, comma-separated
+    #[param(rename = "anthropic-beta")]
+    anthropic_beta: Option<String>,
+}
+
+#[derive(IntoParams)]
+#[into_params(parameter_in = Path)]
+struct ProviderPath {
+    /// Provider to send the request to, bypassing model routes (e.g. `claude-custom`)
+    provider: String,
+}
+
+// ============================================================================
+// Common schemas
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    message: String,
+    /// Set for some errors, e.g. `invalid_request_error`
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    /// Set for some errors, e.g. `content_filter` when the upstream refused the prompt
+    code: Option<String>,
+}
+
+/// Errors every client route can return
+#[derive(IntoResponses)]
+enum ClientErrors {
+    #[response(status = 400, description = "Invalid request, or the upstream refused the prompt")]
+    BadRequest(#[to_schema] ErrorResponse),
+    #[response(status = 401, description = "Missing or invalid credentials")]
+    Unauthorized(ErrorResponse),
+    #[response(status = 403, description = "Missing scope, model not allowed, or invalid request signature")]
+    Forbidden(ErrorResponse),
+    #[response(
+        status = 429,
+        description = "Rate limit or daily quota exceeded",
+        headers(("retry-after" = u64, description = "Seconds until a request would be admitted"))
+    )]
+    TooManyRequests(ErrorResponse),
+    #[response(status = 500, description = "Upstream or internal failure")]
+    InternalError(ErrorResponse),
+}
+
+/// A chat response; its headers are common to all chat routes
+#[derive(IntoResponses)]
+enum ChatResponses<T: ToSchema> {
+    #[response(
+        status = 200,
+        description = "The response, or an SSE stream of chunks when `stream` is set",
+        headers(
+            ("x-aiproxy-warnings" = String, description = "One per parameter that could not be converted for the upstream"),
+            ("x-aiproxy-cache" = String, description = "`hit`, `miss` or `stale` when the response cache is enabled"),
+            ("x-cache" = String, description = "`STALE` when a stale cached response stood in for a failed upstream"),
+            ("x-conversation-id" = String, description = "Server-side conversation the exchange was stored in"),
+            ("x-request-id" = String, description = "The client's request id, or the one generated for the request"),
+            ("x-upstream-request-id" = String, description = "The upstream's id for the call, when it reported one"),
+            ("x-ratelimit-limit-requests" = u32, description = "Request limit of the client or upstream"),
+            ("x-ratelimit-remaining-requests" = u32),
+            ("x-ratelimit-reset-requests" = String, description = "Time until the request window resets, e.g. `6m0s`"),
+            ("x-ratelimit-limit-tokens" = u32, description = "Upstream token limit, when reported"),
+            ("x-ratelimit-remaining-tokens" = u32),
+            ("x-ratelimit-reset-tokens" = String),
+        )
+    )]
+    Ok(T),
+}
+
+// ============================================================================
+// OpenAI schemas
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+struct ChatCompletionRequest {
+    /// Model or model alias
+    model: String,
+    messages: Vec<ChatMessage>,
+    stream: Option<bool>,
+    max_tokens: Option<u32>,
+    max_completion_tokens: Option<u32>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    /// A string or up to four strings
+    #[schema(value_type = Option<Object>)]
+    stop: Option<Value>,
+    tools: Option<Vec<Tool>>,
+    /// `none`, `auto`, `required` or a named function
+    #[schema(value_type = Option<Object>)]
+    tool_choice: Option<Value>,
+    parallel_tool_calls: Option<bool>,
+    #[schema(value_type = Option<Object>)]
+    response_format: Option<Value>,
+    /// End-user identifier, hashed when `hash_end_user_ids` is enabled
+    user: Option<String>,
+    /// Attribution tags (proxy extension); removed before the request goes upstream
+    metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ChatMessage {
+    /// `system`, `developer`, `user`, `assistant` or `tool`
+    role: String,
+    content: Option<MessageContent>,
+    name: Option<String>,
+    tool_calls: Option<Vec<ToolCall>>,
+    tool_call_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+/// A content part: `text`, `image_url` / `image` (URL or base64 data), `tool_use`, `tool_result` and others
+#[derive(Serialize, ToSchema)]
+struct ContentPart {
+    #[serde(rename = "type")]
+    kind: String,
+    text: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    image_url: Option<Value>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct Tool {
+    /// `function`, or a built-in such as `web_search` or `code_interpreter`
+    #[serde(rename = "type")]
+    kind: String,
+    function: Option<FunctionDefinition>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct FunctionDefinition {
+    name: String,
+    description: Option<String>,
+    /// JSON Schema of the arguments
+    #[schema(value_type = Option<Object>)]
+    parameters: Option<Value>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    function: FunctionCall,
+}
+
+#[derive(Serialize, ToSchema)]
+struct FunctionCall {
+    name: String,
+    /// JSON-encoded arguments
+    arguments: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ChatCompletion {
+    id: String,
+    object: String,
+    created: i64,
+    model: String,
+    choices: Vec<ChatChoice>,
+    usage: Usage,
+    /// Parameters that could not be converted for the upstream (proxy extension)
+    aiproxy_warnings: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ChatChoice {
+    index: u32,
+    message: ChatMessage,
+    /// `stop`, `length`, `tool_calls` or `content_filter`
+    finish_reason: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct Usage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    total_tokens: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ModelList {
+    object: String,
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ModelEntry {
+    id: String,
+    object: String,
+    owned_by: String,
+}
+
+// ============================================================================
+// Claude schemas
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+struct MessagesRequest {
+    /// Model or model alias
+    model: String,
+    max_tokens: u32,
+    messages: Vec<ClaudeMessage>,
+    /// A string or text blocks
+    #[schema(value_type = Option<Object>)]
+    system: Option<Value>,
+    stream: Option<bool>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    top_k: Option<u32>,
+    stop_sequences: Option<Vec<String>>,
+    #[schema(value_type = Option<Vec<Object>>)]
+    tools: Option<Vec<Value>>,
+    #[schema(value_type = Option<Object>)]
+    tool_choice: Option<Value>,
+    #[schema(value_type = Option<Object>)]
+    thinking: Option<Value>,
+    /// `user_id` identifies the end user; other keys are attribution tags
+    metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ClaudeMessage {
+    /// `user` or `assistant`
+    role: String,
+    content: MessageContent,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ClaudeResponse {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    role: String,
+    /// `text`, `thinking`, `tool_use` and other content blocks
+    #[schema(value_type = Vec<Object>)]
+    content: Vec<Value>,
+    model: String,
+    /// `end_turn`, `max_tokens`, `stop_sequence`, `tool_use` or `refusal`
+    stop_reason: String,
+    usage: ClaudeUsage,
+    aiproxy_warnings: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ClaudeUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+// ============================================================================
+// Gemini schemas
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentRequest {
+    #[schema(value_type = Vec<Object>)]
+    contents: Vec<Value>,
+    #[schema(value_type = Option<Object>)]
+    system_instruction: Option<Value>,
+    #[schema(value_type = Option<Object>)]
+    generation_config: Option<Value>,
+    #[schema(value_type = Option<Vec<Object>>)]
+    tools: Option<Vec<Value>>,
+    #[schema(value_type = Option<Object>)]
+    tool_config: Option<Value>,
+    #[schema(value_type = Option<Vec<Object>>)]
+    safety_settings: Option<Vec<Value>>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[schema(value_type = Vec<Object>)]
+    candidates: Vec<Value>,
+    #[schema(value_type = Option<Object>)]
+    usage_metadata: Option<Value>,
+    #[schema(value_type = Option<Object>)]
+    prompt_feedback: Option<Value>,
+}
+
+// ============================================================================
+// Proxy schemas
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+struct Health {
+    status: String,
+    timestamp: String,
+    /// Default provider
+    provider: String,
+    /// `memory` or `redis`
+    shared_state: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TokenCountRequest {
+    /// Target model; or several in `models`
+    model: Option<String>,
+    models: Option<Vec<String>>,
+    messages: Option<Vec<ChatMessage>>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RerankRequest {
+    model: Option<String>,
+    query: String,
+    /// Strings or `{"text": ...}` objects
+    #[schema(value_type = Vec<Object>)]
+    documents: Vec<Value>,
+    top_n: Option<usize>,
+    return_documents: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RerankResponse {
+    id: String,
+    results: Vec<RerankResult>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RerankResult {
+    index: usize,
+    relevance_score: f64,
+    #[schema(value_type = Option<Object>)]
+    document: Option<Value>,
+}
+
+// ============================================================================
+// Client routes
+// ============================================================================
+
+#[utoipa::path(get, path = "/health", tag = "Proxy", responses((status = 200, body = Health)))]
+fn health() {}
+
+#[utoipa::path(get, path = "/livez", tag = "Proxy", responses((status = 200, description = "The process is serving requests", body = Object)))]
+fn livez() {}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "Proxy",
+    responses(
+        (status = 200, description = "Ready for traffic; `checks` lists the individual checks", body = Object),
+        (status = 503, description = "Not ready, e.g. every probed upstream is unreachable", body = Object)
+    )
+)]
+fn readyz() {}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "Proxy",
+    responses((status = 200, description = "Request, provider, cache and concurrency counters", body = Object))
+)]
+fn stats() {}
+
+#[utoipa::path(
+    post,
+    path = "/v1/chat/completions",
+    tag = "OpenAI",
+    request_body = ChatCompletionRequest,
+    params(ProxyHeaders, OpenAIHeaders),
+    responses(ChatResponses<ChatCompletion>, ClientErrors),
+    security(("bearer" = []), ("x_api_key" = []), ("key_query" = []))
+)]
+fn chat_completions() {}
+
+#[utoipa::path(
+    post,
+    path = "/{provider}/v1/chat/completions",
+    tag = "OpenAI",
+    request_body = ChatCompletionRequest,
+    params(ProviderPath, ProxyHeaders, OpenAIHeaders),
+    responses(ChatResponses<ChatCompletion>, ClientErrors),
+    security(("bearer" = []), ("x_api_key" = []), ("key_query" = []))
+)]
+fn provider_chat_completions() {}
+
+#[utoipa::path(
+    get,
+    path = "/v1/models",
+    tag = "OpenAI",
+    responses(
+        (status = 200, body = ModelList, headers(("etag" = String))),
+        (status = 304, description = "`If-None-Match` matched the current list"),
+        ClientErrors
+    ),
+    security(("bearer" = []), ("x_api_key" = []), ("key_query" = []))
+)]
+fn models() {}
+
+#[utoipa::path(
+    get,
+    path = "/v1/models/{model}",
+    tag = "OpenAI",
+    params(("model" = String, Path)),
+    responses(
+        (status = 200, body = ModelEntry, headers(("etag" = String))),
+        (status = 304, description = "`If-None-Match` matched the current details"),
+        (status = 404, body = ErrorResponse),
+        ClientErrors
+    ),
+    security(("bearer" = []), ("x_api_key" = []), ("key_query" = []))
+)]
+fn retrieve_model() {}
+
+#[utoipa::path(
+    get,
+    path = "/{provider}/v1/models",
+    tag = "OpenAI",
+    params(ProviderPath),
+    responses((status = 200, body = ModelList), ClientErrors),
+    security(("bearer" = []), ("x_api_key" = []), ("key_query" = []))
+)]
+fn provider_models() {}
+
+#[utoipa::path(
+    post,
+    path = "/v1/messages",
+    tag = "Claude",
+    request_body = MessagesRequest,
+    params(ProxyHeaders, ClaudeHeaders),
+    responses(ChatResponses<ClaudeResponse>, ClientErrors),
+    security(("x_api_key" = []), ("bearer" = []))
+)]
+fn messages() {}
+
+#[utoipa::path(
+    post,
+    path = "/{provider}/v1/messages",
+    tag = "Claude",
+    request_body = MessagesRequest,
+    params(ProviderPath, ProxyHeaders, ClaudeHeaders),
+    responses(ChatResponses<ClaudeResponse>, ClientErrors),
+    security(("x_api_key" = []), ("bearer" = []))
+)]
+fn provider_messages() {}
+
+#[utoipa::path(
+    post,
+    path = "/v1/token-count",
+    tag = "Proxy",
+    request_body = TokenCountRequest,
+    responses((status = 200, description = "Prompt token estimates per target model", body = Object), ClientErrors),
+    security(("bearer" = []), ("x_api_key" = []))
+)]
+fn token_count() {}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rerank",
+    tag = "Proxy",
+    request_body = RerankRequest,
+    responses((status = 200, body = RerankResponse), ClientErrors),
+    security(("bearer" = []), ("x_api_key" = []))
+)]
+fn rerank() {}
+
+#[utoipa::path(
+    get,
+    path = "/v1/usage",
+    tag = "Proxy",
+    params(
+        ("conversation_id" = Option<String>, Query, description = "Conversation whose requests to total"),
+        ("tag" = Option<String>, Query, description = "Tag as `key=value` whose requests to total")
+    ),
+    responses(
+        (status = 200, description = "Tokens, cost, provider mix and latency of the caller's requests", body = Object),
+        (status = 404, description = "Usage rollup disabled, or no requests recorded", body = ErrorResponse),
+        ClientErrors
+    ),
+    security(("bearer" = []), ("x_api_key" = []))
+)]
+fn usage_rollup() {}
+
+#[utoipa::path(
+    get,
+    path = "/v1/files",
+    tag = "OpenAI",
+    description = "Relayed to the OpenAI Files API (also `POST /v1/files` and `/v1/files/{file_id}[/content]`); needs the `files` scope",
+    responses((status = 200, body = Object), ClientErrors),
+    security(("bearer" = []))
+)]
+fn files() {}
+
+#[utoipa::path(
+    get,
+    path = "/v1/fine_tuning/jobs",
+    tag = "OpenAI",
+    description = "Relayed to the OpenAI fine-tuning API (also `POST` and `/v1/fine_tuning/jobs/{job_id}[/events|/checkpoints|/cancel|/pause|/resume]`); needs the `fine_tuning` scope",
+    responses((status = 200, body = Object), ClientErrors),
+    security(("bearer" = []))
+)]
+fn fine_tuning_jobs() {}
+
+#[utoipa::path(
+    get,
+    path = "/v1beta/models",
+    tag = "Gemini",
+    responses((status = 200, body = Object), ClientErrors),
+    security(("x_goog_api_key" = []), ("key_query" = []))
+)]
+fn gemini_models() {}
+
+#[utoipa::path(
+    post,
+    path = "/v1beta/models/{model}:generateContent",
+    tag = "Gemini",
+    request_body = GenerateContentRequest,
+    params(("model" = String, Path, description = "Model name"), ProxyHeaders),
+    responses((status = 200, body = GenerateContentResponse), ClientErrors),
+    security(("x_goog_api_key" = []), ("key_query" = []))
+)]
+fn gemini_generate() {}
+
+#[utoipa::path(
+    post,
+    path = "/v1beta/models/{model}:streamGenerateContent",
+    tag = "Gemini",
+    description = "Streamed as server-sent events (`alt=sse`), one partial `GenerateContentResponse` per event",
+    request_body = GenerateContentRequest,
+    params(("model" = String, Path, description = "Model name"), ProxyHeaders),
+    responses((status = 200, content_type = "text/event-stream", body = String), ClientErrors),
+    security(("x_goog_api_key" = []), ("key_query" = []))
+)]
+fn gemini_stream_generate() {}
+
+// ============================================================================
+// Admin routes
+// ============================================================================
+
+#[utoipa::path(
+    get,
+    path = "/admin/login",
+    tag = "Admin",
+    responses((status = 303, description = "Redirect to the OIDC provider (when `oidc` is configured)")),
+)]
+fn admin_login() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/callback",
+    tag = "Admin",
+    params(("code" = Option<String>, Query), ("state" = Option<String>, Query), ("error" = Option<String>, Query)),
+    responses((status = 303, description = "Session cookie set; redirect to the admin UI"), (status = 401, body = ErrorResponse)),
+)]
+fn admin_callback() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/session",
+    tag = "Admin",
+    responses((status = 200, description = "The signed-in admin and role", body = Object), (status = 401, body = ErrorResponse)),
+)]
+fn admin_session() {}
+
+#[utoipa::path(post, path = "/admin/logout", tag = "Admin", responses((status = 200, body = Object)))]
+fn admin_logout() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/providers",
+    tag = "Admin",
+    responses((status = 200, description = "Provider pools and their health", body = Object), (status = 401, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_providers() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/providers/health",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Health score (100 is healthy), quarantine state and failure counts of each pool key, by provider and uuid", body = Object),
+        (status = 401, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+fn admin_key_health() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/providers/{provider_type}/{uuid}/disable",
+    tag = "Admin",
+    params(("provider_type" = String, Path), ("uuid" = String, Path)),
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_disable_provider() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/providers/{provider_type}/{uuid}/enable",
+    tag = "Admin",
+    params(("provider_type" = String, Path), ("uuid" = String, Path)),
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_enable_provider() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/routing",
+    tag = "Admin",
+    responses((status = 200, description = "Default provider, providers, routes and aliases", body = Object)),
+    security(("bearer" = []))
+)]
+fn admin_get_routing() {}
+
+#[utoipa::path(
+    put,
+    path = "/admin/routing",
+    tag = "Admin",
+    request_body = UpdateRoutingRequest,
+    responses((status = 200, body = Object), (status = 400, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_update_routing() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "Admin",
+    responses((status = 200, description = "The new routing", body = Object), (status = 500, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_reload() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/cache/clear",
+    tag = "Admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+fn admin_clear_cache() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/models/refresh",
+    tag = "Admin",
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_refresh_models() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    tag = "Admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+fn admin_list_keys() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys",
+    tag = "Admin",
+    request_body = CreateKeyRequest,
+    responses((status = 200, description = "The key record and its plaintext, shown once", body = Object)),
+    security(("bearer" = []))
+)]
+fn admin_create_key() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/keys/{id}/rotate",
+    tag = "Admin",
+    params(("id" = String, Path)),
+    request_body = RotateKeyRequest,
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_rotate_key() {}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/keys/{id}",
+    tag = "Admin",
+    params(("id" = String, Path)),
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_revoke_key() {}
+
+#[utoipa::path(
+    put,
+    path = "/admin/keys/{id}/privacy",
+    tag = "Admin",
+    params(("id" = String, Path)),
+    request_body = KeyPrivacyRequest,
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_key_privacy() {}
+
+#[utoipa::path(
+    put,
+    path = "/admin/keys/{id}/models",
+    tag = "Admin",
+    params(("id" = String, Path)),
+    request_body = KeyModelsRequest,
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_key_models() {}
+
+#[utoipa::path(
+    put,
+    path = "/admin/keys/{id}/limits",
+    tag = "Admin",
+    params(("id" = String, Path)),
+    request_body = KeyLimitsRequest,
+    responses((status = 200, body = Object), (status = 404, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_key_limits() {}
+
+#[utoipa::path(
+    post,
+    path = "/admin/privacy/purge",
+    tag = "Admin",
+    request_body = PurgeRequest,
+    responses((status = 200, description = "Counts of deleted items", body = Object), (status = 400, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+fn admin_purge() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    tag = "Admin",
+    params(
+        ("action" = Option<String>, Query, description = "Only entries with this action"),
+        ("limit" = Option<usize>, Query, description = "Most recent entries to return (default 100)")
+    ),
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+fn admin_audit() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit/verify",
+    tag = "Admin",
+    responses((status = 200, description = "Whether the audit log hash chain is intact", body = Object)),
+    security(("bearer" = []))
+)]
+fn admin_audit_verify() {}
+
+#[utoipa::path(
+    get,
+    path = "/admin/requests/{id}",
+    tag = "Admin",
+    params(("id" = String, Path, description = "The request's `x-request-id`")),
+    responses(
+        (status = 200, description = "Client body, upstream body and response, warnings and timings", body = Object),
+        (status = 404, description = "Transcripts are off, or the request is no longer kept")
+    ),
+    security(("bearer" = []))
+)]
+fn admin_request_transcript() {}
@@ -0,0 +1,228 @@
+/*!
+ * Rate-Limit Pacing
+ *
+ * Upstreams publish their limits in `x-ratelimit-*` response headers
+ * (Anthropic's `anthropic-ratelimit-*` are read the same way). With pacing
+ * enabled, each provider's latest published budgets are tracked, and every
+ * call is counted against the request budget until the next response corrects
+ * it. Once a budget is down to its reserve (`reserve_ratio` of the limit),
+ * further calls wait for it to reset instead of provoking a 429. A wait
+ * longer than `max_wait_ms` is not worth holding the client for: the call is
+ * refused with the time left until the reset. Wait times per provider are
+ * reported in `/stats` under `pacing`.
+ */
+
+use crate::config::RateLimitPacingConfig;
+use crate::metrics::percentile;
+use http::HeaderMap;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Number of wait samples retained per provider for percentile calculation
+const WAIT_WINDOW: usize = 1000;
+
+/// One published budget, e.g. requests or tokens per minute
+#[derive(Debug, Clone)]
+struct Budget {
+    limit: Option<f64>,
+    remaining: f64,
+    resets_at: Instant,
+}
+
+impl Budget {
+    /// Time until the budget allows another call, `None` when it allows one now
+    fn wait(&self, now: Instant, reserve_ratio: f64) -> Option<Duration> {
+        let reserve = self.limit.map_or(0.0, |limit| (limit * reserve_ratio).ceil());
+        (self.remaining - reserve < 1.0).then(|| self.resets_at - now)
+    }
+}
+
+#[derive(Debug, Default)]
+struct PacerState {
+    /// Keyed by the header's kind: `requests`, `tokens`, `input-tokens`, ...
+    budgets: BTreeMap<String, Budget>,
+    calls: u64,
+    /// Calls that had to wait
+    paced: u64,
+    /// Calls refused because the wait would have been too long
+    refused: u64,
+    waits_ms: VecDeque<u64>,
+}
+
+/// The budgets one provider has published
+pub struct Pacer {
+    config: RateLimitPacingConfig,
+    state: Mutex<PacerState>,
+}
+
+impl Pacer {
+    pub fn new(config: &RateLimitPacingConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: Mutex::new(PacerState::default()),
+        }
+    }
+
+    /// Take in the `x-ratelimit-*` headers of an upstream response
+    pub fn observe(&self, headers: &HeaderMap) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        for (name, value) in headers {
+            let Some(kind) = name.as_str().strip_prefix("x-ratelimit-remaining-") else {
+                continue;
+            };
+            let header = |field: &str| headers.get(format!("x-ratelimit-{}-{}", field, kind)).and_then(|v| v.to_str().ok());
+            let (Some(remaining), Some(reset)) = (
+                value.to_str().ok().and_then(|v| v.trim().parse::<f64>().ok()),
+                header("reset").and_then(parse_reset),
+            ) else {
+                continue;
+            };
+            let budget = Budget {
+                limit: header("limit").and_then(|v| v.trim().parse().ok()),
+                remaining,
+                resets_at: now + reset,
+            };
+            state.budgets.insert(kind.to_string(), budget);
+        }
+    }
+
+    /// Wait until every budget allows a call, then count it against the request budget.
+    /// Returns how long the call waited, or, when the wait would exceed `max_wait_ms`,
+    /// the time until the budget resets.
+    pub async fn acquire(&self) -> Result<Duration, Duration> {
+        let started = Instant::now();
+        let mut slept = false;
+        let max_wait = Duration::from_millis(self.config.max_wait_ms);
+        loop {
+            let wait = {
+                let now = Instant::now();
+                let mut state = self.state.lock().unwrap();
+                // An expired budget says nothing until the next response publishes a new one
+                state.budgets.retain(|_, budget| budget.resets_at > now);
+                let wait = state.budgets.values().filter_map(|budget| budget.wait(now, self.config.reserve_ratio)).max();
+                let Some(wait) = wait else {
+                    if let Some(requests) = state.budgets.get_mut("requests") {
+                        requests.remaining -= 1.0;
+                    }
+                    let waited = if slept { started.elapsed() } else { Duration::ZERO };
+                    state.record(waited);
+                    return Ok(waited);
+                };
+                if started.elapsed() + wait > max_wait {
+                    state.refused += 1;
+                    return Err(wait);
+                }
+                wait
+            };
+            tokio::time::sleep(wait).await;
+            slept = true;
+        }
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        let mut sorted: Vec<u64> = state.waits_ms.iter().copied().collect();
+        sorted.sort_unstable();
+        let budgets: serde_json::Map<String, Value> = state
+            .budgets
+            .iter()
+            .filter(|(_, budget)| budget.resets_at > now)
+            .map(|(kind, budget)| {
+                (
+                    kind.clone(),
+                    json!({
+                        "limit": budget.limit,
+                        "remaining": budget.remaining.max(0.0),
+                        "resets_in_ms": (budget.resets_at - now).as_millis() as u64,
+                    }),
+                )
+            })
+            .collect();
+        json!({
+            "budgets": budgets,
+            "calls": state.calls,
+            "paced": state.paced,
+            "refused": state.refused,
+            "wait_ms": {
+                "p50": percentile(&sorted, 50.0),
+                "p90": percentile(&sorted, 90.0),
+                "p99": percentile(&sorted, 99.0),
+                "max": sorted.last(),
+            }
+        })
+    }
+}
+
+impl PacerState {
+    fn record(&mut self, waited: Duration) {
+        self.calls += 1;
+        if !waited.is_zero() {
+            self.paced += 1;
+        }
+        if self.waits_ms.len() >= WAIT_WINDOW {
+            self.waits_ms.pop_front();
+        }
+        self.waits_ms.push_back(waited.as_millis() as u64);
+    }
+}
+
+/// Per-provider pacers, created on first use
+pub struct RateLimitPacing {
+    config: RateLimitPacingConfig,
+    pacers: Mutex<HashMap<String, Arc<Pacer>>>,
+}
+
+impl RateLimitPacing {
+    pub fn new(config: &RateLimitPacingConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pacers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn pacer(&self, provider: &str) -> Arc<Pacer> {
+        self.pacers
+            .lock()
+            .unwrap()
+            .entry(provider.to_string())
+            .or_insert_with(|| Arc::new(Pacer::new(&self.config)))
+            .clone()
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let pacers = self.pacers.lock().unwrap();
+        Value::Object(pacers.iter().map(|(provider, pacer)| (provider.clone(), pacer.snapshot())).collect())
+    }
+}
+
+/// A reset header's duration: OpenAI's `20ms`, `1s`, `6m0s`, `1h2m3.5s`, or bare seconds
+pub fn parse_reset(value: &str) -> Option<Duration> {
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut secs = 0.0;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let number: f64 = rest[..end].parse().ok()?;
+        rest = &rest[end..];
+        let (unit, len) = if rest.starts_with("ms") {
+            (0.001, 2)
+        } else if rest.starts_with('h') {
+            (3600.0, 1)
+        } else if rest.starts_with('m') {
+            (60.0, 1)
+        } else if rest.starts_with('s') || rest.is_empty() {
+            (1.0, rest.len().min(1))
+        } else {
+            return None;
+        };
+        secs += number * unit;
+        rest = &rest[len..];
+    }
+    Some(Duration::from_secs_f64(secs))
+}
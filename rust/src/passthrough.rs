@@ -0,0 +1,79 @@
+/*!
+ * API Passthrough
+ *
+ * OpenAI endpoints the proxy does not interpret (the Files API, fine-tuning
+ * jobs) are relayed to the provider as they are: the body is streamed through
+ * in both directions, only content headers are carried over, and the
+ * provider's own credentials replace the client's.
+ */
+
+use http::{HeaderMap, Method, Uri};
+use std::time::Duration;
+
+/// Uploads and downloads can far outlast a chat call
+pub const PASSTHROUGH_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Inbound headers sent on; everything else (notably credentials) is dropped
+const REQUEST_HEADERS: &[&str] = &["content-type", "content-length", "accept"];
+
+/// Upstream response headers returned to the client
+const RESPONSE_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "content-disposition",
+    "openai-processing-ms",
+];
+
+/// A raw API call to relay with the provider's credentials
+pub struct ForwardRequest {
+    pub method: Method,
+    /// Path relative to the provider's API base (e.g. `/files/file-abc`), with any query string
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: reqwest::Body,
+}
+
+fn filter_headers(headers: &HeaderMap, keep: &[&str]) -> HeaderMap {
+    let mut filtered = HeaderMap::new();
+    for (name, value) in headers {
+        if keep.contains(&name.as_str()) {
+            filtered.append(name.clone(), value.clone());
+        }
+    }
+    filtered
+}
+
+pub fn request_headers(inbound: &HeaderMap) -> HeaderMap {
+    filter_headers(inbound, REQUEST_HEADERS)
+}
+
+pub fn response_headers(upstream: &HeaderMap) -> HeaderMap {
+    filter_headers(upstream, RESPONSE_HEADERS)
+}
+
+/// Upstream path for an inbound `/v1/...` URI: the version prefix belongs to the provider's base URL
+pub fn upstream_path(uri: &Uri) -> String {
+    let path = uri.path();
+    let path = path.strip_prefix("/v1").filter(|p| p.starts_with('/')).unwrap_or(path);
+    match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    }
+}
+
+/// Audit log action for a relayed call that changes upstream state, e.g.
+/// `fine_tuning.create` or `fine_tuning.cancel`; `None` for reads and untracked endpoints
+pub fn audit_action(method: &Method, path: &str) -> Option<&'static str> {
+    if method != Method::POST {
+        return None;
+    }
+    let path = path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["fine_tuning", "jobs"] => Some("fine_tuning.create"),
+        ["fine_tuning", "jobs", _, "cancel"] => Some("fine_tuning.cancel"),
+        ["fine_tuning", "jobs", _, "pause"] => Some("fine_tuning.pause"),
+        ["fine_tuning", "jobs", _, "resume"] => Some("fine_tuning.resume"),
+        _ => None,
+    }
+}
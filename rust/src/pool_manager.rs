@@ -2,27 +2,58 @@
  * Provider Pool Manager
  *
  * Manages pools of API service providers with health checking and load balancing.
+ * Requests to a provider with a pool use its keys in turn, skipping the ones
+ * taken out of rotation by hand or quarantined by their health score (see
+ * `key_health`).
  */
 
-use crate::config::ProviderConfig;
-use anyhow::Result;
+use crate::config::{KeyHealthConfig, ProviderConfig};
+use crate::key_health::{KeyHealth, KeyOutcome};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
 pub struct ProviderPoolManager {
     pools: Arc<RwLock<HashMap<String, Vec<ProviderStatus>>>>,
     round_robin_index: Arc<RwLock<HashMap<String, usize>>>,
-    max_error_count: u32,
+    key_health: KeyHealthConfig,
 }
 
 struct ProviderStatus {
     config: ProviderConfig,
     is_healthy: bool,
+    health: KeyHealth,
+}
+
+/// A pool key picked for one request
+#[derive(Clone, PartialEq)]
+pub struct PoolKey {
+    pub provider_type: String,
+    pub uuid: String,
+    pub api_key: String,
+}
+
+impl std::fmt::Debug for PoolKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PoolKey")
+            .field("provider_type", &self.provider_type)
+            .field("uuid", &self.uuid)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProviderStatus {
+    fn to_config(&self) -> ProviderConfig {
+        let mut config = self.config.clone();
+        config.is_healthy = self.is_healthy;
+        config
+    }
 }
 
 impl ProviderPoolManager {
-    pub fn new(pools: HashMap<String, Vec<ProviderConfig>>) -> Self {
+    pub fn new(pools: HashMap<String, Vec<ProviderConfig>>, key_health: &KeyHealthConfig) -> Self {
         let mut status_pools = HashMap::new();
         
         for (provider_type, configs) in pools {
@@ -31,6 +62,7 @@ impl ProviderPoolManager {
                 .map(|config| ProviderStatus {
                     is_healthy: config.is_healthy,
                     config,
+                    health: KeyHealth::default(),
                 })
                 .collect();
             status_pools.insert(provider_type, statuses);
@@ -39,7 +71,7 @@ impl ProviderPoolManager {
         Self {
             pools: Arc::new(RwLock::new(status_pools)),
             round_robin_index: Arc::new(RwLock::new(HashMap::new())),
-            max_error_count: 3,
+            key_health: key_health.clone(),
         }
     }
 
@@ -65,43 +97,172 @@ impl ProviderPoolManager {
         Some(selected.config.clone())
     }
 
-    pub async fn mark_provider_unhealthy(&self, provider_type: &str, uuid: &str) {
+    /// The next healthy key of the provider's pool, for providers authenticated by API key
+    pub async fn select_key(&self, provider_type: &str) -> Option<PoolKey> {
+        if !self.pools.read().await.contains_key(provider_type) {
+            return None;
+        }
+        let selected = self.select_provider(provider_type).await?;
+        Some(PoolKey {
+            provider_type: provider_type.to_string(),
+            api_key: crate::key_health::api_key(&selected)?.to_string(),
+            uuid: selected.uuid,
+        })
+    }
+
+    /// Returns false if no provider with the given uuid exists
+    pub async fn mark_provider_unhealthy(&self, provider_type: &str, uuid: &str) -> bool {
         let mut pools = self.pools.write().await;
         if let Some(pool) = pools.get_mut(provider_type) {
             for provider in pool.iter_mut() {
                 if provider.config.uuid == uuid {
                     provider.is_healthy = false;
+                    // Out of rotation until enabled again, not until a probe succeeds
+                    provider.health.release();
                     tracing::warn!(
                         "Marked provider {} ({}) as unhealthy",
                         provider_type,
                         uuid
                     );
-                    break;
+                    return true;
                 }
             }
         }
+        false
     }
 
-    pub async fn mark_provider_healthy(&self, provider_type: &str, uuid: &str) {
+    /// Returns false if no provider with the given uuid exists
+    pub async fn mark_provider_healthy(&self, provider_type: &str, uuid: &str) -> bool {
         let mut pools = self.pools.write().await;
         if let Some(pool) = pools.get_mut(provider_type) {
             for provider in pool.iter_mut() {
                 if provider.config.uuid == uuid {
                     provider.is_healthy = true;
+                    provider.health.release();
                     tracing::info!(
                         "Marked provider {} ({}) as healthy",
                         provider_type,
                         uuid
                     );
-                    break;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Take the credentials of each key from `pools` (e.g. re-fetched secrets), keeping its health
+    pub async fn update_credentials(&self, pools: &HashMap<String, Vec<ProviderConfig>>) {
+        let mut current = self.pools.write().await;
+        for (provider_type, configs) in pools {
+            let Some(pool) = current.get_mut(provider_type) else { continue };
+            for config in configs {
+                if let Some(provider) = pool.iter_mut().find(|p| p.config.uuid == config.uuid) {
+                    provider.config.credentials = config.credentials.clone();
                 }
             }
         }
     }
 
-    pub async fn perform_health_checks(&self) {
-        // TODO: Implement periodic health checks
-        tracing::info!("Performing health checks on all providers...");
+    /// Look up a single provider with its live health state
+    pub async fn get_provider(&self, provider_type: &str, uuid: &str) -> Option<ProviderConfig> {
+        let pools = self.pools.read().await;
+        pools
+            .get(provider_type)?
+            .iter()
+            .find(|p| p.config.uuid == uuid)
+            .map(ProviderStatus::to_config)
     }
-}
 
+    /// Snapshot of all pools with their live health state
+    pub async fn snapshot(&self) -> HashMap<String, Vec<ProviderConfig>> {
+        let pools = self.pools.read().await;
+        pools
+            .iter()
+            .map(|(provider_type, pool)| {
+                (provider_type.clone(), pool.iter().map(ProviderStatus::to_config).collect())
+            })
+            .collect()
+    }
+
+    /// Record a call made with a pool key. Returns the quarantine period when the call
+    /// puts the key into quarantine; the key is then out of rotation and an alert is sent
+    pub async fn record_outcome(&self, provider_type: &str, uuid: &str, outcome: KeyOutcome) -> Option<Duration> {
+        let mut pools = self.pools.write().await;
+        let provider = pools.get_mut(provider_type)?.iter_mut().find(|p| p.config.uuid == uuid)?;
+        if !matches!(outcome, KeyOutcome::Success | KeyOutcome::Other) {
+            provider.config.error_count += 1;
+            provider.config.last_error_time = Some(chrono::Utc::now().to_rfc3339());
+        }
+        let backoff = provider.health.record(outcome, &self.key_health, Instant::now())?;
+        provider.is_healthy = false;
+        tracing::warn!(
+            "Key {} ({}) quarantined for {}s after {:?}",
+            provider_type,
+            uuid,
+            backoff.as_secs(),
+            outcome
+        );
+        crate::alerts::circuit_opened(
+            &format!("{} ({})", provider_type, uuid),
+            format!("key quarantined for {}s after repeated failures ({:?})", backoff.as_secs(), outcome),
+        );
+        Some(backoff)
+    }
+
+    /// Probe the quarantined keys that are past their backoff; returns the keys whose
+    /// state changed, with whether they are back in rotation
+    pub async fn probe_quarantined(&self, client: &reqwest::Client) -> Vec<(String, String, bool)> {
+        let now = Instant::now();
+        let due: Vec<(String, ProviderConfig)> = {
+            let pools = self.pools.read().await;
+            pools
+                .iter()
+                .flat_map(|(provider_type, pool)| {
+                    pool.iter()
+                        .filter(|p| p.health.due_for_probe(now))
+                        .map(|p| (provider_type.clone(), p.config.clone()))
+                })
+                .collect()
+        };
+
+        let mut changed = Vec::new();
+        for (provider_type, config) in due {
+            // Keys that cannot be probed are let back in and judged by the next requests
+            let ok = crate::key_health::probe(client, &provider_type, &config).await.unwrap_or(true);
+            let mut pools = self.pools.write().await;
+            let Some(provider) = pools.get_mut(&provider_type).and_then(|pool| pool.iter_mut().find(|p| p.config.uuid == config.uuid)) else {
+                continue;
+            };
+            match provider.health.probed(ok, &self.key_health, Instant::now()) {
+                None => {
+                    provider.is_healthy = true;
+                    tracing::info!("Key {} ({}) is back in rotation", provider_type, config.uuid);
+                    changed.push((provider_type, config.uuid, true));
+                }
+                Some(backoff) => {
+                    tracing::warn!("Key {} ({}) still fails, quarantined for {}s", provider_type, config.uuid, backoff.as_secs());
+                }
+            }
+        }
+        changed
+    }
+
+    /// Health score and quarantine state of every pool key, by provider and uuid
+    pub async fn health_snapshot(&self) -> Value {
+        let now = Instant::now();
+        let pools = self.pools.read().await;
+        Value::Object(
+            pools
+                .iter()
+                .map(|(provider_type, pool)| {
+                    let keys = pool
+                        .iter()
+                        .map(|p| (p.config.uuid.clone(), p.health.snapshot(&self.key_health, now)))
+                        .collect();
+                    (provider_type.clone(), Value::Object(keys))
+                })
+                .collect(),
+        )
+    }
+}
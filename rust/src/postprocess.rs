@@ -0,0 +1,268 @@
+/*!
+ * Response Post-Processing
+ *
+ * Config-driven rewrites of assistant text: regex replacements, trailing
+ * whitespace trimming and code fence normalization. Text is processed a line at
+ * a time, so streamed responses are re-chunked on line boundaries and regex
+ * rules only ever see one line.
+ */
+
+use crate::config::PostProcessConfig;
+use anyhow::{Context, Result};
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub struct PostProcessor {
+    replacements: Vec<(Regex, String)>,
+    trim_trailing_whitespace: bool,
+    normalize_code_fences: bool,
+}
+
+/// Per-text progress: the unfinished line plus fence and newline bookkeeping
+#[derive(Debug, Default)]
+pub struct TextState {
+    partial_line: String,
+    in_fence: bool,
+    /// Newlines not yet emitted because they might turn out to be trailing
+    held_newlines: usize,
+    emitted_any: bool,
+    ends_with_newline: bool,
+}
+
+impl PostProcessor {
+    pub fn new(config: &PostProcessConfig) -> Result<Self> {
+        let replacements = config
+            .replacements
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .with_context(|| format!("Invalid post-processing pattern: {}", rule.pattern))
+                    .map(|re| (re, rule.replacement.clone()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            replacements,
+            trim_trailing_whitespace: config.trim_trailing_whitespace,
+            normalize_code_fences: config.normalize_code_fences,
+        })
+    }
+
+    fn process_line(&self, line: &str, state: &mut TextState) -> String {
+        let mut line = line.to_string();
+        for (re, replacement) in &self.replacements {
+            line = re.replace_all(&line, replacement.as_str()).into_owned();
+        }
+
+        if self.normalize_code_fences {
+            let body = line.trim_start();
+            let indent = &line[..line.len() - body.len()];
+            let marker = if body.starts_with("```") {
+                Some('`')
+            } else if body.starts_with("~~~") {
+                Some('~')
+            } else {
+                None
+            };
+            if let Some(marker) = marker {
+                let info = body.trim_start_matches(marker).trim();
+                // A line like ```code``` is inline code, not a fence
+                if !info.contains('`') {
+                    line = format!("{}```{}", indent, info);
+                    state.in_fence = !state.in_fence;
+                }
+            }
+        }
+
+        if self.trim_trailing_whitespace {
+            line.truncate(line.trim_end().len());
+        }
+        line
+    }
+
+    fn emit(&self, line: &str, terminated: bool, state: &mut TextState, out: &mut String) {
+        let start = out.len();
+        if self.trim_trailing_whitespace {
+            if !line.is_empty() {
+                out.extend(std::iter::repeat_n('\n', state.held_newlines));
+                out.push_str(line);
+                state.held_newlines = 0;
+            }
+            if terminated {
+                state.held_newlines += 1;
+            }
+        } else {
+            out.push_str(line);
+            if terminated {
+                out.push('\n');
+            }
+        }
+        if out.len() > start {
+            state.emitted_any = true;
+            state.ends_with_newline = out.ends_with('\n');
+        }
+    }
+
+    /// Process the complete lines in `text`, keeping any unfinished line for later
+    pub fn push(&self, state: &mut TextState, text: &str) -> String {
+        state.partial_line.push_str(text);
+        let Some(last_newline) = state.partial_line.rfind('\n') else {
+            return String::new();
+        };
+
+        let complete: String = state.partial_line.drain(..=last_newline).collect();
+        let mut out = String::new();
+        for line in complete[..complete.len() - 1].split('\n') {
+            let line = self.process_line(line.strip_suffix('\r').unwrap_or(line), state);
+            self.emit(&line, true, state, &mut out);
+        }
+        out
+    }
+
+    /// Process the last line and close a code fence left open
+    pub fn finish(&self, state: &mut TextState) -> String {
+        let mut out = String::new();
+        let rest = std::mem::take(&mut state.partial_line);
+        if !rest.is_empty() {
+            let line = self.process_line(&rest, state);
+            self.emit(&line, false, state, &mut out);
+        }
+
+        if self.normalize_code_fences && state.in_fence {
+            if state.emitted_any && !state.ends_with_newline {
+                out.push('\n');
+            }
+            out.push_str("```");
+            state.in_fence = false;
+        }
+        out
+    }
+
+    pub fn process_text(&self, text: &str) -> String {
+        let mut state = TextState::default();
+        let mut out = self.push(&mut state, text);
+        out.push_str(&self.finish(&mut state));
+        out
+    }
+
+    /// Process a buffered OpenAI or Claude response in place
+    pub fn process_response(&self, response: &mut Value) {
+        if let Some(choices) = response.get_mut("choices").and_then(|c| c.as_array_mut()) {
+            for message in choices.iter_mut().filter_map(|c| c.get_mut("message")?.as_object_mut()) {
+                if let Some(text) = message.get("content").and_then(|c| c.as_str()) {
+                    let processed = self.process_text(text);
+                    message.insert("content".to_string(), json!(processed));
+                }
+            }
+        }
+
+        if let Some(blocks) = response.get_mut("content").and_then(|c| c.as_array_mut()) {
+            for block in blocks.iter_mut().filter_map(|b| b.as_object_mut()) {
+                if block.get("type").and_then(|t| t.as_str()) != Some("text") {
+                    continue;
+                }
+                if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                    let processed = self.process_text(text);
+                    block.insert("text".to_string(), json!(processed));
+                }
+            }
+        }
+    }
+}
+
+/// Stateful processing of stream chunks, one text state per choice / content block
+pub struct StreamProcessor {
+    processor: Arc<PostProcessor>,
+    states: HashMap<u64, TextState>,
+}
+
+impl StreamProcessor {
+    pub fn new(processor: Arc<PostProcessor>) -> Self {
+        Self {
+            processor,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Process one chunk; may return an extra chunk carrying the last line before a block stop
+    pub fn process(&mut self, mut chunk: Value) -> Vec<Value> {
+        if let Some(choices) = chunk.get_mut("choices").and_then(|c| c.as_array_mut()) {
+            for choice in choices.iter_mut() {
+                let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let finished = choice.get("finish_reason").is_some_and(|r| !r.is_null());
+                let Some(delta) = choice.get_mut("delta").and_then(|d| d.as_object_mut()) else {
+                    continue;
+                };
+
+                let text = delta.get("content").and_then(|c| c.as_str());
+                let had_content = text.is_some();
+                let state = self.states.entry(index).or_default();
+                let mut out = self.processor.push(state, text.unwrap_or(""));
+                if finished {
+                    out.push_str(&self.processor.finish(state));
+                    self.states.remove(&index);
+                }
+                if had_content || !out.is_empty() {
+                    delta.insert("content".to_string(), json!(out));
+                }
+            }
+            return vec![chunk];
+        }
+
+        let index = chunk.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+        match chunk.get("type").and_then(|t| t.as_str()) {
+            Some("content_block_delta") => {
+                let delta = chunk.get_mut("delta").and_then(|d| d.as_object_mut());
+                if let Some(delta) = delta.filter(|d| d.get("type").and_then(|t| t.as_str()) == Some("text_delta")) {
+                    let text = delta.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                    let out = self.processor.push(self.states.entry(index).or_default(), text);
+                    delta.insert("text".to_string(), json!(out));
+                }
+                vec![chunk]
+            }
+            Some("content_block_stop") => {
+                let out = self
+                    .states
+                    .remove(&index)
+                    .map(|mut state| self.processor.finish(&mut state))
+                    .unwrap_or_default();
+                if out.is_empty() {
+                    return vec![chunk];
+                }
+                let flushed = json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": {"type": "text_delta", "text": out},
+                });
+                vec![flushed, chunk]
+            }
+            _ => vec![chunk],
+        }
+    }
+}
+
+/// Wrap a provider stream with post-processing
+pub fn process_stream(
+    inner: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+    processor: Arc<PostProcessor>,
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    Box::pin(stream! {
+        let mut inner = inner;
+        let mut processor = StreamProcessor::new(processor);
+        while let Some(item) = inner.next().await {
+            match item {
+                Ok(chunk) => {
+                    for chunk in processor.process(chunk) {
+                        yield Ok(chunk);
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+    })
+}
@@ -0,0 +1,251 @@
+/*!
+ * Prompt Compression
+ *
+ * Shrinks OpenAI- and Claude-format prompts before dispatch. Trailing
+ * whitespace and runs of blank lines are collapsed, operator-listed
+ * boilerplate is removed, and a paragraph repeated from earlier in the prompt
+ * (a document pasted twice, a context block resent every turn) is replaced by
+ * a short marker. Optionally a model then condenses long older messages; the
+ * latest messages are always sent as written. The prompt's token counts
+ * before and after are reported in the response usage.
+ */
+
+use crate::config::{LlmCompressionConfig, PromptCompressionConfig};
+use crate::stream_recovery::ValueStream;
+use crate::tokenizer::ModelTokenizer;
+use anyhow::{Context, Result};
+use futures::{Future, StreamExt};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use tracing::{info, warn};
+
+/// Stands in for a paragraph that already appeared earlier in the prompt
+pub const REPEATED_MARKER: &str = "[repeated context omitted]";
+
+const LLM_PROMPT: &str = "Compress the text you are given. Keep every fact, name, number, \
+instruction and code block, and drop filler, pleasantries and repetition. Reply with the compressed text only.";
+
+/// Prompt size before and after compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionStats {
+    pub original_tokens: usize,
+    pub compressed_tokens: usize,
+}
+
+impl CompressionStats {
+    /// Record the counts under `usage.prompt_compression` of a response or stream chunk
+    /// (Claude's `message_start` carries its usage in `message`); chunks without usage are left alone
+    pub fn apply(&self, response: &mut Value) {
+        let pointer = if response.get("usage").is_some_and(Value::is_object) {
+            "/usage"
+        } else if response.pointer("/message/usage").is_some_and(Value::is_object) {
+            "/message/usage"
+        } else {
+            return;
+        };
+        if let Some(usage) = response.pointer_mut(pointer) {
+            usage["prompt_compression"] = json!({
+                "original_tokens": self.original_tokens,
+                "compressed_tokens": self.compressed_tokens,
+            });
+        }
+    }
+}
+
+/// Apply `stats` to every chunk of a stream that carries usage
+pub fn annotate_stream(stream: ValueStream, stats: CompressionStats) -> ValueStream {
+    Box::pin(stream.map(move |chunk| {
+        chunk.map(|mut chunk| {
+            stats.apply(&mut chunk);
+            chunk
+        })
+    }))
+}
+
+pub struct PromptCompressor {
+    config: PromptCompressionConfig,
+    boilerplate: Vec<Regex>,
+}
+
+impl PromptCompressor {
+    pub fn new(config: &PromptCompressionConfig) -> Result<Self> {
+        let boilerplate = config
+            .boilerplate_patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid boilerplate pattern: {}", pattern)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            config: config.clone(),
+            boilerplate,
+        })
+    }
+
+    /// Compress the prompt of a request for `model` in place. `call(model, body)` sends an
+    /// OpenAI-format request for the model-based pass and returns the OpenAI-format response
+    pub async fn compress<F, Fut>(&self, model: &str, body: &mut Value, call: F) -> CompressionStats
+    where
+        F: Fn(String, Value) -> Fut,
+        Fut: Future<Output = Result<Value>>,
+    {
+        let tokenizer = ModelTokenizer::for_model(model);
+        let original_tokens = tokenizer.count_prompt(body);
+
+        self.compress_text(body);
+        if let Some(llm) = &self.config.llm {
+            condense(llm, &tokenizer, body, call).await;
+        }
+
+        let stats = CompressionStats {
+            original_tokens,
+            compressed_tokens: tokenizer.count_prompt(body),
+        };
+        if stats.compressed_tokens < stats.original_tokens {
+            info!("Compressed prompt for {}: {} -> {} tokens", model, stats.original_tokens, stats.compressed_tokens);
+        }
+        stats
+    }
+
+    /// The rule-based steps: whitespace, boilerplate and repeated paragraphs
+    pub fn compress_text(&self, body: &mut Value) {
+        let mut seen = HashSet::new();
+        for_each_text(body, |text| {
+            for re in &self.boilerplate {
+                if re.is_match(text) {
+                    *text = re.replace_all(text, "").into_owned();
+                }
+            }
+            if self.config.collapse_whitespace {
+                *text = collapse_whitespace(text);
+            }
+            if self.config.deduplicate {
+                *text = deduplicate(text, &mut seen, self.config.min_block_chars);
+            }
+        });
+    }
+}
+
+/// Visit the text of the system prompt and of every message, in order
+fn for_each_text(body: &mut Value, mut f: impl FnMut(&mut String)) {
+    if let Some(system) = body.get_mut("system") {
+        visit_content(system, &mut f);
+    }
+    for message in body.get_mut("messages").and_then(|m| m.as_array_mut()).into_iter().flatten() {
+        if let Some(content) = message.get_mut("content") {
+            visit_content(content, &mut f);
+        }
+    }
+}
+
+/// A string, or a list of OpenAI text parts / Claude text blocks
+fn visit_content(content: &mut Value, f: &mut impl FnMut(&mut String)) {
+    match content {
+        Value::String(text) => f(text),
+        Value::Array(parts) => {
+            for part in parts {
+                let is_text = part.get("type").and_then(|t| t.as_str()).unwrap_or("text") == "text";
+                if let (true, Some(Value::String(text))) = (is_text, part.get_mut("text")) {
+                    f(text);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Strip trailing whitespace from every line and keep at most one blank line in a row
+pub fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank_run = 0;
+    for line in text.trim_end().lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 1 || out.is_empty() {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Replace paragraphs of at least `min_chars` already in `seen` with the marker; new ones are added to it
+pub fn deduplicate(text: &str, seen: &mut HashSet<String>, min_chars: usize) -> String {
+    let mut blocks: Vec<&str> = Vec::new();
+    let mut changed = false;
+    for block in text.split("\n\n") {
+        let key = block.trim();
+        if key.chars().count() < min_chars || seen.insert(key.to_string()) {
+            blocks.push(block);
+            continue;
+        }
+        changed = true;
+        // Consecutive repeated paragraphs share one marker
+        if blocks.last() != Some(&REPEATED_MARKER) {
+            blocks.push(REPEATED_MARKER);
+        }
+    }
+    if changed {
+        blocks.join("\n\n")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Have the model rewrite long string messages outside the most recent ones, in parallel;
+/// a failed or longer rewrite leaves the message as it was
+async fn condense<F, Fut>(llm: &LlmCompressionConfig, tokenizer: &ModelTokenizer, body: &mut Value, call: F)
+where
+    F: Fn(String, Value) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return;
+    };
+    let older = messages.len().saturating_sub(llm.keep_recent);
+    let long: Vec<(usize, String)> = messages[..older]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, message)| {
+            let text = message.get("content")?.as_str()?;
+            (tokenizer.count_text(text) >= llm.min_tokens).then(|| (i, text.to_string()))
+        })
+        .collect();
+    if long.is_empty() {
+        return;
+    }
+
+    let rewrites = long.iter().map(|(_, text)| {
+        call(
+            llm.model.clone(),
+            json!({
+                "model": llm.model,
+                "messages": [
+                    {"role": "system", "content": LLM_PROMPT},
+                    {"role": "user", "content": text}
+                ],
+                "temperature": 0
+            }),
+        )
+    });
+    let results = futures::future::join_all(rewrites).await;
+
+    for ((i, text), result) in long.into_iter().zip(results) {
+        let rewritten = match result {
+            Ok(response) => crate::logger::extract_text_from_response(&response, "openai"),
+            Err(e) => {
+                warn!("Prompt compression with {} failed: {}", llm.model, e);
+                continue;
+            }
+        };
+        if !rewritten.trim().is_empty() && tokenizer.count_text(&rewritten) < tokenizer.count_text(&text) {
+            messages[i]["content"] = json!(rewritten.trim());
+        }
+    }
+}
@@ -0,0 +1,196 @@
+/*!
+ * Provider registry
+ *
+ * The providers requests can be sent to, the routers and routing table
+ * choosing one per request, and model aliases. They live behind a lock as one immutable snapshot
+ * that is swapped whole, so the admin API, config reloads and credential
+ * refreshes can change them while requests in flight keep the snapshot they
+ * started with.
+ */
+
+use crate::adapter::{create_adapter, ApiServiceAdapter};
+use crate::common::ModelProvider;
+use crate::config::{Config, ModelAlias, ModelRoute};
+use crate::router::{self, RouteRequest, Router};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// The provider a request is sent to
+#[derive(Clone)]
+pub struct Upstream {
+    pub provider: String,
+    pub adapter: Arc<dyn ApiServiceAdapter>,
+}
+
+/// One consistent view of providers, routes and aliases
+#[derive(Clone)]
+pub struct Routing {
+    pub default_provider: String,
+    pub adapters: HashMap<String, Arc<dyn ApiServiceAdapter>>,
+    /// Built from `routers`; consulted in order before `routes`
+    pub routers: Vec<Arc<dyn Router>>,
+    /// Checked in order; the first rule matching the model wins
+    pub routes: Vec<ModelRoute>,
+    /// Alias → the model(s) it stands for
+    pub aliases: HashMap<String, ModelAlias>,
+}
+
+impl Routing {
+    /// Build adapters for the default provider and every provider the routes name,
+    /// plus those of `default_model_providers` that can be created, for `/:provider/v1/...` paths
+    pub async fn build(config: &Config) -> Result<Self> {
+        let mut extra: HashMap<String, Arc<dyn ApiServiceAdapter>> = HashMap::new();
+        for name in &config.default_model_providers {
+            let Some(provider) = ModelProvider::from_str(name) else {
+                continue;
+            };
+            match create_adapter(provider, config).await {
+                Ok(adapter) => {
+                    extra.insert(name.clone(), Arc::from(adapter));
+                }
+                Err(e) if name == &config.model_provider => return Err(e),
+                Err(e) => warn!("Provider {} is unavailable: {}", name, e),
+            }
+        }
+
+        Self::with_adapters(
+            config,
+            config.model_provider.clone(),
+            config.model_routes.clone(),
+            config.model_aliases.clone(),
+            &extra,
+        )
+        .await
+    }
+
+    /// A routing with the given table, keeping the adapters in `existing` and
+    /// creating those the table needs but `existing` lacks from `config`
+    pub async fn with_adapters(
+        config: &Config,
+        default_provider: String,
+        routes: Vec<ModelRoute>,
+        aliases: HashMap<String, ModelAlias>,
+        existing: &HashMap<String, Arc<dyn ApiServiceAdapter>>,
+    ) -> Result<Self> {
+        let routers: Vec<Arc<dyn Router>> = config.routers.iter().map(router::build).collect();
+        let routed: Vec<String> = routers.iter().flat_map(|router| router.providers()).collect();
+        let pinned: Vec<String> = aliases.values().flat_map(|alias| alias.targets()).filter_map(|target| target.provider).collect();
+        let needed: BTreeSet<&str> = std::iter::once(default_provider.as_str())
+            .chain(routes.iter().map(|route| route.provider.as_str()))
+            .chain(routed.iter().map(String::as_str))
+            .chain(pinned.iter().map(String::as_str))
+            .collect();
+
+        let mut adapters = existing.clone();
+        for name in needed {
+            if !adapters.contains_key(name) {
+                let provider = ModelProvider::from_str(name).ok_or_else(|| anyhow!("Invalid model provider: {}", name))?;
+                adapters.insert(name.to_string(), Arc::from(create_adapter(provider, config).await?));
+            }
+        }
+
+        Ok(Self { default_provider, adapters, routers, routes, aliases })
+    }
+
+    /// The same table with every adapter created afresh from `config` (e.g. new credentials)
+    pub async fn rebuild(&self, config: &Config) -> Result<Self> {
+        let mut adapters = HashMap::new();
+        for name in self.adapters.keys() {
+            let provider = ModelProvider::from_str(name).ok_or_else(|| anyhow!("Invalid model provider: {}", name))?;
+            adapters.insert(name.clone(), Arc::from(create_adapter(provider, config).await?));
+        }
+        Self::with_adapters(config, self.default_provider.clone(), self.routes.clone(), self.aliases.clone(), &adapters).await
+    }
+
+    /// The model an alias stands for (its first target); other names are returned unchanged
+    pub fn resolve_alias(&self, model: &str) -> String {
+        match self.aliases.get(model).map(ModelAlias::targets) {
+            Some(targets) if !targets.is_empty() => targets[0].model.clone(),
+            _ => model.to_string(),
+        }
+    }
+
+    pub fn default_upstream(&self) -> Upstream {
+        Upstream {
+            provider: self.default_provider.clone(),
+            adapter: self.adapters[&self.default_provider].clone(),
+        }
+    }
+
+    /// The provider for `model` when there is no request body to route by
+    pub fn upstream_for(&self, model: &str, requested: Option<&str>) -> Result<Upstream> {
+        self.upstream_for_request(&RouteRequest::new(model, None), requested)
+    }
+
+    /// The provider for a request: the one named in the request path if given, else the
+    /// first router's pick, else the first matching route, else the default
+    pub fn upstream_for_request(&self, request: &RouteRequest, requested: Option<&str>) -> Result<Upstream> {
+        let provider = match requested {
+            Some(provider) => provider.to_string(),
+            None => self
+                .routers
+                .iter()
+                .find_map(|router| router.select(request))
+                .or_else(|| route_for(&self.routes, request.model).map(|route| route.provider.clone()))
+                .unwrap_or_else(|| self.default_provider.clone()),
+        };
+        let provider = provider.as_str();
+        let adapter = self
+            .adapters
+            .get(provider)
+            .ok_or_else(|| anyhow!("Provider {} is not configured", provider))?;
+        Ok(Upstream { provider: provider.to_string(), adapter: adapter.clone() })
+    }
+
+    pub fn providers(&self) -> Vec<String> {
+        let mut providers: Vec<String> = self.adapters.keys().cloned().collect();
+        providers.sort();
+        providers
+    }
+
+    /// The table as shown by the admin API and recorded in the audit log
+    pub fn summary(&self) -> Value {
+        json!({
+            "default_provider": self.default_provider,
+            "providers": self.providers(),
+            "routes": self.routes,
+            "aliases": self.aliases,
+        })
+    }
+
+    /// Re-read the configuration the way it was loaded at startup and build its routing
+    pub async fn reload() -> Result<Self> {
+        let mut config = Config::load()?;
+        crate::secret_refs::resolve_config(&mut config, &reqwest::Client::new()).await?;
+        Self::build(&config).await
+    }
+}
+
+/// First route whose `model` matches: exactly, or as a prefix when it ends in `*`
+pub fn route_for<'a>(routes: &'a [ModelRoute], model: &str) -> Option<&'a ModelRoute> {
+    routes.iter().find(|route| router::matches(&route.model, model))
+}
+
+pub struct ProviderRegistry {
+    routing: RwLock<Arc<Routing>>,
+}
+
+impl ProviderRegistry {
+    pub fn new(routing: Routing) -> Self {
+        Self { routing: RwLock::new(Arc::new(routing)) }
+    }
+
+    /// The routing in effect; hold on to it for the whole request
+    pub async fn snapshot(&self) -> Arc<Routing> {
+        self.routing.read().await.clone()
+    }
+
+    /// Swap in a new routing, returning the one it replaces
+    pub async fn replace(&self, routing: Routing) -> Arc<Routing> {
+        std::mem::replace(&mut *self.routing.write().await, Arc::new(routing))
+    }
+}
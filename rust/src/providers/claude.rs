@@ -4,14 +4,19 @@
 
 use crate::adapter::ApiServiceAdapter;
 use crate::common::*;
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::convert_detailed::{claude_uses_code_execution, CLAUDE_CODE_EXECUTION_BETA};
+use crate::regions::Regions;
+use crate::request_context::{merge_beta_flags, RequestContext, ANTHROPIC_BETA};
+use crate::upstream_error::UpstreamError;
 use anyhow::Result;
 use async_stream::stream;
 use async_trait::async_trait;
 use futures::future::BoxFuture;
-use futures::Stream;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder};
 use serde_json::json;
-use std::pin::Pin;
+use std::sync::Arc;
 use tokio_stream::StreamExt;
 use tracing::{debug, warn};
 
@@ -29,107 +34,153 @@ const CLAUDE_MODELS: &[&str] = &[
 pub struct ClaudeApiService {
     client: Client,
     api_key: String,
-    base_url: String,
+    regions: Arc<Regions>,
+    /// `anthropic-beta` flags sent on every request
+    beta_flags: Vec<String>,
     max_retries: u32,
     base_delay: u64,
 }
 
 impl ClaudeApiService {
-    pub fn new(api_key: String, base_url: Option<String>, max_retries: u32, base_delay: u64) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(60))  // 减少到60秒
-            .connect_timeout(std::time::Duration::from_secs(10))
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .pool_max_idle_per_host(10)
-            .tcp_nodelay(true)
-            .build()?;
-
-        let base_url = base_url.unwrap_or_else(|| "https://api.anthropic.com".to_string());
-
+    pub fn new(
+        api_key: String,
+        regions: Arc<Regions>,
+        beta_flags: Vec<String>,
+        max_retries: u32,
+        base_delay: u64,
+        client: Client,
+    ) -> Result<Self> {
         Ok(Self {
             client,
             api_key,
-            base_url,
+            regions,
+            beta_flags,
             max_retries,
             base_delay,
         })
     }
 
+    /// Build an upstream request with forwarded headers and the merged beta flags
+    fn messages_request(&self, url: &str, body: &serde_json::Value, ctx: &RequestContext) -> RequestBuilder {
+        let request = self.client
+            .post(url)
+            .header("x-api-key", ctx.api_key().unwrap_or(&self.api_key))
+            .header("Content-Type", "application/json")
+            .header("anthropic-version", "2023-06-01")
+            .json(body);
+        let request = ctx.apply(request);
+
+        let mut requested = ctx.anthropic_beta.clone();
+        if let Some(forwarded) = ctx.header(ANTHROPIC_BETA) {
+            requested.extend(forwarded.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()));
+        }
+        if claude_uses_code_execution(body) {
+            requested.push(CLAUDE_CODE_EXECUTION_BETA.to_string());
+        }
+        requested.extend(crate::builtin_tools::beta_flags(body));
+        match merge_beta_flags(&self.beta_flags, &requested).and_then(|v| HeaderValue::from_str(&v).ok()) {
+            // Replace rather than append so a forwarded header is not sent twice
+            Some(value) => request.headers(HeaderMap::from_iter([(HeaderName::from_static(ANTHROPIC_BETA), value)])),
+            None => request,
+        }
+    }
+
     fn call_api_with_retry<'a>(
         &'a self,
         endpoint: &'a str,
         body: serde_json::Value,
+        ctx: &'a RequestContext,
         retry_count: u32,
     ) -> BoxFuture<'a, Result<serde_json::Value>> {
         Box::pin(async move {
-        let url = format!("{}{}", self.base_url, endpoint);
-
-        let response = self.client
-            .post(&url)
-            .header("x-api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .json(&body)
-            .send()
+        if retry_count == 0 {
+            crate::retry_budget::global().record_request();
+        }
+        let response = self
+            .regions
+            .send(|base_url| self.messages_request(&format!("{}{}", base_url, endpoint), &body, ctx).send())
             .await?;
+        crate::rate_limit::record_upstream(response.headers());
+        crate::request_id::record_upstream(response.headers());
 
         let status = response.status();
 
         if status.is_success() {
-            let result: serde_json::Value = response.json().await?;
+            let result = crate::json::from_vec(response.bytes().await?.into())?;
             return Ok(result);
         }
 
         // Handle retryable errors
-        if (status.as_u16() == 429 || status.is_server_error()) && retry_count < self.max_retries {
+        let retryable = status.as_u16() == 429 || status.is_server_error();
+        if retryable && retry_count < self.max_retries && crate::retry_budget::global().try_retry() {
             let delay = self.base_delay * 2_u64.pow(retry_count);
             warn!("Request failed with status {}, retrying in {}ms...", status, delay);
             tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-            return self.call_api_with_retry(endpoint, body, retry_count + 1).await;
+            return self.call_api_with_retry(endpoint, body, ctx, retry_count + 1).await;
         }
 
         let error_text = response.text().await?;
-        anyhow::bail!("API call failed ({}): {}", status, error_text)
+        Err(UpstreamError::new(status, error_text).into())
         })
     }
 }
 
+/// Send the end user as `metadata.user_id`; Anthropic has no top-level `user` field
+fn set_end_user(body: &mut serde_json::Value, ctx: &RequestContext) {
+    if let (Some(user), Some(obj)) = (ctx.end_user.as_ref(), body.as_object_mut()) {
+        obj.remove("user");
+        let metadata = obj.entry("metadata").or_insert_with(|| json!({}));
+        if let Some(metadata) = metadata.as_object_mut() {
+            metadata.insert("user_id".to_string(), json!(user));
+        }
+    }
+}
+
 #[async_trait]
 impl ApiServiceAdapter for ClaudeApiService {
+    fn protocol(&self) -> ModelProtocol {
+        ModelProtocol::Claude
+    }
+
     async fn generate_content(
         &self,
-        _model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<serde_json::Value> {
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatResponse> {
         debug!("Claude generate_content");
-        self.call_api_with_retry("/v1/messages", request_body, 0).await
+        let mut request_body = request.into_protocol(ModelProtocol::Claude, Some(model))?;
+        set_end_user(&mut request_body, ctx);
+        let response = self.call_api_with_retry("/v1/messages", request_body, ctx, 0).await?;
+        Ok(ChatResponse::Claude(response))
     }
 
     async fn generate_content_stream(
         &self,
-        _model: &str,
-        mut request_body: serde_json::Value,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>> {
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatStream> {
         debug!("Claude generate_content_stream");
+        let mut request_body = request.into_protocol(ModelProtocol::Claude, Some(model))?;
 
         // Ensure stream flag is set
         if let Some(obj) = request_body.as_object_mut() {
             obj.insert("stream".to_string(), json!(true));
         }
+        set_end_user(&mut request_body, ctx);
 
-        let url = format!("{}/v1/messages", self.base_url);
-        let response = self.client
-            .post(&url)
-            .header("x-api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .json(&request_body)
-            .send()
+        let response = self
+            .regions
+            .send(|base_url| self.messages_request(&format!("{}/v1/messages", base_url), &request_body, ctx).send())
             .await?;
+        crate::rate_limit::record_upstream(response.headers());
+        crate::request_id::record_upstream(response.headers());
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
             let error_text = response.text().await?;
-            anyhow::bail!("Stream API call failed: {}", error_text);
+            return Err(UpstreamError::stream(status, error_text).into());
         }
 
         let byte_stream = response.bytes_stream();
@@ -149,8 +200,8 @@ impl ApiServiceAdapter for ClaudeApiService {
                             
                             let mut data = String::new();
                             for line in event_block.lines() {
-                                if line.starts_with("data: ") {
-                                    data = line[6..].to_string();
+                                if let Some(rest) = line.strip_prefix("data: ") {
+                                    data = rest.to_string();
                                     break;
                                 }
                             }
@@ -166,14 +217,14 @@ impl ApiServiceAdapter for ClaudeApiService {
                         }
                     }
                     Err(e) => {
-                        yield Err(anyhow::anyhow!("Stream error: {}", e));
+                        yield Err(anyhow::Error::new(e).context("Stream error"));
                         return;
                     }
                 }
             }
         };
 
-        Ok(Box::pin(stream))
+        Ok(ChatStream::Claude(Box::pin(stream)))
     }
 
     async fn list_models(&self) -> Result<ModelListResponse> {
@@ -186,7 +237,7 @@ impl ApiServiceAdapter for ClaudeApiService {
                 id: Some(model_id.to_string()),
                 name: Some(model_id.to_string()),
                 object: Some("model".to_string()),
-                created: Some(chrono::Utc::now().timestamp()),
+                created: None,
                 owned_by: Some("anthropic".to_string()),
                 extra: Default::default(),
             })
@@ -199,6 +250,10 @@ impl ApiServiceAdapter for ClaudeApiService {
         })
     }
 
+    fn upstream_url(&self) -> Option<String> {
+        Some(self.regions.primary().to_string())
+    }
+
     async fn refresh_token(&self) -> Result<()> {
         // Claude uses static API keys, no refresh needed
         Ok(())
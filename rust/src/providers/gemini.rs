@@ -4,18 +4,19 @@
 
 use crate::adapter::ApiServiceAdapter;
 use crate::common::*;
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::request_context::RequestContext;
+use crate::upstream_error::UpstreamError;
 use anyhow::{Context, Result};
 use async_stream::stream;
 use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine as _};
 use chrono::Utc;
 use futures::future::BoxFuture;
-use futures::Stream;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::path::PathBuf;
-use std::pin::Pin;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::sync::RwLock;
@@ -53,14 +54,8 @@ impl GeminiApiService {
         project_id: Option<String>,
         max_retries: u32,
         base_delay: u64,
+        client: Client,
     ) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(60))  // 减少到60秒
-            .connect_timeout(std::time::Duration::from_secs(10))
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .pool_max_idle_per_host(10)
-            .tcp_nodelay(true)
-            .build()?;
 
         // Determine credentials path
         let credentials_path = oauth_creds_file.unwrap_or_else(|| {
@@ -77,7 +72,7 @@ impl GeminiApiService {
             Self::load_credentials_from_file(&credentials_path).await?
         };
 
-        let mut service = Self {
+        let service = Self {
             client,
             credentials: Arc::new(RwLock::new(credentials)),
             credentials_path,
@@ -232,16 +227,20 @@ impl GeminiApiService {
     }
 
     async fn call_api(&self, method: &str, body: serde_json::Value) -> Result<serde_json::Value> {
-        self.call_api_with_retry(method, body, 0).await
+        self.call_api_with_retry(method, body, &RequestContext::default(), 0).await
     }
 
     fn call_api_with_retry<'a>(
         &'a self,
         method: &'a str,
         body: serde_json::Value,
+        ctx: &'a RequestContext,
         retry_count: u32,
     ) -> BoxFuture<'a, Result<serde_json::Value>> {
         Box::pin(async move {
+        if retry_count == 0 {
+            crate::retry_budget::global().record_request();
+        }
         // Check and refresh token if needed
         {
             let creds = self.credentials.read().await;
@@ -261,72 +260,86 @@ impl GeminiApiService {
         );
 
         let creds = self.credentials.read().await;
-        let response = self.client
+        let request = self.client
             .post(&url)
             .header("Authorization", format!("Bearer {}", creds.access_token))
             .header("Content-Type", "application/json")
-            .json(&body)
+            .json(&body);
+        let response = ctx
+            .apply(request)
             .send()
             .await?;
+        crate::request_id::record_upstream(response.headers());
 
         let status = response.status();
         
         if status.is_success() {
-            let result: serde_json::Value = response.json().await?;
+            let result = crate::json::from_vec(response.bytes().await?.into())?;
             return Ok(result);
         }
 
         // Handle retryable errors
-        if (status.as_u16() == 429 || status.is_server_error()) && retry_count < self.max_retries {
+        let retryable = status.as_u16() == 429 || status.is_server_error();
+        if retryable && retry_count < self.max_retries && crate::retry_budget::global().try_retry() {
             let delay = self.base_delay * 2_u64.pow(retry_count);
             warn!("Request failed with status {}, retrying in {}ms...", status, delay);
             tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-            return self.call_api_with_retry(method, body, retry_count + 1).await;
+            return self.call_api_with_retry(method, body, ctx, retry_count + 1).await;
         }
 
         let error_text = response.text().await?;
-        anyhow::bail!("API call failed ({}): {}", status, error_text)
+        Err(UpstreamError::new(status, error_text).into())
         })
     }
+
+    /// Call generateContent and keep only the Gemini-compliant fields
+    async fn generate(&self, request_body: serde_json::Value, ctx: &RequestContext) -> Result<serde_json::Value> {
+        let response = self.call_api_with_retry("generateContent", request_body, ctx, 0).await?;
+
+        Ok(json!({
+            "candidates": response.get("candidates"),
+            "usageMetadata": response.get("usageMetadata"),
+            "promptFeedback": response.get("promptFeedback"),
+        }))
+    }
 }
 
 #[async_trait]
 impl ApiServiceAdapter for GeminiApiService {
+    fn protocol(&self) -> ModelProtocol {
+        ModelProtocol::Gemini
+    }
+
     async fn generate_content(
         &self,
         model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<serde_json::Value> {
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatResponse> {
         debug!("Generating content with model: {}", model);
-        
-        let response = self.call_api("generateContent", request_body).await?;
-        
-        // Transform to Gemini-compliant format
-        let compliant = json!({
-            "candidates": response.get("candidates"),
-            "usageMetadata": response.get("usageMetadata"),
-            "promptFeedback": response.get("promptFeedback"),
-        });
-        
-        Ok(compliant)
+        let request_body = request.into_protocol(ModelProtocol::Gemini, Some(model))?;
+        let response = self.generate(request_body, ctx).await?;
+        Ok(ChatResponse::Gemini(response))
     }
 
     async fn generate_content_stream(
         &self,
         model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>> {
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatStream> {
         debug!("Generating streaming content with model: {}", model);
-        
+        let request_body = request.into_protocol(ModelProtocol::Gemini, Some(model))?;
+
         // For now, implement as non-streaming and convert
         // TODO: Implement true streaming
-        let response = self.generate_content(model, request_body).await?;
+        let response = self.generate(request_body, ctx).await?;
         
         let stream = stream! {
             yield Ok(response);
         };
         
-        Ok(Box::pin(stream))
+        Ok(ChatStream::Gemini(Box::pin(stream)))
     }
 
     async fn list_models(&self) -> Result<ModelListResponse> {
@@ -338,7 +351,7 @@ impl ApiServiceAdapter for GeminiApiService {
                 id: Some(format!("models/{}", model_id)),
                 name: Some(model_id.clone()),
                 object: Some("model".to_string()),
-                created: Some(Utc::now().timestamp()),
+                created: None,
                 owned_by: Some("google".to_string()),
                 extra: Default::default(),
             })
@@ -351,6 +364,10 @@ impl ApiServiceAdapter for GeminiApiService {
         })
     }
 
+    fn upstream_url(&self) -> Option<String> {
+        Some(CODE_ASSIST_ENDPOINT.to_string())
+    }
+
     async fn refresh_token(&self) -> Result<()> {
         let creds = self.credentials.read().await;
         if self.is_token_expired(&creds) {
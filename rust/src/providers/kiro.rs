@@ -7,17 +7,18 @@
 
 use crate::adapter::ApiServiceAdapter;
 use crate::common::*;
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::request_context::RequestContext;
+use crate::upstream_error::UpstreamError;
 use anyhow::{Context, Result};
 use async_stream::stream;
 use async_trait::async_trait;
 use base64::Engine;
 use futures::future::BoxFuture;
-use futures::Stream;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::path::PathBuf;
-use std::pin::Pin;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::sync::RwLock;
@@ -40,6 +41,8 @@ const CLAUDE_MODELS: &[&str] = &[
 
 const CHAT_TRIGGER_TYPE_MANUAL: &str = "MANUAL";
 const ORIGIN_AI_EDITOR: &str = "AI_EDITOR";
+/// CodeWhisperer answers in one piece, so it gets a tighter limit than the shared client
+const KIRO_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
 // Model mapping from Claude to CodeWhisperer format
 fn map_model_to_codewhisperer(model: &str) -> &'static str {
@@ -88,14 +91,8 @@ impl KiroApiService {
         oauth_creds_file: Option<PathBuf>,
         max_retries: u32,
         base_delay: u64,
+        client: Client,
     ) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))  // 减少到30秒
-            .connect_timeout(std::time::Duration::from_secs(5))  // 连接超时5秒
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .pool_max_idle_per_host(10)
-            .tcp_nodelay(true)  // 禁用 Nagle 算法，减少延迟
-            .build()?;
 
         let credentials_path = oauth_creds_file.unwrap_or_else(|| {
             dirs::home_dir()
@@ -233,8 +230,7 @@ impl KiroApiService {
         }
 
         // Process all but the last message into history
-        for i in start_index..messages.len().saturating_sub(1) {
-            let message = &messages[i];
+        for message in messages.iter().take(messages.len().saturating_sub(1)).skip(start_index) {
             let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("");
 
             match role {
@@ -263,7 +259,7 @@ impl KiroApiService {
                 }
                 "assistant" => {
                     let (content, tool_uses, tool_results) = self.extract_assistant_message_details(message);
-                    let mut assistant_msg = json!({
+                    let assistant_msg = json!({
                         "content": content,
                         "toolUses": if tool_uses.is_empty() { serde_json::Value::Null } else { json!(tool_uses) },
                         "toolResults": if tool_results.is_empty() { serde_json::Value::Null } else { json!(tool_results) }
@@ -840,8 +836,7 @@ impl KiroApiService {
         let mut in_string = false;
         let mut escape_next = false;
         
-        for i in (start_pos + 1)..bytes.len() {
-            let ch = bytes[i];
+        for (i, &ch) in bytes.iter().enumerate().skip(start_pos + 1) {
             
             if escape_next {
                 escape_next = false;
@@ -898,7 +893,7 @@ impl KiroApiService {
         // Add quotes to unquoted keys
         repaired_json = regex::Regex::new(r#"([{,]\s*)([a-zA-Z0-9_]+?)\s*:"#).ok()?.replace_all(&repaired_json, r#"$1"$2":"#).to_string();
         // Quote unquoted values
-        repaired_json = regex::Regex::new(r":\s*([a-zA-Z0-9_]+)(?=[,\}\]])").ok()?.replace_all(&repaired_json, r#":"$1""#).to_string();
+        repaired_json = regex::Regex::new(r":\s*([a-zA-Z0-9_]+)(\s*[,\}\]])").ok()?.replace_all(&repaired_json, r#":"$1"$2"#).to_string();
         
         match serde_json::from_str::<serde_json::Value>(&repaired_json) {
             Ok(arguments_obj) => {
@@ -906,7 +901,7 @@ impl KiroApiService {
                     return None;
                 }
                 
-                let tool_call_id = format!("call_{}", Uuid::new_v4().to_string().replace("-", "")[..8].to_string());
+                let tool_call_id = format!("call_{}", &Uuid::new_v4().simple().to_string()[..8]);
                 Some(json!({
                     "id": tool_call_id,
                     "type": "tool_use",
@@ -925,10 +920,14 @@ impl KiroApiService {
         &'a self,
         endpoint: &'a str,
         body: serde_json::Value,
+        ctx: &'a RequestContext,
         retry_count: u32,
     ) -> BoxFuture<'a, Result<serde_json::Value>> {
         Box::pin(async move {
-        self.call_api_with_retry_and_refresh(endpoint, body, retry_count, false).await
+        if retry_count == 0 {
+            crate::retry_budget::global().record_request();
+        }
+        self.call_api_with_retry_and_refresh(endpoint, body, ctx, retry_count, false).await
         })
     }
     
@@ -936,6 +935,7 @@ impl KiroApiService {
         &'a self,
         endpoint: &'a str,
         body: serde_json::Value,
+        ctx: &'a RequestContext,
         retry_count: u32,
         is_retry_after_refresh: bool,
     ) -> BoxFuture<'a, Result<serde_json::Value>> {
@@ -967,14 +967,18 @@ impl KiroApiService {
         debug!("CodeWhisperer request: {}", serde_json::to_string_pretty(&codewhisperer_request)?);
 
         let api_call_start = std::time::Instant::now();
-        let response = self.client
+        let request = self.client
             .post(&url)
             .header("Authorization", format!("Bearer {}", creds.access_token))
             .header("Content-Type", "application/json")
             .header("amz-sdk-invocation-id", Uuid::new_v4().to_string())
-            .json(&codewhisperer_request)
+            .timeout(KIRO_REQUEST_TIMEOUT)
+            .json(&codewhisperer_request);
+        let response = ctx
+            .apply(request)
             .send()
             .await?;
+        crate::request_id::record_upstream(response.headers());
         
         let api_call_duration = api_call_start.elapsed();
         info!("API call took: {:?}", api_call_duration);
@@ -1081,17 +1085,18 @@ impl KiroApiService {
             match self.refresh_access_token().await {
                 Ok(_) => {
                     info!("Token refreshed successfully, retrying request...");
-                    return self.call_api_with_retry_and_refresh(endpoint, body, retry_count, true).await;
+                    return self.call_api_with_retry_and_refresh(endpoint, body, ctx, retry_count, true).await;
                 }
                 Err(e) => {
                     error!("Token refresh failed during 403 retry: {}", e);
                     let error_text = response.text().await?;
-                    anyhow::bail!("API call failed (403 Forbidden) and token refresh failed: {}", error_text);
+                    return Err(UpstreamError::new(status, format!("{} (token refresh failed)", error_text)).into());
                 }
             }
         }
 
-        if (status.as_u16() == 429 || status.is_server_error()) && retry_count < self.max_retries {
+        let retryable = status.as_u16() == 429 || status.is_server_error();
+        if retryable && retry_count < self.max_retries && crate::retry_budget::global().try_retry() {
             // 检查 Retry-After 头
             let retry_after_secs = response.headers()
                 .get("Retry-After")
@@ -1107,38 +1112,47 @@ impl KiroApiService {
             
             warn!("Request failed with status {}, retrying in {}ms...", status, delay);
             tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-            return self.call_api_with_retry(endpoint, body, retry_count + 1).await;
+            return self.call_api_with_retry(endpoint, body, ctx, retry_count + 1).await;
         }
 
         let error_text = response.text().await?;
-        anyhow::bail!("API call failed ({}): {}", status, error_text)
+        Err(UpstreamError::new(status, error_text).into())
         })
     }
 }
 
 #[async_trait]
 impl ApiServiceAdapter for KiroApiService {
+    fn protocol(&self) -> ModelProtocol {
+        ModelProtocol::Claude
+    }
+
     async fn generate_content(
         &self,
-        _model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<serde_json::Value> {
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatResponse> {
         debug!("Kiro generate_content");
-        self.call_api_with_retry("/v1/messages", request_body, 0).await
+        let request_body = request.into_protocol(ModelProtocol::Claude, Some(model))?;
+        let response = self.call_api_with_retry("/v1/messages", request_body, ctx, 0).await?;
+        Ok(ChatResponse::Claude(response))
     }
 
     async fn generate_content_stream(
         &self,
-        _model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>> {
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatStream> {
         debug!("Kiro generate_content_stream");
+        let request_body = request.into_protocol(ModelProtocol::Claude, Some(model))?;
 
         // Note: Kiro/CodeWhisperer doesn't support true streaming
         // We'll get the full response and simulate streaming
         
         // Get the full response first
-        let full_response = self.call_api_with_retry("/v1/messages", request_body, 0).await?;
+        let full_response = self.call_api_with_retry("/v1/messages", request_body, ctx, 0).await?;
         
         // Extract the content array from the response
         let content_array = full_response
@@ -1276,7 +1290,7 @@ impl ApiServiceAdapter for KiroApiService {
             }));
         };
 
-        Ok(Box::pin(stream))
+        Ok(ChatStream::Claude(Box::pin(stream)))
     }
 
     async fn list_models(&self) -> Result<ModelListResponse> {
@@ -1288,7 +1302,7 @@ impl ApiServiceAdapter for KiroApiService {
                 id: Some(model_id.to_string()),
                 name: Some(model_id.to_string()),
                 object: Some("model".to_string()),
-                created: Some(chrono::Utc::now().timestamp()),
+                created: None,
                 owned_by: Some("kiro".to_string()),
                 extra: Default::default(),
             })
@@ -1301,6 +1315,10 @@ impl ApiServiceAdapter for KiroApiService {
         })
     }
 
+    fn upstream_url(&self) -> Option<String> {
+        Some(format!("https://codewhisperer.{}.amazonaws.com", self.region))
+    }
+
     async fn refresh_token(&self) -> Result<()> {
         let creds = self.credentials.read().await;
         if self.is_token_expired(&creds) {
@@ -1309,5 +1327,12 @@ impl ApiServiceAdapter for KiroApiService {
         }
         Ok(())
     }
+
+    async fn clear_cache(&self) -> usize {
+        let mut cache = self.request_cache.write().await;
+        let cleared = cache.len();
+        cache.clear();
+        cleared
+    }
 }
 
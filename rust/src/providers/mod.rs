@@ -4,9 +4,14 @@
  * Contains implementations for different AI service providers.
  */
 
+#[cfg(feature = "gemini")]
 pub mod gemini;
+#[cfg(feature = "openai")]
 pub mod openai;
+#[cfg(feature = "claude")]
 pub mod claude;
+#[cfg(feature = "kiro")]
 pub mod kiro;
+#[cfg(feature = "qwen")]
 pub mod qwen;
 
@@ -2,124 +2,190 @@
  * OpenAI API Service Implementation
  */
 
-use crate::adapter::ApiServiceAdapter;
+use crate::adapter::{ApiServiceAdapter, ByteStream};
 use crate::common::*;
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::convert_detailed::drop_code_interpreter;
+use crate::passthrough::{ForwardRequest, PASSTHROUGH_TIMEOUT};
+use crate::regions::Regions;
+use crate::request_context::{RequestContext, OPENAI_ORGANIZATION, OPENAI_PROJECT};
+use crate::request_id::CLIENT_REQUEST_ID_HEADER;
+use crate::upstream_error::UpstreamError;
 use anyhow::Result;
 use async_stream::stream;
 use async_trait::async_trait;
 use futures::future::BoxFuture;
-use futures::Stream;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde_json::json;
-use std::pin::Pin;
+use std::sync::Arc;
 use tokio_stream::StreamExt;
 use tracing::{debug, warn};
 
 pub struct OpenAIApiService {
     client: Client,
     api_key: String,
-    base_url: String,
+    regions: Arc<Regions>,
+    organization: Option<String>,
+    project: Option<String>,
     max_retries: u32,
     base_delay: u64,
 }
 
 impl OpenAIApiService {
-    pub fn new(api_key: String, base_url: Option<String>, max_retries: u32, base_delay: u64) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(60))  // 减少到60秒
-            .connect_timeout(std::time::Duration::from_secs(10))
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .pool_max_idle_per_host(10)
-            .tcp_nodelay(true)
-            .build()?;
-
-        let base_url = base_url.unwrap_or_else(|| "https://api.openai.com/v1".to_string());
-
+    pub fn new(
+        api_key: String,
+        regions: Arc<Regions>,
+        organization: Option<String>,
+        project: Option<String>,
+        max_retries: u32,
+        base_delay: u64,
+        client: Client,
+    ) -> Result<Self> {
         Ok(Self {
             client,
             api_key,
-            base_url,
+            regions,
+            organization,
+            project,
             max_retries,
             base_delay,
         })
     }
 
+    /// Build an upstream request scoped to the client's or the configured organization/project
+    fn chat_request(&self, url: &str, body: &serde_json::Value, ctx: &RequestContext) -> RequestBuilder {
+        let request = self.client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .json(body);
+        self.scoped(request, ctx)
+    }
+
+    /// Add credentials, organization/project scope and forwarded headers
+    fn scoped(&self, request: RequestBuilder, ctx: &RequestContext) -> RequestBuilder {
+        let api_key = ctx.api_key().unwrap_or(&self.api_key);
+        let mut request = request.header("Authorization", format!("Bearer {}", api_key));
+
+        if let Some(org) = ctx.openai_organization.as_ref().or(self.organization.as_ref()) {
+            request = request.header(OPENAI_ORGANIZATION, org);
+        }
+        if let Some(project) = ctx.openai_project.as_ref().or(self.project.as_ref()) {
+            request = request.header(OPENAI_PROJECT, project);
+        }
+        if let Some(id) = &ctx.request_id {
+            request = request.header(CLIENT_REQUEST_ID_HEADER, id);
+        }
+        ctx.apply(request)
+    }
+
     fn call_api_with_retry<'a>(
         &'a self,
         endpoint: &'a str,
         body: serde_json::Value,
+        ctx: &'a RequestContext,
         retry_count: u32,
     ) -> BoxFuture<'a, Result<serde_json::Value>> {
         Box::pin(async move {
-        let url = format!("{}{}", self.base_url, endpoint);
-
-        let response = self.client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
+        if retry_count == 0 {
+            crate::retry_budget::global().record_request();
+        }
+        let response = self
+            .regions
+            .send(|base_url| self.chat_request(&format!("{}{}", base_url, endpoint), &body, ctx).send())
             .await?;
+        crate::rate_limit::record_upstream(response.headers());
+        crate::request_id::record_upstream(response.headers());
 
         let status = response.status();
         
         if status.is_success() {
-            let result: serde_json::Value = response.json().await?;
+            let result = crate::json::from_vec(response.bytes().await?.into())?;
             return Ok(result);
         }
 
         // Handle retryable errors
-        if (status.as_u16() == 429 || status.is_server_error()) && retry_count < self.max_retries {
+        let retryable = status.as_u16() == 429 || status.is_server_error();
+        if retryable && retry_count < self.max_retries && crate::retry_budget::global().try_retry() {
             let delay = self.base_delay * 2_u64.pow(retry_count);
             warn!("Request failed with status {}, retrying in {}ms...", status, delay);
             tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-            return self.call_api_with_retry(endpoint, body, retry_count + 1).await;
+            return self.call_api_with_retry(endpoint, body, ctx, retry_count + 1).await;
         }
 
         let error_text = response.text().await?;
-        anyhow::bail!("API call failed ({}): {}", status, error_text)
+        Err(UpstreamError::new(status, error_text).into())
         })
     }
+
+    /// Start a streaming chat completion, failing on a non-success status
+    async fn open_stream(&self, mut request_body: serde_json::Value, ctx: &RequestContext) -> Result<reqwest::Response> {
+        // Ensure stream flag is set
+        if let Some(obj) = request_body.as_object_mut() {
+            obj.insert("stream".to_string(), json!(true));
+        }
+        set_end_user(&mut request_body, ctx);
+
+        let response = self
+            .regions
+            .send(|base_url| self.chat_request(&format!("{}/chat/completions", base_url), &request_body, ctx).send())
+            .await?;
+        crate::rate_limit::record_upstream(response.headers());
+        crate::request_id::record_upstream(response.headers());
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(UpstreamError::stream(status, error_text).into());
+        }
+        Ok(response)
+    }
+}
+
+/// Send the end user as OpenAI's `user` field (replacing the raw id when hashing is on)
+fn set_end_user(body: &mut serde_json::Value, ctx: &RequestContext) {
+    if let (Some(user), Some(obj)) = (ctx.end_user.as_ref(), body.as_object_mut()) {
+        obj.insert("user".to_string(), json!(user));
+    }
+}
+
+/// Chat Completions cannot run code, so a code-interpreter tool would be rejected
+fn drop_unsupported_tools(body: &mut serde_json::Value) {
+    if drop_code_interpreter(body) {
+        warn!("Dropped code interpreter tool: not available on the OpenAI chat API");
+    }
 }
 
 #[async_trait]
 impl ApiServiceAdapter for OpenAIApiService {
+    fn protocol(&self) -> ModelProtocol {
+        ModelProtocol::OpenAI
+    }
+
     async fn generate_content(
         &self,
-        _model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<serde_json::Value> {
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatResponse> {
         debug!("OpenAI generate_content");
-        self.call_api_with_retry("/chat/completions", request_body, 0).await
+        let mut request_body = request.into_protocol(ModelProtocol::OpenAI, Some(model))?;
+        set_end_user(&mut request_body, ctx);
+        drop_unsupported_tools(&mut request_body);
+        let response = self.call_api_with_retry("/chat/completions", request_body, ctx, 0).await?;
+        Ok(ChatResponse::OpenAI(response))
     }
 
     async fn generate_content_stream(
         &self,
-        _model: &str,
-        mut request_body: serde_json::Value,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>> {
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatStream> {
         debug!("OpenAI generate_content_stream");
 
-        // Ensure stream flag is set
-        if let Some(obj) = request_body.as_object_mut() {
-            obj.insert("stream".to_string(), json!(true));
-        }
-
-        let url = format!("{}/chat/completions", self.base_url);
-        let response = self.client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            anyhow::bail!("Stream API call failed: {}", error_text);
-        }
-
-        let byte_stream = response.bytes_stream();
+        let mut request_body = request.into_protocol(ModelProtocol::OpenAI, Some(model))?;
+        drop_unsupported_tools(&mut request_body);
+        let byte_stream = self.open_stream(request_body, ctx).await?.bytes_stream();
         
         let stream = stream! {
             let mut bytes_stream = byte_stream;
@@ -134,8 +200,7 @@ impl ApiServiceAdapter for OpenAIApiService {
                             let line = buffer[..newline_pos].trim().to_string();
                             buffer = buffer[newline_pos + 1..].to_string();
                             
-                            if line.starts_with("data: ") {
-                                let json_data = &line[6..];
+                            if let Some(json_data) = line.strip_prefix("data: ") {
                                 if json_data == "[DONE]" {
                                     return;
                                 }
@@ -150,24 +215,45 @@ impl ApiServiceAdapter for OpenAIApiService {
                         }
                     }
                     Err(e) => {
-                        yield Err(anyhow::anyhow!("Stream error: {}", e));
+                        yield Err(anyhow::Error::new(e).context("Stream error"));
                         return;
                     }
                 }
             }
         };
 
-        Ok(Box::pin(stream))
+        Ok(ChatStream::OpenAI(Box::pin(stream)))
+    }
+
+    fn supports_stream_passthrough(&self) -> bool {
+        true
+    }
+
+    async fn generate_content_stream_raw(
+        &self,
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ByteStream> {
+        debug!("OpenAI generate_content_stream_raw");
+
+        let mut request_body = request.into_protocol(ModelProtocol::OpenAI, Some(model))?;
+        drop_unsupported_tools(&mut request_body);
+        let byte_stream = self.open_stream(request_body, ctx).await?.bytes_stream();
+        Ok(Box::pin(byte_stream.map(|chunk| chunk.map_err(anyhow::Error::from))))
     }
 
     async fn list_models(&self) -> Result<ModelListResponse> {
         debug!("OpenAI list_models");
         
-        let url = format!("{}/models", self.base_url);
-        let response = self.client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .send()
+        let response = self
+            .regions
+            .send(|base_url| {
+                self.client
+                    .get(format!("{}/models", base_url))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .send()
+            })
             .await?;
 
         if !response.status().is_success() {
@@ -179,6 +265,53 @@ impl ApiServiceAdapter for OpenAIApiService {
         Ok(result)
     }
 
+    async fn get_model(&self, model: &str) -> Result<Option<ModelInfo>> {
+        debug!("OpenAI get_model {}", model);
+
+        let response = self
+            .regions
+            .send(|base_url| {
+                self.client
+                    .get(format!("{}/models/{}", base_url, model))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .send()
+            })
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!("Get model failed: {}", error_text);
+        }
+
+        Ok(Some(response.json().await?))
+    }
+
+    fn upstream_url(&self) -> Option<String> {
+        Some(self.regions.primary().to_string())
+    }
+
+    async fn forward(&self, request: ForwardRequest, ctx: &RequestContext) -> Result<reqwest::Response> {
+        // The body may be a stream that cannot be resent, so there is no failover here
+        let base_url = self.regions.preferred();
+        let url = format!("{}{}", base_url, request.path);
+        debug!("Forwarding {} {}", request.method, url);
+        let upstream = self
+            .client
+            .request(request.method, url)
+            .headers(request.headers)
+            .timeout(PASSTHROUGH_TIMEOUT)
+            .body(request.body);
+        let result = self.scoped(upstream, ctx).send().await;
+        if let Ok(response) = &result {
+            crate::request_id::record_upstream(response.headers());
+        }
+        self.regions.record(&base_url, result.as_ref().is_ok_and(|r| !r.status().is_server_error()));
+        Ok(result?)
+    }
+
     async fn refresh_token(&self) -> Result<()> {
         // OpenAI uses static API keys, no refresh needed
         Ok(())
@@ -5,18 +5,19 @@
  * Uses OpenAI-compatible format.
  */
 
-use crate::adapter::ApiServiceAdapter;
+use crate::adapter::{ApiServiceAdapter, ByteStream};
 use crate::common::*;
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::request_context::RequestContext;
+use crate::upstream_error::UpstreamError;
 use anyhow::{Context, Result};
 use async_stream::stream;
 use async_trait::async_trait;
 use futures::future::BoxFuture;
-use futures::Stream;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::path::PathBuf;
-use std::pin::Pin;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::sync::RwLock;
@@ -49,14 +50,8 @@ impl QwenApiService {
         oauth_creds_file: Option<PathBuf>,
         max_retries: u32,
         base_delay: u64,
+        client: Client,
     ) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(60))  // 减少到60秒
-            .connect_timeout(std::time::Duration::from_secs(10))
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .pool_max_idle_per_host(10)
-            .tcp_nodelay(true)
-            .build()?;
 
         let credentials_path = oauth_creds_file.unwrap_or_else(|| {
             dirs::home_dir()
@@ -97,7 +92,10 @@ impl QwenApiService {
 
     async fn refresh_access_token(&self) -> Result<()> {
         info!("Refreshing Qwen access token...");
-        warn!("Qwen token refresh not fully implemented - requires Qwen OAuth flow");
+        warn!(
+            "Qwen token refresh not fully implemented - requires Qwen OAuth flow (re-authenticate to update {:?})",
+            self.credentials_path
+        );
         Ok(())
     }
 
@@ -105,9 +103,13 @@ impl QwenApiService {
         &'a self,
         endpoint: &'a str,
         body: serde_json::Value,
+        ctx: &'a RequestContext,
         retry_count: u32,
     ) -> BoxFuture<'a, Result<serde_json::Value>> {
         Box::pin(async move {
+        if retry_count == 0 {
+            crate::retry_budget::global().record_request();
+        }
         {
             let creds = self.credentials.read().await;
             if self.is_token_expired(&creds) {
@@ -119,73 +121,103 @@ impl QwenApiService {
         let url = format!("{}{}", QWEN_API_BASE, endpoint);
         let creds = self.credentials.read().await;
 
-        let response = self.client
+        let request = self.client
             .post(&url)
             .header("Authorization", format!("Bearer {}", creds.access_token))
             .header("Content-Type", "application/json")
-            .json(&body)
+            .json(&body);
+        let response = ctx
+            .apply(request)
             .send()
             .await?;
+        crate::request_id::record_upstream(response.headers());
 
         let status = response.status();
 
         if status.is_success() {
-            let result: serde_json::Value = response.json().await?;
+            let result = crate::json::from_vec(response.bytes().await?.into())?;
             return Ok(result);
         }
 
-        if (status.as_u16() == 429 || status.is_server_error()) && retry_count < self.max_retries {
+        let retryable = status.as_u16() == 429 || status.is_server_error();
+        if retryable && retry_count < self.max_retries && crate::retry_budget::global().try_retry() {
             let delay = self.base_delay * 2_u64.pow(retry_count);
             warn!("Request failed with status {}, retrying in {}ms...", status, delay);
             tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-            return self.call_api_with_retry(endpoint, body, retry_count + 1).await;
+            return self.call_api_with_retry(endpoint, body, ctx, retry_count + 1).await;
         }
 
         let error_text = response.text().await?;
-        anyhow::bail!("API call failed ({}): {}", status, error_text)
+        Err(UpstreamError::new(status, error_text).into())
         })
     }
-}
-
-#[async_trait]
-impl ApiServiceAdapter for QwenApiService {
-    async fn generate_content(
-        &self,
-        _model: &str,
-        request_body: serde_json::Value,
-    ) -> Result<serde_json::Value> {
-        debug!("Qwen generate_content");
-        self.call_api_with_retry("/chat/completions", request_body, 0).await
-    }
-
-    async fn generate_content_stream(
-        &self,
-        _model: &str,
-        mut request_body: serde_json::Value,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>> {
-        debug!("Qwen generate_content_stream");
 
+    /// Start a streaming chat completion, failing on a non-success status
+    async fn open_stream(&self, mut request_body: serde_json::Value, ctx: &RequestContext) -> Result<reqwest::Response> {
         if let Some(obj) = request_body.as_object_mut() {
             obj.insert("stream".to_string(), json!(true));
         }
+        set_end_user(&mut request_body, ctx);
 
         let creds = self.credentials.read().await;
         let url = format!("{}/chat/completions", QWEN_API_BASE);
         
-        let response = self.client
+        let request = self.client
             .post(&url)
             .header("Authorization", format!("Bearer {}", creds.access_token))
             .header("Content-Type", "application/json")
-            .json(&request_body)
+            .json(&request_body);
+        let response = ctx
+            .apply(request)
             .send()
             .await?;
+        crate::request_id::record_upstream(response.headers());
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
             let error_text = response.text().await?;
-            anyhow::bail!("Stream API call failed: {}", error_text);
+            return Err(UpstreamError::stream(status, error_text).into());
         }
+        Ok(response)
+    }
+}
+
+/// Qwen's compatible-mode API accepts OpenAI's `user` field
+fn set_end_user(body: &mut serde_json::Value, ctx: &RequestContext) {
+    if let (Some(user), Some(obj)) = (ctx.end_user.as_ref(), body.as_object_mut()) {
+        obj.insert("user".to_string(), json!(user));
+    }
+}
+
+#[async_trait]
+impl ApiServiceAdapter for QwenApiService {
+    fn protocol(&self) -> ModelProtocol {
+        ModelProtocol::OpenAI
+    }
+
+    async fn generate_content(
+        &self,
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatResponse> {
+        debug!("Qwen generate_content");
+        let mut request_body = request.into_protocol(ModelProtocol::OpenAI, Some(model))?;
+        set_end_user(&mut request_body, ctx);
+        let response = self.call_api_with_retry("/chat/completions", request_body, ctx, 0).await?;
+        Ok(ChatResponse::OpenAI(response))
+    }
+
+    async fn generate_content_stream(
+        &self,
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ChatStream> {
+        debug!("Qwen generate_content_stream");
 
-        let byte_stream = response.bytes_stream();
+        let request_body = request.into_protocol(ModelProtocol::OpenAI, Some(model))?;
+        let byte_stream = self.open_stream(request_body, ctx).await?.bytes_stream();
         
         let stream = stream! {
             let mut bytes_stream = byte_stream;
@@ -200,8 +232,7 @@ impl ApiServiceAdapter for QwenApiService {
                             let line = buffer[..newline_pos].trim().to_string();
                             buffer = buffer[newline_pos + 1..].to_string();
                             
-                            if line.starts_with("data: ") {
-                                let json_data = &line[6..];
+                            if let Some(json_data) = line.strip_prefix("data: ") {
                                 if json_data == "[DONE]" {
                                     return;
                                 }
@@ -214,14 +245,31 @@ impl ApiServiceAdapter for QwenApiService {
                         }
                     }
                     Err(e) => {
-                        yield Err(anyhow::anyhow!("Stream error: {}", e));
+                        yield Err(anyhow::Error::new(e).context("Stream error"));
                         return;
                     }
                 }
             }
         };
 
-        Ok(Box::pin(stream))
+        Ok(ChatStream::OpenAI(Box::pin(stream)))
+    }
+
+    fn supports_stream_passthrough(&self) -> bool {
+        true
+    }
+
+    async fn generate_content_stream_raw(
+        &self,
+        model: &str,
+        request: ChatRequest,
+        ctx: &RequestContext,
+    ) -> Result<ByteStream> {
+        debug!("Qwen generate_content_stream_raw");
+
+        let request_body = request.into_protocol(ModelProtocol::OpenAI, Some(model))?;
+        let byte_stream = self.open_stream(request_body, ctx).await?.bytes_stream();
+        Ok(Box::pin(byte_stream.map(|chunk| chunk.map_err(anyhow::Error::from))))
     }
 
     async fn list_models(&self) -> Result<ModelListResponse> {
@@ -246,6 +294,10 @@ impl ApiServiceAdapter for QwenApiService {
         })
     }
 
+    fn upstream_url(&self) -> Option<String> {
+        Some(QWEN_API_BASE.to_string())
+    }
+
     async fn refresh_token(&self) -> Result<()> {
         let creds = self.credentials.read().await;
         if self.is_token_expired(&creds) {
@@ -0,0 +1,285 @@
+/*!
+ * Rate Limiting
+ *
+ * Sliding-window request limits per client. The in-memory limiter is exact for a
+ * single instance; the Redis limiter applies the same window across every
+ * instance in a cluster.
+ *
+ * Every response carries OpenAI-style `x-ratelimit-*` headers so client SDK
+ * backoff keeps working behind the proxy: request counts come from the proxy's
+ * own limiter, token counts (and request counts when the proxy does not limit)
+ * from the upstream's headers, Anthropic's `anthropic-ratelimit-*` included.
+ */
+
+use crate::config::Config;
+use anyhow::Result;
+use async_trait::async_trait;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use redis::aio::ConnectionManager;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// Outcome of a rate-limit check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// How long until a request would be admitted (zero when allowed)
+    pub retry_after: Duration,
+}
+
+#[async_trait]
+pub trait RateLimiter: Send + Sync {
+    /// Count a request for `key` against `limit` requests per `window`
+    async fn check(&self, key: &str, limit: u32, window: Duration) -> Result<RateLimitDecision>;
+}
+
+/// Single-instance sliding-window log
+#[derive(Default)]
+pub struct MemoryRateLimiter {
+    windows: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl MemoryRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimiter for MemoryRateLimiter {
+    async fn check(&self, key: &str, limit: u32, window: Duration) -> Result<RateLimitDecision> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().await;
+        let hits = windows.entry(key.to_string()).or_default();
+
+        while hits.front().is_some_and(|t| now.duration_since(*t) >= window) {
+            hits.pop_front();
+        }
+
+        if hits.len() < limit as usize {
+            hits.push_back(now);
+            return Ok(RateLimitDecision {
+                allowed: true,
+                limit,
+                remaining: limit - hits.len() as u32,
+                retry_after: Duration::ZERO,
+            });
+        }
+
+        let oldest = *hits.front().expect("window is full");
+        Ok(RateLimitDecision {
+            allowed: false,
+            limit,
+            remaining: 0,
+            retry_after: window.saturating_sub(now.duration_since(oldest)),
+        })
+    }
+}
+
+/// Sliding window over a Redis sorted set, evaluated atomically in a Lua script
+const SLIDING_WINDOW_SCRIPT: &str = r"
+local key = KEYS[1]
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
+local member = ARGV[4]
+
+redis.call('ZREMRANGEBYSCORE', key, '-inf', now - window)
+local count = redis.call('ZCARD', key)
+if count < limit then
+    redis.call('ZADD', key, now, member)
+    redis.call('PEXPIRE', key, window)
+    return {1, limit - count - 1, 0}
+end
+
+local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+local retry_after = window - (now - tonumber(oldest[2]))
+return {0, 0, retry_after}
+";
+
+/// Cluster-wide limiter shared by every instance using the same Redis
+pub struct RedisRateLimiter {
+    conn: ConnectionManager,
+    prefix: String,
+    script: redis::Script,
+}
+
+impl RedisRateLimiter {
+    pub fn new(conn: ConnectionManager, prefix: impl Into<String>) -> Self {
+        Self {
+            conn,
+            prefix: prefix.into(),
+            script: redis::Script::new(SLIDING_WINDOW_SCRIPT),
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimiter for RedisRateLimiter {
+    async fn check(&self, key: &str, limit: u32, window: Duration) -> Result<RateLimitDecision> {
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        // Unique member so concurrent requests in the same millisecond are all counted
+        let member = format!("{}-{}", now_ms, uuid::Uuid::new_v4().simple());
+
+        let mut conn = self.conn.clone();
+        let (allowed, remaining, retry_after_ms): (i64, i64, i64) = self
+            .script
+            .key(format!("{}ratelimit:{}", self.prefix, key))
+            .arg(now_ms)
+            .arg(window.as_millis() as u64)
+            .arg(limit)
+            .arg(member)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(RateLimitDecision {
+            allowed: allowed == 1,
+            limit,
+            remaining: remaining.max(0) as u32,
+            retry_after: Duration::from_millis(retry_after_ms.max(0) as u64),
+        })
+    }
+}
+
+/// Build the configured limiter; `None` when rate limiting is disabled
+pub fn build_limiter(
+    config: &Config,
+    redis: Option<ConnectionManager>,
+) -> Result<Option<Arc<dyn RateLimiter>>> {
+    if config.rate_limit_requests == 0 {
+        return Ok(None);
+    }
+
+    match config.rate_limit_backend.as_str() {
+        "memory" => Ok(Some(Arc::new(MemoryRateLimiter::new()))),
+        "redis" => {
+            let conn = redis.ok_or_else(|| {
+                anyhow::anyhow!("rate_limit_backend \"redis\" requires redis_url to be set")
+            })?;
+            Ok(Some(Arc::new(RedisRateLimiter::new(conn, config.cluster_key_prefix.clone()))))
+        }
+        other => anyhow::bail!("Unknown rate_limit_backend: {}", other),
+    }
+}
+
+// ============================================================================
+// Rate-limit response headers
+// ============================================================================
+
+tokio::task_local! {
+    /// Rate-limit headers gathered while handling the current request
+    static HEADERS: std::sync::Mutex<RateLimitHeaders>;
+}
+
+#[derive(Default)]
+struct RateLimitHeaders {
+    proxy: HeaderMap,
+    upstream: HeaderMap,
+}
+
+/// Run a request handler, returning its output and the `x-ratelimit-*` headers
+/// to send with the response; the proxy's own values win over the upstream's
+pub async fn scope<F: Future>(f: F) -> (F::Output, HeaderMap) {
+    HEADERS
+        .scope(std::sync::Mutex::new(RateLimitHeaders::default()), async {
+            let output = f.await;
+            let headers = HEADERS.with(|headers| {
+                let mut headers = headers.lock().unwrap();
+                let mut merged = std::mem::take(&mut headers.upstream);
+                merged.extend(std::mem::take(&mut headers.proxy));
+                merged
+            });
+            (output, headers)
+        })
+        .await
+}
+
+fn record(f: impl FnOnce(&mut RateLimitHeaders)) {
+    // Outside a request scope (e.g. the embedded client) there is nothing to report
+    let _ = HEADERS.try_with(|headers| f(&mut headers.lock().unwrap()));
+}
+
+/// OpenAI's duration format for reset headers: `1s`, `6m0s`, `1h2m3s`
+pub fn format_reset(duration: Duration) -> String {
+    let secs = duration.as_secs_f64().ceil() as u64;
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{}s", m, s),
+        (h, m, s) => format!("{}h{}m{}s", h, m, s),
+    }
+}
+
+/// Headers describing a limiter decision; the window resets in full at the latest after `window`
+pub fn decision_headers(decision: &RateLimitDecision, window: Duration) -> HeaderMap {
+    let reset = if decision.allowed { window } else { decision.retry_after };
+    let mut headers = HeaderMap::new();
+    headers.insert("x-ratelimit-limit-requests", HeaderValue::from(decision.limit));
+    headers.insert("x-ratelimit-remaining-requests", HeaderValue::from(decision.remaining));
+    if let Ok(value) = HeaderValue::from_str(&format_reset(reset)) {
+        headers.insert("x-ratelimit-reset-requests", value);
+    }
+    headers
+}
+
+/// Report the proxy limiter's decision for the current request
+pub fn record_decision(decision: &RateLimitDecision, window: Duration) {
+    let headers = decision_headers(decision, window);
+    record(|current| current.proxy.extend(headers));
+}
+
+/// `x-ratelimit-*` headers equivalent to an upstream response's rate-limit headers
+pub fn upstream_headers(upstream: &HeaderMap) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in upstream {
+        if name.as_str().starts_with("x-ratelimit-") {
+            headers.insert(name.clone(), value.clone());
+            continue;
+        }
+        // anthropic-ratelimit-{requests,tokens,input-tokens,output-tokens}-{limit,remaining,reset}
+        let Some(rest) = name.as_str().strip_prefix("anthropic-ratelimit-") else {
+            continue;
+        };
+        let Some((kind, field)) = rest.rsplit_once('-') else {
+            continue;
+        };
+        let value = match field {
+            "limit" | "remaining" => value.clone(),
+            // An RFC 3339 timestamp, rewritten as the time left
+            "reset" => {
+                let Some(at) = value.to_str().ok().and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok()) else {
+                    continue;
+                };
+                let left = (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default();
+                match HeaderValue::from_str(&format_reset(left)) {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+        if let Ok(name) = HeaderName::from_bytes(format!("x-ratelimit-{}-{}", field, kind).as_bytes()) {
+            headers.insert(name, value);
+        }
+    }
+    headers
+}
+
+/// The upstream `x-ratelimit-*` headers reported so far for the current request
+pub fn recorded_upstream() -> HeaderMap {
+    HEADERS
+        .try_with(|headers| headers.lock().unwrap().upstream.clone())
+        .unwrap_or_default()
+}
+
+/// Report an upstream response's rate-limit headers for the current request
+pub fn record_upstream(upstream: &HeaderMap) {
+    let headers = upstream_headers(upstream);
+    if !headers.is_empty() {
+        record(|current| current.upstream.extend(headers));
+    }
+}
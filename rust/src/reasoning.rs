@@ -0,0 +1,267 @@
+/*!
+ * Reasoning Tag Filter
+ *
+ * Removes `<think>…</think>` style reasoning blocks that models such as
+ * DeepSeek-R1 or QwQ emit inline, or moves them into a `reasoning_content`
+ * field. Works on buffered responses and on streams where a tag may be split
+ * across chunks, for both OpenAI and Claude response shapes.
+ */
+
+use crate::config::{ReasoningFilterRule, ReasoningMode};
+use anyhow::Result;
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::pin::Pin;
+
+/// Text split into the part meant for the client and the reasoning part
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FilterOutput {
+    pub content: String,
+    pub reasoning: String,
+}
+
+impl FilterOutput {
+    fn extend(&mut self, other: FilterOutput) {
+        self.content.push_str(&other.content);
+        self.reasoning.push_str(&other.reasoning);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.content.is_empty() && self.reasoning.is_empty()
+    }
+}
+
+/// Incremental splitter for one text stream
+pub struct TagFilter {
+    open: String,
+    close: String,
+    in_reasoning: bool,
+    /// Text withheld because it may be the start of a tag
+    pending: String,
+    /// Drop the blank lines models put between the reasoning and the answer
+    trim_next: bool,
+}
+
+/// Length of the longest suffix of `text` that is a proper prefix of `tag`
+fn partial_tag_len(text: &str, tag: &str) -> usize {
+    (1..tag.len())
+        .rev()
+        .find(|&k| text.ends_with(&tag[..k]))
+        .unwrap_or(0)
+}
+
+impl TagFilter {
+    pub fn new(tag: &str) -> Self {
+        Self {
+            open: format!("<{}>", tag),
+            close: format!("</{}>", tag),
+            in_reasoning: false,
+            pending: String::new(),
+            trim_next: false,
+        }
+    }
+
+    pub fn push(&mut self, text: &str) -> FilterOutput {
+        let mut out = FilterOutput::default();
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.push_str(text);
+        let mut rest = buffer.as_str();
+
+        loop {
+            let tag = if self.in_reasoning { &self.close } else { &self.open };
+            let (before, found) = match rest.find(tag.as_str()) {
+                Some(pos) => (&rest[..pos], Some(pos + tag.len())),
+                None => {
+                    let keep = partial_tag_len(rest, tag);
+                    self.pending = rest[rest.len() - keep..].to_string();
+                    (&rest[..rest.len() - keep], None)
+                }
+            };
+
+            if self.in_reasoning {
+                out.reasoning.push_str(before);
+            } else {
+                self.push_content(&mut out, before);
+            }
+
+            match found {
+                Some(end) => {
+                    self.trim_next = self.in_reasoning;
+                    self.in_reasoning = !self.in_reasoning;
+                    rest = &rest[end..];
+                }
+                None => break,
+            }
+        }
+        out
+    }
+
+    /// Flush withheld text at the end of the stream
+    pub fn finish(&mut self) -> FilterOutput {
+        let pending = std::mem::take(&mut self.pending);
+        let mut out = FilterOutput::default();
+        if self.in_reasoning {
+            out.reasoning = pending;
+        } else {
+            self.push_content(&mut out, &pending);
+        }
+        out
+    }
+
+    fn push_content(&mut self, out: &mut FilterOutput, text: &str) {
+        let text = if self.trim_next { text.trim_start() } else { text };
+        if !text.is_empty() {
+            self.trim_next = false;
+            out.content.push_str(text);
+        }
+    }
+}
+
+/// Split a complete text into answer and reasoning
+pub fn split_reasoning(text: &str, tag: &str) -> FilterOutput {
+    let mut filter = TagFilter::new(tag);
+    let mut out = filter.push(text);
+    out.extend(filter.finish());
+    out
+}
+
+/// First rule whose model pattern matches (case-insensitive)
+pub fn rule_for<'a>(rules: &'a [ReasoningFilterRule], model: &str) -> Option<&'a ReasoningFilterRule> {
+    let model = model.to_ascii_lowercase();
+    rules.iter().find(|rule| {
+        let pattern = rule.model.to_ascii_lowercase();
+        match pattern.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => model == pattern,
+        }
+    })
+}
+
+/// Write filtered text back into `object[text_key]`, adding `reasoning_content` in field mode
+fn write_back(object: &mut serde_json::Map<String, Value>, text_key: &str, out: &FilterOutput, mode: ReasoningMode) {
+    object.insert(text_key.to_string(), json!(out.content));
+    if mode == ReasoningMode::Field && !out.reasoning.is_empty() {
+        let existing = object.get("reasoning_content").and_then(|v| v.as_str()).unwrap_or("");
+        let reasoning = format!("{}{}", existing, out.reasoning);
+        object.insert("reasoning_content".to_string(), json!(reasoning));
+    }
+}
+
+/// Filter a buffered OpenAI or Claude response in place
+pub fn filter_response(rule: &ReasoningFilterRule, response: &mut Value) {
+    if let Some(choices) = response.get_mut("choices").and_then(|c| c.as_array_mut()) {
+        for message in choices.iter_mut().filter_map(|c| c.get_mut("message")?.as_object_mut()) {
+            if let Some(text) = message.get("content").and_then(|c| c.as_str()) {
+                let out = split_reasoning(text, &rule.tag);
+                write_back(message, "content", &out, rule.mode);
+            }
+        }
+    }
+
+    if let Some(blocks) = response.get_mut("content").and_then(|c| c.as_array_mut()) {
+        for block in blocks.iter_mut().filter_map(|b| b.as_object_mut()) {
+            if block.get("type").and_then(|t| t.as_str()) != Some("text") {
+                continue;
+            }
+            if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                let out = split_reasoning(text, &rule.tag);
+                write_back(block, "text", &out, rule.mode);
+            }
+        }
+    }
+}
+
+/// Stateful filter over stream chunks, one tag filter per choice / content block
+pub struct StreamFilter {
+    rule: ReasoningFilterRule,
+    filters: HashMap<u64, TagFilter>,
+}
+
+impl StreamFilter {
+    pub fn new(rule: ReasoningFilterRule) -> Self {
+        Self {
+            rule,
+            filters: HashMap::new(),
+        }
+    }
+
+    fn filter(&mut self, index: u64) -> &mut TagFilter {
+        let tag = &self.rule.tag;
+        self.filters.entry(index).or_insert_with(|| TagFilter::new(tag))
+    }
+
+    /// Filter one chunk; may return an extra chunk carrying flushed text before a block stop
+    pub fn process(&mut self, mut chunk: Value) -> Vec<Value> {
+        let mode = self.rule.mode;
+
+        if let Some(choices) = chunk.get_mut("choices").and_then(|c| c.as_array_mut()) {
+            for choice in choices.iter_mut() {
+                let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let finished = choice.get("finish_reason").is_some_and(|r| !r.is_null());
+                let Some(delta) = choice.get_mut("delta").and_then(|d| d.as_object_mut()) else {
+                    continue;
+                };
+
+                let text = delta.get("content").and_then(|c| c.as_str());
+                let had_content = text.is_some();
+                let mut out = self.filter(index).push(text.unwrap_or(""));
+                if finished {
+                    out.extend(self.filter(index).finish());
+                    self.filters.remove(&index);
+                }
+                if had_content || !out.is_empty() {
+                    write_back(delta, "content", &out, mode);
+                }
+            }
+            return vec![chunk];
+        }
+
+        let index = chunk.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+        match chunk.get("type").and_then(|t| t.as_str()) {
+            Some("content_block_delta") => {
+                let delta = chunk.get_mut("delta").and_then(|d| d.as_object_mut());
+                if let Some(delta) = delta.filter(|d| d.get("type").and_then(|t| t.as_str()) == Some("text_delta")) {
+                    let text = delta.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                    let out = self.filter(index).push(text);
+                    write_back(delta, "text", &out, mode);
+                }
+                vec![chunk]
+            }
+            Some("content_block_stop") => {
+                let out = self.filters.remove(&index).map(|mut f| f.finish()).unwrap_or_default();
+                if out.is_empty() {
+                    return vec![chunk];
+                }
+                let mut delta = serde_json::Map::new();
+                delta.insert("type".to_string(), json!("text_delta"));
+                write_back(&mut delta, "text", &out, mode);
+                let flushed = json!({"type": "content_block_delta", "index": index, "delta": delta});
+                vec![flushed, chunk]
+            }
+            _ => vec![chunk],
+        }
+    }
+}
+
+/// Wrap a provider stream with the reasoning filter
+pub fn filter_stream(
+    inner: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+    rule: ReasoningFilterRule,
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    Box::pin(stream! {
+        let mut inner = inner;
+        let mut filter = StreamFilter::new(rule);
+        while let Some(item) = inner.next().await {
+            match item {
+                Ok(chunk) => {
+                    for chunk in filter.process(chunk) {
+                        yield Ok(chunk);
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+    })
+}
@@ -0,0 +1,192 @@
+/*!
+ * Regional Endpoints
+ *
+ * A provider can be reached through several base URLs (regions), listed in
+ * order of preference. Requests go to the first endpoint in rotation; a
+ * connection failure or 5xx moves on to the next one, and an endpoint that
+ * keeps failing is taken out of rotation for a cooldown. A background probe
+ * checks every endpoint, so a recovered region is preferred again and a dead
+ * one is skipped before a client request has to find out.
+ */
+
+use crate::config::RegionFailoverConfig;
+use anyhow::Result;
+use futures::Future;
+use reqwest::{Client, Response};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Endpoints to use: the single base URL (if set) followed by the regional ones, or `default`
+pub fn base_urls(base_url: Option<&String>, regional: &[String], default: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for url in base_url.into_iter().chain(regional) {
+        let url = url.trim_end_matches('/').to_string();
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    if urls.is_empty() {
+        urls.push(default.to_string());
+    }
+    urls
+}
+
+#[derive(Debug, Default)]
+struct EndpointState {
+    consecutive_failures: u32,
+    /// Out of rotation until then
+    down_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    base_url: String,
+    state: Mutex<EndpointState>,
+}
+
+#[derive(Debug)]
+pub struct Regions {
+    endpoints: Vec<Endpoint>,
+    config: RegionFailoverConfig,
+}
+
+impl Regions {
+    pub fn new(base_urls: Vec<String>, config: &RegionFailoverConfig) -> Self {
+        Self {
+            endpoints: base_urls
+                .into_iter()
+                .map(|base_url| Endpoint {
+                    base_url,
+                    state: Mutex::new(EndpointState::default()),
+                })
+                .collect(),
+            config: config.clone(),
+        }
+    }
+
+    /// The most preferred endpoint
+    pub fn primary(&self) -> &str {
+        self.endpoints.first().map_or("", |endpoint| endpoint.base_url.as_str())
+    }
+
+    fn endpoint(&self, base_url: &str) -> Option<&Endpoint> {
+        self.endpoints.iter().find(|endpoint| endpoint.base_url == base_url)
+    }
+
+    /// Whether an endpoint is in rotation; one whose cooldown has ended is tried again
+    pub fn is_up(&self, base_url: &str) -> bool {
+        self.endpoint(base_url).is_some_and(|endpoint| {
+            let state = endpoint.state.lock().unwrap();
+            state.down_until.is_none_or(|until| until <= Instant::now())
+        })
+    }
+
+    /// Endpoints in the order to try them: those in rotation by preference, then
+    /// the rest (soonest back first) as a last resort
+    pub fn candidates(&self) -> Vec<String> {
+        let (up, mut down): (Vec<&Endpoint>, Vec<&Endpoint>) =
+            self.endpoints.iter().partition(|endpoint| self.is_up(&endpoint.base_url));
+        down.sort_by_key(|endpoint| endpoint.state.lock().unwrap().down_until);
+        up.into_iter().chain(down).map(|endpoint| endpoint.base_url.clone()).collect()
+    }
+
+    /// The endpoint to send a request that cannot be retried elsewhere
+    pub fn preferred(&self) -> String {
+        self.candidates().into_iter().next().unwrap_or_default()
+    }
+
+    /// Record the outcome of a call to an endpoint
+    pub fn record(&self, base_url: &str, ok: bool) {
+        let Some(endpoint) = self.endpoint(base_url) else {
+            return;
+        };
+        let mut state = endpoint.state.lock().unwrap();
+        if ok {
+            if state.down_until.take().is_some() {
+                info!("Endpoint {} is back in rotation", base_url);
+            }
+            state.consecutive_failures = 0;
+            return;
+        }
+        state.consecutive_failures += 1;
+        let now = Instant::now();
+        let in_rotation = state.down_until.is_none_or(|until| until <= now);
+        if in_rotation && state.consecutive_failures >= self.config.failure_threshold.max(1) {
+            warn!(
+                "Endpoint {} failed {} times in a row; out of rotation for {}s",
+                base_url, state.consecutive_failures, self.config.cooldown_secs
+            );
+            crate::alerts::circuit_opened(
+                base_url,
+                format!(
+                    "failed {} times in a row; out of rotation for {}s",
+                    state.consecutive_failures, self.config.cooldown_secs
+                ),
+            );
+            state.down_until = Some(now + Duration::from_secs(self.config.cooldown_secs));
+        }
+    }
+
+    /// Send a request to the preferred endpoint, failing over to the next on a
+    /// connection error or 5xx; the last endpoint's response is returned as is
+    pub async fn send<F, Fut>(&self, send: F) -> Result<Response>
+    where
+        F: Fn(&str) -> Fut,
+        Fut: Future<Output = reqwest::Result<Response>>,
+    {
+        let candidates = self.candidates();
+        let last = candidates.len().saturating_sub(1);
+        for (position, base_url) in candidates.iter().enumerate() {
+            match send(base_url).await {
+                Ok(response) => {
+                    let failed = response.status().is_server_error();
+                    self.record(base_url, !failed);
+                    if !failed || position == last {
+                        return Ok(response);
+                    }
+                    warn!("Endpoint {} returned {}; failing over", base_url, response.status());
+                }
+                Err(e) => {
+                    self.record(base_url, false);
+                    if position == last {
+                        return Err(e.into());
+                    }
+                    warn!("Endpoint {} unreachable ({}); failing over", base_url, e);
+                }
+            }
+        }
+        anyhow::bail!("No upstream endpoint configured")
+    }
+
+    /// Probe every endpoint once; any response below 500 means it is reachable
+    pub async fn check_health(&self, client: &Client) {
+        for endpoint in &self.endpoints {
+            let result = client.get(&endpoint.base_url).timeout(PROBE_TIMEOUT).send().await;
+            let ok = result.is_ok_and(|response| !response.status().is_server_error());
+            self.record(&endpoint.base_url, ok);
+        }
+    }
+
+    /// Probe the endpoints periodically, until the provider using them is dropped;
+    /// nothing to do with a single endpoint
+    pub fn spawn_health_checks(self: &Arc<Self>, client: Client) {
+        let interval = self.config.health_check_interval_secs;
+        if self.endpoints.len() < 2 || interval == 0 {
+            return;
+        }
+        let regions = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+            loop {
+                ticker.tick().await;
+                let Some(regions) = regions.upgrade() else {
+                    break;
+                };
+                regions.check_health(&client).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,293 @@
+/*!
+ * Request Context
+ *
+ * Per-request data carried from the HTTP handlers down to the upstream
+ * providers, such as inbound headers the operator allows through.
+ */
+
+use crate::pool_manager::PoolKey;
+use http::{HeaderMap, HeaderName};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const ANTHROPIC_BETA: &str = "anthropic-beta";
+pub const OPENAI_ORGANIZATION: &str = "openai-organization";
+pub const OPENAI_PROJECT: &str = "openai-project";
+pub const END_USER_HEADER: &str = "x-user-id";
+pub const TAGS_HEADER: &str = "x-aiproxy-tags";
+
+/// Tags kept per request, and the longest key / value accepted
+const MAX_TAGS: usize = 16;
+const MAX_TAG_LEN: usize = 128;
+
+/// Headers that carry client credentials or describe the inbound connection;
+/// never forwarded even when an allowlist pattern matches them
+const NEVER_FORWARD: &[&str] = &[
+    "authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "cookie",
+    "host",
+    "content-length",
+    "content-type",
+    "content-encoding",
+    "accept-encoding",
+    "connection",
+    "keep-alive",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    /// Inbound headers sent on to the upstream provider
+    pub forward_headers: HeaderMap,
+    /// `anthropic-beta` flags requested by the client on the Claude path
+    pub anthropic_beta: Vec<String>,
+    /// Client-selected OpenAI organization and project; override the provider config
+    pub openai_organization: Option<String>,
+    pub openai_project: Option<String>,
+    /// End-user identifier (already hashed when hashing is enabled) for upstreams that accept one
+    pub end_user: Option<String>,
+    /// Caller-supplied attribution tags for logs and usage metrics
+    pub tags: BTreeMap<String, String>,
+    /// The client opted out of content logging
+    pub no_content_logging: bool,
+    /// The request's id, returned to the client and sent to upstreams that record one
+    pub request_id: Option<String>,
+    /// Key from the provider's pool to use instead of the configured one
+    pub pool_key: Option<PoolKey>,
+    /// Upstream timeout for this request, replacing the HTTP client's
+    pub timeout: Option<Duration>,
+}
+
+/// End-user id from the OpenAI `user` or Claude `metadata.user_id` body field, falling back
+/// to the `x-user-id` header
+pub fn end_user_id(headers: &HeaderMap, body: &Value) -> Option<String> {
+    body.get("user")
+        .or_else(|| body.pointer("/metadata/user_id"))
+        .and_then(|v| v.as_str())
+        .or_else(|| headers.get(END_USER_HEADER).and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+}
+
+fn valid_tag(key: &str, value: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_TAG_LEN
+        && value.len() <= MAX_TAG_LEN
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+/// `key=value` pairs from an `x-aiproxy-tags` header, comma-separated
+pub fn parse_tags(header: &str) -> BTreeMap<String, String> {
+    header
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .filter(|(key, value)| valid_tag(key, value))
+        .take(MAX_TAGS)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Tags from the `metadata` body extension and the `x-aiproxy-tags` header (the
+/// header wins on conflicts). String metadata entries other than `user_id` are
+/// removed from the body, as upstreams differ in which metadata keys they accept.
+pub fn take_tags(headers: &HeaderMap, body: &mut Value) -> BTreeMap<String, String> {
+    let mut tags = BTreeMap::new();
+    if let Some(metadata) = body.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+        let keys: Vec<String> = metadata
+            .iter()
+            .filter(|(key, value)| key.as_str() != "user_id" && value.is_string())
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            if let Some(Value::String(value)) = metadata.remove(&key) {
+                if valid_tag(&key, &value) {
+                    tags.insert(key, value);
+                }
+            }
+        }
+        if metadata.is_empty() {
+            if let Some(body) = body.as_object_mut() {
+                body.remove("metadata");
+            }
+        }
+    }
+    for header in headers.get_all(TAGS_HEADER).iter().filter_map(|v| v.to_str().ok()) {
+        tags.extend(parse_tags(header));
+    }
+
+    while tags.len() > MAX_TAGS {
+        tags.pop_last();
+    }
+    tags
+}
+
+/// Tags as `key=value` pairs for log lines
+pub fn format_tags(tags: &BTreeMap<String, String>) -> String {
+    tags.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>().join(",")
+}
+
+/// Stable pseudonym for an end-user id: hex HMAC-SHA256 keyed by the salt
+pub fn hash_end_user(id: &str, salt: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(id.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Beta flags from every `anthropic-beta` header (each may be comma-separated)
+pub fn beta_flags(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(ANTHROPIC_BETA)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|flag| !flag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Forced flags first, then requested ones, without duplicates; `None` when empty
+pub fn merge_beta_flags(forced: &[String], requested: &[String]) -> Option<String> {
+    let mut merged: Vec<&str> = Vec::new();
+    for flag in forced.iter().chain(requested) {
+        if !merged.contains(&flag.as_str()) {
+            merged.push(flag);
+        }
+    }
+    (!merged.is_empty()).then(|| merged.join(","))
+}
+
+/// Whether a header name matches the allowlist; entries are case-insensitive and
+/// may end in `*` to match a prefix (e.g. `x-trace-*`)
+pub fn is_forwardable(name: &str, allowlist: &[String]) -> bool {
+    let name = name.to_ascii_lowercase();
+    if NEVER_FORWARD.contains(&name.as_str()) {
+        return false;
+    }
+
+    allowlist.iter().any(|entry| {
+        let entry = entry.trim().to_ascii_lowercase();
+        match entry.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == entry,
+        }
+    })
+}
+
+impl RequestContext {
+    /// Pick the allowlisted headers out of an inbound request
+    pub fn from_headers(headers: &HeaderMap, allowlist: &[String]) -> Self {
+        let mut forward_headers = HeaderMap::new();
+        if !allowlist.is_empty() {
+            for (name, value) in headers {
+                if is_forwardable(name.as_str(), allowlist) {
+                    forward_headers.append(name.clone(), value.clone());
+                }
+            }
+        }
+        Self {
+            forward_headers,
+            ..Default::default()
+        }
+    }
+
+    /// Also carry the client's `anthropic-beta` flags, whether or not they are allowlisted
+    pub fn with_anthropic_beta(mut self, headers: &HeaderMap) -> Self {
+        self.anthropic_beta = beta_flags(headers);
+        self
+    }
+
+    /// Also carry the client's `OpenAI-Organization` / `OpenAI-Project` headers
+    pub fn with_openai_scope(mut self, headers: &HeaderMap) -> Self {
+        let value = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+        };
+        self.openai_organization = value(OPENAI_ORGANIZATION);
+        self.openai_project = value(OPENAI_PROJECT);
+        self
+    }
+
+    /// Attach the end-user id, hashing it first when a salt is given
+    pub fn with_end_user(mut self, id: Option<String>, hash_salt: Option<&str>) -> Self {
+        self.end_user = match hash_salt {
+            Some(salt) => id.map(|id| hash_end_user(&id, salt)),
+            None => id,
+        };
+        self
+    }
+
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn with_pool_key(mut self, pool_key: Option<PoolKey>) -> Self {
+        self.pool_key = pool_key;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The pool key to authenticate with, if one was picked
+    pub fn api_key(&self) -> Option<&str> {
+        self.pool_key.as_ref().map(|key| key.api_key.as_str())
+    }
+
+    pub fn with_no_content_logging(mut self, no_content_logging: bool) -> Self {
+        // Transcripts hold content too
+        if no_content_logging {
+            crate::transcripts::skip();
+        }
+        self.no_content_logging = no_content_logging;
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        HeaderName::try_from(name)
+            .ok()
+            .and_then(|name| self.forward_headers.get(name))
+            .and_then(|v| v.to_str().ok())
+    }
+
+    /// Add the forwarded headers to an upstream request, replacing defaults of the same name,
+    /// and set the request's timeout
+    pub fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request = match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+        if self.forward_headers.is_empty() {
+            request
+        } else {
+            request.headers(self.forward_headers.clone())
+        }
+    }
+}
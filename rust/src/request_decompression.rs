@@ -0,0 +1,61 @@
+/*!
+ * Request Decompression
+ *
+ * Some batch clients compress their (large) request bodies. JSON bodies sent
+ * with `Content-Encoding: gzip` or `br` are decompressed, off the async
+ * runtime, before parsing. The output is held to `max_body_bytes`, the limit
+ * on plain bodies, so compression cannot get a larger body in and a small
+ * compressed body cannot expand into gigabytes (a decompression bomb);
+ * decoding stops as soon as the limit is passed. Set `request_decompression`
+ * to false to refuse compressed bodies instead.
+ */
+
+use bytes::Bytes;
+use std::io::Read;
+
+/// Why a compressed body could not be decompressed
+#[derive(Debug)]
+pub enum DecompressError {
+    /// An encoding other than gzip, br or identity
+    Unsupported(String),
+    /// The body decompresses to more than the limit
+    TooLarge { limit: usize },
+    /// The body is not valid data of its encoding
+    Corrupt(std::io::Error),
+}
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported(encoding) => {
+                write!(f, "Unsupported Content-Encoding `{}`; request bodies may be sent as gzip or br", encoding)
+            }
+            Self::TooLarge { limit } => write!(f, "Request body decompresses to more than {} bytes", limit),
+            Self::Corrupt(e) => write!(f, "Request body could not be decompressed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecompressError {}
+
+/// The body as sent, decoded per its `Content-Encoding` into at most `limit` bytes
+pub fn decompress(encoding: &str, body: Bytes, limit: usize) -> Result<Bytes, DecompressError> {
+    let encoding = encoding.trim().to_ascii_lowercase();
+    let reader: Box<dyn Read + '_> = match encoding.as_str() {
+        "" | "identity" => return Ok(body),
+        "gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(&body[..])),
+        "br" => Box::new(brotli::Decompressor::new(&body[..], 4096)),
+        _ => return Err(DecompressError::Unsupported(encoding)),
+    };
+
+    // One byte past the limit tells a body at the limit from one beyond it
+    let mut decoded = Vec::new();
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(DecompressError::Corrupt)?;
+    if decoded.len() > limit {
+        return Err(DecompressError::TooLarge { limit });
+    }
+    Ok(decoded.into())
+}
@@ -0,0 +1,82 @@
+/*!
+ * Request IDs
+ *
+ * Every client API request gets an id: the client's own `x-request-id`, or a
+ * generated one. It is returned in the `x-request-id` response header, attached
+ * to the request's log lines and sent to OpenAI-compatible upstreams as
+ * `x-client-request-id`. The id the upstream assigned (`x-request-id`,
+ * Anthropic's `request-id`, AWS's `x-amzn-requestid`) is returned as
+ * `x-upstream-request-id`, so a failing call can be traced on both sides.
+ */
+
+use http::{HeaderMap, HeaderValue};
+use std::future::Future;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const UPSTREAM_REQUEST_ID_HEADER: &str = "x-upstream-request-id";
+/// How OpenAI accepts a caller-chosen id for its own logs and support requests
+pub const CLIENT_REQUEST_ID_HEADER: &str = "x-client-request-id";
+
+/// Upstream response headers carrying the provider's id for the call, in order of preference
+const UPSTREAM_ID_HEADERS: &[&str] = &["x-request-id", "request-id", "x-amzn-requestid"];
+
+/// Longest client-supplied id accepted; longer or non-printable ones are replaced
+const MAX_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    /// The upstream id of the latest upstream call made for the current request
+    static UPSTREAM_ID: std::sync::Mutex<Option<String>>;
+}
+
+/// The client's request id, when it sent a usable one
+pub fn from_headers(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_ID_LEN && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(String::from)
+}
+
+/// The client's request id, or a new one
+pub fn from_headers_or_new(headers: &HeaderMap) -> String {
+    from_headers(headers).unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// The id an upstream assigned to its response
+pub fn upstream_id(headers: &HeaderMap) -> Option<String> {
+    UPSTREAM_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(String::from)
+}
+
+/// Run a request handler, returning its output and the id of the last upstream call it made
+pub async fn scope<F: Future>(f: F) -> (F::Output, Option<String>) {
+    UPSTREAM_ID
+        .scope(std::sync::Mutex::new(None), async {
+            let output = f.await;
+            let upstream = UPSTREAM_ID.with(|id| id.lock().unwrap().take());
+            (output, upstream)
+        })
+        .await
+}
+
+/// Report an upstream response's id for the current request; a retry's id replaces the earlier one
+pub fn record_upstream(headers: &HeaderMap) {
+    if let Some(id) = upstream_id(headers) {
+        // Outside a request scope (e.g. the embedded client) there is nothing to report
+        let _ = UPSTREAM_ID.try_with(|current| *current.lock().unwrap() = Some(id));
+    }
+}
+
+/// Set the request and upstream ids on a response
+pub fn apply(headers: &mut HeaderMap, id: &str, upstream: Option<&str>) {
+    if let Ok(value) = HeaderValue::from_str(id) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+    if let Some(value) = upstream.and_then(|id| HeaderValue::from_str(id).ok()) {
+        headers.insert(UPSTREAM_REQUEST_ID_HEADER, value);
+    }
+}
@@ -0,0 +1,89 @@
+/*!
+ * Request Limits
+ *
+ * Rejects oversized client requests before anything is sent upstream: too
+ * many messages, too many images, or too much base64 payload (inline images,
+ * audio and files) in total. An upstream would take its time to refuse such a
+ * request, or time out on it. `request_limits` applies to every client; a
+ * client key's own `limits` replace it field by field. Unlike the model
+ * registry's limits, these hold whatever model is asked for.
+ */
+
+use crate::config::RequestLimits;
+use crate::model_registry::RequestFeatures;
+use serde_json::Value;
+
+/// What a request (in any protocol) carries, as far as the limits go
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestSize {
+    pub messages: usize,
+    pub images: usize,
+    pub base64_bytes: usize,
+}
+
+impl RequestSize {
+    pub fn of(body: &Value) -> Self {
+        let features = RequestFeatures::of(body);
+        let mut base64_bytes = 0;
+        count_base64(body, &mut base64_bytes);
+        Self {
+            messages: features.messages,
+            images: features.images.len(),
+            base64_bytes,
+        }
+    }
+}
+
+/// The first limit the request exceeds, as a message for the client
+pub fn check(limits: &RequestLimits, body: &Value) -> Result<(), String> {
+    if *limits == RequestLimits::default() {
+        return Ok(());
+    }
+    let size = RequestSize::of(body);
+    if let Some(limit) = limits.max_messages.filter(|limit| size.messages > *limit) {
+        return Err(format!("Request has {} messages; at most {} are allowed", size.messages, limit));
+    }
+    if let Some(limit) = limits.max_images.filter(|limit| size.images > *limit) {
+        return Err(format!("Request has {} images; at most {} are allowed", size.images, limit));
+    }
+    if let Some(limit) = limits.max_base64_bytes.filter(|limit| size.base64_bytes > *limit) {
+        return Err(format!(
+            "Request has {:.1} MB of base64 data; at most {:.1} MB is allowed",
+            mb(size.base64_bytes),
+            mb(limit)
+        ));
+    }
+    Ok(())
+}
+
+fn mb(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Base64 payloads anywhere in the body: `data:` URLs (OpenAI images and files), Claude
+/// `base64` sources, Gemini inline data and OpenAI input audio
+fn count_base64(value: &Value, total: &mut usize) {
+    match value {
+        Value::String(text) => {
+            if let Some((_, data)) = text.strip_prefix("data:").and_then(|url| url.split_once(";base64,")) {
+                *total += data.len();
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| count_base64(item, total)),
+        Value::Object(fields) => {
+            let is_payload = fields.get("type").and_then(|t| t.as_str()) == Some("base64");
+            for (name, field) in fields {
+                match (name.as_str(), field) {
+                    ("data", Value::String(data)) if is_payload => *total += data.len(),
+                    ("inlineData" | "inline_data" | "input_audio", Value::Object(inline)) => {
+                        if let Some(data) = inline.get("data").and_then(|d| d.as_str()) {
+                            *total += data.len();
+                        }
+                    }
+                    _ => count_base64(field, total),
+                }
+            }
+        }
+        _ => {}
+    }
+}
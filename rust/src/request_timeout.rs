@@ -0,0 +1,50 @@
+/*!
+ * Request Timeout
+ *
+ * One global upstream timeout suits nobody: an interactive client would rather
+ * fail fast, while a batch job can wait minutes for a long generation. A client
+ * may set its own timeout with `x-aiproxy-timeout-ms`, capped at
+ * `request_timeouts.max_ms`. Without the header, the timeout configured for the
+ * request path (`request_timeouts.routes`) applies, then the one for the
+ * provider (`request_timeouts.providers`), then `http_client.request_timeout_secs`.
+ */
+
+use crate::config::RequestTimeoutConfig;
+use http::HeaderMap;
+use std::time::Duration;
+
+pub const TIMEOUT_HEADER: &str = "x-aiproxy-timeout-ms";
+
+/// The client's requested timeout in milliseconds, capped at `max_ms`; `Err` when the header
+/// is not a positive integer
+pub fn from_headers(headers: &HeaderMap, max_ms: u64) -> Result<Option<u64>, String> {
+    let Some(value) = headers.get(TIMEOUT_HEADER) else {
+        return Ok(None);
+    };
+    match value.to_str().ok().and_then(|v| v.trim().parse::<u64>().ok()) {
+        Some(ms) if ms > 0 => Ok(Some(ms.min(max_ms))),
+        _ => Err(format!("{} must be a positive number of milliseconds", TIMEOUT_HEADER)),
+    }
+}
+
+/// The timeout for a request, or `None` to keep the HTTP client's default
+pub fn resolve(config: &RequestTimeoutConfig, headers: &HeaderMap, path: &str, provider: &str) -> Result<Option<Duration>, String> {
+    let ms = match from_headers(headers, config.max_ms)? {
+        Some(ms) => Some(ms),
+        None => route_timeout(config, path).or_else(|| config.providers.get(provider).copied()),
+    };
+    Ok(ms.map(Duration::from_millis))
+}
+
+/// An exact path wins over patterns, and a longer pattern over a shorter one
+fn route_timeout(config: &RequestTimeoutConfig, path: &str) -> Option<u64> {
+    if let Some(&ms) = config.routes.get(path) {
+        return Some(ms);
+    }
+    config
+        .routes
+        .iter()
+        .filter(|(pattern, _)| crate::router::matches(pattern, path))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, &ms)| ms)
+}
@@ -0,0 +1,124 @@
+/*!
+ * Rerank
+ *
+ * Cohere/Jina-compatible `/v1/rerank` requests and responses. Requests go to a
+ * dedicated reranking backend when one is configured; otherwise the chat
+ * provider scores every document in a single prompt and the scores are
+ * parsed back out of its reply.
+ */
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+/// Longest document text included in a scoring prompt, in characters
+const MAX_DOCUMENT_CHARS: usize = 4000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RerankRequest {
+    pub model: Option<String>,
+    pub query: String,
+    pub documents: Vec<String>,
+    pub top_n: Option<usize>,
+    pub return_documents: bool,
+}
+
+impl RerankRequest {
+    /// Documents may be plain strings or objects with a `text` field
+    pub fn parse(body: &Value) -> Result<Self> {
+        let query = body
+            .get("query")
+            .and_then(|q| q.as_str())
+            .context("query is required")?
+            .to_string();
+        let documents = body
+            .get("documents")
+            .and_then(|d| d.as_array())
+            .context("documents is required")?
+            .iter()
+            .enumerate()
+            .map(|(index, document)| match document {
+                Value::String(text) => Ok(text.clone()),
+                _ => document
+                    .get("text")
+                    .and_then(|t| t.as_str())
+                    .map(String::from)
+                    .with_context(|| format!("documents[{}] must be a string or have a text field", index)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            model: body.get("model").and_then(|m| m.as_str()).map(String::from),
+            query,
+            documents,
+            top_n: body.get("top_n").and_then(|n| n.as_u64()).map(|n| n as usize),
+            // Cohere defaults to returning documents, Jina too
+            return_documents: body.get("return_documents").and_then(|r| r.as_bool()).unwrap_or(true),
+        })
+    }
+
+    /// OpenAI-format chat request asking `model` to score every document
+    pub fn scoring_prompt(&self, model: &str) -> Value {
+        let documents: String = self
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let text: String = text.chars().take(MAX_DOCUMENT_CHARS).collect();
+                format!("[{}] {}\n", index, text)
+            })
+            .collect();
+        let instructions = format!(
+            "Rate how relevant each document is to the query, from 0.0 (unrelated) to 1.0 (fully answers it). \
+             Reply with only a JSON array of {} numbers, one per document, in document order.",
+            self.documents.len()
+        );
+
+        json!({
+            "model": model,
+            "temperature": 0,
+            "messages": [
+                {"role": "system", "content": instructions},
+                {"role": "user", "content": format!("Query: {}\n\nDocuments:\n{}", self.query, documents)},
+            ],
+        })
+    }
+
+    /// Cohere-style response: results sorted by score, cut to `top_n`
+    pub fn response(&self, id: &str, scores: &[f64]) -> Value {
+        let mut ranked: Vec<(usize, f64)> = scores.iter().copied().enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(self.top_n.unwrap_or(ranked.len()));
+
+        let results: Vec<Value> = ranked
+            .into_iter()
+            .map(|(index, score)| {
+                let mut result = json!({"index": index, "relevance_score": score});
+                if self.return_documents {
+                    result["document"] = json!({"text": self.documents[index]});
+                }
+                result
+            })
+            .collect();
+        json!({"id": id, "model": self.model, "results": results})
+    }
+}
+
+/// Scores from a model's reply: the first JSON array in the text, one number per document
+pub fn parse_scores(reply: &str, documents: usize) -> Result<Vec<f64>> {
+    let start = reply.find('[').context("No score array in the model reply")?;
+    let end = reply.rfind(']').filter(|end| *end > start).context("No score array in the model reply")?;
+    let scores: Vec<Value> = serde_json::from_str(&reply[start..=end]).context("Malformed score array in the model reply")?;
+    if scores.len() != documents {
+        anyhow::bail!("Model returned {} scores for {} documents", scores.len(), documents);
+    }
+    scores
+        .iter()
+        .map(|score| {
+            score
+                .as_f64()
+                .or_else(|| score.as_str().and_then(|s| s.trim().parse().ok()))
+                .map(|score| score.clamp(0.0, 1.0))
+                .context("Non-numeric score in the model reply")
+        })
+        .collect()
+}
@@ -0,0 +1,198 @@
+/*!
+ * Response Cache
+ *
+ * Reuses the response to an identical chat request. The cache key is derived
+ * from a canonical form of the request rather than its raw bytes: object keys
+ * are sorted, null fields, per-request identifiers and streaming options are
+ * dropped, and a message whose content is a single text part is treated the
+ * same as plain string content. Sampling parameters (`temperature`, `seed`,
+ * ...) only distinguish entries when listed in `vary`, so deployments that
+ * treat them as noise can share entries across them.
+ *
+ * With stale-if-error on, entries are kept past their TTL so that an expired
+ * one can answer in place of a failed upstream call, marked `X-Cache: STALE`.
+ */
+
+use crate::cluster::SharedStore;
+use crate::common::ModelProtocol;
+use crate::config::{ResponseCacheConfig, StreamPacingConfig};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Response header reporting `hit`, `miss` or `stale`
+pub const CACHE_HEADER: &str = "x-aiproxy-cache";
+
+/// Conventional header marking a stale response served because the upstream failed
+pub const STALE_HEADER: &str = "x-cache";
+
+/// Fields that never change what the model answers
+const VOLATILE_FIELDS: &[&str] = &["stream", "stream_options", "user", "request_id", "metadata"];
+
+/// Sampling parameters, left out of the key unless listed in `vary`
+const SAMPLING_FIELDS: &[&str] = &["temperature", "top_p", "top_k", "seed"];
+
+/// Whether a `Cache-Control` request header asks to skip the cache
+pub fn bypass(cache_control: Option<&str>) -> bool {
+    cache_control.is_some_and(|value| {
+        value
+            .split(',')
+            .any(|directive| matches!(directive.trim().to_ascii_lowercase().as_str(), "no-cache" | "no-store"))
+    })
+}
+
+/// A single text part says the same as plain string content
+fn normalize_content(content: &mut Value) {
+    // Only a bare `{"type": "text", "text": ...}`; extra fields such as `cache_control` matter
+    let plain_text = |part: &Value| part.get("type").and_then(|t| t.as_str()) == Some("text") && part.as_object().is_some_and(|p| p.len() == 2);
+    let text = match content.as_array().map(Vec::as_slice) {
+        Some([part]) if plain_text(part) => part.get("text").cloned(),
+        _ => None,
+    };
+    if let Some(text) = text {
+        *content = text;
+    }
+}
+
+fn drop_nulls(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|_, field| !field.is_null());
+            fields.values_mut().for_each(drop_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(drop_nulls),
+        _ => {}
+    }
+}
+
+/// The parts of a request that decide its response
+pub fn canonical_request(body: &Value, config: &ResponseCacheConfig) -> Value {
+    let mut body = body.clone();
+    drop_nulls(&mut body);
+    if let Some(fields) = body.as_object_mut() {
+        fields.retain(|key, _| {
+            let sampling_ignored = SAMPLING_FIELDS.contains(&key.as_str()) && !config.vary.contains(key);
+            !VOLATILE_FIELDS.contains(&key.as_str()) && !sampling_ignored && !config.ignore_fields.contains(key)
+        });
+        if let Some(system) = fields.get_mut("system") {
+            normalize_content(system);
+        }
+        for message in fields.get_mut("messages").and_then(|m| m.as_array_mut()).into_iter().flatten() {
+            if let Some(content) = message.get_mut("content") {
+                normalize_content(content);
+            }
+        }
+    }
+    body
+}
+
+/// JSON with object keys in sorted order, whatever order the map keeps them in
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(fields) => {
+            let mut entries: Vec<(&String, &Value)> = fields.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let entries: Vec<String> = entries
+                .into_iter()
+                .map(|(key, field)| format!("{}:{}", Value::String(key.clone()), canonical_json(field)))
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+        Value::Array(items) => format!("[{}]", items.iter().map(canonical_json).collect::<Vec<_>>().join(",")),
+        other => other.to_string(),
+    }
+}
+
+/// Cache key of a request in a client protocol
+pub fn cache_key(protocol: ModelProtocol, body: &Value, config: &ResponseCacheConfig) -> String {
+    let canonical = canonical_json(&canonical_request(body, config));
+    let digest = Sha256::digest(canonical.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("response_cache:{}:{}", protocol.as_str(), hex)
+}
+
+/// How long past the TTL a client may be served a stale entry; 0 means never
+pub fn stale_window(config: &ResponseCacheConfig, client_id: &str) -> u64 {
+    config
+        .stale_if_error_clients
+        .get(client_id)
+        .copied()
+        .unwrap_or(config.stale_if_error_secs)
+}
+
+/// A response with the time (Unix seconds) it was stored
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    stored_at: i64,
+    response: Value,
+}
+
+pub struct ResponseCache {
+    store: Arc<dyn SharedStore>,
+    config: ResponseCacheConfig,
+}
+
+impl ResponseCache {
+    pub fn new(store: Arc<dyn SharedStore>, config: &ResponseCacheConfig) -> Self {
+        Self {
+            store,
+            config: config.clone(),
+        }
+    }
+
+    /// How hits are streamed back to streaming requests
+    pub fn pacing(&self) -> &StreamPacingConfig {
+        &self.config.replay_pacing
+    }
+
+    pub fn key(&self, protocol: ModelProtocol, body: &Value) -> String {
+        cache_key(protocol, body, &self.config)
+    }
+
+    /// The cached response if it is within its TTL; a store failure counts as a miss
+    pub async fn get(&self, key: &str) -> Option<Value> {
+        self.lookup(key, 0).await
+    }
+
+    /// The cached response if it expired no more than the client's stale window ago
+    pub async fn get_stale(&self, key: &str, client_id: &str) -> Option<Value> {
+        match stale_window(&self.config, client_id) {
+            0 => None,
+            window => self.lookup(key, window).await,
+        }
+    }
+
+    async fn lookup(&self, key: &str, stale_secs: u64) -> Option<Value> {
+        let entry: Entry = match self.store.get(key).await {
+            Ok(cached) => serde_json::from_str(&cached?).ok()?,
+            Err(e) => {
+                warn!("Response cache lookup failed: {}", e);
+                return None;
+            }
+        };
+        let age = chrono::Utc::now().timestamp().saturating_sub(entry.stored_at).max(0) as u64;
+        (age <= self.config.ttl_secs.saturating_add(stale_secs)).then_some(entry.response)
+    }
+
+    /// Store a response, kept past its TTL for as long as any client may be served it stale
+    pub async fn put(&self, key: &str, response: &Value) -> Result<()> {
+        let longest_stale = self
+            .config
+            .stale_if_error_clients
+            .values()
+            .copied()
+            .chain([self.config.stale_if_error_secs])
+            .max()
+            .unwrap_or_default();
+        let ttl = Duration::from_secs(self.config.ttl_secs.saturating_add(longest_stale));
+        let entry = Entry {
+            stored_at: chrono::Utc::now().timestamp(),
+            response: response.clone(),
+        };
+        self.store.set(key, &serde_json::to_string(&entry)?, Some(ttl)).await
+    }
+}
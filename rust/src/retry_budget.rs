@@ -0,0 +1,108 @@
+/*!
+ * Retry Budget
+ *
+ * Caps upstream retries across all requests at a fraction of recent request
+ * volume (plus a small floor for quiet periods), so a full upstream outage
+ * does not multiply traffic and slow down recovery. The per-request retry
+ * policy of each provider still applies underneath.
+ */
+
+use crate::config::RetryBudgetConfig;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+static GLOBAL: OnceLock<RetryBudget> = OnceLock::new();
+
+/// Requests and retries started within one second
+#[derive(Debug, Default)]
+struct Bucket {
+    second: u64,
+    requests: u64,
+    retries: u64,
+}
+
+pub struct RetryBudget {
+    config: RetryBudgetConfig,
+    started: Instant,
+    buckets: Mutex<VecDeque<Bucket>>,
+    denied: AtomicU64,
+}
+
+impl RetryBudget {
+    pub fn new(config: &RetryBudgetConfig) -> Self {
+        Self {
+            config: config.clone(),
+            started: Instant::now(),
+            buckets: Mutex::new(VecDeque::new()),
+            denied: AtomicU64::new(0),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Count a first attempt
+    pub fn record_request(&self) {
+        self.record_request_at(self.now());
+    }
+
+    /// Whether a retry may be sent now; an allowed retry is counted against the budget
+    pub fn try_retry(&self) -> bool {
+        self.try_retry_at(self.now())
+    }
+
+    /// Retries refused since startup
+    pub fn denied(&self) -> u64 {
+        self.denied.load(Ordering::Relaxed)
+    }
+
+    pub fn record_request_at(&self, second: u64) {
+        let mut buckets = self.buckets.lock().unwrap();
+        self.bucket(&mut buckets, second).requests += 1;
+    }
+
+    pub fn try_retry_at(&self, second: u64) -> bool {
+        if !self.config.enabled {
+            return true;
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        self.bucket(&mut buckets, second);
+
+        let (requests, retries) = buckets
+            .iter()
+            .fold((0, 0), |(requests, retries), b| (requests + b.requests, retries + b.retries));
+        let allowed = self.config.min_retries_per_sec * self.config.window_secs as f64 + self.config.ratio * requests as f64;
+        if (retries as f64) < allowed {
+            self.bucket(&mut buckets, second).retries += 1;
+            true
+        } else {
+            self.denied.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// The bucket for `second`, dropping buckets that fell out of the window
+    fn bucket<'a>(&self, buckets: &'a mut VecDeque<Bucket>, second: u64) -> &'a mut Bucket {
+        let oldest = second.saturating_sub(self.config.window_secs.saturating_sub(1));
+        while buckets.front().is_some_and(|b| b.second < oldest) {
+            buckets.pop_front();
+        }
+        if buckets.back().is_none_or(|b| b.second < second) {
+            buckets.push_back(Bucket { second, ..Default::default() });
+        }
+        buckets.back_mut().unwrap()
+    }
+}
+
+/// Install the process-wide budget; later calls are ignored
+pub fn configure(config: &RetryBudgetConfig) {
+    let _ = GLOBAL.set(RetryBudget::new(config));
+}
+
+/// The process-wide budget shared by every provider
+pub fn global() -> &'static RetryBudget {
+    GLOBAL.get_or_init(|| RetryBudget::new(&RetryBudgetConfig::default()))
+}
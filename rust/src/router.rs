@@ -0,0 +1,177 @@
+/*!
+ * Routers
+ *
+ * Provider-selection policies. Each `routers` entry in the configuration
+ * becomes a `Router`; they are consulted in order and the first one to pick a
+ * provider decides, then `model_routes` and finally `model_provider`. A
+ * provider named in the request path always wins. New policies implement the
+ * trait and a `RouterConfig` variant instead of growing the request handlers.
+ */
+
+use crate::config::{ModelRoute, RouterConfig};
+use crate::model_registry::RequestFeatures;
+use crate::provider_registry::route_for;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// What a router may look at
+pub struct RouteRequest<'a> {
+    pub model: &'a str,
+    /// The client's request body, when routing a chat request
+    pub body: Option<&'a Value>,
+}
+
+impl<'a> RouteRequest<'a> {
+    pub fn new(model: &'a str, body: Option<&'a Value>) -> Self {
+        Self { model, body }
+    }
+}
+
+pub trait Router: Send + Sync {
+    /// The provider for the request, or `None` to leave it to the next router
+    fn select(&self, request: &RouteRequest) -> Option<String>;
+
+    /// Every provider the router may pick, so their adapters exist before it does
+    fn providers(&self) -> Vec<String>;
+}
+
+/// Whether `model` matches a pattern: exactly, or as a prefix when it ends in `*`
+pub fn matches(pattern: &str, model: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix),
+        None => model == pattern,
+    }
+}
+
+pub fn build(config: &RouterConfig) -> Arc<dyn Router> {
+    match config {
+        RouterConfig::Static { routes } => Arc::new(StaticRouter::new(routes.clone())),
+        RouterConfig::Weighted { model, weights } => {
+            Arc::new(WeightedRouter::new(model, weights.iter().map(|(provider, weight)| (provider.clone(), *weight)).collect()))
+        }
+        RouterConfig::Capability { model, vision, tools } => Arc::new(CapabilityRouter::new(model, vision.clone(), tools.clone())),
+        RouterConfig::Cost { model, prices } => {
+            Arc::new(CostRouter::new(model, prices.iter().map(|(provider, price)| (provider.clone(), *price)).collect()))
+        }
+    }
+}
+
+/// The first rule matching the model
+pub struct StaticRouter {
+    routes: Vec<ModelRoute>,
+}
+
+impl StaticRouter {
+    pub fn new(routes: Vec<ModelRoute>) -> Self {
+        Self { routes }
+    }
+}
+
+impl Router for StaticRouter {
+    fn select(&self, request: &RouteRequest) -> Option<String> {
+        route_for(&self.routes, request.model).map(|route| route.provider.clone())
+    }
+
+    fn providers(&self) -> Vec<String> {
+        self.routes.iter().map(|route| route.provider.clone()).collect()
+    }
+}
+
+/// Smooth weighted round-robin: over any run of requests each provider gets its share,
+/// and picks of the same provider are spread out rather than bunched
+pub struct WeightedRouter {
+    model: String,
+    weights: Vec<(String, u32)>,
+    current: Mutex<Vec<i64>>,
+}
+
+impl WeightedRouter {
+    pub fn new(model: &str, weights: Vec<(String, u32)>) -> Self {
+        let current = Mutex::new(vec![0; weights.len()]);
+        Self { model: model.to_string(), weights, current }
+    }
+}
+
+impl Router for WeightedRouter {
+    fn select(&self, request: &RouteRequest) -> Option<String> {
+        let total: i64 = self.weights.iter().map(|(_, weight)| i64::from(*weight)).sum();
+        if total == 0 || !matches(&self.model, request.model) {
+            return None;
+        }
+        let mut current = self.current.lock().unwrap();
+        for (value, (_, weight)) in current.iter_mut().zip(&self.weights) {
+            *value += i64::from(*weight);
+        }
+        let (best, _) = current.iter().enumerate().max_by_key(|(i, value)| (**value, std::cmp::Reverse(*i)))?;
+        current[best] -= total;
+        Some(self.weights[best].0.clone())
+    }
+
+    fn providers(&self) -> Vec<String> {
+        self.weights.iter().filter(|(_, weight)| *weight > 0).map(|(provider, _)| provider.clone()).collect()
+    }
+}
+
+/// Requests with images go to `vision`, requests with tools to `tools`; images decide
+/// when a request has both
+pub struct CapabilityRouter {
+    model: String,
+    vision: Option<String>,
+    tools: Option<String>,
+}
+
+impl CapabilityRouter {
+    pub fn new(model: &str, vision: Option<String>, tools: Option<String>) -> Self {
+        Self { model: model.to_string(), vision, tools }
+    }
+}
+
+impl Router for CapabilityRouter {
+    fn select(&self, request: &RouteRequest) -> Option<String> {
+        let body = request.body?;
+        if !matches(&self.model, request.model) {
+            return None;
+        }
+        let features = RequestFeatures::of(body);
+        if !features.images.is_empty() && self.vision.is_some() {
+            return self.vision.clone();
+        }
+        if features.tools > 0 {
+            return self.tools.clone();
+        }
+        None
+    }
+
+    fn providers(&self) -> Vec<String> {
+        self.vision.iter().chain(&self.tools).cloned().collect()
+    }
+}
+
+/// The provider with the lowest configured price; ties go to the first listed
+pub struct CostRouter {
+    model: String,
+    prices: Vec<(String, f64)>,
+}
+
+impl CostRouter {
+    pub fn new(model: &str, prices: Vec<(String, f64)>) -> Self {
+        Self { model: model.to_string(), prices }
+    }
+}
+
+impl Router for CostRouter {
+    fn select(&self, request: &RouteRequest) -> Option<String> {
+        if !matches(&self.model, request.model) {
+            return None;
+        }
+        self.prices
+            .iter()
+            .filter(|(_, price)| price.is_finite())
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(provider, _)| provider.clone())
+    }
+
+    fn providers(&self) -> Vec<String> {
+        self.prices.iter().map(|(provider, _)| provider.clone()).collect()
+    }
+}
@@ -0,0 +1,246 @@
+/*!
+ * External Secret References
+ *
+ * Credentials in config may reference an external secret store instead of
+ * holding the value:
+ *
+ * - `vault://<path>#<field>` - HashiCorp Vault (KV v1 or v2), using `VAULT_ADDR` and `VAULT_TOKEN`
+ * - `aws-sm://<name>#<field>` - AWS Secrets Manager, using the standard `AWS_*` environment credentials
+ *
+ * `#<field>` selects a key from a JSON secret and may be omitted for single-value secrets.
+ *
+ * Periodic refreshes reach provider credentials (the adapters and the pool
+ * keys); the server's own secrets (`required_api_key`, `redis_url`, ...) are
+ * read once at startup, so rotating them needs a restart.
+ */
+
+use crate::config::Config;
+use crate::secrets::credential_fields;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+const VAULT_SCHEME: &str = "vault://";
+const AWS_SM_SCHEME: &str = "aws-sm://";
+
+/// Credential fields resolved once at startup and never refreshed
+const STARTUP_ONLY_FIELDS: &[&str] = &[
+    "required_api_key",
+    "admin_api_key",
+    "redis_url",
+    "end_user_hash_salt",
+    "jwt.hs256_secret",
+    "oidc.client_secret",
+    "request_signing.secret",
+];
+
+pub fn is_reference(value: &str) -> bool {
+    value.starts_with(VAULT_SCHEME) || value.starts_with(AWS_SM_SCHEME)
+}
+
+/// Whether any credential in the config is an external reference
+pub fn has_references(config: &Config) -> bool {
+    let mut config = config.clone();
+    credential_fields(&mut config)
+        .iter()
+        .any(|(_, value)| is_reference(value))
+}
+
+/// Fields holding a reference that a refresh does not reach
+pub fn startup_only_references(config: &Config) -> Vec<String> {
+    let mut config = config.clone();
+    credential_fields(&mut config)
+        .into_iter()
+        .filter(|(field, value)| STARTUP_ONLY_FIELDS.contains(&field.as_str()) && is_reference(value))
+        .map(|(field, _)| field)
+        .collect()
+}
+
+/// Resolve every external reference in the config in place, returning how many were resolved
+pub async fn resolve_config(config: &mut Config, client: &Client) -> Result<usize> {
+    let mut resolved = 0;
+    for (field, value) in credential_fields(config) {
+        if is_reference(value) {
+            *value = resolve(value, client)
+                .await
+                .with_context(|| format!("Failed to resolve secret reference for {}", field))?;
+            debug!("Resolved secret reference for {}", field);
+            resolved += 1;
+        }
+    }
+    if resolved > 0 {
+        info!("Resolved {} credential(s) from external secret stores", resolved);
+    }
+    Ok(resolved)
+}
+
+/// Resolve a single reference
+pub async fn resolve(reference: &str, client: &Client) -> Result<String> {
+    let (location, field) = match reference.split_once('#') {
+        Some((location, field)) => (location, Some(field)),
+        None => (reference, None),
+    };
+
+    if let Some(path) = location.strip_prefix(VAULT_SCHEME) {
+        let secret = fetch_vault_secret(path, client).await?;
+        select_field(&secret, field)
+    } else if let Some(name) = location.strip_prefix(AWS_SM_SCHEME) {
+        let secret_string = fetch_aws_secret(name, client).await?;
+        match serde_json::from_str::<Value>(&secret_string) {
+            Ok(secret @ Value::Object(_)) => select_field(&secret, field),
+            _ if field.is_none() => Ok(secret_string),
+            _ => anyhow::bail!("Secret {} is not a JSON object, cannot select a field", name),
+        }
+    } else {
+        anyhow::bail!("Unsupported secret reference: {}", reference)
+    }
+}
+
+/// Pick a field from a JSON secret; without a field the secret must hold exactly one value
+fn select_field(secret: &Value, field: Option<&str>) -> Result<String> {
+    let obj = secret
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("Secret payload is not a JSON object"))?;
+
+    let value = match field {
+        Some(field) => obj
+            .get(field)
+            .ok_or_else(|| anyhow::anyhow!("Secret has no field '{}'", field))?,
+        None if obj.len() == 1 => obj.values().next().unwrap(),
+        None => anyhow::bail!("Secret has {} fields; specify one with #field", obj.len()),
+    };
+
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        other => Ok(other.to_string()),
+    }
+}
+
+async fn fetch_vault_secret(path: &str, client: &Client) -> Result<Value> {
+    let addr = std::env::var("VAULT_ADDR").context("VAULT_ADDR is not set")?;
+    let token = std::env::var("VAULT_TOKEN").context("VAULT_TOKEN is not set")?;
+
+    let url = format!("{}/v1/{}", addr.trim_end_matches('/'), path.trim_start_matches('/'));
+    let response = client
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .context("Vault request failed")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        anyhow::bail!("Vault returned {}: {}", status, error_text);
+    }
+
+    let body: Value = response.json().await?;
+    let data = body
+        .get("data")
+        .ok_or_else(|| anyhow::anyhow!("Vault response has no data"))?;
+
+    // KV v2 nests the secret under data.data
+    match data.get("data") {
+        Some(inner) if data.get("metadata").is_some() => Ok(inner.clone()),
+        _ => Ok(data.clone()),
+    }
+}
+
+async fn fetch_aws_secret(name: &str, client: &Client) -> Result<String> {
+    let access_key = std::env::var("AWS_ACCESS_KEY_ID").context("AWS_ACCESS_KEY_ID is not set")?;
+    let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").context("AWS_SECRET_ACCESS_KEY is not set")?;
+    let session_token = std::env::var("AWS_SESSION_TOKEN").ok();
+    let region = std::env::var("AWS_REGION")
+        .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+        .unwrap_or_else(|_| "us-east-1".to_string());
+
+    let host = format!("secretsmanager.{}.amazonaws.com", region);
+    let target = "secretsmanager.GetSecretValue";
+    let content_type = "application/x-amz-json-1.1";
+    let body = serde_json::json!({ "SecretId": name }).to_string();
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date_stamp = now.format("%Y%m%d").to_string();
+
+    // SigV4: headers must be listed in sorted order
+    let mut canonical_headers = format!(
+        "content-type:{}\nhost:{}\nx-amz-date:{}\n",
+        content_type, host, amz_date
+    );
+    let mut signed_headers = "content-type;host;x-amz-date".to_string();
+    if let Some(ref token) = session_token {
+        canonical_headers.push_str(&format!("x-amz-security-token:{}\n", token));
+        signed_headers.push_str(";x-amz-security-token");
+    }
+    canonical_headers.push_str(&format!("x-amz-target:{}\n", target));
+    signed_headers.push_str(";x-amz-target");
+
+    let canonical_request = format!(
+        "POST\n/\n\n{}\n{}\n{}",
+        canonical_headers,
+        signed_headers,
+        hex_sha256(body.as_bytes())
+    );
+    let credential_scope = format!("{}/{}/secretsmanager/aws4_request", date_stamp, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        credential_scope,
+        hex_sha256(canonical_request.as_bytes())
+    );
+
+    let k_date = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date_stamp.as_bytes());
+    let k_region = hmac_sha256(&k_date, region.as_bytes());
+    let k_service = hmac_sha256(&k_region, b"secretsmanager");
+    let k_signing = hmac_sha256(&k_service, b"aws4_request");
+    let signature = to_hex(&hmac_sha256(&k_signing, string_to_sign.as_bytes()));
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key, credential_scope, signed_headers, signature
+    );
+
+    let mut request = client
+        .post(format!("https://{}/", host))
+        .header("Content-Type", content_type)
+        .header("X-Amz-Date", &amz_date)
+        .header("X-Amz-Target", target)
+        .header("Authorization", authorization)
+        .body(body);
+    if let Some(token) = session_token {
+        request = request.header("X-Amz-Security-Token", token);
+    }
+
+    let response = request.send().await.context("AWS Secrets Manager request failed")?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        anyhow::bail!("AWS Secrets Manager returned {}: {}", status, error_text);
+    }
+
+    let result: Value = response.json().await?;
+    result
+        .get("SecretString")
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow::anyhow!("Secret {} has no SecretString", name))
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub(crate) fn hex_sha256(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,131 @@
+/*!
+ * Secret Encryption
+ *
+ * Provider credentials may be stored in config files as `enc:v1:<base64>` values
+ * (AES-256-GCM). They are decrypted in memory at startup with the master key from
+ * the `AICLIENT_MASTER_KEY` environment variable.
+ */
+
+use crate::config::Config;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest, Sha256};
+
+pub const MASTER_KEY_ENV: &str = "AICLIENT_MASTER_KEY";
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// Derive the AES key: a base64-encoded 32-byte key is used as-is, anything else is hashed
+pub fn derive_key(master_key: &str) -> [u8; 32] {
+    if let Ok(bytes) = general_purpose::STANDARD.decode(master_key.trim()) {
+        if let Ok(key) = <[u8; 32]>::try_from(bytes.as_slice()) {
+            return key;
+        }
+    }
+    Sha256::digest(master_key.as_bytes()).into()
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+pub fn encrypt(plaintext: &str, key: &[u8; 32]) -> Result<String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, general_purpose::STANDARD.encode(payload)))
+}
+
+pub fn decrypt(value: &str, key: &[u8; 32]) -> Result<String> {
+    let encoded = value
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("Value is not an encrypted secret"))?;
+    let payload = general_purpose::STANDARD
+        .decode(encoded)
+        .context("Failed to decode encrypted secret")?;
+    if payload.len() <= NONCE_LEN {
+        anyhow::bail!("Encrypted secret is truncated");
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt secret (wrong master key?)"))?;
+
+    String::from_utf8(plaintext).context("Decrypted secret is not valid UTF-8")
+}
+
+/// Decrypt a single value in place if it is encrypted
+fn decrypt_in_place(value: &mut String, key: Option<&[u8; 32]>, field: &str) -> Result<()> {
+    if !is_encrypted(value) {
+        return Ok(());
+    }
+    let key = key.ok_or_else(|| {
+        anyhow::anyhow!("{} is encrypted but {} is not set", field, MASTER_KEY_ENV)
+    })?;
+    *value = decrypt(value, key).with_context(|| format!("Failed to decrypt {}", field))?;
+    Ok(())
+}
+
+/// Every config value that may hold a credential, with a field name for diagnostics
+pub fn credential_fields(config: &mut Config) -> Vec<(String, &mut String)> {
+    let mut fields = vec![("required_api_key".to_string(), &mut config.required_api_key)];
+
+    let optional_fields = [
+        (&mut config.admin_api_key, "admin_api_key"),
+        (&mut config.openai_api_key, "openai_api_key"),
+        (&mut config.claude_api_key, "claude_api_key"),
+        (&mut config.gemini_oauth_creds_base64, "gemini_oauth_creds_base64"),
+        (&mut config.kiro_oauth_creds_base64, "kiro_oauth_creds_base64"),
+        (&mut config.redis_url, "redis_url"),
+        (&mut config.end_user_hash_salt, "end_user_hash_salt"),
+    ];
+    for (value, field) in optional_fields {
+        if let Some(value) = value.as_mut() {
+            fields.push((field.to_string(), value));
+        }
+    }
+
+    if let Some(secret) = config.jwt.as_mut().and_then(|jwt| jwt.hs256_secret.as_mut()) {
+        fields.push(("jwt.hs256_secret".to_string(), secret));
+    }
+
+    if let Some(secret) = config.oidc.as_mut().and_then(|oidc| oidc.client_secret.as_mut()) {
+        fields.push(("oidc.client_secret".to_string(), secret));
+    }
+
+    if let Some(signing) = config.request_signing.as_mut() {
+        fields.push(("request_signing.secret".to_string(), &mut signing.secret));
+    }
+
+    for (provider_type, pool) in config.provider_pools.iter_mut() {
+        for provider in pool.iter_mut() {
+            for (name, value) in provider.credentials.iter_mut() {
+                if let serde_json::Value::String(s) = value {
+                    fields.push((format!("{}.{}.{}", provider_type, provider.uuid, name), s));
+                }
+            }
+        }
+    }
+
+    fields
+}
+
+/// Decrypt every encrypted credential in the configuration
+pub fn decrypt_config(config: &mut Config) -> Result<()> {
+    let key = std::env::var(MASTER_KEY_ENV).ok().map(|k| derive_key(&k));
+
+    for (field, value) in credential_fields(config) {
+        decrypt_in_place(value, key.as_ref(), &field)?;
+    }
+
+    Ok(())
+}
@@ -2,32 +2,106 @@
  * HTTP server implementation
  */
 
-use crate::adapter::{create_adapter, ApiServiceAdapter};
+use crate::adapter::ApiServiceAdapter;
+use crate::audit::AuditLog;
+use crate::cluster::SharedStore;
 use crate::common::*;
-use crate::config::Config;
+use crate::concurrency::{ConcurrencyLimits, Outcome, Permit};
+use crate::pacing::RateLimitPacing;
+use crate::conversations::{Conversation, ConversationStore, CONVERSATION_HEADER};
+use crate::convert::{ChatRequest, ChatStream, WARNINGS_FIELD, WARNINGS_HEADER};
+use crate::config::{AliasTarget, CascadeConfig, Config, EnsembleConfig, ModelAlias, ReasoningFilterRule, RequestLimits};
+use crate::dataset::{DatasetRecorder, Recording};
+use crate::transcripts::{Transcript, TranscriptStore};
+use crate::health::ReadinessProbe;
+use crate::jwt_auth::JwtValidator;
+use crate::keys::KeyStore;
+use crate::logger::ConversationLogger;
+use crate::metrics::{Metrics, StreamTimer};
+use crate::duplicate_requests::DuplicateDetector;
+use crate::idempotency::{Claim, IdempotencyStore, IdempotentRequest};
+use crate::model_list_cache::ModelListCache;
+use crate::model_registry::ModelRegistry;
+use crate::oidc::OidcClient;
+use crate::passthrough::ForwardRequest;
+use crate::pool_manager::ProviderPoolManager;
+use crate::provider_registry::{ProviderRegistry, Routing, Upstream};
+use crate::postprocess::PostProcessor;
+use crate::prompt_compression::{CompressionStats, PromptCompressor};
+use crate::rate_limit::RateLimiter;
+use crate::request_context::{end_user_id, format_tags, take_tags, RequestContext};
+use crate::rerank::{parse_scores, RerankRequest};
+use crate::response_cache::{ResponseCache, CACHE_HEADER, STALE_HEADER};
+use crate::stream_recovery::ValueStream;
+use crate::tool_loop::ToolLoop;
+use crate::tool_minification::ToolMinifier;
+use crate::usage_rollup::{Scope, Turn, UsageRollup};
+use crate::web_search::WebSearch;
 use anyhow::Result;
 use axum::{
-    body::Body,
-    extract::{Path, Query, State},
-    http::{HeaderMap, Method, StatusCode},
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, FromRequest, OriginalUri, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::{self, Next},
     response::{IntoResponse, Response, Sse},
     response::sse::Event,
     routing::{get, post},
     Json, Router,
 };
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn, Instrument};
 
 /// Application state
 pub struct AppState {
     pub config: Config,
-    pub adapter: Box<dyn ApiServiceAdapter>,
+    /// Providers, model routes and aliases; swapped by the admin API, config
+    /// reloads and refreshes of externally referenced credentials
+    pub providers: ProviderRegistry,
+    pub metrics: Metrics,
+    pub pool_manager: ProviderPoolManager,
+    pub audit: AuditLog,
+    pub key_store: KeyStore,
+    /// Cross-instance state (in-memory unless cluster mode is enabled)
+    pub shared_state: Arc<dyn SharedStore>,
+    pub rate_limiter: Option<Arc<dyn RateLimiter>>,
+    pub jwt_validator: Option<JwtValidator>,
+    pub oidc: Option<OidcClient>,
+    pub post_processor: Option<Arc<PostProcessor>>,
+    pub prompt_compressor: Option<PromptCompressor>,
+    pub tool_minifier: Option<ToolMinifier>,
+    pub concurrency: Option<ConcurrencyLimits>,
+    pub pacing: Option<RateLimitPacing>,
+    pub tool_loop: Option<ToolLoop>,
+    pub web_search: Option<WebSearch>,
+    pub model_registry: ModelRegistry,
+    /// Prompt/response log (`prompt_log_mode`), redacted per the `logging` config
+    pub prompt_log: ConversationLogger,
+    pub conversations: Option<ConversationStore>,
+    pub response_cache: Option<ResponseCache>,
+    pub model_list_cache: Option<ModelListCache>,
+    pub duplicates: Option<DuplicateDetector>,
+    pub idempotency: Option<IdempotencyStore>,
+    pub usage_rollup: Option<UsageRollup>,
+    pub dataset: Option<Arc<DatasetRecorder>>,
+    pub readiness: ReadinessProbe,
+    pub transcripts: Option<TranscriptStore>,
+}
+
+impl AppState {
+    /// The adapter of the default provider
+    pub async fn current_adapter(&self) -> Arc<dyn ApiServiceAdapter> {
+        self.providers.snapshot().await.default_upstream().adapter
+    }
 }
 
 /// Start the HTTP server
@@ -36,175 +110,1931 @@ pub async fn start_server(config: Config) -> Result<()> {
     let port = config.port;
     let addr = format!("{}:{}", host, port);
 
-    // Create adapter
-    let provider = ModelProvider::from_str(&config.model_provider)
-        .ok_or_else(|| anyhow::anyhow!("Invalid model provider: {}", config.model_provider))?;
-    let adapter = create_adapter(provider, &config).await?;
+    // Resolve vault:// and aws-sm:// credential references, keeping the raw config for refreshes
+    let raw_config = config.clone();
+    let mut config = config;
+    let secrets_client = reqwest::Client::new();
+    crate::secret_refs::resolve_config(&mut config, &secrets_client).await?;
+
+    crate::retry_budget::configure(&config.retry_budget);
+    crate::log_redaction::configure(&config.logging);
+    if let Some(ref alerts) = config.alerts {
+        crate::alerts::configure(alerts, crate::http_client::shared(&config.http_client)?);
+    }
+
+    // Create adapters for the default provider and every routed one
+    let routing = Routing::build(&config).await?;
+    if config.http_client.prewarm {
+        let client = crate::http_client::shared(&config.http_client)?;
+        let upstreams: Vec<String> = routing.adapters.values().filter_map(|adapter| adapter.upstream_url()).collect();
+        tokio::spawn(async move {
+            crate::http_client::prewarm(&client, &upstreams).await;
+        });
+    }
+    let pool_manager = ProviderPoolManager::new(config.provider_pools.clone(), &config.key_health);
+    let audit = AuditLog::open(config.audit_log_file_path.clone()).await?;
+    let key_store = KeyStore::open(config.client_keys_file_path.clone()).await?;
+    let redis = match config.redis_url.as_deref() {
+        Some(url) => Some(crate::cluster::redis_connection(url).await?),
+        None => None,
+    };
+    let shared_state = crate::cluster::open_store(&config, redis.clone());
+    let rate_limiter = crate::rate_limit::build_limiter(&config, redis)?;
+    let post_processor = config
+        .post_processing
+        .as_ref()
+        .map(PostProcessor::new)
+        .transpose()?
+        .map(Arc::new);
+    let prompt_compressor = config.prompt_compression.as_ref().map(PromptCompressor::new).transpose()?;
+    let tool_minifier = config.tool_minification.as_ref().map(ToolMinifier::new);
+
+    let tool_loop = match config.tool_loop {
+        Some(ref tool_loop) => Some(ToolLoop::new(tool_loop, crate::http_client::shared(&config.http_client)?)),
+        None => None,
+    };
+    let web_search = match config.web_search {
+        Some(ref web_search) => Some(WebSearch::new(web_search, crate::http_client::shared(&config.http_client)?)),
+        None => None,
+    };
+    let conversations = config
+        .conversations
+        .as_ref()
+        .map(|conversations| ConversationStore::new(shared_state.clone(), conversations));
+    let response_cache = config
+        .response_cache
+        .as_ref()
+        .map(|response_cache| ResponseCache::new(shared_state.clone(), response_cache));
+    let model_list_cache = config
+        .model_list_cache
+        .as_ref()
+        .map(|model_list_cache| ModelListCache::new(shared_state.clone(), model_list_cache));
+    let duplicates = config
+        .duplicate_requests
+        .as_ref()
+        .map(|duplicates| DuplicateDetector::new(shared_state.clone(), duplicates));
+    let idempotency = config
+        .idempotency
+        .as_ref()
+        .map(|idempotency| IdempotencyStore::new(shared_state.clone(), idempotency));
+    let usage_rollup = config
+        .usage_rollup
+        .as_ref()
+        .map(|usage_rollup| UsageRollup::new(shared_state.clone(), usage_rollup));
+    let dataset = match config.dataset {
+        Some(ref dataset) => {
+            let recorder = Arc::new(DatasetRecorder::new(dataset, crate::http_client::shared(&config.http_client)?));
+            recorder.spawn_flusher();
+            Some(recorder)
+        }
+        None => None,
+    };
 
     // Create application state
     let state = Arc::new(AppState { 
         config: config.clone(),
-        adapter,
+        providers: ProviderRegistry::new(routing),
+        metrics: Metrics::new(),
+        pool_manager,
+        audit,
+        key_store,
+        shared_state,
+        rate_limiter,
+        jwt_validator: config.jwt.clone().map(JwtValidator::new),
+        oidc: config.oidc.clone().map(OidcClient::new),
+        post_processor,
+        prompt_compressor,
+        tool_minifier,
+        concurrency: config
+            .adaptive_concurrency
+            .enabled
+            .then(|| ConcurrencyLimits::new(&config.adaptive_concurrency)),
+        pacing: config
+            .rate_limit_pacing
+            .enabled
+            .then(|| RateLimitPacing::new(&config.rate_limit_pacing)),
+        tool_loop,
+        web_search,
+        model_registry: ModelRegistry::new(&config.model_registry),
+        prompt_log: ConversationLogger::new(&config.prompt_log_mode, &config.prompt_log_base_name),
+        conversations,
+        response_cache,
+        model_list_cache,
+        duplicates,
+        idempotency,
+        usage_rollup,
+        dataset,
+        readiness: ReadinessProbe::new(&config.readiness, crate::http_client::shared(&config.http_client)?),
+        transcripts: config.transcripts.as_ref().map(TranscriptStore::new),
     });
     let state_clone = state.clone();
 
-    // Build CORS layer
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
-        .allow_headers(Any);
+    // Hold readiness until the tokenizers and model lists are loaded
+    if config.readiness.warm_up {
+        let warm_state = state.clone();
+        tokio::spawn(async move {
+            let routing = warm_state.providers.snapshot().await;
+            crate::health::warm_up(&routing).await;
+            warm_state.readiness.finish_warm_up();
+        });
+    }
+
+    // Periodically pick up keys changed through the CLI and persist last-used timestamps
+    let flush_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = flush_state.key_store.reload_if_changed().await {
+                error!("Failed to reload client keys: {}", e);
+            }
+            if let Err(e) = flush_state.key_store.flush_if_dirty().await {
+                error!("Failed to persist client keys: {}", e);
+            }
+        }
+    });
+
+    // Probe quarantined pool keys once their backoff is over
+    if !config.provider_pools.is_empty() {
+        let probe_state = state.clone();
+        let client = crate::http_client::shared(&config.http_client)?;
+        let period = std::time::Duration::from_secs(config.key_health.probe_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                for (provider_type, uuid, healthy) in probe_state.pool_manager.probe_quarantined(&client).await {
+                    let store = probe_state.shared_state.as_ref();
+                    if let Err(e) = crate::cluster::publish_provider_health(store, &provider_type, &uuid, healthy).await {
+                        warn!("Failed to publish health of key {}: {}", uuid, e);
+                    }
+                }
+            }
+        });
+    }
+
+    // In cluster mode, pick up provider health changes made by other instances
+    if config.redis_url.is_some() {
+        let sync_state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                if let Err(e) = crate::cluster::sync_provider_health(
+                    sync_state.shared_state.as_ref(),
+                    &sync_state.pool_manager,
+                )
+                .await
+                {
+                    warn!("Failed to sync provider health from cluster: {}", e);
+                }
+            }
+        });
+    }
+
+    // Periodically re-fetch external provider credentials; on a change the pool keys take
+    // the new values and the adapters are rebuilt
+    if config.secret_refresh_interval_secs > 0 && crate::secret_refs::has_references(&raw_config) {
+        let refresh_state = state.clone();
+        let period = std::time::Duration::from_secs(config.secret_refresh_interval_secs);
+        let mut current = config.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let mut refreshed = raw_config.clone();
+                if let Err(e) = crate::secret_refs::resolve_config(&mut refreshed, &secrets_client).await {
+                    warn!("Secret refresh failed, keeping current credentials: {:#}", e);
+                    continue;
+                }
+                if serde_json::to_value(&refreshed).ok() == serde_json::to_value(&current).ok() {
+                    continue;
+                }
+                refresh_state.pool_manager.update_credentials(&refreshed.provider_pools).await;
+                match refresh_state.providers.snapshot().await.rebuild(&refreshed).await {
+                    Ok(routing) => {
+                        refresh_state.providers.replace(routing).await;
+                        info!("Provider credentials changed; adapters rebuilt");
+                        current = refreshed;
+                    }
+                    Err(e) => error!("Failed to rebuild adapters with refreshed credentials: {}", e),
+                }
+            }
+        });
+    }
+
+    // SIGHUP reloads providers, routes and aliases from the configuration, like `POST /admin/reload`
+    #[cfg(unix)]
+    {
+        let reload_state = state.clone();
+        tokio::spawn(async move {
+            let Ok(mut hangup) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) else {
+                return;
+            };
+            while hangup.recv().await.is_some() {
+                match Routing::reload().await {
+                    Ok(routing) => {
+                        reload_state.providers.replace(routing).await;
+                        info!("Configuration reloaded on SIGHUP: providers, routes and aliases replaced");
+                    }
+                    Err(e) => error!("Reload failed, keeping current routing: {:#}", e),
+                }
+            }
+        });
+    }
+
+    let app = with_layers(&state, main_routes(&state));
+
+    // Create TCP listeners; each extra one speaks a single protocol
+    let listener = TcpListener::bind(&addr).await?;
+    let mut protocol_listeners = Vec::new();
+    for listener_config in &state.config.listeners {
+        let protocol = ModelProtocol::from_str(&listener_config.protocol)
+            .ok_or_else(|| anyhow::anyhow!("Unknown listener protocol: {}", listener_config.protocol))?;
+        let addr = format!("{}:{}", listener_config.host.as_deref().unwrap_or(&host), listener_config.port);
+        let listener = TcpListener::bind(&addr).await?;
+        protocol_listeners.push((addr, protocol, listener, with_layers(&state, listener_routes(&state, protocol))));
+    }
+
+    info!("--- Unified API Server Configuration ---");
+    info!("  Host: {}", host);
+    info!("  Port: {}", port);
+    info!("  Primary Model Provider: {}", state_clone.config.model_provider);
+    info!("------------------------------------------");
+    info!("\nUnified API Server running on http://{}", addr);
+    info!("Supports multiple API formats:");
+    info!("  • OpenAI-compatible: /v1/chat/completions, /v1/models");
+    info!("  • Gemini-compatible: /v1beta/models, /v1beta/models/{{model}}:generateContent, /v1beta/models/{{model}}:streamGenerateContent");
+    info!("  • Claude-compatible: /v1/messages");
+    info!("  • Token count: /v1/token-count");
+    info!("  • Rerank: /v1/rerank");
+    info!("  • Single-protocol prefixes: /openai/v1/..., /claude/v1/..., /gemini/v1beta/...");
+    info!("  • Files API passthrough: /v1/files");
+    info!("  • Fine-tuning passthrough: /v1/fine_tuning/jobs");
+    info!("  • Health check: /health");
+    info!("  • Stats summary: /stats");
+    info!("  • API docs: /docs (OpenAPI document: /openapi.json)");
+    info!("  • Admin API: /admin/providers, /admin/routing, /admin/reload, /admin/cache/clear, /admin/keys, /admin/audit");
+    if state_clone.oidc.is_some() {
+        info!("  • Admin login (OIDC): /admin/login");
+    }
+
+    for (addr, protocol, listener, app) in protocol_listeners {
+        info!("  • {} API only: http://{}", protocol.as_str(), addr);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("Listener {} stopped: {}", addr, e);
+            }
+        });
+    }
+
+    // Start serving
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// Client API routes of one protocol
+fn protocol_routes(protocol: ModelProtocol) -> Router<Arc<AppState>> {
+    match protocol {
+        ModelProtocol::OpenAI => Router::new()
+            .route("/v1/chat/completions", post(openai_chat_handler))
+            .route("/v1/models", get(openai_models_handler))
+            .route("/v1/models/:model", get(openai_model_handler))
+            .route("/v1/files", get(files_handler).post(files_handler))
+            .route("/v1/files/:file_id", get(files_handler).delete(files_handler))
+            .route("/v1/files/:file_id/content", get(files_handler))
+            .route("/v1/fine_tuning/jobs", get(fine_tuning_handler).post(fine_tuning_handler))
+            .route("/v1/fine_tuning/jobs/:job_id", get(fine_tuning_handler))
+            .route("/v1/fine_tuning/jobs/:job_id/events", get(fine_tuning_handler))
+            .route("/v1/fine_tuning/jobs/:job_id/checkpoints", get(fine_tuning_handler))
+            .route("/v1/fine_tuning/jobs/:job_id/cancel", post(fine_tuning_handler))
+            .route("/v1/fine_tuning/jobs/:job_id/pause", post(fine_tuning_handler))
+            .route("/v1/fine_tuning/jobs/:job_id/resume", post(fine_tuning_handler))
+            .route("/:provider/v1/chat/completions", post(openai_chat_handler))
+            .route("/:provider/v1/models", get(openai_models_handler)),
+        // Anthropic SDKs list models at the same path as OpenAI's, in their own format
+        ModelProtocol::Claude => claude_message_routes()
+            .route("/v1/models", get(claude_models_handler))
+            .route("/:provider/v1/models", get(claude_models_handler)),
+        ModelProtocol::Gemini => Router::new()
+            .route("/v1beta/models", get(gemini_models_handler))
+            .route("/v1beta/models/:model_action", post(gemini_content_handler)),
+    }
+}
+
+fn claude_message_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/v1/messages", post(claude_messages_handler))
+        .route("/:provider/v1/messages", post(claude_messages_handler))
+}
+
+/// Request ids, transcripts, request signing and rate-limit headers apply to every client API route
+fn client_api(state: &Arc<AppState>, routes: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+    routes
+        .route_layer(middleware::from_fn_with_state(state.clone(), verify_request_signature))
+        .route_layer(middleware::from_fn(rate_limit_headers))
+        .route_layer(middleware::from_fn_with_state(state.clone(), keep_transcript))
+        .route_layer(middleware::from_fn(request_ids))
+}
+
+/// The main port: every protocol (`/v1/models` in OpenAI form) plus protocol path prefixes,
+/// proxy endpoints, admin API and docs
+fn main_routes(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let api = protocol_routes(ModelProtocol::OpenAI)
+        .merge(claude_message_routes())
+        .merge(protocol_routes(ModelProtocol::Gemini))
+        .route("/v1/token-count", post(token_count_handler))
+        .route("/v1/rerank", post(rerank_handler))
+        .route("/v1/usage", get(usage_rollup_handler));
+
+    let mut routes = Router::new()
+        .route("/health", get(health_handler))
+        .route("/livez", get(liveness_handler))
+        .route("/readyz", get(readiness_handler))
+        .route("/stats", get(stats_handler))
+        .merge(client_api(state, api));
+    // `/openai/v1/...`, `/claude/v1/...`, `/gemini/v1beta/...`: one protocol per prefix, as on a dedicated listener
+    for protocol in [ModelProtocol::OpenAI, ModelProtocol::Claude, ModelProtocol::Gemini] {
+        routes = routes.nest(&format!("/{}", protocol.as_str()), client_api(state, protocol_routes(protocol)));
+    }
+
+    routes
+        .merge(crate::admin::routes())
+        .merge(utoipa_swagger_ui::SwaggerUi::new("/docs").url("/openapi.json", crate::openapi::document()))
+}
+
+/// A port dedicated to one protocol, for native SDKs pointed at it unmodified
+fn listener_routes(state: &Arc<AppState>, protocol: ModelProtocol) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/livez", get(liveness_handler))
+        .route("/readyz", get(readiness_handler))
+        .merge(client_api(state, protocol_routes(protocol)))
+}
+
+/// Request tracking, compression and CORS around a listener's routes
+fn with_layers(state: &Arc<AppState>, routes: Router<Arc<AppState>>) -> Router {
+    let config = &state.config;
+    // Build CORS layer
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
+        .allow_headers(Any)
+        .expose_headers([
+            header::HeaderName::from_static(WARNINGS_HEADER),
+            header::HeaderName::from_static(CACHE_HEADER),
+            header::HeaderName::from_static(STALE_HEADER),
+            header::HeaderName::from_static(crate::ensemble::MEMBER_HEADER),
+            header::HeaderName::from_static(crate::cascade::MODEL_HEADER),
+            header::HeaderName::from_static(crate::aliases::MODEL_HEADER),
+            header::HeaderName::from_static(crate::idempotency::REPLAYED_HEADER),
+            header::HeaderName::from_static(crate::request_id::REQUEST_ID_HEADER),
+            header::HeaderName::from_static(crate::request_id::UPSTREAM_REQUEST_ID_HEADER),
+        ]);
+
+    // SSE is never compressed: the encoder buffers output, which would hold back streamed events
+    let compression = CompressionLayer::new()
+        .gzip(config.response_compression)
+        .br(config.response_compression)
+        .compress_when(
+            SizeAbove::new(config.compression_min_size)
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::IMAGES)
+                .and(NotForContentType::SSE),
+        );
+
+    routes
+        .layer(middleware::from_fn_with_state(state.clone(), track_requests))
+        .with_state(state.clone())
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(compression)
+        .layer(cors)
+}
+
+/// Health check handler
+async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(json!({
+        "status": "healthy",
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "provider": state.config.model_provider,
+        "shared_state": state.shared_state.backend()
+    }))
+}
+
+/// Liveness probe: the process is up and serving requests
+async fn liveness_handler() -> impl IntoResponse {
+    Json(json!({"status": "alive"}))
+}
+
+/// Readiness probe: 503 while the instance should not receive traffic
+async fn readiness_handler(State(state): State<Arc<AppState>>) -> Response {
+    let routing = state.providers.snapshot().await;
+    let (ready, checks) = state.readiness.check(&routing).await;
+    let (status, label) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+    (status, Json(json!({"status": label, "checks": checks}))).into_response()
+}
+
+/// Scope required for chat/content generation endpoints
+pub const SCOPE_CHAT: &str = "chat";
+/// Scope required for model listing endpoints
+pub const SCOPE_MODELS: &str = "models";
+/// Scope required for the Files API
+pub const SCOPE_FILES: &str = "files";
+/// Scope required for fine-tuning jobs
+pub const SCOPE_FINE_TUNING: &str = "fine_tuning";
+
+/// Who is calling, as established by `authorize_client`
+pub struct ClientIdentity {
+    /// Key for rate limits and quotas
+    pub id: String,
+    pub tenant: Option<String>,
+    /// `None` means every model is allowed
+    pub allowed_models: Option<Vec<String>>,
+    /// Opted out of content logging (a client key setting)
+    pub no_content_logging: bool,
+    /// Request size limits of the client key, over `request_limits`
+    pub request_limits: Option<RequestLimits>,
+}
+
+impl ClientIdentity {
+    fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            tenant: None,
+            allowed_models: None,
+            no_content_logging: false,
+            request_limits: None,
+        }
+    }
+
+    /// Whether the client may call a model or alias; the allowlist may use `prefix*` patterns
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.allowed_models
+            .as_ref()
+            .map(|models| crate::keys::allows_model(models, model))
+            .unwrap_or(true)
+    }
+
+    /// 403 listing the models the client may call when `model` is not among them
+    pub fn check_model(&self, model: &str) -> Result<(), AppError> {
+        match self.allowed_models {
+            Some(ref allowed) if !self.allows_model(model) => Err(AppError::ModelNotAllowed {
+                model: model.to_string(),
+                allowed: allowed.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop models the client may not use from a listing
+    pub fn filter_models(&self, models: &mut ModelListResponse) {
+        for list in [models.data.as_mut(), models.models.as_mut()].into_iter().flatten() {
+            list.retain(|m| m.model_id().map(|id| self.allows_model(id)).unwrap_or(false));
+        }
+    }
+}
+
+/// Check client credentials against the static API key, client JWTs and the client key store
+async fn authorize_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+    scope: &str,
+) -> Result<ClientIdentity, AppError> {
+    let auth_header = headers.get("authorization").and_then(|v| v.to_str().ok());
+    let api_key_header = headers.get("x-api-key").and_then(|v| v.to_str().ok());
+    let goog_api_key = headers.get("x-goog-api-key").and_then(|v| v.to_str().ok());
+    let query_key = params.get("key").map(|s| s.as_str());
+
+    if is_authorized(
+        auth_header,
+        api_key_header,
+        goog_api_key,
+        query_key,
+        &state.config.required_api_key,
+    ) {
+        let identity = ClientIdentity::new("static");
+        check_rate_limit(state, &identity.id).await?;
+        return Ok(identity);
+    }
+
+    let presented = auth_header
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or(api_key_header)
+        .or(goog_api_key)
+        .or(query_key)
+        .ok_or(AppError::Unauthorized)?;
+
+    if let Some(ref validator) = state.jwt_validator {
+        if crate::jwt_auth::looks_like_jwt(presented) {
+            let claims = validator.validate(presented).await.map_err(|e| {
+                debug!("Rejected client JWT: {:#}", e);
+                AppError::Unauthorized
+            })?;
+            if !claims.allows(scope) {
+                return Err(AppError::Forbidden(format!("Token does not have the '{}' scope.", scope)));
+            }
+
+            let identity = ClientIdentity {
+                id: match claims.tenant {
+                    Some(ref tenant) => format!("tenant:{}", tenant),
+                    None => format!("jwt:{}", claims.subject),
+                },
+                allowed_models: claims.allowed_models.or_else(|| tenant_models(state, claims.tenant.as_deref())),
+                tenant: claims.tenant,
+                no_content_logging: false,
+                request_limits: None,
+            };
+            check_rate_limit(state, &identity.id).await?;
+            if let Some(quota) = claims.quota {
+                check_daily_quota(state, &identity.id, quota).await?;
+            }
+            return Ok(identity);
+        }
+    }
+
+    match state.key_store.authenticate(presented).await {
+        Some(client_key) if client_key.allows(scope) => {
+            let mut identity = ClientIdentity::new(format!("key:{}", client_key.id));
+            identity.no_content_logging = client_key.no_content_logging;
+            identity.allowed_models = client_key.models.or_else(|| tenant_models(state, client_key.tenant.as_deref()));
+            identity.tenant = client_key.tenant;
+            identity.request_limits = client_key.limits;
+            check_rate_limit(state, &identity.id).await?;
+            Ok(identity)
+        }
+        Some(_) => Err(AppError::Forbidden(format!(
+            "API key does not have the '{}' scope.",
+            scope
+        ))),
+        None => Err(AppError::Unauthorized),
+    }
+}
+
+/// The `tenant_models` allowlist of a tenant, if it has one
+fn tenant_models(state: &AppState, tenant: Option<&str>) -> Option<Vec<String>> {
+    tenant.and_then(|tenant| state.config.tenant_models.get(tenant)).cloned()
+}
+
+/// Shared store counter of a client's requests on the day of `now`
+pub(crate) fn daily_quota_key(client_id: &str, now: chrono::DateTime<chrono::Utc>) -> String {
+    format!("quota:{}:{}", client_id, now.format("%Y%m%d"))
+}
+
+/// Count the request against a per-day quota shared across the cluster
+async fn check_daily_quota(state: &AppState, client_id: &str, quota: u64) -> Result<(), AppError> {
+    let now = chrono::Utc::now();
+    let tomorrow = (now + chrono::Duration::days(1))
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is valid")
+        .and_utc();
+    let until_reset = (tomorrow - now).to_std().unwrap_or_default();
+
+    let key = daily_quota_key(client_id, now);
+    match state.shared_state.incr(&key, 1, until_reset).await {
+        Ok(used) => {
+            crate::alerts::quota_used(client_id, used as u64, quota);
+            if used as u64 > quota {
+                return Err(AppError::TooManyRequests {
+                    message: format!("Daily quota of {} requests exceeded.", quota),
+                    retry_after_secs: until_reset.as_secs(),
+                });
+            }
+            Ok(())
+        }
+        Err(e) => {
+            warn!("Quota store unavailable, allowing request: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Enforce the per-client request limit; limiter backend failures let the request through
+async fn check_rate_limit(state: &AppState, client_id: &str) -> Result<(), AppError> {
+    let Some(ref limiter) = state.rate_limiter else {
+        return Ok(());
+    };
+
+    let window = std::time::Duration::from_secs(state.config.rate_limit_window_secs);
+    let decision = limiter.check(client_id, state.config.rate_limit_requests, window).await;
+    if let Ok(ref decision) = decision {
+        crate::rate_limit::record_decision(decision, window);
+    }
+    match decision {
+        Ok(decision) if !decision.allowed => Err(AppError::TooManyRequests {
+            message: format!(
+                "Rate limit of {} requests per {}s exceeded.",
+                decision.limit, state.config.rate_limit_window_secs
+            ),
+            retry_after_secs: decision.retry_after.as_secs_f64().ceil() as u64,
+        }),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            warn!("Rate limiter unavailable, allowing request: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Stats summary handler
+async fn stats_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut stats = state.metrics.snapshot();
+    stats["retries_denied"] = json!(crate::retry_budget::global().denied());
+    if let Some(ref limits) = state.concurrency {
+        stats["concurrency"] = limits.snapshot();
+    }
+    if let Some(ref pacing) = state.pacing {
+        stats["pacing"] = pacing.snapshot();
+    }
+    Json(stats)
+}
+
+/// Reject client API requests without a valid HMAC signature when signing is required
+async fn verify_request_signature(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(ref signing) = state.config.request_signing else {
+        return Ok(next.run(request).await);
+    };
+
+    let (parts, body) = request.into_parts();
+    // Held to the same limit as an unsigned body
+    let bytes = axum::body::to_bytes(body, state.config.max_body_bytes)
+        .await
+        .map_err(|_| AppError::PayloadTooLarge("Request body is too large.".to_string()))?;
+
+    let header = |name: &str| parts.headers.get(name).and_then(|v| v.to_str().ok());
+    let timestamp = header(&signing.timestamp_header);
+    let signature = header(&signing.signature_header);
+    let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+    crate::request_signing::verify(
+        signing,
+        timestamp,
+        signature,
+        parts.method.as_str(),
+        path,
+        &bytes,
+        chrono::Utc::now().timestamp(),
+    )
+    .map_err(|e| AppError::Forbidden(e.to_string()))?;
+
+    // A valid signature may only be used once within the replay window
+    let window = std::time::Duration::from_secs(signing.replay_window_secs);
+    let replay_key = format!("signature:{}", signature.unwrap_or_default());
+    match state.shared_state.set_if_absent(&replay_key, "1", window).await {
+        Ok(true) => {}
+        Ok(false) => return Err(AppError::Forbidden("Request signature was already used.".to_string())),
+        Err(e) => warn!("Replay store unavailable, skipping replay check: {}", e),
+    }
+
+    Ok(next.run(Request::from_parts(parts, axum::body::Body::from(bytes))).await)
+}
+
+/// Attach the `x-ratelimit-*` headers gathered while handling a client API request
+async fn rate_limit_headers(request: Request, next: Next) -> Response {
+    let (mut response, headers) = crate::rate_limit::scope(next.run(request)).await;
+    response.headers_mut().extend(headers);
+    response
+}
+
+/// Give the request an id (the client's own when it sent one) for the handlers, logs and
+/// response, and return the upstream's id for the call alongside it
+async fn request_ids(mut request: Request, next: Next) -> Response {
+    let id = crate::request_id::from_headers_or_new(request.headers());
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        request.headers_mut().insert(crate::request_id::REQUEST_ID_HEADER, value);
+    }
+    let span = tracing::info_span!("request", id = %id);
+    let (mut response, upstream) = crate::request_id::scope(next.run(request).instrument(span)).await;
+    if let Some(upstream) = &upstream {
+        info!("Request {} was upstream request {}", id, upstream);
+    }
+    crate::request_id::apply(response.headers_mut(), &id, upstream.as_deref());
+    response
+}
+
+/// With transcripts configured, keep the request's bodies, warnings and timings
+async fn keep_transcript(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let Some(store) = &state.transcripts else {
+        return next.run(request).await;
+    };
+    let started = Instant::now();
+    let started_at = chrono::Utc::now();
+    let id = crate::request_id::from_headers(request.headers()).unwrap_or_default();
+    let (method, path) = (request.method().to_string(), request.uri().path().to_string());
+
+    // Handlers note the client body after authentication; without one there is nothing to keep
+    let (response, capture) = crate::transcripts::scope(next.run(request)).await;
+    if !capture.skipped && capture.request.is_some() {
+        let warnings = response
+            .headers()
+            .get_all(WARNINGS_HEADER)
+            .iter()
+            .filter_map(|v| v.to_str().ok().map(String::from))
+            .collect();
+        store.push(Transcript {
+            id,
+            client_id: capture.client_id,
+            method,
+            path,
+            started_at,
+            status: response.status().as_u16(),
+            request: capture.request,
+            upstream_request: capture.upstream_request,
+            upstream_response: capture.upstream_response,
+            warnings,
+            upstream_ms: capture.upstream_ms,
+            total_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+    response
+}
+
+/// Count every request and its outcome for the stats summary
+async fn track_requests(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Sampled-out requests only log warnings and errors
+    let rate = crate::log_redaction::sample_rate(request.uri().path(), &state.config.logging.sample_rates);
+    let response = crate::log_redaction::scope(crate::log_redaction::draw(rate), next.run(request)).await;
+    state.metrics.record_request(response.status().as_u16());
+    response
+}
+
+/// OpenAI chat completions handler
+async fn openai_chat_handler(
+    State(state): State<Arc<AppState>>,
+    provider_path: Option<Path<String>>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    JsonBody(body): JsonBody,
+) -> Result<Response, AppError> {
+    let identity = authorize_client(&state, &headers, &params, SCOPE_CHAT).await?;
+    crate::transcripts::record_client_request(&identity.id, &body);
+    let idempotent = idempotent_request(&state, &identity, &headers, ModelProtocol::OpenAI, &body)?;
+    let handle = openai_chat(state.clone(), identity, provider_path, uri, headers, body);
+    with_idempotency(&state, idempotent, handle).await
+}
+
+async fn openai_chat(
+    state: Arc<AppState>,
+    identity: ClientIdentity,
+    provider_path: Option<Path<String>>,
+    uri: Uri,
+    headers: HeaderMap,
+    mut body: Value,
+) -> Result<Response, AppError> {
+    info!("Received OpenAI chat request");
+
+    let model = body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| AppError::BadRequest("model is required".to_string()))?
+        .to_string();
+    identity.check_model(&model)?;
+    check_request_limits(&state, &identity, &body)?;
+    let routing = state.providers.snapshot().await;
+    let (model, alias_provider) = resolve_alias(&routing, model, &mut body);
+    if let Some(virtual_model) = VirtualModel::find(&state.config, &routing, &model) {
+        return serve_virtual_model(&state, &identity, &headers, uri.path(), virtual_model, ModelProtocol::OpenAI, body).await;
+    }
+    let provider_path = provider_path.or(alias_provider.map(Path));
+    let conversation = begin_conversation(&state, &identity, &headers, &mut body).await?;
+    let model = route_by_capability(&state, &identity, model, &mut body)?;
+    let compression = compress_prompt(&state, &routing, &model, &mut body).await;
+    minify_tools(&state, &model, ModelProtocol::OpenAI, &mut body);
+    check_model_limits(&state, &model, &mut body)?;
+    let upstream = select_upstream(&routing, &model, &body, provider_path)?;
+    let ctx = request_context(&state, &headers, &mut body)
+        .with_no_content_logging(identity.no_content_logging)
+        .with_pool_key(state.pool_manager.select_key(&upstream.provider).await)
+        .with_timeout(request_timeout(&state, &headers, uri.path(), &upstream.provider)?);
+    log_prompt(&state, &ctx, "input", crate::logger::extract_prompt_from_request(&body, "openai")).await;
+    let reasoning_rule = crate::reasoning::rule_for(&state.config.reasoning_filters, &model).cloned();
+    let stream = body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    // The adapter converts the request into its own protocol once, on the way out
+    let adapter = upstream.adapter.clone();
+    let backend = adapter.protocol();
+    let mut request = ChatRequest::OpenAI(body);
+    let warnings = conversion_warnings(request.body(), ModelProtocol::OpenAI, backend);
+
+    let cache = CacheContext {
+        key: response_cache_key(&state, &headers, ModelProtocol::OpenAI, request.body()),
+        protocol: ModelProtocol::OpenAI,
+        stream,
+    };
+    if let Some(hit) = cached_response(&state, cache.key.as_deref()).await {
+        return Ok(serve_cached(&state, &ctx, conversation, &cache, hit, &warnings, "hit").await);
+    }
+    if let Some(hit) = check_duplicate(&state, &identity, ModelProtocol::OpenAI, request.body()).await? {
+        return Ok(serve_cached(&state, &ctx, conversation, &cache, hit, &warnings, "hit").await);
+    }
+    let recording = begin_recording(&state, &identity, &ctx, &headers, ModelProtocol::OpenAI, &model, request.body());
+    let usage = usage_scopes(&state, &identity, &headers, &ctx);
+
+    // Web search and providers that cannot stream answer in one piece, replayed to a streaming client
+    let simulate = stream
+        && (streams_buffered(&state, &upstream) || (state.web_search.is_some() && crate::web_search::requested(request.body())));
+    let mut permit = acquire_upstream(&state, &upstream).await?;
+    if stream && !simulate {
+        let started = Instant::now();
+        let resume_attempts = resume_attempts(&state, &upstream);
+        let resume_body = (resume_attempts > 0).then(|| request.body().clone());
+
+        // Nothing to rewrite: relay the upstream bytes without parsing them
+        let passthrough = backend == ModelProtocol::OpenAI && adapter.supports_stream_passthrough();
+        let rewrites = reasoning_rule.is_some() || state.post_processor.is_some() || compression.is_some();
+        if passthrough && !rewrites && conversation.is_none() && recording.is_none() && usage.is_none() {
+            let result = adapter.generate_content_stream_raw(&model, request, &ctx).await;
+            record_upstream(&state, &upstream, &ctx, &mut permit, started, &result).await;
+            let bytes = match result {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("Failed to start streaming: {}", e);
+                    return upstream_failed(&state, &ctx, &identity, None, &cache, &warnings, e).await;
+                }
+            };
+            return Ok(passthrough_stream(&state, &ctx, Box::pin(crate::concurrency::hold(bytes, permit))));
+        }
+
+        let result = adapter.generate_content_stream(&model, request, &ctx).await;
+        record_upstream(&state, &upstream, &ctx, &mut permit, started, &result).await;
+        let result = result.and_then(|stream| match resume_body {
+            // Resumed in the backend's Claude events, then converted for the client
+            Some(resume_body) => {
+                let stream = stream.into_protocol(ModelProtocol::Claude, Some(&model))?;
+                let stream = resumable_stream(adapter, &model, &ctx, ModelProtocol::OpenAI, resume_body, resume_attempts, stream);
+                ChatStream::Claude(stream).into_protocol(ModelProtocol::OpenAI, Some(&model))
+            }
+            None => stream.into_protocol(ModelProtocol::OpenAI, Some(&model)),
+        });
+        let stream = match result {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("Failed to start streaming: {}", e);
+                return upstream_failed(&state, &ctx, &identity, conversation, &cache, &warnings, e).await;
+            }
+        };
+        let stream = record_stream_usage(&state, usage, &upstream, &model, started, crate::stream_recovery::salvage(stream));
+        let stream = process_stream(&state, &ctx, &upstream, model, started, reasoning_rule, stream);
+        let stream = with_compression_stats(stream, compression);
+        let stream = record_conversation(&state, conversation, ModelProtocol::OpenAI, stream);
+        let stream = record_dataset(recording, stream);
+        let stream = crate::concurrency::hold(stream, permit);
+        return Ok(with_warnings(openai_sse(&state, &ctx, stream), &warnings));
+    }
+
+    if simulate {
+        without_stream(request.body_mut());
+    }
+    let started = Instant::now();
+    let (adapter, model_ref, ctx_ref) = (&adapter, &model, &ctx);
+    let call = |body| async move {
+        let request = ChatRequest::OpenAI(body);
+        let response = adapter.generate_content(model_ref, request, ctx_ref).await?;
+        response.into_protocol(ModelProtocol::OpenAI, Some(model_ref))
+    };
+    let result = match (&state.web_search, &state.tool_loop) {
+        // Built-in web search is answered with the configured search backend
+        (Some(web_search), _) if crate::web_search::requested(request.body()) => web_search.run(request.into_body(), call).await,
+        // Registered tools are run here; only the final answer goes back to the client
+        (_, Some(tool_loop)) => tool_loop.run(request.into_body(), call).await,
+        _ => adapter
+            .generate_content(&model, request, &ctx)
+            .await
+            .and_then(|response| response.into_protocol(ModelProtocol::OpenAI, Some(&model))),
+    };
+    record_upstream(&state, &upstream, &ctx, &mut permit, started, &result).await;
+    drop(permit);
+    let mut response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            error!("OpenAI chat request failed: {}", e);
+            return upstream_failed(&state, &ctx, &identity, conversation, &cache, &warnings, e).await;
+        }
+    };
+    process_response(&state, &ctx, reasoning_rule.as_ref(), &mut response);
+    record_usage(&state, usage.as_ref(), &upstream.provider, &model, crate::metrics::usage_tokens(&response), started.elapsed()).await;
+    if let Some(stats) = compression {
+        stats.apply(&mut response);
+    }
+    log_prompt(&state, &ctx, "output", crate::logger::extract_text_from_response(&response, "openai")).await;
+    finish_conversation(&state, conversation, ModelProtocol::OpenAI, &response).await;
+    if let Some(recording) = recording {
+        recording.finish(&response).await;
+    }
+    cache_response(&state, cache.key.as_deref(), &response).await;
+    let response = if simulate {
+        with_warnings(openai_sse(&state, &ctx, simulated_stream(&state, &response, ModelProtocol::OpenAI)), &warnings)
+    } else {
+        json_with_warnings(response, &warnings)
+    };
+    Ok(with_cache_status(response, cache.key.as_ref().map(|_| "miss")))
+}
+
+/// The request's `Idempotency-Key`, when it has one and idempotency records are kept
+fn idempotent_request(
+    state: &AppState,
+    identity: &ClientIdentity,
+    headers: &HeaderMap,
+    protocol: ModelProtocol,
+    body: &Value,
+) -> Result<Option<IdempotentRequest>, AppError> {
+    let (Some(_), Some(key)) = (&state.idempotency, headers.get(crate::idempotency::IDEMPOTENCY_HEADER)) else {
+        return Ok(None);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| crate::idempotency::valid_key(key))
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid {} header", crate::idempotency::IDEMPOTENCY_HEADER)))?;
+    Ok(Some(IdempotentRequest::new(&identity.id, key, protocol, body)))
+}
+
+/// Handle a request once per idempotency key: a retry gets the recorded JSON answer, and
+/// a failed or streamed answer gives the key up again
+async fn with_idempotency(
+    state: &AppState,
+    request: Option<IdempotentRequest>,
+    handle: impl std::future::Future<Output = Result<Response, AppError>>,
+) -> Result<Response, AppError> {
+    let (Some(records), Some(request)) = (&state.idempotency, request) else {
+        return handle.await;
+    };
+    match records.claim(&request).await {
+        Ok(Claim::New) => {}
+        Ok(Claim::Replay(response)) => {
+            let mut response = Json(response).into_response();
+            response.headers_mut().insert(crate::idempotency::REPLAYED_HEADER, HeaderValue::from_static("true"));
+            return Ok(response);
+        }
+        Ok(Claim::InProgress) => {
+            return Err(AppError::Conflict("A request with this Idempotency-Key is still in progress.".to_string()));
+        }
+        Ok(Claim::Mismatch) => {
+            return Err(AppError::UnprocessableEntity(
+                "This Idempotency-Key was already used for a different request.".to_string(),
+            ));
+        }
+        Err(e) => {
+            warn!("Idempotency store unavailable, handling request: {}", e);
+            return handle.await;
+        }
+    }
+
+    let result = handle.await;
+    let recordable = match result {
+        Ok(ref response) => {
+            let content_type = response.headers().get(header::CONTENT_TYPE).map(HeaderValue::as_bytes);
+            response.status().is_success() && content_type.is_some_and(|v| v.starts_with(b"application/json"))
+        }
+        Err(_) => false,
+    };
+    if !recordable {
+        if let Err(e) = records.release(&request).await {
+            warn!("Failed to release idempotency key: {}", e);
+        }
+        return result;
+    }
+
+    // A buffered JSON answer, already in memory
+    let (parts, body) = result?.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.map_err(|e| AppError::InternalError(e.into()))?;
+    match serde_json::from_slice::<Value>(&bytes) {
+        Ok(answer) => {
+            if let Err(e) = records.complete(&request, &answer).await {
+                warn!("Failed to record idempotent response: {}", e);
+            }
+        }
+        Err(_) => {
+            let _ = records.release(&request).await;
+        }
+    }
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+/// Response cache key of a request, unless caching is off or the client sent `Cache-Control: no-cache`
+fn response_cache_key(state: &AppState, headers: &HeaderMap, protocol: ModelProtocol, body: &Value) -> Option<String> {
+    let cache = state.response_cache.as_ref()?;
+    let cache_control = headers.get(header::CACHE_CONTROL).and_then(|v| v.to_str().ok());
+    (!crate::response_cache::bypass(cache_control)).then(|| cache.key(protocol, body))
+}
+
+/// Count the request against `duplicate_requests`; past the limit, its cached answer when
+/// the response cache has one (regardless of `Cache-Control`), else a 429
+async fn check_duplicate(
+    state: &AppState,
+    identity: &ClientIdentity,
+    protocol: ModelProtocol,
+    body: &Value,
+) -> Result<Option<Value>, AppError> {
+    let Some(ref detector) = state.duplicates else {
+        return Ok(None);
+    };
+    let repeat = match detector.observe(&identity.id, protocol, body).await {
+        Ok(Some(repeat)) => repeat,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            warn!("Duplicate request store unavailable, allowing request: {}", e);
+            return Ok(None);
+        }
+    };
+    let window = detector.config().window_secs;
+    if repeat.first {
+        warn!("Client {} sent the same request {} times within {}s", identity.id, repeat.count, window);
+        crate::alerts::duplicate_requests(
+            &identity.id,
+            format!("sent the same request {} times within {}s; repeats are cut short", repeat.count, window),
+        );
+    }
+
+    let key = state.response_cache.as_ref().map(|cache| cache.key(protocol, body));
+    match cached_response(state, key.as_deref()).await {
+        Some(hit) => Ok(Some(hit)),
+        None => Err(AppError::TooManyRequests {
+            message: format!(
+                "The same request was sent {} times within {} seconds; check the client for a retry loop.",
+                repeat.count, window
+            ),
+            retry_after_secs: window,
+        }),
+    }
+}
+
+async fn cached_response(state: &AppState, key: Option<&str>) -> Option<Value> {
+    let (cache, key) = (state.response_cache.as_ref()?, key?);
+    let cached = cache.get(key).await;
+    match cached {
+        Some(_) => state.metrics.record_cache_hit(),
+        None => state.metrics.record_cache_miss(),
+    }
+    cached
+}
+
+/// A cached response streamed back at the configured pace
+fn replay_cached(state: &AppState, cached: &Value, protocol: ModelProtocol) -> ValueStream {
+    let pacing = state.response_cache.as_ref().map(|cache| cache.pacing().clone()).unwrap_or_default();
+    crate::simulated_stream::replay(cached, protocol, &pacing)
+}
+
+/// Whether `upstream` is configured as unable to stream
+fn streams_buffered(state: &AppState, upstream: &Upstream) -> bool {
+    state.config.simulated_streaming.providers.contains(&upstream.provider)
+}
+
+fn without_stream(body: &mut Value) {
+    if let Some(fields) = body.as_object_mut() {
+        fields.remove("stream");
+        fields.remove("stream_options");
+    }
+}
+
+/// A buffered answer streamed to a streaming client at the `simulated_streaming` pace
+fn simulated_stream(state: &AppState, response: &Value, protocol: ModelProtocol) -> ValueStream {
+    crate::simulated_stream::replay(response, protocol, &state.config.simulated_streaming.pacing)
+}
+
+/// What a chat handler needs to answer from the response cache
+struct CacheContext {
+    key: Option<String>,
+    protocol: ModelProtocol,
+    stream: bool,
+}
+
+/// Answer with a cached response, streamed if the request asked for a stream
+async fn serve_cached(
+    state: &AppState,
+    ctx: &RequestContext,
+    conversation: Option<Conversation>,
+    cache: &CacheContext,
+    cached: Value,
+    warnings: &[String],
+    status: &'static str,
+) -> Response {
+    let protocol = cache.protocol;
+    log_prompt(state, ctx, "output", crate::logger::extract_text_from_response(&cached, protocol.as_str())).await;
+    let response = if cache.stream {
+        let stream = replay_cached(state, &cached, protocol);
+        let stream = record_conversation(state, conversation, protocol, stream);
+        with_warnings(protocol_sse(state, ctx, protocol, stream), warnings)
+    } else {
+        finish_conversation(state, conversation, protocol, &cached).await;
+        json_with_warnings(cached, warnings)
+    };
+    with_cache_status(response, Some(status))
+}
+
+/// A failed upstream call: answered from a stale cache entry when the client allows it, else an error
+async fn upstream_failed(
+    state: &AppState,
+    ctx: &RequestContext,
+    identity: &ClientIdentity,
+    conversation: Option<Conversation>,
+    cache: &CacheContext,
+    warnings: &[String],
+    error: anyhow::Error,
+) -> Result<Response, AppError> {
+    // A refused prompt is an answer, not an outage
+    if error.is::<crate::convert_detailed::PromptBlocked>() {
+        return Err(error.into());
+    }
+    let stale = match (&state.response_cache, &cache.key) {
+        (Some(response_cache), Some(key)) => response_cache.get_stale(key, &identity.id).await,
+        _ => None,
+    };
+    match stale {
+        Some(stale) => {
+            warn!("Upstream failed ({}); serving a stale cached response", error);
+            Ok(serve_cached(state, ctx, conversation, cache, stale, warnings, "stale").await)
+        }
+        None => Err(error.into()),
+    }
+}
+
+async fn cache_response(state: &AppState, key: Option<&str>, response: &Value) {
+    if let (Some(cache), Some(key)) = (&state.response_cache, key) {
+        if let Err(e) = cache.put(key, response).await {
+            warn!("Failed to cache response: {}", e);
+        }
+    }
+}
+
+/// Report a cache `hit`, `miss` or `stale` in the `x-aiproxy-cache` header; stale
+/// responses are also marked `X-Cache: STALE`
+fn with_cache_status(mut response: Response, status: Option<&'static str>) -> Response {
+    if let Some(status) = status {
+        response.headers_mut().insert(CACHE_HEADER, HeaderValue::from_static(status));
+        if status == "stale" {
+            response.headers_mut().insert(STALE_HEADER, HeaderValue::from_static("STALE"));
+        }
+    }
+    response
+}
+
+/// Start recording the exchange for the dataset when the client opted in
+fn begin_recording(
+    state: &AppState,
+    identity: &ClientIdentity,
+    ctx: &RequestContext,
+    headers: &HeaderMap,
+    protocol: ModelProtocol,
+    model: &str,
+    body: &Value,
+) -> Option<Recording> {
+    if identity.no_content_logging {
+        return None;
+    }
+    let recorder = state.dataset.as_ref().filter(|recorder| recorder.records(&identity.id))?;
+    let scores = crate::dataset::parse_scores(headers.get(crate::dataset::SCORES_HEADER).and_then(|v| v.to_str().ok()));
+    let recording = recorder.begin(&identity.id, ctx.end_user.as_deref(), protocol, model, body);
+    Some(recording.with_scores(scores))
+}
+
+fn record_dataset(recording: Option<Recording>, stream: ValueStream) -> ValueStream {
+    match recording {
+        Some(recording) => recording.record_stream(stream),
+        None => stream,
+    }
+}
+
+/// With the conversation store enabled, prepend the stored history of the request's
+/// `x-conversation-id` conversation to its messages
+async fn begin_conversation(
+    state: &AppState,
+    identity: &ClientIdentity,
+    headers: &HeaderMap,
+    body: &mut Value,
+) -> Result<Option<Conversation>, AppError> {
+    let (Some(store), Some(id)) = (&state.conversations, headers.get(CONVERSATION_HEADER)) else {
+        return Ok(None);
+    };
+    let id = id
+        .to_str()
+        .ok()
+        .filter(|id| crate::conversations::valid_id(id))
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid {} header", CONVERSATION_HEADER)))?;
+    let mut conversation = store.begin(id, body).await.map_err(AppError::InternalError)?;
+    conversation.owner = Some(identity.id.clone());
+    debug!("Conversation {} continues with {} messages", id, conversation.messages.len());
+    Ok(Some(conversation))
+}
+
+/// Store a conversation with the reply of a buffered response
+async fn finish_conversation(state: &AppState, conversation: Option<Conversation>, protocol: ModelProtocol, response: &Value) {
+    if let (Some(store), Some(conversation)) = (&state.conversations, conversation) {
+        store.finish(conversation, crate::conversations::assistant_message(response, protocol)).await;
+    }
+}
+
+/// Store a conversation with the reply once its stream completes
+fn record_conversation(
+    state: &AppState,
+    conversation: Option<Conversation>,
+    protocol: ModelProtocol,
+    stream: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    match (&state.conversations, conversation) {
+        (Some(store), Some(conversation)) => store.record_stream(conversation, protocol, stream),
+        _ => stream,
+    }
+}
+
+/// Write to the prompt log unless the client opted out; a failed write is not the client's problem
+async fn log_prompt(state: &AppState, ctx: &RequestContext, log_type: &str, content: String) {
+    if ctx.no_content_logging {
+        return;
+    }
+    if let Err(e) = state.prompt_log.log_conversation(log_type, &content).await {
+        warn!("Failed to write prompt log: {}", e);
+    }
+}
+
+/// OpenAI SSE response for a chunk stream, ending with `[DONE]`
+fn openai_sse(state: &AppState, ctx: &RequestContext, stream: impl Stream<Item = Result<Value>> + Send + 'static) -> Response {
+    if let Some(ref end_user) = ctx.end_user {
+        state.metrics.record_end_user(end_user, 0, 0);
+    }
+    let stream_guard = state.metrics.stream_started();
+    let failed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let stream_failed = failed.clone();
+    let events = crate::stream_errors::until_error(stream).map(move |result| {
+        let _active = &stream_guard;
+        let data = match result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                error!("Stream error: {}", e);
+                stream_failed.store(true, std::sync::atomic::Ordering::Relaxed);
+                crate::stream_errors::openai_error_chunk(&e)
+            }
+        };
+        Ok::<_, Infallible>(Event::default().data(serde_json::to_string(&data).unwrap_or_default()))
+    });
+    // A failed stream ends with its error chunk, not `[DONE]`, so it cannot pass for a complete one
+    let done = futures::stream::once(async move {
+        (!failed.load(std::sync::atomic::Ordering::Relaxed)).then(|| Ok::<_, Infallible>(Event::default().data("[DONE]")))
+    })
+    .filter_map(futures::future::ready);
+    Sse::new(events.chain(done)).into_response()
+}
+
+/// Claude SSE response for an event stream; each event is named after its `type`
+fn claude_sse(state: &AppState, ctx: &RequestContext, stream: impl Stream<Item = Result<Value>> + Send + 'static) -> Response {
+    if let Some(ref end_user) = ctx.end_user {
+        state.metrics.record_end_user(end_user, 0, 0);
+    }
+    let stream_guard = state.metrics.stream_started();
+    let events = crate::stream_errors::until_error(stream).map(move |result| {
+        let _active = &stream_guard;
+        match result {
+            Ok(chunk) => {
+                let data = serde_json::to_string(&chunk).unwrap_or_default();
+                let event_type = chunk.get("type").and_then(|t| t.as_str()).unwrap_or("message");
+                Ok::<_, Infallible>(Event::default().event(event_type).data(data))
+            }
+            Err(e) => {
+                error!("Stream error: {}", e);
+                let error_data = crate::stream_errors::claude_error_event(&e);
+                Ok(Event::default().event("error").data(serde_json::to_string(&error_data).unwrap_or_default()))
+            }
+        }
+    });
+    Sse::new(events).into_response()
+}
+
+/// Gemini SSE response (`alt=sse`) for a stream of partial responses; the stream has no end marker
+fn gemini_sse(state: &AppState, ctx: &RequestContext, stream: impl Stream<Item = Result<Value>> + Send + 'static) -> Response {
+    if let Some(ref end_user) = ctx.end_user {
+        state.metrics.record_end_user(end_user, 0, 0);
+    }
+    let stream_guard = state.metrics.stream_started();
+    let events = crate::stream_errors::until_error(stream).map(move |result| {
+        let _active = &stream_guard;
+        let data = match result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                error!("Stream error: {}", e);
+                crate::stream_errors::gemini_error_chunk(&e)
+            }
+        };
+        Ok::<_, Infallible>(Event::default().data(serde_json::to_string(&data).unwrap_or_default()))
+    });
+    Sse::new(events).into_response()
+}
+
+/// SSE response in the client's protocol
+fn protocol_sse(
+    state: &AppState,
+    ctx: &RequestContext,
+    protocol: ModelProtocol,
+    stream: impl Stream<Item = Result<Value>> + Send + 'static,
+) -> Response {
+    match protocol {
+        ModelProtocol::OpenAI => openai_sse(state, ctx, stream),
+        ModelProtocol::Claude => claude_sse(state, ctx, stream),
+        ModelProtocol::Gemini => gemini_sse(state, ctx, stream),
+    }
+}
+
+/// Lossy-conversion warnings for a request about to be sent to a `backend` speaking another protocol
+fn conversion_warnings(body: &Value, protocol: ModelProtocol, backend: ModelProtocol) -> Vec<String> {
+    let warnings = crate::convert::conversion_warnings(body, protocol, backend);
+    if !warnings.is_empty() {
+        warn!("Lossy conversion to {}: {}", backend.as_str(), warnings.join("; "));
+    }
+    warnings
+}
+
+/// Attach warnings as `x-aiproxy-warnings` headers, one per warning
+fn with_warnings(mut response: Response, warnings: &[String]) -> Response {
+    for warning in warnings {
+        if let Ok(value) = HeaderValue::from_str(warning) {
+            response.headers_mut().append(WARNINGS_HEADER, value);
+        }
+    }
+    response
+}
+
+/// JSON response carrying warnings in both the headers and an `aiproxy_warnings` field
+fn json_with_warnings(mut response: Value, warnings: &[String]) -> Response {
+    if !warnings.is_empty() {
+        response[WARNINGS_FIELD] = json!(warnings);
+    }
+    with_warnings(Json(response).into_response(), warnings)
+}
+
+/// Wait for the upstream's published rate limits to allow a call, then for an upstream
+/// slot when adaptive concurrency is enabled
+async fn acquire_upstream(state: &AppState, upstream: &Upstream) -> Result<Option<Permit>, AppError> {
+    if let Some(ref pacing) = state.pacing {
+        if let Err(reset) = pacing.pacer(&upstream.provider).acquire().await {
+            return Err(AppError::TooManyRequests {
+                message: format!("Upstream rate limit of {} is exhausted", upstream.provider),
+                retry_after_secs: reset.as_secs_f64().ceil().max(1.0) as u64,
+            });
+        }
+    }
+    let Some(ref limits) = state.concurrency else {
+        return Ok(None);
+    };
+    let limiter = limits.limiter(&upstream.provider);
+    match limiter.acquire().await {
+        Ok(permit) => Ok(Some(permit)),
+        Err(e) => Err(AppError::TooManyRequests {
+            message: e.to_string(),
+            retry_after_secs: 1,
+        }),
+    }
+}
+
+/// Report an upstream call to the metrics, the health of the pool key it used and, if one
+/// is held, its concurrency permit
+fn record_upstream<'a, T>(
+    state: &'a AppState,
+    upstream: &Upstream,
+    ctx: &'a RequestContext,
+    permit: &mut Option<Permit>,
+    started: Instant,
+    result: &Result<T>,
+) -> impl std::future::Future<Output = ()> + 'a {
+    state.metrics.record_provider_call(&upstream.provider, started.elapsed(), result.is_ok());
+    crate::alerts::record_call(&upstream.provider, result.is_ok());
+    if let Some(ref pacing) = state.pacing {
+        pacing.pacer(&upstream.provider).observe(&crate::rate_limit::recorded_upstream());
+    }
+    if let Some(permit) = permit {
+        permit.record(Outcome::of(result, started.elapsed()));
+    }
+    // Classified now: the result (a stream, say) need not be shared across the await
+    let key = ctx.pool_key.as_ref().map(|key| (key, crate::key_health::KeyOutcome::of(result)));
+    async move {
+        let Some((key, outcome)) = key else {
+            return;
+        };
+        if state.pool_manager.record_outcome(&key.provider_type, &key.uuid, outcome).await.is_some() {
+            // Other instances stop using the key too
+            if let Err(e) = crate::cluster::publish_provider_health(state.shared_state.as_ref(), &key.provider_type, &key.uuid, false).await {
+                warn!("Failed to publish quarantine of key {}: {}", key.uuid, e);
+            }
+        }
+    }
+}
+
+/// Relay an upstream SSE body unchanged
+fn passthrough_stream(state: &AppState, ctx: &RequestContext, bytes: crate::adapter::ByteStream) -> Response {
+    if let Some(ref end_user) = ctx.end_user {
+        state.metrics.record_end_user(end_user, 0, 0);
+    }
+    state.metrics.record_tags(&ctx.tags, 0, 0);
+    let stream_guard = state.metrics.stream_started();
+    let body = crate::stream_errors::openai_bytes(bytes).map(move |chunk| {
+        let _active = &stream_guard;
+        chunk.map_err(std::io::Error::other)
+    });
+    (
+        [(header::CONTENT_TYPE, "text/event-stream"), (header::CACHE_CONTROL, "no-cache")],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+/// Salt end-user ids are hashed with, `None` when they are kept as sent
+pub(crate) fn end_user_hash_salt(config: &Config) -> Option<&str> {
+    config
+        .hash_end_user_ids
+        .then(|| config.end_user_hash_salt.as_deref().unwrap_or_default())
+}
+
+/// Per-request upstream context: forwarded headers, beta flags, org scope, end user and tags
+fn request_context(state: &AppState, headers: &HeaderMap, body: &mut Value) -> RequestContext {
+    let hash_salt = end_user_hash_salt(&state.config);
+    let tags = take_tags(headers, body);
+    if !tags.is_empty() {
+        info!("Request tags: {}", format_tags(&tags));
+    }
+    RequestContext::from_headers(headers, &state.config.forward_headers)
+        .with_anthropic_beta(headers)
+        .with_openai_scope(headers)
+        .with_end_user(end_user_id(headers, body), hash_salt)
+        .with_tags(tags)
+        .with_request_id(crate::request_id::from_headers(headers))
+}
+
+/// Reasoning filter, post-processing and end-user usage for a buffered response
+fn process_response(state: &AppState, ctx: &RequestContext, reasoning_rule: Option<&ReasoningFilterRule>, response: &mut Value) {
+    if let Some(rule) = reasoning_rule {
+        crate::reasoning::filter_response(rule, response);
+    }
+    if let Some(ref processor) = state.post_processor {
+        processor.process_response(response);
+    }
+    let (input_tokens, output_tokens) = crate::metrics::usage_tokens(response);
+    if let Some(ref end_user) = ctx.end_user {
+        state.metrics.record_end_user(end_user, input_tokens, output_tokens);
+    }
+    state.metrics.record_tags(&ctx.tags, input_tokens, output_tokens);
+}
+
+/// The client and scopes (conversation, tags) a request's usage is rolled up under
+#[derive(Clone)]
+struct UsageScopes {
+    client_id: String,
+    scopes: Vec<Scope>,
+}
+
+/// `None` without a usage rollup or when the request has neither a conversation id nor tags
+fn usage_scopes(state: &AppState, identity: &ClientIdentity, headers: &HeaderMap, ctx: &RequestContext) -> Option<UsageScopes> {
+    state.usage_rollup.as_ref()?;
+    let conversation_id = headers
+        .get(CONVERSATION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| crate::conversations::valid_id(id));
+    let scopes = Scope::of(conversation_id, &ctx.tags);
+    (!scopes.is_empty()).then(|| UsageScopes {
+        client_id: identity.id.clone(),
+        scopes,
+    })
+}
+
+/// Add an upstream call to the usage rollup, priced from the model registry
+async fn record_usage(state: &AppState, usage: Option<&UsageScopes>, provider: &str, model: &str, tokens: (u64, u64), latency: Duration) {
+    let (Some(rollup), Some(usage)) = (&state.usage_rollup, usage) else {
+        return;
+    };
+    let (input_tokens, output_tokens) = tokens;
+    let spec = state.model_registry.lookup(model);
+    let turn = Turn {
+        provider: provider.to_string(),
+        input_tokens,
+        output_tokens,
+        latency,
+        cost_usd: crate::usage_rollup::cost_usd(
+            input_tokens,
+            output_tokens,
+            spec.as_ref().and_then(|s| s.input_price),
+            spec.as_ref().and_then(|s| s.output_price),
+        ),
+    };
+    if let Err(e) = rollup.record(&usage.client_id, &usage.scopes, &turn).await {
+        warn!("Failed to record usage rollup: {}", e);
+    }
+}
+
+/// Instrumentation, reasoning filter and post-processing for a provider stream
+fn process_stream(
+    state: &Arc<AppState>,
+    ctx: &RequestContext,
+    upstream: &Upstream,
+    model: String,
+    started: Instant,
+    reasoning_rule: Option<ReasoningFilterRule>,
+    stream: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    let stream = instrument_stream(state.clone(), upstream.provider.clone(), model, ctx.tags.clone(), started, stream);
+    let stream = match reasoning_rule {
+        Some(rule) => crate::reasoning::filter_stream(stream, rule),
+        None => stream,
+    };
+    match state.post_processor {
+        Some(ref processor) => crate::postprocess::process_stream(stream, processor.clone()),
+        None => stream,
+    }
+}
+
+/// The default provider's models plus ensembles and cascades, limited to those the client may use
+async fn list_models(state: &AppState, identity: &ClientIdentity) -> Result<ModelListResponse, AppError> {
+    let upstream = state.providers.snapshot().await.default_upstream();
+    let mut models = match state.model_list_cache {
+        Some(ref cache) => cache.models(&upstream.provider, upstream.adapter.list_models()).await?,
+        None => upstream.adapter.list_models().await?,
+    };
+    crate::ensemble::list_models(&state.config.ensembles, &mut models);
+    crate::cascade::list_models(&state.config.cascades, &mut models);
+    identity.filter_models(&mut models);
+    Ok(models)
+}
+
+/// OpenAI models list handler
+async fn openai_models_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let identity = authorize_client(&state, &headers, &params, SCOPE_MODELS).await?;
+
+    info!("Received OpenAI models list request");
+
+    let models = list_models(&state, &identity).await?;
+    Ok(crate::http_cache::conditional_json(&headers, &models.to_openai()))
+}
+
+/// OpenAI retrieve-model handler
+async fn openai_model_handler(
+    State(state): State<Arc<AppState>>,
+    Path(model): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let identity = authorize_client(&state, &headers, &params, SCOPE_MODELS).await?;
+    identity.check_model(&model)?;
+
+    // Ensembles and cascades are answered by the proxy, like in the model list
+    let mut virtual_models = ModelListResponse { object: None, data: None, models: None };
+    crate::ensemble::list_models(&state.config.ensembles, &mut virtual_models);
+    crate::cascade::list_models(&state.config.cascades, &mut virtual_models);
+    let info = match virtual_models.entries().find(|m| m.model_id() == Some(&model)) {
+        Some(info) => Some(info.clone()),
+        None => {
+            let upstream = state.providers.snapshot().await.default_upstream();
+            match state.model_list_cache {
+                Some(ref cache) => cache.model(&upstream.provider, &model, upstream.adapter.get_model(&model)).await?,
+                None => upstream.adapter.get_model(&model).await?,
+            }
+        }
+    };
+    let body = info
+        .as_ref()
+        .and_then(ModelInfo::to_openai)
+        .ok_or_else(|| AppError::NotFound(format!("The model '{}' does not exist", model)))?;
+    Ok(crate::http_cache::conditional_json(&headers, &body))
+}
+
+/// Models list in Anthropic's format, for ports that speak only the Claude protocol
+async fn claude_models_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let identity = authorize_client(&state, &headers, &params, SCOPE_MODELS).await?;
 
-    // Build application router
-    let app = Router::new()
-        .route("/health", get(health_handler))
-        .route("/v1/chat/completions", post(openai_chat_handler))
-        .route("/v1/models", get(openai_models_handler))
-        .route("/v1/messages", post(claude_messages_handler))
-        .route("/v1beta/models", get(gemini_models_handler))
-        .route(
-            "/v1beta/models/:model/:action",
-            post(gemini_content_handler),
-        )
-        .route("/:provider/v1/chat/completions", post(openai_chat_handler))
-        .route("/:provider/v1/models", get(openai_models_handler))
-        .route("/:provider/v1/messages", post(claude_messages_handler))
-        .with_state(state)
-        .layer(cors);
+    info!("Received Claude models list request");
 
-    // Create TCP listener
-    let listener = TcpListener::bind(&addr).await?;
+    let models = list_models(&state, &identity).await?;
+    Ok(crate::http_cache::conditional_json(&headers, &models.to_claude()))
+}
 
-    info!("--- Unified API Server Configuration ---");
-    info!("  Host: {}", host);
-    info!("  Port: {}", port);
-    info!("  Primary Model Provider: {}", state_clone.config.model_provider);
-    info!("------------------------------------------");
-    info!("\nUnified API Server running on http://{}", addr);
-    info!("Supports multiple API formats:");
-    info!("  • OpenAI-compatible: /v1/chat/completions, /v1/models");
-    info!("  • Gemini-compatible: /v1beta/models, /v1beta/models/{{model}}:generateContent");
-    info!("  • Claude-compatible: /v1/messages");
-    info!("  • Health check: /health");
+/// How often a failed stream is resumed: only backends that continue a prefill can
+fn resume_attempts(state: &AppState, upstream: &Upstream) -> u32 {
+    match ModelProvider::from_str(&upstream.provider) {
+        Some(ref provider) if crate::stream_recovery::supports_prefill(provider) => state.config.stream_resume_attempts,
+        _ => 0,
+    }
+}
 
-    // Start serving
-    axum::serve(listener, app).await?;
+/// Resume a stream of Claude events that fails partway, re-sending `body` (written in
+/// `protocol`) with the text so far as an assistant prefill
+fn resumable_stream(
+    adapter: Arc<dyn ApiServiceAdapter>,
+    model: &str,
+    ctx: &RequestContext,
+    protocol: ModelProtocol,
+    body: Value,
+    attempts: u32,
+    stream: ValueStream,
+) -> ValueStream {
+    let (model, ctx) = (model.to_string(), ctx.clone());
+    crate::stream_recovery::resumable(stream, body, attempts, move |body| {
+        let (adapter, model, ctx) = (adapter.clone(), model.clone(), ctx.clone());
+        async move {
+            let request = ChatRequest::new(protocol, body);
+            let stream = adapter.generate_content_stream(&model, request, &ctx).await?;
+            stream.into_protocol(ModelProtocol::Claude, Some(&model))
+        }
+    })
+}
 
-    Ok(())
+/// Log and record time to first token and throughput once the stream ends
+fn instrument_stream(
+    state: Arc<AppState>,
+    provider: String,
+    model: String,
+    tags: BTreeMap<String, String>,
+    started: Instant,
+    inner: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    Box::pin(async_stream::stream! {
+        let mut inner = inner;
+        let mut timer = StreamTimer::new(started);
+        while let Some(item) = inner.next().await {
+            if let Ok(ref chunk) = item {
+                timer.observe(chunk, Instant::now());
+            }
+            yield item;
+        }
+
+        let timing = timer.finish(Instant::now());
+        info!(
+            "Stream finished: provider={} model={} ttft_ms={:?} output_tokens={} tokens_per_second={:.1} tags={}",
+            provider,
+            model,
+            timing.ttft.map(|t| t.as_millis()),
+            timing.output_tokens,
+            timing.tokens_per_second.unwrap_or(0.0),
+            format_tags(&tags),
+        );
+        state.metrics.record_stream(&provider, &model, &timing);
+        state.metrics.record_tags(&tags, 0, timing.output_tokens);
+    })
 }
 
-/// Health check handler
-async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    Json(json!({
-        "status": "healthy",
-        "timestamp": chrono::Utc::now().to_rfc3339(),
-        "provider": state.config.model_provider
-    }))
+/// Add a stream to the usage rollup once it ends
+fn record_stream_usage(
+    state: &Arc<AppState>,
+    usage: Option<UsageScopes>,
+    upstream: &Upstream,
+    model: &str,
+    started: Instant,
+    inner: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    let Some(usage) = usage else {
+        return inner;
+    };
+    let (state, provider, model) = (state.clone(), upstream.provider.clone(), model.to_string());
+    Box::pin(async_stream::stream! {
+        let mut inner = inner;
+        let mut timer = StreamTimer::new(started);
+        let mut input_tokens = 0;
+        while let Some(item) = inner.next().await {
+            if let Ok(ref chunk) = item {
+                timer.observe(chunk, Instant::now());
+                // OpenAI reports usage on the last chunk, Anthropic on `message_start`
+                let message = chunk.get("message").unwrap_or(&Value::Null);
+                input_tokens = input_tokens
+                    .max(crate::metrics::usage_tokens(chunk).0)
+                    .max(crate::metrics::usage_tokens(message).0);
+            }
+            yield item;
+        }
+        let tokens = (input_tokens, timer.finish(Instant::now()).output_tokens);
+        record_usage(&state, Some(&usage), &provider, &model, tokens, started.elapsed()).await;
+    })
 }
 
-/// OpenAI chat completions handler
-async fn openai_chat_handler(
+/// Prompt token estimates for a chat request, per target model
+async fn token_count_handler(
     State(state): State<Arc<AppState>>,
-    provider_path: Option<Path<String>>,
     headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
-    Json(body): Json<Value>,
+    JsonBody(body): JsonBody,
 ) -> Result<Response, AppError> {
-    // Check authorization
-    let auth_header = headers.get("authorization").and_then(|v| v.to_str().ok());
-    let api_key_header = headers.get("x-api-key").and_then(|v| v.to_str().ok());
-    let goog_api_key = headers.get("x-goog-api-key").and_then(|v| v.to_str().ok());
-    let query_key = params.get("key").map(|s| s.as_str());
+    let identity = authorize_client(&state, &headers, &params, SCOPE_CHAT).await?;
+    crate::transcripts::record_client_request(&identity.id, &body);
 
-    if !is_authorized(
-        auth_header,
-        api_key_header,
-        goog_api_key,
-        query_key,
-        &state.config.required_api_key,
-    ) {
-        return Err(AppError::Unauthorized);
+    if body.get("model").is_none() && body.get("models").is_none() {
+        return Err(AppError::BadRequest("model or models is required".to_string()));
     }
+    // Loading an encoding and tokenizing a long prompt are CPU-bound
+    let counts = tokio::task::spawn_blocking(move || crate::tokenizer::count_request(&body))
+        .await
+        .map_err(|e| AppError::InternalError(e.into()))?;
+    Ok(Json(counts).into_response())
+}
 
-    info!("Received OpenAI chat request");
+/// Totals of the caller's requests in a conversation (`conversation_id`) or with a tag (`tag=key=value`)
+async fn usage_rollup_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, AppError> {
+    let identity = authorize_client(&state, &headers, &params, SCOPE_CHAT).await?;
+    let Some(ref rollup) = state.usage_rollup else {
+        return Err(AppError::NotFound("Usage rollup is not enabled".to_string()));
+    };
 
-    // TODO: Implement actual request handling
-    Ok(Json(json!({
-        "id": "chatcmpl-123",
-        "object": "chat.completion",
-        "created": chrono::Utc::now().timestamp(),
-        "model": "test-model",
-        "choices": [{
-            "index": 0,
-            "message": {
-                "role": "assistant",
-                "content": "This is a placeholder response from Rust implementation"
-            },
-            "finish_reason": "stop"
-        }],
-        "usage": {
-            "prompt_tokens": 0,
-            "completion_tokens": 0,
-            "total_tokens": 0
+    let scope = match (params.get("conversation_id"), params.get("tag")) {
+        (Some(id), None) if crate::conversations::valid_id(id) => Scope::Conversation(id.clone()),
+        (None, Some(tag)) => {
+            let tags = crate::request_context::parse_tags(tag);
+            match tags.iter().next() {
+                Some((key, value)) if tags.len() == 1 => Scope::Tag(format!("{}={}", key, value)),
+                _ => return Err(AppError::BadRequest("tag must be a single key=value pair".to_string())),
+            }
         }
-    }))
-    .into_response())
+        (Some(_), None) => return Err(AppError::BadRequest("Invalid conversation_id".to_string())),
+        _ => return Err(AppError::BadRequest("Exactly one of conversation_id or tag is required".to_string())),
+    };
+
+    let providers = state.providers.snapshot().await.providers();
+    match rollup.rollup(&identity.id, &scope, &providers).await.map_err(AppError::InternalError)? {
+        Some(totals) => Ok(Json(totals).into_response()),
+        None => Err(AppError::NotFound("No usage recorded".to_string())),
+    }
 }
 
-/// OpenAI models list handler
-async fn openai_models_handler(
+/// Cohere/Jina-compatible rerank handler
+async fn rerank_handler(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
+    JsonBody(mut body): JsonBody,
 ) -> Result<Response, AppError> {
-    // Check authorization
-    let auth_header = headers.get("authorization").and_then(|v| v.to_str().ok());
-    let api_key_header = headers.get("x-api-key").and_then(|v| v.to_str().ok());
-    let goog_api_key = headers.get("x-goog-api-key").and_then(|v| v.to_str().ok());
-    let query_key = params.get("key").map(|s| s.as_str());
+    let identity = authorize_client(&state, &headers, &params, SCOPE_CHAT).await?;
+    crate::transcripts::record_client_request(&identity.id, &body);
+    let request = RerankRequest::parse(&body).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    if let Some(ref model) = request.model {
+        identity.check_model(model)?;
+    }
 
-    if !is_authorized(
-        auth_header,
-        api_key_header,
-        goog_api_key,
-        query_key,
-        &state.config.required_api_key,
-    ) {
-        return Err(AppError::Unauthorized);
+    info!("Received rerank request for {} documents", request.documents.len());
+    let started = Instant::now();
+
+    // A dedicated reranking backend speaks the same schema: relay the request
+    if let Some(ref rerank) = state.config.rerank {
+        if let Some(ref model) = rerank.model {
+            body["model"] = json!(model);
+        }
+        let client = crate::http_client::shared(&state.config.http_client)?;
+        let mut upstream = client.post(&rerank.url).json(&body);
+        if let Some(ref api_key) = rerank.api_key {
+            upstream = upstream.bearer_auth(api_key);
+        }
+        let result: Result<Value> = async {
+            let response = upstream.send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                anyhow::bail!("Rerank call failed ({}): {}", status, response.text().await.unwrap_or_default());
+            }
+            crate::json::from_vec(response.bytes().await?.into())
+        }
+        .await;
+        state.metrics.record_provider_call("rerank", started.elapsed(), result.is_ok());
+        return Ok(Json(result?).into_response());
     }
 
-    info!("Received OpenAI models list request");
+    // Otherwise the chat provider scores the documents
+    let model = request
+        .model
+        .clone()
+        .ok_or_else(|| AppError::BadRequest("model is required".to_string()))?;
+    let ctx = request_context(&state, &headers, &mut body);
+    let chat = ChatRequest::OpenAI(request.scoring_prompt(&model));
+    let result = state.current_adapter().await.generate_content(&model, chat, &ctx).await;
+    state.metrics.record_provider_call(&state.config.model_provider, started.elapsed(), result.is_ok());
+    crate::alerts::record_call(&state.config.model_provider, result.is_ok());
 
-    // TODO: Implement actual model listing
-    Ok(Json(json!({
-        "object": "list",
-        "data": []
-    }))
-    .into_response())
+    let reply = result.and_then(|response| response.into_protocol(ModelProtocol::OpenAI, Some(&model)))?;
+    let text = reply
+        .pointer("/choices/0/message/content")
+        .and_then(|c| c.as_str())
+        .unwrap_or_default();
+    let scores = parse_scores(text, request.documents.len())?;
+    let id = format!("rerank-{}", uuid::Uuid::new_v4());
+    Ok(Json(request.response(&id, &scores)).into_response())
+}
+
+/// OpenAI Files API passthrough
+async fn files_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+) -> Result<Response, AppError> {
+    relay_openai_api(&state, &params, request, SCOPE_FILES).await
+}
+
+/// OpenAI fine-tuning jobs passthrough
+async fn fine_tuning_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+) -> Result<Response, AppError> {
+    relay_openai_api(&state, &params, request, SCOPE_FINE_TUNING).await
+}
+
+/// Relay an OpenAI API call the proxy does not interpret, streaming both bodies;
+/// calls that change upstream state are recorded in the audit log
+async fn relay_openai_api(
+    state: &AppState,
+    params: &HashMap<String, String>,
+    request: Request,
+    scope: &str,
+) -> Result<Response, AppError> {
+    let (parts, body) = request.into_parts();
+    let identity = authorize_client(state, &parts.headers, params, scope).await?;
+
+    let path = crate::passthrough::upstream_path(&parts.uri);
+    let audit_action = crate::passthrough::audit_action(&parts.method, &path);
+    info!("Forwarding {} {}", parts.method, path);
+
+    let forward = ForwardRequest {
+        method: parts.method,
+        path: path.clone(),
+        headers: crate::passthrough::request_headers(&parts.headers),
+        body: reqwest::Body::wrap_stream(body.into_data_stream()),
+    };
+    let ctx = RequestContext::default()
+        .with_openai_scope(&parts.headers)
+        .with_request_id(crate::request_id::from_headers(&parts.headers));
+
+    let started = Instant::now();
+    let result = state.current_adapter().await.forward(forward, &ctx).await;
+    state.metrics.record_provider_call(&state.config.model_provider, started.elapsed(), result.is_ok());
+    crate::alerts::record_call(&state.config.model_provider, result.is_ok());
+    let upstream = result.map_err(|e| {
+        error!("API passthrough failed: {}", e);
+        AppError::InternalError(e)
+    })?;
+
+    let status = upstream.status();
+    let headers = crate::passthrough::response_headers(upstream.headers());
+    let body = match audit_action {
+        // Audited responses are small job objects: buffer them to record the outcome
+        Some(action) => {
+            let bytes = upstream.bytes().await.map_err(|e| AppError::InternalError(e.into()))?;
+            let job: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+            let outcome = json!({
+                "status": status.as_u16(),
+                "job_id": job.get("id"),
+                "job_status": job.get("status"),
+            });
+            state.audit.record(&identity.id, action, &path, Value::Null, outcome).await?;
+            Body::from(bytes)
+        }
+        None => Body::from_stream(upstream.bytes_stream()),
+    };
+
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Ok(response)
 }
 
 /// Claude messages handler
 async fn claude_messages_handler(
     State(state): State<Arc<AppState>>,
+    provider_path: Option<Path<String>>,
+    OriginalUri(uri): OriginalUri,
     headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
-    Json(body): Json<Value>,
+    JsonBody(body): JsonBody,
 ) -> Result<Response, AppError> {
-    // Check authorization
-    let auth_header = headers.get("authorization").and_then(|v| v.to_str().ok());
-    let api_key_header = headers.get("x-api-key").and_then(|v| v.to_str().ok());
-    let goog_api_key = headers.get("x-goog-api-key").and_then(|v| v.to_str().ok());
-    let query_key = params.get("key").map(|s| s.as_str());
-
-    if !is_authorized(
-        auth_header,
-        api_key_header,
-        goog_api_key,
-        query_key,
-        &state.config.required_api_key,
-    ) {
-        return Err(AppError::Unauthorized);
-    }
+    let identity = authorize_client(&state, &headers, &params, SCOPE_CHAT).await?;
+    crate::transcripts::record_client_request(&identity.id, &body);
+    let idempotent = idempotent_request(&state, &identity, &headers, ModelProtocol::Claude, &body)?;
+    let handle = claude_messages(state.clone(), identity, provider_path, uri, headers, body);
+    with_idempotency(&state, idempotent, handle).await
+}
 
+async fn claude_messages(
+    state: Arc<AppState>,
+    identity: ClientIdentity,
+    provider_path: Option<Path<String>>,
+    uri: Uri,
+    headers: HeaderMap,
+    mut body: Value,
+) -> Result<Response, AppError> {
     info!("Received Claude messages request");
 
     // Extract model from request  
@@ -212,152 +2042,575 @@ async fn claude_messages_handler(
         .and_then(|v| v.as_str())
         .unwrap_or("claude-3-5-sonnet-20241022")
         .to_string();
+    identity.check_model(&model)?;
+    check_request_limits(&state, &identity, &body)?;
+    let routing = state.providers.snapshot().await;
+    let (model, alias_provider) = resolve_alias(&routing, model, &mut body);
+    if let Some(virtual_model) = VirtualModel::find(&state.config, &routing, &model) {
+        return serve_virtual_model(&state, &identity, &headers, uri.path(), virtual_model, ModelProtocol::Claude, body).await;
+    }
+    let provider_path = provider_path.or(alias_provider.map(Path));
+    let conversation = begin_conversation(&state, &identity, &headers, &mut body).await?;
+    let model = route_by_capability(&state, &identity, model, &mut body)?;
+    let compression = compress_prompt(&state, &routing, &model, &mut body).await;
+    minify_tools(&state, &model, ModelProtocol::Claude, &mut body);
+    check_model_limits(&state, &model, &mut body)?;
+    let upstream = select_upstream(&routing, &model, &body, provider_path)?;
+    check_builtin_tools(&upstream, &body)?;
+    let ctx = request_context(&state, &headers, &mut body)
+        .with_no_content_logging(identity.no_content_logging)
+        .with_pool_key(state.pool_manager.select_key(&upstream.provider).await)
+        .with_timeout(request_timeout(&state, &headers, uri.path(), &upstream.provider)?);
+    log_prompt(&state, &ctx, "input", crate::logger::extract_prompt_from_request(&body, "claude")).await;
+    let reasoning_rule = crate::reasoning::rule_for(&state.config.reasoning_filters, &model).cloned();
+    let backend = upstream.adapter.protocol();
+    let warnings = conversion_warnings(&body, ModelProtocol::Claude, backend);
 
     // Check if streaming is requested
     let stream = body.get("stream")
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
 
-    if stream {
+    let cache = CacheContext {
+        key: response_cache_key(&state, &headers, ModelProtocol::Claude, &body),
+        protocol: ModelProtocol::Claude,
+        stream,
+    };
+    if let Some(hit) = cached_response(&state, cache.key.as_deref()).await {
+        return Ok(serve_cached(&state, &ctx, conversation, &cache, hit, &warnings, "hit").await);
+    }
+    if let Some(hit) = check_duplicate(&state, &identity, ModelProtocol::Claude, &body).await? {
+        return Ok(serve_cached(&state, &ctx, conversation, &cache, hit, &warnings, "hit").await);
+    }
+    let recording = begin_recording(&state, &identity, &ctx, &headers, ModelProtocol::Claude, &model, &body);
+    let usage = usage_scopes(&state, &identity, &headers, &ctx);
+
+    let simulate = stream && streams_buffered(&state, &upstream);
+    if stream && !simulate {
         // Handle streaming response
         info!("Streaming response requested for Claude messages");
         
-        match state.adapter.generate_content_stream(&model, body).await {
-            Ok(stream) => {
-                // Convert the stream to SSE format
-                // Claude API uses simple SSE format with only 'data:' lines
-                let sse_stream = stream.map(|result| {
-                    match result {
-                        Ok(chunk) => {
-                            // Format as SSE event with event type based on chunk type
-                            let data = serde_json::to_string(&chunk).unwrap_or_default();
-                            let event_type = chunk.get("type").and_then(|t| t.as_str()).unwrap_or("message");
-                            Ok::<_, Infallible>(Event::default().event(event_type).data(data))
-                        }
-                        Err(e) => {
-                            error!("Stream error: {}", e);
-                            // Send error event
-                            let error_data = json!({
-                                "type": "error",
-                                "error": {
-                                    "message": e.to_string()
-                                }
-                            });
-                            Ok(Event::default().event("error").data(serde_json::to_string(&error_data).unwrap_or_default()))
-                        }
-                    }
-                });
-                
-                Ok(Sse::new(sse_stream).into_response())
-            }
+        let resume_attempts = resume_attempts(&state, &upstream);
+        let resume_body = (resume_attempts > 0).then(|| body.clone());
+
+        let mut permit = acquire_upstream(&state, &upstream).await?;
+        let started = Instant::now();
+        let adapter = upstream.adapter.clone();
+        let result = adapter.generate_content_stream(&model, ChatRequest::Claude(body), &ctx).await;
+        record_upstream(&state, &upstream, &ctx, &mut permit, started, &result).await;
+        let stream = match result.and_then(|stream| stream.into_protocol(ModelProtocol::Claude, Some(&model))) {
+            Ok(stream) => stream,
             Err(e) => {
                 error!("Failed to start streaming: {}", e);
-                Err(AppError::InternalError(e))
+                return upstream_failed(&state, &ctx, &identity, conversation, &cache, &warnings, e).await;
             }
-        }
+        };
+        let stream = match resume_body {
+            Some(resume_body) => resumable_stream(adapter, &model, &ctx, ModelProtocol::Claude, resume_body, resume_attempts, stream),
+            None => stream,
+        };
+        let stream = crate::stream_recovery::salvage(stream);
+        let stream = record_stream_usage(&state, usage, &upstream, &model, started, stream);
+        let stream = process_stream(&state, &ctx, &upstream, model.clone(), started, reasoning_rule, stream);
+        let stream = with_compression_stats(stream, compression);
+        let stream = record_conversation(&state, conversation, ModelProtocol::Claude, stream);
+        let stream = record_dataset(recording, stream);
+        let stream = crate::concurrency::hold(stream, permit);
+        Ok(with_warnings(claude_sse(&state, &ctx, stream), &warnings))
     } else {
-        // Handle non-streaming response
-        match state.adapter.generate_content(&model, body).await {
-            Ok(response) => {
+        // Handle non-streaming response (replayed as a stream to a streaming client of a buffered-only provider)
+        if simulate {
+            without_stream(&mut body);
+        }
+        let mut permit = acquire_upstream(&state, &upstream).await?;
+        let started = Instant::now();
+        let request = ChatRequest::Claude(body);
+        let result = upstream.adapter.generate_content(&model, request, &ctx).await;
+        record_upstream(&state, &upstream, &ctx, &mut permit, started, &result).await;
+        drop(permit);
+        let result = result.and_then(|response| response.into_protocol(ModelProtocol::Claude, Some(&model)));
+
+        match result {
+            Ok(mut response) => {
                 info!("Claude messages request completed successfully");
-                Ok(Json(response).into_response())
+                process_response(&state, &ctx, reasoning_rule.as_ref(), &mut response);
+                let tokens = crate::metrics::usage_tokens(&response);
+                record_usage(&state, usage.as_ref(), &upstream.provider, &model, tokens, started.elapsed()).await;
+                if let Some(stats) = compression {
+                    stats.apply(&mut response);
+                }
+                log_prompt(&state, &ctx, "output", crate::logger::extract_text_from_response(&response, "claude")).await;
+                finish_conversation(&state, conversation, ModelProtocol::Claude, &response).await;
+                if let Some(recording) = recording {
+                    recording.finish(&response).await;
+                }
+                cache_response(&state, cache.key.as_deref(), &response).await;
+                let response = if simulate {
+                    with_warnings(claude_sse(&state, &ctx, simulated_stream(&state, &response, ModelProtocol::Claude)), &warnings)
+                } else {
+                    json_with_warnings(response, &warnings)
+                };
+                Ok(with_cache_status(response, cache.key.as_ref().map(|_| "miss")))
             }
             Err(e) => {
                 error!("Claude messages request failed: {}", e);
-                Err(AppError::InternalError(e))
+                upstream_failed(&state, &ctx, &identity, conversation, &cache, &warnings, e).await
             }
         }
     }
 }
 
+/// A model the proxy answers by asking the models behind it
+enum VirtualModel<'a> {
+    Ensemble(&'a EnsembleConfig),
+    Cascade(&'a CascadeConfig),
+    /// An alias with fallback targets
+    Alias(&'a str, Vec<AliasTarget>),
+}
+
+impl<'a> VirtualModel<'a> {
+    fn find(config: &'a Config, routing: &Routing, model: &'a str) -> Option<Self> {
+        let fallback = routing.aliases.get(model).map(ModelAlias::targets).filter(|targets| targets.len() > 1);
+        crate::ensemble::find(&config.ensembles, model)
+            .map(Self::Ensemble)
+            .or_else(|| crate::cascade::find(&config.cascades, model).map(Self::Cascade))
+            .or_else(|| fallback.map(|targets| Self::Alias(model, targets)))
+    }
+}
+
+/// Answer a request for an ensemble or cascade model; a streaming request gets the chosen answer replayed.
+/// The request as a whole goes through the conversation store, response cache and dataset; each
+/// member call goes through the same routing, limits and admission as a direct request
+async fn serve_virtual_model(
+    state: &AppState,
+    identity: &ClientIdentity,
+    headers: &HeaderMap,
+    path: &str,
+    virtual_model: VirtualModel<'_>,
+    protocol: ModelProtocol,
+    mut body: Value,
+) -> Result<Response, AppError> {
+    let model = body.get("model").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let routing = &state.providers.snapshot().await;
+    let conversation = begin_conversation(state, identity, headers, &mut body).await?;
+    let ctx = request_context(state, headers, &mut body).with_no_content_logging(identity.no_content_logging);
+    log_prompt(state, &ctx, "input", crate::logger::extract_prompt_from_request(&body, protocol.as_str())).await;
+    let stream = body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let cache = CacheContext {
+        key: response_cache_key(state, headers, protocol, &body),
+        protocol,
+        stream,
+    };
+    if let Some(hit) = cached_response(state, cache.key.as_deref()).await {
+        return Ok(serve_cached(state, &ctx, conversation, &cache, hit, &[], "hit").await);
+    }
+    if let Some(hit) = check_duplicate(state, identity, protocol, &body).await? {
+        return Ok(serve_cached(state, &ctx, conversation, &cache, hit, &[], "hit").await);
+    }
+    let recording = begin_recording(state, identity, &ctx, headers, protocol, &model, &body);
+    let usage = usage_scopes(state, identity, headers, &ctx);
+
+    let (ctx_ref, usage_ref) = (&ctx, usage.as_ref());
+    let call_provider = |model: String, provider: Option<String>, mut body: Value| async move {
+        let model = route_by_capability(state, identity, model, &mut body)?;
+        check_model_limits(state, &model, &mut body)?;
+        let upstream = select_upstream(routing, &model, &body, provider.map(Path))?;
+        if protocol == ModelProtocol::Claude {
+            check_builtin_tools(&upstream, &body)?;
+        }
+        let ctx = ctx_ref
+            .clone()
+            .with_pool_key(state.pool_manager.select_key(&upstream.provider).await)
+            .with_timeout(request_timeout(state, headers, path, &upstream.provider)?);
+        let mut permit = acquire_upstream(state, &upstream).await?;
+        let started = Instant::now();
+        let result = upstream.adapter.generate_content(&model, ChatRequest::new(protocol, body), &ctx).await;
+        record_upstream(state, &upstream, &ctx, &mut permit, started, &result).await;
+        drop(permit);
+        let mut response = result.and_then(|response| response.into_protocol(protocol, Some(&model)))?;
+        if let Some(rule) = crate::reasoning::rule_for(&state.config.reasoning_filters, &model) {
+            crate::reasoning::filter_response(rule, &mut response);
+        }
+        // Every member or step is billed, not just the answer returned
+        let tokens = crate::metrics::usage_tokens(&response);
+        record_usage(state, usage_ref, &upstream.provider, &model, tokens, started.elapsed()).await;
+        anyhow::Ok(response)
+    };
+    let call = |model: String, body: Value| call_provider(model, None, body);
+    let result = match virtual_model {
+        VirtualModel::Ensemble(ensemble) => {
+            crate::ensemble::run(ensemble, protocol, body, call).await.map(|answer| (answer, crate::ensemble::MEMBER_HEADER))
+        }
+        VirtualModel::Cascade(cascade) => {
+            crate::cascade::run(cascade, protocol, body, call).await.map(|answer| (answer, crate::cascade::MODEL_HEADER))
+        }
+        VirtualModel::Alias(alias, targets) => {
+            let call = |target: AliasTarget, body: Value| call_provider(target.model, target.provider, body);
+            crate::aliases::run(alias, &targets, body, call).await.map(|answer| (answer, crate::aliases::MODEL_HEADER))
+        }
+    };
+    let ((mut response, answered_by), header_name) = match result {
+        Ok(answer) => answer,
+        Err(e) => {
+            error!("Request for {} failed: {}", model, e);
+            return upstream_failed(state, &ctx, identity, conversation, &cache, &[], e).await;
+        }
+    };
+
+    // Reasoning was filtered per member, by the rule of the model that answered
+    process_response(state, &ctx, None, &mut response);
+    log_prompt(state, &ctx, "output", crate::logger::extract_text_from_response(&response, protocol.as_str())).await;
+    finish_conversation(state, conversation, protocol, &response).await;
+    if let Some(recording) = recording {
+        recording.finish(&response).await;
+    }
+    cache_response(state, cache.key.as_deref(), &response).await;
+    let mut response = match stream {
+        false => Json(response).into_response(),
+        true => protocol_sse(state, &ctx, protocol, simulated_stream(state, &response, protocol)),
+    };
+    if let Ok(value) = HeaderValue::from_str(&answered_by) {
+        response.headers_mut().insert(header_name, value);
+    }
+    Ok(with_cache_status(response, cache.key.as_ref().map(|_| "miss")))
+}
+
+/// Replace a single-target model alias with the model it stands for, in the request body
+/// too, with the target's parameters; returns the model and the provider the target is
+/// pinned to. Aliases with fallback targets are served as virtual models
+fn resolve_alias(routing: &Routing, model: String, body: &mut Value) -> (String, Option<String>) {
+    let Some(targets) = routing.aliases.get(&model).map(ModelAlias::targets) else {
+        return (model, None);
+    };
+    let [target] = targets.as_slice() else {
+        return (model, None);
+    };
+    debug!("Resolved model alias {} to {}", model, target.model);
+    crate::aliases::apply(target, body);
+    (target.model.clone(), target.provider.clone())
+}
+
+/// The provider serving `model`; a provider named in the path (`/:provider/v1/...`) must be configured
+fn select_upstream(routing: &Routing, model: &str, body: &Value, provider_path: Option<Path<String>>) -> Result<Upstream, AppError> {
+    let requested = provider_path.map(|Path(provider)| provider);
+    routing
+        .upstream_for_request(&crate::router::RouteRequest::new(model, Some(body)), requested.as_deref())
+        .map_err(|e| AppError::NotFound(e.to_string()))
+}
+
+/// With capability routing on, switch to a fallback model the client may use when the
+/// requested one lacks what the request needs (vision, tools); 422 when none qualifies
+fn route_by_capability(state: &AppState, identity: &ClientIdentity, model: String, body: &mut Value) -> Result<String, AppError> {
+    let registry = &state.config.model_registry;
+    if !registry.capability_routing {
+        return Ok(model);
+    }
+    let features = crate::model_registry::RequestFeatures::of(body);
+    let fallbacks: Vec<String> = registry
+        .fallback_models
+        .iter()
+        .filter(|candidate| identity.allows_model(candidate))
+        .cloned()
+        .collect();
+    let routed = state
+        .model_registry
+        .route(&model, &fallbacks, &features)
+        .map_err(|e| AppError::UnprocessableEntity(e.to_string()))?;
+    if routed != model {
+        info!("Routing request for {} to {}: it needs capabilities {} lacks", model, routed, model);
+        body["model"] = json!(routed);
+    }
+    Ok(routed)
+}
+
+/// Shrink the prompt when compression is configured; the counts go into the response usage
+async fn compress_prompt(state: &AppState, routing: &Routing, model: &str, body: &mut Value) -> Option<CompressionStats> {
+    let compressor = state.prompt_compressor.as_ref()?;
+    let ctx = RequestContext::default();
+    let ctx = &ctx;
+    let call = |model: String, body: Value| async move {
+        let upstream = routing.upstream_for(&model, None)?;
+        let request = ChatRequest::OpenAI(body);
+        let response = upstream.adapter.generate_content(&model, request, ctx).await?;
+        response.into_protocol(ModelProtocol::OpenAI, Some(&model))
+    };
+    Some(compressor.compress(model, body, call).await)
+}
+
+/// Shrink the tool definitions when minification is configured; the savings are logged
+fn minify_tools(state: &AppState, model: &str, protocol: ModelProtocol, body: &mut Value) {
+    if let Some(minifier) = &state.tool_minifier {
+        minifier.minify(model, protocol, body);
+    }
+}
+
+fn with_compression_stats(stream: ValueStream, stats: Option<CompressionStats>) -> ValueStream {
+    match stats {
+        Some(stats) => crate::prompt_compression::annotate_stream(stream, stats),
+        None => stream,
+    }
+}
+
+/// Reject (or, in lenient mode, clamp) requests over the model's limits, when enforcement is on
+fn check_model_limits(state: &AppState, model: &str, body: &mut Value) -> Result<(), AppError> {
+    let registry = &state.config.model_registry;
+    if !registry.enforce_limits {
+        return Ok(());
+    }
+    let notes = state
+        .model_registry
+        .enforce(model, body, registry.lenient)
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    for note in notes {
+        info!("Adjusted request for {}: {}", model, note);
+    }
+    Ok(())
+}
+
+/// Reject a request exceeding the client's size limits before any work is done on it
+fn check_request_limits(state: &AppState, identity: &ClientIdentity, body: &Value) -> Result<(), AppError> {
+    let global = &state.config.request_limits;
+    let limits = match identity.request_limits {
+        Some(ref limits) => limits.or(global),
+        None => global.clone(),
+    };
+    crate::request_limits::check(&limits, body).map_err(AppError::PayloadTooLarge)
+}
+
+/// The upstream timeout picked by the client's header or the route / provider config
+fn request_timeout(state: &AppState, headers: &HeaderMap, path: &str, provider: &str) -> Result<Option<std::time::Duration>, AppError> {
+    crate::request_timeout::resolve(&state.config.request_timeouts, headers, path, provider).map_err(AppError::BadRequest)
+}
+
+/// Anthropic built-in tools (computer use, bash, text editor) must be well formed and need a Claude backend
+fn check_builtin_tools(upstream: &Upstream, body: &Value) -> Result<(), AppError> {
+    let tool_types = crate::builtin_tools::requested(body);
+    if tool_types.is_empty() {
+        return Ok(());
+    }
+    crate::builtin_tools::validate(body).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let provider = &upstream.provider;
+    if !crate::builtin_tools::supported(ModelProvider::from_str(provider).as_ref()) {
+        return Err(AppError::BadRequest(crate::builtin_tools::unsupported_message(provider, &tool_types)));
+    }
+    Ok(())
+}
+
 /// Gemini models list handler
 async fn gemini_models_handler(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Response, AppError> {
-    // Check authorization
-    let auth_header = headers.get("authorization").and_then(|v| v.to_str().ok());
-    let api_key_header = headers.get("x-api-key").and_then(|v| v.to_str().ok());
-    let goog_api_key = headers.get("x-goog-api-key").and_then(|v| v.to_str().ok());
-    let query_key = params.get("key").map(|s| s.as_str());
-
-    if !is_authorized(
-        auth_header,
-        api_key_header,
-        goog_api_key,
-        query_key,
-        &state.config.required_api_key,
-    ) {
-        return Err(AppError::Unauthorized);
-    }
+    let identity = authorize_client(&state, &headers, &params, SCOPE_MODELS).await?;
 
     info!("Received Gemini models list request");
 
-    // TODO: Implement actual model listing
-    Ok(Json(json!({
-        "models": []
-    }))
-    .into_response())
+    let models = list_models(&state, &identity).await?;
+    Ok(crate::http_cache::conditional_json(&headers, &models.to_gemini()))
 }
 
-/// Gemini content generation handler
+/// Gemini content generation handler: `models/{model}:generateContent`, or
+/// `:streamGenerateContent` answered as server-sent events (`alt=sse`, which Google's SDKs send)
 async fn gemini_content_handler(
     State(state): State<Arc<AppState>>,
-    Path((model, action)): Path<(String, String)>,
+    Path(model_action): Path<String>,
+    OriginalUri(uri): OriginalUri,
     headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
-    Json(body): Json<Value>,
+    JsonBody(mut body): JsonBody,
 ) -> Result<Response, AppError> {
-    // Check authorization
-    let auth_header = headers.get("authorization").and_then(|v| v.to_str().ok());
-    let api_key_header = headers.get("x-api-key").and_then(|v| v.to_str().ok());
-    let goog_api_key = headers.get("x-goog-api-key").and_then(|v| v.to_str().ok());
-    let query_key = params.get("key").map(|s| s.as_str());
+    let identity = authorize_client(&state, &headers, &params, SCOPE_CHAT).await?;
+    crate::transcripts::record_client_request(&identity.id, &body);
+    let (model, stream) = gemini_method(&model_action)?;
+    // Carried in the body like the other protocols' model and stream flag, so caching,
+    // aliases and virtual models see them; they are dropped again on the way to a Gemini backend
+    let fields = body
+        .as_object_mut()
+        .ok_or_else(|| AppError::BadRequest("The request body must be a JSON object".to_string()))?;
+    fields.insert("model".to_string(), json!(model));
+    if stream {
+        fields.insert("stream".to_string(), json!(true));
+    }
+    let idempotent = idempotent_request(&state, &identity, &headers, ModelProtocol::Gemini, &body)?;
+    let handle = gemini_content(state.clone(), identity, uri, headers, model, body);
+    with_idempotency(&state, idempotent, handle).await
+}
 
-    if !is_authorized(
-        auth_header,
-        api_key_header,
-        goog_api_key,
-        query_key,
-        &state.config.required_api_key,
-    ) {
-        return Err(AppError::Unauthorized);
-    }
-
-    info!("Received Gemini content request for model: {}, action: {}", model, action);
-
-    // TODO: Implement actual request handling
-    Ok(Json(json!({
-        "candidates": [{
-            "content": {
-                "parts": [{
-                    "text": "This is a placeholder response from Rust implementation"
-                }],
-                "role": "model"
-            },
-            "finishReason": "STOP"
-        }]
-    }))
-    .into_response())
+/// The model and whether the answer is streamed, from a `{model}:{method}` path segment
+fn gemini_method(model_action: &str) -> Result<(String, bool), AppError> {
+    match model_action.rsplit_once(':') {
+        Some((model, "generateContent")) if !model.is_empty() => Ok((model.to_string(), false)),
+        Some((model, "streamGenerateContent")) if !model.is_empty() => Ok((model.to_string(), true)),
+        _ => Err(AppError::NotFound(format!("Unsupported Gemini method: models/{}", model_action))),
+    }
+}
+
+/// The conversation store and dataset keep OpenAI and Claude messages only, and prompt
+/// compression rewrites OpenAI messages; none of them apply to Gemini requests
+async fn gemini_content(
+    state: Arc<AppState>,
+    identity: ClientIdentity,
+    uri: Uri,
+    headers: HeaderMap,
+    model: String,
+    mut body: Value,
+) -> Result<Response, AppError> {
+    info!("Received Gemini content request for model: {}", model);
+
+    if state.conversations.is_some() && headers.contains_key(CONVERSATION_HEADER) {
+        return Err(AppError::BadRequest(format!("{} is not supported on the Gemini API", CONVERSATION_HEADER)));
+    }
+    identity.check_model(&model)?;
+    check_request_limits(&state, &identity, &body)?;
+    let routing = state.providers.snapshot().await;
+    let (model, alias_provider) = resolve_alias(&routing, model, &mut body);
+    if let Some(virtual_model) = VirtualModel::find(&state.config, &routing, &model) {
+        return serve_virtual_model(&state, &identity, &headers, uri.path(), virtual_model, ModelProtocol::Gemini, body).await;
+    }
+    let model = route_by_capability(&state, &identity, model, &mut body)?;
+    minify_tools(&state, &model, ModelProtocol::Gemini, &mut body);
+    check_model_limits(&state, &model, &mut body)?;
+    let upstream = select_upstream(&routing, &model, &body, alias_provider.map(Path))?;
+    let ctx = request_context(&state, &headers, &mut body)
+        .with_no_content_logging(identity.no_content_logging)
+        .with_pool_key(state.pool_manager.select_key(&upstream.provider).await)
+        .with_timeout(request_timeout(&state, &headers, uri.path(), &upstream.provider)?);
+    log_prompt(&state, &ctx, "input", crate::logger::extract_prompt_from_request(&body, "gemini")).await;
+    let reasoning_rule = crate::reasoning::rule_for(&state.config.reasoning_filters, &model).cloned();
+    let warnings = conversion_warnings(&body, ModelProtocol::Gemini, upstream.adapter.protocol());
+    let stream = body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let cache = CacheContext {
+        key: response_cache_key(&state, &headers, ModelProtocol::Gemini, &body),
+        protocol: ModelProtocol::Gemini,
+        stream,
+    };
+    if let Some(hit) = cached_response(&state, cache.key.as_deref()).await {
+        return Ok(serve_cached(&state, &ctx, None, &cache, hit, &warnings, "hit").await);
+    }
+    if let Some(hit) = check_duplicate(&state, &identity, ModelProtocol::Gemini, &body).await? {
+        return Ok(serve_cached(&state, &ctx, None, &cache, hit, &warnings, "hit").await);
+    }
+    let usage = usage_scopes(&state, &identity, &headers, &ctx);
+
+    let simulate = stream && streams_buffered(&state, &upstream);
+    let mut permit = acquire_upstream(&state, &upstream).await?;
+    let started = Instant::now();
+    if stream && !simulate {
+        let result = upstream.adapter.generate_content_stream(&model, ChatRequest::Gemini(body), &ctx).await;
+        record_upstream(&state, &upstream, &ctx, &mut permit, started, &result).await;
+        let stream = match result.and_then(|stream| stream.into_protocol(ModelProtocol::Gemini, Some(&model))) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("Failed to start streaming: {}", e);
+                return upstream_failed(&state, &ctx, &identity, None, &cache, &warnings, e).await;
+            }
+        };
+        let stream = record_stream_usage(&state, usage, &upstream, &model, started, stream);
+        let stream = process_stream(&state, &ctx, &upstream, model, started, reasoning_rule, stream);
+        let stream = crate::concurrency::hold(stream, permit);
+        return Ok(with_warnings(gemini_sse(&state, &ctx, stream), &warnings));
+    }
+
+    // Replayed as a stream to a streaming client of a buffered-only provider
+    if simulate {
+        without_stream(&mut body);
+    }
+    let result = upstream.adapter.generate_content(&model, ChatRequest::Gemini(body), &ctx).await;
+    record_upstream(&state, &upstream, &ctx, &mut permit, started, &result).await;
+    drop(permit);
+    let mut response = match result.and_then(|response| response.into_protocol(ModelProtocol::Gemini, Some(&model))) {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Gemini content request failed: {}", e);
+            return upstream_failed(&state, &ctx, &identity, None, &cache, &warnings, e).await;
+        }
+    };
+    process_response(&state, &ctx, reasoning_rule.as_ref(), &mut response);
+    record_usage(&state, usage.as_ref(), &upstream.provider, &model, crate::metrics::usage_tokens(&response), started.elapsed()).await;
+    log_prompt(&state, &ctx, "output", crate::logger::extract_text_from_response(&response, "gemini")).await;
+    cache_response(&state, cache.key.as_deref(), &response).await;
+    let response = if simulate {
+        with_warnings(gemini_sse(&state, &ctx, simulated_stream(&state, &response, ModelProtocol::Gemini)), &warnings)
+    } else {
+        json_with_warnings(response, &warnings)
+    };
+    Ok(with_cache_status(response, cache.key.as_ref().map(|_| "miss")))
 }
 
 /// Application error type
 #[derive(Debug)]
 pub enum AppError {
     Unauthorized,
+    Forbidden(String),
+    /// The client's allowlist does not include the model; the 403 lists what it does include
+    ModelNotAllowed { model: String, allowed: Vec<String> },
     BadRequest(String),
+    NotFound(String),
+    /// Another request holds the same idempotency key
+    Conflict(String),
+    TooManyRequests { message: String, retry_after_secs: u64 },
+    UnprocessableEntity(String),
+    /// The request exceeds the client's `request_limits`
+    PayloadTooLarge(String),
+    /// The upstream refused the prompt (e.g. Gemini `promptFeedback.blockReason`)
+    ContentFiltered(String),
     InternalError(anyhow::Error),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        if let Self::TooManyRequests { message, retry_after_secs } = self {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [("retry-after", retry_after_secs.to_string())],
+                Json(json!({ "error": { "message": message } })),
+            )
+                .into_response();
+        }
+        if let Self::ModelNotAllowed { model, allowed } = self {
+            let message = if allowed.is_empty() {
+                format!("Model '{}' is not allowed for this client, which may call no models.", model)
+            } else {
+                format!("Model '{}' is not allowed for this client. Allowed models: {}", model, allowed.join(", "))
+            };
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "error": {
+                        "message": message,
+                        "type": "permission_error",
+                        "code": "model_not_allowed",
+                        "allowed_models": allowed,
+                    }
+                })),
+            )
+                .into_response();
+        }
+        if let Self::ContentFiltered(message) = self {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": { "message": message, "type": "invalid_request_error", "code": "content_filter" } })),
+            )
+                .into_response();
+        }
+
         let (status, message) = match self {
             Self::Unauthorized => (
                 StatusCode::UNAUTHORIZED,
                 "Unauthorized: API key is invalid or missing.".to_string(),
             ),
+            Self::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            Self::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            Self::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            Self::TooManyRequests { .. } | Self::ContentFiltered(_) | Self::ModelNotAllowed { .. } => {
+                unreachable!("handled above")
+            }
+            Self::UnprocessableEntity(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg),
+            Self::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
             Self::InternalError(e) => {
                 error!("Internal error: {}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
@@ -368,9 +2621,78 @@ impl IntoResponse for AppError {
     }
 }
 
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "Unauthorized"),
+            Self::ModelNotAllowed { model, .. } => write!(f, "Model '{}' is not allowed for this client", model),
+            Self::TooManyRequests { message, .. } => write!(f, "{}", message),
+            Self::Forbidden(msg)
+            | Self::BadRequest(msg)
+            | Self::NotFound(msg)
+            | Self::Conflict(msg)
+            | Self::UnprocessableEntity(msg)
+            | Self::PayloadTooLarge(msg)
+            | Self::ContentFiltered(msg) => write!(f, "{}", msg),
+            Self::InternalError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Lets a rejection cross code that speaks `anyhow` (a virtual model's member calls) intact
+impl std::error::Error for AppError {}
+
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
-        Self::InternalError(err)
+        let err = match err.downcast::<AppError>() {
+            Ok(rejection) => return rejection,
+            Err(err) => err,
+        };
+        match err.downcast_ref::<crate::convert_detailed::PromptBlocked>() {
+            Some(blocked) => Self::ContentFiltered(blocked.to_string()),
+            None => Self::InternalError(err),
+        }
+    }
+}
+
+/// JSON request body, parsed with the hot-path parser (SIMD with the `simd-json` feature);
+/// a gzip or Brotli `Content-Encoding` is decoded first (see `request_decompression` module)
+pub struct JsonBody(pub Value);
+
+#[axum::async_trait]
+impl FromRequest<Arc<AppState>> for JsonBody {
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let encoding = req
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let mut bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| AppError::BadRequest(e.body_text()))?;
+        if let Some(encoding) = encoding {
+            if !state.config.request_decompression {
+                return Err(AppError::BadRequest(format!(
+                    "Compressed request bodies are not accepted (Content-Encoding `{}`)",
+                    encoding
+                )));
+            }
+            // A compressed body may not expand past the limit a plain one is held to
+            let limit = state.config.max_body_bytes;
+            // Decoding megabytes of gzip or Brotli is CPU-bound
+            let decoded = tokio::task::spawn_blocking(move || crate::request_decompression::decompress(&encoding, bytes, limit))
+                .await
+                .map_err(|e| AppError::InternalError(e.into()))?;
+            bytes = decoded.map_err(|e| match e {
+                crate::request_decompression::DecompressError::TooLarge { .. } => AppError::PayloadTooLarge(e.to_string()),
+                _ => AppError::BadRequest(e.to_string()),
+            })?;
+        }
+        crate::json::from_vec(bytes.into())
+            .map(JsonBody)
+            .map_err(|e| AppError::BadRequest(format!("Invalid JSON body: {}", e)))
     }
 }
 
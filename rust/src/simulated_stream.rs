@@ -0,0 +1,172 @@
+/*!
+ * Simulated Streaming
+ *
+ * Turns a complete response into the stream a streaming request expects:
+ * OpenAI `chat.completion.chunk`s, Claude message events or Gemini partial
+ * responses, with text split
+ * into small deltas sent at a steady pace, so streaming clients render a
+ * response that was never streamed (e.g. a cache hit) just like a live one.
+ */
+
+use crate::common::ModelProtocol;
+use crate::config::StreamPacingConfig;
+use crate::stream_recovery::ValueStream;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Text cut into pieces of at most `chunk_chars` characters
+pub fn split_text(text: &str, chunk_chars: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.chunks(chunk_chars.max(1)).map(|piece| piece.iter().collect()).collect()
+}
+
+/// OpenAI stream chunks carrying a chat completion
+pub fn openai_chunks(response: &Value, chunk_chars: usize) -> Vec<Value> {
+    let id = response.get("id").cloned().unwrap_or_else(|| json!(format!("chatcmpl-{}", uuid::Uuid::new_v4())));
+    let created = response.get("created").cloned().unwrap_or_else(|| json!(chrono::Utc::now().timestamp()));
+    let model = response.get("model").cloned().unwrap_or(Value::Null);
+    let chunk = |index: &Value, delta: Value, finish_reason: Value| {
+        json!({
+            "id": id,
+            "object": "chat.completion.chunk",
+            "created": created,
+            "model": model,
+            "choices": [{"index": index, "delta": delta, "finish_reason": finish_reason}],
+        })
+    };
+
+    let mut chunks = Vec::new();
+    let choices = response.get("choices").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+    for (position, choice) in choices.iter().enumerate() {
+        let index = choice.get("index").cloned().unwrap_or(json!(position));
+        let message = choice.get("message").unwrap_or(&Value::Null);
+        chunks.push(chunk(&index, json!({"role": "assistant", "content": ""}), Value::Null));
+        for field in ["reasoning_content", "content"] {
+            let text = message.get(field).and_then(|t| t.as_str()).unwrap_or_default();
+            for piece in split_text(text, chunk_chars) {
+                chunks.push(chunk(&index, json!({field: piece}), Value::Null));
+            }
+        }
+        let tool_calls = message.get("tool_calls").and_then(|t| t.as_array()).cloned().unwrap_or_default();
+        for (call_index, call) in tool_calls.into_iter().enumerate() {
+            let mut call = call;
+            call["index"] = json!(call_index);
+            chunks.push(chunk(&index, json!({"tool_calls": [call]}), Value::Null));
+        }
+        let finish_reason = choice.get("finish_reason").cloned().unwrap_or(json!("stop"));
+        chunks.push(chunk(&index, json!({}), finish_reason));
+    }
+    if let (Some(last), Some(usage)) = (chunks.last_mut(), response.get("usage")) {
+        last["usage"] = usage.clone();
+    }
+    chunks
+}
+
+/// Claude stream events carrying a message
+pub fn claude_events(response: &Value, chunk_chars: usize) -> Vec<Value> {
+    let usage = response.get("usage").cloned().unwrap_or_else(|| json!({}));
+    let mut events = vec![json!({
+        "type": "message_start",
+        "message": {
+            "id": response.get("id").cloned().unwrap_or_else(|| json!(format!("msg_{}", uuid::Uuid::new_v4()))),
+            "type": "message",
+            "role": "assistant",
+            "model": response.get("model").cloned().unwrap_or(Value::Null),
+            "content": [],
+            "stop_reason": null,
+            "stop_sequence": null,
+            "usage": {"input_tokens": usage.get("input_tokens").cloned().unwrap_or(json!(0)), "output_tokens": 0},
+        },
+    })];
+
+    let blocks = response.get("content").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+    for (index, block) in blocks.into_iter().enumerate() {
+        let delta = |delta: Value| json!({"type": "content_block_delta", "index": index, "delta": delta});
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => {
+                events.push(json!({"type": "content_block_start", "index": index, "content_block": {"type": "text", "text": ""}}));
+                for piece in split_text(block["text"].as_str().unwrap_or_default(), chunk_chars) {
+                    events.push(delta(json!({"type": "text_delta", "text": piece})));
+                }
+            }
+            Some("thinking") => {
+                events.push(json!({"type": "content_block_start", "index": index, "content_block": {"type": "thinking", "thinking": ""}}));
+                for piece in split_text(block["thinking"].as_str().unwrap_or_default(), chunk_chars) {
+                    events.push(delta(json!({"type": "thinking_delta", "thinking": piece})));
+                }
+                if let Some(signature) = block.get("signature") {
+                    events.push(delta(json!({"type": "signature_delta", "signature": signature})));
+                }
+            }
+            Some("tool_use") => {
+                let mut start = block.clone();
+                start["input"] = json!({});
+                events.push(json!({"type": "content_block_start", "index": index, "content_block": start}));
+                let input = block.get("input").cloned().unwrap_or_else(|| json!({}));
+                events.push(delta(json!({"type": "input_json_delta", "partial_json": input.to_string()})));
+            }
+            // Server tool blocks and the like arrive whole
+            _ => events.push(json!({"type": "content_block_start", "index": index, "content_block": block})),
+        }
+        events.push(json!({"type": "content_block_stop", "index": index}));
+    }
+
+    events.push(json!({
+        "type": "message_delta",
+        "delta": {
+            "stop_reason": response.get("stop_reason").cloned().unwrap_or(json!("end_turn")),
+            "stop_sequence": response.get("stop_sequence").cloned().unwrap_or(Value::Null),
+        },
+        "usage": {"output_tokens": usage.get("output_tokens").cloned().unwrap_or(json!(0))},
+    }));
+    events.push(json!({"type": "message_stop"}));
+    events
+}
+
+/// Gemini partial responses carrying a complete one
+pub fn gemini_chunks(response: &Value, chunk_chars: usize) -> Vec<Value> {
+    let mut chunks = Vec::new();
+    let candidates = response.get("candidates").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+    for (position, candidate) in candidates.iter().enumerate() {
+        let index = candidate.get("index").cloned().unwrap_or(json!(position));
+        let chunk = |parts: Vec<Value>| json!({"candidates": [{"index": index, "content": {"role": "model", "parts": parts}}]});
+        let parts = candidate.pointer("/content/parts").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+        for part in parts {
+            match part.get("text").and_then(|t| t.as_str()) {
+                Some(text) => {
+                    for piece in split_text(text, chunk_chars) {
+                        let mut piece_part = part.clone();
+                        piece_part["text"] = json!(piece);
+                        chunks.push(chunk(vec![piece_part]));
+                    }
+                }
+                // Function calls, inline data and the like arrive whole
+                None => chunks.push(chunk(vec![part])),
+            }
+        }
+        let mut last = chunk(Vec::new());
+        last["candidates"][0]["finishReason"] = candidate.get("finishReason").cloned().unwrap_or(json!("STOP"));
+        chunks.push(last);
+    }
+    if let (Some(last), Some(usage)) = (chunks.last_mut(), response.get("usageMetadata")) {
+        last["usageMetadata"] = usage.clone();
+    }
+    chunks
+}
+
+/// Stream a complete response in `protocol` at the configured pace
+pub fn replay(response: &Value, protocol: ModelProtocol, pacing: &StreamPacingConfig) -> ValueStream {
+    let chunks = match protocol {
+        ModelProtocol::OpenAI => openai_chunks(response, pacing.chunk_chars),
+        ModelProtocol::Claude => claude_events(response, pacing.chunk_chars),
+        ModelProtocol::Gemini => gemini_chunks(response, pacing.chunk_chars),
+    };
+    let interval = Duration::from_millis(pacing.interval_ms);
+    Box::pin(futures::stream::iter(chunks.into_iter().enumerate()).then(move |(position, chunk)| async move {
+        if position > 0 && !interval.is_zero() {
+            tokio::time::sleep(interval).await;
+        }
+        Ok(chunk)
+    }))
+}
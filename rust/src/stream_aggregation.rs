@@ -0,0 +1,330 @@
+/*!
+ * Stream Aggregation
+ *
+ * The reverse of simulated streaming: some backends (e.g. OAuth endpoints
+ * built for chat apps) only answer with a stream. For providers listed in
+ * `stream_aggregation.providers`, non-streaming calls open a stream upstream,
+ * consume it and assemble the chunks into the single response a buffered
+ * call returns, usage included.
+ */
+
+use crate::adapter::{ApiServiceAdapter, ByteStream};
+use crate::common::{ModelInfo, ModelListResponse, ModelProtocol};
+use crate::convert::{ChatRequest, ChatResponse, ChatStream};
+use crate::passthrough::ForwardRequest;
+use crate::request_context::RequestContext;
+use crate::stream_recovery::ValueStream;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// Consume a stream in `protocol` and build the complete response it carries
+pub async fn aggregate(mut chunks: ValueStream, protocol: ModelProtocol) -> Result<Value> {
+    let mut aggregator = Aggregator::new(protocol);
+    while let Some(chunk) = chunks.next().await {
+        aggregator.observe(&chunk?)?;
+    }
+    aggregator.finish()
+}
+
+/// Builds a response from stream chunks, one chunk at a time
+pub struct Aggregator {
+    protocol: ModelProtocol,
+    /// Response fields taken from the chunks as they go by (id, model, usage, ...)
+    fields: Map<String, Value>,
+    /// OpenAI: choices by index; Claude: content blocks by index; Gemini: the candidate's parts
+    parts: BTreeMap<u64, Value>,
+    /// Claude `input_json_delta` fragments by block index
+    partial_json: BTreeMap<u64, String>,
+    chunks: usize,
+}
+
+impl Aggregator {
+    pub fn new(protocol: ModelProtocol) -> Self {
+        Self {
+            protocol,
+            fields: Map::new(),
+            parts: BTreeMap::new(),
+            partial_json: BTreeMap::new(),
+            chunks: 0,
+        }
+    }
+
+    pub fn observe(&mut self, chunk: &Value) -> Result<()> {
+        self.chunks += 1;
+        match self.protocol {
+            ModelProtocol::OpenAI => self.observe_openai(chunk),
+            ModelProtocol::Claude => self.observe_claude(chunk),
+            ModelProtocol::Gemini => self.observe_gemini(chunk),
+        }
+    }
+
+    fn observe_openai(&mut self, chunk: &Value) -> Result<()> {
+        if let Some(error) = chunk.get("error") {
+            return Err(anyhow!("Upstream stream error: {}", error));
+        }
+        for field in ["id", "created", "model", "system_fingerprint"] {
+            if let Some(value) = chunk.get(field).filter(|v| !v.is_null()) {
+                self.fields.entry(field).or_insert_with(|| value.clone());
+            }
+        }
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            self.fields.insert("usage".to_string(), usage.clone());
+        }
+
+        for choice in chunk.get("choices").and_then(|c| c.as_array()).into_iter().flatten() {
+            let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            let stored = self.parts.entry(index).or_insert_with(|| {
+                json!({"index": index, "message": {"role": "assistant", "content": ""}, "finish_reason": null})
+            });
+            let delta = choice.get("delta").unwrap_or(&Value::Null);
+            for field in ["content", "reasoning_content"] {
+                if let Some(fragment) = delta.get(field).and_then(|t| t.as_str()) {
+                    append(&mut stored["message"][field], fragment);
+                }
+            }
+            for call in delta.get("tool_calls").and_then(|t| t.as_array()).into_iter().flatten() {
+                let position = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize;
+                let calls = &mut stored["message"]["tool_calls"];
+                if !calls.is_array() {
+                    *calls = json!([]);
+                }
+                let calls = calls.as_array_mut().unwrap();
+                while calls.len() <= position {
+                    calls.push(json!({"id": "", "type": "function", "function": {"name": "", "arguments": ""}}));
+                }
+                let call_stored = &mut calls[position];
+                if let Some(id) = call.get("id").filter(|id| !id.is_null()) {
+                    call_stored["id"] = id.clone();
+                }
+                for field in ["name", "arguments"] {
+                    if let Some(fragment) = call.pointer(&format!("/function/{}", field)).and_then(|v| v.as_str()) {
+                        append(&mut call_stored["function"][field], fragment);
+                    }
+                }
+            }
+            if let Some(reason) = choice.get("finish_reason").filter(|r| !r.is_null()) {
+                stored["finish_reason"] = reason.clone();
+            }
+        }
+        Ok(())
+    }
+
+    fn observe_claude(&mut self, chunk: &Value) -> Result<()> {
+        match chunk.get("type").and_then(|t| t.as_str()) {
+            Some("message_start") => {
+                if let Some(message) = chunk.get("message").and_then(|m| m.as_object()) {
+                    self.fields.extend(message.clone());
+                }
+            }
+            Some("content_block_start") => {
+                let index = chunk.get("index").and_then(|i| i.as_u64()).unwrap_or(self.parts.len() as u64);
+                if let Some(block) = chunk.get("content_block") {
+                    self.parts.insert(index, block.clone());
+                }
+            }
+            Some("content_block_delta") => {
+                let index = chunk.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let delta = chunk.get("delta").unwrap_or(&Value::Null);
+                if let Some(json) = delta.get("partial_json").and_then(|j| j.as_str()) {
+                    self.partial_json.entry(index).or_default().push_str(json);
+                    return Ok(());
+                }
+                let block = self.parts.entry(index).or_insert_with(|| json!({"type": "text", "text": ""}));
+                if let Some(citation) = delta.get("citation") {
+                    match block["citations"].as_array_mut() {
+                        Some(citations) => citations.push(citation.clone()),
+                        None => block["citations"] = json!([citation]),
+                    }
+                }
+                // text_delta, thinking_delta and signature_delta extend the field they are named after
+                for field in ["text", "thinking", "signature"] {
+                    if let Some(fragment) = delta.get(field).and_then(|t| t.as_str()) {
+                        append(&mut block[field], fragment);
+                    }
+                }
+            }
+            Some("content_block_stop") => {
+                let index = chunk.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                if let (Some(json), Some(block)) = (self.partial_json.remove(&index), self.parts.get_mut(&index)) {
+                    block["input"] = serde_json::from_str(&json).unwrap_or_else(|_| json!({}));
+                }
+            }
+            Some("message_delta") => {
+                for (field, value) in chunk.get("delta").and_then(|d| d.as_object()).into_iter().flatten() {
+                    self.fields.insert(field.clone(), value.clone());
+                }
+                // The final usage adds output tokens (and may restate input tokens) to message_start's
+                if let Some(usage) = chunk.get("usage").and_then(|u| u.as_object()) {
+                    let stored = self.fields.entry("usage").or_insert_with(|| json!({}));
+                    if let Some(stored) = stored.as_object_mut() {
+                        stored.extend(usage.iter().filter(|(_, v)| !v.is_null()).map(|(k, v)| (k.clone(), v.clone())));
+                    }
+                }
+            }
+            Some("error") => {
+                return Err(anyhow!("Upstream stream error: {}", chunk.get("error").unwrap_or(chunk)));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn observe_gemini(&mut self, chunk: &Value) -> Result<()> {
+        for field in ["usageMetadata", "modelVersion", "responseId", "promptFeedback"] {
+            if let Some(value) = chunk.get(field) {
+                self.fields.insert(field.to_string(), value.clone());
+            }
+        }
+        let Some(candidate) = chunk.pointer("/candidates/0") else {
+            return Ok(());
+        };
+        for field in ["finishReason", "groundingMetadata", "safetyRatings", "citationMetadata"] {
+            if let Some(value) = candidate.get(field) {
+                self.fields.insert(format!("candidate.{}", field), value.clone());
+            }
+        }
+        for part in candidate.pointer("/content/parts").and_then(|p| p.as_array()).into_iter().flatten() {
+            // Text continues the previous part when both are answer text, or both thoughts
+            let previous = self.parts.values_mut().next_back();
+            if let (Some(text), Some(previous)) = (part.get("text").and_then(|t| t.as_str()), previous) {
+                let same_kind = previous.get("thought") == part.get("thought") && previous.get("text").is_some();
+                if same_kind && part.get("thoughtSignature").is_none() {
+                    append(&mut previous["text"], text);
+                    continue;
+                }
+            }
+            let index = self.parts.len() as u64;
+            self.parts.insert(index, part.clone());
+        }
+        Ok(())
+    }
+
+    /// The assembled response; an empty stream is an error
+    pub fn finish(mut self) -> Result<Value> {
+        if self.chunks == 0 {
+            return Err(anyhow!("Upstream stream ended without any data"));
+        }
+        let parts: Vec<Value> = std::mem::take(&mut self.parts).into_values().collect();
+        let fields = &mut self.fields;
+        let response = match self.protocol {
+            ModelProtocol::OpenAI => {
+                let choices: Vec<Value> = parts
+                    .into_iter()
+                    .map(|mut choice| {
+                        let message = &mut choice["message"];
+                        if message.get("tool_calls").is_some() && message["content"] == "" {
+                            message["content"] = Value::Null;
+                        }
+                        choice
+                    })
+                    .collect();
+                let mut response = json!({
+                    "id": fields.remove("id").unwrap_or_else(|| json!(format!("chatcmpl-{}", uuid::Uuid::new_v4()))),
+                    "object": "chat.completion",
+                    "created": fields.remove("created").unwrap_or_else(|| json!(chrono::Utc::now().timestamp())),
+                    "model": fields.remove("model").unwrap_or(Value::Null),
+                    "choices": choices,
+                });
+                let object = response.as_object_mut().unwrap();
+                object.extend(std::mem::take(fields));
+                response
+            }
+            ModelProtocol::Claude => {
+                fields.insert("content".to_string(), json!(parts));
+                fields.entry("type").or_insert_with(|| json!("message"));
+                fields.entry("role").or_insert_with(|| json!("assistant"));
+                Value::Object(std::mem::take(fields))
+            }
+            ModelProtocol::Gemini => {
+                let mut candidate = json!({"content": {"role": "model", "parts": parts}, "index": 0});
+                let mut response = Map::new();
+                for (field, value) in std::mem::take(fields) {
+                    match field.strip_prefix("candidate.") {
+                        Some(field) => candidate[field] = value,
+                        None => {
+                            response.insert(field, value);
+                        }
+                    }
+                }
+                response.insert("candidates".to_string(), json!([candidate]));
+                Value::Object(response)
+            }
+        };
+        Ok(response)
+    }
+}
+
+fn append(target: &mut Value, fragment: &str) {
+    let joined = format!("{}{}", target.as_str().unwrap_or_default(), fragment);
+    *target = json!(joined);
+}
+
+/// Answers non-streaming calls from the wrapped adapter's stream
+pub struct StreamAggregatingAdapter {
+    inner: Box<dyn ApiServiceAdapter>,
+}
+
+impl StreamAggregatingAdapter {
+    pub fn new(inner: Box<dyn ApiServiceAdapter>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl ApiServiceAdapter for StreamAggregatingAdapter {
+    fn protocol(&self) -> ModelProtocol {
+        self.inner.protocol()
+    }
+
+    async fn generate_content(&self, model: &str, request: ChatRequest, ctx: &RequestContext) -> Result<ChatResponse> {
+        let protocol = self.inner.protocol();
+        let mut body = request.into_protocol(protocol, Some(model))?;
+        if let (ModelProtocol::OpenAI, Some(fields)) = (protocol, body.as_object_mut()) {
+            // Without this an OpenAI stream carries no usage
+            fields.insert("stream_options".to_string(), json!({"include_usage": true}));
+        }
+        let stream = self.inner.generate_content_stream(model, ChatRequest::new(protocol, body), ctx).await?;
+        let protocol = stream.protocol();
+        let response = aggregate(stream.into_chunks(), protocol).await?;
+        Ok(ChatResponse::new(protocol, response))
+    }
+
+    async fn generate_content_stream(&self, model: &str, request: ChatRequest, ctx: &RequestContext) -> Result<ChatStream> {
+        self.inner.generate_content_stream(model, request, ctx).await
+    }
+
+    fn supports_stream_passthrough(&self) -> bool {
+        self.inner.supports_stream_passthrough()
+    }
+
+    async fn generate_content_stream_raw(&self, model: &str, request: ChatRequest, ctx: &RequestContext) -> Result<ByteStream> {
+        self.inner.generate_content_stream_raw(model, request, ctx).await
+    }
+
+    async fn forward(&self, request: ForwardRequest, ctx: &RequestContext) -> Result<reqwest::Response> {
+        self.inner.forward(request, ctx).await
+    }
+
+    async fn list_models(&self) -> Result<ModelListResponse> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model(&self, model: &str) -> Result<Option<ModelInfo>> {
+        self.inner.get_model(model).await
+    }
+
+    async fn refresh_token(&self) -> Result<()> {
+        self.inner.refresh_token().await
+    }
+
+    fn upstream_url(&self) -> Option<String> {
+        self.inner.upstream_url()
+    }
+
+    async fn clear_cache(&self) -> usize {
+        self.inner.clear_cache().await
+    }
+}
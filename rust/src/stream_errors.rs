@@ -0,0 +1,225 @@
+/*!
+ * Stream Errors
+ *
+ * Once a streaming response has started, its status line is long gone: an
+ * upstream failure can only be reported inside the stream. The failure is sent
+ * as a final event in the client's dialect, an OpenAI or Gemini error chunk
+ * (`data: {"error": {...}}`) or an Anthropic `error` event, and the stream ends
+ * there, so SDKs raise an error with a usable message and type instead of
+ * seeing a dropped connection. Errors the upstream reports in-band (a Claude
+ * `error` event, a Gemini `error` chunk) are carried across protocol
+ * conversion the same way.
+ */
+
+use crate::upstream_error::Failure;
+use anyhow::Result;
+use async_stream::stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+
+/// What went wrong, as far as the client needs to know
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    RateLimited,
+    Overloaded,
+    Timeout,
+    Api,
+}
+
+impl ErrorKind {
+    /// From an upstream HTTP status
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            429 => Self::RateLimited,
+            503 | 529 => Self::Overloaded,
+            408 | 504 => Self::Timeout,
+            _ => Self::Api,
+        }
+    }
+
+    /// From an Anthropic error `type` or a Gemini error `status`
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "rate_limit_error" | "RESOURCE_EXHAUSTED" => Self::RateLimited,
+            "overloaded_error" | "UNAVAILABLE" => Self::Overloaded,
+            "timeout_error" | "DEADLINE_EXCEEDED" => Self::Timeout,
+            _ => Self::Api,
+        }
+    }
+
+    /// OpenAI error `type` and `code`
+    pub fn openai(self) -> (&'static str, &'static str) {
+        match self {
+            Self::RateLimited => ("rate_limit_error", "rate_limit_exceeded"),
+            Self::Overloaded => ("server_error", "overloaded"),
+            Self::Timeout => ("timeout_error", "timeout"),
+            Self::Api => ("server_error", "upstream_error"),
+        }
+    }
+
+    /// Anthropic error `type`
+    pub fn claude(self) -> &'static str {
+        match self {
+            Self::RateLimited => "rate_limit_error",
+            Self::Overloaded => "overloaded_error",
+            Self::Timeout | Self::Api => "api_error",
+        }
+    }
+
+    /// Gemini error `code` and `status`
+    pub fn gemini(self) -> (u16, &'static str) {
+        match self {
+            Self::RateLimited => (429, "RESOURCE_EXHAUSTED"),
+            Self::Overloaded => (503, "UNAVAILABLE"),
+            Self::Timeout => (504, "DEADLINE_EXCEEDED"),
+            Self::Api => (500, "INTERNAL"),
+        }
+    }
+}
+
+/// An error the upstream reported inside its stream
+#[derive(Debug, Clone)]
+pub struct UpstreamStreamError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for UpstreamStreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Upstream stream error: {}", self.message)
+    }
+}
+
+impl std::error::Error for UpstreamStreamError {}
+
+impl UpstreamStreamError {
+    /// A Claude `error` event, or `None` for any other event
+    pub fn from_claude_event(event: &Value) -> Option<Self> {
+        if event.get("type").and_then(|t| t.as_str()) != Some("error") {
+            return None;
+        }
+        let error = event.get("error");
+        Some(Self {
+            kind: ErrorKind::from_name(error.and_then(|e| e.get("type")).and_then(|t| t.as_str()).unwrap_or_default()),
+            message: error_message(error),
+        })
+    }
+
+    /// A Gemini chunk carrying an `error` object, or `None` for a regular chunk
+    pub fn from_gemini_chunk(chunk: &Value) -> Option<Self> {
+        let error = chunk.get("error")?;
+        let kind = match error.get("status").and_then(|s| s.as_str()) {
+            Some(status) => ErrorKind::from_name(status),
+            None => ErrorKind::from_status(error.get("code").and_then(|c| c.as_u64()).unwrap_or_default() as u16),
+        };
+        Some(Self {
+            kind,
+            message: error_message(Some(error)),
+        })
+    }
+}
+
+fn error_message(error: Option<&Value>) -> String {
+    error
+        .and_then(|e| e.get("message"))
+        .and_then(|m| m.as_str())
+        .unwrap_or("The upstream stream failed")
+        .to_string()
+}
+
+/// Classify a failure: in-band upstream errors keep their kind, others go by `upstream_error::classify`
+pub fn classify(error: &anyhow::Error) -> ErrorKind {
+    if let Some(upstream) = error.downcast_ref::<UpstreamStreamError>() {
+        return upstream.kind;
+    }
+    match crate::upstream_error::classify(error) {
+        Failure::Status(status) => ErrorKind::from_status(status),
+        Failure::Timeout => ErrorKind::Timeout,
+        Failure::Other => ErrorKind::Api,
+    }
+}
+
+/// The message shown to the client; in-band upstream errors without the local prefix
+fn client_message(error: &anyhow::Error) -> String {
+    match error.downcast_ref::<UpstreamStreamError>() {
+        Some(upstream) => upstream.message.clone(),
+        None => format!("{:#}", error),
+    }
+}
+
+/// The OpenAI error chunk reporting a failed stream
+pub fn openai_error_chunk(error: &anyhow::Error) -> Value {
+    let (error_type, code) = classify(error).openai();
+    json!({
+        "error": {
+            "message": client_message(error),
+            "type": error_type,
+            "param": null,
+            "code": code
+        }
+    })
+}
+
+/// The Anthropic `error` event reporting a failed stream
+pub fn claude_error_event(error: &anyhow::Error) -> Value {
+    json!({
+        "type": "error",
+        "error": {
+            "type": classify(error).claude(),
+            "message": client_message(error)
+        }
+    })
+}
+
+/// The Gemini error chunk reporting a failed stream
+pub fn gemini_error_chunk(error: &anyhow::Error) -> Value {
+    let (code, status) = classify(error).gemini();
+    json!({
+        "error": {
+            "code": code,
+            "message": client_message(error),
+            "status": status
+        }
+    })
+}
+
+/// The stream up to and including its first error; nothing after a failure reaches the client
+pub fn until_error<S>(inner: S) -> impl Stream<Item = Result<Value>> + Send
+where
+    S: Stream<Item = Result<Value>> + Send + 'static,
+{
+    stream! {
+        let mut inner = Box::pin(inner);
+        while let Some(item) = inner.next().await {
+            let failed = item.is_err();
+            yield item;
+            if failed {
+                return;
+            }
+        }
+    }
+}
+
+/// Relayed OpenAI SSE bytes; a transport failure becomes a final error chunk instead of
+/// an aborted body
+pub fn openai_bytes<S>(inner: S) -> impl Stream<Item = Result<Bytes>> + Send
+where
+    S: Stream<Item = Result<Bytes>> + Send + 'static,
+{
+    stream! {
+        let mut inner = Box::pin(inner);
+        while let Some(item) = inner.next().await {
+            match item {
+                Ok(bytes) => yield Ok(bytes),
+                Err(e) => {
+                    tracing::error!("Stream error: {}", e);
+                    // The blank line ends any event the failure cut short
+                    let chunk = serde_json::to_string(&openai_error_chunk(&e)).unwrap_or_default();
+                    yield Ok(Bytes::from(format!("\n\ndata: {}\n\n", chunk)));
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+/*!
+ * Stream Recovery
+ *
+ * Resumes a Claude-format stream that dies partway: the request is re-issued
+ * with the text streamed so far as an assistant prefill, and the new stream is
+ * spliced into the client's (duplicate message/block starts dropped, block
+ * indices shifted) so the response continues instead of being truncated.
+ * When a stream cannot be recovered after content was sent, it is closed with
+ * the partial content and an `error` stop reason instead of being cut off.
+ */
+
+use crate::common::ModelProvider;
+use anyhow::Result;
+use async_stream::stream;
+use futures::{Future, Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::pin::Pin;
+use tracing::warn;
+
+pub type ValueStream = Pin<Box<dyn Stream<Item = Result<Value>> + Send>>;
+
+/// Backends that continue a trailing assistant message instead of starting a new one
+pub fn supports_prefill(provider: &ModelProvider) -> bool {
+    matches!(provider, ModelProvider::ClaudeCustom)
+}
+
+/// How a resumed stream's block indices map onto what the client has seen
+#[derive(Debug, Clone, Copy)]
+struct Splice {
+    /// Text block still open on the client; the resumed block 0 continues it
+    continue_block: Option<u64>,
+    next_index: u64,
+}
+
+/// What has been sent to the client so far
+#[derive(Debug, Default)]
+pub struct StreamProgress {
+    message_started: bool,
+    finished: bool,
+    has_non_text_block: bool,
+    text: String,
+    open_text_block: Option<u64>,
+    next_index: u64,
+    splice: Option<Splice>,
+}
+
+fn event_type(event: &Value) -> &str {
+    event.get("type").and_then(|t| t.as_str()).unwrap_or("")
+}
+
+impl StreamProgress {
+    /// Track an upstream event, rewriting it if it comes from a resumed request;
+    /// `None` means the client has already seen its equivalent
+    pub fn process(&mut self, mut event: Value) -> Option<Value> {
+        let kind = event_type(&event).to_string();
+
+        if let Some(splice) = self.splice {
+            if kind == "message_start" && self.message_started {
+                return None;
+            }
+            if let Some(index) = event.get("index").and_then(|i| i.as_u64()) {
+                if kind == "content_block_start" && index == 0 && splice.continue_block.is_some() {
+                    return None;
+                }
+                let mapped = match splice.continue_block {
+                    Some(open) => open + index,
+                    None => splice.next_index + index,
+                };
+                event["index"] = json!(mapped);
+            }
+        }
+
+        let index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+        match kind.as_str() {
+            "message_start" => self.message_started = true,
+            "content_block_start" => {
+                if event.pointer("/content_block/type").and_then(|t| t.as_str()) == Some("text") {
+                    self.open_text_block = Some(index);
+                } else {
+                    self.has_non_text_block = true;
+                    self.open_text_block = None;
+                }
+                self.next_index = self.next_index.max(index + 1);
+            }
+            "content_block_delta" if event.pointer("/delta/type").and_then(|t| t.as_str()) == Some("text_delta") => {
+                if let Some(text) = event.pointer("/delta/text").and_then(|t| t.as_str()) {
+                    self.text.push_str(text);
+                }
+            }
+            "content_block_stop" if self.open_text_block == Some(index) => self.open_text_block = None,
+            "message_stop" => self.finished = true,
+            _ => {}
+        }
+        Some(event)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Text streamed so far
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Tool calls cannot be continued from a prefill, so only text-only responses resume
+    pub fn can_resume(&self) -> bool {
+        !self.finished && !self.has_non_text_block
+    }
+
+    /// Request body for the next attempt, and start splicing its events
+    pub fn resume_request(&mut self, original: &Value) -> Value {
+        self.splice = Some(Splice {
+            continue_block: self.open_text_block,
+            next_index: self.next_index,
+        });
+
+        let mut body = original.clone();
+        // The API rejects a prefill ending in whitespace; the model regenerates it
+        let prefill = self.text.trim_end();
+        if prefill.is_empty() {
+            return body;
+        }
+        let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+            return body;
+        };
+
+        match messages.last_mut() {
+            // Extend a prefill the client already supplied
+            Some(last) if last.get("role").and_then(|r| r.as_str()) == Some("assistant") => {
+                match last.get_mut("content") {
+                    Some(Value::String(content)) => content.push_str(prefill),
+                    Some(Value::Array(blocks)) => blocks.push(json!({"type": "text", "text": prefill})),
+                    _ => last["content"] = json!(prefill),
+                }
+            }
+            _ => messages.push(json!({"role": "assistant", "content": prefill})),
+        }
+        body
+    }
+}
+
+/// Wrap a stream so upstream failures are resumed up to `max_attempts` times
+pub fn resumable<F, Fut>(first: ValueStream, body: Value, max_attempts: u32, reopen: F) -> ValueStream
+where
+    F: Fn(Value) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<ValueStream>> + Send,
+{
+    Box::pin(stream! {
+        let mut progress = StreamProgress::default();
+        let mut current = first;
+        let mut attempts = 0;
+
+        loop {
+            let failure = loop {
+                match current.next().await {
+                    Some(Ok(event)) if event_type(&event) == "error" => {
+                        break anyhow::anyhow!("Upstream error event: {}", event.get("error").unwrap_or(&event));
+                    }
+                    Some(Ok(event)) => {
+                        if let Some(event) = progress.process(event) {
+                            yield Ok(event);
+                        }
+                    }
+                    Some(Err(e)) => break e,
+                    None if progress.finished() => return,
+                    None => break anyhow::anyhow!("Upstream stream ended before message_stop"),
+                }
+            };
+
+            if attempts >= max_attempts || !progress.can_resume() {
+                yield Err(failure);
+                return;
+            }
+            attempts += 1;
+            warn!(
+                "Stream failed after {} chars ({}), resuming (attempt {}/{})",
+                progress.text().len(),
+                failure,
+                attempts,
+                max_attempts
+            );
+
+            match reopen(progress.resume_request(&body)).await {
+                Ok(stream) => current = stream,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            }
+        }
+    })
+}
+
+/// Stop reason / finish reason marking a response cut short by an upstream failure
+pub const SALVAGED_STOP_REASON: &str = "error";
+
+/// Enough of the stream seen so far to close it properly
+#[derive(Debug, Default)]
+pub struct SalvageState {
+    produced_content: bool,
+    message_stopped: bool,
+    open_blocks: BTreeSet<u64>,
+    openai_id: Option<Value>,
+    openai_model: Option<Value>,
+    finished_choices: BTreeSet<u64>,
+    open_choices: BTreeSet<u64>,
+}
+
+impl SalvageState {
+    pub fn observe(&mut self, event: &Value) {
+        if let Some(choices) = event.get("choices").and_then(|c| c.as_array()) {
+            if let Some(id) = event.get("id") {
+                self.openai_id = Some(id.clone());
+            }
+            if let Some(model) = event.get("model") {
+                self.openai_model = Some(model.clone());
+            }
+            for choice in choices {
+                let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                if choice.get("finish_reason").is_some_and(|r| !r.is_null()) {
+                    self.finished_choices.insert(index);
+                } else {
+                    self.open_choices.insert(index);
+                }
+                self.produced_content = true;
+            }
+            return;
+        }
+
+        let index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+        match event_type(event) {
+            "content_block_start" => {
+                self.open_blocks.insert(index);
+            }
+            "content_block_delta" => self.produced_content = true,
+            "content_block_stop" => {
+                self.open_blocks.remove(&index);
+            }
+            "message_stop" => self.message_stopped = true,
+            _ => {}
+        }
+    }
+
+    /// Events that end the response with what was already sent; empty when
+    /// nothing was produced (the error should then be surfaced as is)
+    pub fn closing_events(&self, error: &str) -> Vec<Value> {
+        if !self.produced_content || self.message_stopped {
+            return Vec::new();
+        }
+        let annotation = json!({"type": "api_error", "message": error});
+
+        if self.openai_id.is_some() || !self.open_choices.is_empty() {
+            return self
+                .open_choices
+                .difference(&self.finished_choices)
+                .map(|index| {
+                    json!({
+                        "id": self.openai_id.clone().unwrap_or(Value::Null),
+                        "object": "chat.completion.chunk",
+                        "model": self.openai_model.clone().unwrap_or(Value::Null),
+                        "choices": [{"index": index, "delta": {}, "finish_reason": SALVAGED_STOP_REASON}],
+                        "error": annotation,
+                    })
+                })
+                .collect();
+        }
+
+        let mut events: Vec<Value> = self
+            .open_blocks
+            .iter()
+            .map(|index| json!({"type": "content_block_stop", "index": index}))
+            .collect();
+        events.push(json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": SALVAGED_STOP_REASON, "stop_sequence": null},
+            "usage": {"output_tokens": 0},
+            "error": annotation,
+        }));
+        events.push(json!({"type": "message_stop"}));
+        events
+    }
+}
+
+/// Close a failed stream gracefully once content has reached the client
+pub fn salvage(inner: ValueStream) -> ValueStream {
+    Box::pin(stream! {
+        let mut inner = inner;
+        let mut state = SalvageState::default();
+        while let Some(item) = inner.next().await {
+            match item {
+                Ok(event) => {
+                    state.observe(&event);
+                    yield Ok(event);
+                }
+                Err(e) => {
+                    let closing = state.closing_events(&e.to_string());
+                    if closing.is_empty() {
+                        yield Err(e);
+                    } else {
+                        warn!("Stream failed after partial content, closing with stop reason \"{}\": {}", SALVAGED_STOP_REASON, e);
+                        for event in closing {
+                            yield Ok(event);
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,169 @@
+/*!
+ * Tokenizer
+ *
+ * Prompt token estimates for chat requests. OpenAI models are counted with
+ * their own BPE encoding (plus the documented per-message overhead); other
+ * families have no public tokenizer, so their prompts are counted with
+ * cl100k and scaled by a family-specific factor.
+ */
+
+use serde_json::{json, Value};
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+/// Tokens added per message for the role and separators, and once to prime the reply
+const TOKENS_PER_MESSAGE: usize = 3;
+const REPLY_PRIMING_TOKENS: usize = 3;
+/// Cost of one image whose size is unknown: a 1024x1024 image at high detail
+const IMAGE_TOKENS: usize = 765;
+/// Claude's tokenizer produces noticeably more tokens than cl100k for the same text
+const CLAUDE_SCALE: f64 = 1.15;
+
+/// How prompts for one model are counted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelTokenizer {
+    pub encoding: &'static str,
+    /// Multiplier applied to the cl100k count for non-OpenAI models
+    pub scale: f64,
+    /// Whether `encoding` is the model's own tokenizer
+    pub native: bool,
+}
+
+impl ModelTokenizer {
+    pub fn for_model(model: &str) -> Self {
+        match get_tokenizer(model) {
+            Some(Tokenizer::O200kBase) => Self::native("o200k_base"),
+            Some(Tokenizer::Cl100kBase) => Self::native("cl100k_base"),
+            _ if model.starts_with("claude") => Self::approximate(CLAUDE_SCALE),
+            _ => Self::approximate(1.0),
+        }
+    }
+
+    fn native(encoding: &'static str) -> Self {
+        Self {
+            encoding,
+            scale: 1.0,
+            native: true,
+        }
+    }
+
+    fn approximate(scale: f64) -> Self {
+        Self {
+            encoding: "cl100k_base",
+            scale,
+            native: false,
+        }
+    }
+
+    fn bpe(&self) -> &'static CoreBPE {
+        match self.encoding {
+            "o200k_base" => tiktoken_rs::o200k_base_singleton(),
+            _ => tiktoken_rs::cl100k_base_singleton(),
+        }
+    }
+
+    /// Load every encoding's vocabulary now instead of on the first count (takes a moment)
+    pub fn preload() {
+        tiktoken_rs::cl100k_base_singleton();
+        tiktoken_rs::o200k_base_singleton();
+    }
+
+    pub fn count_text(&self, text: &str) -> usize {
+        self.bpe().encode_ordinary(text).len()
+    }
+
+    /// Estimated prompt tokens of an OpenAI- or Claude-format chat request
+    pub fn count_prompt(&self, request: &Value) -> usize {
+        let mut counter = PromptCounter { tokenizer: self, tokens: 0 };
+
+        if let Some(system) = request.get("system") {
+            counter.tokens += TOKENS_PER_MESSAGE;
+            counter.content(system);
+        }
+        for message in request.get("messages").and_then(|m| m.as_array()).into_iter().flatten() {
+            counter.tokens += TOKENS_PER_MESSAGE;
+            counter.content(message.get("content").unwrap_or(&Value::Null));
+            if let Some(name) = message.get("name").and_then(|n| n.as_str()) {
+                counter.text(name);
+            }
+            for call in message.get("tool_calls").and_then(|c| c.as_array()).into_iter().flatten() {
+                counter.json(call.get("function").unwrap_or(call));
+            }
+        }
+        // Tool definitions are sent as part of the prompt
+        if let Some(tools) = request.get("tools").filter(|t| !t.is_null()) {
+            counter.json(tools);
+        }
+        counter.tokens += REPLY_PRIMING_TOKENS;
+
+        (counter.tokens as f64 * self.scale).ceil() as usize
+    }
+}
+
+struct PromptCounter<'a> {
+    tokenizer: &'a ModelTokenizer,
+    tokens: usize,
+}
+
+impl PromptCounter<'_> {
+    fn text(&mut self, text: &str) {
+        self.tokens += self.tokenizer.count_text(text);
+    }
+
+    fn json(&mut self, value: &Value) {
+        self.text(&value.to_string());
+    }
+
+    /// Message content: a string or a list of OpenAI parts / Claude blocks
+    fn content(&mut self, content: &Value) {
+        match content {
+            Value::String(text) => self.text(text),
+            Value::Array(parts) => parts.iter().for_each(|part| self.part(part)),
+            _ => {}
+        }
+    }
+
+    fn part(&mut self, part: &Value) {
+        match part.get("type").and_then(|t| t.as_str()).unwrap_or("text") {
+            "image" | "image_url" | "input_image" => self.tokens += IMAGE_TOKENS,
+            "tool_use" => {
+                self.text(part.get("name").and_then(|n| n.as_str()).unwrap_or_default());
+                self.json(part.get("input").unwrap_or(&Value::Null));
+            }
+            "tool_result" => self.content(part.get("content").unwrap_or(&Value::Null)),
+            _ => {
+                let text = part.get("text").or_else(|| part.get("thinking"));
+                if let Some(text) = text.and_then(|t| t.as_str()) {
+                    self.text(text);
+                }
+            }
+        }
+    }
+}
+
+/// Per-model prompt token estimates for a chat request; counts for the
+/// `models` listed in the body, or for its `model` when none are listed
+pub fn count_request(request: &Value) -> Value {
+    let mut models: Vec<&str> = request
+        .get("models")
+        .and_then(|m| m.as_array())
+        .map(|models| models.iter().filter_map(|m| m.as_str()).collect())
+        .unwrap_or_default();
+    if models.is_empty() {
+        models.extend(request.get("model").and_then(|m| m.as_str()));
+    }
+
+    let counts: Vec<Value> = models
+        .into_iter()
+        .map(|model| {
+            let tokenizer = ModelTokenizer::for_model(model);
+            json!({
+                "model": model,
+                "prompt_tokens": tokenizer.count_prompt(request),
+                "tokenizer": tokenizer.encoding,
+                "exact_tokenizer": tokenizer.native,
+            })
+        })
+        .collect();
+    json!({"object": "token_count", "data": counts})
+}
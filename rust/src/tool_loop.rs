@@ -0,0 +1,216 @@
+/*!
+ * Tool Loop
+ *
+ * Agent mode for OpenAI-format chat: operator-registered HTTP tools are added
+ * to the request, and whenever the model calls only registered tools the proxy
+ * POSTs each call to its callback URL, appends the results to the
+ * conversation and asks again. The client only sees the final answer (with
+ * usage summed over every round). A reply that calls a tool the client
+ * defined itself is returned as is, so client-side tools keep working.
+ */
+
+use crate::config::{HttpToolConfig, ToolLoopConfig};
+use anyhow::Result;
+use futures::Future;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// A tool call the model made
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+pub struct ToolLoop {
+    config: ToolLoopConfig,
+    client: Client,
+}
+
+impl ToolLoop {
+    pub fn new(config: &ToolLoopConfig, client: Client) -> Self {
+        Self {
+            config: config.clone(),
+            client,
+        }
+    }
+
+    fn tool(&self, name: &str) -> Option<&HttpToolConfig> {
+        self.config.tools.iter().find(|tool| tool.name == name)
+    }
+
+    /// Add the registered tools to an OpenAI request; a client tool of the same name takes precedence
+    pub fn inject_tools(&self, body: &mut Value) {
+        let Some(obj) = body.as_object_mut() else {
+            return;
+        };
+        let tools = obj.entry("tools").or_insert_with(|| json!([]));
+        let Some(tools) = tools.as_array_mut() else {
+            return;
+        };
+        for tool in &self.config.tools {
+            let defined = tools
+                .iter()
+                .any(|t| t.pointer("/function/name").and_then(|n| n.as_str()) == Some(tool.name.as_str()));
+            if !defined {
+                tools.push(json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    },
+                }));
+            }
+        }
+    }
+
+    /// The reply's tool calls, if there are any and every one of them is a registered tool
+    pub fn registered_calls(&self, response: &Value) -> Option<Vec<ToolCall>> {
+        handled_calls(response, |name| self.tool(name).is_some())
+    }
+
+    /// Run one tool call; failures are reported to the model as the tool's result
+    pub async fn invoke(&self, call: &ToolCall) -> String {
+        let Some(tool) = self.tool(&call.name) else {
+            return json!({"error": format!("Unknown tool: {}", call.name)}).to_string();
+        };
+        let arguments = serde_json::from_str(&call.arguments).unwrap_or_else(|_| json!(call.arguments));
+
+        let mut request = self
+            .client
+            .post(&tool.url)
+            .timeout(Duration::from_secs(self.config.timeout_secs))
+            .json(&json!({"name": call.name, "arguments": arguments}));
+        for (name, value) in &tool.headers {
+            request = request.header(name, value);
+        }
+
+        let result: Result<String> = async {
+            let response = request.send().await?;
+            let status = response.status();
+            let text = response.text().await?;
+            if !status.is_success() {
+                anyhow::bail!("Tool call failed ({}): {}", status, text);
+            }
+            Ok(text)
+        }
+        .await;
+
+        result.unwrap_or_else(|e| {
+            warn!("Tool {} failed: {}", call.name, e);
+            json!({"error": e.to_string()}).to_string()
+        })
+    }
+
+    /// Drive the conversation until the model stops calling registered tools;
+    /// `call` sends one OpenAI-format request upstream
+    pub async fn run<F, Fut>(&self, mut body: Value, call: F) -> Result<Value>
+    where
+        F: Fn(Value) -> Fut,
+        Fut: Future<Output = Result<Value>>,
+    {
+        self.inject_tools(&mut body);
+        run_loop(
+            body,
+            self.config.max_iterations,
+            |name| self.tool(name).is_some(),
+            |tool_call| async move { self.invoke(&tool_call).await },
+            call,
+        )
+        .await
+    }
+}
+
+/// The reply's tool calls, if there are any and `handles` accepts every one of them
+pub fn handled_calls(response: &Value, handles: impl Fn(&str) -> bool) -> Option<Vec<ToolCall>> {
+    let calls = response.pointer("/choices/0/message/tool_calls")?.as_array()?;
+    if calls.is_empty() {
+        return None;
+    }
+    calls
+        .iter()
+        .map(|call| {
+            let name = call.pointer("/function/name")?.as_str()?;
+            if !handles(name) {
+                return None;
+            }
+            Some(ToolCall {
+                id: call.get("id")?.as_str()?.to_string(),
+                name: name.to_string(),
+                arguments: call
+                    .pointer("/function/arguments")
+                    .and_then(|a| a.as_str())
+                    .unwrap_or("{}")
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Call the model, run the tools it asks for with `execute` while `handles`
+/// accepts all of them, and repeat; after `max_iterations` rounds an answer is forced
+pub async fn run_loop<H, E, EFut, F, Fut>(mut body: Value, max_iterations: u32, handles: H, execute: E, call: F) -> Result<Value>
+where
+    H: Fn(&str) -> bool,
+    E: Fn(ToolCall) -> EFut,
+    EFut: Future<Output = String>,
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    let mut usage = Usage::default();
+
+    for round in 0..=max_iterations {
+        if round == max_iterations {
+            // Out of rounds: ask for an answer with what has been gathered
+            body["tool_choice"] = json!("none");
+        }
+        let mut response = call(body.clone()).await?;
+        usage.add(&response);
+
+        let Some(calls) = handled_calls(&response, &handles) else {
+            usage.apply(&mut response);
+            return Ok(response);
+        };
+        info!("Tool loop round {}: running {} tool call(s)", round + 1, calls.len());
+
+        let assistant = response.pointer("/choices/0/message").cloned().unwrap_or(Value::Null);
+        let mut appended = vec![assistant];
+        for tool_call in calls {
+            let id = tool_call.id.clone();
+            appended.push(json!({"role": "tool", "tool_call_id": id, "content": execute(tool_call).await}));
+        }
+        if let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) {
+            messages.extend(appended);
+        }
+    }
+    anyhow::bail!("Tool loop did not finish within {} rounds", max_iterations)
+}
+
+/// Token usage summed over every round
+#[derive(Debug, Default)]
+struct Usage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+impl Usage {
+    fn add(&mut self, response: &Value) {
+        let (prompt, completion) = crate::metrics::usage_tokens(response);
+        self.prompt_tokens += prompt;
+        self.completion_tokens += completion;
+    }
+
+    fn apply(&self, response: &mut Value) {
+        if response.get("usage").is_some() {
+            response["usage"] = json!({
+                "prompt_tokens": self.prompt_tokens,
+                "completion_tokens": self.completion_tokens,
+                "total_tokens": self.prompt_tokens + self.completion_tokens,
+            });
+        }
+    }
+}
@@ -0,0 +1,158 @@
+/*!
+ * Tool Minification
+ *
+ * Tool definitions are resent with every request, and they are written for
+ * people: markdown descriptions, indented paragraphs, worked examples in the
+ * parameter schemas. With `tool_minification` configured, tool descriptions
+ * (of the tool and of every parameter) have their whitespace collapsed and,
+ * by default, their markdown reduced to plain text, and `examples` are removed
+ * from the schemas. Names, types, enums and required fields are never touched.
+ * The tokens saved are logged with the request.
+ */
+
+use crate::common::ModelProtocol;
+use crate::config::ToolMinificationConfig;
+use crate::tokenizer::ModelTokenizer;
+use regex::Regex;
+use serde_json::Value;
+use tracing::info;
+
+/// Size of a request's tool definitions before and after minification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinificationStats {
+    pub original_tokens: usize,
+    pub minified_tokens: usize,
+}
+
+impl MinificationStats {
+    pub fn saved_tokens(&self) -> usize {
+        self.original_tokens.saturating_sub(self.minified_tokens)
+    }
+}
+
+/// Schema keywords holding further schemas, keyed (`properties`) or not
+const SCHEMA_MAPS: &[&str] = &["properties", "patternProperties", "$defs", "definitions"];
+const SCHEMA_VALUES: &[&str] = &["items", "additionalProperties", "not", "if", "then", "else"];
+const SCHEMA_LISTS: &[&str] = &["anyOf", "oneOf", "allOf", "prefixItems"];
+
+pub struct ToolMinifier {
+    config: ToolMinificationConfig,
+    /// Markdown patterns and what their matches become
+    markdown: Vec<(Regex, &'static str)>,
+    whitespace: Regex,
+}
+
+impl ToolMinifier {
+    pub fn new(config: &ToolMinificationConfig) -> Self {
+        let markdown = [
+            (r"(?m)^\s*```[\w+-]*\s*$", ""),
+            (r"(?m)^\s{0,3}#{1,6}\s+", ""),
+            (r"(?m)^\s*(?:[-*+]|\d+[.)])\s+", ""),
+            (r"!?\[([^\]]*)\]\([^)]*\)", "$1"),
+            (r"\*\*([^*]+)\*\*", "$1"),
+            (r"__([^_]+)__", "$1"),
+            (r"\*(\S[^*]*)\*", "$1"),
+            (r"`([^`]+)`", "$1"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).expect("valid markdown pattern"), replacement))
+        .collect();
+        Self {
+            config: config.clone(),
+            markdown,
+            whitespace: Regex::new(r"\s+").expect("valid whitespace pattern"),
+        }
+    }
+
+    /// Minify the tool definitions of a `protocol` request for `model` in place; `None` when
+    /// the request has no tools
+    pub fn minify(&self, model: &str, protocol: ModelProtocol, body: &mut Value) -> Option<MinificationStats> {
+        let tools = body.get_mut("tools")?.as_array_mut().filter(|tools| !tools.is_empty())?;
+        let tokenizer = ModelTokenizer::for_model(model);
+        let count = |tools: &Vec<Value>| tokenizer.count_text(&serde_json::to_string(tools).unwrap_or_default());
+        let original_tokens = count(tools);
+
+        for tool in tools.iter_mut() {
+            match protocol {
+                ModelProtocol::OpenAI => {
+                    if let Some(function) = tool.get_mut("function") {
+                        self.minify_function(function, "parameters");
+                    }
+                }
+                ModelProtocol::Claude => self.minify_function(tool, "input_schema"),
+                ModelProtocol::Gemini => {
+                    for declaration in tool.get_mut("functionDeclarations").and_then(|d| d.as_array_mut()).into_iter().flatten() {
+                        self.minify_function(declaration, "parameters");
+                    }
+                }
+            }
+        }
+
+        let stats = MinificationStats {
+            original_tokens,
+            minified_tokens: count(tools),
+        };
+        if stats.saved_tokens() > 0 {
+            info!(
+                "Minified tool definitions for {}: {} -> {} tokens ({} saved)",
+                model,
+                stats.original_tokens,
+                stats.minified_tokens,
+                stats.saved_tokens()
+            );
+        }
+        Some(stats)
+    }
+
+    /// Description text as one plain line
+    pub fn minify_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if self.config.strip_markdown {
+            for (pattern, replacement) in &self.markdown {
+                text = pattern.replace_all(&text, *replacement).into_owned();
+            }
+        }
+        self.whitespace.replace_all(text.trim(), " ").into_owned()
+    }
+
+    fn minify_function(&self, function: &mut Value, schema_field: &str) {
+        self.minify_description(function);
+        if let Some(schema) = function.get_mut(schema_field) {
+            self.minify_schema(schema);
+        }
+    }
+
+    fn minify_description(&self, object: &mut Value) {
+        if let Some(Value::String(description)) = object.get_mut("description") {
+            *description = self.minify_text(description);
+        }
+    }
+
+    /// Descriptions and examples throughout a schema; property names are left alone
+    pub fn minify_schema(&self, schema: &mut Value) {
+        self.minify_description(schema);
+        let Some(object) = schema.as_object_mut() else {
+            return;
+        };
+        if self.config.drop_examples {
+            object.remove("examples");
+            object.remove("example");
+        }
+        for (key, value) in object.iter_mut() {
+            match key.as_str() {
+                key if SCHEMA_MAPS.contains(&key) => {
+                    for nested in value.as_object_mut().into_iter().flat_map(|map| map.values_mut()) {
+                        self.minify_schema(nested);
+                    }
+                }
+                key if SCHEMA_VALUES.contains(&key) => self.minify_schema(value),
+                key if SCHEMA_LISTS.contains(&key) => {
+                    for nested in value.as_array_mut().into_iter().flatten() {
+                        self.minify_schema(nested);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
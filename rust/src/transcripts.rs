@@ -0,0 +1,154 @@
+/*!
+ * Request Transcripts
+ *
+ * With `transcripts` configured, the most recent client API requests are kept
+ * in memory for `/admin/requests/:id`: the body the client sent, the body sent
+ * upstream after conversion, the upstream's answer, conversion warnings and
+ * timings. That answers "why did the model get X?" without turning on content
+ * logging. Only the latest `capacity` requests are kept, and nothing is kept
+ * for clients with content logging disabled. Streamed answers are not
+ * buffered, so their transcripts have no upstream response.
+ *
+ * The chat handlers note the client and its body once the client is
+ * authenticated, and only requests with a noted body are kept: unauthenticated requests and
+ * relayed routes such as the Files API never are, and their bodies are never
+ * read twice.
+ */
+
+use crate::config::TranscriptConfig;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Transcript {
+    pub id: String,
+    /// The authenticated client, whose transcripts a privacy purge deletes
+    pub client_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub started_at: DateTime<Utc>,
+    pub status: u16,
+    /// The body as the client sent it
+    pub request: Option<Value>,
+    /// The body of the latest upstream call, in the upstream's protocol
+    pub upstream_request: Option<Value>,
+    /// The upstream's answer to it, before conversion back
+    pub upstream_response: Option<Value>,
+    pub warnings: Vec<String>,
+    /// From the latest upstream call being sent to its answer arriving
+    pub upstream_ms: Option<u64>,
+    pub total_ms: u64,
+}
+
+/// What a request's upstream calls reported while it was handled
+#[derive(Debug, Default)]
+pub struct Capture {
+    pub client_id: Option<String>,
+    /// The body the client sent, noted once the client was authenticated
+    pub request: Option<Value>,
+    pub upstream_request: Option<Value>,
+    pub upstream_response: Option<Value>,
+    pub upstream_ms: Option<u64>,
+    /// The client asked for its content not to be kept
+    pub skipped: bool,
+    upstream_started: Option<Instant>,
+}
+
+tokio::task_local! {
+    static CAPTURE: Mutex<Capture>;
+}
+
+/// Run a request handler, returning its output and what its upstream calls reported
+pub async fn scope<F: Future>(f: F) -> (F::Output, Capture) {
+    CAPTURE
+        .scope(Mutex::new(Capture::default()), async {
+            let output = f.await;
+            let capture = CAPTURE.with(|capture| std::mem::take(&mut *capture.lock().unwrap()));
+            (output, capture)
+        })
+        .await
+}
+
+/// Note the client and the body it sent; call only after authenticating the client
+pub fn record_client_request(client_id: &str, body: &Value) {
+    let _ = CAPTURE.try_with(|capture| {
+        let mut capture = capture.lock().unwrap();
+        capture.client_id = Some(client_id.to_string());
+        capture.request = Some(body.clone());
+    });
+}
+
+/// Note a body about to be sent upstream; a retry's body replaces the earlier one
+pub fn record_upstream_request(body: &Value) {
+    // Outside a transcript scope (transcripts off, or the embedded client) nothing is kept
+    let _ = CAPTURE.try_with(|capture| {
+        let mut capture = capture.lock().unwrap();
+        capture.upstream_request = Some(body.clone());
+        capture.upstream_response = None;
+        capture.upstream_started = Some(Instant::now());
+    });
+}
+
+/// Note the upstream's answer to the latest upstream request
+pub fn record_upstream_response(body: &Value) {
+    let _ = CAPTURE.try_with(|capture| {
+        let mut capture = capture.lock().unwrap();
+        capture.upstream_ms = capture.upstream_started.map(|started| started.elapsed().as_millis() as u64);
+        capture.upstream_response = Some(body.clone());
+    });
+}
+
+/// Keep no transcript of the current request
+pub fn skip() {
+    let _ = CAPTURE.try_with(|capture| capture.lock().unwrap().skipped = true);
+}
+
+/// The latest transcripts, oldest first
+pub struct TranscriptStore {
+    capacity: usize,
+    transcripts: Mutex<VecDeque<Transcript>>,
+}
+
+impl TranscriptStore {
+    pub fn new(config: &TranscriptConfig) -> Self {
+        Self {
+            capacity: config.capacity,
+            transcripts: Mutex::new(VecDeque::with_capacity(config.capacity)),
+        }
+    }
+
+    /// Keep a transcript, dropping the oldest once full
+    pub fn push(&self, transcript: Transcript) {
+        let mut transcripts = self.transcripts.lock().unwrap();
+        if transcripts.len() == self.capacity {
+            transcripts.pop_front();
+        }
+        transcripts.push_back(transcript);
+    }
+
+    /// The transcript of the latest request with this id
+    pub fn get(&self, id: &str) -> Option<Transcript> {
+        self.transcripts.lock().unwrap().iter().rev().find(|t| t.id == id).cloned()
+    }
+
+    /// Delete every transcript of a client; returns how many there were
+    pub fn purge_client(&self, client_id: &str) -> usize {
+        let mut transcripts = self.transcripts.lock().unwrap();
+        let before = transcripts.len();
+        transcripts.retain(|t| t.client_id.as_deref() != Some(client_id));
+        before - transcripts.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transcripts.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
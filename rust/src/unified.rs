@@ -0,0 +1,1258 @@
+/*!
+ * Unified Request Representation
+ *
+ * A provider-agnostic form of chat requests and responses. Every conversion
+ * of a request or buffered response between OpenAI, Claude and Gemini reads
+ * the source into it and writes the target from it, so each protocol has one
+ * reader and one writer instead of a converter per pair. It keeps what not
+ * every protocol has room for: reasoning parts, code the provider ran,
+ * structured-output formats, web citations and several candidates per answer.
+ * OpenAI message names travel as leading marker text (see `convert_detailed`).
+ * Stream chunks are converted separately, in `convert_detailed`.
+ *
+ * Readers and writers take their input by value and move content (text, image
+ * data, tool arguments) across instead of copying it.
+ */
+
+use crate::convert_detailed::{
+    gemini_finish_reason, gemini_prompt_blocked, name_marker, openai_json_schema, parse_name_marker, set_annotations,
+    synthetic_tool_call_id, CLAUDE_CODE_EXECUTION_TOOL, DEFAULT_MAX_TOKENS,
+};
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Part {
+    Text(String),
+    /// Base64 image data
+    InlineImage { media_type: String, data: String },
+    ImageUrl(String),
+    /// Reasoning; the signature, when the provider gave one, is needed to send it back
+    Thinking { text: String, signature: Option<String> },
+    ToolCall { id: String, name: String, arguments: Value },
+    ToolResult { id: String, name: String, content: String },
+    /// Code the provider ran itself and what it printed
+    CodeExecution { id: String, code: String, output: String, failed: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub role: Role,
+    pub parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tool {
+    Function { name: String, description: Option<Value>, parameters: Option<Value> },
+    CodeExecution,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolChoice {
+    Auto,
+    None,
+    Required,
+    Function(String),
+}
+
+/// Structured output the client asked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseFormat {
+    Json,
+    /// JSON following this JSON Schema
+    JsonSchema(Value),
+}
+
+/// Sampling parameters, kept as the JSON values the client sent
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Generation {
+    pub max_tokens: Option<Value>,
+    pub temperature: Option<Value>,
+    pub top_p: Option<Value>,
+    pub top_k: Option<Value>,
+    pub stop: Vec<Value>,
+    pub candidate_count: Option<Value>,
+    pub presence_penalty: Option<Value>,
+    pub frequency_penalty: Option<Value>,
+    pub seed: Option<Value>,
+    pub response_format: Option<ResponseFormat>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnifiedRequest {
+    pub model: Option<String>,
+    /// System instructions, in order
+    pub system: Vec<String>,
+    pub messages: Vec<Message>,
+    pub tools: Vec<Tool>,
+    pub tool_choice: Option<ToolChoice>,
+    pub parallel_tool_calls: Option<bool>,
+    pub generation: Generation,
+    pub user: Option<String>,
+    pub stream: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FinishReason {
+    #[default]
+    Stop,
+    Length,
+    ToolCalls,
+    ContentFilter,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// A web source an answer cites
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+/// One answer; there are several when the client asked for more than one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Candidate {
+    pub parts: Vec<Part>,
+    pub finish_reason: FinishReason,
+    pub citations: Vec<Citation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnifiedResponse {
+    pub model: String,
+    /// Never empty: a response without candidates reads as one empty candidate
+    pub candidates: Vec<Candidate>,
+    pub usage: Usage,
+}
+
+fn string(value: Option<&Value>) -> Option<String> {
+    value.and_then(|v| v.as_str()).map(String::from)
+}
+
+/// The value at `pointer`, moved out of `value`; `None` when absent or null
+fn take(value: &mut Value, pointer: &str) -> Option<Value> {
+    value.pointer_mut(pointer).map(Value::take).filter(|v| !v.is_null())
+}
+
+fn take_string(value: &mut Value, pointer: &str) -> Option<String> {
+    match take(value, pointer) {
+        Some(Value::String(text)) => Some(text),
+        _ => None,
+    }
+}
+
+fn take_array(value: &mut Value, pointer: &str) -> Vec<Value> {
+    match take(value, pointer) {
+        Some(Value::Array(items)) => items,
+        _ => Vec::new(),
+    }
+}
+
+/// Text of a tool result's content: a string, text blocks/parts, or any other JSON
+fn result_text(content: Option<Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text,
+        Some(Value::Array(blocks)) => blocks
+            .into_iter()
+            .filter_map(|mut block| take_string(&mut block, "/text"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Arguments of an OpenAI tool call: a JSON string or, from lenient clients, an object
+fn tool_arguments(arguments: Option<Value>) -> Value {
+    match arguments {
+        Some(Value::String(raw)) => serde_json::from_str(&raw).unwrap_or_else(|_| json!({})),
+        Some(value) if value.is_object() => value,
+        _ => json!({}),
+    }
+}
+
+/// The candidates read, or one empty candidate when there were none
+fn or_empty(mut candidates: Vec<Candidate>) -> Vec<Candidate> {
+    if candidates.is_empty() {
+        candidates.push(Candidate::default());
+    }
+    candidates
+}
+
+/// Split a `data:` URL into its media type and base64 payload; any other URL is handed back
+fn split_data_url(mut url: String) -> Result<(String, String), String> {
+    let Some(comma) = url.find(',').filter(|_| url.starts_with("data:")) else {
+        return Err(url);
+    };
+    let data = url.split_off(comma + 1);
+    let media_type = url["data:".len()..comma].split(';').next().filter(|t| !t.is_empty()).unwrap_or("image/jpeg");
+    Ok((media_type.to_string(), data))
+}
+
+impl FinishReason {
+    pub(crate) fn from_openai(reason: Option<&str>) -> Self {
+        match reason {
+            Some("length") => Self::Length,
+            Some("tool_calls" | "function_call") => Self::ToolCalls,
+            Some("content_filter") => Self::ContentFilter,
+            _ => Self::Stop,
+        }
+    }
+
+    fn from_claude(reason: Option<&str>) -> Self {
+        match reason {
+            Some("max_tokens") => Self::Length,
+            Some("tool_use") => Self::ToolCalls,
+            Some("refusal") => Self::ContentFilter,
+            _ => Self::Stop,
+        }
+    }
+
+    fn openai(self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::Length => "length",
+            Self::ToolCalls => "tool_calls",
+            Self::ContentFilter => "content_filter",
+        }
+    }
+
+    pub(crate) fn claude(self) -> &'static str {
+        match self {
+            Self::Stop => "end_turn",
+            Self::Length => "max_tokens",
+            Self::ToolCalls => "tool_use",
+            Self::ContentFilter => "refusal",
+        }
+    }
+
+    pub(crate) fn gemini(self) -> &'static str {
+        match self {
+            Self::Stop | Self::ToolCalls => "STOP",
+            Self::Length => "MAX_TOKENS",
+            Self::ContentFilter => "SAFETY",
+        }
+    }
+}
+
+// ============================================================================
+// Readers
+// ============================================================================
+
+impl UnifiedRequest {
+    /// `stream` is left out: adapters set it themselves on streaming calls
+    pub fn from_openai(mut request: Value) -> Result<Self> {
+        let mut unified = Self {
+            model: take_string(&mut request, "/model"),
+            user: take_string(&mut request, "/user"),
+            ..Self::default()
+        };
+
+        let messages = take_array(&mut request, "/messages");
+        let tool_names: HashMap<String, String> = messages
+            .iter()
+            .filter_map(|m| m.get("tool_calls")?.as_array())
+            .flatten()
+            .filter_map(|call| Some((string(call.get("id"))?, string(call.pointer("/function/name"))?)))
+            .collect();
+        for mut message in messages {
+            let role = string(message.get("role"));
+            match role.as_deref() {
+                // System and developer messages are instructions wherever they appear
+                Some("system" | "developer") => {
+                    let texts = openai_content_parts(take(&mut message, "/content")).into_iter().filter_map(|part| match part {
+                        Part::Text(text) => Some(text),
+                        _ => None,
+                    });
+                    unified.system.extend(texts);
+                }
+                Some("tool") => {
+                    let id = take_string(&mut message, "/tool_call_id").unwrap_or_default();
+                    let name = match tool_names.get(&id) {
+                        Some(name) => name.clone(),
+                        None => take_string(&mut message, "/name").unwrap_or_else(|| "unknown".to_string()),
+                    };
+                    let content = result_text(take(&mut message, "/content"));
+                    unified.messages.push(Message { role: Role::User, parts: vec![Part::ToolResult { id, name, content }] });
+                }
+                role => {
+                    let role = if role == Some("assistant") { Role::Assistant } else { Role::User };
+                    let mut parts = openai_content_parts(take(&mut message, "/content"));
+                    for mut call in take_array(&mut message, "/tool_calls") {
+                        let Some(name) = take_string(&mut call, "/function/name") else {
+                            continue;
+                        };
+                        parts.push(Part::ToolCall {
+                            id: take_string(&mut call, "/id").unwrap_or_default(),
+                            name,
+                            arguments: tool_arguments(take(&mut call, "/function/arguments")),
+                        });
+                    }
+                    if let Some(name) = take_string(&mut message, "/name").filter(|n| !n.is_empty() && !parts.is_empty()) {
+                        parts.insert(0, Part::Text(name_marker(&name)));
+                    }
+                    unified.messages.push(Message { role, parts });
+                }
+            }
+        }
+
+        for mut tool in take_array(&mut request, "/tools") {
+            if matches!(tool.get("type").and_then(|t| t.as_str()), Some("code_interpreter" | "code_execution")) {
+                unified.tools.push(Tool::CodeExecution);
+            } else if let Some(name) = take_string(&mut tool, "/function/name") {
+                // Other built-in tools (file search, etc.) have no function and no counterpart
+                unified.tools.push(Tool::Function {
+                    name,
+                    description: take(&mut tool, "/function/description"),
+                    parameters: take(&mut tool, "/function/parameters"),
+                });
+            }
+        }
+        unified.tool_choice = match take(&mut request, "/tool_choice") {
+            Some(Value::String(mode)) => Some(match mode.as_str() {
+                "none" => ToolChoice::None,
+                "required" => ToolChoice::Required,
+                _ => ToolChoice::Auto,
+            }),
+            Some(mut choice) => take_string(&mut choice, "/function/name").map(ToolChoice::Function),
+            None => None,
+        };
+        unified.parallel_tool_calls = request.get("parallel_tool_calls").and_then(|p| p.as_bool());
+
+        let generation = &mut unified.generation;
+        generation.max_tokens = take(&mut request, "/max_tokens");
+        generation.temperature = take(&mut request, "/temperature");
+        generation.top_p = take(&mut request, "/top_p");
+        generation.top_k = take(&mut request, "/top_k");
+        generation.stop = match take(&mut request, "/stop") {
+            Some(Value::String(stop)) => vec![json!(stop)],
+            Some(Value::Array(stop)) => stop,
+            _ => Vec::new(),
+        };
+        generation.candidate_count = take(&mut request, "/n");
+        generation.presence_penalty = take(&mut request, "/presence_penalty");
+        generation.frequency_penalty = take(&mut request, "/frequency_penalty");
+        generation.seed = take(&mut request, "/seed");
+        generation.response_format = match take_string(&mut request, "/response_format/type").as_deref() {
+            Some("json_schema") => match take(&mut request, "/response_format/json_schema/schema") {
+                Some(schema) => Some(ResponseFormat::JsonSchema(schema)),
+                None => Some(ResponseFormat::Json),
+            },
+            Some("json_object") => Some(ResponseFormat::Json),
+            _ => None,
+        };
+        Ok(unified)
+    }
+
+    pub fn from_claude(mut request: Value) -> Result<Self> {
+        let mut unified = Self {
+            model: take_string(&mut request, "/model"),
+            user: take_string(&mut request, "/metadata/user_id"),
+            stream: request.get("stream").and_then(|s| s.as_bool()),
+            ..Self::default()
+        };
+        unified.system = match take(&mut request, "/system") {
+            Some(Value::String(text)) => vec![text],
+            Some(Value::Array(blocks)) => blocks.into_iter().filter_map(|mut block| take_string(&mut block, "/text")).collect(),
+            _ => Vec::new(),
+        };
+
+        let messages = take_array(&mut request, "/messages");
+        let tool_names: HashMap<String, String> = messages
+            .iter()
+            .filter_map(|m| m.get("content")?.as_array())
+            .flatten()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+            .filter_map(|b| Some((string(b.get("id"))?, string(b.get("name"))?)))
+            .collect();
+        for mut message in messages {
+            let role = match message.get("role").and_then(|r| r.as_str()) {
+                Some("assistant") => Role::Assistant,
+                _ => Role::User,
+            };
+            let parts = match take(&mut message, "/content") {
+                Some(Value::String(text)) => vec![Part::Text(text)],
+                Some(Value::Array(blocks)) => claude_blocks_to_parts(blocks, &tool_names),
+                _ => Vec::new(),
+            };
+            unified.messages.push(Message { role, parts });
+        }
+
+        for mut tool in take_array(&mut request, "/tools") {
+            if tool["type"] == CLAUDE_CODE_EXECUTION_TOOL {
+                unified.tools.push(Tool::CodeExecution);
+            } else if let (Some(name), Some(schema)) = (take_string(&mut tool, "/name"), take(&mut tool, "/input_schema")) {
+                // Other server tools (web search, etc.) have no schema and no counterpart
+                unified.tools.push(Tool::Function {
+                    name,
+                    description: take(&mut tool, "/description"),
+                    parameters: Some(schema),
+                });
+            }
+        }
+        if let Some(choice) = request.get("tool_choice") {
+            unified.tool_choice = match choice.get("type").and_then(|t| t.as_str()) {
+                Some("none") => Some(ToolChoice::None),
+                Some("any") => Some(ToolChoice::Required),
+                Some("auto") => Some(ToolChoice::Auto),
+                Some("tool") => string(choice.get("name")).map(ToolChoice::Function),
+                _ => None,
+            };
+            unified.parallel_tool_calls = choice.get("disable_parallel_tool_use").and_then(|d| d.as_bool()).map(|disabled| !disabled);
+        }
+
+        let generation = &mut unified.generation;
+        generation.max_tokens = take(&mut request, "/max_tokens");
+        generation.temperature = take(&mut request, "/temperature");
+        generation.top_p = take(&mut request, "/top_p");
+        generation.top_k = take(&mut request, "/top_k");
+        generation.stop = take_array(&mut request, "/stop_sequences");
+        Ok(unified)
+    }
+
+    /// Gemini names the model in the URL; the server carries it in the body's `model` field
+    pub fn from_gemini(mut request: Value) -> Result<Self> {
+        let system = take_array(&mut request, "/systemInstruction/parts")
+            .into_iter()
+            .filter_map(|mut part| take_string(&mut part, "/text"))
+            .collect();
+        let mut unified = Self {
+            model: take_string(&mut request, "/model"),
+            system,
+            ..Self::default()
+        };
+
+        // Calls from the latest model turn that have not been answered yet
+        let mut pending: Vec<(String, String)> = Vec::new();
+        let mut used_ids = HashSet::new();
+        for mut content in take_array(&mut request, "/contents") {
+            let role = match content.get("role").and_then(|r| r.as_str()) {
+                Some("model") => Role::Assistant,
+                _ => Role::User,
+            };
+            let mut converted = gemini_parts_to_parts(take_array(&mut content, "/parts"));
+            let mut calls = Vec::new();
+            for part in &mut converted {
+                match part {
+                    Part::ToolCall { id, name, .. } => {
+                        // The same call repeated in a later turn derives the same id
+                        let base = id.clone();
+                        let mut suffix = 1;
+                        while !used_ids.insert(id.clone()) {
+                            suffix += 1;
+                            *id = format!("{}_{}", base, suffix);
+                        }
+                        calls.push((id.clone(), name.clone()));
+                    }
+                    Part::ToolResult { id, name, .. } => {
+                        *id = match pending.iter().position(|(pending_id, pending_name)| {
+                            if id.is_empty() { pending_name == name } else { pending_id == id }
+                        }) {
+                            Some(index) => pending.remove(index).0,
+                            None if id.is_empty() => synthetic_tool_call_id(name, &json!({}), 0),
+                            None => id.clone(),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+            if !calls.is_empty() {
+                pending = calls;
+            }
+            // Gemini's thought signatures mean nothing to other providers
+            for part in &mut converted {
+                if let Part::Thinking { signature, .. } = part {
+                    *signature = None;
+                }
+            }
+            // Claude wants a turn's tool results ahead of anything else in it
+            converted.sort_by_key(|part| !matches!(part, Part::ToolResult { .. }));
+            if !converted.is_empty() {
+                unified.messages.push(Message { role, parts: converted });
+            }
+        }
+
+        let mut code_execution = false;
+        for mut tool in take_array(&mut request, "/tools") {
+            code_execution |= tool.get("codeExecution").is_some();
+            for mut declaration in take_array(&mut tool, "/functionDeclarations") {
+                unified.tools.push(Tool::Function {
+                    name: take_string(&mut declaration, "/name").unwrap_or_default(),
+                    description: take(&mut declaration, "/description"),
+                    parameters: take(&mut declaration, "/parameters"),
+                });
+            }
+        }
+        if code_execution {
+            unified.tools.push(Tool::CodeExecution);
+        }
+        if let Some(config) = request.pointer("/toolConfig/functionCallingConfig") {
+            unified.tool_choice = match config.get("mode").and_then(|m| m.as_str()) {
+                Some("NONE") => Some(ToolChoice::None),
+                Some("AUTO") => Some(ToolChoice::Auto),
+                Some("ANY") => match config.get("allowedFunctionNames").and_then(|n| n.as_array()).map(|n| n.as_slice()) {
+                    Some([name]) => string(Some(name)).map(ToolChoice::Function),
+                    _ => Some(ToolChoice::Required),
+                },
+                _ => None,
+            };
+        }
+
+        if let Some(mut config) = take(&mut request, "/generationConfig") {
+            let generation = &mut unified.generation;
+            generation.max_tokens = take(&mut config, "/maxOutputTokens");
+            generation.temperature = take(&mut config, "/temperature");
+            generation.top_p = take(&mut config, "/topP");
+            generation.top_k = take(&mut config, "/topK");
+            generation.stop = take_array(&mut config, "/stopSequences");
+            generation.candidate_count = take(&mut config, "/candidateCount");
+            generation.presence_penalty = take(&mut config, "/presencePenalty");
+            generation.frequency_penalty = take(&mut config, "/frequencyPenalty");
+            generation.seed = take(&mut config, "/seed");
+            generation.response_format = match take(&mut config, "/responseSchema") {
+                Some(schema) => Some(ResponseFormat::JsonSchema(crate::gemini_schema::to_json_schema(&schema))),
+                None if config.get("responseMimeType").and_then(|t| t.as_str()) == Some("application/json") => Some(ResponseFormat::Json),
+                None => None,
+            };
+        }
+        Ok(unified)
+    }
+}
+
+fn openai_content_parts(content: Option<Value>) -> Vec<Part> {
+    match content {
+        Some(Value::String(text)) if !text.is_empty() => vec![Part::Text(text)],
+        Some(Value::Array(items)) => items
+            .into_iter()
+            .filter_map(|mut item| match item.get("type").and_then(|t| t.as_str()) {
+                Some("text") => take_string(&mut item, "/text").filter(|t| !t.is_empty()).map(Part::Text),
+                Some("image_url") => {
+                    let url = take_string(&mut item, "/image_url/url").or_else(|| take_string(&mut item, "/image_url"))?;
+                    Some(match split_data_url(url) {
+                        Ok((media_type, data)) => Part::InlineImage { media_type, data },
+                        Err(url) => Part::ImageUrl(url),
+                    })
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn claude_blocks_to_parts(blocks: Vec<Value>, tool_names: &HashMap<String, String>) -> Vec<Part> {
+    let mut parts = Vec::new();
+    for mut block in blocks {
+        let kind = string(block.get("type"));
+        match kind.as_deref() {
+            Some("text") => parts.extend(take_string(&mut block, "/text").map(Part::Text)),
+            Some("image") => match block.pointer("/source/type").and_then(|t| t.as_str()) {
+                Some("base64") => parts.push(Part::InlineImage {
+                    media_type: take_string(&mut block, "/source/media_type").unwrap_or_else(|| "image/jpeg".to_string()),
+                    data: take_string(&mut block, "/source/data").unwrap_or_default(),
+                }),
+                Some("url") => parts.extend(take_string(&mut block, "/source/url").map(Part::ImageUrl)),
+                _ => {}
+            },
+            Some("thinking") => parts.push(Part::Thinking {
+                text: take_string(&mut block, "/thinking").unwrap_or_default(),
+                signature: take_string(&mut block, "/signature"),
+            }),
+            Some("tool_use") => parts.push(Part::ToolCall {
+                id: take_string(&mut block, "/id").unwrap_or_default(),
+                name: take_string(&mut block, "/name").unwrap_or_default(),
+                arguments: take(&mut block, "/input").unwrap_or_else(|| json!({})),
+            }),
+            Some("tool_result") => {
+                let id = take_string(&mut block, "/tool_use_id").unwrap_or_default();
+                let name = tool_names.get(&id).cloned().unwrap_or_else(|| "unknown".to_string());
+                parts.push(Part::ToolResult { id, name, content: result_text(take(&mut block, "/content")) });
+            }
+            Some("server_tool_use") if block["name"] == "code_execution" => parts.push(Part::CodeExecution {
+                id: take_string(&mut block, "/id").unwrap_or_default(),
+                code: take_string(&mut block, "/input/code").unwrap_or_default(),
+                output: String::new(),
+                failed: false,
+            }),
+            Some("code_execution_tool_result") => {
+                let id = block.get("tool_use_id").and_then(|i| i.as_str());
+                let execution = parts.iter_mut().rev().find(|part| matches!(part, Part::CodeExecution { id: call, .. } if Some(call.as_str()) == id));
+                if let Some(Part::CodeExecution { output, failed, .. }) = execution {
+                    let text = |field: &str| string(block.pointer(&format!("/content/{}", field))).unwrap_or_default();
+                    *output = format!("{}{}", text("stdout"), text("stderr"));
+                    *failed = block.pointer("/content/return_code").and_then(|c| c.as_i64()).is_some_and(|c| c != 0);
+                }
+            }
+            _ => {}
+        }
+    }
+    parts
+}
+
+/// Gemini parts; function calls without an id get one derived from the call and its position
+fn gemini_parts_to_parts(parts: Vec<Value>) -> Vec<Part> {
+    let mut converted = Vec::new();
+    let mut position = 0;
+    for mut part in parts {
+        if let Some(text) = take_string(&mut part, "/text") {
+            converted.push(if part.get("thought").and_then(|t| t.as_bool()) == Some(true) {
+                Part::Thinking { text, signature: take_string(&mut part, "/thoughtSignature") }
+            } else {
+                Part::Text(text)
+            });
+        } else if let Some(mut inline) = take(&mut part, "/inlineData") {
+            converted.push(Part::InlineImage {
+                media_type: take_string(&mut inline, "/mimeType").unwrap_or_else(|| "image/jpeg".to_string()),
+                data: take_string(&mut inline, "/data").unwrap_or_default(),
+            });
+        } else if let Some(uri) = take_string(&mut part, "/fileData/fileUri") {
+            converted.push(Part::ImageUrl(uri));
+        } else if let Some(mut call) = take(&mut part, "/functionCall") {
+            let name = take_string(&mut call, "/name").unwrap_or_default();
+            let arguments = take(&mut call, "/args").unwrap_or_else(|| json!({}));
+            let id = match take_string(&mut call, "/id").filter(|id| !id.is_empty()) {
+                Some(id) => id,
+                None => synthetic_tool_call_id(&name, &arguments, position),
+            };
+            position += 1;
+            converted.push(Part::ToolCall { id, name, arguments });
+        } else if let Some(mut response) = take(&mut part, "/functionResponse") {
+            // The `content` we wrapped a result in on the way to Gemini, or the whole response as JSON
+            let content = match take(&mut response, "/response") {
+                Some(Value::Object(mut fields)) if fields.len() == 1 && fields.get("content").is_some_and(|c| c.is_string()) => {
+                    result_text(fields.remove("content"))
+                }
+                other => result_text(other),
+            };
+            converted.push(Part::ToolResult {
+                id: take_string(&mut response, "/id").unwrap_or_default(),
+                name: take_string(&mut response, "/name").unwrap_or_default(),
+                content,
+            });
+        } else if let Some(code) = take_string(&mut part, "/executableCode/code") {
+            let executions = converted.iter().filter(|p| matches!(p, Part::CodeExecution { .. })).count();
+            converted.push(Part::CodeExecution {
+                id: synthetic_tool_call_id("code_interpreter", &json!(code), executions),
+                code,
+                output: String::new(),
+                failed: false,
+            });
+        } else if let Some(mut result) = take(&mut part, "/codeExecutionResult") {
+            if let Some(Part::CodeExecution { output, failed, .. }) = converted.iter_mut().rev().find(|p| matches!(p, Part::CodeExecution { .. })) {
+                *output = take_string(&mut result, "/output").unwrap_or_default();
+                *failed = result.get("outcome").and_then(|o| o.as_str()).is_some_and(|o| o != "OUTCOME_OK");
+            }
+        }
+    }
+    converted
+}
+
+impl UnifiedResponse {
+    pub fn from_openai(mut response: Value) -> Result<Self> {
+        let candidates = take_array(&mut response, "/choices")
+            .into_iter()
+            .map(|mut choice| {
+                let finish_reason = FinishReason::from_openai(choice.get("finish_reason").and_then(|r| r.as_str()));
+                let mut message = take(&mut choice, "/message").unwrap_or(Value::Null);
+                let mut parts = Vec::new();
+                if let Some(reasoning) = take_string(&mut message, "/reasoning_content") {
+                    parts.push(Part::Thinking { text: reasoning, signature: None });
+                }
+                parts.extend(openai_content_parts(take(&mut message, "/content")));
+                for mut call in take_array(&mut message, "/tool_calls") {
+                    parts.push(Part::ToolCall {
+                        id: take_string(&mut call, "/id").unwrap_or_default(),
+                        name: take_string(&mut call, "/function/name").unwrap_or_default(),
+                        arguments: tool_arguments(take(&mut call, "/function/arguments")),
+                    });
+                }
+                let citations = take_array(&mut message, "/annotations")
+                    .into_iter()
+                    .filter_map(|mut annotation| {
+                        Some(Citation {
+                            url: take_string(&mut annotation, "/url_citation/url")?,
+                            title: take_string(&mut annotation, "/url_citation/title"),
+                        })
+                    })
+                    .collect();
+                Candidate { parts, finish_reason, citations }
+            })
+            .collect();
+        let usage = |field: &str| response.pointer(&format!("/usage/{}", field)).and_then(|t| t.as_u64()).unwrap_or(0);
+        Ok(Self {
+            model: string(response.get("model")).unwrap_or_default(),
+            candidates: or_empty(candidates),
+            usage: Usage { input_tokens: usage("prompt_tokens"), output_tokens: usage("completion_tokens") },
+        })
+    }
+
+    pub fn from_claude(mut response: Value) -> Result<Self> {
+        let blocks = take_array(&mut response, "/content");
+        let citations = blocks
+            .iter()
+            .filter_map(|block| block.get("citations")?.as_array())
+            .flatten()
+            .filter_map(|citation| Some(Citation { url: string(citation.get("url"))?, title: string(citation.get("title")) }))
+            .collect();
+        let usage = |field: &str| response.pointer(&format!("/usage/{}", field)).and_then(|t| t.as_u64()).unwrap_or(0);
+        Ok(Self {
+            model: string(response.get("model")).unwrap_or_default(),
+            candidates: vec![Candidate {
+                parts: claude_blocks_to_parts(blocks, &HashMap::new()),
+                finish_reason: FinishReason::from_claude(response.get("stop_reason").and_then(|r| r.as_str())),
+                citations,
+            }],
+            usage: Usage { input_tokens: usage("input_tokens"), output_tokens: usage("output_tokens") },
+        })
+    }
+
+    /// A blocked prompt is an error
+    pub fn from_gemini(mut response: Value) -> Result<Self> {
+        if let Some(blocked) = gemini_prompt_blocked(&response) {
+            return Err(blocked.into());
+        }
+        let candidates = take_array(&mut response, "/candidates")
+            .into_iter()
+            .map(|mut candidate| {
+                let citations = candidate
+                    .pointer("/groundingMetadata/groundingChunks")
+                    .and_then(|c| c.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|chunk| {
+                        let web = chunk.get("web")?;
+                        Some(Citation { url: string(web.get("uri"))?, title: string(web.get("title")) })
+                    })
+                    .collect();
+                let parts = gemini_parts_to_parts(take_array(&mut candidate, "/content/parts"));
+                let has_tool_calls = parts.iter().any(|p| matches!(p, Part::ToolCall { .. }));
+                let reason = gemini_finish_reason(candidate.get("finishReason").and_then(|r| r.as_str()), has_tool_calls);
+                Candidate { parts, finish_reason: FinishReason::from_openai(Some(reason)), citations }
+            })
+            .collect();
+        let usage = |field: &str| response.pointer(&format!("/usageMetadata/{}", field)).and_then(|t| t.as_u64()).unwrap_or(0);
+        Ok(Self {
+            model: string(response.get("modelVersion")).unwrap_or_default(),
+            candidates: or_empty(candidates),
+            usage: Usage { input_tokens: usage("promptTokenCount"), output_tokens: usage("candidatesTokenCount") },
+        })
+    }
+}
+
+// ============================================================================
+// Writers
+// ============================================================================
+
+fn openai_tool_call(id: String, name: String, arguments: &Value) -> Value {
+    json!({"id": id, "type": "function", "function": {"name": name, "arguments": arguments.to_string()}})
+}
+
+fn openai_image(url: String) -> Value {
+    json!({"type": "image_url", "image_url": {"url": url}})
+}
+
+/// OpenAI messages for one message: its tool results first, as they answer the
+/// previous turn, then its content split at name markers
+fn openai_messages(message: Message) -> Vec<Value> {
+    let role = match message.role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+    };
+    let mut messages = Vec::new();
+    let mut content = Vec::new();
+    let mut tool_calls = Vec::new();
+    for part in message.parts {
+        match part {
+            Part::Text(text) => content.push(json!({"type": "text", "text": text})),
+            Part::InlineImage { media_type, mut data } => {
+                data.insert_str(0, &format!("data:{};base64,", media_type));
+                content.push(openai_image(data));
+            }
+            Part::ImageUrl(url) => content.push(openai_image(url)),
+            Part::ToolCall { id, name, arguments } => tool_calls.push(openai_tool_call(id, name, &arguments)),
+            Part::ToolResult { id, content, .. } => messages.push(json!({"role": "tool", "tool_call_id": id, "content": content})),
+            // Earlier reasoning and code the provider ran have no OpenAI request form
+            Part::Thinking { .. } | Part::CodeExecution { .. } => {}
+        }
+    }
+    messages.extend(split_named_messages(role, content, tool_calls));
+    messages
+}
+
+/// Build OpenAI messages from content parts, starting a new message at each
+/// name marker; tool calls go on the last message, even one without content
+fn split_named_messages(role: &str, parts: Vec<Value>, tool_calls: Vec<Value>) -> Vec<Value> {
+    let mut segments: Vec<(Option<String>, Vec<Value>)> = Vec::new();
+    for part in parts {
+        let marker = part.get("text").and_then(|t| t.as_str()).and_then(parse_name_marker);
+        match (marker, segments.last_mut()) {
+            (Some(name), _) => segments.push((Some(name.to_string()), Vec::new())),
+            (None, Some(segment)) => segment.1.push(part),
+            (None, None) => segments.push((None, vec![part])),
+        }
+    }
+    if !tool_calls.is_empty() && segments.is_empty() {
+        segments.push((None, Vec::new()));
+    }
+
+    let last = segments.len().saturating_sub(1);
+    let has_tool_calls = !tool_calls.is_empty();
+    let mut tool_calls = Some(tool_calls).filter(|_| has_tool_calls);
+    segments
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (_, parts))| !parts.is_empty() || (*i == last && has_tool_calls))
+        .map(|(i, (name, mut parts))| {
+            let content = match parts.as_mut_slice() {
+                [] => Value::Null,
+                [part] if part.get("type").and_then(|t| t.as_str()) == Some("text") => part["text"].take(),
+                _ => json!(parts),
+            };
+            let mut message = json!({"role": role, "content": content});
+            if let Some(name) = name {
+                message["name"] = json!(name);
+            }
+            if i == last {
+                if let Some(calls) = tool_calls.take() {
+                    message["tool_calls"] = json!(calls);
+                }
+            }
+            message
+        })
+        .collect()
+}
+
+fn claude_blocks(parts: Vec<Part>) -> Vec<Value> {
+    let mut blocks = Vec::new();
+    for part in parts {
+        match part {
+            Part::Text(text) if !text.is_empty() => blocks.push(json!({"type": "text", "text": text})),
+            Part::Text(_) => {}
+            Part::InlineImage { media_type, data } => {
+                blocks.push(json!({"type": "image", "source": {"type": "base64", "media_type": media_type, "data": data}}))
+            }
+            Part::ImageUrl(url) => blocks.push(json!({"type": "image", "source": {"type": "url", "url": url}})),
+            // Claude rejects earlier reasoning it cannot verify
+            Part::Thinking { text, signature: Some(signature) } => {
+                blocks.push(json!({"type": "thinking", "thinking": text, "signature": signature}))
+            }
+            Part::Thinking { .. } => {}
+            Part::ToolCall { id, name, arguments } => blocks.push(json!({"type": "tool_use", "id": id, "name": name, "input": arguments})),
+            Part::ToolResult { id, content, .. } => blocks.push(json!({"type": "tool_result", "tool_use_id": id, "content": content})),
+            Part::CodeExecution { id, code, output, failed } => {
+                blocks.push(json!({"type": "server_tool_use", "id": id, "name": "code_execution", "input": {"code": code}}));
+                let (stdout, stderr) = if failed { (String::new(), output) } else { (output, String::new()) };
+                blocks.push(json!({
+                    "type": "code_execution_tool_result",
+                    "tool_use_id": id,
+                    "content": {
+                        "type": "code_execution_result",
+                        "stdout": stdout,
+                        "stderr": stderr,
+                        "return_code": if failed { 1 } else { 0 },
+                    },
+                }));
+            }
+        }
+    }
+    blocks
+}
+
+fn gemini_parts(parts: Vec<Part>) -> Vec<Value> {
+    let mut converted = Vec::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => converted.push(json!({"text": text})),
+            Part::InlineImage { media_type, data } => converted.push(json!({"inlineData": {"mimeType": media_type, "data": data}})),
+            Part::ImageUrl(url) => converted.push(json!({"fileData": {"mimeType": "image/jpeg", "fileUri": url}})),
+            // Another provider's reasoning cannot be verified by Gemini
+            Part::Thinking { .. } => {}
+            Part::ToolCall { name, arguments, .. } => converted.push(json!({"functionCall": {"name": name, "args": arguments}})),
+            Part::ToolResult { name, content, .. } => {
+                converted.push(json!({"functionResponse": {"name": name, "response": {"content": content}}}))
+            }
+            Part::CodeExecution { code, output, failed, .. } => {
+                converted.push(json!({"executableCode": {"language": "PYTHON", "code": code}}));
+                let outcome = if failed { "OUTCOME_FAILED" } else { "OUTCOME_OK" };
+                converted.push(json!({"codeExecutionResult": {"outcome": outcome, "output": output}}));
+            }
+        }
+    }
+    converted
+}
+
+impl UnifiedRequest {
+    pub fn into_openai(self) -> Value {
+        // One system message per instruction, so several round-trip
+        let mut messages: Vec<Value> = self.system.into_iter().map(|text| json!({"role": "system", "content": text})).collect();
+        messages.extend(self.messages.into_iter().flat_map(openai_messages));
+
+        let mut request = json!({"messages": messages});
+        if let Some(model) = self.model {
+            request["model"] = json!(model);
+        }
+        let generation = self.generation;
+        // OpenAI has no top_k and rejects requests that carry it
+        for (field, value) in [
+            ("max_tokens", generation.max_tokens),
+            ("temperature", generation.temperature),
+            ("top_p", generation.top_p),
+            ("n", generation.candidate_count),
+            ("presence_penalty", generation.presence_penalty),
+            ("frequency_penalty", generation.frequency_penalty),
+            ("seed", generation.seed),
+        ] {
+            if let Some(value) = value {
+                request[field] = value;
+            }
+        }
+        if !generation.stop.is_empty() {
+            request["stop"] = json!(generation.stop);
+        }
+        match generation.response_format {
+            Some(ResponseFormat::Json) => request["response_format"] = json!({"type": "json_object"}),
+            Some(ResponseFormat::JsonSchema(schema)) => request["response_format"] = openai_json_schema(schema),
+            None => {}
+        }
+        if let Some(user) = self.user {
+            request["user"] = json!(user);
+        }
+        if let Some(stream) = self.stream {
+            request["stream"] = json!(stream);
+        }
+        if !self.tools.is_empty() {
+            let tools: Vec<Value> = self
+                .tools
+                .into_iter()
+                .map(|tool| match tool {
+                    Tool::Function { name, description, parameters } => {
+                        let mut function = json!({"name": name});
+                        if let Some(description) = description {
+                            function["description"] = description;
+                        }
+                        if let Some(parameters) = parameters {
+                            function["parameters"] = parameters;
+                        }
+                        json!({"type": "function", "function": function})
+                    }
+                    Tool::CodeExecution => json!({"type": "code_interpreter"}),
+                })
+                .collect();
+            request["tools"] = json!(tools);
+        }
+        if let Some(choice) = self.tool_choice {
+            request["tool_choice"] = match choice {
+                ToolChoice::Auto => json!("auto"),
+                ToolChoice::None => json!("none"),
+                ToolChoice::Required => json!("required"),
+                ToolChoice::Function(name) => json!({"type": "function", "function": {"name": name}}),
+            };
+        }
+        if let Some(parallel) = self.parallel_tool_calls {
+            request["parallel_tool_calls"] = json!(parallel);
+        }
+        request
+    }
+
+    pub fn into_claude(self) -> Value {
+        let mut messages: Vec<Value> = Vec::new();
+        for message in self.messages {
+            let mut blocks = claude_blocks(message.parts);
+            if blocks.is_empty() {
+                continue;
+            }
+            let role = match message.role {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            // A user turn right after one holding only tool results continues it
+            if message.role == Role::User {
+                if let Some(previous) = messages.last_mut().filter(|m| m["role"] == "user") {
+                    let previous_blocks = previous["content"].as_array_mut().expect("blocks");
+                    if previous_blocks.iter().all(|b| b["type"] == "tool_result") {
+                        previous_blocks.append(&mut blocks);
+                        continue;
+                    }
+                }
+            }
+            messages.push(json!({"role": role, "content": blocks}));
+        }
+
+        let generation = self.generation;
+        let mut request = json!({
+            "model": self.model.as_deref().unwrap_or("claude-3-opus"),
+            "max_tokens": generation.max_tokens.unwrap_or(json!(DEFAULT_MAX_TOKENS)),
+            "messages": messages,
+        });
+        let mut system = self.system;
+        match system.len() {
+            0 => {}
+            1 => request["system"] = json!(system.remove(0)),
+            _ => request["system"] = json!(system.into_iter().map(|text| json!({"type": "text", "text": text})).collect::<Vec<_>>()),
+        }
+        for (field, value) in [("temperature", generation.temperature), ("top_p", generation.top_p), ("top_k", generation.top_k)] {
+            if let Some(value) = value {
+                request[field] = value;
+            }
+        }
+        if !generation.stop.is_empty() {
+            request["stop_sequences"] = json!(generation.stop);
+        }
+        if let Some(user) = self.user {
+            request["metadata"] = json!({"user_id": user});
+        }
+        if let Some(stream) = self.stream {
+            request["stream"] = json!(stream);
+        }
+        if !self.tools.is_empty() {
+            let tools: Vec<Value> = self
+                .tools
+                .into_iter()
+                .map(|tool| match tool {
+                    Tool::Function { name, description, parameters } => {
+                        let mut tool = json!({"name": name, "input_schema": parameters.unwrap_or_else(|| json!({"type": "object"}))});
+                        if let Some(description) = description {
+                            tool["description"] = description;
+                        }
+                        tool
+                    }
+                    Tool::CodeExecution => json!({"type": CLAUDE_CODE_EXECUTION_TOOL, "name": "code_execution"}),
+                })
+                .collect();
+            request["tools"] = json!(tools);
+        }
+        let mut choice = match self.tool_choice {
+            Some(ToolChoice::Auto) => Some(json!({"type": "auto"})),
+            Some(ToolChoice::None) => Some(json!({"type": "none"})),
+            Some(ToolChoice::Required) => Some(json!({"type": "any"})),
+            Some(ToolChoice::Function(name)) => Some(json!({"type": "tool", "name": name})),
+            None if self.parallel_tool_calls == Some(false) => Some(json!({"type": "auto"})),
+            None => None,
+        };
+        if let Some(mut choice) = choice.take() {
+            if self.parallel_tool_calls == Some(false) {
+                choice["disable_parallel_tool_use"] = json!(true);
+            }
+            request["tool_choice"] = choice;
+        }
+        request
+    }
+
+    pub fn into_gemini(self) -> Value {
+        let mut request = json!({});
+        if !self.system.is_empty() {
+            request["systemInstruction"] = json!({"parts": self.system.into_iter().map(|text| json!({"text": text})).collect::<Vec<_>>()});
+        }
+        let mut contents: Vec<Value> = Vec::new();
+        for message in self.messages {
+            let role = match message.role {
+                Role::User => "user",
+                Role::Assistant => "model",
+            };
+            let mut parts = gemini_parts(message.parts);
+            if parts.is_empty() {
+                continue;
+            }
+            // Consecutive messages from one side share a turn
+            match contents.last_mut().filter(|c| c["role"] == role) {
+                Some(content) => content["parts"].as_array_mut().expect("parts").append(&mut parts),
+                None => contents.push(json!({"role": role, "parts": parts})),
+            }
+        }
+        request["contents"] = json!(contents);
+
+        let generation = self.generation;
+        let mut config = Map::new();
+        for (field, value) in [
+            ("maxOutputTokens", generation.max_tokens),
+            ("temperature", generation.temperature),
+            ("topP", generation.top_p),
+            ("topK", generation.top_k),
+            ("candidateCount", generation.candidate_count),
+            ("presencePenalty", generation.presence_penalty),
+            ("frequencyPenalty", generation.frequency_penalty),
+            ("seed", generation.seed),
+        ] {
+            if let Some(value) = value {
+                config.insert(field.to_string(), value);
+            }
+        }
+        if !generation.stop.is_empty() {
+            config.insert("stopSequences".to_string(), json!(generation.stop));
+        }
+        if let Some(format) = generation.response_format {
+            config.insert("responseMimeType".to_string(), json!("application/json"));
+            if let ResponseFormat::JsonSchema(schema) = format {
+                config.insert("responseSchema".to_string(), crate::gemini_schema::to_gemini(&schema).schema);
+            }
+        }
+        if !config.is_empty() {
+            request["generationConfig"] = Value::Object(config);
+        }
+
+        let code_execution = self.tools.contains(&Tool::CodeExecution);
+        let declarations: Vec<Value> = self
+            .tools
+            .into_iter()
+            .filter_map(|tool| match tool {
+                Tool::Function { name, description, parameters } => {
+                    let mut declaration = json!({"name": name});
+                    if let Some(description) = description {
+                        declaration["description"] = description;
+                    }
+                    if let Some(parameters) = parameters.as_ref().and_then(crate::gemini_schema::parameters) {
+                        declaration["parameters"] = parameters.schema;
+                    }
+                    Some(declaration)
+                }
+                Tool::CodeExecution => None,
+            })
+            .collect();
+        let mut tools = Vec::new();
+        if !declarations.is_empty() {
+            tools.push(json!({"functionDeclarations": declarations}));
+        }
+        if code_execution {
+            tools.push(json!({"codeExecution": {}}));
+        }
+        if !tools.is_empty() {
+            request["tools"] = json!(tools);
+        }
+        if let Some(choice) = self.tool_choice {
+            let config = match choice {
+                ToolChoice::Auto => json!({"mode": "AUTO"}),
+                ToolChoice::None => json!({"mode": "NONE"}),
+                ToolChoice::Required => json!({"mode": "ANY"}),
+                ToolChoice::Function(name) => json!({"mode": "ANY", "allowedFunctionNames": [name]}),
+            };
+            request["toolConfig"] = json!({"functionCallingConfig": config});
+        }
+        request
+    }
+}
+
+impl UnifiedResponse {
+    /// One choice per candidate; text parts are joined as a stream would deliver them
+    pub fn into_openai(self) -> Value {
+        let choices: Vec<Value> = self
+            .candidates
+            .into_iter()
+            .enumerate()
+            .map(|(index, candidate)| {
+                let mut content = String::new();
+                let mut tool_calls = Vec::new();
+                let mut executions = Vec::new();
+                for part in candidate.parts {
+                    match part {
+                        Part::Text(text) => content.push_str(&text),
+                        Part::ToolCall { id, name, arguments } => tool_calls.push(openai_tool_call(id, name, &arguments)),
+                        // Shaped like an Assistants run step
+                        Part::CodeExecution { id, code, output, .. } => executions.push(json!({
+                            "id": id,
+                            "type": "code_interpreter",
+                            "code_interpreter": {"input": code, "outputs": [{"type": "logs", "logs": output}]},
+                        })),
+                        Part::InlineImage { .. } | Part::ImageUrl(_) | Part::Thinking { .. } | Part::ToolResult { .. } => {}
+                    }
+                }
+                let mut message = json!({"role": "assistant", "content": content});
+                if !tool_calls.is_empty() {
+                    message["tool_calls"] = json!(tool_calls);
+                }
+                set_annotations(&mut message, candidate.citations.iter().map(|c| (c.url.as_str(), c.title.as_deref())));
+                if !executions.is_empty() {
+                    message["code_interpreter_calls"] = json!(executions);
+                }
+                json!({"index": index, "message": message, "finish_reason": candidate.finish_reason.openai()})
+            })
+            .collect();
+        json!({
+            "id": format!("chatcmpl-{}", Uuid::new_v4()),
+            "object": "chat.completion",
+            "created": chrono::Utc::now().timestamp(),
+            "model": self.model,
+            "choices": choices,
+            "usage": {
+                "prompt_tokens": self.usage.input_tokens,
+                "completion_tokens": self.usage.output_tokens,
+                "total_tokens": self.usage.input_tokens + self.usage.output_tokens,
+            },
+        })
+    }
+
+    /// The first candidate; Claude has room for only one
+    pub fn into_claude(self) -> Value {
+        let candidate = self.candidates.into_iter().next().unwrap_or_default();
+        // Reasoning in a response is shown as is; the signature is only checked when it is sent back
+        let content: Vec<Value> = candidate
+            .parts
+            .into_iter()
+            .flat_map(|part| match part {
+                Part::Thinking { text, signature } => {
+                    vec![json!({"type": "thinking", "thinking": text, "signature": signature.unwrap_or_default()})]
+                }
+                part => claude_blocks(vec![part]),
+            })
+            .collect();
+        json!({
+            "id": format!("msg_{}", Uuid::new_v4()),
+            "type": "message",
+            "role": "assistant",
+            "content": content,
+            "model": self.model,
+            "stop_reason": candidate.finish_reason.claude(),
+            "usage": {"input_tokens": self.usage.input_tokens, "output_tokens": self.usage.output_tokens},
+        })
+    }
+
+    pub fn into_gemini(self) -> Value {
+        let candidates: Vec<Value> = self
+            .candidates
+            .into_iter()
+            .enumerate()
+            .map(|(index, candidate)| {
+                let parts: Vec<Value> = candidate
+                    .parts
+                    .into_iter()
+                    .flat_map(|part| match part {
+                        Part::Thinking { text, signature } => {
+                            let mut thought = json!({"text": text, "thought": true});
+                            if let Some(signature) = signature {
+                                thought["thoughtSignature"] = json!(signature);
+                            }
+                            vec![thought]
+                        }
+                        part => gemini_parts(vec![part]),
+                    })
+                    .collect();
+                json!({
+                    "index": index,
+                    "content": {"role": "model", "parts": parts},
+                    "finishReason": candidate.finish_reason.gemini(),
+                })
+            })
+            .collect();
+        json!({
+            "candidates": candidates,
+            "usageMetadata": {
+                "promptTokenCount": self.usage.input_tokens,
+                "candidatesTokenCount": self.usage.output_tokens,
+                "totalTokenCount": self.usage.input_tokens + self.usage.output_tokens,
+            },
+            "modelVersion": self.model,
+        })
+    }
+}
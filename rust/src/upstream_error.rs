@@ -0,0 +1,72 @@
+/*!
+ * Upstream Errors
+ *
+ * Provider adapters report an upstream that answered with a failure status as
+ * an `UpstreamError` carrying that status. Everything that reacts to failed
+ * calls (stream error events, key health, adaptive concurrency) sorts them
+ * with `classify`, which reads the status from the error, and timeouts from
+ * the HTTP client's error, instead of searching the message text.
+ */
+
+use reqwest::StatusCode;
+use std::fmt;
+
+/// An upstream call answered with a non-success status
+#[derive(Debug, Clone)]
+pub struct UpstreamError {
+    pub status: StatusCode,
+    /// The upstream's error body
+    pub message: String,
+    /// Failed while opening a stream
+    pub stream: bool,
+}
+
+impl UpstreamError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            stream: false,
+        }
+    }
+
+    pub fn stream(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            stream: true,
+            ..Self::new(status, message)
+        }
+    }
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.stream { "Stream API call" } else { "API call" };
+        write!(f, "{} failed ({}): {}", kind, self.status, self.message)
+    }
+}
+
+impl std::error::Error for UpstreamError {}
+
+/// How an upstream call failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The upstream answered with this status
+    Status(u16),
+    /// No answer in time
+    Timeout,
+    /// Anything else: connection errors, unreadable responses, local failures
+    Other,
+}
+
+/// Classify a failed call by the first `UpstreamError` or timeout in its chain of causes
+pub fn classify(error: &anyhow::Error) -> Failure {
+    for cause in error.chain() {
+        if let Some(upstream) = cause.downcast_ref::<UpstreamError>() {
+            return Failure::Status(upstream.status.as_u16());
+        }
+        if cause.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_timeout()) {
+            return Failure::Timeout;
+        }
+    }
+    Failure::Other
+}
@@ -0,0 +1,217 @@
+/*!
+ * Usage Rollup
+ *
+ * Agent sessions make dozens of calls; profiling them one request at a time
+ * misses the picture. With `usage_rollup` configured, every chat request that
+ * carries an `x-conversation-id` or tags (`x-aiproxy-tags`, `metadata`) adds
+ * its tokens, cost, upstream latency and provider to running totals for that
+ * conversation and for each tag. `GET /v1/usage?conversation_id=...` (or
+ * `?tag=key=value`) returns the totals of the caller's own requests. Cost is
+ * priced from the model registry's `input_price` / `output_price`; requests to
+ * models without a price are counted separately. Totals live in the shared
+ * store for `ttl_secs` from a session's first request, so every instance adds
+ * to the same numbers. Each client's scopes are indexed so `purge_client` can
+ * delete all of its totals.
+ */
+
+use crate::cluster::SharedStore;
+use crate::config::UsageRollupConfig;
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What one request is rolled up under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    Conversation(String),
+    /// `key=value`
+    Tag(String),
+}
+
+impl Scope {
+    /// The conversation and every tag of a request
+    pub fn of(conversation_id: Option<&str>, tags: &BTreeMap<String, String>) -> Vec<Self> {
+        conversation_id
+            .map(|id| Self::Conversation(id.to_string()))
+            .into_iter()
+            .chain(tags.iter().map(|(key, value)| Self::Tag(format!("{}={}", key, value))))
+            .collect()
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Self::Conversation(id) => format!("conversation:{}", id),
+            Self::Tag(tag) => format!("tag:{}", tag),
+        }
+    }
+}
+
+/// One upstream request's contribution
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub provider: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub latency: Duration,
+    /// `None` when the model has no price
+    pub cost_usd: Option<f64>,
+}
+
+/// Cost in USD of a request, from prices per million tokens
+pub fn cost_usd(input_tokens: u64, output_tokens: u64, input_price: Option<f64>, output_price: Option<f64>) -> Option<f64> {
+    if input_price.is_none() && output_price.is_none() {
+        return None;
+    }
+    let price = |tokens: u64, per_million: Option<f64>| tokens as f64 * per_million.unwrap_or(0.0) / 1_000_000.0;
+    Some(price(input_tokens, input_price) + price(output_tokens, output_price))
+}
+
+/// Totals kept for every scope, besides the per-provider ones
+const COUNTERS: [&str; 8] = [
+    "requests",
+    "input_tokens",
+    "output_tokens",
+    "latency_ms",
+    "cost_micro_usd",
+    "unpriced_requests",
+    "first_at",
+    "last_at",
+];
+
+pub struct UsageRollup {
+    store: Arc<dyn SharedStore>,
+    config: UsageRollupConfig,
+}
+
+impl UsageRollup {
+    pub fn new(store: Arc<dyn SharedStore>, config: &UsageRollupConfig) -> Self {
+        Self {
+            store,
+            config: config.clone(),
+        }
+    }
+
+    /// Shared store key prefix of a client's totals for a scope; hashed, as tag values are free text
+    fn prefix(client_id: &str, scope: &Scope) -> String {
+        let digest = Sha256::digest(format!("{}\n{}", client_id, scope.label()).as_bytes());
+        format!("usage:{:x}", digest)
+    }
+
+    /// Shared store key of the index of a client's scopes
+    fn client_key(client_id: &str) -> String {
+        format!("usage_client:{:x}", Sha256::digest(client_id.as_bytes()))
+    }
+
+    /// Key prefixes of a client's totals and the providers counted under each (some may have expired since)
+    async fn indexed(&self, client_id: &str) -> Result<BTreeMap<String, BTreeSet<String>>> {
+        match self.store.get(&Self::client_key(client_id)).await? {
+            Some(stored) => serde_json::from_str(&stored).context("Stored usage index is not a prefix map"),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Add a request to the totals of each of its scopes
+    pub async fn record(&self, client_id: &str, scopes: &[Scope], turn: &Turn) -> Result<()> {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut index = self.indexed(client_id).await?;
+        for scope in scopes {
+            let prefix = Self::prefix(client_id, scope);
+            index.entry(prefix.clone()).or_default().insert(turn.provider.clone());
+            let mut counters = vec![
+                ("requests".to_string(), 1),
+                ("input_tokens".to_string(), turn.input_tokens as i64),
+                ("output_tokens".to_string(), turn.output_tokens as i64),
+                ("latency_ms".to_string(), turn.latency.as_millis() as i64),
+                (format!("provider:{}:requests", turn.provider), 1),
+                (format!("provider:{}:tokens", turn.provider), (turn.input_tokens + turn.output_tokens) as i64),
+            ];
+            match turn.cost_usd {
+                // Counted in micro-dollars: the store only adds integers
+                Some(cost) => counters.push(("cost_micro_usd".to_string(), (cost * 1_000_000.0).round() as i64)),
+                None => counters.push(("unpriced_requests".to_string(), 1)),
+            }
+            for (counter, delta) in counters {
+                self.store.incr(&format!("{}:{}", prefix, counter), delta, ttl).await?;
+            }
+            self.store.set_if_absent(&format!("{}:first_at", prefix), &now, ttl).await?;
+            self.store.set(&format!("{}:last_at", prefix), &now, Some(ttl)).await?;
+        }
+        // Refreshed with every request, so it outlives the totals it lists
+        self.store.set(&Self::client_key(client_id), &serde_json::to_string(&index)?, Some(ttl)).await
+    }
+
+    /// Delete every total of a client; returns how many scopes were indexed
+    pub async fn purge_client(&self, client_id: &str) -> Result<usize> {
+        let index = self.indexed(client_id).await?;
+        for (prefix, providers) in &index {
+            let provider_counters = providers
+                .iter()
+                .flat_map(|provider| [format!("provider:{}:requests", provider), format!("provider:{}:tokens", provider)]);
+            for counter in COUNTERS.iter().map(|c| c.to_string()).chain(provider_counters) {
+                self.store.delete(&format!("{}:{}", prefix, counter)).await?;
+            }
+        }
+        self.store.delete(&Self::client_key(client_id)).await?;
+        Ok(index.len())
+    }
+
+    /// A client's totals for a scope, with the mix over `providers`; `None` when nothing was recorded
+    pub async fn rollup(&self, client_id: &str, scope: &Scope, providers: &[String]) -> Result<Option<Value>> {
+        let prefix = Self::prefix(client_id, scope);
+        let counter = |name: String| {
+            let key = format!("{}:{}", prefix, name);
+            async move { Ok::<_, anyhow::Error>(self.store.get(&key).await?.and_then(|v| v.parse::<i64>().ok()).unwrap_or(0)) }
+        };
+
+        let requests = counter("requests".to_string()).await?;
+        if requests == 0 {
+            return Ok(None);
+        }
+        let input_tokens = counter("input_tokens".to_string()).await?;
+        let output_tokens = counter("output_tokens".to_string()).await?;
+        let latency_ms = counter("latency_ms".to_string()).await?;
+        let unpriced = counter("unpriced_requests".to_string()).await?;
+        let cost_usd = (unpriced < requests).then_some(counter("cost_micro_usd".to_string()).await? as f64 / 1_000_000.0);
+
+        let mut mix = Map::new();
+        for provider in providers {
+            let provider_requests = counter(format!("provider:{}:requests", provider)).await?;
+            if provider_requests > 0 {
+                let tokens = counter(format!("provider:{}:tokens", provider)).await?;
+                mix.insert(
+                    provider.clone(),
+                    json!({
+                        "requests": provider_requests,
+                        "share": provider_requests as f64 / requests as f64,
+                        "tokens": tokens,
+                    }),
+                );
+            }
+        }
+
+        let (kind, id) = match scope {
+            Scope::Conversation(id) => ("conversation_id", id),
+            Scope::Tag(tag) => ("tag", tag),
+        };
+        Ok(Some(json!({
+            kind: id,
+            "requests": requests,
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens,
+            "total_tokens": input_tokens + output_tokens,
+            "cost_usd": cost_usd,
+            "unpriced_requests": unpriced,
+            "providers": mix,
+            "latency_ms": {
+                "total": latency_ms,
+                "mean": latency_ms as f64 / requests as f64,
+            },
+            "first_at": self.store.get(&format!("{}:first_at", prefix)).await?,
+            "last_at": self.store.get(&format!("{}:last_at", prefix)).await?,
+        })))
+    }
+}
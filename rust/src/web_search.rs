@@ -0,0 +1,198 @@
+/*!
+ * Web Search
+ *
+ * Bridges built-in web search tools (OpenAI `web_search`, Gemini
+ * `googleSearch` grounding) to a configured search API, so web-grounded
+ * requests work whatever provider serves them. The built-in tool is swapped
+ * for a plain `web_search` function, the proxy runs the searches the model
+ * asks for, and the sources it was given come back as OpenAI `url_citation`
+ * annotations — the same shape Gemini grounding and Claude citations are
+ * normalized to.
+ */
+
+use crate::config::WebSearchConfig;
+use crate::convert_detailed::set_annotations;
+use crate::tool_loop::{run_loop, ToolCall};
+use anyhow::{Context, Result};
+use futures::Future;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+/// Name of the function tool the model searches with
+pub const SEARCH_TOOL: &str = "web_search";
+
+/// Longest snippet of a result passed to the model, in characters
+const MAX_SNIPPET_CHARS: usize = 1500;
+
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// One search hit
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub content: String,
+}
+
+fn is_search_tool(tool: &Value) -> bool {
+    let typed = matches!(
+        tool.get("type").and_then(|t| t.as_str()),
+        Some("web_search" | "web_search_preview")
+    );
+    typed || ["googleSearch", "google_search", "googleSearchRetrieval", "google_search_retrieval"]
+        .iter()
+        .any(|key| tool.get(key).is_some())
+}
+
+/// Whether an OpenAI-format request asks for a built-in web search
+pub fn requested(body: &Value) -> bool {
+    body.get("web_search_options").is_some()
+        || body
+            .get("tools")
+            .and_then(|t| t.as_array())
+            .is_some_and(|tools| tools.iter().any(is_search_tool))
+}
+
+/// Replace the built-in search tools with the proxy's `web_search` function
+pub fn bridge_request(body: &mut Value) {
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+    obj.remove("web_search_options");
+    let tools = obj.entry("tools").or_insert_with(|| json!([]));
+    let Some(tools) = tools.as_array_mut() else {
+        return;
+    };
+    tools.retain(|tool| !is_search_tool(tool));
+    tools.push(json!({
+        "type": "function",
+        "function": {
+            "name": SEARCH_TOOL,
+            "description": "Search the web for current information. Cite the sources you use.",
+            "parameters": {
+                "type": "object",
+                "properties": {"query": {"type": "string", "description": "Search query"}},
+                "required": ["query"],
+            },
+        },
+    }));
+}
+
+/// Search results as the tool result the model reads, sources numbered
+pub fn format_results(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return "No results found.".to_string();
+    }
+    results
+        .iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let snippet: String = result.content.chars().take(MAX_SNIPPET_CHARS).collect();
+            format!("[{}] {}\nURL: {}\n{}\n", index + 1, result.title, result.url, snippet)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub struct WebSearch {
+    config: WebSearchConfig,
+    client: Client,
+}
+
+impl WebSearch {
+    pub fn new(config: &WebSearchConfig, client: Client) -> Self {
+        Self {
+            config: config.clone(),
+            client,
+        }
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .timeout(SEARCH_TIMEOUT)
+            .json(&json!({"query": query, "max_results": self.config.max_results}));
+        if let Some(ref api_key) = self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("Search failed ({}): {}", status, response.text().await.unwrap_or_default());
+        }
+        let body: Value = response.json().await?;
+        let results = body
+            .get("results")
+            .and_then(|r| r.as_array())
+            .context("Search response has no results array")?
+            .iter()
+            .filter_map(|result| {
+                Some(SearchResult {
+                    url: result.get("url")?.as_str()?.to_string(),
+                    title: result.get("title").and_then(|t| t.as_str()).unwrap_or_default().to_string(),
+                    content: result.get("content").and_then(|c| c.as_str()).unwrap_or_default().to_string(),
+                })
+            })
+            .take(self.config.max_results)
+            .collect();
+        Ok(results)
+    }
+
+    /// Answer a web search request, running the model's searches here; `call`
+    /// sends one OpenAI-format request upstream
+    pub async fn run<F, Fut>(&self, mut body: Value, call: F) -> Result<Value>
+    where
+        F: Fn(Value) -> Fut,
+        Fut: Future<Output = Result<Value>>,
+    {
+        bridge_request(&mut body);
+        let sources = Mutex::new(Vec::new());
+
+        let execute = |tool_call: ToolCall| {
+            let sources = &sources;
+            async move {
+                let arguments: Value = serde_json::from_str(&tool_call.arguments).unwrap_or(Value::Null);
+                let Some(query) = arguments.get("query").and_then(|q| q.as_str()) else {
+                    return json!({"error": "query is required"}).to_string();
+                };
+                match self.search(query).await {
+                    Ok(results) => {
+                        let text = format_results(&results);
+                        sources.lock().unwrap().extend(results);
+                        text
+                    }
+                    Err(e) => {
+                        warn!("Web search for {:?} failed: {}", query, e);
+                        json!({"error": e.to_string()}).to_string()
+                    }
+                }
+            }
+        };
+        let mut response = run_loop(body, self.config.max_iterations, |name| name == SEARCH_TOOL, execute, call).await?;
+
+        let sources = sources.into_inner().unwrap();
+        if let Some(message) = response.pointer_mut("/choices/0/message") {
+            // Keep citations the provider added itself, then the sources searched here
+            let existing: Vec<(String, Option<String>)> = message
+                .get("annotations")
+                .and_then(|a| a.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|annotation| {
+                    let citation = annotation.get("url_citation")?;
+                    let title = citation.get("title").and_then(|t| t.as_str()).map(String::from);
+                    Some((citation.get("url")?.as_str()?.to_string(), title))
+                })
+                .collect();
+            let searched = sources.iter().map(|s| (s.url.as_str(), Some(s.title.as_str()).filter(|t| !t.is_empty())));
+            let citations = existing.iter().map(|(url, title)| (url.as_str(), title.as_deref())).chain(searched);
+            set_annotations(message, citations);
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,80 @@
+/*!
+ * Alerts Tests
+ *
+ * Unit tests for webhook payloads, alert dedup, error-rate spikes and quota thresholds.
+ */
+
+use aiclient2api_rust::alerts::*;
+use aiclient2api_rust::config::{AlertsConfig, WebhookFormat};
+use std::time::{Duration, Instant};
+
+fn alert(subject: &str) -> Alert {
+    Alert {
+        kind: AlertKind::CircuitOpen,
+        subject: subject.to_string(),
+        message: "failed 3 times in a row".to_string(),
+    }
+}
+
+#[test]
+fn test_payload_shapes() {
+    let alert = alert("https://api.example.com");
+    assert_eq!(
+        payload(WebhookFormat::Slack, &alert, 0)["text"],
+        "[circuit_open] https://api.example.com: failed 3 times in a row"
+    );
+    assert_eq!(
+        payload(WebhookFormat::Discord, &alert, 4)["content"],
+        "[circuit_open] https://api.example.com: failed 3 times in a row (4 repeats suppressed)"
+    );
+    let generic = payload(WebhookFormat::Generic, &alert, 4);
+    assert_eq!(generic["kind"], "circuit_open");
+    assert_eq!(generic["subject"], "https://api.example.com");
+    assert_eq!(generic["suppressed"], 4);
+}
+
+#[test]
+fn test_repeats_are_held_back_and_counted() {
+    let alerter = Alerter::new(&AlertsConfig::default(), reqwest::Client::new());
+    let start = Instant::now();
+
+    assert_eq!(alerter.admit(&alert("a"), start), Some(0));
+    assert_eq!(alerter.admit(&alert("a"), start + Duration::from_secs(10)), None);
+    assert_eq!(alerter.admit(&alert("a"), start + Duration::from_secs(20)), None);
+    assert_eq!(alerter.admit(&alert("b"), start + Duration::from_secs(20)), Some(0), "other subjects are not repeats");
+    assert_eq!(alerter.admit(&alert("a"), start + Duration::from_secs(601)), Some(2));
+}
+
+#[test]
+fn test_error_rate_spike_needs_enough_calls_in_the_window() {
+    let config = AlertsConfig {
+        error_rate_threshold: 0.5,
+        error_rate_window_secs: 10,
+        error_rate_min_requests: 4,
+        ..Default::default()
+    };
+    let alerter = Alerter::new(&config, reqwest::Client::new());
+
+    assert!(alerter.record_call_at("openai", false, 0).is_none());
+    assert!(alerter.record_call_at("openai", false, 1).is_none());
+    assert!(alerter.record_call_at("openai", true, 2).is_none());
+    let spike = alerter.record_call_at("openai", true, 3).expect("2 of 4 calls failed");
+    assert_eq!(spike.kind, AlertKind::ErrorRateSpike);
+    assert_eq!(spike.subject, "openai");
+
+    // The failures fall out of the window
+    assert!(alerter.record_call_at("openai", true, 11).is_none());
+    assert!(alerter.record_call_at("claude", false, 11).is_none(), "providers are counted separately");
+}
+
+#[test]
+fn test_budget_thresholds_fire_once_when_crossed() {
+    let thresholds = [0.8, 1.0];
+    assert!(crossed_thresholds(&thresholds, 7, 10).is_empty());
+    assert_eq!(crossed_thresholds(&thresholds, 8, 10), vec![0.8]);
+    assert!(crossed_thresholds(&thresholds, 9, 10).is_empty());
+    assert_eq!(crossed_thresholds(&thresholds, 10, 10), vec![1.0]);
+    assert!(crossed_thresholds(&thresholds, 11, 10).is_empty());
+    assert_eq!(crossed_thresholds(&thresholds, 1, 1), vec![0.8, 1.0]);
+    assert!(crossed_thresholds(&thresholds, 5, 0).is_empty());
+}
@@ -0,0 +1,69 @@
+/*!
+ * Model Alias Tests
+ *
+ * Tests for aliases with fallback targets and parameter overrides.
+ */
+
+use aiclient2api_rust::aliases;
+use aiclient2api_rust::config::{AliasTarget, Config, ModelAlias};
+use aiclient2api_rust::config_validation::{self, Severity};
+use serde_json::json;
+use std::sync::Mutex;
+
+fn fast() -> ModelAlias {
+    serde_json::from_value(json!([
+        {"model": "llama-3.1-8b-instant", "provider": "openai-custom"},
+        {"model": "gpt-4o-mini", "params": {"temperature": 0.3}}
+    ]))
+    .unwrap()
+}
+
+#[test]
+fn test_aliases_parse_as_names_or_targets() {
+    let config: Config = serde_json::from_value(json!({"model_aliases": {"smart": "gpt-4o"}})).unwrap();
+    assert_eq!(config.model_aliases["smart"], ModelAlias::from("gpt-4o"));
+    assert_eq!(config.model_aliases["smart"].targets(), vec![AliasTarget::new("gpt-4o")]);
+
+    let targets = fast().targets();
+    assert_eq!(targets[0].provider.as_deref(), Some("openai-custom"));
+    assert_eq!(targets[1].params["temperature"], 0.3);
+    // A plain alias is written back as a plain name
+    assert_eq!(serde_json::to_value(ModelAlias::from("gpt-4o")).unwrap(), json!("gpt-4o"));
+}
+
+#[tokio::test]
+async fn test_targets_are_tried_in_order() {
+    let body = json!({"model": "fast", "stream": true, "temperature": 1.0, "messages": []});
+    let sent = Mutex::new(Vec::new());
+    let (response, model) = aliases::run("fast", &fast().targets(), body, |target, body| {
+        sent.lock().unwrap().push((target.provider.clone(), body.clone()));
+        async move {
+            match target.model.as_str() {
+                "llama-3.1-8b-instant" => Err(anyhow::anyhow!("API call failed (503 Service Unavailable)")),
+                _ => Ok(json!({"answered": true})),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(response, json!({"answered": true}));
+    assert_eq!(model, "gpt-4o-mini");
+
+    let sent = sent.into_inner().unwrap();
+    assert_eq!(sent[0].0.as_deref(), Some("openai-custom"));
+    assert_eq!(sent[0].1["temperature"], 1.0);
+    assert_eq!(sent[1].1, json!({"model": "gpt-4o-mini", "temperature": 0.3, "messages": []}));
+}
+
+#[test]
+fn test_alias_checks() {
+    let content = "{\n  \"model_aliases\": {\n    \"fast\": [{\"model\": \"gpt-4o-mini\", \"provider\": \"groq\"}],\n    \"empty\": []\n  }\n}";
+    let (config, _) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    let diagnostics = config_validation::validate(&config, "config.json", Some(content), None);
+    let mut found: Vec<_> = diagnostics.iter().map(|d| (d.path.as_str(), d.severity)).collect();
+    found.sort_by_key(|(path, _)| *path);
+    assert_eq!(
+        found,
+        vec![("model_aliases.empty", Severity::Error), ("model_aliases.fast[0].provider", Severity::Error)]
+    );
+}
@@ -0,0 +1,46 @@
+/*!
+ * Audit Log Tests
+ *
+ * Unit tests for the hash-chained admin audit log.
+ */
+
+use aiclient2api_rust::audit::*;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_record_and_query() {
+    let log = AuditLog::open(None).await.unwrap();
+
+    log.record("alice", "provider.disable", "openai-custom/uuid-1", json!({"is_healthy": true}), json!({"is_healthy": false}))
+        .await
+        .unwrap();
+    log.record("bob", "cache.clear", "claude-kiro-oauth", json!({"entries": 4}), json!({"entries": 0}))
+        .await
+        .unwrap();
+
+    let all = log.query(None, 10).await;
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[0].action, "cache.clear");
+    assert_eq!(all[1].seq, 1);
+
+    let filtered = log.query(Some("provider.disable"), 10).await;
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].actor, "alice");
+
+    assert_eq!(log.verify().await, Ok(2));
+}
+
+#[tokio::test]
+async fn test_tampering_is_detected() {
+    let log = AuditLog::open(None).await.unwrap();
+
+    log.record("alice", "provider.disable", "a/1", json!(null), json!(null)).await.unwrap();
+    log.record("alice", "provider.enable", "a/1", json!(null), json!(null)).await.unwrap();
+
+    let mut entries = log.query(None, 10).await;
+    entries.reverse();
+    assert!(verify_chain(&entries).is_ok());
+
+    entries[0].actor = "mallory".to_string();
+    assert_eq!(verify_chain(&entries), Err(1));
+}
@@ -0,0 +1,61 @@
+/*!
+ * Built-in Tools Tests
+ *
+ * Unit tests for Anthropic computer use, bash and text editor tool checks.
+ */
+
+use aiclient2api_rust::builtin_tools::*;
+use aiclient2api_rust::common::ModelProvider;
+use serde_json::json;
+
+fn computer_use_request() -> serde_json::Value {
+    json!({
+        "model": "claude-sonnet-4-20250514",
+        "max_tokens": 1024,
+        "messages": [{"role": "user", "content": "Open the settings"}],
+        "tools": [
+            {"type": "computer_20250124", "name": "computer", "display_width_px": 1280, "display_height_px": 800},
+            {"type": "bash_20250124", "name": "bash"},
+            {"type": "text_editor_20250429", "name": "str_replace_based_edit_tool"},
+            {"name": "lookup", "input_schema": {"type": "object"}}
+        ]
+    })
+}
+
+#[test]
+fn test_builtin_tools_are_found_with_their_beta_flags() {
+    let request = computer_use_request();
+    assert_eq!(requested(&request), vec!["computer_20250124", "bash_20250124", "text_editor_20250429"]);
+    assert!(validate(&request).is_ok());
+    // Both 2025-01-24 tools share one flag; the newer text editor needs none
+    assert_eq!(beta_flags(&request), vec!["computer-use-2025-01-24"]);
+
+    let plain = json!({"tools": [{"name": "lookup", "input_schema": {"type": "object"}}]});
+    assert!(requested(&plain).is_empty());
+    assert!(beta_flags(&plain).is_empty());
+}
+
+#[test]
+fn test_malformed_builtin_tools_are_rejected() {
+    let mut request = computer_use_request();
+    request["tools"][0]["display_height_px"] = json!(0);
+    let err = validate(&request).unwrap_err().to_string();
+    assert!(err.contains("display_height_px"), "{}", err);
+
+    let mut request = computer_use_request();
+    request["tools"][1]["name"] = json!("shell");
+    let err = validate(&request).unwrap_err().to_string();
+    assert!(err.contains("bash_20250124 tool must be named bash"), "{}", err);
+}
+
+#[test]
+fn test_only_claude_api_backends_support_builtin_tools() {
+    assert!(supported(Some(&ModelProvider::ClaudeCustom)));
+    assert!(!supported(Some(&ModelProvider::ClaudeKiroOAuth)));
+    assert!(!supported(Some(&ModelProvider::GeminiCliOAuth)));
+    assert!(!supported(None));
+
+    let message = unsupported_message("gemini-cli-oauth", &["computer_20250124", "bash_20250124"]);
+    assert!(message.contains("computer_20250124, bash_20250124"));
+    assert!(message.contains("gemini-cli-oauth"));
+}
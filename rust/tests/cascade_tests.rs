@@ -0,0 +1,94 @@
+/*!
+ * Cascade Tests
+ *
+ * Unit tests for escalating from cheap to costly models on poor answers.
+ */
+
+use aiclient2api_rust::cascade::{self, is_refusal};
+use aiclient2api_rust::config::CascadeConfig;
+use aiclient2api_rust::ModelProtocol;
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+
+fn cascade(judge: Option<&str>) -> CascadeConfig {
+    serde_json::from_value(json!({
+        "model": "auto",
+        "steps": ["mini", "large"],
+        "min_chars": 10,
+        "judge": judge
+    }))
+    .unwrap()
+}
+
+fn claude_answer(model: &str, text: &str, stop_reason: &str) -> Value {
+    json!({
+        "type": "message",
+        "model": model,
+        "content": [{"type": "text", "text": text}],
+        "stop_reason": stop_reason
+    })
+}
+
+fn request() -> Value {
+    json!({"model": "auto", "max_tokens": 256, "messages": [{"role": "user", "content": "Name three primary colors"}]})
+}
+
+#[tokio::test]
+async fn test_good_cheap_answer_is_kept() {
+    let asked = Mutex::new(Vec::new());
+    let call = |model: String, _body: Value| {
+        asked.lock().unwrap().push(model.clone());
+        async move { Ok(claude_answer(&model, "Red, yellow and blue.", "end_turn")) }
+    };
+
+    let (response, step) = cascade::run(&cascade(None), ModelProtocol::Claude, request(), call).await.unwrap();
+    assert_eq!(step, "mini");
+    assert_eq!(response["model"], "auto");
+    assert_eq!(asked.into_inner().unwrap(), vec!["mini"]);
+}
+
+#[tokio::test]
+async fn test_poor_cheap_answers_escalate() {
+    for (text, stop_reason) in [("Red.", "end_turn"), ("I'm sorry, but I can't help with that.", "end_turn"), ("Red, yellow and", "max_tokens")] {
+        let call = |model: String, _body: Value| async move {
+            match model.as_str() {
+                "mini" => Ok(claude_answer(&model, text, stop_reason)),
+                _ => Ok(claude_answer(&model, "Red, yellow and blue.", "end_turn")),
+            }
+        };
+        let (response, step) = cascade::run(&cascade(None), ModelProtocol::Claude, request(), call).await.unwrap();
+        assert_eq!(step, "large", "{:?} should escalate", text);
+        assert_eq!(response["content"][0]["text"], "Red, yellow and blue.");
+    }
+
+    // A failing step escalates; the last step's error is returned
+    let call = |model: String, _body: Value| async move { Err::<Value, _>(anyhow!("{} unavailable", model)) };
+    let err = cascade::run(&cascade(None), ModelProtocol::Claude, request(), call).await.unwrap_err();
+    assert_eq!(err.to_string(), "large unavailable");
+}
+
+#[tokio::test]
+async fn test_judge_score_decides_escalation() {
+    for (score, expected) in [("8", "mini"), ("Score: 4/10", "large"), ("no idea", "large")] {
+        let call = |model: String, body: Value| async move {
+            match model.as_str() {
+                "judge" => {
+                    assert!(body["messages"][0]["content"].as_str().unwrap().contains("Answer:\nRed, green and blue light."));
+                    Ok(claude_answer(&model, score, "end_turn"))
+                }
+                _ => Ok(claude_answer(&model, "Red, green and blue light.", "end_turn")),
+            }
+        };
+        let (_, step) = cascade::run(&cascade(Some("judge")), ModelProtocol::Claude, request(), call).await.unwrap();
+        assert_eq!(step, expected, "judge said {:?}", score);
+    }
+}
+
+#[test]
+fn test_refusal_detection() {
+    assert!(is_refusal("I can’t help with that request.", &[]));
+    assert!(is_refusal("  Sorry, I can't do that", &[]));
+    assert!(!is_refusal("Here is how you can help yourself: ...", &[]));
+    assert!(is_refusal("As an AI model, I decline.", &["as an ai model".to_string()]));
+}
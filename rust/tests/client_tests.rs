@@ -0,0 +1,171 @@
+/*!
+ * Embedded Client Tests
+ *
+ * Unit tests for sending requests through `UnifiedClient` without the server.
+ */
+
+#![cfg(feature = "openai")]
+
+use aiclient2api_rust::config::{Config, ModelRoute};
+use aiclient2api_rust::convert::ChatRequest;
+use aiclient2api_rust::{ModelProtocol, UnifiedClient};
+use futures::StreamExt;
+use httpmock::prelude::*;
+use serde_json::json;
+
+fn config(server: &MockServer) -> Config {
+    Config {
+        model_provider: "openai-custom".to_string(),
+        openai_api_key: Some("sk-test".to_string()),
+        openai_base_url: Some(server.base_url()),
+        ..Config::default()
+    }
+}
+
+fn claude_request(model: &str) -> ChatRequest {
+    ChatRequest::new(
+        ModelProtocol::Claude,
+        json!({
+            "model": model,
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "Hi"}]
+        }),
+    )
+}
+
+fn openai_completion() -> serde_json::Value {
+    json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "model": "gpt-4o-mini",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": "Hello there"},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5}
+    })
+}
+
+#[tokio::test]
+async fn test_chat_converts_request_and_response() {
+    let server = MockServer::start_async().await;
+    let upstream = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/chat/completions")
+                .json_body_partial(r#"{"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "Hi"}]}"#);
+            then.status(200).json_body(openai_completion());
+        })
+        .await;
+
+    let mut config = config(&server);
+    config.model_aliases.insert("fast".to_string(), "gpt-4o-mini".into());
+    let client = UnifiedClient::new(config).await.unwrap();
+
+    let response = client.chat(claude_request("fast")).await.unwrap();
+    upstream.assert_async().await;
+    assert_eq!(response["type"], "message");
+    assert_eq!(response["content"][0]["text"], "Hello there");
+    assert_eq!(response["stop_reason"], "end_turn");
+}
+
+#[tokio::test]
+async fn test_chat_rejects_unconfigured_provider() {
+    let server = MockServer::start_async().await;
+    let mut config = config(&server);
+    config.model_routes.push(ModelRoute {
+        model: "gpt-*".to_string(),
+        provider: "openai-custom".to_string(),
+    });
+    let client = UnifiedClient::new(config).await.unwrap();
+
+    assert!(client.chat(ChatRequest::OpenAI(json!({"messages": []}))).await.is_err());
+    let err = client.chat_with_provider("claude-custom", claude_request("gpt-4o")).await.unwrap_err();
+    assert!(err.to_string().contains("not configured"));
+    assert_eq!(client.routing().await.providers(), vec!["openai-custom"]);
+}
+
+#[tokio::test]
+async fn test_repeated_requests_are_served_from_the_cache() {
+    let server = MockServer::start_async().await;
+    let upstream = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/chat/completions");
+            then.status(200).json_body(openai_completion());
+        })
+        .await;
+
+    let mut config = config(&server);
+    config.response_cache = Some(serde_json::from_value(json!({})).unwrap());
+    let client = UnifiedClient::new(config).await.unwrap();
+
+    let first = client.chat(claude_request("gpt-4o-mini")).await.unwrap();
+    let second = client.chat(claude_request("gpt-4o-mini")).await.unwrap();
+    assert_eq!(first, second);
+    upstream.assert_hits_async(1).await;
+}
+
+#[tokio::test]
+async fn test_stream_is_simulated_for_buffered_only_providers() {
+    let server = MockServer::start_async().await;
+    let upstream = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/chat/completions")
+                .matches(|request| !String::from_utf8_lossy(request.body.as_deref().unwrap_or_default()).contains("stream"));
+            then.status(200).json_body(openai_completion());
+        })
+        .await;
+
+    let mut config = config(&server);
+    config.simulated_streaming = serde_json::from_value(json!({
+        "providers": ["openai-custom"],
+        "pacing": {"chunk_chars": 5, "interval_ms": 0}
+    }))
+    .unwrap();
+    let client = UnifiedClient::new(config).await.unwrap();
+
+    let mut request = claude_request("gpt-4o-mini");
+    request.body_mut()["stream"] = json!(true);
+    let events: Vec<_> = client.chat_stream(request).await.unwrap().collect().await;
+    upstream.assert_async().await;
+
+    let text: String = events
+        .iter()
+        .filter_map(|event| event.as_ref().unwrap().pointer("/delta/text").and_then(|t| t.as_str()))
+        .collect();
+    assert_eq!(text, "Hello there");
+    assert_eq!(events.iter().filter(|e| e.as_ref().unwrap()["type"] == "content_block_delta").count(), 3);
+    assert_eq!(events.last().unwrap().as_ref().unwrap()["type"], "message_stop");
+}
+
+#[tokio::test]
+async fn test_stream_only_provider_answers_buffered_requests() {
+    let server = MockServer::start_async().await;
+    let chunks = [
+        json!({"id": "chatcmpl-2", "model": "gpt-4o-mini", "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hello"}, "finish_reason": null}]}),
+        json!({"id": "chatcmpl-2", "model": "gpt-4o-mini", "choices": [{"index": 0, "delta": {"content": " there"}, "finish_reason": "stop"}]}),
+        json!({"id": "chatcmpl-2", "model": "gpt-4o-mini", "choices": [], "usage": {"prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5}}),
+    ];
+    let sse: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect::<String>() + "data: [DONE]\n\n";
+    let upstream = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/chat/completions")
+                .json_body_partial(r#"{"stream": true, "stream_options": {"include_usage": true}}"#);
+            then.status(200).header("content-type", "text/event-stream").body(sse);
+        })
+        .await;
+
+    let mut config = config(&server);
+    config.stream_aggregation.providers = vec!["openai-custom".to_string()];
+    let client = UnifiedClient::new(config).await.unwrap();
+
+    let response = client.chat(claude_request("gpt-4o-mini")).await.unwrap();
+    upstream.assert_async().await;
+    assert_eq!(response["content"][0]["text"], "Hello there");
+    assert_eq!(response["stop_reason"], "end_turn");
+    assert_eq!(response["usage"]["input_tokens"], 3);
+    assert_eq!(response["usage"]["output_tokens"], 2);
+}
@@ -0,0 +1,50 @@
+/*!
+ * Cluster Shared State Tests
+ *
+ * Unit tests for the in-memory shared store and provider health sync.
+ */
+
+use aiclient2api_rust::cluster::*;
+use aiclient2api_rust::config::{KeyHealthConfig, ProviderConfig};
+use aiclient2api_rust::pool_manager::ProviderPoolManager;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_memory_counter_resets_after_ttl() {
+    let store = MemoryStore::new();
+
+    assert_eq!(store.incr("rl:a", 1, Duration::from_millis(50)).await.unwrap(), 1);
+    assert_eq!(store.incr("rl:a", 2, Duration::from_millis(50)).await.unwrap(), 3);
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!(store.incr("rl:a", 1, Duration::from_millis(50)).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_memory_set_if_absent() {
+    let store = MemoryStore::new();
+
+    assert!(store.set_if_absent("idem:1", "a", Duration::from_secs(60)).await.unwrap());
+    assert!(!store.set_if_absent("idem:1", "b", Duration::from_secs(60)).await.unwrap());
+    assert_eq!(store.get("idem:1").await.unwrap().as_deref(), Some("a"));
+
+    store.delete("idem:1").await.unwrap();
+    assert_eq!(store.get("idem:1").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_provider_health_sync() {
+    let provider: ProviderConfig = serde_json::from_value(serde_json::json!({ "uuid": "p1" })).unwrap();
+    let pool_manager = ProviderPoolManager::new(HashMap::from([("openai-custom".to_string(), vec![provider])]), &KeyHealthConfig::default());
+    let store = MemoryStore::new();
+
+    // Another instance tripped the circuit
+    publish_provider_health(&store, "openai-custom", "p1", false).await.unwrap();
+    sync_provider_health(&store, &pool_manager).await.unwrap();
+    assert!(!pool_manager.get_provider("openai-custom", "p1").await.unwrap().is_healthy);
+
+    publish_provider_health(&store, "openai-custom", "p1", true).await.unwrap();
+    sync_provider_health(&store, &pool_manager).await.unwrap();
+    assert!(pool_manager.get_provider("openai-custom", "p1").await.unwrap().is_healthy);
+}
@@ -0,0 +1,104 @@
+/*!
+ * Adaptive Concurrency Tests
+ *
+ * Unit tests for the AIMD limiter: probing upward, backing off on overload
+ * and latency spikes, and queueing for a free slot.
+ */
+
+use aiclient2api_rust::concurrency::*;
+use aiclient2api_rust::config::AdaptiveConcurrencyConfig;
+use aiclient2api_rust::upstream_error::UpstreamError;
+use reqwest::StatusCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn limiter(initial_limit: usize, max_limit: usize) -> Arc<AdaptiveLimiter> {
+    Arc::new(AdaptiveLimiter::new(&AdaptiveConcurrencyConfig {
+        enabled: true,
+        initial_limit,
+        min_limit: 1,
+        max_limit,
+        backoff_ratio: 0.5,
+        latency_tolerance: 2.0,
+        queue_timeout_ms: 50,
+    }))
+}
+
+#[test]
+fn test_healthy_calls_probe_upward_to_max() {
+    let limiter = limiter(1, 3);
+    let mut first = limiter.try_acquire().unwrap();
+    assert!(limiter.try_acquire().is_none());
+
+    // +1/limit per success: one success at limit 1 adds a slot
+    first.record(Outcome::Success(Duration::from_millis(100)));
+    assert_eq!(limiter.limit(), 2);
+    let mut permits = vec![first, limiter.try_acquire().unwrap()];
+
+    // Further successes stay capped at max_limit
+    for _ in 0..20 {
+        let mut permit = permits.pop().unwrap();
+        permit.record(Outcome::Success(Duration::from_millis(100)));
+        drop(permit);
+        permits.push(limiter.try_acquire().unwrap());
+    }
+    assert_eq!(limiter.limit(), 3);
+    permits.push(limiter.try_acquire().unwrap());
+    assert!(limiter.try_acquire().is_none());
+}
+
+#[test]
+fn test_overload_backs_off_once_per_congestion_event() {
+    let limiter = limiter(8, 8);
+    let mut permits: Vec<Permit> = (0..8).map(|_| limiter.try_acquire().unwrap()).collect();
+
+    // Every in-flight call hitting the same 429 wave only halves the limit once
+    for permit in permits.iter_mut() {
+        permit.record(Outcome::Overloaded);
+    }
+    assert_eq!(limiter.limit(), 4);
+    drop(permits);
+
+    // A call started after the cut can cut again; unrelated errors never do
+    let mut permit = limiter.try_acquire().unwrap();
+    permit.record(Outcome::Ignored);
+    assert_eq!(limiter.limit(), 4);
+    let mut permit = limiter.try_acquire().unwrap();
+    permit.record(Outcome::Overloaded);
+    assert_eq!(limiter.limit(), 2);
+
+    assert!(is_overload_error(&UpstreamError::new(StatusCode::TOO_MANY_REQUESTS, "slow down").into()));
+    assert!(is_overload_error(&UpstreamError::new(StatusCode::SERVICE_UNAVAILABLE, "").into()));
+    assert!(!is_overload_error(&UpstreamError::new(StatusCode::BAD_REQUEST, "rate limit must be positive").into()));
+}
+
+#[test]
+fn test_latency_spike_counts_as_overload() {
+    let limiter = limiter(4, 8);
+    let mut permit = limiter.try_acquire().unwrap();
+    permit.record(Outcome::Success(Duration::from_millis(200)));
+    drop(permit);
+    assert_eq!(limiter.limit(), 4);
+
+    let mut permit = limiter.try_acquire().unwrap();
+    permit.record(Outcome::Success(Duration::from_millis(1000)));
+    assert_eq!(limiter.limit(), 2);
+}
+
+#[tokio::test]
+async fn test_acquire_waits_for_a_released_slot() {
+    let limiter = limiter(1, 1);
+    let held = limiter.try_acquire().unwrap();
+
+    // Nothing is released: the request is rejected after the queue timeout
+    assert!(limiter.acquire().await.is_err());
+
+    let waiter = {
+        let limiter = limiter.clone();
+        tokio::spawn(async move { limiter.acquire().await.is_ok() })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(held);
+    assert!(waiter.await.unwrap());
+    assert_eq!(limiter.in_flight(), 0);
+}
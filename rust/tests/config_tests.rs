@@ -0,0 +1,136 @@
+/*!
+ * Configuration Tests
+ *
+ * Unit tests for configuration loading and validation.
+ */
+
+use aiclient2api_rust::config::{Config, CONFIG_JSON_ENV};
+use aiclient2api_rust::config_validation::{self, Severity};
+
+fn args(extra: &[&str]) -> Vec<String> {
+    std::iter::once("aiclient2api-rust")
+        .chain(extra.iter().copied())
+        .map(String::from)
+        .collect()
+}
+
+// Environment variables are process-wide, so all env-driven cases share one test
+#[test]
+fn test_config_from_environment_json() {
+    std::env::set_var(
+        CONFIG_JSON_ENV,
+        r#"{"port": 4000, "model_provider": "openai-custom", "openai_api_key": "sk-env"}"#,
+    );
+    let config = Config::load_with_args(&args(&["--config", "does-not-exist.json"])).unwrap();
+    assert_eq!(config.port, 4000);
+    assert_eq!(config.model_provider, "openai-custom");
+    assert_eq!(config.openai_api_key.as_deref(), Some("sk-env"));
+
+    // CLI arguments still override the environment document
+    let config = Config::load_with_args(&args(&["--port", "5000"])).unwrap();
+    assert_eq!(config.port, 5000);
+
+    std::env::set_var(CONFIG_JSON_ENV, "{not json");
+    let err = Config::load_with_args(&args(&[])).unwrap_err();
+    assert!(err.to_string().contains(CONFIG_JSON_ENV));
+
+    std::env::remove_var(CONFIG_JSON_ENV);
+}
+
+#[test]
+fn test_type_mismatch_reports_path_and_line() {
+    let content = "{\n  \"http_client\": {\n    \"connect_timeout_secs\": \"ten\"\n  }\n}";
+    let err = config_validation::parse::<Config>(content, "config.json").unwrap_err();
+    assert_eq!(err.severity, Severity::Error);
+    assert_eq!(err.path, "http_client.connect_timeout_secs");
+    assert_eq!(err.line, Some(3));
+    assert!(err.to_string().starts_with("config.json:3:"));
+    assert!(err.message.contains("expected u64"));
+}
+
+#[test]
+fn test_unknown_fields_are_warnings() {
+    let content = "{\n  \"prot\": 3000,\n  \"logging\": {\"scrub_secret\": false},\n  \"model_registry\": {\"models\": [{\"model\": \"x\", \"vison\": true}]}\n}";
+    let (_, warnings) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    let paths: Vec<_> = warnings.iter().map(|w| (w.path.as_str(), w.line)).collect();
+    assert_eq!(
+        paths,
+        vec![
+            ("prot", Some(2)),
+            ("logging.scrub_secret", Some(3)),
+            ("model_registry.models[0].vison", Some(4)),
+        ]
+    );
+    assert!(warnings.iter().all(|w| w.severity == Severity::Warning));
+}
+
+#[test]
+fn test_cross_field_checks() {
+    let content = "{\n  \"provider_pools\": {\n    \"openai-custom\": [{\"uuid\": \"a\"}, {\"uuid\": \"a\"}],\n    \"openai\": []\n  },\n  \"rate_limit_backend\": \"redis\"\n}";
+    let (config, _) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    let diagnostics = config_validation::validate(&config, "config.json", Some(content), None);
+    let found: Vec<_> = diagnostics.iter().map(|d| (d.path.as_str(), d.line)).collect();
+    assert!(found.contains(&("provider_pools.openai", Some(4))));
+    assert!(found.contains(&("provider_pools.openai-custom[1].uuid", Some(3))));
+    assert!(found.contains(&("rate_limit_backend", Some(6))));
+
+    let valid = Config::default();
+    assert!(config_validation::validate(&valid, "config.json", None, None).is_empty());
+}
+
+#[test]
+fn test_route_to_undefined_provider() {
+    let content = "{\n  \"model_routes\": [\n    {\"model\": \"gpt-*\", \"provider\": \"openai-custom\"},\n    {\"model\": \"claude-*\", \"provider\": \"anthropic\"}\n  ]\n}";
+    let (config, _) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    let diagnostics = config_validation::validate(&config, "config.json", Some(content), None);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path, "model_routes[1].provider");
+    assert_eq!(diagnostics[0].line, Some(4));
+    assert!(diagnostics[0].message.contains("anthropic"));
+}
+
+#[test]
+fn test_router_checks() {
+    let content = "{\n  \"routers\": [\n    {\"type\": \"weighted\", \"model\": \"gpt-*\", \"weights\": {\"openai-custom\": 0}},\n    {\"type\": \"capability\"},\n    {\"type\": \"cost\", \"model\": \"*\", \"prices\": {\"anthropic\": -1.0}}\n  ]\n}";
+    let (config, _) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    let diagnostics = config_validation::validate(&config, "config.json", Some(content), None);
+    let found: Vec<_> = diagnostics.iter().map(|d| (d.path.as_str(), d.severity)).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("routers[0].weights", Severity::Error),
+            ("routers[1]", Severity::Warning),
+            ("routers[2].prices.anthropic", Severity::Error),
+            ("routers[2]", Severity::Error),
+        ]
+    );
+}
+
+#[test]
+fn test_listener_checks() {
+    let content = "{\n  \"port\": 3000,\n  \"listeners\": [\n    {\"port\": 3001, \"protocol\": \"claude\"},\n    {\"port\": 3000, \"protocol\": \"openai\"},\n    {\"port\": 3002, \"protocol\": \"ollama\"}\n  ]\n}";
+    let (config, _) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    let diagnostics = config_validation::validate(&config, "config.json", Some(content), None);
+    let found: Vec<_> = diagnostics.iter().map(|d| (d.path.as_str(), d.line)).collect();
+    assert_eq!(found, vec![("listeners[1].port", Some(5)), ("listeners[2].protocol", Some(6))]);
+    assert_eq!(config.listeners[0].host, None);
+}
+
+#[test]
+fn test_ensemble_checks() {
+    let content = "{\n  \"ensembles\": [\n    {\"model\": \"best\", \"members\": [\"gpt-4o\", \"best\"]},\n    {\"model\": \"best\", \"members\": [], \"judge\": \"best\"}\n  ]\n}";
+    let (config, _) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    let diagnostics = config_validation::validate(&config, "config.json", Some(content), None);
+    let found: Vec<_> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(found, vec!["ensembles[0].members", "ensembles[1].model", "ensembles[1].members", "ensembles[1].judge"]);
+}
+
+#[test]
+fn test_cascade_checks() {
+    let content = "{\n  \"ensembles\": [{\"model\": \"auto\", \"members\": [\"gpt-4o\"]}],\n  \"cascades\": [{\"model\": \"auto\", \"steps\": [\"gpt-4o-mini\"], \"min_score\": 11}]\n}";
+    let (config, _) = config_validation::parse::<Config>(content, "config.json").unwrap();
+    assert!(config.cascades[0].detect_refusals);
+    let diagnostics = config_validation::validate(&config, "config.json", Some(content), None);
+    let found: Vec<_> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(found, vec!["cascades[0].model", "cascades[0].steps", "cascades[0].min_score"]);
+}
@@ -0,0 +1,188 @@
+/*!
+ * Auto Continuation Tests
+ *
+ * Unit tests for continuing answers cut off by the output token limit.
+ */
+
+use aiclient2api_rust::config::AutoContinuationConfig;
+use aiclient2api_rust::continuation::{continuation_request, continued, stitch, ContinuationPolicy};
+use aiclient2api_rust::stream_recovery::ValueStream;
+use aiclient2api_rust::ModelProtocol;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+fn policy(max_continuations: u32) -> ContinuationPolicy {
+    let config: AutoContinuationConfig = serde_json::from_value(json!({"max_continuations": max_continuations})).unwrap();
+    ContinuationPolicy { config, prefill: true, model: "claude-sonnet-4".to_string() }
+}
+
+fn stream(events: Vec<Value>) -> ValueStream {
+    Box::pin(futures::stream::iter(events.into_iter().map(Ok)))
+}
+
+/// A Claude text stream stopping for `stop_reason`
+fn claude_segment(text: &[&str], stop_reason: &str, output_tokens: u64) -> Vec<Value> {
+    let mut events = vec![
+        json!({"type": "message_start", "message": {"id": "msg_1", "type": "message", "role": "assistant", "content": [], "usage": {"input_tokens": 10, "output_tokens": 1}}}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+    ];
+    for piece in text {
+        events.push(json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": piece}}));
+    }
+    events.push(json!({"type": "content_block_stop", "index": 0}));
+    events.push(json!({"type": "message_delta", "delta": {"stop_reason": stop_reason}, "usage": {"output_tokens": output_tokens}}));
+    events.push(json!({"type": "message_stop"}));
+    events
+}
+
+#[test]
+fn test_continuation_request() {
+    let claude = json!({"model": "claude-sonnet-4", "max_tokens": 10, "messages": [{"role": "user", "content": "Write a poem"}]});
+    let prefilled = continuation_request(&claude, ModelProtocol::Claude, "Roses are red,\n", true, "Continue.");
+    assert_eq!(prefilled["messages"][1], json!({"role": "assistant", "content": "Roses are red,"}));
+    assert_eq!(prefilled["messages"].as_array().unwrap().len(), 2);
+
+    let openai = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Write a poem"}, {"role": "assistant", "content": "Title: "}]});
+    let prompted = continuation_request(&openai, ModelProtocol::OpenAI, "Roses", false, "Continue.");
+    assert_eq!(prompted["messages"][1]["content"], "Title: Roses");
+    assert_eq!(prompted["messages"][2], json!({"role": "user", "content": "Continue."}));
+
+    let gemini = json!({"contents": [{"role": "user", "parts": [{"text": "Write a poem"}]}]});
+    let prompted = continuation_request(&gemini, ModelProtocol::Gemini, "Roses", false, "Continue.");
+    assert_eq!(prompted["contents"][1], json!({"role": "model", "parts": [{"text": "Roses"}]}));
+    assert_eq!(prompted["contents"][2]["role"], "user");
+}
+
+#[test]
+fn test_buffered_segments_are_stitched() {
+    let mut response = json!({
+        "type": "message",
+        "content": [{"type": "text", "text": "Roses are red,"}],
+        "stop_reason": "max_tokens",
+        "usage": {"input_tokens": 10, "output_tokens": 5}
+    });
+    let next = json!({
+        "type": "message",
+        "content": [{"type": "text", "text": " violets are blue."}],
+        "stop_reason": "end_turn",
+        "usage": {"input_tokens": 15, "output_tokens": 6}
+    });
+    stitch(&mut response, next, ModelProtocol::Claude);
+    assert_eq!(response["content"], json!([{"type": "text", "text": "Roses are red, violets are blue."}]));
+    assert_eq!(response["stop_reason"], "end_turn");
+    assert_eq!(response["usage"], json!({"input_tokens": 25, "output_tokens": 11}));
+
+    let mut response = json!({"choices": [{"index": 0, "message": {"role": "assistant", "content": "One, "}, "finish_reason": "length"}], "usage": {"completion_tokens": 2}});
+    let next = json!({"choices": [{"index": 0, "message": {"role": "assistant", "content": "two."}, "finish_reason": "stop"}], "usage": {"completion_tokens": 2}});
+    stitch(&mut response, next, ModelProtocol::OpenAI);
+    assert_eq!(response["choices"][0]["message"]["content"], "One, two.");
+    assert_eq!(response["choices"][0]["finish_reason"], "stop");
+    assert_eq!(response["usage"]["completion_tokens"], 4);
+}
+
+#[tokio::test]
+async fn test_stream_segments_are_spliced() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    let reopen = move |body: Value| {
+        seen.lock().unwrap().push(body);
+        async { Ok(stream(claude_segment(&[" violets", " are blue."], "end_turn", 4))) }
+    };
+    let body = json!({"model": "claude-sonnet-4", "max_tokens": 5, "messages": [{"role": "user", "content": "Write a poem"}]});
+    let first = stream(claude_segment(&["Roses", " are red,"], "max_tokens", 5));
+
+    let events: Vec<Value> = continued(first, ModelProtocol::Claude, body, policy(3), reopen)
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["messages"][1], json!({"role": "assistant", "content": "Roses are red,"}));
+
+    let kinds: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(kinds.iter().filter(|k| **k == "message_start").count(), 1);
+    assert_eq!(kinds.iter().filter(|k| **k == "content_block_start").count(), 1);
+    assert_eq!(kinds.iter().filter(|k| **k == "content_block_stop").count(), 1);
+    assert_eq!(kinds.iter().filter(|k| **k == "message_stop").count(), 1);
+    assert!(events.iter().filter(|e| e.get("index").is_some()).all(|e| e["index"] == 0));
+
+    let text: String = events.iter().filter_map(|e| e.pointer("/delta/text").and_then(|t| t.as_str())).collect();
+    assert_eq!(text, "Roses are red, violets are blue.");
+    let end = events.iter().find(|e| e["type"] == "message_delta").unwrap();
+    assert_eq!(end["delta"]["stop_reason"], "end_turn");
+    assert_eq!(end["usage"]["output_tokens"], 9);
+    assert_eq!(end["usage"]["input_tokens"], 20);
+}
+
+#[tokio::test]
+async fn test_continuations_stop_at_the_limit() {
+    let reopen = |_body: Value| async { Ok(stream(claude_segment(&[" more"], "max_tokens", 1))) };
+    let body = json!({"model": "claude-sonnet-4", "max_tokens": 1, "messages": [{"role": "user", "content": "Go"}]});
+    let first = stream(claude_segment(&["Start"], "max_tokens", 1));
+
+    let events: Vec<Value> = continued(first, ModelProtocol::Claude, body, policy(2), reopen)
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+    let text: String = events.iter().filter_map(|e| e.pointer("/delta/text").and_then(|t| t.as_str())).collect();
+    assert_eq!(text, "Start more more");
+    let end = events.iter().find(|e| e["type"] == "message_delta").unwrap();
+    assert_eq!(end["delta"]["stop_reason"], "max_tokens");
+    assert_eq!(end["usage"]["output_tokens"], 3);
+}
+
+#[cfg(feature = "openai")]
+#[tokio::test]
+async fn test_openai_answers_are_continued_with_a_prompt() {
+    use aiclient2api_rust::config::Config;
+    use aiclient2api_rust::convert::ChatRequest;
+    use aiclient2api_rust::UnifiedClient;
+    use httpmock::prelude::*;
+
+    let server = MockServer::start_async().await;
+    let answer = |content: &str, finish_reason: &str| {
+        json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "model": "gpt-4o-mini",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": content}, "finish_reason": finish_reason}],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 3, "total_tokens": 8}
+        })
+    };
+    let first = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/chat/completions").matches(|request| {
+                !String::from_utf8_lossy(request.body.as_deref().unwrap_or_default()).contains("Continue exactly")
+            });
+            then.status(200).json_body(answer("One, two, ", "length"));
+        })
+        .await;
+    let second = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/chat/completions").body_contains("Continue exactly");
+            then.status(200).json_body(answer("three.", "stop"));
+        })
+        .await;
+
+    let config = Config {
+        model_provider: "openai-custom".to_string(),
+        openai_api_key: Some("sk-test".to_string()),
+        openai_base_url: Some(server.base_url()),
+        auto_continuation: Some(serde_json::from_value(json!({})).unwrap()),
+        ..Config::default()
+    };
+    let client = UnifiedClient::new(config).await.unwrap();
+    let request = ChatRequest::new(
+        ModelProtocol::OpenAI,
+        json!({"model": "gpt-4o-mini", "max_tokens": 3, "messages": [{"role": "user", "content": "Count to three"}]}),
+    );
+    let response = client.chat(request).await.unwrap();
+
+    first.assert_async().await;
+    second.assert_async().await;
+    assert_eq!(response["choices"][0]["message"]["content"], "One, two, three.");
+    assert_eq!(response["choices"][0]["finish_reason"], "stop");
+    assert_eq!(response["usage"]["total_tokens"], 16);
+}
@@ -0,0 +1,146 @@
+/*!
+ * Conversation Store Tests
+ *
+ * Unit tests for server-side conversation history.
+ */
+
+use aiclient2api_rust::cluster::MemoryStore;
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::ConversationStoreConfig;
+use aiclient2api_rust::conversations::*;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn store(max_messages: usize) -> ConversationStore {
+    let config = ConversationStoreConfig {
+        ttl_secs: 60,
+        max_messages,
+    };
+    ConversationStore::new(Arc::new(MemoryStore::new()), &config)
+}
+
+fn user(text: &str) -> Value {
+    json!({"role": "user", "content": text})
+}
+
+#[tokio::test]
+async fn test_turns_are_replayed_in_front_of_the_new_message() {
+    let store = store(100);
+
+    let mut first = json!({"model": "gpt-4o", "messages": [{"role": "system", "content": "Be brief."}, user("Hi")]});
+    let conversation = store.begin("conv-1", &mut first).await.unwrap();
+    assert_eq!(conversation.messages.len(), 2);
+    let response = json!({"choices": [{"message": {"role": "assistant", "content": "Hello!"}}]});
+    store.finish(conversation, assistant_message(&response, ModelProtocol::OpenAI)).await;
+
+    let mut second = json!({"model": "gpt-4o", "messages": [user("How are you?")]});
+    store.begin("conv-1", &mut second).await.unwrap();
+    let roles: Vec<&str> = second["messages"].as_array().unwrap().iter().map(|m| m["role"].as_str().unwrap()).collect();
+    assert_eq!(roles, vec!["system", "user", "assistant", "user"]);
+    assert_eq!(second["messages"][2]["content"], "Hello!");
+
+    // A new system message replaces the stored one
+    let mut third = json!({"messages": [{"role": "system", "content": "Be verbose."}, user("Again")]});
+    store.begin("conv-1", &mut third).await.unwrap();
+    let systems: Vec<&Value> = third["messages"].as_array().unwrap().iter().filter(|m| m["role"] == "system").collect();
+    assert_eq!(systems, vec![&json!({"role": "system", "content": "Be verbose."})]);
+
+    let mut other = json!({"messages": [user("Hi")]});
+    assert_eq!(store.begin("conv-2", &mut other).await.unwrap().messages.len(), 1);
+    assert!(valid_id("conv-1") && !valid_id("") && !valid_id("a b") && !valid_id(&"x".repeat(200)));
+}
+
+#[tokio::test]
+async fn test_purge_owner_deletes_only_that_owners_conversations() {
+    let store = store(100);
+    let reply = json!({"role": "assistant", "content": "Hello!"});
+    for (id, owner) in [("conv-a", Some("key:alice")), ("conv-b", Some("key:alice")), ("conv-c", Some("key:bob")), ("conv-d", None)] {
+        let mut body = json!({"messages": [user("Hi")]});
+        let mut conversation = store.begin(id, &mut body).await.unwrap();
+        conversation.owner = owner.map(str::to_string);
+        store.finish(conversation, Some(reply.clone())).await;
+    }
+    assert_eq!(store.owned_by("key:alice").await.unwrap(), vec!["conv-a", "conv-b"]);
+
+    assert_eq!(store.purge_owner("key:alice").await.unwrap(), 2);
+    assert!(store.history("conv-a").await.unwrap().is_empty());
+    assert!(store.history("conv-b").await.unwrap().is_empty());
+    assert_eq!(store.history("conv-c").await.unwrap().len(), 2);
+    assert_eq!(store.history("conv-d").await.unwrap().len(), 2);
+    assert!(store.owned_by("key:alice").await.unwrap().is_empty());
+}
+
+#[test]
+fn test_trim_keeps_system_and_starts_at_a_user_turn() {
+    let messages = vec![
+        json!({"role": "system", "content": "S"}),
+        user("1"),
+        json!({"role": "assistant", "content": null, "tool_calls": [{"id": "t1"}]}),
+        json!({"role": "tool", "tool_call_id": "t1", "content": "42"}),
+        json!({"role": "assistant", "content": "It is 42."}),
+        user("2"),
+        json!({"role": "assistant", "content": "Sure."}),
+    ];
+    let trimmed = trim(messages.clone(), 4);
+    assert_eq!(trimmed, vec![messages[0].clone(), messages[5].clone(), messages[6].clone()]);
+    assert_eq!(trim(messages.clone(), 10), messages);
+}
+
+#[tokio::test]
+async fn test_streamed_reply_is_stored_when_the_stream_completes() {
+    let store = store(100);
+    let mut body = json!({"messages": [user("Weather?")]});
+    let conversation = store.begin("claude-1", &mut body).await.unwrap();
+
+    let chunks = vec![
+        json!({"type": "message_start", "message": {"role": "assistant"}}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Let me "}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "check."}}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "tu_1", "name": "weather", "input": {}}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"city\":"}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"Paris\"}"}}),
+        json!({"type": "content_block_stop", "index": 1}),
+        json!({"type": "message_stop"}),
+    ];
+    let stream = futures::stream::iter(chunks.into_iter().map(Ok)).boxed();
+    let relayed: Vec<_> = store.record_stream(conversation, ModelProtocol::Claude, stream).collect().await;
+    assert_eq!(relayed.len(), 10);
+
+    let history = store.history("claude-1").await.unwrap();
+    assert_eq!(
+        history[1],
+        json!({"role": "assistant", "content": [
+            {"type": "text", "text": "Let me check."},
+            {"type": "tool_use", "id": "tu_1", "name": "weather", "input": {"city": "Paris"}}
+        ]})
+    );
+
+    // A failed stream leaves the history untouched
+    let mut body = json!({"messages": [user("And tomorrow?")]});
+    let conversation = store.begin("claude-1", &mut body).await.unwrap();
+    let stream = futures::stream::iter(vec![Err(anyhow::anyhow!("upstream reset"))]).boxed();
+    let _: Vec<_> = store.record_stream(conversation, ModelProtocol::Claude, stream).collect().await;
+    assert_eq!(store.history("claude-1").await.unwrap().len(), 2);
+}
+
+#[test]
+fn test_openai_stream_deltas_rebuild_the_message() {
+    let mut reply = StreamedReply::new(ModelProtocol::OpenAI);
+    for delta in [
+        json!({"role": "assistant", "content": "Calling"}),
+        json!({"content": " tools", "tool_calls": [{"index": 0, "id": "c1", "function": {"name": "lookup", "arguments": "{\"q\":"}}]}),
+        json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"x\"}"}}]}),
+    ] {
+        reply.observe(&json!({"choices": [{"index": 0, "delta": delta}]}));
+    }
+    assert_eq!(
+        reply.into_message().unwrap(),
+        json!({"role": "assistant", "content": "Calling tools", "tool_calls": [
+            {"id": "c1", "type": "function", "function": {"name": "lookup", "arguments": "{\"q\":\"x\"}"}}
+        ]})
+    );
+    assert!(StreamedReply::new(ModelProtocol::OpenAI).into_message().is_none());
+}
@@ -0,0 +1,342 @@
+/*!
+ * Conversion Property Tests
+ *
+ * Property-based round trips through the converters: arbitrary multi-turn
+ * requests with text, images, names and tool calls must keep their meaning
+ * through openai -> claude -> openai and claude -> gemini -> claude.
+ */
+
+use aiclient2api_rust::convert_detailed::*;
+use proptest::prelude::*;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+// ============================================================================
+// Generators
+// ============================================================================
+
+fn text() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9 ]{0,15}"
+}
+
+fn ident() -> impl Strategy<Value = String> {
+    "[a-z][a-z_]{0,7}"
+}
+
+fn tool_args() -> impl Strategy<Value = Value> {
+    prop::collection::btree_map(ident(), text(), 0..3).prop_map(|args| json!(args))
+}
+
+fn openai_image_part() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        "[A-Za-z0-9]{4,12}".prop_map(|data| json!({"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", data)}})),
+        "[a-z]{3,8}".prop_map(|host| json!({"type": "image_url", "image_url": {"url": format!("https://{}.example/a.png", host)}})),
+    ]
+}
+
+fn openai_content() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        text().prop_map(Value::String),
+        prop::collection::vec(
+            prop_oneof![text().prop_map(|t| json!({"type": "text", "text": t})), openai_image_part()],
+            1..3
+        )
+        .prop_map(Value::Array),
+    ]
+}
+
+#[derive(Debug, Clone)]
+enum OpenAITurn {
+    User { content: Value, name: Option<String> },
+    Assistant { text: String, name: Option<String> },
+    ToolCalls { text: Option<String>, name: Option<String>, calls: Vec<(String, Value, String)> },
+}
+
+fn openai_turn() -> impl Strategy<Value = OpenAITurn> {
+    let name = || prop::option::of(ident());
+    prop_oneof![
+        (openai_content(), name()).prop_map(|(content, name)| OpenAITurn::User { content, name }),
+        (text(), name()).prop_map(|(text, name)| OpenAITurn::Assistant { text, name }),
+        (
+            prop::option::of(text()),
+            name(),
+            prop::collection::vec((ident(), tool_args(), text()), 1..3)
+        )
+            .prop_map(|(text, name, calls)| OpenAITurn::ToolCalls { text, name, calls }),
+    ]
+}
+
+fn openai_request() -> impl Strategy<Value = Value> {
+    (
+        prop::option::of(text()),
+        prop::collection::vec(openai_turn(), 1..6),
+        prop::option::of(prop_oneof![text().prop_map(Value::String), prop::collection::vec(text(), 1..3).prop_map(|s| json!(s))]),
+        prop::option::of(1u32..100),
+        prop::option::of(ident()),
+        prop::option::of(prop_oneof![Just(json!("none")), Just(json!("required")), ident().prop_map(|n| json!({"type": "function", "function": {"name": n}}))]),
+    )
+        .prop_map(|(system, turns, stop, top_k, user, tool_choice)| {
+            let mut messages = Vec::new();
+            if let Some(system) = system {
+                messages.push(json!({"role": "system", "content": system}));
+            }
+            let mut tools = Vec::new();
+            let mut next_id = 0;
+            for turn in turns {
+                match turn {
+                    OpenAITurn::User { content, name } => messages.push(named(json!({"role": "user", "content": content}), name)),
+                    OpenAITurn::Assistant { text, name } => messages.push(named(json!({"role": "assistant", "content": text}), name)),
+                    OpenAITurn::ToolCalls { text, name, calls } => {
+                        let mut results = Vec::new();
+                        let tool_calls: Vec<Value> = calls
+                            .into_iter()
+                            .map(|(function, args, result)| {
+                                next_id += 1;
+                                let id = format!("call_{}", next_id);
+                                tools.push(json!({"type": "function", "function": {"name": function, "parameters": {"type": "object"}}}));
+                                results.push(json!({"role": "tool", "tool_call_id": id, "content": result}));
+                                json!({"id": id, "type": "function", "function": {"name": function, "arguments": args.to_string()}})
+                            })
+                            .collect();
+                        let message = json!({"role": "assistant", "content": text, "tool_calls": tool_calls});
+                        messages.push(named(message, name));
+                        messages.extend(results);
+                    }
+                }
+            }
+
+            let mut request = json!({"model": "gpt-4o", "max_tokens": 256, "messages": messages});
+            for (field, value) in [("stop", stop), ("top_k", top_k.map(|k| json!(k))), ("user", user.map(Value::String))] {
+                if let Some(value) = value {
+                    request[field] = value;
+                }
+            }
+            if !tools.is_empty() {
+                request["tools"] = json!(tools);
+                if let Some(tool_choice) = tool_choice {
+                    request["tool_choice"] = tool_choice;
+                }
+            }
+            request
+        })
+}
+
+fn named(mut message: Value, name: Option<String>) -> Value {
+    if let Some(name) = name {
+        message["name"] = json!(name);
+    }
+    message
+}
+
+#[derive(Debug, Clone)]
+enum ClaudeBlock {
+    Text(String),
+    Image(String),
+}
+
+fn claude_user_blocks() -> impl Strategy<Value = Vec<ClaudeBlock>> {
+    prop::collection::vec(
+        prop_oneof![text().prop_map(ClaudeBlock::Text), "[A-Za-z0-9]{4,12}".prop_map(ClaudeBlock::Image)],
+        0..3,
+    )
+}
+
+fn claude_request() -> impl Strategy<Value = Value> {
+    (
+        prop::option::of(text()),
+        prop::collection::vec(
+            (
+                claude_user_blocks(),
+                prop::option::of(text()),
+                prop::collection::vec((ident(), tool_args(), text()), 0..3),
+            ),
+            1..4,
+        ),
+        prop::option::of(prop::collection::vec(text(), 1..3)),
+        prop::option::of(1u32..100),
+        prop::option::of(prop_oneof![Just(json!({"type": "auto"})), Just(json!({"type": "any"})), Just(json!({"type": "none"}))]),
+    )
+        .prop_map(|(system, exchanges, stop_sequences, top_k, tool_choice)| {
+            let mut messages = Vec::new();
+            let mut tools: Vec<Value> = Vec::new();
+            let mut pending_results: Vec<Value> = Vec::new();
+            let mut next_id = 0;
+
+            for (user_blocks, reply, calls) in exchanges {
+                let mut blocks: Vec<Value> = std::mem::take(&mut pending_results);
+                blocks.extend(user_blocks.into_iter().map(|block| match block {
+                    ClaudeBlock::Text(t) => json!({"type": "text", "text": t}),
+                    ClaudeBlock::Image(data) => json!({"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": data}}),
+                }));
+                if blocks.is_empty() {
+                    blocks.push(json!({"type": "text", "text": "hi"}));
+                }
+                messages.push(json!({"role": "user", "content": blocks}));
+
+                let mut reply_blocks: Vec<Value> = reply.into_iter().map(|t| json!({"type": "text", "text": t})).collect();
+                for (name, input, result) in calls {
+                    next_id += 1;
+                    let id = format!("toolu_{}", next_id);
+                    if !tools.iter().any(|t| t["name"] == json!(name)) {
+                        tools.push(json!({"name": name, "description": "tool", "input_schema": {"type": "object"}}));
+                    }
+                    reply_blocks.push(json!({"type": "tool_use", "id": id, "name": name, "input": input}));
+                    pending_results.push(json!({"type": "tool_result", "tool_use_id": id, "content": result}));
+                }
+                if reply_blocks.is_empty() {
+                    reply_blocks.push(json!({"type": "text", "text": "ok"}));
+                }
+                messages.push(json!({"role": "assistant", "content": reply_blocks}));
+            }
+            if !pending_results.is_empty() {
+                messages.push(json!({"role": "user", "content": pending_results}));
+            }
+
+            let mut request = json!({"model": "claude-3-opus", "max_tokens": 1024, "messages": messages});
+            if let Some(system) = system {
+                request["system"] = json!(system);
+            }
+            if let Some(stop_sequences) = stop_sequences {
+                request["stop_sequences"] = json!(stop_sequences);
+            }
+            if let Some(top_k) = top_k {
+                request["top_k"] = json!(top_k);
+            }
+            if !tools.is_empty() {
+                request["tools"] = json!(tools);
+                if let Some(tool_choice) = tool_choice {
+                    request["tool_choice"] = tool_choice;
+                }
+            }
+            request
+        })
+}
+
+// ============================================================================
+// Normalization
+// ============================================================================
+
+/// Renumber tool ids by first appearance, so regenerated ids compare equal
+/// as long as results still point at the same calls
+fn renumber_ids(value: &mut Value, ids: &mut HashMap<String, usize>) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if matches!(key.as_str(), "id" | "tool_call_id" | "tool_use_id") {
+                    if let Value::String(id) = field {
+                        let next = ids.len();
+                        *field = json!(format!("#{}", ids.entry(id.clone()).or_insert(next)));
+                        continue;
+                    }
+                }
+                renumber_ids(field, ids);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| renumber_ids(item, ids)),
+        _ => {}
+    }
+}
+
+/// OpenAI content as a list of parts (a bare string is one text part)
+fn openai_parts(content: &Value) -> Value {
+    match content {
+        Value::String(text) => json!([{"type": "text", "text": text}]),
+        other => other.clone(),
+    }
+}
+
+fn normalize_openai(request: &Value) -> Value {
+    let mut messages: Vec<Value> = request["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| {
+            let mut message = message.clone();
+            if message["role"] != "tool" && !message["content"].is_null() {
+                message["content"] = openai_parts(&message["content"]);
+            }
+            message
+        })
+        .collect();
+    let mut ids = HashMap::new();
+    for message in &mut messages {
+        renumber_ids(message, &mut ids);
+    }
+
+    let mut out = Map::new();
+    out.insert("messages".into(), json!(messages));
+    // top_k is dropped on the way back to OpenAI, which has no such parameter
+    for field in ["user", "tools"] {
+        if let Some(value) = request.get(field) {
+            out.insert(field.into(), value.clone());
+        }
+    }
+    if let Some(stop) = request.get("stop") {
+        let stop = if stop.is_string() { json!([stop]) } else { stop.clone() };
+        out.insert("stop".into(), stop);
+    }
+    if let Some(choice) = request.get("tool_choice").filter(|c| **c != json!("auto")) {
+        out.insert("tool_choice".into(), choice.clone());
+    }
+    Value::Object(out)
+}
+
+fn normalize_claude(request: &Value) -> Value {
+    let mut messages = request["messages"].clone();
+    for message in messages.as_array_mut().unwrap() {
+        if let Some(text) = message["content"].as_str() {
+            message["content"] = json!([{"type": "text", "text": text}]);
+        }
+    }
+    renumber_ids(&mut messages, &mut HashMap::new());
+
+    let mut out = Map::new();
+    out.insert("messages".into(), messages);
+    for field in ["system", "max_tokens", "stop_sequences", "top_k", "tools", "tool_choice"] {
+        if let Some(value) = request.get(field) {
+            out.insert(field.into(), value.clone());
+        }
+    }
+    Value::Object(out)
+}
+
+// ============================================================================
+// Properties
+// ============================================================================
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn prop_openai_claude_openai_round_trip(request in openai_request()) {
+        let claude = openai_request_to_claude(request.clone()).unwrap();
+        let restored = claude_request_to_openai(claude).unwrap();
+        prop_assert_eq!(normalize_openai(&restored), normalize_openai(&request));
+    }
+
+    #[test]
+    fn prop_claude_gemini_claude_round_trip(request in claude_request()) {
+        let gemini = claude_request_to_gemini(request.clone()).unwrap();
+        let restored = gemini_request_to_claude(gemini).unwrap();
+        prop_assert_eq!(normalize_claude(&restored), normalize_claude(&request));
+    }
+
+    #[test]
+    fn prop_openai_gemini_openai_keeps_tool_pairing(request in openai_request()) {
+        let gemini = openai_request_to_gemini(request.clone()).unwrap();
+        let restored = gemini_request_to_openai(gemini).unwrap();
+
+        // Every tool result still answers a call made earlier in the conversation
+        let mut seen = Vec::new();
+        for message in restored["messages"].as_array().unwrap() {
+            for call in message["tool_calls"].as_array().into_iter().flatten() {
+                seen.push(call["id"].clone());
+            }
+            if message["role"] == "tool" {
+                prop_assert!(seen.contains(&message["tool_call_id"]));
+            }
+        }
+        let count = |r: &Value, role: &str| r["messages"].as_array().unwrap().iter().filter(|m| m["role"] == role).count();
+        prop_assert_eq!(count(&restored, "tool"), count(&request, "tool"));
+    }
+}
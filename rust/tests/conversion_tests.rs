@@ -5,7 +5,7 @@
  */
 
 use aiclient2api_rust::convert_detailed::*;
-use serde_json::json;
+use serde_json::{json, Value};
 
 #[test]
 fn test_openai_to_gemini_basic() {
@@ -212,3 +212,1056 @@ fn test_multimodal_conversion() {
     assert!(parts[1].get("inlineData").is_some());
 }
 
+
+#[test]
+fn test_message_names_round_trip_through_claude() {
+    let openai_req = json!({
+        "model": "gpt-4",
+        "messages": [
+            {"role": "user", "name": "alice", "content": "Hi, I'm Alice"},
+            {"role": "assistant", "name": "planner", "content": "Hello Alice"},
+            {"role": "user", "content": "No name here"}
+        ]
+    });
+
+    let claude_req = openai_request_to_claude(openai_req.clone()).unwrap();
+    assert_eq!(claude_req["messages"][0]["content"][0]["text"], name_marker("alice"));
+    assert_eq!(claude_req["messages"][0]["content"][1]["text"], "Hi, I'm Alice");
+
+    let restored = claude_request_to_openai(claude_req).unwrap();
+    assert_eq!(restored["messages"], openai_req["messages"]);
+}
+
+#[test]
+fn test_message_names_survive_gemini_merge() {
+    let openai_req = json!({
+        "messages": [
+            {"role": "user", "name": "alice", "content": "First"},
+            {"role": "user", "name": "bob", "content": "Second"}
+        ]
+    });
+
+    // Consecutive user messages share one Gemini turn but keep their own markers
+    let gemini_req = openai_request_to_gemini(openai_req.clone()).unwrap();
+    assert_eq!(gemini_req["contents"].as_array().unwrap().len(), 1);
+    assert_eq!(gemini_req["contents"][0]["parts"].as_array().unwrap().len(), 4);
+
+    let restored = gemini_request_to_openai(gemini_req).unwrap();
+    assert_eq!(restored["messages"], openai_req["messages"]);
+
+    assert_eq!(parse_name_marker("<name>bob</name>"), Some("bob"));
+    assert_eq!(parse_name_marker("<name>bob</name> said hi"), None);
+}
+
+#[test]
+fn test_tool_call_ids_round_trip_through_claude() {
+    let openai_req = json!({
+        "messages": [
+            {"role": "user", "content": "Weather in Paris and Rome?"},
+            {"role": "assistant", "content": null, "tool_calls": [
+                {"id": "call_a", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}},
+                {"id": "call_b", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Rome\"}"}}
+            ]},
+            {"role": "tool", "tool_call_id": "call_a", "content": "Sunny"},
+            {"role": "tool", "tool_call_id": "call_b", "content": "Rainy"}
+        ]
+    });
+
+    let claude_req = openai_request_to_claude(openai_req.clone()).unwrap();
+    let messages = claude_req["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1]["content"][1]["id"], "call_b");
+    assert_eq!(messages[1]["content"][1]["input"]["city"], "Rome");
+    assert_eq!(messages[2]["content"].as_array().unwrap().len(), 2);
+
+    let restored = claude_request_to_openai(claude_req.clone()).unwrap();
+    assert_eq!(restored["messages"][1]["tool_calls"][0]["id"], "call_a");
+    assert_eq!(restored["messages"][3], json!({"role": "tool", "tool_call_id": "call_b", "content": "Rainy"}));
+
+    // Gemini has no ids: results are named after the call they answer
+    let gemini_req = claude_request_to_gemini(claude_req).unwrap();
+    assert_eq!(gemini_req["contents"][2]["parts"][1]["functionResponse"]["name"], "weather");
+}
+
+#[test]
+fn test_gemini_function_calls_get_stable_ids() {
+    let gemini_resp = json!({
+        "candidates": [{
+            "content": {"role": "model", "parts": [{"functionCall": {"name": "weather", "args": {"city": "Paris"}}}]},
+            "finishReason": "STOP"
+        }]
+    });
+
+    let openai_resp = gemini_response_to_openai(gemini_resp.clone(), "gemini-2.5-flash").unwrap();
+    let call = &openai_resp["choices"][0]["message"]["tool_calls"][0];
+    assert_eq!(openai_resp["choices"][0]["finish_reason"], "tool_calls");
+    assert_eq!(call["id"], synthetic_tool_call_id("weather", &json!({"city": "Paris"}), 0));
+
+    // The same call seen again in history maps to the same id
+    let gemini_req = json!({"contents": [
+        {"role": "user", "parts": [{"text": "Weather?"}]},
+        {"role": "model", "parts": [{"functionCall": {"name": "weather", "args": {"city": "Paris"}}}]}
+    ]});
+    let openai_req = gemini_request_to_openai(gemini_req).unwrap();
+    assert_eq!(openai_req["messages"][1]["tool_calls"][0]["id"], call["id"]);
+
+    let claude_resp = gemini_response_to_claude(gemini_resp, "claude-3-opus").unwrap();
+    assert_eq!(claude_resp["content"][0]["id"], call["id"]);
+    assert_eq!(claude_resp["stop_reason"], "tool_use");
+}
+
+#[test]
+fn test_gemini_function_responses_become_tool_results() {
+    let gemini_req = json!({"contents": [
+        {"role": "user", "parts": [{"text": "Weather in Paris and Rome?"}]},
+        {"role": "model", "parts": [
+            {"functionCall": {"name": "weather", "args": {"city": "Paris"}}},
+            {"functionCall": {"name": "weather", "args": {"city": "Rome"}}}
+        ]},
+        {"role": "user", "parts": [
+            {"functionResponse": {"name": "weather", "response": {"content": "Sunny"}}},
+            {"functionResponse": {"name": "weather", "response": {"temp_c": 18}}}
+        ]}
+    ]});
+
+    let openai_req = gemini_request_to_openai(gemini_req.clone()).unwrap();
+    let messages = openai_req["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 4);
+    assert_eq!(messages[2]["role"], "tool");
+    assert_eq!(messages[2]["tool_call_id"], messages[1]["tool_calls"][0]["id"]);
+    assert_eq!(messages[2]["content"], "Sunny");
+    assert_eq!(messages[3]["tool_call_id"], messages[1]["tool_calls"][1]["id"]);
+    assert_eq!(messages[3]["content"], "{\"temp_c\":18}");
+
+    let claude_req = gemini_request_to_claude(gemini_req).unwrap();
+    let results = claude_req["messages"][2]["content"].as_array().unwrap();
+    assert_eq!(results[0]["type"], "tool_result");
+    assert_eq!(results[0]["tool_use_id"], claude_req["messages"][1]["content"][0]["id"]);
+    assert_eq!(results[1]["tool_use_id"], claude_req["messages"][1]["content"][1]["id"]);
+}
+
+#[test]
+fn test_tool_messages_round_trip_through_gemini() {
+    let openai_req = json!({"messages": [
+        {"role": "user", "content": "Weather?"},
+        {"role": "assistant", "content": null, "tool_calls": [
+            {"id": "call_a", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}}
+        ]},
+        {"role": "tool", "tool_call_id": "call_a", "content": "Sunny"}
+    ]});
+
+    let gemini_req = openai_request_to_gemini(openai_req).unwrap();
+    assert_eq!(gemini_req["contents"][2]["parts"][0]["functionResponse"]["name"], "weather");
+
+    // Gemini drops the original id; the result still pairs with its call
+    let restored = gemini_request_to_openai(gemini_req).unwrap();
+    assert_eq!(restored["messages"][2]["content"], "Sunny");
+    assert_eq!(restored["messages"][2]["tool_call_id"], restored["messages"][1]["tool_calls"][0]["id"]);
+}
+
+#[test]
+fn test_claude_sampling_and_tool_fields_translate() {
+    let claude_req = json!({
+        "model": "claude-3-opus",
+        "max_tokens": 512,
+        "messages": [{"role": "user", "content": "Weather?"}],
+        "stop_sequences": ["END"],
+        "top_k": 40,
+        "metadata": {"user_id": "user-42"},
+        "tools": [{"name": "weather", "description": "Get weather", "input_schema": {"type": "object"}}],
+        "tool_choice": {"type": "tool", "name": "weather", "disable_parallel_tool_use": true}
+    });
+
+    let openai_req = claude_request_to_openai(claude_req.clone()).unwrap();
+    assert_eq!(openai_req["stop"], json!(["END"]));
+    assert!(openai_req.get("top_k").is_none());
+    assert_eq!(openai_req["user"], "user-42");
+    assert_eq!(openai_req["tools"][0]["function"]["name"], "weather");
+    assert_eq!(openai_req["tool_choice"]["function"]["name"], "weather");
+    assert_eq!(openai_req["parallel_tool_calls"], false);
+
+    let back = openai_request_to_claude(openai_req).unwrap();
+    for field in ["stop_sequences", "metadata", "tools", "tool_choice"] {
+        assert_eq!(back[field], claude_req[field], "{}", field);
+    }
+
+    let gemini_req = claude_request_to_gemini(claude_req).unwrap();
+    assert_eq!(gemini_req["generationConfig"]["stopSequences"], json!(["END"]));
+    assert_eq!(gemini_req["generationConfig"]["topK"], 40);
+    assert_eq!(gemini_req["tools"][0]["functionDeclarations"][0]["name"], "weather");
+    assert_eq!(
+        gemini_req["toolConfig"]["functionCallingConfig"],
+        json!({"mode": "ANY", "allowedFunctionNames": ["weather"]})
+    );
+}
+
+#[test]
+fn test_openai_stop_and_tool_choice_to_gemini() {
+    let openai_req = json!({
+        "messages": [{"role": "user", "content": "Hi"}],
+        "stop": "\n\n",
+        "tool_choice": "required",
+        "tools": [{"type": "function", "function": {"name": "lookup", "parameters": {"type": "object"}}}]
+    });
+
+    let gemini_req = openai_request_to_gemini(openai_req).unwrap();
+    assert_eq!(gemini_req["generationConfig"]["stopSequences"], json!(["\n\n"]));
+    assert_eq!(gemini_req["toolConfig"]["functionCallingConfig"]["mode"], "ANY");
+
+    let restored = gemini_request_to_openai(gemini_req).unwrap();
+    assert_eq!(restored["tool_choice"], "required");
+    assert_eq!(restored["tools"][0]["function"]["name"], "lookup");
+}
+
+#[test]
+fn test_convert_data_maps_backend_responses_to_openai() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::{convert_data, ConversionType};
+
+    let claude_resp = json!({
+        "content": [
+            {"type": "text", "text": "Checking."},
+            {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {"city": "Paris"}}
+        ],
+        "stop_reason": "tool_use",
+        "usage": {"input_tokens": 12, "output_tokens": 7}
+    });
+    let openai = convert_data(claude_resp, ConversionType::Response, ModelProtocol::Claude, ModelProtocol::OpenAI, Some("claude-3-opus")).unwrap();
+    assert_eq!(openai["choices"][0]["finish_reason"], "tool_calls");
+    assert_eq!(openai["choices"][0]["message"]["tool_calls"][0]["id"], "toolu_1");
+    assert_eq!(openai["choices"][0]["message"]["tool_calls"][0]["function"]["arguments"], "{\"city\":\"Paris\"}");
+    assert_eq!(openai["usage"]["total_tokens"], 19);
+
+    let gemini_resp = json!({
+        "candidates": [{"content": {"role": "model", "parts": [{"text": "Hi"}]}, "finishReason": "STOP"}],
+        "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 1, "totalTokenCount": 4}
+    });
+    let openai = convert_data(gemini_resp, ConversionType::Response, ModelProtocol::Gemini, ModelProtocol::OpenAI, Some("gemini-2.5-flash")).unwrap();
+    assert_eq!(openai["choices"][0]["message"]["content"], "Hi");
+    assert_eq!(openai["usage"]["total_tokens"], 4);
+}
+
+#[test]
+fn test_chat_request_converts_only_across_protocols() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatRequest;
+
+    let body = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}], "max_tokens": 10});
+    let same = ChatRequest::OpenAI(body.clone()).into_protocol(ModelProtocol::OpenAI, Some("gpt-4o")).unwrap();
+    assert_eq!(same, body);
+
+    let claude = ChatRequest::OpenAI(body).into_protocol(ModelProtocol::Claude, Some("claude-3-opus")).unwrap();
+    assert_eq!(claude["messages"][0]["role"], "user");
+    assert_eq!(claude["max_tokens"], 10);
+}
+
+#[test]
+fn test_gemini_request_carries_its_model_only_inside_the_server() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatRequest;
+
+    let body = json!({
+        "model": "gemini-2.5-flash",
+        "stream": true,
+        "contents": [{"role": "user", "parts": [{"text": "Hi"}]}]
+    });
+    let gemini = ChatRequest::Gemini(body.clone()).into_protocol(ModelProtocol::Gemini, Some("gemini-2.5-flash")).unwrap();
+    assert_eq!(gemini, json!({"contents": [{"role": "user", "parts": [{"text": "Hi"}]}]}));
+
+    let openai = ChatRequest::Gemini(body).into_protocol(ModelProtocol::OpenAI, Some("gemini-2.5-flash")).unwrap();
+    assert_eq!(openai["model"], "gemini-2.5-flash");
+    assert_eq!(openai["messages"][0]["content"], "Hi");
+}
+
+#[test]
+fn test_chat_response_openai_to_claude() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatResponse;
+
+    let openai = json!({
+        "id": "chatcmpl-1",
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": "Checking.",
+                "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}}]
+            },
+            "finish_reason": "tool_calls"
+        }],
+        "usage": {"prompt_tokens": 12, "completion_tokens": 7, "total_tokens": 19}
+    });
+    let claude = ChatResponse::OpenAI(openai).into_protocol(ModelProtocol::Claude, Some("claude-3-opus")).unwrap();
+    assert_eq!(claude["content"][0], json!({"type": "text", "text": "Checking."}));
+    assert_eq!(claude["content"][1]["type"], "tool_use");
+    assert_eq!(claude["content"][1]["id"], "call_1");
+    assert_eq!(claude["content"][1]["input"], json!({"city": "Paris"}));
+    assert_eq!(claude["stop_reason"], "tool_use");
+    assert_eq!(claude["usage"]["output_tokens"], 7);
+}
+
+#[tokio::test]
+async fn test_chat_stream_claude_events_to_openai_chunks() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let events = vec![
+        json!({"type": "message_start", "message": {"id": "msg_1"}}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Checking."}}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {}}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"city\":"}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"Paris\"}"}}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}}),
+        json!({"type": "message_stop"}),
+    ];
+    let stream = ChatStream::Claude(Box::pin(futures::stream::iter(events.into_iter().map(Ok))));
+    let chunks: Vec<_> = stream
+        .into_protocol(ModelProtocol::OpenAI, Some("claude-3-opus"))
+        .unwrap()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(chunks.len(), 6);
+    assert!(chunks.iter().all(|c| c["id"] == chunks[0]["id"] && c["object"] == "chat.completion.chunk"));
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "Checking.");
+    assert_eq!(chunks[2]["choices"][0]["delta"]["tool_calls"][0]["id"], "toolu_1");
+    let arguments: String = chunks[3..5]
+        .iter()
+        .map(|c| c["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"].as_str().unwrap())
+        .collect();
+    assert_eq!(arguments, "{\"city\":\"Paris\"}");
+    assert_eq!(chunks[5]["choices"][0]["finish_reason"], "tool_calls");
+}
+
+#[tokio::test]
+async fn test_chat_stream_gemini_chunks_to_openai() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let chunks = vec![
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "Hel"}]}}]}),
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "lo"}]}, "finishReason": "MAX_TOKENS"}]}),
+    ];
+    let stream = ChatStream::Gemini(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))));
+    let out: Vec<_> = stream
+        .into_protocol(ModelProtocol::OpenAI, Some("gemini-2.5-flash"))
+        .unwrap()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(out[0]["choices"][0]["delta"]["content"], "Hel");
+    assert!(out[0]["choices"][0]["finish_reason"].is_null());
+    assert_eq!(out[1]["choices"][0]["finish_reason"], "length");
+    assert_eq!(out[0]["id"], out[1]["id"]);
+}
+
+#[tokio::test]
+async fn test_chat_stream_openai_chunks_to_claude() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let chunks = vec![
+        json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hi"}, "finish_reason": null}]}),
+        json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":"}}]}, "finish_reason": null}]}),
+        json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "\"Paris\"}"}}]}, "finish_reason": null}]}),
+        json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "tool_calls"}]}),
+        json!({"choices": [], "usage": {"prompt_tokens": 12, "completion_tokens": 7}}),
+    ];
+    let stream = ChatStream::OpenAI(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))));
+    let events: Vec<_> = stream
+        .into_protocol(ModelProtocol::Claude, Some("gpt-4o"))
+        .unwrap()
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    let types: Vec<_> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(
+        types,
+        [
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_stop",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop",
+        ]
+    );
+    assert_eq!(events[0]["message"]["model"], "gpt-4o");
+    assert_eq!(events[2]["delta"], json!({"type": "text_delta", "text": "Hi"}));
+    assert_eq!(events[4]["index"], 1);
+    assert_eq!(events[4]["content_block"]["id"], "call_1");
+    assert_eq!(events[4]["content_block"]["name"], "get_weather");
+    let arguments: String = events[5..7].iter().map(|e| e["delta"]["partial_json"].as_str().unwrap()).collect();
+    assert_eq!(arguments, "{\"city\":\"Paris\"}");
+    assert_eq!(events[8]["delta"]["stop_reason"], "tool_use");
+    assert_eq!(events[8]["usage"], json!({"input_tokens": 12, "output_tokens": 7}));
+}
+
+#[tokio::test]
+async fn test_chat_stream_gemini_chunks_to_claude() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let chunks = vec![
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "Hel"}]}}]}),
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "lo"}]}, "finishReason": "MAX_TOKENS"}]}),
+    ];
+    let stream = ChatStream::Gemini(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))));
+    let events: Vec<_> = stream
+        .into_protocol(ModelProtocol::Claude, Some("gemini-2.5-flash"))
+        .unwrap()
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    let text: String = events
+        .iter()
+        .filter(|e| e["type"] == "content_block_delta")
+        .map(|e| e["delta"]["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(text, "Hello");
+    let last = &events[events.len() - 2..];
+    assert_eq!(last[0]["delta"]["stop_reason"], "max_tokens");
+    assert_eq!(last[1]["type"], "message_stop");
+}
+
+#[tokio::test]
+async fn test_chat_stream_to_claude_does_not_close_a_failed_stream() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let chunks = vec![
+        Ok(json!({"choices": [{"index": 0, "delta": {"content": "Hi"}, "finish_reason": null}]})),
+        Err(anyhow::anyhow!("connection reset")),
+    ];
+    let stream = ChatStream::OpenAI(Box::pin(futures::stream::iter(chunks)));
+    let items: Vec<_> = stream.into_protocol(ModelProtocol::Claude, None).unwrap().collect().await;
+
+    assert!(items.last().unwrap().is_err());
+    assert!(items.iter().flatten().all(|e| e["type"] != "message_stop"));
+}
+
+#[test]
+fn test_openai_chunk_sequence_to_claude_events() {
+    use aiclient2api_rust::convert_detailed::OpenAIStreamToClaude;
+
+    let chunk = |delta: Value, finish_reason: Value| {
+        json!({"choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]})
+    };
+    let mut converter = OpenAIStreamToClaude::new("o3");
+    let mut events = Vec::new();
+    for chunk in [
+        chunk(json!({"role": "assistant", "reasoning_content": "Two "}), Value::Null),
+        chunk(json!({"reasoning_content": "cities"}), Value::Null),
+        chunk(json!({"content": "On it"}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 0, "id": "call_a", "function": {"name": "weather", "arguments": "{}"}}]}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 1, "id": "call_b", "function": {"name": "time", "arguments": ""}}]}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 1, "function": {"arguments": "{}"}}]}), Value::Null),
+        chunk(json!({}), json!("tool_calls")),
+    ] {
+        events.extend(converter.convert(&chunk));
+    }
+    // Only the first chunk starts the message, and none closes it
+    assert_eq!(events.iter().filter(|e| e["type"] == "message_start").count(), 1);
+    assert!(events.iter().all(|e| e["type"] != "message_stop"));
+    events.extend(converter.finish());
+
+    let starts: Vec<_> = events.iter().filter(|e| e["type"] == "content_block_start").collect();
+    let blocks: Vec<_> = starts.iter().map(|e| (e["index"].as_u64().unwrap(), e["content_block"]["type"].as_str().unwrap())).collect();
+    assert_eq!(blocks, [(0, "thinking"), (1, "text"), (2, "tool_use"), (3, "tool_use")]);
+    assert_eq!(starts[3]["content_block"]["id"], "call_b");
+    let stops: Vec<_> = events.iter().filter(|e| e["type"] == "content_block_stop").map(|e| e["index"].as_u64().unwrap()).collect();
+    assert_eq!(stops, [0, 1, 2, 3]);
+
+    let deltas: Vec<_> = events.iter().filter(|e| e["type"] == "content_block_delta").map(|e| (e["index"].as_u64().unwrap(), &e["delta"])).collect();
+    assert_eq!(deltas[0], (0, &json!({"type": "thinking_delta", "thinking": "Two "})));
+    assert_eq!(deltas[2], (1, &json!({"type": "text_delta", "text": "On it"})));
+    assert_eq!(deltas[3], (2, &json!({"type": "input_json_delta", "partial_json": "{}"})));
+    assert_eq!(deltas[4], (3, &json!({"type": "input_json_delta", "partial_json": "{}"})));
+    assert_eq!(events[events.len() - 2]["delta"]["stop_reason"], "tool_use");
+}
+
+#[test]
+fn test_empty_openai_stream_is_still_a_whole_claude_message() {
+    use aiclient2api_rust::convert_detailed::OpenAIStreamToClaude;
+
+    let events = OpenAIStreamToClaude::new("gpt-4o").finish();
+    let types: Vec<_> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(types, ["message_start", "message_delta", "message_stop"]);
+    assert_eq!(events[1]["delta"]["stop_reason"], "end_turn");
+}
+
+#[tokio::test]
+async fn test_gemini_chunk_sequence_to_claude_events() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let chunks = vec![
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "Looking up"}]}}]}),
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"functionCall": {"name": "weather", "args": {"city": "Paris"}}}]}, "finishReason": "STOP"}]}),
+        json!({"candidates": [], "usageMetadata": {"promptTokenCount": 9, "candidatesTokenCount": 4}}),
+    ];
+    let stream = ChatStream::Gemini(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))));
+    let events: Vec<_> = stream
+        .into_protocol(ModelProtocol::Claude, Some("gemini-2.5-flash"))
+        .unwrap()
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    let tool = events.iter().find(|e| e["content_block"]["type"] == "tool_use").unwrap();
+    assert_eq!(tool["index"], 1);
+    assert_eq!(tool["content_block"]["name"], "weather");
+    assert!(tool["content_block"]["id"].as_str().is_some_and(|id| !id.is_empty()));
+    let arguments = events.iter().find(|e| e["delta"]["type"] == "input_json_delta").unwrap();
+    assert_eq!(serde_json::from_str::<Value>(arguments["delta"]["partial_json"].as_str().unwrap()).unwrap(), json!({"city": "Paris"}));
+    assert_eq!(events[events.len() - 2]["delta"]["stop_reason"], "tool_use");
+}
+
+#[test]
+fn test_lone_stream_chunks_are_not_converted_to_claude() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::{convert_data, ConversionType};
+
+    let chunk = json!({"choices": [{"index": 0, "delta": {"content": "Hi"}, "finish_reason": null}]});
+    assert!(convert_data(chunk, ConversionType::StreamChunk, ModelProtocol::OpenAI, ModelProtocol::Claude, None).is_err());
+}
+
+#[tokio::test]
+async fn test_chat_stream_openai_chunks_to_gemini() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let chunk = |delta: Value, finish_reason: Value| json!({"choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]});
+    let chunks = vec![
+        chunk(json!({"role": "assistant", "content": ""}), Value::Null),
+        chunk(json!({"content": "Checking."}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 0, "id": "call_1", "function": {"name": "weather", "arguments": "{\"city\":"}}]}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"Paris\"}"}}]}), Value::Null),
+        chunk(json!({}), json!("tool_calls")),
+        json!({"choices": [], "usage": {"prompt_tokens": 12, "completion_tokens": 7, "total_tokens": 19}}),
+    ];
+    let stream = ChatStream::OpenAI(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))));
+    let out: Vec<_> = stream
+        .into_protocol(ModelProtocol::Gemini, Some("gpt-4o"))
+        .unwrap()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    // The role opener and the argument pieces have nothing to pass on by themselves
+    assert_eq!(out.len(), 3);
+    assert_eq!(out[0]["candidates"][0]["content"]["parts"], json!([{"text": "Checking."}]));
+    assert_eq!(
+        out[1]["candidates"][0]["content"]["parts"],
+        json!([{"functionCall": {"name": "weather", "args": {"city": "Paris"}}}])
+    );
+    assert_eq!(out[1]["candidates"][0]["finishReason"], "STOP");
+    assert_eq!(out[2]["usageMetadata"]["totalTokenCount"], 19);
+}
+
+#[tokio::test]
+async fn test_chat_stream_claude_events_to_gemini() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::ChatStream;
+    use futures::StreamExt;
+
+    let events = vec![
+        json!({"type": "message_start", "message": {"id": "msg_1"}}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hello"}}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "max_tokens"}}),
+        json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}),
+    ];
+    let stream = ChatStream::Claude(Box::pin(futures::stream::iter(events.into_iter().map(Ok))));
+    let out: Vec<_> = stream.into_protocol(ModelProtocol::Gemini, Some("claude-3-opus")).unwrap().collect().await;
+
+    assert_eq!(out.len(), 3);
+    assert_eq!(out[0].as_ref().unwrap()["candidates"][0]["content"]["parts"][0]["text"], "Hello");
+    assert_eq!(out[1].as_ref().unwrap()["candidates"][0]["finishReason"], "MAX_TOKENS");
+    assert!(out[2].is_err());
+}
+
+#[test]
+fn test_base64_images_move_between_formats() {
+    let data = "iVBORw0KGgo".repeat(10_000);
+    let url = format!("data:image/png;base64,{}", data);
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "messages": [{"role": "user", "content": [
+            {"type": "text", "text": "What is this?"},
+            {"type": "image_url", "image_url": {"url": url}}
+        ]}]
+    });
+
+    let claude = openai_request_to_claude(openai_req.clone()).unwrap();
+    let source = &claude["messages"][0]["content"][1]["source"];
+    assert_eq!(source["media_type"], "image/png");
+    assert_eq!(source["data"], data.as_str());
+
+    let gemini = claude_request_to_gemini(claude.clone()).unwrap();
+    assert_eq!(gemini["contents"][0]["parts"][1]["inlineData"]["data"], data.as_str());
+
+    let restored = claude_request_to_openai(claude).unwrap();
+    assert_eq!(restored["messages"][0]["content"][1]["image_url"]["url"], url.as_str());
+
+    let gemini = openai_request_to_gemini(openai_req).unwrap();
+    assert_eq!(gemini["contents"][0]["parts"][1]["inlineData"]["mimeType"], "image/png");
+    let restored = gemini_request_to_openai(gemini).unwrap();
+    assert_eq!(restored["messages"][0]["content"][1]["image_url"]["url"], url.as_str());
+}
+
+#[test]
+fn test_web_citations_become_url_citation_annotations() {
+    let gemini_resp = json!({
+        "candidates": [{
+            "content": {"role": "model", "parts": [{"text": "Rust 1.80 shipped in July."}]},
+            "finishReason": "STOP",
+            "groundingMetadata": {"groundingChunks": [
+                {"web": {"uri": "https://blog.rust-lang.org/1.80", "title": "Announcing Rust 1.80"}},
+                {"web": {"uri": "https://blog.rust-lang.org/1.80", "title": "Announcing Rust 1.80"}}
+            ]}
+        }]
+    });
+    let openai = gemini_response_to_openai(gemini_resp, "gemini-2.5-pro").unwrap();
+    assert_eq!(
+        openai["choices"][0]["message"]["annotations"],
+        json!([{"type": "url_citation", "url_citation": {"url": "https://blog.rust-lang.org/1.80", "title": "Announcing Rust 1.80"}}])
+    );
+
+    let claude_resp = json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{"type": "text", "text": "It shipped in July.", "citations": [
+            {"type": "web_search_result_location", "url": "https://example.com/rust", "title": "Rust news", "cited_text": "July"}
+        ]}],
+        "stop_reason": "end_turn",
+        "usage": {"input_tokens": 10, "output_tokens": 5}
+    });
+    let openai = claude_response_to_openai(claude_resp, "claude-sonnet-4").unwrap();
+    let annotation = &openai["choices"][0]["message"]["annotations"][0];
+    assert_eq!(annotation["url_citation"]["url"], "https://example.com/rust");
+    assert_eq!(annotation["url_citation"]["title"], "Rust news");
+}
+
+#[test]
+fn test_code_interpreter_tool_maps_to_native_code_execution() {
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "messages": [{"role": "user", "content": "What is 2**100?"}],
+        "tools": [{"type": "code_interpreter"}, {"type": "function", "function": {"name": "lookup", "parameters": {"type": "object"}}}]
+    });
+
+    let gemini = openai_request_to_gemini(openai_req.clone()).unwrap();
+    assert_eq!(gemini["tools"][0]["functionDeclarations"][0]["name"], "lookup");
+    assert_eq!(gemini["tools"][1], json!({"codeExecution": {}}));
+
+    let claude = openai_request_to_claude(openai_req.clone()).unwrap();
+    assert_eq!(claude["tools"][0], json!({"type": CLAUDE_CODE_EXECUTION_TOOL, "name": "code_execution"}));
+    assert!(claude_uses_code_execution(&claude));
+
+    // Claude and Gemini requests carry it across through the OpenAI form
+    let to_gemini = claude_request_to_gemini(claude).unwrap();
+    assert_eq!(to_gemini["tools"][1], json!({"codeExecution": {}}));
+    let to_claude = gemini_request_to_claude(gemini).unwrap();
+    assert!(claude_uses_code_execution(&to_claude));
+
+    // Backends without code execution get the request without it
+    let mut only_code = json!({"messages": [], "tools": [{"type": "code_execution"}], "tool_choice": "auto"});
+    assert!(drop_code_interpreter(&mut only_code));
+    assert_eq!(only_code, json!({"messages": []}));
+    let mut with_function = openai_req;
+    assert!(drop_code_interpreter(&mut with_function));
+    assert_eq!(with_function["tools"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_executed_code_is_returned_as_code_interpreter_calls() {
+    let gemini_resp = json!({
+        "candidates": [{
+            "content": {"role": "model", "parts": [
+                {"text": "Let me compute it."},
+                {"executableCode": {"language": "PYTHON", "code": "print(2**100)"}},
+                {"codeExecutionResult": {"outcome": "OUTCOME_OK", "output": "1267650600228229401496703205376\n"}},
+                {"text": "2**100 = 1267650600228229401496703205376"}
+            ]},
+            "finishReason": "STOP"
+        }]
+    });
+    let openai = gemini_response_to_openai(gemini_resp.clone(), "gemini-2.5-pro").unwrap();
+    let message = &openai["choices"][0]["message"];
+    assert_eq!(openai["choices"][0]["finish_reason"], "stop");
+    let call = &message["code_interpreter_calls"][0];
+    assert_eq!(call["type"], "code_interpreter");
+    assert_eq!(call["code_interpreter"]["input"], "print(2**100)");
+    assert_eq!(call["code_interpreter"]["outputs"][0]["logs"], "1267650600228229401496703205376\n");
+
+    let claude = gemini_response_to_claude(gemini_resp, "gemini-2.5-pro").unwrap();
+    let blocks = claude["content"].as_array().unwrap();
+    let tool_use = blocks.iter().find(|b| b["type"] == "server_tool_use").unwrap();
+    let result = blocks.iter().find(|b| b["type"] == "code_execution_tool_result").unwrap();
+    assert_eq!(tool_use["input"]["code"], "print(2**100)");
+    assert_eq!(result["tool_use_id"], tool_use["id"]);
+    assert_eq!(result["content"]["return_code"], 0);
+    assert_eq!(claude["stop_reason"], "end_turn");
+
+    let claude_resp = json!({
+        "content": [
+            {"type": "server_tool_use", "id": "srvtoolu_1", "name": "code_execution", "input": {"code": "1/0"}},
+            {"type": "code_execution_tool_result", "tool_use_id": "srvtoolu_1", "content": {
+                "type": "code_execution_result", "stdout": "", "stderr": "ZeroDivisionError", "return_code": 1
+            }},
+            {"type": "text", "text": "That raises an error."}
+        ],
+        "stop_reason": "end_turn"
+    });
+    let openai = claude_response_to_openai(claude_resp, "claude-sonnet-4").unwrap();
+    let call = &openai["choices"][0]["message"]["code_interpreter_calls"][0];
+    assert_eq!(call["id"], "srvtoolu_1");
+    assert_eq!(call["code_interpreter"]["outputs"][0]["logs"], "ZeroDivisionError");
+    assert_eq!(openai["choices"][0]["message"]["content"], "That raises an error.");
+}
+
+#[test]
+fn test_lossy_conversions_are_reported() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::conversion_warnings;
+
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "max_tokens": 100,
+        "messages": [{"role": "user", "content": "Hi"}],
+        "logit_bias": {"50256": -100},
+        "presence_penalty": 0.5,
+        "n": 2,
+        "tools": [{"type": "file_search"}, {"type": "function", "function": {"name": "lookup"}}]
+    });
+    let warnings = conversion_warnings(&openai_req, ModelProtocol::OpenAI, ModelProtocol::Claude);
+    assert_eq!(
+        warnings,
+        vec![
+            "logit_bias has no Claude equivalent and was dropped",
+            "presence_penalty has no Claude equivalent and was dropped",
+            "tools[0] (file_search) has no Claude equivalent and was dropped",
+            "n > 1 is not supported by Claude; a single choice is returned",
+        ]
+    );
+    assert!(conversion_warnings(&openai_req, ModelProtocol::OpenAI, ModelProtocol::OpenAI).is_empty());
+
+    let gemini_req = json!({
+        "contents": [],
+        "safetySettings": [{"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_NONE"}],
+        "generationConfig": {"frequencyPenalty": 0.2},
+        "tools": [{"googleSearch": {}}, {"codeExecution": {}}]
+    });
+    let warnings = conversion_warnings(&gemini_req, ModelProtocol::Gemini, ModelProtocol::Claude);
+    assert!(warnings.contains(&"safetySettings has no Claude equivalent and was dropped".to_string()));
+    assert!(warnings.contains(&"generationConfig/frequencyPenalty has no Claude equivalent and was dropped".to_string()));
+    assert!(warnings.contains(&"tools[0] (googleSearch) has no Claude equivalent and was dropped".to_string()));
+    assert!(warnings.contains(&"max_tokens is required by Claude; the default was used".to_string()));
+    assert_eq!(warnings.len(), 4);
+
+    // Faithful conversions stay quiet
+    let claude_req = json!({"max_tokens": 10, "messages": [{"role": "user", "content": "Hi"}]});
+    assert!(conversion_warnings(&claude_req, ModelProtocol::Claude, ModelProtocol::Gemini).is_empty());
+}
+
+#[test]
+fn test_sampling_parameters_are_clamped_to_the_backend_range() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::{conversion_warnings, ChatRequest};
+
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "max_tokens": 0,
+        "temperature": 1.5,
+        "top_p": 0.9,
+        "messages": [{"role": "user", "content": "Hi"}]
+    });
+    let warnings = conversion_warnings(&openai_req, ModelProtocol::OpenAI, ModelProtocol::Claude);
+    assert_eq!(
+        warnings,
+        vec![
+            "temperature 1.5 is outside the range Claude accepts; clamped to the maximum of 1",
+            "max_tokens 0 is outside the range Claude accepts; clamped to the minimum of 1",
+        ]
+    );
+    let claude_req = ChatRequest::OpenAI(openai_req.clone())
+        .into_protocol(ModelProtocol::Claude, Some("claude-sonnet-4"))
+        .unwrap();
+    assert_eq!(claude_req["temperature"], 1.0);
+    assert_eq!(claude_req["top_p"], 0.9);
+    assert_eq!(claude_req["max_tokens"], 1);
+
+    // 1.5 is a valid OpenAI and Gemini temperature
+    let gemini_req = ChatRequest::OpenAI(openai_req)
+        .into_protocol(ModelProtocol::Gemini, Some("gemini-2.5-flash"))
+        .unwrap();
+    assert_eq!(gemini_req["generationConfig"]["temperature"], 1.5);
+
+    let gemini_req = json!({"contents": [], "generationConfig": {"temperature": 2.5, "topP": 1.2}});
+    let warnings = conversion_warnings(&gemini_req, ModelProtocol::Gemini, ModelProtocol::Gemini);
+    assert_eq!(warnings.len(), 2);
+    let same = ChatRequest::Gemini(gemini_req).into_protocol(ModelProtocol::Gemini, None).unwrap();
+    assert_eq!(same["generationConfig"], json!({"temperature": 2.0, "topP": 1.0}));
+}
+
+#[test]
+fn test_gemini_generation_parameters_round_trip() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::conversion_warnings;
+
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "messages": [{"role": "user", "content": "List three colors as JSON"}],
+        "top_k": 40,
+        "n": 2,
+        "presence_penalty": 0.5,
+        "frequency_penalty": 0.25,
+        "seed": 7,
+        "response_format": {"type": "json_object"}
+    });
+    assert!(conversion_warnings(&openai_req, ModelProtocol::OpenAI, ModelProtocol::Gemini).is_empty());
+
+    let gemini_req = openai_request_to_gemini(openai_req).unwrap();
+    assert_eq!(
+        gemini_req["generationConfig"],
+        json!({
+            "topK": 40,
+            "candidateCount": 2,
+            "presencePenalty": 0.5,
+            "frequencyPenalty": 0.25,
+            "seed": 7,
+            "responseMimeType": "application/json"
+        })
+    );
+    // OpenAI has no top_k
+    assert_eq!(
+        conversion_warnings(&gemini_req, ModelProtocol::Gemini, ModelProtocol::OpenAI),
+        ["top_k is not supported by OpenAI; dropped"]
+    );
+
+    let restored = gemini_request_to_openai(gemini_req).unwrap();
+    assert!(restored.get("top_k").is_none());
+    assert_eq!(restored["n"], 2);
+    assert_eq!(restored["presence_penalty"], 0.5);
+    assert_eq!(restored["frequency_penalty"], 0.25);
+    assert_eq!(restored["seed"], 7);
+    assert_eq!(restored["response_format"], json!({"type": "json_object"}));
+
+    // Each candidate becomes a choice
+    let gemini_resp = json!({
+        "candidates": [
+            {"index": 0, "content": {"role": "model", "parts": [{"text": "[\"red\"]"}]}, "finishReason": "STOP"},
+            {"index": 1, "content": {"role": "model", "parts": [{"text": "[\"blue\"]"}]}, "finishReason": "MAX_TOKENS"}
+        ]
+    });
+    let openai_resp = gemini_response_to_openai(gemini_resp, "gemini-2.5-flash").unwrap();
+    let choices = openai_resp["choices"].as_array().unwrap();
+    assert_eq!(choices.len(), 2);
+    assert_eq!(choices[1]["index"], 1);
+    assert_eq!(choices[1]["message"]["content"], "[\"blue\"]");
+    assert_eq!(choices[1]["finish_reason"], "length");
+}
+
+#[test]
+fn test_json_schema_response_format_becomes_gemini_response_schema() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::conversion_warnings;
+
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "messages": [{"role": "user", "content": "Name a color"}],
+        "response_format": {
+            "type": "json_schema",
+            "json_schema": {
+                "name": "color",
+                "strict": true,
+                "schema": {
+                    "type": "object",
+                    "properties": {"color": {"type": "string"}},
+                    "required": ["color"],
+                    "additionalProperties": false
+                }
+            }
+        }
+    });
+    let warnings = conversion_warnings(&openai_req, ModelProtocol::OpenAI, ModelProtocol::Gemini);
+    assert_eq!(
+        warnings,
+        vec!["response_format json_schema uses additionalProperties which Gemini does not support; dropped from the schema"]
+    );
+
+    let gemini_req = openai_request_to_gemini(openai_req).unwrap();
+    assert_eq!(gemini_req["generationConfig"]["responseMimeType"], "application/json");
+    assert_eq!(
+        gemini_req["generationConfig"]["responseSchema"],
+        json!({"type": "object", "properties": {"color": {"type": "string"}}, "required": ["color"]})
+    );
+    assert!(conversion_warnings(&gemini_req, ModelProtocol::Gemini, ModelProtocol::OpenAI).is_empty());
+
+    let restored = gemini_request_to_openai(gemini_req).unwrap();
+    assert_eq!(restored["response_format"]["type"], "json_schema");
+    assert_eq!(restored["response_format"]["json_schema"]["schema"]["required"], json!(["color"]));
+}
+
+#[test]
+fn test_tool_schemas_are_sanitized_for_gemini() {
+    use aiclient2api_rust::common::ModelProtocol;
+    use aiclient2api_rust::convert::{conversion_warnings, ChatRequest};
+
+    let parameters = json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "type": "object",
+        "properties": {
+            "city": {"type": "string"},
+            "unit": {"type": ["string", "null"], "enum": ["c", "f"]},
+            "homepage": {"type": "string", "format": "uri"}
+        },
+        "required": ["city"],
+        "additionalProperties": false
+    });
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "messages": [{"role": "user", "content": "Weather?"}],
+        "tools": [
+            {"type": "function", "function": {"name": "get_weather", "parameters": parameters}},
+            {"type": "function", "function": {"name": "now", "parameters": {"type": "object", "properties": {}}}}
+        ]
+    });
+    assert_eq!(
+        conversion_warnings(&openai_req, ModelProtocol::OpenAI, ModelProtocol::Gemini),
+        vec!["tools[0] (get_weather) parameters use additionalProperties, format uri which Gemini does not support; dropped from the schema"]
+    );
+
+    let expected = json!({
+        "type": "object",
+        "properties": {
+            "city": {"type": "string"},
+            "unit": {"type": "string", "nullable": true, "enum": ["c", "f"]},
+            "homepage": {"type": "string"}
+        },
+        "required": ["city"]
+    });
+    let gemini_req = openai_request_to_gemini(openai_req).unwrap();
+    let declarations = &gemini_req["tools"][0]["functionDeclarations"];
+    assert_eq!(declarations[0]["parameters"], expected);
+    assert!(declarations[1].get("parameters").is_none(), "no-argument functions are declared without a schema");
+
+    let claude_req = json!({
+        "model": "claude-sonnet-4",
+        "max_tokens": 100,
+        "messages": [{"role": "user", "content": "Weather?"}],
+        "tools": [{"name": "get_weather", "input_schema": parameters}]
+    });
+    assert_eq!(conversion_warnings(&claude_req, ModelProtocol::Claude, ModelProtocol::Gemini).len(), 1);
+    let gemini_req = ChatRequest::Claude(claude_req).into_protocol(ModelProtocol::Gemini, None).unwrap();
+    assert_eq!(gemini_req["tools"][0]["functionDeclarations"][0]["parameters"], expected);
+}
+
+#[test]
+fn test_gemini_filter_finish_reasons_are_not_hidden() {
+    let response = |reason: &str| {
+        json!({
+            "candidates": [{"content": {"role": "model", "parts": [{"text": "I can"}]}, "finishReason": reason}],
+            "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 2, "totalTokenCount": 7}
+        })
+    };
+    for reason in ["SAFETY", "RECITATION", "OTHER"] {
+        let openai = gemini_response_to_openai(response(reason), "gemini-2.5-flash").unwrap();
+        assert_eq!(openai["choices"][0]["finish_reason"], "content_filter");
+        let claude = gemini_response_to_claude(response(reason), "gemini-2.5-flash").unwrap();
+        assert_eq!(claude["stop_reason"], "refusal");
+    }
+    let openai = gemini_response_to_openai(response("MAX_TOKENS"), "gemini-2.5-flash").unwrap();
+    assert_eq!(openai["choices"][0]["finish_reason"], "length");
+    let claude = gemini_response_to_claude(response("STOP"), "gemini-2.5-flash").unwrap();
+    assert_eq!(claude["stop_reason"], "end_turn");
+
+    // Refusals survive the OpenAI <-> Claude round trip
+    let claude = openai_response_to_claude(openai_response_with("content_filter"), "claude-3-opus").unwrap();
+    assert_eq!(claude["stop_reason"], "refusal");
+    let openai = claude_response_to_openai(claude, "gpt-4o").unwrap();
+    assert_eq!(openai["choices"][0]["finish_reason"], "content_filter");
+
+    let chunk = json!({"candidates": [{"content": {"role": "model", "parts": []}, "finishReason": "SAFETY"}]});
+    assert_eq!(gemini_chunk_to_openai(&chunk, "chatcmpl-1", "gemini")["choices"][0]["finish_reason"], "content_filter");
+}
+
+fn openai_response_with(finish_reason: &str) -> serde_json::Value {
+    json!({
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": ""}, "finish_reason": finish_reason}],
+        "usage": {"prompt_tokens": 1, "completion_tokens": 0}
+    })
+}
+
+#[test]
+fn test_gemini_blocked_prompt_is_an_error() {
+    let blocked = json!({"promptFeedback": {"blockReason": "SAFETY", "safetyRatings": []}, "usageMetadata": {"promptTokenCount": 5}});
+    assert_eq!(gemini_prompt_blocked(&blocked), Some(PromptBlocked { reason: "SAFETY".to_string() }));
+
+    let err = gemini_response_to_openai(blocked.clone(), "gemini-2.5-flash").unwrap_err();
+    assert_eq!(err.downcast_ref::<PromptBlocked>().unwrap().reason, "SAFETY");
+    assert!(gemini_response_to_claude(blocked.clone(), "gemini-2.5-flash").unwrap_err().is::<PromptBlocked>());
+
+    // Streams end with a content_filter chunk instead
+    let chunk = gemini_chunk_to_openai(&blocked, "chatcmpl-1", "gemini-2.5-flash");
+    assert_eq!(chunk["choices"][0]["finish_reason"], "content_filter");
+    assert_eq!(chunk["choices"][0]["delta"], json!({}));
+}
+
+#[test]
+fn test_multiple_system_messages_are_kept_in_order() {
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "messages": [
+            {"role": "system", "content": "You are a support agent."},
+            {"role": "developer", "content": [{"type": "text", "text": "Answer in French."}]},
+            {"role": "user", "content": "Hi"}
+        ]
+    });
+    let claude_req = openai_request_to_claude(openai_req.clone()).unwrap();
+    assert_eq!(
+        claude_req["system"],
+        json!([
+            {"type": "text", "text": "You are a support agent."},
+            {"type": "text", "text": "Answer in French."}
+        ])
+    );
+    assert_eq!(claude_req["messages"].as_array().unwrap().len(), 1);
+
+    let restored = claude_request_to_openai(claude_req.clone()).unwrap();
+    assert_eq!(restored["messages"][0], json!({"role": "system", "content": "You are a support agent."}));
+    assert_eq!(restored["messages"][1], json!({"role": "system", "content": "Answer in French."}));
+    assert_eq!(restored["messages"][2]["role"], "user");
+
+    let gemini_req = claude_request_to_gemini(claude_req).unwrap();
+    assert_eq!(
+        gemini_req["systemInstruction"]["parts"],
+        json!([{"text": "You are a support agent."}, {"text": "Answer in French."}])
+    );
+    let from_gemini = gemini_request_to_openai(gemini_req).unwrap();
+    assert_eq!(from_gemini["messages"][1]["content"], "Answer in French.");
+
+    // A single system message stays a plain string
+    let single = json!({"messages": [{"role": "system", "content": "Be brief."}, {"role": "user", "content": "Hi"}]});
+    assert_eq!(openai_request_to_claude(single).unwrap()["system"], "Be brief.");
+}
@@ -0,0 +1,134 @@
+/*!
+ * Dataset Tests
+ *
+ * Unit tests for record anonymization, scores, the JSONL file sink and purging.
+ */
+
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::DatasetConfig;
+use aiclient2api_rust::dataset::*;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+
+fn recorder(directory: &Path, max_file_bytes: u64) -> Arc<DatasetRecorder> {
+    let config = DatasetConfig {
+        clients: vec!["key:eval".to_string()],
+        directory: directory.to_path_buf(),
+        max_file_bytes,
+        flush_interval_secs: 0,
+        s3: None,
+    };
+    Arc::new(DatasetRecorder::new(&config, reqwest::Client::new()))
+}
+
+fn records(directory: &Path) -> Vec<Vec<Value>> {
+    let mut files: Vec<_> = std::fs::read_dir(directory).unwrap().map(|entry| entry.unwrap().path()).collect();
+    files.sort();
+    files
+        .iter()
+        .map(|file| {
+            std::fs::read_to_string(file)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_prompt_is_anonymized() {
+    let request = json!({
+        "model": "gpt-4o",
+        "user": "alice",
+        "metadata": {"user_id": "u-1"},
+        "messages": [{"role": "user", "content": [{"type": "text", "text":
+            "Mail jane.doe@example.com or call +1 415 555 0100 from 10.0.0.12, key sk-abcdefghijklmnop1234"
+        }]}]
+    });
+    let prompt = prompt(&request);
+    assert_eq!(prompt.as_object().unwrap().keys().collect::<Vec<_>>(), vec!["messages"]);
+    assert_eq!(
+        prompt["messages"][0]["content"][0]["text"],
+        "Mail [EMAIL] or call [PHONE] from [IP], key [REDACTED]"
+    );
+    assert_eq!(anonymize_text("Version 2.1 shipped on 2024-05-01"), "Version 2.1 shipped on 2024-05-01");
+}
+
+#[test]
+fn test_scores_keep_only_numbers() {
+    assert_eq!(parse_scores(Some(r#"{"helpful": 1, "accuracy": 0.75, "note": "ok"}"#)), json!({"helpful": 1, "accuracy": 0.75}));
+    assert_eq!(parse_scores(Some("not json")), json!({}));
+    assert_eq!(parse_scores(None), json!({}));
+}
+
+#[tokio::test]
+async fn test_buffered_exchanges_go_to_rotating_files() {
+    let directory = std::env::temp_dir().join(format!("dataset-test-{}", uuid::Uuid::new_v4()));
+    let recorder = recorder(&directory, 1);
+    assert!(recorder.records("key:eval") && !recorder.records("static"));
+
+    let request = json!({"model": "claude-sonnet-4", "messages": [{"role": "user", "content": "Hi"}]});
+    let response = json!({"content": [{"type": "text", "text": "Hello"}], "usage": {"input_tokens": 3, "output_tokens": 1}});
+    for _ in 0..2 {
+        let recording = recorder
+            .begin("key:eval", Some("alice"), ModelProtocol::Claude, "claude-sonnet-4", &request)
+            .with_scores(json!({"rating": 5}));
+        recording.finish(&response).await;
+    }
+
+    let files = records(&directory);
+    assert_eq!(files.len(), 2, "a full file is rotated");
+    let record = &files[0][0];
+    assert_eq!(record["model"], "claude-sonnet-4");
+    assert_eq!(record["prompt"]["messages"][0]["content"], "Hi");
+    assert_eq!(record["completion"], json!({"role": "assistant", "content": [{"type": "text", "text": "Hello"}]}));
+    assert_eq!(record["usage"]["output_tokens"], 1);
+    assert_eq!(record["scores"], json!({"rating": 5}));
+    assert_eq!(record["client"], subject_hash("key:eval"));
+    assert_eq!(record["end_user"], subject_hash("alice"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_streamed_exchange_is_recorded_when_complete() {
+    let directory = std::env::temp_dir().join(format!("dataset-test-{}", uuid::Uuid::new_v4()));
+    let recorder = recorder(&directory, 1024 * 1024);
+    let request = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}]});
+    let chunks = vec![
+        Ok(json!({"choices": [{"delta": {"content": "Hel"}}]})),
+        Ok(json!({"choices": [{"delta": {"content": "lo"}}], "usage": {"total_tokens": 5}})),
+    ];
+    let recording = recorder.begin("key:eval", None, ModelProtocol::OpenAI, "gpt-4o", &request);
+    let relayed: Vec<_> = recording.record_stream(Box::pin(futures::stream::iter(chunks))).collect().await;
+    assert_eq!(relayed.len(), 2);
+
+    let files = records(&directory);
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0][0]["completion"], json!({"role": "assistant", "content": "Hello"}));
+    assert_eq!(files[0][0]["usage"]["total_tokens"], 5);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_purge_removes_records_of_a_client_or_end_user() {
+    let directory = std::env::temp_dir().join(format!("dataset-test-{}", uuid::Uuid::new_v4()));
+    let recorder = recorder(&directory, 1024 * 1024);
+    let request = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}]});
+    let response = json!({"choices": [{"message": {"role": "assistant", "content": "Hello"}}]});
+    for (client, end_user) in [("key:eval", Some("alice")), ("key:eval", Some("bob")), ("key:other", None)] {
+        let recording = recorder.begin(client, end_user, ModelProtocol::OpenAI, "gpt-4o", &request);
+        recording.finish(&response).await;
+    }
+
+    assert_eq!(recorder.purge(None, Some("alice")).await.unwrap(), 1);
+    assert_eq!(records(&directory)[0].len(), 2);
+    assert_eq!(recorder.purge(Some("key:eval"), None).await.unwrap(), 1);
+    let remaining = records(&directory);
+    assert_eq!(remaining[0].len(), 1);
+    assert_eq!(remaining[0][0]["client"], subject_hash("key:other"));
+    assert_eq!(recorder.purge(Some("key:eval"), Some("alice")).await.unwrap(), 0);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
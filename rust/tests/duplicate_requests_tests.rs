@@ -0,0 +1,54 @@
+/*!
+ * Duplicate Request Tests
+ *
+ * Unit tests for spotting clients that repeat the same request.
+ */
+
+use aiclient2api_rust::cluster::MemoryStore;
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::DuplicateRequestConfig;
+use aiclient2api_rust::duplicate_requests::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn detector(max_repeats: u64) -> DuplicateDetector {
+    let config = DuplicateRequestConfig { max_repeats, window_secs: 60 };
+    DuplicateDetector::new(Arc::new(MemoryStore::new()), &config)
+}
+
+#[tokio::test]
+async fn test_repeats_past_the_limit_are_flagged_once_as_first() {
+    let detector = detector(2);
+    let body = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hi"}]});
+
+    assert_eq!(detector.observe("key:a", ModelProtocol::OpenAI, &body).await.unwrap(), None);
+    assert_eq!(detector.observe("key:a", ModelProtocol::OpenAI, &body).await.unwrap(), None);
+    assert_eq!(
+        detector.observe("key:a", ModelProtocol::OpenAI, &body).await.unwrap(),
+        Some(Repeat { count: 3, first: true })
+    );
+    assert_eq!(
+        detector.observe("key:a", ModelProtocol::OpenAI, &body).await.unwrap(),
+        Some(Repeat { count: 4, first: false })
+    );
+}
+
+#[tokio::test]
+async fn test_counts_are_per_client_and_per_request() {
+    let detector = detector(1);
+    let body = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hi"}]});
+    let other = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hello"}]});
+
+    assert!(detector.observe("key:a", ModelProtocol::OpenAI, &body).await.unwrap().is_none());
+    assert!(detector.observe("key:b", ModelProtocol::OpenAI, &body).await.unwrap().is_none());
+    assert!(detector.observe("key:a", ModelProtocol::OpenAI, &other).await.unwrap().is_none());
+    assert!(detector.observe("key:a", ModelProtocol::Claude, &body).await.unwrap().is_none());
+    assert!(detector.observe("key:a", ModelProtocol::OpenAI, &body).await.unwrap().is_some());
+}
+
+#[test]
+fn test_key_order_does_not_make_a_request_different() {
+    let a = json!({"model": "gpt-4o", "temperature": 0.2, "messages": []});
+    let b = json!({"messages": [], "temperature": 0.2, "model": "gpt-4o"});
+    assert_eq!(counter_key("key:a", ModelProtocol::OpenAI, &a), counter_key("key:a", ModelProtocol::OpenAI, &b));
+}
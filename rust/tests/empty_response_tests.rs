@@ -0,0 +1,100 @@
+/*!
+ * Empty Response Retry Tests
+ *
+ * Unit tests for detecting and retrying answers without content.
+ */
+
+use aiclient2api_rust::empty_response::{has_content, is_degenerate};
+use aiclient2api_rust::ModelProtocol;
+use serde_json::json;
+
+#[test]
+fn test_degenerate_responses() {
+    let openai = |message: serde_json::Value, finish_reason: &str| json!({"choices": [{"index": 0, "message": message, "finish_reason": finish_reason}]});
+    assert!(is_degenerate(&json!({"choices": []}), ModelProtocol::OpenAI));
+    assert!(is_degenerate(&openai(json!({"role": "assistant", "content": " \n "}), "stop"), ModelProtocol::OpenAI));
+    assert!(is_degenerate(&openai(json!({"role": "assistant", "content": null}), "stop"), ModelProtocol::OpenAI));
+    assert!(!is_degenerate(&openai(json!({"role": "assistant", "content": "Hi"}), "stop"), ModelProtocol::OpenAI));
+    assert!(!is_degenerate(
+        &openai(json!({"role": "assistant", "content": null, "tool_calls": [{"id": "call_1"}]}), "tool_calls"),
+        ModelProtocol::OpenAI
+    ));
+    assert!(!is_degenerate(&openai(json!({"role": "assistant", "content": ""}), "content_filter"), ModelProtocol::OpenAI));
+
+    let claude = |content: serde_json::Value| json!({"type": "message", "content": content, "stop_reason": "end_turn"});
+    assert!(is_degenerate(&claude(json!([])), ModelProtocol::Claude));
+    assert!(is_degenerate(&claude(json!([{"type": "thinking", "thinking": "Hmm"}, {"type": "text", "text": ""}])), ModelProtocol::Claude));
+    assert!(!is_degenerate(&claude(json!([{"type": "tool_use", "id": "toolu_1", "name": "f", "input": {}}])), ModelProtocol::Claude));
+
+    assert!(is_degenerate(&json!({"candidates": []}), ModelProtocol::Gemini));
+    assert!(is_degenerate(&json!({"candidates": [{"content": {"parts": [{"text": "Plan", "thought": true}]}}]}), ModelProtocol::Gemini));
+    assert!(!is_degenerate(&json!({"candidates": [{"content": {"parts": [{"functionCall": {"name": "f"}}]}}]}), ModelProtocol::Gemini));
+    assert!(!is_degenerate(&json!({"candidates": [{"finishReason": "SAFETY"}]}), ModelProtocol::Gemini));
+    assert!(!is_degenerate(&json!({"promptFeedback": {"blockReason": "SAFETY"}}), ModelProtocol::Gemini));
+}
+
+#[test]
+fn test_stream_content_detection() {
+    assert!(!has_content(&json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": ""}}]}), ModelProtocol::OpenAI));
+    assert!(has_content(&json!({"choices": [{"index": 0, "delta": {"content": "Hi"}}]}), ModelProtocol::OpenAI));
+    assert!(!has_content(&json!({"type": "message_start", "message": {}}), ModelProtocol::Claude));
+    assert!(!has_content(&json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "  "}}), ModelProtocol::Claude));
+    assert!(has_content(&json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hi"}}), ModelProtocol::Claude));
+    assert!(has_content(&json!({"type": "error", "error": {"message": "Overloaded"}}), ModelProtocol::Claude));
+}
+
+#[cfg(all(feature = "openai", feature = "claude"))]
+#[tokio::test]
+async fn test_empty_answers_are_retried_then_sent_to_the_fallback() {
+    use aiclient2api_rust::config::Config;
+    use aiclient2api_rust::convert::ChatRequest;
+    use aiclient2api_rust::UnifiedClient;
+    use httpmock::prelude::*;
+
+    let server = MockServer::start_async().await;
+    let openai = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/chat/completions");
+            then.status(200).json_body(json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "model": "gpt-4o-mini",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": ""}, "finish_reason": "stop"}]
+            }));
+        })
+        .await;
+    let claude = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/messages");
+            then.status(200).json_body(json!({
+                "id": "msg_1",
+                "type": "message",
+                "role": "assistant",
+                "model": "gpt-4o-mini",
+                "content": [{"type": "text", "text": "Hello"}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 3, "output_tokens": 1}
+            }));
+        })
+        .await;
+
+    let config = Config {
+        model_provider: "openai-custom".to_string(),
+        openai_api_key: Some("sk-test".to_string()),
+        openai_base_url: Some(server.base_url()),
+        claude_api_key: Some("sk-ant-test".to_string()),
+        claude_base_url: Some(server.base_url()),
+        empty_response_retry: Some(serde_json::from_value(json!({"retries": 1, "fallback_provider": "claude-custom"})).unwrap()),
+        ..Config::default()
+    };
+    let client = UnifiedClient::new(config).await.unwrap();
+    let request = ChatRequest::new(
+        ModelProtocol::OpenAI,
+        json!({"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "Hi"}]}),
+    );
+    let response = client.chat(request).await.unwrap();
+
+    openai.assert_hits_async(2).await;
+    claude.assert_async().await;
+    assert_eq!(response["choices"][0]["message"]["content"], "Hello");
+}
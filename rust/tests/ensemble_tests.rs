@@ -0,0 +1,104 @@
+/*!
+ * Ensemble Tests
+ *
+ * Unit tests for fanning a request out to ensemble members and picking the answer.
+ */
+
+use aiclient2api_rust::common::ModelListResponse;
+use aiclient2api_rust::config::EnsembleConfig;
+use aiclient2api_rust::ensemble::{self, parse_verdict};
+use aiclient2api_rust::ModelProtocol;
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+
+fn ensemble(judge: Option<&str>) -> EnsembleConfig {
+    EnsembleConfig {
+        model: "ensemble-best".to_string(),
+        members: vec!["gpt-4o".to_string(), "claude-sonnet".to_string(), "gemini-pro".to_string()],
+        judge: judge.map(String::from),
+    }
+}
+
+fn answer(model: &str, text: &str, finish_reason: &str) -> Value {
+    json!({
+        "model": model,
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": text}, "finish_reason": finish_reason}]
+    })
+}
+
+fn request() -> Value {
+    json!({"model": "ensemble-best", "stream": true, "messages": [{"role": "user", "content": "Why is the sky blue?"}]})
+}
+
+#[tokio::test]
+async fn test_longest_complete_answer_wins_without_judge() {
+    let sent = Mutex::new(Vec::new());
+    let call = |model: String, body: Value| {
+        sent.lock().unwrap().push(body.clone());
+        async move {
+            match model.as_str() {
+                "gpt-4o" => Ok(answer(&model, "Rayleigh scattering.", "stop")),
+                "claude-sonnet" => Ok(answer(&model, "Shorter wavelengths scatter more, so blue light fills the sky", "length")),
+                _ => Ok(answer(&model, "Because of Rayleigh scattering of sunlight.", "stop")),
+            }
+        }
+    };
+
+    let (response, member) = ensemble::run(&ensemble(None), ModelProtocol::OpenAI, request(), call).await.unwrap();
+    assert_eq!(member, "gemini-pro");
+    assert_eq!(response["model"], "ensemble-best");
+    assert_eq!(response["choices"][0]["message"]["content"], "Because of Rayleigh scattering of sunlight.");
+
+    let sent = sent.into_inner().unwrap();
+    assert_eq!(sent.len(), 3);
+    assert!(sent.iter().all(|body| body.get("stream").is_none()));
+    assert_eq!(sent[1]["model"], "claude-sonnet");
+}
+
+#[tokio::test]
+async fn test_judge_picks_and_failed_members_are_skipped() {
+    let judged = Mutex::new(None);
+    let call = |model: String, body: Value| {
+        if model == "judge" {
+            *judged.lock().unwrap() = Some(body.clone());
+        }
+        async move {
+            match model.as_str() {
+                "gpt-4o" => Err(anyhow!("upstream timeout")),
+                "judge" => Ok(answer(&model, "Candidate 1 is best.", "stop")),
+                _ => Ok(answer(&model, &format!("answer from {}", model), "stop")),
+            }
+        }
+    };
+
+    let (response, member) = ensemble::run(&ensemble(Some("judge")), ModelProtocol::OpenAI, request(), call).await.unwrap();
+    assert_eq!(member, "claude-sonnet");
+    assert_eq!(response["choices"][0]["message"]["content"], "answer from claude-sonnet");
+
+    let judged = judged.into_inner().unwrap().unwrap();
+    let prompt = judged["messages"][1]["content"].as_str().unwrap();
+    assert!(prompt.contains("Why is the sky blue?"));
+    assert!(prompt.contains("Candidate 2:\nanswer from gemini-pro"));
+    assert!(!prompt.contains("Candidate 3"));
+}
+
+#[tokio::test]
+async fn test_fails_only_when_every_member_fails() {
+    let call = |model: String, _body: Value| async move { Err::<Value, _>(anyhow!("{} is down", model)) };
+    let err = ensemble::run(&ensemble(Some("judge")), ModelProtocol::Claude, request(), call).await.unwrap_err();
+    assert_eq!(err.to_string(), "gemini-pro is down");
+}
+
+#[test]
+fn test_parse_verdict_and_model_listing() {
+    assert_eq!(parse_verdict("2", 3), Some(1));
+    assert_eq!(parse_verdict("The best is candidate 3.", 3), Some(2));
+    assert_eq!(parse_verdict("4", 3), None);
+    assert_eq!(parse_verdict("none of them", 3), None);
+
+    let mut models: ModelListResponse = serde_json::from_value(json!({"models": [{"name": "gemini-pro"}]})).unwrap();
+    ensemble::list_models(&[ensemble(None)], &mut models);
+    let ids: Vec<_> = models.entries().filter_map(|m| m.model_id()).collect();
+    assert_eq!(ids, vec!["gemini-pro", "ensemble-best"]);
+}
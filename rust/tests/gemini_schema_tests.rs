@@ -0,0 +1,117 @@
+/*!
+ * Gemini Schema Tests
+ *
+ * Unit tests for translating JSON Schema into Gemini's schema dialect.
+ */
+
+use aiclient2api_rust::gemini_schema::*;
+use serde_json::json;
+
+#[test]
+fn test_refs_are_inlined_and_unsupported_keywords_dropped() {
+    let schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "properties": {
+            "owner": {"$ref": "#/$defs/person", "description": "Who owns it"},
+            "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true}
+        },
+        "required": ["owner"],
+        "additionalProperties": false,
+        "$defs": {
+            "person": {"type": "object", "properties": {"name": {"type": "string"}}, "additionalProperties": false}
+        }
+    });
+
+    let translated = to_gemini(&schema);
+    assert_eq!(
+        translated.schema,
+        json!({
+            "type": "object",
+            "properties": {
+                "owner": {"type": "object", "properties": {"name": {"type": "string"}}, "description": "Who owns it"},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["owner"]
+        })
+    );
+    assert_eq!(translated.dropped.into_iter().collect::<Vec<_>>(), vec!["additionalProperties", "uniqueItems"]);
+}
+
+#[test]
+fn test_nullable_const_and_combinators() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "nickname": {"type": ["string", "null"]},
+            "age": {"anyOf": [{"type": "integer"}, {"type": "null"}]},
+            "kind": {"const": "cat"},
+            "id": {"oneOf": [{"type": "string"}, {"type": "integer"}]},
+            "pet": {"allOf": [
+                {"properties": {"name": {"type": "string"}}, "required": ["name"]},
+                {"properties": {"legs": {"type": "integer"}}, "required": ["legs"]}
+            ]}
+        }
+    });
+
+    let translated = to_gemini(&schema);
+    let properties = &translated.schema["properties"];
+    assert_eq!(properties["nickname"], json!({"type": "string", "nullable": true}));
+    assert_eq!(properties["age"], json!({"type": "integer", "nullable": true}));
+    assert_eq!(properties["kind"], json!({"enum": ["cat"]}));
+    assert_eq!(properties["id"], json!({"anyOf": [{"type": "string"}, {"type": "integer"}]}));
+    assert_eq!(
+        properties["pet"],
+        json!({"properties": {"name": {"type": "string"}, "legs": {"type": "integer"}}, "required": ["name", "legs"]})
+    );
+    assert!(translated.dropped.is_empty());
+}
+
+#[test]
+fn test_recursive_refs_stop_at_a_plain_object() {
+    let schema = json!({
+        "$ref": "#/definitions/node",
+        "definitions": {
+            "node": {"type": "object", "properties": {"children": {"type": "array", "items": {"$ref": "#/definitions/node"}}}}
+        }
+    });
+
+    let translated = to_gemini(&schema);
+    assert_eq!(translated.schema["properties"]["children"]["items"], json!({"type": "object"}));
+    assert!(translated.dropped.contains("recursive $ref #/definitions/node"));
+}
+
+#[test]
+fn test_gemini_schemas_translate_back_to_json_schema() {
+    let schema = json!({"type": "OBJECT", "properties": {"name": {"type": "STRING", "nullable": true}}});
+    assert_eq!(
+        to_json_schema(&schema),
+        json!({"type": "object", "properties": {"name": {"type": ["string", "null"]}}})
+    );
+}
+
+#[test]
+fn test_unsupported_string_formats_are_dropped() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "when": {"type": "string", "format": "date-time"},
+            "site": {"type": "string", "format": "uri"},
+            "ratio": {"type": "number", "format": "double"}
+        }
+    });
+
+    let translated = to_gemini(&schema);
+    assert_eq!(translated.schema["properties"]["when"]["format"], "date-time");
+    assert!(translated.schema["properties"]["site"].get("format").is_none());
+    assert_eq!(translated.schema["properties"]["ratio"]["format"], "double");
+    assert_eq!(translated.dropped.into_iter().collect::<Vec<_>>(), vec!["format uri"]);
+}
+
+#[test]
+fn test_functions_without_arguments_have_no_parameters() {
+    assert!(parameters(&json!({"type": "object", "properties": {}, "additionalProperties": false})).is_none());
+    assert!(parameters(&json!({"type": "object"})).is_none());
+    let declared = parameters(&json!({"type": "object", "properties": {"city": {"type": "string"}}})).unwrap();
+    assert_eq!(declared.schema["properties"]["city"], json!({"type": "string"}));
+}
@@ -0,0 +1,83 @@
+/*!
+ * Health Probe Tests
+ *
+ * Unit tests for the readiness checks behind `/readyz`.
+ */
+
+#![cfg(feature = "openai")]
+
+use aiclient2api_rust::adapter::create_adapter;
+use aiclient2api_rust::config::{Config, ReadinessConfig};
+use aiclient2api_rust::health::{warm_up, ReadinessProbe};
+use aiclient2api_rust::provider_registry::Routing;
+use aiclient2api_rust::ModelProvider;
+use httpmock::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+async fn routing(base_url: String) -> Routing {
+    let config = Config {
+        model_provider: "openai-custom".to_string(),
+        openai_api_key: Some("sk-test".to_string()),
+        openai_base_url: Some(base_url),
+        ..Config::default()
+    };
+    let adapter = create_adapter(ModelProvider::OpenAICustom, &config).await.unwrap();
+    Routing {
+        default_provider: "openai-custom".to_string(),
+        adapters: HashMap::from([("openai-custom".to_string(), Arc::from(adapter))]),
+        routers: Vec::new(),
+        routes: Vec::new(),
+        aliases: HashMap::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_readiness_waits_for_warm_up() {
+    let routing = routing("http://127.0.0.1:1/v1".to_string()).await;
+    let probe = ReadinessProbe::new(&ReadinessConfig::default(), reqwest::Client::new());
+    let (ready, checks) = probe.check(&routing).await;
+    assert!(!ready);
+    assert_eq!(checks, serde_json::json!({"config": true, "providers": true, "warmed_up": false}));
+
+    // An unreachable upstream does not hold the warm-up back
+    warm_up(&routing).await;
+    probe.finish_warm_up();
+    assert!(probe.check(&routing).await.0);
+
+    let no_warm_up = ReadinessConfig { warm_up: false, ..Default::default() };
+    let (ready, checks) = ReadinessProbe::new(&no_warm_up, reqwest::Client::new()).check(&routing).await;
+    assert!(ready);
+    assert_eq!(checks, serde_json::json!({"config": true, "providers": true}));
+}
+
+#[tokio::test]
+async fn test_readiness_follows_upstream_reachability() {
+    let server = MockServer::start_async().await;
+    let head = server
+        .mock_async(|when, then| {
+            when.method("HEAD").path("/v1");
+            then.status(404);
+        })
+        .await;
+    let config = ReadinessConfig {
+        warm_up: false,
+        probe_upstreams: true,
+        probe_timeout_secs: 2,
+        ..Default::default()
+    };
+    let probe = ReadinessProbe::new(&config, reqwest::Client::new());
+
+    let up = routing(server.url("/v1")).await;
+    let (ready, checks) = probe.check(&up).await;
+    assert!(ready);
+    assert_eq!(checks["upstreams"]["openai-custom"], true);
+    // Results are reused within the probe interval
+    assert!(probe.check(&up).await.0);
+    head.assert_hits_async(1).await;
+
+    let fresh = ReadinessProbe::new(&ReadinessConfig { probe_interval_secs: 0, ..config }, reqwest::Client::new());
+    let (ready, checks) = fresh.check(&routing("http://127.0.0.1:1/v1".to_string()).await).await;
+    assert!(!ready);
+    assert_eq!(checks["upstreams"]["openai-custom"], false);
+}
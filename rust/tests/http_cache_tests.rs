@@ -0,0 +1,43 @@
+/*!
+ * Conditional Response Tests
+ *
+ * Unit tests for ETag generation and If-None-Match handling.
+ */
+
+#![cfg(feature = "server")]
+
+use aiclient2api_rust::http_cache::*;
+use axum::http::{header, HeaderMap, StatusCode};
+use serde_json::json;
+
+#[test]
+fn test_etag_is_stable_and_content_sensitive() {
+    assert_eq!(etag_for(b"abc"), etag_for(b"abc"));
+    assert_ne!(etag_for(b"abc"), etag_for(b"abd"));
+    assert!(etag_for(b"abc").starts_with('"'));
+}
+
+#[test]
+fn test_if_none_match_forms() {
+    let etag = etag_for(b"models");
+    assert!(if_none_match(&etag, &etag));
+    assert!(if_none_match(&format!("W/{}", etag), &etag));
+    assert!(if_none_match(&format!("\"other\", {}", etag), &etag));
+    assert!(if_none_match("*", &etag));
+    assert!(!if_none_match("\"other\"", &etag));
+}
+
+#[test]
+fn test_conditional_json_returns_304_for_matching_etag() {
+    let body = json!({ "object": "list", "data": [{ "id": "gpt-4o" }] });
+
+    let first = conditional_json(&HeaderMap::new(), &body);
+    assert_eq!(first.status(), StatusCode::OK);
+    let etag = first.headers().get(header::ETAG).unwrap().clone();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::IF_NONE_MATCH, etag.clone());
+    let second = conditional_json(&headers, &body);
+    assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(second.headers().get(header::ETAG), Some(&etag));
+}
@@ -0,0 +1,69 @@
+/*!
+ * HTTP Client Tests
+ *
+ * Unit tests for the shared upstream client and connection pre-warming.
+ */
+
+use aiclient2api_rust::config::HttpClientConfig;
+use aiclient2api_rust::http_client::*;
+use httpmock::prelude::*;
+
+#[test]
+fn test_origin_strips_path_and_query() {
+    assert_eq!(origin("https://api.openai.com/v1/chat?x=1").as_deref(), Some("https://api.openai.com"));
+    assert_eq!(origin("http://localhost:8080/v1").as_deref(), Some("http://localhost:8080"));
+    assert_eq!(origin("not a url"), None);
+}
+
+#[test]
+fn test_shared_client_is_built_once() {
+    let config = HttpClientConfig {
+        http2_keep_alive_interval_secs: Some(30),
+        ..Default::default()
+    };
+    assert!(build(&config).is_ok());
+    assert!(shared(&config).is_ok());
+    // Later configs do not rebuild the process-wide client
+    assert!(shared(&HttpClientConfig::default()).is_ok());
+}
+
+#[tokio::test]
+async fn test_prewarm_connects_once_per_origin() {
+    let server = MockServer::start_async().await;
+    let head = server.mock_async(|when, then| {
+        when.method("HEAD").path("/");
+        then.status(404);
+    }).await;
+
+    let client = build(&HttpClientConfig::default()).unwrap();
+    let upstreams = vec![server.url("/v1"), server.url("/v1/messages"), "http://127.0.0.1:1/v1".to_string()];
+    assert_eq!(prewarm(&client, &upstreams).await, 1);
+    head.assert_hits_async(1).await;
+}
+
+#[tokio::test]
+async fn test_client_identifies_itself() {
+    let server = MockServer::start_async().await;
+    let identified = server.mock_async(|when, then| {
+        when.method(GET)
+            .header("user-agent", "acme-gateway-client/2.1")
+            .header("x-client-name", "billing");
+        then.status(200);
+    }).await;
+
+    let config = HttpClientConfig {
+        user_agent: "acme-gateway-client/2.1".to_string(),
+        headers: [("x-client-name".to_string(), "billing".to_string())].into(),
+        ..Default::default()
+    };
+    let client = build(&config).unwrap();
+    client.get(server.url("/")).send().await.unwrap();
+    identified.assert_async().await;
+
+    assert!(HttpClientConfig::default().user_agent.starts_with("aiclient2api-rust/"));
+    let invalid = HttpClientConfig {
+        headers: [("bad header".to_string(), "x".to_string())].into(),
+        ..Default::default()
+    };
+    assert!(build(&invalid).is_err());
+}
@@ -0,0 +1,67 @@
+/*!
+ * Idempotency Key Tests
+ *
+ * Unit tests for claiming, replaying and releasing idempotency keys.
+ */
+
+use aiclient2api_rust::cluster::MemoryStore;
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::IdempotencyConfig;
+use aiclient2api_rust::idempotency::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn store() -> IdempotencyStore {
+    IdempotencyStore::new(Arc::new(MemoryStore::new()), &IdempotencyConfig::default())
+}
+
+#[tokio::test]
+async fn test_retry_waits_for_then_replays_the_first_answer() {
+    let records = store();
+    let body = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hi"}]});
+    let request = IdempotentRequest::new("key:a", "retry-1", ModelProtocol::OpenAI, &body);
+
+    assert_eq!(records.claim(&request).await.unwrap(), Claim::New);
+    assert_eq!(records.claim(&request).await.unwrap(), Claim::InProgress);
+
+    let answer = json!({"id": "chatcmpl-1", "choices": []});
+    records.complete(&request, &answer).await.unwrap();
+    assert_eq!(records.claim(&request).await.unwrap(), Claim::Replay(answer));
+}
+
+#[tokio::test]
+async fn test_released_key_runs_again() {
+    let records = store();
+    let request = IdempotentRequest::new("key:a", "retry-1", ModelProtocol::OpenAI, &json!({"model": "gpt-4o"}));
+
+    assert_eq!(records.claim(&request).await.unwrap(), Claim::New);
+    records.release(&request).await.unwrap();
+    assert_eq!(records.claim(&request).await.unwrap(), Claim::New);
+}
+
+#[tokio::test]
+async fn test_keys_are_per_client_and_bound_to_one_request() {
+    let records = store();
+    let body = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hi"}]});
+    let other = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hello"}]});
+
+    let first = IdempotentRequest::new("key:a", "retry-1", ModelProtocol::OpenAI, &body);
+    assert_eq!(records.claim(&first).await.unwrap(), Claim::New);
+
+    // Another client's key of the same name is its own
+    let other_client = IdempotentRequest::new("key:b", "retry-1", ModelProtocol::OpenAI, &body);
+    assert_eq!(records.claim(&other_client).await.unwrap(), Claim::New);
+
+    let reused = IdempotentRequest::new("key:a", "retry-1", ModelProtocol::OpenAI, &other);
+    assert_eq!(records.claim(&reused).await.unwrap(), Claim::Mismatch);
+    let other_protocol = IdempotentRequest::new("key:a", "retry-1", ModelProtocol::Claude, &body);
+    assert_eq!(records.claim(&other_protocol).await.unwrap(), Claim::Mismatch);
+}
+
+#[test]
+fn test_valid_key() {
+    assert!(valid_key("4f1c2d3e-retry"));
+    assert!(!valid_key(""));
+    assert!(!valid_key("has space"));
+    assert!(!valid_key(&"k".repeat(256)));
+}
@@ -0,0 +1,23 @@
+/*!
+ * JSON Parsing Tests
+ *
+ * Unit tests for the hot-path body parser.
+ */
+
+use aiclient2api_rust::json;
+use serde_json::json;
+
+#[test]
+fn test_parses_like_serde_json() {
+    let body = r#"{"model":"gpt-4o","messages":[{"role":"user","content":"héllo"}],"temperature":0.5,"n":1}"#.as_bytes();
+    let expected: serde_json::Value = serde_json::from_slice(body).unwrap();
+    assert_eq!(json::from_vec(body.to_vec()).unwrap(), expected);
+    assert_eq!(json::from_slice(body).unwrap(), expected);
+    assert_eq!(expected["messages"][0]["content"], json!("héllo"));
+}
+
+#[test]
+fn test_rejects_invalid_json() {
+    assert!(json::from_vec(b"{\"model\": ".to_vec()).is_err());
+    assert!(json::from_slice(b"not json").is_err());
+}
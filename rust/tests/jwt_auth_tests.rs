@@ -0,0 +1,101 @@
+/*!
+ * JWT Client Authentication Tests
+ *
+ * Unit tests for HS256 validation and claim mapping.
+ */
+
+use aiclient2api_rust::config::JwtConfig;
+use aiclient2api_rust::jwt_auth::*;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::json;
+
+fn config() -> JwtConfig {
+    serde_json::from_value(json!({
+        "hs256_secret": "test-secret",
+        "issuer": "https://idp.example.com"
+    }))
+    .unwrap()
+}
+
+fn token(claims: serde_json::Value, secret: &str) -> String {
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+}
+
+fn expiry() -> i64 {
+    chrono::Utc::now().timestamp() + 3600
+}
+
+#[tokio::test]
+async fn test_hs256_claims_mapping() {
+    let validator = JwtValidator::new(config());
+    let jwt = token(
+        json!({
+            "sub": "user-1",
+            "iss": "https://idp.example.com",
+            "exp": expiry(),
+            "tenant": "acme",
+            "allowed_models": ["gpt-4o", "claude-sonnet-4"],
+            "quota": 1000,
+            "scope": "chat models"
+        }),
+        "test-secret",
+    );
+
+    assert!(looks_like_jwt(&jwt));
+    let identity = validator.validate(&jwt).await.unwrap();
+    assert_eq!(identity.subject, "user-1");
+    assert_eq!(identity.tenant.as_deref(), Some("acme"));
+    assert_eq!(
+        identity.allowed_models,
+        Some(vec!["gpt-4o".to_string(), "claude-sonnet-4".to_string()])
+    );
+    assert_eq!(identity.quota, Some(1000));
+    assert!(identity.allows("chat"));
+    assert!(!identity.allows("files"));
+}
+
+#[tokio::test]
+async fn test_tokens_without_scopes_get_only_the_default_scopes() {
+    let claims = json!({ "sub": "u", "iss": "https://idp.example.com", "exp": expiry() });
+
+    let identity = JwtValidator::new(config()).validate(&token(claims.clone(), "test-secret")).await.unwrap();
+    assert!(!identity.allows("chat"));
+
+    let mut config = config();
+    config.default_scopes = vec!["chat".to_string()];
+    let identity = JwtValidator::new(config).validate(&token(claims, "test-secret")).await.unwrap();
+    assert!(identity.allows("chat"));
+    assert!(!identity.allows("fine_tuning"));
+}
+
+#[tokio::test]
+async fn test_rejects_bad_signature_and_issuer() {
+    let validator = JwtValidator::new(config());
+
+    let wrong_secret = token(
+        json!({ "sub": "u", "iss": "https://idp.example.com", "exp": expiry() }),
+        "other-secret",
+    );
+    assert!(validator.validate(&wrong_secret).await.is_err());
+
+    let wrong_issuer = token(
+        json!({ "sub": "u", "iss": "https://evil.example.com", "exp": expiry() }),
+        "test-secret",
+    );
+    assert!(validator.validate(&wrong_issuer).await.is_err());
+}
+
+#[tokio::test]
+async fn test_rejects_expired_token() {
+    let validator = JwtValidator::new(config());
+    let expired = token(
+        json!({ "sub": "u", "iss": "https://idp.example.com", "exp": chrono::Utc::now().timestamp() - 3600 }),
+        "test-secret",
+    );
+    assert!(validator.validate(&expired).await.is_err());
+}
+
+#[test]
+fn test_static_keys_are_not_jwts() {
+    assert!(!looks_like_jwt("sk-aic-0123456789abcdef"));
+}
@@ -0,0 +1,98 @@
+/*!
+ * Key Health Tests
+ *
+ * Tests for scoring pool keys and quarantining the failing ones.
+ */
+
+use aiclient2api_rust::config::{KeyHealthConfig, ProviderConfig};
+use aiclient2api_rust::key_health::{KeyHealth, KeyOutcome};
+use aiclient2api_rust::pool_manager::ProviderPoolManager;
+use aiclient2api_rust::upstream_error::UpstreamError;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_outcomes_are_classified_by_status() {
+    let outcome = |status: u16| KeyOutcome::of_error(&UpstreamError::new(StatusCode::from_u16(status).unwrap(), "").into());
+    assert_eq!(outcome(401), KeyOutcome::AuthFailure);
+    assert_eq!(outcome(429), KeyOutcome::RateLimited);
+    assert_eq!(outcome(529), KeyOutcome::ServerError);
+    assert_eq!(outcome(400), KeyOutcome::Other);
+    assert_eq!(KeyOutcome::of_error(&anyhow::anyhow!("error sending request: connection refused")), KeyOutcome::Other);
+    // Only the status counts, not what the upstream wrote
+    let bad_request = UpstreamError::new(StatusCode::BAD_REQUEST, "Invalid API key format in tool arguments; rate limit docs");
+    assert_eq!(KeyOutcome::of_error(&bad_request.into()), KeyOutcome::Other);
+}
+
+#[test]
+fn test_quarantine_backs_off_and_decays() {
+    let config = KeyHealthConfig::default();
+    let now = Instant::now();
+    let mut health = KeyHealth::default();
+
+    assert_eq!(health.record(KeyOutcome::RateLimited, &config, now), None);
+    assert_eq!(health.record(KeyOutcome::RateLimited, &config, now), None);
+    assert_eq!(health.score(&config, now), 33);
+    // The penalty halves every half-life
+    assert_eq!(health.score(&config, now + Duration::from_secs(300)), 67);
+
+    assert_eq!(health.record(KeyOutcome::RateLimited, &config, now), Some(Duration::from_secs(60)));
+    assert!(health.is_quarantined());
+    assert!(!health.due_for_probe(now));
+    assert!(health.due_for_probe(now + Duration::from_secs(60)));
+
+    // A failed probe doubles the backoff; a good one puts the key back with a clean score
+    let later = now + Duration::from_secs(60);
+    assert_eq!(health.probed(false, &config, later), Some(Duration::from_secs(120)));
+    assert_eq!(health.probed(true, &config, later), None);
+    assert!(!health.is_quarantined());
+    assert_eq!(health.score(&config, later), 100);
+}
+
+fn key(uuid: &str) -> ProviderConfig {
+    serde_json::from_value(json!({"uuid": uuid, "CUSTOM_API_KEY": format!("sk-{}", uuid)})).unwrap()
+}
+
+#[tokio::test]
+async fn test_pool_skips_quarantined_keys() {
+    let config = KeyHealthConfig {
+        backoff_secs: 0,
+        ..KeyHealthConfig::default()
+    };
+    let pools = HashMap::from([("custom".to_string(), vec![key("a"), key("b")])]);
+    let pool = ProviderPoolManager::new(pools, &config);
+
+    let first = pool.select_key("custom").await.unwrap();
+    assert_eq!((first.uuid.as_str(), first.api_key.as_str()), ("a", "sk-a"));
+    assert_eq!(pool.select_key("custom").await.unwrap().uuid, "b");
+    assert_eq!(pool.select_key("openai-custom").await, None);
+
+    // One auth failure is enough
+    assert!(pool.record_outcome("custom", "a", KeyOutcome::AuthFailure).await.is_some());
+    assert_eq!(pool.select_key("custom").await.unwrap().uuid, "b");
+    assert_eq!(pool.select_key("custom").await.unwrap().uuid, "b");
+    let health = pool.health_snapshot().await;
+    assert_eq!(health["custom"]["a"]["quarantined"], true);
+    assert_eq!(health["custom"]["a"]["auth_failures"], 1);
+
+    // A key that cannot be probed returns once its backoff is over
+    let changed = pool.probe_quarantined(&reqwest::Client::new()).await;
+    assert_eq!(changed, vec![("custom".to_string(), "a".to_string(), true)]);
+    assert!(pool.get_provider("custom", "a").await.unwrap().is_healthy);
+}
+
+#[tokio::test]
+async fn test_refreshed_credentials_keep_key_health() {
+    let pools = HashMap::from([("custom".to_string(), vec![key("a")])]);
+    let pool = ProviderPoolManager::new(pools, &KeyHealthConfig::default());
+    pool.record_outcome("custom", "a", KeyOutcome::AuthFailure).await;
+
+    let mut rotated = key("a");
+    rotated.credentials.insert("CUSTOM_API_KEY".to_string(), json!("sk-rotated"));
+    pool.update_credentials(&HashMap::from([("custom".to_string(), vec![rotated])])).await;
+    let provider = pool.get_provider("custom", "a").await.unwrap();
+    assert_eq!(provider.credentials["CUSTOM_API_KEY"], "sk-rotated");
+    assert!(!provider.is_healthy);
+}
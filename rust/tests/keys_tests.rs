@@ -0,0 +1,189 @@
+/*!
+ * Client Key Store Tests
+ *
+ * Unit tests for client key lifecycle management.
+ */
+
+use aiclient2api_rust::keys::*;
+use chrono::{Duration, Utc};
+
+#[tokio::test]
+async fn test_create_and_authenticate() {
+    let store = KeyStore::open(None).await.unwrap();
+
+    let (record, plaintext) = store
+        .create(Some("ci".to_string()), vec!["chat".to_string()], None)
+        .await
+        .unwrap();
+
+    assert!(plaintext.starts_with("sk-aic-"));
+    assert_ne!(record.key_hash, plaintext);
+
+    let key = store.authenticate(&plaintext).await.unwrap();
+    assert_eq!(key.id, record.id);
+    assert!(key.last_used_at.is_some());
+    assert!(key.allows("chat"));
+    assert!(!key.allows("models"));
+
+    assert!(store.authenticate("sk-aic-unknown").await.is_none());
+}
+
+#[tokio::test]
+async fn test_expired_key_rejected() {
+    let store = KeyStore::open(None).await.unwrap();
+
+    let (_, plaintext) = store
+        .create(None, vec![], Some(Utc::now() - Duration::seconds(1)))
+        .await
+        .unwrap();
+
+    assert!(store.authenticate(&plaintext).await.is_none());
+}
+
+#[tokio::test]
+async fn test_rotate_keeps_old_key_during_grace() {
+    let store = KeyStore::open(None).await.unwrap();
+    let (record, old_plaintext) = store.create(None, vec![], None).await.unwrap();
+
+    let (old, new_key, new_plaintext) = store
+        .rotate(&record.id, Duration::hours(1))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(old.replaced_by.as_deref(), Some(new_key.id.as_str()));
+    assert!(store.authenticate(&old_plaintext).await.is_some());
+    assert!(store.authenticate(&new_plaintext).await.is_some());
+
+    // Zero grace expires the old key immediately
+    let (_, _, _) = store.rotate(&new_key.id, Duration::zero()).await.unwrap().unwrap();
+    assert!(store.authenticate(&new_plaintext).await.is_none());
+}
+
+#[tokio::test]
+async fn test_revoke() {
+    let store = KeyStore::open(None).await.unwrap();
+    let (record, plaintext) = store.create(None, vec![], None).await.unwrap();
+
+    let revoked = store.revoke(&record.id).await.unwrap().unwrap();
+    assert!(revoked.revoked_at.is_some());
+    assert!(store.authenticate(&plaintext).await.is_none());
+    assert!(store.revoke("missing").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_no_content_logging_survives_rotation() {
+    let store = KeyStore::open(None).await.unwrap();
+    let (record, _) = store.create(None, vec![], None).await.unwrap();
+    assert!(!record.no_content_logging);
+
+    let updated = store.set_no_content_logging(&record.id, true).await.unwrap().unwrap();
+    assert!(updated.no_content_logging);
+    assert!(store.set_no_content_logging("missing", true).await.unwrap().is_none());
+
+    let (_, new_key, _) = store.rotate(&record.id, Duration::hours(1)).await.unwrap().unwrap();
+    assert!(new_key.no_content_logging, "the replacement keeps the opt-out");
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_cli_add_list_revoke() {
+    let store = KeyStore::open(None).await.unwrap();
+
+    let output = aiclient2api_rust::keys_cli::run(
+        &args(&["add", "--name", "ci", "--scope", "chat", "--no-content-logging", "--model", "gpt-4o*"]),
+        &store,
+    )
+    .await
+    .unwrap();
+    let plaintext = output.split_whitespace().find(|w| w.starts_with("sk-aic-")).unwrap();
+    let key = store.authenticate(plaintext).await.unwrap();
+    assert_eq!(key.name.as_deref(), Some("ci"));
+    assert_eq!(key.scopes, vec!["chat"]);
+    assert!(key.no_content_logging);
+    assert_eq!(key.models, Some(vec!["gpt-4o*".to_string()]));
+    assert!(key.tenant.is_none());
+
+    let listing = aiclient2api_rust::keys_cli::run(&args(&["list"]), &store).await.unwrap();
+    assert!(listing.contains(&key.id));
+    assert!(listing.contains("active"));
+    assert!(!listing.contains(plaintext), "list never shows the key itself");
+
+    aiclient2api_rust::keys_cli::run(&args(&["revoke", &key.id]), &store).await.unwrap();
+    assert!(store.authenticate(plaintext).await.is_none());
+    assert!(aiclient2api_rust::keys_cli::run(&args(&["revoke", "missing"]), &store).await.is_err());
+    assert!(aiclient2api_rust::keys_cli::run(&args(&["add", "--bogus"]), &store).await.is_err());
+}
+
+#[test]
+fn test_allows_model_patterns() {
+    let allowed = vec!["gpt-4o*".to_string(), "fast".to_string()];
+    assert!(allows_model(&allowed, "gpt-4o-mini"));
+    assert!(allows_model(&allowed, "fast"));
+    assert!(!allows_model(&allowed, "faster"));
+    assert!(!allows_model(&allowed, "o1-preview"));
+    assert!(allows_model(&["*".to_string()], "o1-preview"));
+    assert!(!allows_model(&[], "gpt-4o"));
+}
+
+#[tokio::test]
+async fn test_model_access_survives_rotation() {
+    let store = KeyStore::open(None).await.unwrap();
+    let (record, _) = store.create(None, vec![], None).await.unwrap();
+    assert!(record.models.is_none());
+
+    let key = store
+        .set_model_access(&record.id, Some("research".to_string()), Some(vec!["o1*".to_string()]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(key.tenant.as_deref(), Some("research"));
+
+    let (_, new_key, _) = store.rotate(&record.id, Duration::hours(1)).await.unwrap().unwrap();
+    assert_eq!(new_key.tenant.as_deref(), Some("research"));
+    assert_eq!(new_key.models, Some(vec!["o1*".to_string()]));
+
+    assert!(store.set_model_access("missing", None, None).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_reload_picks_up_keys_added_elsewhere() {
+    let path = std::env::temp_dir().join(format!("keys-test-{}.json", uuid::Uuid::new_v4()));
+    let server = KeyStore::open(Some(path.clone())).await.unwrap();
+    assert!(!server.reload_if_changed().await.unwrap());
+
+    // Another process (the CLI) writes to the same file
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let cli = KeyStore::open(Some(path.clone())).await.unwrap();
+    let (_, plaintext) = cli.create(None, vec![], None).await.unwrap();
+
+    assert!(server.authenticate(&plaintext).await.is_none());
+    assert!(server.reload_if_changed().await.unwrap());
+    assert!(server.authenticate(&plaintext).await.is_some());
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn test_changes_keep_keys_added_elsewhere() {
+    let path = std::env::temp_dir().join(format!("keys-test-{}.json", uuid::Uuid::new_v4()));
+    let server = KeyStore::open(Some(path.clone())).await.unwrap();
+    let (own, _) = server.create(None, vec![], None).await.unwrap();
+
+    // The CLI adds a key before the server's next periodic reload
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let cli = KeyStore::open(Some(path.clone())).await.unwrap();
+    let (added, _) = cli.create(None, vec![], None).await.unwrap();
+
+    // Changing a key rereads the file first, so saving does not drop the CLI's key
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    server.revoke(&own.id).await.unwrap();
+    let reopened = KeyStore::open(Some(path.clone())).await.unwrap();
+    assert!(reopened.get(&added.id).await.is_some());
+    assert!(reopened.get(&own.id).await.unwrap().revoked_at.is_some());
+
+    let _ = std::fs::remove_file(path);
+}
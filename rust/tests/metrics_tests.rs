@@ -0,0 +1,129 @@
+/*!
+ * Metrics Tests
+ *
+ * Unit tests for the stats summary counters.
+ */
+
+use aiclient2api_rust::metrics::*;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_percentile() {
+    let sorted = vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 100];
+
+    assert_eq!(percentile(&sorted, 50.0), Some(50));
+    assert_eq!(percentile(&sorted, 90.0), Some(90));
+    assert_eq!(percentile(&sorted, 99.0), Some(100));
+    assert_eq!(percentile(&[], 50.0), None);
+}
+
+#[test]
+fn test_snapshot_counts() {
+    let metrics = Metrics::new();
+
+    metrics.record_request(200);
+    metrics.record_request(401);
+    metrics.record_request(500);
+    metrics.record_cache_hit();
+    metrics.record_cache_miss();
+    metrics.record_provider_call("openai-custom", Duration::from_millis(120), true);
+    metrics.record_provider_call("openai-custom", Duration::from_millis(80), false);
+
+    let snapshot = metrics.snapshot();
+
+    assert_eq!(snapshot["requests"]["total"], 3);
+    assert_eq!(snapshot["requests"]["client_errors"], 1);
+    assert_eq!(snapshot["requests"]["server_errors"], 1);
+    assert_eq!(snapshot["cache"]["hit_ratio"], 0.5);
+    assert_eq!(snapshot["providers"]["openai-custom"]["requests"], 2);
+    assert_eq!(snapshot["providers"]["openai-custom"]["errors"], 1);
+    assert_eq!(snapshot["providers"]["openai-custom"]["latency_ms"]["p50"], 80);
+}
+
+#[test]
+fn test_active_streams_guard() {
+    let metrics = Metrics::new();
+
+    let guard = metrics.stream_started();
+    assert_eq!(metrics.snapshot()["active_streams"], 1);
+
+    drop(guard);
+    assert_eq!(metrics.snapshot()["active_streams"], 0);
+}
+
+#[test]
+fn test_end_user_usage() {
+    let metrics = Metrics::new();
+
+    let claude = json!({"usage": {"input_tokens": 12, "output_tokens": 30}});
+    let openai = json!({"usage": {"prompt_tokens": 5, "completion_tokens": 7}});
+    assert_eq!(usage_tokens(&claude), (12, 30));
+    assert_eq!(usage_tokens(&openai), (5, 7));
+    assert_eq!(usage_tokens(&json!({})), (0, 0));
+
+    metrics.record_end_user("user-1", 12, 30);
+    metrics.record_end_user("user-1", 5, 7);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot["end_users"]["user-1"]["requests"], 2);
+    assert_eq!(snapshot["end_users"]["user-1"]["input_tokens"], 17);
+    assert_eq!(snapshot["end_users"]["user-1"]["output_tokens"], 37);
+}
+
+#[test]
+fn test_tag_usage() {
+    let metrics = Metrics::new();
+    let tags = BTreeMap::from([("feature".to_string(), "search".to_string()), ("job".to_string(), "nightly".to_string())]);
+
+    metrics.record_tags(&tags, 10, 20);
+    metrics.record_tags(&BTreeMap::from([("feature".to_string(), "search".to_string())]), 1, 2);
+    metrics.record_tags(&BTreeMap::new(), 100, 100);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot["tags"]["feature=search"]["requests"], 2);
+    assert_eq!(snapshot["tags"]["feature=search"]["output_tokens"], 22);
+    assert_eq!(snapshot["tags"]["job=nightly"]["input_tokens"], 10);
+    assert_eq!(snapshot["tags"].as_object().unwrap().len(), 2);
+}
+
+#[test]
+fn test_stream_timer_and_stats() {
+    let start = Instant::now();
+    let mut timer = StreamTimer::new(start);
+
+    timer.observe(&json!({"type": "message_start"}), start + Duration::from_millis(50));
+    timer.observe(
+        &json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hi"}}),
+        start + Duration::from_millis(200),
+    );
+    timer.observe(&json!({"type": "message_delta", "usage": {"output_tokens": 21}}), start + Duration::from_millis(1200));
+
+    let timing = timer.finish(start + Duration::from_millis(1200));
+    assert_eq!(timing.ttft, Some(Duration::from_millis(200)));
+    assert_eq!(timing.output_tokens, 21);
+    assert_eq!(timing.tokens_per_second, Some(20.0));
+
+    let metrics = Metrics::new();
+    metrics.record_stream("claude-custom", "claude-sonnet-4", &timing);
+    let snapshot = metrics.snapshot();
+    let stats = &snapshot["streams"]["claude-custom/claude-sonnet-4"];
+    assert_eq!(stats["streams"], 1);
+    assert_eq!(stats["ttft_ms"]["p50"], 200);
+    assert_eq!(stats["tokens_per_second"], 20.0);
+}
+
+#[test]
+fn test_stream_timer_counts_chunks_without_usage() {
+    let start = Instant::now();
+    let mut timer = StreamTimer::new(start);
+    for _ in 0..3 {
+        timer.observe(&json!({"choices": [{"index": 0, "delta": {"content": "x"}}]}), start);
+    }
+    timer.observe(&json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}), start);
+
+    let timing = timer.finish(start);
+    assert_eq!(timing.output_tokens, 3);
+    assert_eq!(timing.tokens_per_second, None);
+}
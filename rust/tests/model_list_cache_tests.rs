@@ -0,0 +1,85 @@
+/*!
+ * Model List Cache Tests
+ *
+ * Unit tests for caching model lists and model details in the shared store.
+ */
+
+use aiclient2api_rust::cluster::MemoryStore;
+use aiclient2api_rust::common::{ModelInfo, ModelListResponse};
+use aiclient2api_rust::config::ModelListCacheConfig;
+use aiclient2api_rust::model_list_cache::ModelListCache;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+fn model(id: &str) -> ModelInfo {
+    ModelInfo {
+        id: Some(id.to_string()),
+        name: None,
+        object: Some("model".to_string()),
+        created: None,
+        owned_by: Some("openai".to_string()),
+        extra: Default::default(),
+    }
+}
+
+fn cache() -> ModelListCache {
+    ModelListCache::new(Arc::new(MemoryStore::new()), &ModelListCacheConfig::default())
+}
+
+#[tokio::test]
+async fn test_lists_are_fetched_once_per_provider_until_refreshed() {
+    let cache = cache();
+    let fetches = AtomicUsize::new(0);
+    let fetch = || async {
+        fetches.fetch_add(1, Ordering::SeqCst);
+        Ok(ModelListResponse { object: Some("list".to_string()), data: Some(vec![model("gpt-4o")]), models: None })
+    };
+
+    for _ in 0..3 {
+        let models = cache.models("openai-custom", fetch()).await.unwrap();
+        assert_eq!(models.entries().count(), 1);
+    }
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+    cache.models("openai-qwen", fetch()).await.unwrap();
+    assert_eq!(fetches.load(Ordering::SeqCst), 2, "each provider has its own entry");
+
+    cache.refresh().await.unwrap();
+    cache.models("openai-custom", fetch()).await.unwrap();
+    assert_eq!(fetches.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_failed_fetches_are_not_cached() {
+    let cache = cache();
+
+    let failed = cache.models("openai-custom", async { anyhow::bail!("List models failed: 503") }).await;
+    assert!(failed.is_err());
+
+    let models = cache
+        .models("openai-custom", async { Ok(ModelListResponse { object: None, data: Some(vec![]), models: None }) })
+        .await
+        .unwrap();
+    assert_eq!(models.entries().count(), 0);
+}
+
+#[tokio::test]
+async fn test_model_details_are_cached_including_unknown_models() {
+    let cache = cache();
+    let fetches = AtomicUsize::new(0);
+    let fetch = |found: bool| {
+        let fetches = &fetches;
+        async move {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(found.then(|| model("gpt-4o")))
+        }
+    };
+
+    let info = cache.model("openai-custom", "gpt-4o", fetch(true)).await.unwrap();
+    assert_eq!(info.unwrap().model_id(), Some("gpt-4o"));
+    assert!(cache.model("openai-custom", "gpt-4o", fetch(true)).await.unwrap().is_some());
+
+    assert!(cache.model("openai-custom", "gpt-9", fetch(false)).await.unwrap().is_none());
+    assert!(cache.model("openai-custom", "gpt-9", fetch(false)).await.unwrap().is_none());
+    assert_eq!(fetches.load(Ordering::SeqCst), 2);
+}
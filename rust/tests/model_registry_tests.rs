@@ -0,0 +1,132 @@
+/*!
+ * Model Registry Tests
+ *
+ * Unit tests for model limits lookup and request checks.
+ */
+
+use aiclient2api_rust::config::{ModelRegistryConfig, ModelSpec};
+use aiclient2api_rust::model_registry::*;
+use serde_json::json;
+
+fn registry(models: Vec<ModelSpec>) -> ModelRegistry {
+    ModelRegistry::new(&ModelRegistryConfig {
+        models,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_lookup_prefers_longest_prefix_and_config_overrides() {
+    let registry = registry(vec![ModelSpec {
+        model: "gpt-4o".to_string(),
+        max_messages: Some(50),
+        input_price: Some(2.5),
+        ..Default::default()
+    }]);
+
+    let gpt4 = registry.lookup("gpt-4-0613").unwrap();
+    assert_eq!(gpt4.max_output_tokens, Some(8_192));
+    assert_eq!(gpt4.vision, Some(false));
+
+    // Configured fields win; the rest come from the built-in entry
+    let gpt4o = registry.lookup("GPT-4o-2024-08-06").unwrap();
+    assert_eq!(gpt4o.max_messages, Some(50));
+    assert_eq!(gpt4o.max_output_tokens, Some(16_384));
+    assert_eq!(gpt4o.input_price, Some(2.5));
+    assert_eq!(registry.lookup("models/gemini-2.5-pro").unwrap().max_output_tokens, Some(65_536));
+    assert!(registry.lookup("my-local-model").is_none());
+}
+
+#[test]
+fn test_request_features_in_every_protocol() {
+    let png = "A".repeat(4000);
+    let openai = json!({
+        "max_completion_tokens": 100,
+        "messages": [{"role": "user", "content": [
+            {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", png)}},
+            {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+        ]}],
+        "tools": [{"type": "function", "function": {"name": "a"}}]
+    });
+    let features = RequestFeatures::of(&openai);
+    assert_eq!(features.max_tokens, Some(("max_completion_tokens", 100)));
+    assert_eq!(features.images, vec![3000, 0]);
+    assert_eq!(features.tools, 1);
+
+    let claude = json!({"max_tokens": 10, "messages": [
+        {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "t", "content": [
+            {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}}
+        ]}]}
+    ]});
+    assert_eq!(RequestFeatures::of(&claude).images, vec![3]);
+
+    let gemini = json!({
+        "generationConfig": {"maxOutputTokens": 20},
+        "contents": [{"role": "user", "parts": [{"inlineData": {"mimeType": "image/jpeg", "data": "AAAA"}}]}],
+        "tools": [{"functionDeclarations": [{"name": "a"}, {"name": "b"}]}, {"codeExecution": {}}]
+    });
+    let features = RequestFeatures::of(&gemini);
+    assert_eq!(features.max_tokens, Some(("maxOutputTokens", 20)));
+    assert_eq!((features.messages, features.images.len(), features.tools), (1, 1, 3));
+}
+
+#[test]
+fn test_enforce_rejects_precisely_or_clamps_when_lenient() {
+    let registry = registry(Vec::new());
+
+    let mut body = json!({"model": "gpt-4o", "max_tokens": 100_000, "messages": []});
+    let err = registry.enforce("gpt-4o", &mut body, false).unwrap_err().to_string();
+    assert_eq!(err, "max_tokens of 100000 exceeds the 16384 output token limit of gpt-4o");
+
+    let notes = registry.enforce("gpt-4o", &mut body, true).unwrap();
+    assert_eq!(body["max_tokens"], 16_384);
+    assert_eq!(notes, vec!["max_tokens clamped from 100000 to 16384"]);
+
+    // 6 MB image, over Claude's 5 MB cap; lenient mode only clamps max_tokens
+    let data = "A".repeat(8 * 1024 * 1024);
+    let mut body = json!({"max_tokens": 1024, "messages": [{"role": "user", "content": [
+        {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": data}}
+    ]}]});
+    let err = registry.enforce("claude-3-5-sonnet-20241022", &mut body, true).unwrap_err().to_string();
+    assert!(err.starts_with("Image 1 is 6.0 MB"), "{}", err);
+
+    let tools: Vec<_> = (0..129).map(|i| json!({"type": "function", "function": {"name": format!("t{}", i)}})).collect();
+    let mut body = json!({"messages": [], "tools": tools});
+    assert!(registry.enforce("gpt-4.1", &mut body, false).unwrap_err().to_string().contains("129 tools"));
+    assert!(registry.enforce("unknown-model", &mut body, false).unwrap().is_empty());
+}
+
+#[test]
+fn test_route_picks_first_capable_model_or_explains() {
+    let registry = registry(vec![ModelSpec {
+        model: "my-text-model".to_string(),
+        vision: Some(false),
+        tools: Some(false),
+        ..Default::default()
+    }]);
+    let image = json!({"messages": [{"role": "user", "content": [
+        {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+    ]}]});
+    let features = RequestFeatures::of(&image);
+    assert_eq!(features.required_capabilities(), vec![Capability::Vision]);
+
+    let fallbacks = vec!["gpt-3.5-turbo".to_string(), "gpt-4o-mini".to_string()];
+    assert_eq!(registry.route("gpt-4", &fallbacks, &features).unwrap(), "gpt-4o-mini");
+    assert_eq!(registry.route("gpt-4o", &fallbacks, &features).unwrap(), "gpt-4o");
+    // Unknown models are assumed capable
+    assert_eq!(registry.route("local-llava", &[], &features).unwrap(), "local-llava");
+
+    let both = json!({"messages": image["messages"], "tools": [{"type": "function", "function": {"name": "a"}}]});
+    let err = registry
+        .route("my-text-model", &["gpt-4".to_string()], &RequestFeatures::of(&both))
+        .unwrap_err()
+        .to_string();
+    assert_eq!(
+        err,
+        "No model can serve a request needing vision and tools: my-text-model lacks vision and tools; gpt-4 lacks vision"
+    );
+
+    // Plain text requests stay where they are
+    let text = RequestFeatures::of(&json!({"messages": [{"role": "user", "content": "hi"}]}));
+    assert_eq!(registry.route("my-text-model", &[], &text).unwrap(), "my-text-model");
+}
@@ -0,0 +1,90 @@
+/*!
+ * OIDC Admin Login Tests
+ *
+ * Unit tests for PKCE, role mapping, session cookies and the pending login cap.
+ */
+
+use aiclient2api_rust::config::OidcConfig;
+use aiclient2api_rust::oidc::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+fn config() -> OidcConfig {
+    serde_json::from_value(json!({
+        "issuer": "https://idp.example.com",
+        "client_id": "aiclient2api",
+        "redirect_url": "https://proxy.example.com/admin/callback"
+    }))
+    .unwrap()
+}
+
+fn claims(roles: Value) -> HashMap<String, Value> {
+    HashMap::from([("roles".to_string(), roles)])
+}
+
+#[test]
+fn test_pkce_challenge_matches_rfc7636_example() {
+    assert_eq!(
+        pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+        "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+    );
+}
+
+#[test]
+fn test_role_mapping() {
+    let config = config();
+    assert_eq!(
+        role_from_claims(&config, &claims(json!(["viewer", "operator"]))),
+        Some(AdminRole::Operator)
+    );
+    assert_eq!(role_from_claims(&config, &claims(json!("viewer"))), Some(AdminRole::Viewer));
+    assert_eq!(role_from_claims(&config, &claims(json!(["billing"]))), None);
+    assert_eq!(role_from_claims(&config, &HashMap::new()), None);
+
+    assert!(AdminRole::Operator > AdminRole::Viewer);
+}
+
+#[test]
+fn test_session_cookie_parsing() {
+    let header = format!("theme=dark; {}=abc123; other=1", SESSION_COOKIE);
+    assert_eq!(session_id_from_cookie(&header), Some("abc123"));
+    assert_eq!(session_id_from_cookie("theme=dark"), None);
+}
+
+fn login_state(url: &str) -> String {
+    url::Url::parse(url)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == "state")
+        .map(|(_, value)| value.into_owned())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_pending_logins_are_capped() {
+    let server = httpmock::MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method("GET").path("/.well-known/openid-configuration");
+            then.status(200).json_body(json!({
+                "authorization_endpoint": server.url("/authorize"),
+                "token_endpoint": server.url("/token"),
+                "jwks_uri": server.url("/jwks")
+            }));
+        })
+        .await;
+    let client = OidcClient::new(OidcConfig { issuer: server.base_url(), ..config() });
+
+    let first = login_state(&client.begin_login().await.unwrap());
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let mut last = String::new();
+    for _ in 0..MAX_PENDING_LOGINS {
+        last = login_state(&client.begin_login().await.unwrap());
+    }
+
+    // The oldest login was evicted; the newest still reaches the token exchange
+    let evicted = client.complete_login("code", &first).await.unwrap_err();
+    assert!(evicted.to_string().contains("Unknown or expired login state"));
+    let kept = client.complete_login("code", &last).await.unwrap_err();
+    assert!(!kept.to_string().contains("Unknown or expired login state"));
+}
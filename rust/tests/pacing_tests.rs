@@ -0,0 +1,72 @@
+/*!
+ * Rate-Limit Pacing Tests
+ *
+ * Tests for pacing upstream calls against published rate limits.
+ */
+
+use aiclient2api_rust::config::RateLimitPacingConfig;
+use aiclient2api_rust::pacing::{parse_reset, Pacer};
+use http::HeaderMap;
+use std::time::Duration;
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, value.parse().unwrap());
+    }
+    headers
+}
+
+fn pacer(max_wait_ms: u64) -> Pacer {
+    Pacer::new(&RateLimitPacingConfig {
+        enabled: true,
+        reserve_ratio: 0.1,
+        max_wait_ms,
+    })
+}
+
+#[test]
+fn test_parse_reset() {
+    assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+    assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+    assert_eq!(parse_reset("1h2m3.5s"), Some(Duration::from_secs_f64(3723.5)));
+    assert_eq!(parse_reset("30"), Some(Duration::from_secs(30)));
+    assert_eq!(parse_reset("soon"), None);
+    assert_eq!(parse_reset(""), None);
+}
+
+#[tokio::test]
+async fn test_calls_stop_at_the_reserve() {
+    let pacer = pacer(0);
+    // 10% of 20 is held back, so two more calls fit
+    pacer.observe(&headers(&[
+        ("x-ratelimit-limit-requests", "20"),
+        ("x-ratelimit-remaining-requests", "4"),
+        ("x-ratelimit-reset-requests", "1m0s"),
+    ]));
+    assert_eq!(pacer.acquire().await, Ok(Duration::ZERO));
+    assert_eq!(pacer.acquire().await, Ok(Duration::ZERO));
+    let reset = pacer.acquire().await.unwrap_err();
+    assert!(reset > Duration::from_secs(55) && reset <= Duration::from_secs(60));
+
+    let snapshot = pacer.snapshot();
+    assert_eq!(snapshot["budgets"]["requests"]["remaining"], 2.0);
+    assert_eq!(snapshot["calls"], 2);
+    assert_eq!(snapshot["refused"], 1);
+}
+
+#[tokio::test]
+async fn test_exhausted_budget_waits_for_reset() {
+    let pacer = pacer(5000);
+    pacer.observe(&headers(&[
+        ("x-ratelimit-remaining-tokens", "0"),
+        ("x-ratelimit-reset-tokens", "50ms"),
+    ]));
+    let waited = pacer.acquire().await.unwrap();
+    assert!(waited >= Duration::from_millis(40));
+
+    let snapshot = pacer.snapshot();
+    assert_eq!(snapshot["paced"], 1);
+    // The budget expired with its reset and says nothing until the next response
+    assert_eq!(snapshot["budgets"], serde_json::json!({}));
+}
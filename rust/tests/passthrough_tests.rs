@@ -0,0 +1,54 @@
+/*!
+ * API Passthrough Tests
+ *
+ * Unit tests for upstream paths and the headers carried across a relayed call.
+ */
+
+use aiclient2api_rust::passthrough::*;
+use http::{HeaderMap, HeaderValue, Method, Uri};
+
+#[test]
+fn test_upstream_path_drops_version_prefix() {
+    let uri: Uri = "/v1/files/file-abc/content".parse().unwrap();
+    assert_eq!(upstream_path(&uri), "/files/file-abc/content");
+
+    let uri: Uri = "/v1/files?purpose=batch&limit=10".parse().unwrap();
+    assert_eq!(upstream_path(&uri), "/files?purpose=batch&limit=10");
+
+    // Only a whole `/v1` segment is stripped
+    let uri: Uri = "/v1beta/files".parse().unwrap();
+    assert_eq!(upstream_path(&uri), "/v1beta/files");
+}
+
+#[test]
+fn test_only_content_headers_cross_the_proxy() {
+    let mut inbound = HeaderMap::new();
+    inbound.insert("authorization", HeaderValue::from_static("Bearer client-key"));
+    inbound.insert("cookie", HeaderValue::from_static("session=1"));
+    inbound.insert("content-type", HeaderValue::from_static("multipart/form-data; boundary=xyz"));
+    inbound.insert("content-length", HeaderValue::from_static("1024"));
+
+    let forwarded = request_headers(&inbound);
+    assert_eq!(forwarded.len(), 2);
+    assert_eq!(forwarded["content-type"], "multipart/form-data; boundary=xyz");
+    assert!(forwarded.get("authorization").is_none());
+
+    let mut upstream = HeaderMap::new();
+    upstream.insert("content-disposition", HeaderValue::from_static("attachment; filename=\"batch.jsonl\""));
+    upstream.insert("set-cookie", HeaderValue::from_static("__cf_bm=1"));
+    upstream.insert("x-request-id", HeaderValue::from_static("req_1"));
+    let returned = response_headers(&upstream);
+    assert_eq!(returned.len(), 1);
+    assert!(returned.get("set-cookie").is_none());
+    // The upstream's id is returned as `x-upstream-request-id` instead
+    assert!(returned.get("x-request-id").is_none());
+}
+
+#[test]
+fn test_fine_tuning_changes_are_audited() {
+    assert_eq!(audit_action(&Method::POST, "/fine_tuning/jobs"), Some("fine_tuning.create"));
+    assert_eq!(audit_action(&Method::POST, "/fine_tuning/jobs/ftjob-1/cancel"), Some("fine_tuning.cancel"));
+    assert_eq!(audit_action(&Method::GET, "/fine_tuning/jobs/ftjob-1/events?limit=5"), None);
+    assert_eq!(audit_action(&Method::GET, "/fine_tuning/jobs?after=ftjob-1"), None);
+    assert_eq!(audit_action(&Method::POST, "/files"), None);
+}
@@ -0,0 +1,71 @@
+/*!
+ * Post-Processing Tests
+ *
+ * Unit tests for regex replacements, whitespace trimming and code fence
+ * normalization on buffered and streamed text.
+ */
+
+use aiclient2api_rust::config::{PostProcessConfig, ReplacementRule};
+use aiclient2api_rust::postprocess::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn processor(replacements: &[(&str, &str)], trim: bool, fences: bool) -> PostProcessor {
+    PostProcessor::new(&PostProcessConfig {
+        replacements: replacements
+            .iter()
+            .map(|(pattern, replacement)| ReplacementRule {
+                pattern: pattern.to_string(),
+                replacement: replacement.to_string(),
+            })
+            .collect(),
+        trim_trailing_whitespace: trim,
+        normalize_code_fences: fences,
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_replacements_and_trimming() {
+    let p = processor(&[(r"(?i)as an ai model,\s*", ""), (r"colour", "color")], true, false);
+    assert_eq!(
+        p.process_text("As an AI model, I like this colour.   \nSecond line\t\n\n\n"),
+        "I like this color.\nSecond line"
+    );
+}
+
+#[test]
+fn test_fence_normalization_closes_open_fence() {
+    let p = processor(&[], false, true);
+    assert_eq!(p.process_text("Code:\n~~~ Python \nprint(1)\n"), "Code:\n```Python\nprint(1)\n```");
+    assert_eq!(p.process_text("use ```x``` inline"), "use ```x``` inline");
+}
+
+#[test]
+fn test_streamed_text_matches_buffered() {
+    let p = Arc::new(processor(&[("foo", "bar")], true, true));
+    let mut stream = StreamProcessor::new(p.clone());
+
+    let mut streamed = String::new();
+    for piece in ["a fo", "o  \n```rust\nlet x", " = 1;  \n\n"] {
+        let chunks = stream.process(json!({"choices": [{"index": 0, "delta": {"content": piece}}]}));
+        streamed.push_str(chunks[0]["choices"][0]["delta"]["content"].as_str().unwrap());
+    }
+    let chunks = stream.process(json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}));
+    streamed.push_str(chunks[0]["choices"][0]["delta"]["content"].as_str().unwrap());
+
+    assert_eq!(streamed, "a bar\n```rust\nlet x = 1;\n```");
+    assert_eq!(streamed, p.process_text("a foo  \n```rust\nlet x = 1;  \n\n"));
+}
+
+#[test]
+fn test_invalid_pattern_is_rejected() {
+    let config = PostProcessConfig {
+        replacements: vec![ReplacementRule {
+            pattern: "(".to_string(),
+            replacement: String::new(),
+        }],
+        ..Default::default()
+    };
+    assert!(PostProcessor::new(&config).is_err());
+}
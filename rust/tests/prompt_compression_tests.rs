@@ -0,0 +1,103 @@
+/*!
+ * Prompt Compression Tests
+ *
+ * Unit tests for shrinking prompts before dispatch.
+ */
+
+use aiclient2api_rust::config::PromptCompressionConfig;
+use aiclient2api_rust::prompt_compression::{collapse_whitespace, CompressionStats, PromptCompressor, REPEATED_MARKER};
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+
+fn compressor(extra: Value) -> PromptCompressor {
+    let mut config = json!({"min_block_chars": 40});
+    config.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+    let config: PromptCompressionConfig = serde_json::from_value(config).unwrap();
+    PromptCompressor::new(&config).unwrap()
+}
+
+const CONTEXT: &str = "Project notes: the billing service runs on port 8080 and talks to Postgres.";
+
+#[test]
+fn test_whitespace_and_boilerplate() {
+    assert_eq!(collapse_whitespace("\n\nline one   \n\n\n\n  indented\t\nlast\n\n"), "line one\n\n  indented\nlast");
+
+    let compressor = compressor(json!({"boilerplate_patterns": ["(?m)^Sent from my .*$"]}));
+    let mut body = json!({
+        "model": "claude-sonnet-4",
+        "system": [{"type": "text", "text": "Be brief.   \n\n\n"}],
+        "messages": [{"role": "user", "content": [
+            {"type": "text", "text": "Fix the bug.\n\n\nSent from my phone"},
+            {"type": "image", "source": {"type": "base64", "data": "aGk="}}
+        ]}]
+    });
+    compressor.compress_text(&mut body);
+    assert_eq!(body["system"][0]["text"], "Be brief.");
+    assert_eq!(body["messages"][0]["content"][0]["text"], "Fix the bug.");
+    assert_eq!(body["messages"][0]["content"][1]["source"]["data"], "aGk=");
+}
+
+#[tokio::test]
+async fn test_repeated_context_is_replaced() {
+    let compressor = compressor(json!({}));
+    let mut body = json!({
+        "model": "gpt-4o",
+        "messages": [
+            {"role": "system", "content": format!("{}\n\nAnswer in English.", CONTEXT)},
+            {"role": "user", "content": format!("{}\n\n{}\n\nWhich port?", CONTEXT, CONTEXT)},
+            {"role": "user", "content": "Short line\n\nShort line"}
+        ]
+    });
+    let call = |_model: String, _body: Value| async { Err::<Value, _>(anyhow!("not used")) };
+    let stats = compressor.compress("gpt-4o", &mut body, call).await;
+
+    assert_eq!(body["messages"][0]["content"], format!("{}\n\nAnswer in English.", CONTEXT));
+    assert_eq!(body["messages"][1]["content"], format!("{}\n\nWhich port?", REPEATED_MARKER));
+    assert_eq!(body["messages"][2]["content"], "Short line\n\nShort line");
+    assert!(stats.compressed_tokens < stats.original_tokens);
+}
+
+#[tokio::test]
+async fn test_llm_pass_condenses_long_older_messages() {
+    let compressor = compressor(json!({"deduplicate": false, "llm": {"model": "gpt-4o-mini", "min_tokens": 20, "keep_recent": 1}}));
+    let long = "The quarterly report, which was prepared by the finance team over several weeks, shows that revenue grew by twelve percent compared with the previous quarter.";
+    let mut body = json!({
+        "model": "gpt-4o",
+        "messages": [
+            {"role": "user", "content": long},
+            {"role": "assistant", "content": "Noted."},
+            {"role": "user", "content": long}
+        ]
+    });
+    let asked = Mutex::new(Vec::new());
+    let call = |model: String, body: Value| {
+        asked.lock().unwrap().push((model, body));
+        async { Ok(json!({"choices": [{"message": {"role": "assistant", "content": "Q report: revenue +12% QoQ."}}]})) }
+    };
+    let stats = compressor.compress("gpt-4o", &mut body, call).await;
+
+    let asked = asked.into_inner().unwrap();
+    assert_eq!(asked.len(), 1);
+    assert_eq!(asked[0].0, "gpt-4o-mini");
+    assert_eq!(asked[0].1["messages"][1]["content"], long);
+    assert_eq!(body["messages"][0]["content"], "Q report: revenue +12% QoQ.");
+    assert_eq!(body["messages"][2]["content"], long);
+    assert!(stats.compressed_tokens < stats.original_tokens);
+}
+
+#[test]
+fn test_stats_are_recorded_in_usage() {
+    let stats = CompressionStats { original_tokens: 120, compressed_tokens: 80 };
+    let mut response = json!({"usage": {"prompt_tokens": 80, "completion_tokens": 5}});
+    stats.apply(&mut response);
+    assert_eq!(response["usage"]["prompt_compression"], json!({"original_tokens": 120, "compressed_tokens": 80}));
+
+    let mut message_start = json!({"type": "message_start", "message": {"usage": {"input_tokens": 80}}});
+    stats.apply(&mut message_start);
+    assert_eq!(message_start["message"]["usage"]["prompt_compression"]["original_tokens"], 120);
+
+    let mut delta = json!({"choices": [{"delta": {"content": "hi"}}], "usage": null});
+    stats.apply(&mut delta);
+    assert!(delta["usage"].is_null());
+}
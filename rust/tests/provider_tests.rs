@@ -6,8 +6,6 @@
 
 #[cfg(test)]
 mod tests {
-    use serde_json::json;
-
     #[test]
     fn test_model_provider_parsing() {
         use aiclient2api_rust::common::ModelProvider;
@@ -83,5 +81,22 @@ mod tests {
         // Test no key
         assert!(!is_authorized(None, None, None, None, required_key));
     }
-}
 
+    #[test]
+    fn test_model_list_formats() {
+        use aiclient2api_rust::common::ModelListResponse;
+
+        let list: ModelListResponse = serde_json::from_value(serde_json::json!({
+            "object": "list",
+            "models": [{ "id": "models/gemini-2.5-pro", "owned_by": "google" }]
+        }))
+        .unwrap();
+
+        let openai = list.to_openai();
+        assert_eq!(openai["data"][0]["id"], "gemini-2.5-pro");
+        assert_eq!(openai["data"][0]["owned_by"], "google");
+
+        let gemini = list.to_gemini();
+        assert_eq!(gemini["models"][0]["name"], "models/gemini-2.5-pro");
+    }
+}
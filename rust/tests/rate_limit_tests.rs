@@ -0,0 +1,103 @@
+/*!
+ * Rate Limiting Tests
+ *
+ * Unit tests for the in-memory sliding-window limiter and backend selection.
+ */
+
+use aiclient2api_rust::config::Config;
+use aiclient2api_rust::rate_limit::*;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_memory_limiter_blocks_over_limit() {
+    let limiter = MemoryRateLimiter::new();
+    let window = Duration::from_secs(60);
+
+    let first = limiter.check("client", 2, window).await.unwrap();
+    assert!(first.allowed);
+    assert_eq!(first.remaining, 1);
+
+    assert!(limiter.check("client", 2, window).await.unwrap().allowed);
+
+    let blocked = limiter.check("client", 2, window).await.unwrap();
+    assert!(!blocked.allowed);
+    assert!(blocked.retry_after > Duration::ZERO && blocked.retry_after <= window);
+
+    // Limits are tracked per client
+    assert!(limiter.check("other", 2, window).await.unwrap().allowed);
+}
+
+#[tokio::test]
+async fn test_memory_limiter_window_slides() {
+    let limiter = MemoryRateLimiter::new();
+    let window = Duration::from_millis(50);
+
+    assert!(limiter.check("client", 1, window).await.unwrap().allowed);
+    assert!(!limiter.check("client", 1, window).await.unwrap().allowed);
+
+    tokio::time::sleep(Duration::from_millis(70)).await;
+    assert!(limiter.check("client", 1, window).await.unwrap().allowed);
+}
+
+#[test]
+fn test_build_limiter_selection() {
+    let mut config = Config::default();
+    assert!(build_limiter(&config, None).unwrap().is_none());
+
+    config.rate_limit_requests = 10;
+    assert!(build_limiter(&config, None).unwrap().is_some());
+
+    // The Redis backend needs a connection
+    config.rate_limit_backend = "redis".to_string();
+    assert!(build_limiter(&config, None).is_err());
+}
+
+#[test]
+fn test_reset_durations_use_openai_format() {
+    assert_eq!(format_reset(Duration::from_millis(1500)), "2s");
+    assert_eq!(format_reset(Duration::from_secs(360)), "6m0s");
+    assert_eq!(format_reset(Duration::from_secs(3723)), "1h2m3s");
+}
+
+#[test]
+fn test_upstream_rate_limit_headers_are_normalized() {
+    let mut upstream = http::HeaderMap::new();
+    upstream.insert("x-ratelimit-limit-tokens", "150000".parse().unwrap());
+    upstream.insert("anthropic-ratelimit-requests-limit", "50".parse().unwrap());
+    upstream.insert("anthropic-ratelimit-input-tokens-remaining", "39000".parse().unwrap());
+    let reset = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc3339();
+    upstream.insert("anthropic-ratelimit-tokens-reset", reset.parse().unwrap());
+    upstream.insert("content-type", "application/json".parse().unwrap());
+
+    let headers = upstream_headers(&upstream);
+    assert_eq!(headers.len(), 4);
+    assert_eq!(headers["x-ratelimit-limit-tokens"], "150000");
+    assert_eq!(headers["x-ratelimit-limit-requests"], "50");
+    assert_eq!(headers["x-ratelimit-remaining-input-tokens"], "39000");
+    let reset = headers["x-ratelimit-reset-tokens"].to_str().unwrap();
+    assert!(reset == "30s" || reset == "29s", "{}", reset);
+}
+
+#[tokio::test]
+async fn test_proxy_limits_override_upstream_headers() {
+    let limiter = MemoryRateLimiter::new();
+    let window = Duration::from_secs(60);
+
+    let (_, headers) = scope(async {
+        let mut upstream = http::HeaderMap::new();
+        upstream.insert("x-ratelimit-remaining-requests", "4999".parse().unwrap());
+        upstream.insert("x-ratelimit-remaining-tokens", "149000".parse().unwrap());
+        record_upstream(&upstream);
+        record_decision(&limiter.check("client", 10, window).await.unwrap(), window);
+    })
+    .await;
+    assert_eq!(headers["x-ratelimit-limit-requests"], "10");
+    assert_eq!(headers["x-ratelimit-remaining-requests"], "9");
+    assert_eq!(headers["x-ratelimit-reset-requests"], "1m0s");
+    assert_eq!(headers["x-ratelimit-remaining-tokens"], "149000");
+
+    // Nothing is gathered outside a scope
+    record_decision(&limiter.check("client", 10, window).await.unwrap(), window);
+    let (_, headers) = scope(async {}).await;
+    assert!(headers.is_empty());
+}
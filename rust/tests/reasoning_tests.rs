@@ -0,0 +1,74 @@
+/*!
+ * Reasoning Filter Tests
+ *
+ * Unit tests for stripping and relocating `<think>` reasoning blocks.
+ */
+
+use aiclient2api_rust::config::{ReasoningFilterRule, ReasoningMode};
+use aiclient2api_rust::reasoning::*;
+use serde_json::json;
+
+fn rule(model: &str, mode: ReasoningMode) -> ReasoningFilterRule {
+    ReasoningFilterRule {
+        model: model.to_string(),
+        mode,
+        tag: "think".to_string(),
+    }
+}
+
+#[test]
+fn test_split_reasoning() {
+    let out = split_reasoning("<think>plan it</think>\n\nThe answer is 4.", "think");
+    assert_eq!(out.content, "The answer is 4.");
+    assert_eq!(out.reasoning, "plan it");
+
+    let out = split_reasoning("no tags here, 1 < 2", "think");
+    assert_eq!(out.content, "no tags here, 1 < 2");
+    assert!(out.reasoning.is_empty());
+}
+
+#[test]
+fn test_tags_split_across_chunks() {
+    let mut filter = TagFilter::new("think");
+    let mut content = String::new();
+    let mut reasoning = String::new();
+    for piece in ["<th", "ink>step one", " step two</thi", "nk>", "\nDone <", "b>"] {
+        let out = filter.push(piece);
+        content.push_str(&out.content);
+        reasoning.push_str(&out.reasoning);
+    }
+    let out = filter.finish();
+    content.push_str(&out.content);
+
+    assert_eq!(content, "Done <b>");
+    assert_eq!(reasoning, "step one step two");
+}
+
+#[test]
+fn test_stream_chunks_in_field_mode() {
+    let mut filter = StreamFilter::new(rule("deepseek-r1*", ReasoningMode::Field));
+
+    let chunks = filter.process(json!({"choices": [{"index": 0, "delta": {"content": "<think>hmm</think>Hi"}}]}));
+    assert_eq!(chunks[0]["choices"][0]["delta"]["content"], "Hi");
+    assert_eq!(chunks[0]["choices"][0]["delta"]["reasoning_content"], "hmm");
+
+    // Claude shape: withheld text is flushed before the block stops
+    filter.process(json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "ok <"}}));
+    let chunks = filter.process(json!({"type": "content_block_stop", "index": 0}));
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0]["delta"]["text"], "<");
+    assert_eq!(chunks[1]["type"], "content_block_stop");
+}
+
+#[test]
+fn test_rule_matching_and_buffered_strip() {
+    let rules = vec![rule("deepseek-r1*", ReasoningMode::Strip), rule("qwq-32b", ReasoningMode::Field)];
+    assert!(rule_for(&rules, "DeepSeek-R1-Distill").is_some());
+    assert_eq!(rule_for(&rules, "qwq-32b").map(|r| r.mode), Some(ReasoningMode::Field));
+    assert!(rule_for(&rules, "gpt-4o").is_none());
+
+    let mut response = json!({"content": [{"type": "text", "text": "<think>x</think>Answer"}]});
+    filter_response(&rules[0], &mut response);
+    assert_eq!(response["content"][0]["text"], "Answer");
+    assert!(response["content"][0].get("reasoning_content").is_none());
+}
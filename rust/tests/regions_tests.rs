@@ -0,0 +1,105 @@
+/*!
+ * Regional Endpoint Tests
+ *
+ * Unit tests for endpoint preference, failover and health checks.
+ */
+
+use aiclient2api_rust::config::RegionFailoverConfig;
+use aiclient2api_rust::regions::*;
+use httpmock::prelude::*;
+use reqwest::Client;
+
+fn config(cooldown_secs: u64) -> RegionFailoverConfig {
+    RegionFailoverConfig {
+        failure_threshold: 2,
+        cooldown_secs,
+        health_check_interval_secs: 0,
+    }
+}
+
+#[test]
+fn test_base_urls_combine_single_and_regional_endpoints() {
+    let default = "https://api.anthropic.com";
+    assert_eq!(base_urls(None, &[], default), vec![default]);
+    let primary = "https://us.example.com/".to_string();
+    let regional = vec!["https://eu.example.com".to_string(), "https://us.example.com".to_string()];
+    assert_eq!(base_urls(Some(&primary), &regional, default), vec!["https://us.example.com", "https://eu.example.com"]);
+}
+
+#[test]
+fn test_failing_endpoint_leaves_rotation_until_it_recovers() {
+    let regions = Regions::new(vec!["https://us".to_string(), "https://eu".to_string()], &config(60));
+    assert_eq!(regions.candidates(), vec!["https://us", "https://eu"]);
+
+    regions.record("https://us", false);
+    assert!(regions.is_up("https://us"));
+    regions.record("https://us", false);
+    assert!(!regions.is_up("https://us"));
+    assert_eq!(regions.candidates(), vec!["https://eu", "https://us"]);
+    assert_eq!(regions.preferred(), "https://eu");
+    assert_eq!(regions.primary(), "https://us");
+
+    regions.record("https://us", true);
+    assert_eq!(regions.candidates(), vec!["https://us", "https://eu"]);
+
+    // A zero cooldown puts the endpoint straight back in rotation
+    let regions = Regions::new(vec!["https://us".to_string(), "https://eu".to_string()], &config(0));
+    regions.record("https://us", false);
+    regions.record("https://us", false);
+    assert!(regions.is_up("https://us"));
+}
+
+#[tokio::test]
+async fn test_requests_fail_over_to_the_next_region() {
+    let us = MockServer::start_async().await;
+    let eu = MockServer::start_async().await;
+    let us_down = us
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/messages");
+            then.status(503).body("regional outage");
+        })
+        .await;
+    let eu_up = eu
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/messages");
+            then.status(200).body("{}");
+        })
+        .await;
+
+    let regions = Regions::new(vec![us.base_url(), eu.base_url()], &config(60));
+    let client = Client::new();
+    for _ in 0..3 {
+        let response = regions
+            .send(|base_url| client.post(format!("{}/v1/messages", base_url)).send())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    // After two failures the outage region is skipped
+    us_down.assert_hits_async(2).await;
+    eu_up.assert_hits_async(3).await;
+    assert_eq!(regions.candidates(), vec![eu.base_url(), us.base_url()]);
+
+    // The last candidate's error response is returned as is
+    let only = Regions::new(vec![us.base_url()], &config(60));
+    let response = only.send(|base_url| client.post(format!("{}/v1/messages", base_url)).send()).await.unwrap();
+    assert_eq!(response.status(), 503);
+}
+
+#[tokio::test]
+async fn test_health_check_restores_a_recovered_region() {
+    let us = MockServer::start_async().await;
+    us.mock_async(|when, then| {
+        when.method(GET).path("/");
+        then.status(404);
+    })
+    .await;
+
+    let regions = Regions::new(vec![us.base_url(), "http://127.0.0.1:9".to_string()], &config(600));
+    regions.record(&us.base_url(), false);
+    regions.record(&us.base_url(), false);
+    assert!(!regions.is_up(&us.base_url()));
+
+    regions.check_health(&Client::new()).await;
+    assert!(regions.is_up(&us.base_url()));
+}
@@ -0,0 +1,133 @@
+/*!
+ * Request Context Tests
+ *
+ * Unit tests for the upstream header passthrough allowlist, end-user ids and
+ * request tags.
+ */
+
+use aiclient2api_rust::request_context::*;
+use http::{HeaderMap, HeaderValue};
+use serde_json::json;
+
+fn allowlist(entries: &[&str]) -> Vec<String> {
+    entries.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_allowlist_matching() {
+    let list = allowlist(&["Anthropic-Beta", "x-trace-*"]);
+
+    assert!(is_forwardable("anthropic-beta", &list));
+    assert!(is_forwardable("x-trace-id", &list));
+    assert!(!is_forwardable("openai-beta", &list));
+    assert!(!is_forwardable("x-tracer", &list));
+}
+
+#[test]
+fn test_credentials_never_forwarded() {
+    let list = allowlist(&["*"]);
+
+    assert!(is_forwardable("x-custom", &list));
+    assert!(!is_forwardable("authorization", &list));
+    assert!(!is_forwardable("X-Api-Key", &list));
+    assert!(!is_forwardable("cookie", &list));
+    assert!(!is_forwardable("host", &list));
+}
+
+#[test]
+fn test_from_headers_keeps_only_allowed() {
+    let mut headers = HeaderMap::new();
+    headers.insert("anthropic-beta", HeaderValue::from_static("tools-2024-04-04"));
+    headers.insert("x-api-key", HeaderValue::from_static("secret"));
+    headers.insert("user-agent", HeaderValue::from_static("curl"));
+
+    let ctx = RequestContext::from_headers(&headers, &allowlist(&["anthropic-beta", "x-api-key"]));
+    assert_eq!(ctx.forward_headers.len(), 1);
+    assert_eq!(ctx.header("anthropic-beta"), Some("tools-2024-04-04"));
+
+    let ctx = RequestContext::from_headers(&headers, &[]);
+    assert!(ctx.forward_headers.is_empty());
+}
+
+#[test]
+fn test_beta_flags_split_and_merge() {
+    let mut headers = HeaderMap::new();
+    headers.append(ANTHROPIC_BETA, HeaderValue::from_static("token-efficient-tools-2025-02-19, context-1m-2025-08-07"));
+    headers.append(ANTHROPIC_BETA, HeaderValue::from_static("prompt-caching-2024-07-31"));
+
+    let requested = beta_flags(&headers);
+    assert_eq!(requested.len(), 3);
+
+    let forced = allowlist(&["context-1m-2025-08-07"]);
+    assert_eq!(
+        merge_beta_flags(&forced, &requested).as_deref(),
+        Some("context-1m-2025-08-07,token-efficient-tools-2025-02-19,prompt-caching-2024-07-31")
+    );
+    assert_eq!(merge_beta_flags(&[], &[]), None);
+
+    let ctx = RequestContext::from_headers(&headers, &[]).with_anthropic_beta(&headers);
+    assert!(ctx.forward_headers.is_empty());
+    assert_eq!(ctx.anthropic_beta, requested);
+}
+
+#[test]
+fn test_openai_scope_from_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert("OpenAI-Organization", HeaderValue::from_static("org-123"));
+    headers.insert("openai-project", HeaderValue::from_static(" "));
+
+    let ctx = RequestContext::from_headers(&headers, &[]).with_openai_scope(&headers);
+    assert_eq!(ctx.openai_organization.as_deref(), Some("org-123"));
+    assert_eq!(ctx.openai_project, None);
+}
+
+#[test]
+fn test_end_user_id_and_hashing() {
+    let mut headers = HeaderMap::new();
+    headers.insert(END_USER_HEADER, HeaderValue::from_static("header-user"));
+
+    assert_eq!(end_user_id(&headers, &json!({"user": "body-user"})).as_deref(), Some("body-user"));
+    assert_eq!(end_user_id(&headers, &json!({})).as_deref(), Some("header-user"));
+    let claude_body = json!({"metadata": {"user_id": "claude-user", "team": "search"}});
+    assert_eq!(end_user_id(&headers, &claude_body).as_deref(), Some("claude-user"));
+    assert_eq!(end_user_id(&HeaderMap::new(), &json!({"user": ""})), None);
+
+    let hashed = hash_end_user("alice", "salt");
+    assert_eq!(hashed.len(), 64);
+    assert_eq!(hashed, hash_end_user("alice", "salt"));
+    assert_ne!(hashed, hash_end_user("alice", "other-salt"));
+
+    let ctx = RequestContext::default().with_end_user(Some("alice".to_string()), Some("salt"));
+    assert_eq!(ctx.end_user, Some(hashed));
+    let ctx = RequestContext::default().with_end_user(Some("alice".to_string()), None);
+    assert_eq!(ctx.end_user.as_deref(), Some("alice"));
+}
+
+#[test]
+fn test_tags_from_header_and_metadata() {
+    let mut headers = HeaderMap::new();
+    headers.insert(TAGS_HEADER, HeaderValue::from_static("feature=search, job = nightly,bad key=x,novalue"));
+
+    let mut body = json!({"model": "m", "metadata": {"feature": "chat", "team": "ml", "user_id": "u-1", "count": 3}});
+    let tags = take_tags(&headers, &mut body);
+
+    // The header wins over the body; invalid pairs are skipped
+    assert_eq!(format_tags(&tags), "feature=search,job=nightly,team=ml");
+    // Tag entries leave the body; upstream-defined metadata stays
+    assert_eq!(body["metadata"], json!({"user_id": "u-1", "count": 3}));
+
+    let mut body = json!({"metadata": {"team": "ml"}});
+    take_tags(&HeaderMap::new(), &mut body);
+    assert!(body.get("metadata").is_none());
+}
+
+#[test]
+fn test_tag_limits() {
+    let header: Vec<String> = (0..40).map(|i| format!("k{:02}=v", i)).collect();
+    let tags = parse_tags(&header.join(","));
+    assert_eq!(tags.len(), 16);
+
+    let long = "x".repeat(200);
+    assert!(parse_tags(&format!("key={}", long)).is_empty());
+    assert!(parse_tags(&format!("{}=value", long)).is_empty());
+}
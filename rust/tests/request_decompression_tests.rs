@@ -0,0 +1,51 @@
+/*!
+ * Request Decompression Tests
+ *
+ * Unit tests for decoding compressed request bodies.
+ */
+
+use aiclient2api_rust::request_decompression::*;
+use bytes::Bytes;
+use std::io::Write;
+
+const BODY: &[u8] = br#"{"model":"gpt-4o","messages":[{"role":"user","content":"hello"}]}"#;
+
+fn gzip(data: &[u8]) -> Bytes {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap().into()
+}
+
+fn brotli(data: &[u8]) -> Bytes {
+    let mut output = Vec::new();
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+    }
+    output.into()
+}
+
+#[test]
+fn test_decompresses_gzip_and_brotli() {
+    assert_eq!(decompress("gzip", gzip(BODY), 1024).unwrap(), BODY);
+    assert_eq!(decompress("BR", brotli(BODY), 1024).unwrap(), BODY);
+    assert_eq!(decompress("identity", Bytes::from_static(BODY), 1024).unwrap(), BODY);
+}
+
+#[test]
+fn test_refuses_bodies_expanding_past_the_limit() {
+    // A megabyte of zeros compresses to about a kilobyte
+    let bomb = gzip(&vec![0u8; 1024 * 1024]);
+    assert!(bomb.len() < 4096);
+    assert!(matches!(decompress("gzip", bomb.clone(), 64 * 1024), Err(DecompressError::TooLarge { limit: 65536 })));
+    assert_eq!(decompress("gzip", bomb, 1024 * 1024).unwrap().len(), 1024 * 1024);
+}
+
+#[test]
+fn test_rejects_unknown_encodings_and_corrupt_bodies() {
+    let err = decompress("zstd", Bytes::from_static(BODY), 1024).unwrap_err();
+    assert!(matches!(err, DecompressError::Unsupported(_)));
+    assert!(err.to_string().contains("zstd"));
+
+    assert!(matches!(decompress("gzip", Bytes::from_static(BODY), 1024), Err(DecompressError::Corrupt(_))));
+}
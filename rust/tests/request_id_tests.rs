@@ -0,0 +1,89 @@
+/*!
+ * Request ID Tests
+ *
+ * Unit tests for request id handling and upstream id capture.
+ */
+
+use aiclient2api_rust::request_id::*;
+use http::{HeaderMap, HeaderValue};
+
+#[test]
+fn test_client_request_id_is_validated() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(from_headers(&headers), None);
+    assert_eq!(from_headers_or_new(&headers).len(), 36);
+
+    headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static(" trace-42 "));
+    assert_eq!(from_headers(&headers).as_deref(), Some("trace-42"));
+    assert_eq!(from_headers_or_new(&headers), "trace-42");
+
+    headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("has spaces inside"));
+    assert_eq!(from_headers(&headers), None);
+    headers.insert(REQUEST_ID_HEADER, HeaderValue::from_str(&"x".repeat(200)).unwrap());
+    assert_eq!(from_headers(&headers), None);
+}
+
+#[tokio::test]
+async fn test_upstream_id_is_captured_per_request() {
+    let mut anthropic = HeaderMap::new();
+    anthropic.insert("request-id", HeaderValue::from_static("req_011CAnthropic"));
+    let mut openai = HeaderMap::new();
+    openai.insert("x-request-id", HeaderValue::from_static("req_openai"));
+    openai.insert("request-id", HeaderValue::from_static("ignored"));
+    assert_eq!(upstream_id(&anthropic).as_deref(), Some("req_011CAnthropic"));
+    assert_eq!(upstream_id(&openai).as_deref(), Some("req_openai"));
+
+    // A retry's id replaces the first attempt's
+    let ((), upstream) = scope(async {
+        record_upstream(&anthropic);
+        record_upstream(&openai);
+    })
+    .await;
+    assert_eq!(upstream.as_deref(), Some("req_openai"));
+
+    // Nothing is kept outside a scope
+    record_upstream(&openai);
+    let ((), upstream) = scope(async {}).await;
+    assert_eq!(upstream, None);
+
+    let mut response = HeaderMap::new();
+    apply(&mut response, "trace-42", Some("req_openai"));
+    assert_eq!(response[REQUEST_ID_HEADER], "trace-42");
+    assert_eq!(response[UPSTREAM_REQUEST_ID_HEADER], "req_openai");
+}
+
+#[cfg(feature = "openai")]
+#[tokio::test]
+async fn test_openai_receives_the_client_request_id() {
+    use aiclient2api_rust::adapter::create_adapter;
+    use aiclient2api_rust::config::Config;
+    use aiclient2api_rust::convert::ChatRequest;
+    use aiclient2api_rust::request_context::RequestContext;
+    use aiclient2api_rust::ModelProvider;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    let server = MockServer::start_async().await;
+    let upstream = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/chat/completions").header(CLIENT_REQUEST_ID_HEADER, "trace-42");
+            then.status(200).header("x-request-id", "req_upstream").json_body(json!({
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi"}, "finish_reason": "stop"}]
+            }));
+        })
+        .await;
+    let config = Config {
+        model_provider: "openai-custom".to_string(),
+        openai_api_key: Some("sk-test".to_string()),
+        openai_base_url: Some(server.base_url()),
+        ..Config::default()
+    };
+    let adapter = create_adapter(ModelProvider::OpenAICustom, &config).await.unwrap();
+    let ctx = RequestContext::default().with_request_id(Some("trace-42".to_string()));
+    let request = ChatRequest::OpenAI(json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hi"}]}));
+
+    let (response, upstream_id) = scope(adapter.generate_content("gpt-4o", request, &ctx)).await;
+    response.unwrap();
+    upstream.assert_async().await;
+    assert_eq!(upstream_id.as_deref(), Some("req_upstream"));
+}
@@ -0,0 +1,69 @@
+/*!
+ * Request Limits Tests
+ *
+ * Unit tests for measuring requests and rejecting oversized ones.
+ */
+
+use aiclient2api_rust::config::RequestLimits;
+use aiclient2api_rust::request_limits::*;
+use serde_json::json;
+
+#[test]
+fn test_size_counts_base64_in_every_protocol() {
+    let openai = json!({
+        "messages": [{"role": "user", "content": [
+            {"type": "text", "text": "data:not-base64"},
+            {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}},
+            {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}},
+            {"type": "input_audio", "input_audio": {"data": "BBBBBB", "format": "wav"}}
+        ]}]
+    });
+    assert_eq!(RequestSize::of(&openai), RequestSize { messages: 1, images: 2, base64_bytes: 10 });
+
+    let claude = json!({
+        "messages": [
+            {"role": "user", "content": [{"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "CCCCCCCC"}}]},
+            {"role": "assistant", "content": "ok"}
+        ]
+    });
+    assert_eq!(RequestSize::of(&claude), RequestSize { messages: 2, images: 1, base64_bytes: 8 });
+
+    let gemini = json!({
+        "contents": [{"role": "user", "parts": [{"inlineData": {"mimeType": "application/pdf", "data": "DDDD"}}]}]
+    });
+    assert_eq!(RequestSize::of(&gemini), RequestSize { messages: 1, images: 0, base64_bytes: 4 });
+}
+
+#[test]
+fn test_check_rejects_the_first_exceeded_limit() {
+    let body = json!({
+        "messages": [
+            {"role": "user", "content": [{"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}}]},
+            {"role": "user", "content": "again"}
+        ]
+    });
+
+    assert!(check(&RequestLimits::default(), &body).is_ok());
+
+    let limits = RequestLimits { max_messages: Some(1), ..Default::default() };
+    assert_eq!(check(&limits, &body).unwrap_err(), "Request has 2 messages; at most 1 are allowed");
+
+    let limits = RequestLimits { max_images: Some(0), ..Default::default() };
+    assert!(check(&limits, &body).unwrap_err().contains("1 images"));
+
+    let limits = RequestLimits { max_base64_bytes: Some(3), ..Default::default() };
+    assert!(check(&limits, &body).unwrap_err().contains("base64"));
+    let limits = RequestLimits { max_base64_bytes: Some(4), ..Default::default() };
+    assert!(check(&limits, &body).is_ok());
+}
+
+#[test]
+fn test_key_limits_override_global_field_by_field() {
+    let global = RequestLimits { max_messages: Some(100), max_images: Some(10), max_base64_bytes: None };
+    let key = RequestLimits { max_images: Some(2), ..Default::default() };
+
+    assert_eq!(
+        key.or(&global),
+        RequestLimits { max_messages: Some(100), max_images: Some(2), max_base64_bytes: None }
+    );
+}
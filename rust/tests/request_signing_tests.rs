@@ -0,0 +1,67 @@
+/*!
+ * Request Signing Tests
+ *
+ * Unit tests for HMAC signature verification and the replay window.
+ */
+
+use aiclient2api_rust::config::RequestSigningConfig;
+use aiclient2api_rust::request_signing::*;
+
+fn config() -> RequestSigningConfig {
+    serde_json::from_value(serde_json::json!({ "secret": "shared-secret" })).unwrap()
+}
+
+const BODY: &[u8] = br#"{"model":"claude-sonnet-4","messages":[]}"#;
+const NOW: i64 = 1_760_000_000;
+
+#[test]
+fn test_valid_signature() {
+    let config = config();
+    let ts = NOW.to_string();
+    let sig = compute_signature("shared-secret", &ts, "POST", "/v1/messages", BODY);
+
+    assert_eq!(verify(&config, Some(&ts), Some(&sig), "POST", "/v1/messages", BODY, NOW), Ok(()));
+    let prefixed = format!("sha256={}", sig);
+    assert_eq!(verify(&config, Some(&ts), Some(&prefixed), "POST", "/v1/messages", BODY, NOW + 10), Ok(()));
+}
+
+#[test]
+fn test_tampered_request_is_rejected() {
+    let config = config();
+    let ts = NOW.to_string();
+    let sig = compute_signature("shared-secret", &ts, "POST", "/v1/messages", BODY);
+
+    let other_body = br#"{"model":"claude-opus-4","messages":[]}"#;
+    assert_eq!(
+        verify(&config, Some(&ts), Some(&sig), "POST", "/v1/messages", other_body, NOW),
+        Err(SignatureError::Mismatch)
+    );
+    assert_eq!(
+        verify(&config, Some(&ts), Some(&sig), "POST", "/v1/chat/completions", BODY, NOW),
+        Err(SignatureError::Mismatch)
+    );
+    assert_eq!(
+        verify(&config, Some(&ts), Some("zz"), "POST", "/v1/messages", BODY, NOW),
+        Err(SignatureError::Mismatch)
+    );
+}
+
+#[test]
+fn test_timestamp_window() {
+    let config = config();
+    let old = (NOW - 301).to_string();
+    let sig = compute_signature("shared-secret", &old, "GET", "/v1/models", b"");
+
+    assert_eq!(
+        verify(&config, Some(&old), Some(&sig), "GET", "/v1/models", b"", NOW),
+        Err(SignatureError::Expired)
+    );
+    assert_eq!(
+        verify(&config, Some("yesterday"), Some(&sig), "GET", "/v1/models", b"", NOW),
+        Err(SignatureError::InvalidTimestamp)
+    );
+    assert_eq!(
+        verify(&config, None, Some(&sig), "GET", "/v1/models", b"", NOW),
+        Err(SignatureError::Missing)
+    );
+}
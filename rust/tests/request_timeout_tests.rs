@@ -0,0 +1,61 @@
+/*!
+ * Request Timeout Tests
+ *
+ * Unit tests for picking a request's upstream timeout from its header, route and provider.
+ */
+
+use aiclient2api_rust::config::RequestTimeoutConfig;
+use aiclient2api_rust::request_timeout::*;
+use http::HeaderMap;
+use std::time::Duration;
+
+fn config() -> RequestTimeoutConfig {
+    RequestTimeoutConfig {
+        max_ms: 120_000,
+        routes: [("/v1/messages".to_string(), 30_000), ("/batch/*".to_string(), 90_000), ("/batch/v1/*".to_string(), 100_000)]
+            .into_iter()
+            .collect(),
+        providers: [("openai-custom".to_string(), 45_000)].into_iter().collect(),
+    }
+}
+
+fn header(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(TIMEOUT_HEADER, value.parse().unwrap());
+    headers
+}
+
+#[test]
+fn test_header_wins_and_is_capped_at_the_maximum() {
+    let config = config();
+    assert_eq!(
+        resolve(&config, &header("2500"), "/v1/messages", "openai-custom").unwrap(),
+        Some(Duration::from_millis(2500))
+    );
+    assert_eq!(
+        resolve(&config, &header("3600000"), "/v1/messages", "openai-custom").unwrap(),
+        Some(Duration::from_millis(120_000))
+    );
+}
+
+#[test]
+fn test_invalid_header_is_rejected() {
+    for value in ["0", "-5", "soon", "1.5"] {
+        assert!(from_headers(&header(value), 120_000).is_err(), "{} should be rejected", value);
+    }
+    assert_eq!(from_headers(&HeaderMap::new(), 120_000).unwrap(), None);
+}
+
+#[test]
+fn test_route_then_provider_then_client_default() {
+    let config = config();
+    let none = HeaderMap::new();
+    assert_eq!(resolve(&config, &none, "/v1/messages", "openai-custom").unwrap(), Some(Duration::from_secs(30)));
+    assert_eq!(
+        resolve(&config, &none, "/batch/v1/chat/completions", "openai-custom").unwrap(),
+        Some(Duration::from_secs(100)),
+        "the longer pattern is the more specific one"
+    );
+    assert_eq!(resolve(&config, &none, "/v1/chat/completions", "openai-custom").unwrap(), Some(Duration::from_secs(45)));
+    assert_eq!(resolve(&config, &none, "/v1/chat/completions", "claude-custom").unwrap(), None);
+}
@@ -0,0 +1,53 @@
+/*!
+ * Rerank Tests
+ *
+ * Unit tests for parsing rerank requests, scoring prompts and ranking results.
+ */
+
+use aiclient2api_rust::rerank::*;
+use serde_json::json;
+
+#[test]
+fn test_parse_accepts_strings_and_text_objects() {
+    let request = RerankRequest::parse(&json!({
+        "model": "gpt-4o-mini",
+        "query": "capital of France",
+        "documents": ["Paris is the capital.", {"text": "Berlin is in Germany."}],
+        "top_n": 1
+    }))
+    .unwrap();
+    assert_eq!(request.documents, ["Paris is the capital.", "Berlin is in Germany."]);
+    assert_eq!(request.top_n, Some(1));
+    assert!(request.return_documents);
+
+    assert!(RerankRequest::parse(&json!({"query": "q"})).is_err());
+    assert!(RerankRequest::parse(&json!({"query": "q", "documents": [{"id": 1}]})).is_err());
+}
+
+#[test]
+fn test_scoring_prompt_numbers_documents() {
+    let request = RerankRequest::parse(&json!({"query": "capital of France", "documents": ["Paris", "Berlin"]})).unwrap();
+    let prompt = request.scoring_prompt("gpt-4o-mini");
+    assert_eq!(prompt["model"], "gpt-4o-mini");
+    assert!(prompt["messages"][0]["content"].as_str().unwrap().contains("JSON array of 2 numbers"));
+    let user = prompt["messages"][1]["content"].as_str().unwrap();
+    assert!(user.contains("[0] Paris") && user.contains("[1] Berlin"));
+}
+
+#[test]
+fn test_scores_are_ranked_and_truncated() {
+    let scores = parse_scores("Here you go:\n```json\n[0.2, \"0.9\", 1.5]\n```", 3).unwrap();
+    assert_eq!(scores, [0.2, 0.9, 1.0]);
+    assert!(parse_scores("[0.5]", 3).is_err());
+    assert!(parse_scores("no idea", 1).is_err());
+
+    let request = RerankRequest::parse(&json!({
+        "query": "q", "documents": ["a", "b", "c"], "top_n": 2, "return_documents": false
+    }))
+    .unwrap();
+    let response = request.response("rerank-1", &scores);
+    assert_eq!(response["results"], json!([
+        {"index": 2, "relevance_score": 1.0},
+        {"index": 1, "relevance_score": 0.9}
+    ]));
+}
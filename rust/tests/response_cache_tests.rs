@@ -0,0 +1,114 @@
+/*!
+ * Response Cache Tests
+ *
+ * Unit tests for canonical cache keys and the cache store.
+ */
+
+use aiclient2api_rust::cluster::MemoryStore;
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::ResponseCacheConfig;
+use aiclient2api_rust::response_cache::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn config(vary: &[&str]) -> ResponseCacheConfig {
+    ResponseCacheConfig {
+        ttl_secs: 60,
+        vary: vary.iter().map(|s| s.to_string()).collect(),
+        ignore_fields: vec!["n_probs".to_string()],
+        replay_pacing: Default::default(),
+        stale_if_error_secs: 0,
+        stale_if_error_clients: Default::default(),
+    }
+}
+
+#[test]
+fn test_semantically_identical_requests_share_a_key() {
+    let config = config(&["temperature", "seed"]);
+    let plain = json!({"model": "gpt-4o", "temperature": 0.2, "messages": [{"role": "user", "content": "Hi"}]});
+    let noisy = json!({
+        "messages": [{"content": [{"type": "text", "text": "Hi"}], "role": "user"}],
+        "stream": true,
+        "stream_options": {"include_usage": true},
+        "user": "alice",
+        "request_id": "req-123",
+        "tools": null,
+        "n_probs": 5,
+        "temperature": 0.2,
+        "model": "gpt-4o"
+    });
+    assert_eq!(cache_key(ModelProtocol::OpenAI, &plain, &config), cache_key(ModelProtocol::OpenAI, &noisy, &config));
+
+    let other_question = json!({"model": "gpt-4o", "temperature": 0.2, "messages": [{"role": "user", "content": "Bye"}]});
+    assert_ne!(cache_key(ModelProtocol::OpenAI, &plain, &config), cache_key(ModelProtocol::OpenAI, &other_question, &config));
+    assert_ne!(cache_key(ModelProtocol::OpenAI, &plain, &config), cache_key(ModelProtocol::Claude, &plain, &config));
+
+    // A text part with cache_control is not plain text
+    let annotated = json!({"model": "gpt-4o", "temperature": 0.2, "messages": [{"role": "user", "content": [
+        {"type": "text", "text": "Hi", "cache_control": {"type": "ephemeral"}}
+    ]}]});
+    assert_ne!(cache_key(ModelProtocol::OpenAI, &plain, &config), cache_key(ModelProtocol::OpenAI, &annotated, &config));
+}
+
+#[test]
+fn test_sampling_parameters_only_vary_when_configured() {
+    let cold = json!({"model": "gpt-4o", "temperature": 0.0, "seed": 1, "messages": []});
+    let warm = json!({"model": "gpt-4o", "temperature": 0.9, "seed": 2, "messages": []});
+
+    let strict = config(&["temperature", "seed"]);
+    assert_ne!(cache_key(ModelProtocol::OpenAI, &cold, &strict), cache_key(ModelProtocol::OpenAI, &warm, &strict));
+    let seed_only = config(&["seed"]);
+    assert_ne!(cache_key(ModelProtocol::OpenAI, &cold, &seed_only), cache_key(ModelProtocol::OpenAI, &warm, &seed_only));
+    let relaxed = config(&[]);
+    assert_eq!(cache_key(ModelProtocol::OpenAI, &cold, &relaxed), cache_key(ModelProtocol::OpenAI, &warm, &relaxed));
+    assert_eq!(canonical_request(&warm, &relaxed), json!({"model": "gpt-4o", "messages": []}));
+}
+
+#[test]
+fn test_canonical_json_sorts_keys_and_bypass_directives() {
+    assert_eq!(canonical_json(&json!({"b": [{"d": 1, "c": null}], "a": "x"})), r#"{"a":"x","b":[{"c":null,"d":1}]}"#);
+    assert!(bypass(Some("no-cache")) && bypass(Some("max-age=0, No-Store")));
+    assert!(!bypass(Some("max-age=60")) && !bypass(None));
+}
+
+#[tokio::test]
+async fn test_responses_round_trip_through_the_store() {
+    let cache = ResponseCache::new(Arc::new(MemoryStore::new()), &config(&[]));
+    let key = cache.key(ModelProtocol::Claude, &json!({"model": "claude-sonnet-4", "messages": []}));
+    assert!(key.starts_with("response_cache:claude:"));
+    assert!(cache.get(&key).await.is_none());
+
+    let response = json!({"type": "message", "content": [{"type": "text", "text": "Hello"}]});
+    cache.put(&key, &response).await.unwrap();
+    assert_eq!(cache.get(&key).await, Some(response));
+}
+
+#[test]
+fn test_stale_window_per_client() {
+    let mut config = config(&[]);
+    config.stale_if_error_secs = 600;
+    config.stale_if_error_clients.insert("key:batch".to_string(), 86400);
+    config.stale_if_error_clients.insert("tenant:billing".to_string(), 0);
+
+    assert_eq!(stale_window(&config, "static"), 600);
+    assert_eq!(stale_window(&config, "key:batch"), 86400);
+    assert_eq!(stale_window(&config, "tenant:billing"), 0);
+}
+
+#[tokio::test]
+async fn test_stale_entries_only_for_clients_that_allow_them() {
+    let mut config = config(&[]);
+    config.ttl_secs = 0;
+    config.stale_if_error_clients.insert("key:batch".to_string(), 3600);
+    let store = Arc::new(MemoryStore::new());
+    let cache = ResponseCache::new(store, &config);
+    let key = cache.key(ModelProtocol::OpenAI, &json!({"model": "gpt-4o", "messages": []}));
+
+    let response = json!({"choices": [{"message": {"role": "assistant", "content": "Hi"}}]});
+    cache.put(&key, &response).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    assert!(cache.get(&key).await.is_none(), "expired entries are not hits");
+    assert_eq!(cache.get_stale(&key, "key:batch").await, Some(response));
+    assert!(cache.get_stale(&key, "static").await.is_none());
+}
@@ -0,0 +1,57 @@
+/*!
+ * Retry Budget Tests
+ *
+ * Unit tests for the global cap on upstream retries.
+ */
+
+use aiclient2api_rust::config::RetryBudgetConfig;
+use aiclient2api_rust::retry_budget::RetryBudget;
+
+fn budget(ratio: f64, min_retries_per_sec: f64) -> RetryBudget {
+    RetryBudget::new(&RetryBudgetConfig {
+        ratio,
+        min_retries_per_sec,
+        window_secs: 10,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_retries_capped_at_ratio_of_requests() {
+    let budget = budget(0.1, 0.0);
+    for _ in 0..100 {
+        budget.record_request_at(0);
+    }
+    let allowed = (0..100).filter(|_| budget.try_retry_at(1)).count();
+    assert_eq!(allowed, 10);
+    assert_eq!(budget.denied(), 90);
+}
+
+#[test]
+fn test_floor_allows_retries_without_traffic() {
+    let budget = budget(0.1, 0.5);
+    let allowed = (0..10).filter(|_| budget.try_retry_at(0)).count();
+    assert_eq!(allowed, 5);
+}
+
+#[test]
+fn test_budget_recovers_after_window() {
+    let budget = budget(0.5, 0.0);
+    budget.record_request_at(0);
+    budget.record_request_at(0);
+    assert!(budget.try_retry_at(0));
+    assert!(!budget.try_retry_at(5));
+
+    // Old requests and retries age out together
+    budget.record_request_at(12);
+    budget.record_request_at(12);
+    assert!(budget.try_retry_at(12));
+
+    let disabled = RetryBudget::new(&RetryBudgetConfig {
+        enabled: false,
+        ratio: 0.0,
+        min_retries_per_sec: 0.0,
+        ..Default::default()
+    });
+    assert!(disabled.try_retry_at(0));
+}
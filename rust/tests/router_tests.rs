@@ -0,0 +1,59 @@
+/*!
+ * Router Tests
+ *
+ * Tests for the provider-selection policies.
+ */
+
+use aiclient2api_rust::config::RouterConfig;
+use aiclient2api_rust::router::{self, RouteRequest};
+use serde_json::json;
+
+fn router(config: serde_json::Value) -> std::sync::Arc<dyn router::Router> {
+    router::build(&serde_json::from_value::<RouterConfig>(config).unwrap())
+}
+
+#[test]
+fn test_static_router() {
+    let router = router(json!({"type": "static", "routes": [{"model": "claude-*", "provider": "claude-custom"}]}));
+    assert_eq!(router.select(&RouteRequest::new("claude-3-opus", None)).as_deref(), Some("claude-custom"));
+    assert_eq!(router.select(&RouteRequest::new("gpt-4o", None)), None);
+    assert_eq!(router.providers(), vec!["claude-custom"]);
+}
+
+#[test]
+fn test_weighted_router_spreads_by_weight() {
+    let router = router(json!({"type": "weighted", "model": "gpt-*", "weights": {"a": 3, "b": 1, "c": 0}}));
+    let picks: Vec<String> = (0..8).filter_map(|_| router.select(&RouteRequest::new("gpt-4o", None))).collect();
+    assert_eq!(picks, vec!["a", "a", "b", "a", "a", "a", "b", "a"]);
+    assert_eq!(router.select(&RouteRequest::new("claude-3", None)), None);
+    assert_eq!(router.providers(), vec!["a", "b"]);
+}
+
+#[test]
+fn test_capability_router() {
+    let router = router(json!({"type": "capability", "vision": "gemini-cli-oauth", "tools": "claude-custom"}));
+    let image = json!({"messages": [{"role": "user", "content": [
+        {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+    ]}], "tools": [{"type": "function", "function": {"name": "f"}}]});
+    let tools = json!({"messages": [{"role": "user", "content": "hi"}], "tools": [{"type": "function", "function": {"name": "f"}}]});
+    let plain = json!({"messages": [{"role": "user", "content": "hi"}]});
+    assert_eq!(router.select(&RouteRequest::new("gpt-4o", Some(&image))).as_deref(), Some("gemini-cli-oauth"));
+    assert_eq!(router.select(&RouteRequest::new("gpt-4o", Some(&tools))).as_deref(), Some("claude-custom"));
+    assert_eq!(router.select(&RouteRequest::new("gpt-4o", Some(&plain))), None);
+    // Without a body there is nothing to route by
+    assert_eq!(router.select(&RouteRequest::new("gpt-4o", None)), None);
+}
+
+#[test]
+fn test_cost_router_picks_cheapest() {
+    let router = router(json!({"type": "cost", "model": "*", "prices": {"openai-custom": 2.5, "openai-qwen-oauth": 0.8}}));
+    assert_eq!(router.select(&RouteRequest::new("any", None)).as_deref(), Some("openai-qwen-oauth"));
+}
+
+#[test]
+fn test_pattern_matching() {
+    assert!(router::matches("gpt-*", "gpt-4o"));
+    assert!(router::matches("*", "anything"));
+    assert!(router::matches("gpt-4o", "gpt-4o"));
+    assert!(!router::matches("gpt-4o", "gpt-4o-mini"));
+}
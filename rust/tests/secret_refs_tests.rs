@@ -0,0 +1,54 @@
+/*!
+ * Secret Reference Tests
+ *
+ * Unit tests for vault:// and aws-sm:// credential references.
+ */
+
+use aiclient2api_rust::config::Config;
+use aiclient2api_rust::secret_refs::*;
+
+#[test]
+fn test_is_reference() {
+    assert!(is_reference("vault://secret/data/openai#api_key"));
+    assert!(is_reference("aws-sm://prod/claude"));
+    assert!(!is_reference("sk-plain-key"));
+    assert!(!is_reference("enc:v1:AAAA"));
+}
+
+#[test]
+fn test_has_references_checks_credential_fields() {
+    let mut config = Config::default();
+    assert!(!has_references(&config));
+
+    config.claude_api_key = Some("aws-sm://prod/claude#key".to_string());
+    assert!(has_references(&config));
+}
+
+#[tokio::test]
+async fn test_resolve_rejects_unknown_scheme() {
+    let client = reqwest::Client::new();
+    assert!(resolve("gcp-sm://projects/x", &client).await.is_err());
+}
+
+#[tokio::test]
+async fn test_resolve_config_without_references_is_noop() {
+    let mut config = Config {
+        openai_api_key: Some("sk-plain".to_string()),
+        ..Default::default()
+    };
+
+    let resolved = resolve_config(&mut config, &reqwest::Client::new()).await.unwrap();
+    assert_eq!(resolved, 0);
+    assert_eq!(config.openai_api_key.as_deref(), Some("sk-plain"));
+}
+
+#[test]
+fn test_server_secrets_are_startup_only() {
+    let config = Config {
+        required_api_key: "vault://secret/data/proxy#key".to_string(),
+        redis_url: Some("redis://localhost".to_string()),
+        openai_api_key: Some("vault://secret/data/openai#api_key".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(startup_only_references(&config), vec!["required_api_key".to_string()]);
+}
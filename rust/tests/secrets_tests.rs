@@ -0,0 +1,37 @@
+/*!
+ * Secret Encryption Tests
+ *
+ * Unit tests for encrypted credentials at rest.
+ */
+
+use aiclient2api_rust::secrets::*;
+
+#[test]
+fn test_encrypt_decrypt_roundtrip() {
+    let key = derive_key("correct horse battery staple");
+
+    let encrypted = encrypt("sk-ant-secret", &key).unwrap();
+    assert!(is_encrypted(&encrypted));
+    assert!(!encrypted.contains("sk-ant-secret"));
+
+    assert_eq!(decrypt(&encrypted, &key).unwrap(), "sk-ant-secret");
+}
+
+#[test]
+fn test_decrypt_with_wrong_key_fails() {
+    let encrypted = encrypt("sk-openai", &derive_key("key-a")).unwrap();
+    assert!(decrypt(&encrypted, &derive_key("key-b")).is_err());
+}
+
+#[test]
+fn test_derive_key_accepts_raw_base64_key() {
+    // 32 bytes of 0x01, base64-encoded
+    let raw = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+    assert_eq!(derive_key(raw), [1u8; 32]);
+}
+
+#[test]
+fn test_plaintext_is_not_encrypted() {
+    assert!(!is_encrypted("sk-plain"));
+    assert!(decrypt("sk-plain", &derive_key("k")).is_err());
+}
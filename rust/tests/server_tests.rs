@@ -0,0 +1,188 @@
+/*!
+ * Server Tests
+ *
+ * End-to-end tests running the server binary against a mock upstream.
+ */
+
+#![cfg(all(feature = "server", feature = "openai"))]
+
+use httpmock::prelude::*;
+use serde_json::{json, Value};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+/// The server binary, killed when dropped
+struct Server {
+    child: Child,
+    base_url: String,
+}
+
+impl Server {
+    /// Start the server from `config` with an `openai-custom` provider pointing at `upstream`
+    async fn start(upstream: &MockServer, config: Value) -> Self {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        // An empty working directory, so nothing is read from or written next to the tests
+        let directory = std::env::temp_dir().join(format!("server-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_aiclient2api-rust"))
+            .args(["--host", "127.0.0.1", "--port", &port.to_string(), "--api-key", "test-key"])
+            .args(["--model-provider", "openai-custom", "--openai-api-key", "sk-test"])
+            .args(["--openai-base-url", &upstream.base_url()])
+            .env("AIPROXY_CONFIG_JSON", config.to_string())
+            .current_dir(&directory)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let server = Self {
+            child,
+            base_url: format!("http://127.0.0.1:{}", port),
+        };
+
+        let client = reqwest::Client::new();
+        for _ in 0..100 {
+            if client.get(format!("{}/health", server.base_url)).send().await.is_ok() {
+                return server;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("server did not start");
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn sse(chunks: &[Value]) -> String {
+    let mut body: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect();
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+#[tokio::test]
+async fn test_claude_messages_stream_from_an_openai_provider() {
+    let upstream = MockServer::start_async().await;
+    let chunk = |delta: Value, finish_reason: Value| {
+        json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "model": "gpt-4o",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        })
+    };
+    let body = sse(&[
+        chunk(json!({"role": "assistant", "content": "Checking"}), Value::Null),
+        chunk(
+            json!({"tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":"}}]}),
+            Value::Null,
+        ),
+        chunk(json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"Paris\"}"}}]}), Value::Null),
+        chunk(json!({}), json!("tool_calls")),
+    ]);
+    let mock = upstream
+        .mock_async(|when, then| {
+            when.method(POST).path("/chat/completions");
+            then.status(200).header("content-type", "text/event-stream").body(body);
+        })
+        .await;
+    let server = Server::start(&upstream, json!({})).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/messages", server.base_url))
+        .header("x-api-key", "test-key")
+        .json(&json!({
+            "model": "gpt-4o",
+            "max_tokens": 100,
+            "stream": true,
+            "messages": [{"role": "user", "content": "Weather in Paris?"}]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let text = response.text().await.unwrap();
+
+    mock.assert_async().await;
+    let events: Vec<Value> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    let types: Vec<_> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(
+        types,
+        [
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_stop",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop",
+        ]
+    );
+    assert_eq!(events[2]["delta"]["text"], "Checking");
+    assert_eq!(events[4]["content_block"]["name"], "get_weather");
+    assert_eq!(events[8]["delta"]["stop_reason"], "tool_use");
+}
+
+#[tokio::test]
+async fn test_purge_deletes_usage_rollups_and_transcripts() {
+    let upstream = MockServer::start_async().await;
+    upstream
+        .mock_async(|when, then| {
+            when.method(POST).path("/chat/completions");
+            then.status(200).json_body(json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "model": "gpt-4o",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 5, "completion_tokens": 1, "total_tokens": 6}
+            }));
+        })
+        .await;
+    let config = json!({
+        "admin_api_key": "admin-key",
+        "usage_rollup": {},
+        "transcripts": {"capacity": 10}
+    });
+    let server = Server::start(&upstream, config).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/v1/chat/completions", server.base_url))
+        .bearer_auth("test-key")
+        .header("x-conversation-id", "session-1")
+        .header("x-request-id", "req-1")
+        .json(&json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "Hello"}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let usage = || client.get(format!("{}/v1/usage?conversation_id=session-1", server.base_url)).bearer_auth("test-key").send();
+    let transcript = || client.get(format!("{}/admin/requests/req-1", server.base_url)).bearer_auth("admin-key").send();
+    assert_eq!(usage().await.unwrap().status(), 200);
+    assert_eq!(transcript().await.unwrap().status(), 200);
+
+    let purged: Value = client
+        .post(format!("{}/admin/privacy/purge", server.base_url))
+        .bearer_auth("admin-key")
+        .json(&json!({"client_id": "static"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(purged["purged"]["usage_rollups"], 1);
+    assert_eq!(purged["purged"]["transcripts"], 1);
+    assert_eq!(usage().await.unwrap().status(), 404);
+    assert_eq!(transcript().await.unwrap().status(), 404);
+}
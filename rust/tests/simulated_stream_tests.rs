@@ -0,0 +1,114 @@
+/*!
+ * Simulated Streaming Tests
+ *
+ * Unit tests for streaming complete responses as paced chunks.
+ */
+
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::StreamPacingConfig;
+use aiclient2api_rust::conversations::StreamedReply;
+use aiclient2api_rust::simulated_stream::*;
+use futures::StreamExt;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_openai_chunks_rebuild_the_completion() {
+    let response = json!({
+        "id": "chatcmpl-1",
+        "created": 1700000000,
+        "model": "gpt-4o",
+        "choices": [{"index": 0, "finish_reason": "tool_calls", "message": {
+            "role": "assistant",
+            "content": "Looking that up for you.",
+            "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "lookup", "arguments": "{\"q\":\"x\"}"}}]
+        }}],
+        "usage": {"prompt_tokens": 10, "completion_tokens": 7, "total_tokens": 17}
+    });
+    let chunks = openai_chunks(&response, 10);
+
+    assert!(chunks.iter().all(|c| c["id"] == "chatcmpl-1" && c["object"] == "chat.completion.chunk"));
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    let text_chunks = chunks.iter().filter(|c| c["choices"][0]["delta"]["content"].as_str().is_some_and(|t| !t.is_empty())).count();
+    assert_eq!(text_chunks, 3);
+    let last = chunks.last().unwrap();
+    assert_eq!(last["choices"][0]["finish_reason"], "tool_calls");
+    assert_eq!(last["usage"]["total_tokens"], 17);
+
+    let mut reply = StreamedReply::new(ModelProtocol::OpenAI);
+    chunks.iter().for_each(|chunk| reply.observe(chunk));
+    assert_eq!(
+        reply.into_message().unwrap(),
+        json!({"role": "assistant", "content": "Looking that up for you.", "tool_calls": [
+            {"id": "call_1", "type": "function", "function": {"name": "lookup", "arguments": "{\"q\":\"x\"}"}}
+        ]})
+    );
+}
+
+#[test]
+fn test_claude_events_rebuild_the_message() {
+    let content = json!([
+        {"type": "thinking", "thinking": "The user wants weather.", "signature": "sig"},
+        {"type": "text", "text": "Checking the forecast."},
+        {"type": "tool_use", "id": "tu_1", "name": "weather", "input": {"city": "Paris"}}
+    ]);
+    let response = json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-sonnet-4",
+        "content": content,
+        "stop_reason": "tool_use",
+        "usage": {"input_tokens": 12, "output_tokens": 30}
+    });
+    let events = claude_events(&response, 8);
+
+    let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(types.first(), Some(&"message_start"));
+    assert_eq!(&types[types.len() - 2..], &["message_delta", "message_stop"]);
+    assert_eq!(events[0]["message"]["usage"]["input_tokens"], 12);
+    assert_eq!(events[events.len() - 2]["delta"]["stop_reason"], "tool_use");
+    assert_eq!(events[events.len() - 2]["usage"]["output_tokens"], 30);
+
+    let mut reply = StreamedReply::new(ModelProtocol::Claude);
+    events.iter().for_each(|event| reply.observe(event));
+    assert_eq!(reply.into_message().unwrap(), json!({"role": "assistant", "content": content}));
+}
+
+#[test]
+fn test_gemini_chunks_carry_the_candidates() {
+    let response = json!({
+        "candidates": [{"index": 0, "finishReason": "STOP", "content": {"role": "model", "parts": [
+            {"text": "Looking that up."},
+            {"functionCall": {"name": "lookup", "args": {"q": "x"}}}
+        ]}}],
+        "usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 7, "totalTokenCount": 17}
+    });
+    let chunks = gemini_chunks(&response, 8);
+
+    let text: String = chunks.iter().filter_map(|c| c["candidates"][0]["content"]["parts"][0]["text"].as_str()).collect();
+    assert_eq!(text, "Looking that up.");
+    assert_eq!(chunks.len(), 4);
+    assert_eq!(chunks[2]["candidates"][0]["content"]["parts"][0]["functionCall"]["name"], "lookup");
+    let last = chunks.last().unwrap();
+    assert_eq!(last["candidates"][0]["finishReason"], "STOP");
+    assert_eq!(last["usageMetadata"]["totalTokenCount"], 17);
+}
+
+#[tokio::test]
+async fn test_replay_is_paced() {
+    assert_eq!(split_text("héllo wörld", 4), vec!["héll", "o wö", "rld"]);
+    assert_eq!(split_text("", 4), Vec::<String>::new());
+
+    let response = json!({"choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "abcdefgh"}}]});
+    let pacing = StreamPacingConfig {
+        chunk_chars: 2,
+        interval_ms: 10,
+    };
+    let started = Instant::now();
+    let chunks: Vec<_> = replay(&response, ModelProtocol::OpenAI, &pacing).collect().await;
+    // Role chunk, four text chunks and the finish chunk, with a pause between each
+    assert_eq!(chunks.len(), 6);
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert!(chunks.iter().all(|chunk| chunk.is_ok()));
+}
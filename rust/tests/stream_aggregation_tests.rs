@@ -0,0 +1,102 @@
+/*!
+ * Stream Aggregation Tests
+ *
+ * Unit tests for assembling a complete response from stream chunks.
+ */
+
+use aiclient2api_rust::stream_aggregation::aggregate;
+use aiclient2api_rust::stream_recovery::ValueStream;
+use aiclient2api_rust::ModelProtocol;
+use anyhow::anyhow;
+use serde_json::{json, Value};
+
+fn stream(chunks: Vec<Value>) -> ValueStream {
+    Box::pin(futures::stream::iter(chunks.into_iter().map(Ok)))
+}
+
+#[tokio::test]
+async fn test_openai_chunks_with_tool_calls() {
+    let chunk = |delta: Value, finish_reason: Value| {
+        json!({"id": "chatcmpl-9", "created": 1700000000, "model": "gpt-4o", "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]})
+    };
+    let chunks = vec![
+        chunk(json!({"role": "assistant", "content": ""}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "get_weather", "arguments": ""}}]}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 0, "function": {"arguments": "{\"city\":"}}]}), Value::Null),
+        chunk(json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"Paris\"}"}}]}), Value::Null),
+        chunk(json!({}), json!("tool_calls")),
+        json!({"id": "chatcmpl-9", "choices": [], "usage": {"prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20}}),
+    ];
+
+    let response = aggregate(stream(chunks), ModelProtocol::OpenAI).await.unwrap();
+    assert_eq!(response["id"], "chatcmpl-9");
+    assert_eq!(response["object"], "chat.completion");
+    assert_eq!(response["created"], 1700000000);
+    let choice = &response["choices"][0];
+    assert_eq!(choice["finish_reason"], "tool_calls");
+    assert!(choice["message"]["content"].is_null());
+    assert_eq!(choice["message"]["tool_calls"][0]["id"], "call_1");
+    assert_eq!(choice["message"]["tool_calls"][0]["function"]["arguments"], r#"{"city":"Paris"}"#);
+    assert_eq!(response["usage"]["total_tokens"], 20);
+}
+
+#[tokio::test]
+async fn test_claude_events() {
+    let chunks = vec![
+        json!({"type": "message_start", "message": {"id": "msg_1", "type": "message", "role": "assistant", "model": "claude-sonnet-4", "content": [], "stop_reason": null, "usage": {"input_tokens": 25, "output_tokens": 1}}}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": "Look it up."}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "signature_delta", "signature": "sig"}}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "content_block_start", "index": 1, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "text_delta", "text": "Checking"}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "text_delta", "text": " now."}}),
+        json!({"type": "content_block_stop", "index": 1}),
+        json!({"type": "content_block_start", "index": 2, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "search", "input": {}}}),
+        json!({"type": "content_block_delta", "index": 2, "delta": {"type": "input_json_delta", "partial_json": "{\"q\": "}}),
+        json!({"type": "content_block_delta", "index": 2, "delta": {"type": "input_json_delta", "partial_json": "\"rust\"}"}}),
+        json!({"type": "content_block_stop", "index": 2}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "tool_use", "stop_sequence": null}, "usage": {"output_tokens": 42}}),
+        json!({"type": "message_stop"}),
+    ];
+
+    let response = aggregate(stream(chunks), ModelProtocol::Claude).await.unwrap();
+    assert_eq!(response["id"], "msg_1");
+    assert_eq!(response["stop_reason"], "tool_use");
+    assert_eq!(response["usage"], json!({"input_tokens": 25, "output_tokens": 42}));
+    assert_eq!(response["content"][0], json!({"type": "thinking", "thinking": "Look it up.", "signature": "sig"}));
+    assert_eq!(response["content"][1], json!({"type": "text", "text": "Checking now."}));
+    assert_eq!(response["content"][2]["input"], json!({"q": "rust"}));
+
+    let error = aggregate(
+        stream(vec![json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}})]),
+        ModelProtocol::Claude,
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("Overloaded"));
+}
+
+#[tokio::test]
+async fn test_gemini_chunks_and_failures() {
+    let chunks = vec![
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "Hel"}]}}], "modelVersion": "gemini-2.5-flash"}),
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "lo"}, {"functionCall": {"name": "lookup", "args": {"id": 1}}}]}}]}),
+        json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "!"}]}, "finishReason": "STOP"}], "usageMetadata": {"promptTokenCount": 4, "candidatesTokenCount": 3, "totalTokenCount": 7}}),
+    ];
+
+    let response = aggregate(stream(chunks), ModelProtocol::Gemini).await.unwrap();
+    let candidate = &response["candidates"][0];
+    assert_eq!(candidate["finishReason"], "STOP");
+    assert_eq!(candidate["content"]["parts"], json!([{"text": "Hello"}, {"functionCall": {"name": "lookup", "args": {"id": 1}}}, {"text": "!"}]));
+    assert_eq!(response["usageMetadata"]["totalTokenCount"], 7);
+    assert_eq!(response["modelVersion"], "gemini-2.5-flash");
+
+    // An empty or broken stream fails the request instead of returning an empty answer
+    assert!(aggregate(stream(vec![]), ModelProtocol::Gemini).await.is_err());
+    let broken: ValueStream = Box::pin(futures::stream::iter(vec![
+        Ok(json!({"candidates": [{"content": {"parts": [{"text": "Hi"}]}}]})),
+        Err(anyhow!("connection reset")),
+    ]));
+    assert_eq!(aggregate(broken, ModelProtocol::Gemini).await.unwrap_err().to_string(), "connection reset");
+}
@@ -0,0 +1,73 @@
+/*!
+ * Stream Errors Tests
+ *
+ * Unit tests for reporting failures inside a stream in the client's dialect.
+ */
+
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::convert::ChatStream;
+use aiclient2api_rust::stream_errors::*;
+use aiclient2api_rust::upstream_error::UpstreamError;
+use bytes::Bytes;
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[test]
+fn test_errors_are_rendered_in_each_dialect() {
+    let error = anyhow::Error::new(UpstreamError::stream(StatusCode::from_u16(529).unwrap(), "busy"));
+    let chunk = openai_error_chunk(&error);
+    assert_eq!(chunk["error"]["type"], "server_error");
+    assert_eq!(chunk["error"]["code"], "overloaded");
+    assert_eq!(chunk["error"]["message"], "Stream API call failed (529 <unknown status code>): busy");
+
+    let event = claude_error_event(&UpstreamError::new(StatusCode::TOO_MANY_REQUESTS, "slow down").into());
+    assert_eq!(event["type"], "error");
+    assert_eq!(event["error"]["type"], "rate_limit_error");
+
+    let chunk = gemini_error_chunk(&UpstreamError::new(StatusCode::TOO_MANY_REQUESTS, "slow down").into());
+    assert_eq!(chunk["error"]["code"], 429);
+    assert_eq!(chunk["error"]["status"], "RESOURCE_EXHAUSTED");
+
+    assert_eq!(classify(&UpstreamError::new(StatusCode::GATEWAY_TIMEOUT, "").into()), ErrorKind::Timeout);
+    assert_eq!(classify(&anyhow::anyhow!("Stream error: connection reset (os error 104)")), ErrorKind::Api);
+}
+
+#[tokio::test]
+async fn test_in_band_upstream_errors_survive_conversion() {
+    let events = vec![
+        Ok(json!({"type": "message_start", "message": {"id": "msg_1"}})),
+        Ok(json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}})),
+    ];
+    let stream = ChatStream::Claude(Box::pin(futures::stream::iter(events)))
+        .into_protocol(ModelProtocol::OpenAI, Some("claude-sonnet-4"))
+        .unwrap();
+    let items: Vec<_> = until_error(stream).collect().await;
+    assert_eq!(items.len(), 2);
+    let error = items[1].as_ref().unwrap_err();
+    let chunk = openai_error_chunk(error);
+    assert_eq!(chunk["error"]["message"], "Overloaded");
+    assert_eq!(chunk["error"]["code"], "overloaded");
+
+    let gemini = vec![Ok(json!({"error": {"code": 429, "message": "Quota exceeded", "status": "RESOURCE_EXHAUSTED"}}))];
+    let stream = ChatStream::Gemini(Box::pin(futures::stream::iter(gemini)))
+        .into_protocol(ModelProtocol::OpenAI, Some("gemini-2.5-pro"))
+        .unwrap();
+    let items: Vec<_> = stream.collect().await;
+    assert_eq!(classify(items[0].as_ref().unwrap_err()), ErrorKind::RateLimited);
+}
+
+#[tokio::test]
+async fn test_nothing_follows_an_error() {
+    let items = vec![Ok(json!({"n": 1})), Err(anyhow::anyhow!("boom")), Ok(json!({"n": 2}))];
+    let out: Vec<_> = until_error(futures::stream::iter(items)).collect().await;
+    assert_eq!(out.len(), 2);
+    assert!(out[1].is_err());
+
+    let bytes = vec![Ok(Bytes::from_static(b"data: {\"id\":1}\n\n")), Err(anyhow::anyhow!("reset")), Ok(Bytes::from_static(b"late"))];
+    let out: Vec<_> = openai_bytes(futures::stream::iter(bytes)).map(|b| b.unwrap()).collect().await;
+    assert_eq!(out.len(), 2);
+    let tail = String::from_utf8(out[1].to_vec()).unwrap();
+    assert!(tail.starts_with("\n\ndata: {\"error\":"), "{}", tail);
+    assert!(tail.ends_with("\n\n"));
+}
@@ -0,0 +1,127 @@
+/*!
+ * Stream Recovery Tests
+ *
+ * Unit tests for resuming a failed Claude stream with an assistant prefill.
+ */
+
+use aiclient2api_rust::stream_recovery::*;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+fn events(items: Vec<anyhow::Result<Value>>) -> ValueStream {
+    Box::pin(futures::stream::iter(items))
+}
+
+fn text_start(index: u64) -> Value {
+    json!({"type": "content_block_start", "index": index, "content_block": {"type": "text", "text": ""}})
+}
+
+fn text_delta(index: u64, text: &str) -> Value {
+    json!({"type": "content_block_delta", "index": index, "delta": {"type": "text_delta", "text": text}})
+}
+
+#[test]
+fn test_resume_request_appends_prefill() {
+    let mut progress = StreamProgress::default();
+    progress.process(json!({"type": "message_start"}));
+    progress.process(text_start(0));
+    progress.process(text_delta(0, "Hello wor "));
+    assert!(progress.can_resume());
+
+    let original = json!({"messages": [{"role": "user", "content": "Hi"}]});
+    let body = progress.resume_request(&original);
+    assert_eq!(body["messages"][1], json!({"role": "assistant", "content": "Hello wor"}));
+
+    // The resumed stream's own starts are dropped and its block continues block 0
+    assert!(progress.process(json!({"type": "message_start"})).is_none());
+    assert!(progress.process(text_start(0)).is_none());
+    assert_eq!(progress.process(text_delta(0, "ld")).unwrap()["index"], 0);
+    assert_eq!(progress.process(text_start(1)).unwrap()["index"], 1);
+}
+
+#[test]
+fn test_tool_use_is_not_resumable() {
+    let mut progress = StreamProgress::default();
+    progress.process(json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "f"}}));
+    assert!(!progress.can_resume());
+}
+
+#[tokio::test]
+async fn test_resumable_stream_splices_continuation() {
+    let first = events(vec![
+        Ok(json!({"type": "message_start", "message": {"id": "msg_1"}})),
+        Ok(text_start(0)),
+        Ok(text_delta(0, "Hello wor")),
+        Err(anyhow::anyhow!("connection reset")),
+    ]);
+
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    let stream = resumable(first, json!({"messages": [{"role": "user", "content": "Hi"}]}), 1, move |body| {
+        seen.lock().unwrap().push(body);
+        async {
+            Ok(events(vec![
+                Ok(json!({"type": "message_start", "message": {"id": "msg_2"}})),
+                Ok(text_start(0)),
+                Ok(text_delta(0, "ld!")),
+                Ok(json!({"type": "content_block_stop", "index": 0})),
+                Ok(json!({"type": "message_stop"})),
+            ]))
+        }
+    });
+
+    let out: Vec<Value> = stream.map(|item| item.unwrap()).collect().await;
+    let kinds: Vec<&str> = out.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(
+        kinds,
+        ["message_start", "content_block_start", "content_block_delta", "content_block_delta", "content_block_stop", "message_stop"]
+    );
+    assert_eq!(out[0]["message"]["id"], "msg_1");
+    assert_eq!(requests.lock().unwrap()[0]["messages"][1]["content"], "Hello wor");
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts() {
+    let first = events(vec![Ok(text_start(0)), Ok(json!({"type": "error", "error": {"type": "overloaded_error"}}))]);
+    let stream = resumable(first, json!({"messages": []}), 0, |_| async { anyhow::bail!("not called") });
+
+    let out: Vec<anyhow::Result<Value>> = stream.collect().await;
+    assert_eq!(out.len(), 2);
+    assert!(out[1].as_ref().unwrap_err().to_string().contains("overloaded_error"));
+}
+
+#[tokio::test]
+async fn test_salvage_closes_claude_stream() {
+    let stream = salvage(events(vec![
+        Ok(json!({"type": "message_start"})),
+        Ok(text_start(0)),
+        Ok(text_delta(0, "partial")),
+        Err(anyhow::anyhow!("connection reset")),
+    ]));
+
+    let out: Vec<Value> = stream.map(|item| item.unwrap()).collect().await;
+    let kinds: Vec<&str> = out.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(
+        kinds,
+        ["message_start", "content_block_start", "content_block_delta", "content_block_stop", "message_delta", "message_stop"]
+    );
+    assert_eq!(out[4]["delta"]["stop_reason"], SALVAGED_STOP_REASON);
+    assert!(out[4]["error"]["message"].as_str().unwrap().contains("connection reset"));
+}
+
+#[tokio::test]
+async fn test_salvage_openai_and_passthrough() {
+    let stream = salvage(events(vec![
+        Ok(json!({"id": "chatcmpl-1", "model": "gpt-4o", "choices": [{"index": 0, "delta": {"content": "Hi"}, "finish_reason": null}]})),
+        Err(anyhow::anyhow!("boom")),
+    ]));
+    let out: Vec<Value> = stream.map(|item| item.unwrap()).collect().await;
+    assert_eq!(out[1]["id"], "chatcmpl-1");
+    assert_eq!(out[1]["choices"][0]["finish_reason"], SALVAGED_STOP_REASON);
+
+    // Nothing was sent yet: the error is surfaced unchanged
+    let stream = salvage(events(vec![Err(anyhow::anyhow!("refused"))]));
+    let out: Vec<anyhow::Result<Value>> = stream.collect().await;
+    assert!(out[0].is_err());
+}
@@ -9,7 +9,6 @@ use serde_json::json;
 #[tokio::test]
 async fn test_apply_to_openai_overwrite() {
     use aiclient2api_rust::system_prompt::SystemPromptManager;
-    use std::path::PathBuf;
     
     let manager = SystemPromptManager::new(
         None,
@@ -0,0 +1,69 @@
+/*!
+ * Tokenizer Tests
+ *
+ * Unit tests for per-model prompt token estimates.
+ */
+
+use aiclient2api_rust::tokenizer::*;
+use serde_json::json;
+
+#[test]
+fn test_openai_models_use_their_own_encoding() {
+    let gpt4o = ModelTokenizer::for_model("gpt-4o-2024-08-06");
+    assert_eq!(gpt4o.encoding, "o200k_base");
+    assert!(gpt4o.native);
+    assert_eq!(ModelTokenizer::for_model("gpt-4").encoding, "cl100k_base");
+
+    assert_eq!(gpt4o.count_text("hello world"), 2);
+    // 3 per message + 2 text tokens + 3 to prime the reply
+    let request = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "hello world"}]});
+    assert_eq!(gpt4o.count_prompt(&request), 8);
+}
+
+#[test]
+fn test_claude_blocks_images_and_tools_are_counted() {
+    let claude = ModelTokenizer::for_model("claude-3-5-sonnet-20241022");
+    assert!(!claude.native);
+    assert!(claude.scale > 1.0);
+
+    let text_only = json!({"messages": [{"role": "user", "content": "hello world"}]});
+    let request = json!({
+        "system": [{"type": "text", "text": "You are terse."}],
+        "messages": [
+            {"role": "user", "content": [
+                {"type": "text", "text": "hello world"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}}
+            ]},
+            {"role": "assistant", "content": [{"type": "tool_use", "id": "t1", "name": "lookup", "input": {"q": "x"}}]},
+            {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "t1", "content": "result"}]}
+        ],
+        "tools": [{"name": "lookup", "input_schema": {"type": "object"}}]
+    });
+    let plain = claude.count_prompt(&text_only);
+    let full = claude.count_prompt(&request);
+    assert!(full > plain + 765);
+
+    // The approximation scales the cl100k count
+    let cl100k = ModelTokenizer::for_model("gpt-4");
+    assert_eq!(plain, (cl100k.count_prompt(&text_only) as f64 * claude.scale).ceil() as usize);
+}
+
+#[test]
+fn test_count_request_per_target_model() {
+    let request = json!({
+        "model": "gpt-4o",
+        "models": ["gpt-4o", "claude-3-5-sonnet-20241022", "gemini-2.5-pro"],
+        "messages": [{"role": "user", "content": "How many tokens is this?"}]
+    });
+    let counts = count_request(&request);
+    let data = counts["data"].as_array().unwrap();
+    assert_eq!(data.len(), 3);
+    assert_eq!(data[0]["model"], "gpt-4o");
+    assert_eq!(data[0]["exact_tokenizer"], true);
+    assert_eq!(data[2]["tokenizer"], "cl100k_base");
+    assert!(data.iter().all(|d| d["prompt_tokens"].as_u64().unwrap() > 6));
+
+    // Without `models`, the request's own model is counted
+    let counts = count_request(&json!({"model": "gpt-4o", "messages": []}));
+    assert_eq!(counts["data"][0]["prompt_tokens"], 3);
+}
@@ -0,0 +1,127 @@
+/*!
+ * Tool Loop Tests
+ *
+ * Unit tests for running registered HTTP tools on the model's behalf.
+ */
+
+use aiclient2api_rust::config::{HttpToolConfig, ToolLoopConfig};
+use aiclient2api_rust::tool_loop::*;
+use httpmock::prelude::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+fn tool_loop(url: String, max_iterations: u32) -> ToolLoop {
+    let config = ToolLoopConfig {
+        tools: vec![HttpToolConfig {
+            name: "get_weather".to_string(),
+            description: "Current weather for a city".to_string(),
+            parameters: json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+            url,
+            headers: HashMap::from([("x-tool-key".to_string(), "secret".to_string())]),
+        }],
+        max_iterations,
+        timeout_secs: 5,
+    };
+    ToolLoop::new(&config, reqwest::Client::new())
+}
+
+fn tool_call_reply(name: &str) -> Value {
+    json!({
+        "choices": [{"index": 0, "finish_reason": "tool_calls", "message": {
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": name, "arguments": "{\"city\":\"Paris\"}"}}]
+        }}],
+        "usage": {"prompt_tokens": 10, "completion_tokens": 5}
+    })
+}
+
+fn answer(text: &str) -> Value {
+    json!({
+        "choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": text}}],
+        "usage": {"prompt_tokens": 30, "completion_tokens": 8}
+    })
+}
+
+#[test]
+fn test_only_registered_calls_are_run_by_the_proxy() {
+    let tools = tool_loop("http://127.0.0.1:1/weather".to_string(), 3);
+
+    let mut body = json!({"messages": [], "tools": [{"type": "function", "function": {"name": "client_tool"}}]});
+    tools.inject_tools(&mut body);
+    tools.inject_tools(&mut body);
+    assert_eq!(body["tools"].as_array().unwrap().len(), 2);
+    assert_eq!(body["tools"][1]["function"]["name"], "get_weather");
+
+    let calls = tools.registered_calls(&tool_call_reply("get_weather")).unwrap();
+    assert_eq!(calls[0].arguments, "{\"city\":\"Paris\"}");
+    // A client-defined tool goes back to the client
+    assert!(tools.registered_calls(&tool_call_reply("client_tool")).is_none());
+    assert!(tools.registered_calls(&answer("hi")).is_none());
+}
+
+#[tokio::test]
+async fn test_loop_runs_tool_and_returns_final_answer() {
+    let server = MockServer::start_async().await;
+    let callback = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/weather")
+                .header("x-tool-key", "secret")
+                .json_body(json!({"name": "get_weather", "arguments": {"city": "Paris"}}));
+            then.status(200).body("{\"temp_c\":21}");
+        })
+        .await;
+    let tools = tool_loop(server.url("/weather"), 3);
+
+    let requests = Mutex::new(Vec::new());
+    let response = tools
+        .run(json!({"messages": [{"role": "user", "content": "Weather in Paris?"}]}), |body| {
+            let round = {
+                let mut requests = requests.lock().unwrap();
+                requests.push(body);
+                requests.len()
+            };
+            async move { Ok(if round == 1 { tool_call_reply("get_weather") } else { answer("21°C and sunny") }) }
+        })
+        .await
+        .unwrap();
+
+    callback.assert_async().await;
+    assert_eq!(response["choices"][0]["message"]["content"], "21°C and sunny");
+    assert_eq!(response["usage"], json!({"prompt_tokens": 40, "completion_tokens": 13, "total_tokens": 53}));
+
+    let requests = requests.lock().unwrap();
+    let messages = requests[1]["messages"].as_array().unwrap();
+    assert_eq!(messages[1]["tool_calls"][0]["id"], "call_1");
+    assert_eq!(messages[2], json!({"role": "tool", "tool_call_id": "call_1", "content": "{\"temp_c\":21}"}));
+}
+
+#[tokio::test]
+async fn test_failures_reach_the_model_and_rounds_are_capped() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(POST).path("/weather");
+            then.status(500).body("backend down");
+        })
+        .await;
+    let tools = tool_loop(server.url("/weather"), 1);
+
+    let requests = Mutex::new(Vec::new());
+    let response = tools
+        .run(json!({"messages": []}), |body| {
+            let forced = body.get("tool_choice") == Some(&json!("none"));
+            requests.lock().unwrap().push(body);
+            async move { Ok(if forced { answer("Sorry, no data") } else { tool_call_reply("get_weather") }) }
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response["choices"][0]["message"]["content"], "Sorry, no data");
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    let result = requests[1]["messages"][1]["content"].as_str().unwrap();
+    assert!(result.contains("500") && result.contains("backend down"));
+}
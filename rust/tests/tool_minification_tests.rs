@@ -0,0 +1,80 @@
+/*!
+ * Tool Minification Tests
+ *
+ * Unit tests for shrinking tool descriptions and schemas before dispatch.
+ */
+
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::config::ToolMinificationConfig;
+use aiclient2api_rust::tool_minification::ToolMinifier;
+use serde_json::json;
+
+#[test]
+fn test_markdown_is_reduced_to_one_plain_line() {
+    let minifier = ToolMinifier::new(&ToolMinificationConfig::default());
+    let text = "## Search\n\nSearch the **internal** wiki.\n\n- Use `query` for keywords\n- See [the docs](https://example.com/docs)\n\n```json\n{\"query\": \"x\"}\n```\n";
+    assert_eq!(
+        minifier.minify_text(text),
+        "Search Search the internal wiki. Use query for keywords See the docs {\"query\": \"x\"}"
+    );
+
+    let whitespace_only = ToolMinifier::new(&ToolMinificationConfig { strip_markdown: false, drop_examples: false });
+    assert_eq!(whitespace_only.minify_text("  Use **bold**\n\n  text  "), "Use **bold** text");
+}
+
+#[test]
+fn test_openai_tools_are_minified_without_touching_names_or_types() {
+    let minifier = ToolMinifier::new(&ToolMinificationConfig::default());
+    let mut body = json!({
+        "model": "gpt-4o",
+        "messages": [{"role": "user", "content": "hi"}],
+        "tools": [{
+            "type": "function",
+            "function": {
+                "name": "search_wiki",
+                "description": "Search the wiki.\n\n    **Returns** matching pages.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": {"type": "string", "description": "  Keywords\n  to match ", "examples": ["rust async"]},
+                        "examples": {"type": "array", "items": {"type": "string", "example": "a"}}
+                    },
+                    "required": ["query"]
+                }
+            }
+        }]
+    });
+
+    let stats = minifier.minify("gpt-4o", ModelProtocol::OpenAI, &mut body).unwrap();
+    assert!(stats.minified_tokens < stats.original_tokens);
+    assert_eq!(stats.saved_tokens(), stats.original_tokens - stats.minified_tokens);
+
+    let function = &body["tools"][0]["function"];
+    assert_eq!(function["name"], "search_wiki");
+    assert_eq!(function["description"], "Search the wiki. Returns matching pages.");
+    assert_eq!(
+        function["parameters"],
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "Keywords to match"},
+                "examples": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["query"]
+        }),
+        "a property named `examples` is kept"
+    );
+}
+
+#[test]
+fn test_claude_tools_and_requests_without_tools() {
+    let minifier = ToolMinifier::new(&ToolMinificationConfig::default());
+    let mut body = json!({
+        "tools": [{"name": "get_time", "description": "Get the\n\ncurrent time", "input_schema": {"type": "object", "properties": {}}}]
+    });
+    assert!(minifier.minify("claude-sonnet-4", ModelProtocol::Claude, &mut body).is_some());
+    assert_eq!(body["tools"][0]["description"], "Get the current time");
+
+    let mut plain = json!({"messages": []});
+    assert!(minifier.minify("claude-sonnet-4", ModelProtocol::Claude, &mut plain).is_none());
+}
@@ -0,0 +1,99 @@
+/*!
+ * Request Transcript Tests
+ *
+ * Tests for capturing client and upstream traffic and the bounded transcript store.
+ */
+
+use aiclient2api_rust::config::TranscriptConfig;
+use aiclient2api_rust::transcripts::{self, Transcript, TranscriptStore};
+use serde_json::json;
+
+fn transcript(id: &str, status: u16) -> Transcript {
+    Transcript {
+        id: id.to_string(),
+        client_id: Some("key:a".to_string()),
+        method: "POST".to_string(),
+        path: "/v1/chat/completions".to_string(),
+        started_at: chrono::Utc::now(),
+        status,
+        request: Some(json!({"model": "gpt-4o"})),
+        upstream_request: None,
+        upstream_response: None,
+        warnings: Vec::new(),
+        upstream_ms: None,
+        total_ms: 1,
+    }
+}
+
+#[test]
+fn test_store_keeps_only_the_latest() {
+    let store = TranscriptStore::new(&TranscriptConfig { capacity: 2 });
+    store.push(transcript("a", 200));
+    store.push(transcript("b", 200));
+    store.push(transcript("c", 200));
+    assert_eq!(store.len(), 2);
+    assert!(store.get("a").is_none());
+    assert!(store.get("c").is_some());
+
+    // A reused id finds the latest request
+    store.push(transcript("c", 500));
+    assert_eq!(store.get("c").unwrap().status, 500);
+}
+
+#[tokio::test]
+async fn test_scope_captures_the_latest_upstream_call() {
+    let ((), capture) = transcripts::scope(async {
+        transcripts::record_upstream_request(&json!({"attempt": 1}));
+        transcripts::record_upstream_response(&json!({"error": "overloaded"}));
+        // A retry replaces the earlier call, and its answer is not in yet
+        transcripts::record_upstream_request(&json!({"attempt": 2}));
+    })
+    .await;
+    assert_eq!(capture.upstream_request, Some(json!({"attempt": 2})));
+    assert_eq!(capture.upstream_response, None);
+    assert!(!capture.skipped);
+
+    let ((), capture) = transcripts::scope(async {
+        transcripts::skip();
+        transcripts::record_upstream_request(&json!({}));
+        transcripts::record_upstream_response(&json!({}));
+    })
+    .await;
+    assert!(capture.skipped);
+    assert!(capture.upstream_ms.is_some());
+
+    // Outside a scope nothing is kept and nothing panics
+    transcripts::record_upstream_request(&json!({}));
+}
+
+#[tokio::test]
+async fn test_scope_keeps_the_client_body_only_when_noted() {
+    let ((), capture) = transcripts::scope(async {
+        transcripts::record_upstream_request(&json!({}));
+    })
+    .await;
+    assert!(capture.request.is_none());
+
+    let ((), capture) = transcripts::scope(async {
+        transcripts::record_client_request("key:a", &json!({"model": "gpt-4o"}));
+    })
+    .await;
+    assert_eq!(capture.request, Some(json!({"model": "gpt-4o"})));
+    assert_eq!(capture.client_id.as_deref(), Some("key:a"));
+}
+
+#[test]
+fn test_purge_client_deletes_only_that_clients_transcripts() {
+    let store = TranscriptStore::new(&TranscriptConfig { capacity: 10 });
+    store.push(transcript("a", 200));
+    store.push(Transcript {
+        client_id: Some("key:b".to_string()),
+        ..transcript("b", 200)
+    });
+    store.push(transcript("c", 200));
+
+    assert_eq!(store.purge_client("key:a"), 2);
+    assert!(store.get("a").is_none());
+    assert!(store.get("b").is_some());
+    assert_eq!(store.purge_client("key:a"), 0);
+}
@@ -0,0 +1,151 @@
+/*!
+ * Unified Representation Tests
+ *
+ * Tests for the provider-agnostic request and response form.
+ */
+
+use aiclient2api_rust::common::ModelProtocol;
+use aiclient2api_rust::convert::{convert_data, ConversionType};
+use aiclient2api_rust::convert_detailed::{gemini_request_to_claude, gemini_response_to_claude};
+use aiclient2api_rust::unified::{Part, ResponseFormat, Role, ToolChoice, UnifiedRequest, UnifiedResponse};
+use serde_json::json;
+
+#[test]
+fn test_claude_request_round_trips_through_unified_form() {
+    let claude_req = json!({
+        "model": "claude-sonnet-4",
+        "system": "Be brief.",
+        "max_tokens": 512,
+        "messages": [
+            {"role": "user", "content": "Weather?"},
+            {"role": "assistant", "content": [
+                {"type": "tool_use", "id": "toolu_a", "name": "weather", "input": {"city": "Paris"}}
+            ]},
+            {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "toolu_a", "content": "Sunny"}]}
+        ],
+        "temperature": 0.2,
+        "stop_sequences": ["END"],
+        "tool_choice": {"type": "any"},
+        "tools": [{"name": "weather", "input_schema": {"type": "object"}}]
+    });
+
+    let unified = UnifiedRequest::from_claude(claude_req.clone()).unwrap();
+    assert_eq!(unified.system, vec!["Be brief."]);
+    assert_eq!(unified.messages[2].role, Role::User);
+    assert!(matches!(&unified.messages[2].parts[0], Part::ToolResult { name, .. } if name == "weather"));
+
+    let restored = unified.into_claude();
+    assert_eq!(restored["messages"][0]["content"], json!([{"type": "text", "text": "Weather?"}]));
+    assert_eq!(restored["messages"][1], claude_req["messages"][1]);
+    assert_eq!(restored["messages"][2], claude_req["messages"][2]);
+    assert_eq!(restored["system"], "Be brief.");
+    assert_eq!(restored["stop_sequences"], json!(["END"]));
+    assert_eq!(restored["tool_choice"], json!({"type": "any"}));
+}
+
+#[test]
+fn test_gemini_thoughts_stay_reasoning_on_the_way_to_claude() {
+    let gemini_resp = json!({
+        "candidates": [{"content": {"role": "model", "parts": [
+            {"text": "Considering the options", "thought": true, "thoughtSignature": "sig"},
+            {"text": "Take the train."}
+        ]}, "finishReason": "STOP"}],
+        "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 3}
+    });
+    let claude = gemini_response_to_claude(gemini_resp, "gemini-2.5-pro").unwrap();
+    assert_eq!(claude["content"][0]["type"], "thinking");
+    assert_eq!(claude["content"][0]["thinking"], "Considering the options");
+    assert_eq!(claude["content"][1], json!({"type": "text", "text": "Take the train."}));
+
+    // Earlier reasoning in a Gemini history cannot be verified by Claude and is left out
+    let gemini_req = json!({"contents": [
+        {"role": "user", "parts": [{"text": "Train or plane?"}]},
+        {"role": "model", "parts": [{"text": "Hmm", "thought": true, "thoughtSignature": "sig"}, {"text": "Train."}]}
+    ]});
+    let claude_req = gemini_request_to_claude(gemini_req).unwrap();
+    assert_eq!(claude_req["messages"][1]["content"], json!([{"type": "text", "text": "Train."}]));
+}
+
+#[test]
+fn test_responses_convert_to_gemini() {
+    let claude_resp = json!({
+        "type": "message",
+        "role": "assistant",
+        "content": [
+            {"type": "text", "text": "Checking."},
+            {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {"city": "Paris"}}
+        ],
+        "stop_reason": "tool_use",
+        "usage": {"input_tokens": 12, "output_tokens": 4}
+    });
+    let gemini = convert_data(claude_resp, ConversionType::Response, ModelProtocol::Claude, ModelProtocol::Gemini, Some("claude-sonnet-4")).unwrap();
+    assert_eq!(
+        gemini["candidates"][0]["content"]["parts"],
+        json!([{"text": "Checking."}, {"functionCall": {"name": "weather", "args": {"city": "Paris"}}}])
+    );
+    assert_eq!(gemini["candidates"][0]["finishReason"], "STOP");
+    assert_eq!(gemini["usageMetadata"]["totalTokenCount"], 16);
+    assert_eq!(gemini["modelVersion"], "claude-sonnet-4");
+
+    let openai_resp = json!({
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hello"}, "finish_reason": "length"}],
+        "usage": {"prompt_tokens": 3, "completion_tokens": 1}
+    });
+    let gemini = convert_data(openai_resp, ConversionType::Response, ModelProtocol::OpenAI, ModelProtocol::Gemini, None).unwrap();
+    assert_eq!(gemini["candidates"][0]["content"]["parts"], json!([{"text": "Hello"}]));
+    assert_eq!(gemini["candidates"][0]["finishReason"], "MAX_TOKENS");
+}
+
+#[test]
+fn test_openai_request_reads_into_unified_form() {
+    let openai_req = json!({
+        "model": "gpt-4o",
+        "messages": [
+            {"role": "developer", "content": "Be brief."},
+            {"role": "user", "name": "alice", "content": "Weather?"},
+            {"role": "assistant", "content": null, "tool_calls": [
+                {"id": "call_a", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}}
+            ]},
+            {"role": "tool", "tool_call_id": "call_a", "content": "Sunny"}
+        ],
+        "tool_choice": "required",
+        "response_format": {"type": "json_object"}
+    });
+
+    let unified = UnifiedRequest::from_openai(openai_req.clone()).unwrap();
+    assert_eq!(unified.system, vec!["Be brief."]);
+    assert_eq!(unified.messages[0].parts[0], Part::Text("<name>alice</name>".to_string()));
+    assert!(matches!(&unified.messages[1].parts[0], Part::ToolCall { arguments, .. } if arguments["city"] == "Paris"));
+    assert!(matches!(&unified.messages[2].parts[0], Part::ToolResult { name, .. } if name == "weather"));
+    assert_eq!(unified.tool_choice, Some(ToolChoice::Required));
+    assert_eq!(unified.generation.response_format, Some(ResponseFormat::Json));
+
+    let restored = unified.into_openai();
+    assert_eq!(restored["messages"][0], json!({"role": "system", "content": "Be brief."}));
+    assert_eq!(restored["messages"].as_array().unwrap()[1..], openai_req["messages"].as_array().unwrap()[1..]);
+    assert_eq!(restored["response_format"], json!({"type": "json_object"}));
+}
+
+#[test]
+fn test_every_candidate_reaches_openai_and_gemini() {
+    let gemini_resp = json!({
+        "candidates": [
+            {"content": {"role": "model", "parts": [{"text": "Red"}]}, "finishReason": "STOP"},
+            {"content": {"role": "model", "parts": [{"text": "Blue"}]}, "finishReason": "MAX_TOKENS"}
+        ],
+        "usageMetadata": {"promptTokenCount": 4, "candidatesTokenCount": 2}
+    });
+    let unified = UnifiedResponse::from_gemini(gemini_resp).unwrap();
+    assert_eq!(unified.candidates.len(), 2);
+
+    let openai = unified.clone().into_openai();
+    assert_eq!(openai["choices"][1]["message"]["content"], "Blue");
+    assert_eq!(openai["choices"][1]["finish_reason"], "length");
+
+    let gemini = UnifiedResponse::from_openai(openai).unwrap().into_gemini();
+    assert_eq!(gemini["candidates"][1]["content"]["parts"], json!([{"text": "Blue"}]));
+    assert_eq!(gemini["candidates"][1]["finishReason"], "MAX_TOKENS");
+
+    // Claude has room for the first one only
+    assert_eq!(unified.into_claude()["content"], json!([{"type": "text", "text": "Red"}]));
+}
@@ -0,0 +1,25 @@
+/*!
+ * Upstream Error Tests
+ *
+ * Unit tests for classifying failed upstream calls by their status.
+ */
+
+use aiclient2api_rust::upstream_error::*;
+use anyhow::Context;
+use reqwest::StatusCode;
+
+#[test]
+fn test_status_is_read_from_the_error() {
+    let error = UpstreamError::stream(StatusCode::TOO_MANY_REQUESTS, "slow down");
+    assert_eq!(error.to_string(), "Stream API call failed (429 Too Many Requests): slow down");
+    assert_eq!(classify(&error.into()), Failure::Status(429));
+
+    // Found behind added context too
+    let wrapped = Err::<(), _>(UpstreamError::new(StatusCode::UNAUTHORIZED, "bad key")).context("Claude call").unwrap_err();
+    assert_eq!(classify(&wrapped), Failure::Status(401));
+}
+
+#[test]
+fn test_message_text_is_not_a_status() {
+    assert_eq!(classify(&anyhow::anyhow!("API call failed (429 Too Many Requests): slow down")), Failure::Other);
+}
@@ -0,0 +1,99 @@
+/*!
+ * Usage Rollup Tests
+ *
+ * Unit tests for totalling usage per conversation and tag.
+ */
+
+use aiclient2api_rust::cluster::MemoryStore;
+use aiclient2api_rust::config::UsageRollupConfig;
+use aiclient2api_rust::usage_rollup::*;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn rollup() -> UsageRollup {
+    UsageRollup::new(Arc::new(MemoryStore::new()), &UsageRollupConfig::default())
+}
+
+fn turn(provider: &str, input_tokens: u64, output_tokens: u64, latency_ms: u64, cost_usd: Option<f64>) -> Turn {
+    Turn {
+        provider: provider.to_string(),
+        input_tokens,
+        output_tokens,
+        latency: Duration::from_millis(latency_ms),
+        cost_usd,
+    }
+}
+
+#[tokio::test]
+async fn test_turns_add_up_per_conversation() {
+    let rollup = rollup();
+    let scopes = vec![Scope::Conversation("session-1".to_string())];
+    rollup.record("key:a", &scopes, &turn("openai-custom", 100, 20, 400, Some(0.01))).await.unwrap();
+    rollup.record("key:a", &scopes, &turn("claude-custom", 300, 50, 800, None)).await.unwrap();
+
+    let providers = vec!["openai-custom".to_string(), "claude-custom".to_string(), "gemini-cli-oauth".to_string()];
+    let totals = rollup.rollup("key:a", &scopes[0], &providers).await.unwrap().unwrap();
+    assert_eq!(totals["conversation_id"], "session-1");
+    assert_eq!(totals["requests"], 2);
+    assert_eq!(totals["input_tokens"], 400);
+    assert_eq!(totals["output_tokens"], 70);
+    assert_eq!(totals["total_tokens"], 470);
+    assert_eq!(totals["cost_usd"], 0.01);
+    assert_eq!(totals["unpriced_requests"], 1);
+    assert_eq!(totals["latency_ms"]["total"], 1200);
+    assert_eq!(totals["latency_ms"]["mean"], 600.0);
+    assert_eq!(totals["providers"]["claude-custom"]["tokens"], 350);
+    assert_eq!(totals["providers"]["openai-custom"]["share"], 0.5);
+    assert!(totals["providers"].get("gemini-cli-oauth").is_none());
+    assert!(totals["first_at"].is_string());
+}
+
+#[tokio::test]
+async fn test_totals_are_per_client_and_scope() {
+    let rollup = rollup();
+    let mut tags = BTreeMap::new();
+    tags.insert("team".to_string(), "search".to_string());
+    let scopes = Scope::of(Some("session-1"), &tags);
+    assert_eq!(scopes, vec![Scope::Conversation("session-1".to_string()), Scope::Tag("team=search".to_string())]);
+    rollup.record("key:a", &scopes, &turn("openai-custom", 10, 5, 100, None)).await.unwrap();
+
+    let tag = Scope::Tag("team=search".to_string());
+    let totals = rollup.rollup("key:a", &tag, &[]).await.unwrap().unwrap();
+    assert_eq!(totals["tag"], "team=search");
+    assert!(totals["cost_usd"].is_null());
+    assert!(rollup.rollup("key:b", &tag, &[]).await.unwrap().is_none());
+    assert!(rollup.rollup("key:a", &Scope::Tag("team=ads".to_string()), &[]).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_purge_client_deletes_only_that_clients_totals() {
+    let rollup = rollup();
+    let mut tags = BTreeMap::new();
+    tags.insert("team".to_string(), "search".to_string());
+    let scopes = Scope::of(Some("session-1"), &tags);
+    rollup.record("key:a", &scopes, &turn("openai-custom", 10, 5, 100, None)).await.unwrap();
+    rollup.record("key:a", &scopes[..1], &turn("claude-custom", 10, 5, 100, None)).await.unwrap();
+    rollup.record("key:b", &scopes, &turn("openai-custom", 10, 5, 100, None)).await.unwrap();
+
+    assert_eq!(rollup.purge_client("key:a").await.unwrap(), 2);
+    let providers = vec!["openai-custom".to_string(), "claude-custom".to_string()];
+    for scope in &scopes {
+        assert!(rollup.rollup("key:a", scope, &providers).await.unwrap().is_none());
+        assert_eq!(rollup.rollup("key:b", scope, &providers).await.unwrap().unwrap()["requests"], 1);
+    }
+    assert_eq!(rollup.purge_client("key:a").await.unwrap(), 0);
+
+    // A purged client starts over from zero
+    rollup.record("key:a", &scopes[..1], &turn("claude-custom", 1, 1, 10, None)).await.unwrap();
+    let totals = rollup.rollup("key:a", &scopes[0], &providers).await.unwrap().unwrap();
+    assert_eq!(totals["requests"], 1);
+    assert!(totals["providers"].get("openai-custom").is_none());
+}
+
+#[test]
+fn test_cost_is_priced_per_million_tokens() {
+    assert_eq!(cost_usd(1_000_000, 500_000, Some(3.0), Some(15.0)), Some(10.5));
+    assert_eq!(cost_usd(1_000_000, 0, None, Some(15.0)), Some(0.0));
+    assert_eq!(cost_usd(1_000, 1_000, None, None), None);
+}
@@ -0,0 +1,141 @@
+/*!
+ * Web Search Tests
+ *
+ * Unit tests for bridging built-in web search tools to a search backend.
+ */
+
+use aiclient2api_rust::config::WebSearchConfig;
+use aiclient2api_rust::web_search::*;
+use httpmock::prelude::*;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+
+fn web_search(url: String) -> WebSearch {
+    let config = WebSearchConfig {
+        url,
+        api_key: Some("search-key".to_string()),
+        max_results: 2,
+        max_iterations: 2,
+    };
+    WebSearch::new(&config, reqwest::Client::new())
+}
+
+fn search_call(query: &str) -> Value {
+    json!({
+        "choices": [{"index": 0, "finish_reason": "tool_calls", "message": {
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{"id": "call_1", "type": "function", "function": {
+                "name": "web_search",
+                "arguments": json!({"query": query}).to_string()
+            }}]
+        }}]
+    })
+}
+
+fn answer(text: &str) -> Value {
+    json!({"choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": text}}]})
+}
+
+#[test]
+fn test_builtin_search_tools_are_detected_and_replaced() {
+    let openai = json!({"messages": [], "tools": [
+        {"type": "web_search_preview"},
+        {"type": "function", "function": {"name": "lookup"}}
+    ]});
+    let gemini_style = json!({"messages": [], "tools": [{"googleSearch": {}}]});
+    let options = json!({"messages": [], "web_search_options": {"search_context_size": "low"}});
+    assert!(requested(&openai) && requested(&gemini_style) && requested(&options));
+    assert!(!requested(&json!({"messages": [], "tools": [{"type": "function", "function": {"name": "lookup"}}]})));
+
+    let mut body = openai;
+    bridge_request(&mut body);
+    let names: Vec<&str> = body["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["function"]["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["lookup", "web_search"]);
+
+    let mut body = options;
+    bridge_request(&mut body);
+    assert!(body.get("web_search_options").is_none());
+    assert!(!requested(&body));
+}
+
+#[tokio::test]
+async fn test_search_results_reach_the_model_and_become_citations() {
+    let server = MockServer::start_async().await;
+    let backend = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/search")
+                .header("authorization", "Bearer search-key")
+                .json_body(json!({"query": "rust 1.80 release date", "max_results": 2}));
+            then.status(200).json_body(json!({"results": [
+                {"title": "Announcing Rust 1.80", "url": "https://blog.rust-lang.org/1.80", "content": "Released July 25."},
+                {"title": "Rust releases", "url": "https://releases.rs", "content": "Release calendar."},
+                {"title": "Ignored", "url": "https://example.com", "content": "Past max_results."}
+            ]}));
+        })
+        .await;
+    let search = web_search(server.url("/search"));
+
+    let requests = Mutex::new(Vec::new());
+    let response = search
+        .run(json!({"messages": [{"role": "user", "content": "When did Rust 1.80 ship?"}], "tools": [{"type": "web_search"}]}), |body| {
+            let round = {
+                let mut requests = requests.lock().unwrap();
+                requests.push(body);
+                requests.len()
+            };
+            async move { Ok(if round == 1 { search_call("rust 1.80 release date") } else { answer("July 25 [1].") }) }
+        })
+        .await
+        .unwrap();
+
+    backend.assert_async().await;
+    let message = &response["choices"][0]["message"];
+    assert_eq!(message["content"], "July 25 [1].");
+    let annotations = message["annotations"].as_array().unwrap();
+    assert_eq!(annotations.len(), 2);
+    assert_eq!(
+        annotations[0],
+        json!({"type": "url_citation", "url_citation": {"url": "https://blog.rust-lang.org/1.80", "title": "Announcing Rust 1.80"}})
+    );
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[0]["tools"][0]["function"]["name"], "web_search");
+    let result = requests[1]["messages"][2]["content"].as_str().unwrap();
+    assert!(result.starts_with("[1] Announcing Rust 1.80\nURL: https://blog.rust-lang.org/1.80"));
+    assert!(!result.contains("Ignored"));
+}
+
+#[tokio::test]
+async fn test_backend_failure_is_reported_to_the_model() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(POST).path("/search");
+            then.status(503).body("overloaded");
+        })
+        .await;
+    let search = web_search(server.url("/search"));
+
+    let requests = Mutex::new(Vec::new());
+    let response = search
+        .run(json!({"messages": [], "web_search_options": {}}), |body| {
+            let forced = body.get("tool_choice") == Some(&json!("none"));
+            requests.lock().unwrap().push(body);
+            async move { Ok(if forced { answer("I could not search.") } else { search_call("news") }) }
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response["choices"][0]["message"]["content"], "I could not search.");
+    assert!(response["choices"][0]["message"].get("annotations").is_none());
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[1]["messages"][1]["content"].as_str().unwrap().contains("503"));
+}